@@ -0,0 +1,48 @@
+//! Times decompression of delta-encoded numbers at orders 1 through 4,
+//! where reconstructing numbers from their deltas is a large share of
+//! decode time.
+//!
+//! Run with `cargo run --release --example delta_decode`.
+
+use std::time::{Duration, Instant};
+
+use q_compress::{Compressor, CompressorConfig};
+use q_compress::data_types::NumberLike;
+
+const N: usize = 1 << 20;
+const N_ITERS: usize = 20;
+
+fn bench<T: NumberLike>(name: &str, nums: &[T]) {
+  for order in 1..5 {
+    let config = CompressorConfig::default().with_delta_encoding_order(order);
+    let bytes = Compressor::<T>::from_config(config).simple_compress(nums);
+    // the fastest iteration is the least affected by noise
+    let mut best = Duration::MAX;
+    for _ in 0..N_ITERS {
+      let start = Instant::now();
+      let decompressed = q_compress::auto_decompress::<T>(&bytes).expect("decompress");
+      best = best.min(start.elapsed());
+      assert_eq!(decompressed.len(), nums.len());
+    }
+    println!(
+      "{} order {}: {:.2} ns/num",
+      name,
+      order,
+      best.as_nanos() as f64 / nums.len() as f64,
+    );
+  }
+}
+
+fn main() {
+  // a random walk, which delta encoding suits
+  let mut x = 0_i64;
+  let walk = (0..N as i64)
+    .map(|i| {
+      x += (i * 7919) % 201 - 100;
+      x
+    })
+    .collect::<Vec<_>>();
+  bench("i64", &walk);
+  bench("u64", &walk.iter().map(|&x| (x + (1 << 40)) as u64).collect::<Vec<_>>());
+  bench("u32", &walk.iter().map(|&x| (x + (1 << 30)) as u32).collect::<Vec<_>>());
+}
@@ -0,0 +1,179 @@
+use alloc::format;
+use alloc::vec::Vec;
+
+use crate::bit_reader::BitReader;
+use crate::bit_writer::BitWriter;
+use crate::constants::BITS_TO_ENCODE_AFFINE_SCALE_WIDTH;
+use crate::data_types::{NumberLike, UnsignedLike};
+use crate::errors::{QCompressError, QCompressResult};
+use crate::gcd_utils;
+
+/// How a chunk's numbers were written as multiples of a common spacing, as
+/// stored in
+/// [`ChunkMetadata::affine_transform`][crate::ChunkMetadata::affine_transform].
+///
+/// Every number's signed representation is `scale * k` more than
+/// `offset`'s for some integer `k >= 0`, and the chunk body holds the `k`s
+/// instead.
+/// For instance, sensor readings that are all 5 more than a multiple of 37
+/// are compressed as those multiples.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(
+  serialize = "T: serde::Serialize, T::Unsigned: serde::Serialize",
+  deserialize = "T: serde::Deserialize<'de>, T::Unsigned: serde::Deserialize<'de>",
+)))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AffineTransform<T: NumberLike> {
+  /// The spacing between the chunk's numbers' signed representations,
+  /// which is at least 2.
+  pub scale: T::Unsigned,
+  /// The chunk's number with the smallest signed representation.
+  pub offset: T,
+}
+
+// a number's signed representation as an unsigned integer, so that
+// differences between them can't overflow
+fn signed_coord<T: NumberLike>(x: T) -> T::Unsigned {
+  x.to_signed().to_unsigned()
+}
+
+fn from_signed_coord<T: NumberLike>(u: T::Unsigned) -> T {
+  T::from_signed(T::Signed::from_unsigned(u))
+}
+
+// the number of bits the scale takes, which we write first so that small
+// scales stay cheap
+fn bit_width<U: UnsignedLike>(scale: U) -> usize {
+  let mut res = 0;
+  while res < U::BITS && scale >> res > U::ZERO {
+    res += 1;
+  }
+  res
+}
+
+impl<T: NumberLike> AffineTransform<T> {
+  pub(crate) fn parse_from(reader: &mut BitReader) -> QCompressResult<Self> {
+    let width = reader.read_usize(BITS_TO_ENCODE_AFFINE_SCALE_WIDTH)?;
+    if width > T::Unsigned::BITS {
+      return Err(QCompressError::corruption(format!(
+        "affine transform scale width {} exceeds {} bits",
+        width,
+        T::Unsigned::BITS,
+      )));
+    }
+    let scale = reader.read_diff::<T::Unsigned>(width)?;
+    if scale <= T::Unsigned::ONE {
+      return Err(QCompressError::corruption(format!(
+        "invalid affine transform scale {}",
+        scale,
+      )));
+    }
+    let offset = T::read_from(reader)?;
+    Ok(Self { scale, offset })
+  }
+
+  pub(crate) fn write_to(&self, writer: &mut BitWriter) {
+    let width = bit_width(self.scale);
+    writer.write_usize(width, BITS_TO_ENCODE_AFFINE_SCALE_WIDTH);
+    writer.write_diff(self.scale, width);
+    self.offset.write_to(writer);
+  }
+
+  // numbers of the same type whose signed representations are the
+  // multiples k, so the rest of compression can treat them like any other
+  // chunk
+  pub(crate) fn multiples(&self, nums: &[T]) -> Vec<T> {
+    let offset = signed_coord(self.offset);
+    nums.iter()
+      .map(|&x| from_signed_coord((signed_coord(x) - offset) / self.scale))
+      .collect()
+  }
+
+  // inverts `multiples` in place; corrupt multiples wrap around instead of
+  // overflowing
+  pub(crate) fn apply(&self, nums: &mut [T]) {
+    let offset = signed_coord(self.offset);
+    for num in nums {
+      *num = from_signed_coord(signed_coord(*num).wrapping_mul(self.scale).wrapping_add(offset));
+    }
+  }
+}
+
+// the transform with the largest scale that fits every number, if that
+// scale is at least 2
+pub(crate) fn choose_affine_transform<T: NumberLike>(nums: &[T]) -> Option<AffineTransform<T>> {
+  let offset = nums.iter().copied().min_by_key(|&x| signed_coord(x))?;
+  let lower = signed_coord(offset);
+  let mut scale = T::Unsigned::ZERO;
+  for &x in nums {
+    let diff = signed_coord(x) - lower;
+    if diff == T::Unsigned::ZERO {
+      continue;
+    }
+    scale = if scale == T::Unsigned::ZERO {
+      diff
+    } else {
+      gcd_utils::pair_gcd(diff, scale)
+    };
+    if scale == T::Unsigned::ONE {
+      return None;
+    }
+  }
+  if scale > T::Unsigned::ONE {
+    Some(AffineTransform { scale, offset })
+  } else {
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::bit_words::BitWords;
+  use super::*;
+
+  #[test]
+  fn test_choose_affine_transform() {
+    let nums = [42_i64, 5, -32, 116, 5];
+    let transform = choose_affine_transform(&nums).unwrap();
+    assert_eq!(transform, AffineTransform { scale: 37, offset: -32 });
+    let multiples = transform.multiples(&nums);
+    assert_eq!(
+      multiples.iter().map(|x| signed_coord(*x)).collect::<Vec<_>>(),
+      vec![2, 1, 0, 4, 1],
+    );
+    let mut restored = multiples;
+    transform.apply(&mut restored);
+    assert_eq!(restored, nums);
+
+    assert_eq!(choose_affine_transform(&[3_u32, 5, 8]), None);
+    assert_eq!(choose_affine_transform(&[7_u32, 7]), None);
+    assert_eq!(choose_affine_transform::<u32>(&[]), None);
+    assert_eq!(
+      choose_affine_transform(&[u64::MAX, 1]),
+      Some(AffineTransform { scale: u64::MAX - 1, offset: 1 }),
+    );
+    assert_eq!(
+      choose_affine_transform(&[i32::MIN, i32::MAX]),
+      Some(AffineTransform { scale: u32::MAX, offset: i32::MIN }),
+    );
+  }
+
+  #[test]
+  fn test_write_parse() {
+    let transform = AffineTransform { scale: 1000_u64, offset: -7_i64 };
+    let mut writer = BitWriter::default();
+    transform.write_to(&mut writer);
+    let bytes = writer.drain_bytes();
+    let words = BitWords::from(&bytes);
+    let mut reader = BitReader::from(&words);
+    assert_eq!(AffineTransform::<i64>::parse_from(&mut reader).unwrap(), transform);
+
+    let mut writer = BitWriter::default();
+    writer.write_usize(1, BITS_TO_ENCODE_AFFINE_SCALE_WIDTH);
+    writer.write_diff(1_u64, 1);
+    let bytes = writer.drain_bytes();
+    let words = BitWords::from(&bytes);
+    let mut reader = BitReader::from(&words);
+    assert!(AffineTransform::<i64>::parse_from(&mut reader).is_err());
+  }
+}
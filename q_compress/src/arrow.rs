@@ -0,0 +1,326 @@
+//! Conversions between Apache Arrow arrays and .qco files.
+//!
+//! [`compress_array`] writes a standard .qco file whose header
+//! [`UserMetadata`] records the Arrow data type and, if the array has any
+//! nulls, its validity bitmap.
+//! Only the non-null values are compressed.
+//! [`decompress_to_array`] reads such a file back into an equal array.
+//!
+//! Supported data types are `Int8` through `Int64`, `UInt8` through `UInt64`,
+//! `Float32`, `Float64`, and `Timestamp` with any unit and time zone.
+//! 8- and 16-bit integers are compressed as 32-bit integers, microsecond and
+//! nanosecond timestamps as [`TimestampMicros`] and [`TimestampNanos`], and
+//! second and millisecond timestamps as `i64`.
+//! ```
+//! use std::sync::Arc;
+//! use arrow_array::{ArrayRef, Int16Array};
+//! use q_compress::CompressorConfig;
+//! use q_compress::arrow::{compress_array, decompress_to_array};
+//!
+//! let array: ArrayRef = Arc::new(Int16Array::from(vec![Some(3), None, Some(-7)]));
+//! let bytes = compress_array(array.as_ref(), CompressorConfig::default()).expect("compress");
+//! let recovered = decompress_to_array(&bytes).expect("decompress");
+//! assert_eq!(recovered.as_ref(), array.as_ref());
+//! ```
+
+use alloc::format;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+use arrow_array::{Array, ArrayRef, ArrowPrimitiveType, PrimitiveArray};
+use arrow_array::cast::AsArray;
+use arrow_array::types::*;
+use arrow_buffer::NullBuffer;
+use arrow_schema::{DataType, TimeUnit};
+
+use crate::{bits, Compressor, CompressorConfig, Decompressor, UserMetadata};
+use crate::constants::MAGIC_HEADER;
+use crate::data_types::{NumberLike, TimestampMicros, TimestampNanos};
+use crate::errors::{QCompressError, QCompressResult};
+
+/// The header metadata key for the array's Arrow data type, stored as the
+/// UTF-8 bytes of its `Display` representation.
+pub const DATA_TYPE_KEY: &str = "arrow.data_type";
+/// The header metadata key for the array's validity bitmap, present only if
+/// the array has nulls.
+/// It is stored as the array length in 8 little-endian bytes, followed by
+/// 1 bit per element (1 for valid), least significant bit first.
+pub const VALIDITY_KEY: &str = "arrow.validity";
+
+const VALIDITY_LEN_BYTES: usize = 8;
+
+fn validity_bytes(nulls: &NullBuffer) -> Vec<u8> {
+  let mut res = (nulls.len() as u64).to_le_bytes().to_vec();
+  res.resize(VALIDITY_LEN_BYTES + bits::ceil_div(nulls.len(), 8), 0);
+  for (i, is_valid) in nulls.iter().enumerate() {
+    if is_valid {
+      res[VALIDITY_LEN_BYTES + i / 8] |= 1 << (i % 8);
+    }
+  }
+  res
+}
+
+fn parse_validity(bytes: &[u8]) -> QCompressResult<NullBuffer> {
+  let corruption = || QCompressError::corruption("invalid arrow validity bitmap");
+  if bytes.len() < VALIDITY_LEN_BYTES {
+    return Err(corruption());
+  }
+  let mut len_bytes = [0; VALIDITY_LEN_BYTES];
+  len_bytes.copy_from_slice(&bytes[..VALIDITY_LEN_BYTES]);
+  let len = u64::from_le_bytes(len_bytes) as usize;
+  let bitmap = &bytes[VALIDITY_LEN_BYTES..];
+  if bitmap.len() != bits::ceil_div(len, 8) {
+    return Err(corruption());
+  }
+  Ok((0..len).map(|i| bitmap[i / 8] & (1 << (i % 8)) > 0).collect())
+}
+
+fn compress_primitive<A: ArrowPrimitiveType, T: NumberLike, F: Fn(A::Native) -> T>(
+  array: &dyn Array,
+  config: CompressorConfig,
+  metadata: UserMetadata,
+  convert: F,
+) -> QCompressResult<Vec<u8>> {
+  let nums = array.as_primitive::<A>()
+    .iter()
+    .flatten()
+    .map(convert)
+    .collect::<Vec<_>>();
+  let mut compressor = Compressor::<T>::try_from_config(config)?;
+  compressor.set_header_metadata(metadata)?;
+  Ok(compressor.simple_compress(&nums))
+}
+
+/// Compresses the Arrow array into a .qco file, enabling
+/// [`use_user_metadata`][CompressorConfig::use_user_metadata] to record its
+/// data type and nulls.
+/// Will return an error if the config is invalid or the data type is
+/// unsupported.
+pub fn compress_array(array: &dyn Array, config: CompressorConfig) -> QCompressResult<Vec<u8>> {
+  let data_type = array.data_type();
+  let mut metadata = UserMetadata::new();
+  metadata.insert(DATA_TYPE_KEY.to_string(), data_type.to_string().into_bytes());
+  if let Some(nulls) = array.nulls().filter(|nulls| nulls.null_count() > 0) {
+    metadata.insert(VALIDITY_KEY.to_string(), validity_bytes(nulls));
+  }
+  let config = config.with_use_user_metadata(true);
+
+  match data_type {
+    DataType::Int8 => compress_primitive::<Int8Type, _, _>(array, config, metadata, i32::from),
+    DataType::Int16 => compress_primitive::<Int16Type, _, _>(array, config, metadata, i32::from),
+    DataType::Int32 => compress_primitive::<Int32Type, _, _>(array, config, metadata, |x| x),
+    DataType::Int64 => compress_primitive::<Int64Type, _, _>(array, config, metadata, |x| x),
+    DataType::UInt8 => compress_primitive::<UInt8Type, _, _>(array, config, metadata, u32::from),
+    DataType::UInt16 => compress_primitive::<UInt16Type, _, _>(array, config, metadata, u32::from),
+    DataType::UInt32 => compress_primitive::<UInt32Type, _, _>(array, config, metadata, |x| x),
+    DataType::UInt64 => compress_primitive::<UInt64Type, _, _>(array, config, metadata, |x| x),
+    DataType::Float32 => compress_primitive::<Float32Type, _, _>(array, config, metadata, |x| x),
+    DataType::Float64 => compress_primitive::<Float64Type, _, _>(array, config, metadata, |x| x),
+    DataType::Timestamp(TimeUnit::Second, _) =>
+      compress_primitive::<TimestampSecondType, _, _>(array, config, metadata, |x| x),
+    DataType::Timestamp(TimeUnit::Millisecond, _) =>
+      compress_primitive::<TimestampMillisecondType, _, _>(array, config, metadata, |x| x),
+    DataType::Timestamp(TimeUnit::Microsecond, _) =>
+      compress_primitive::<TimestampMicrosecondType, _, _>(array, config, metadata, TimestampMicros::new),
+    DataType::Timestamp(TimeUnit::Nanosecond, _) =>
+      compress_primitive::<TimestampNanosecondType, _, _>(array, config, metadata, TimestampNanos::new),
+    other => Err(QCompressError::invalid_argument(format!(
+      "unsupported arrow data type {}",
+      other,
+    ))),
+  }
+}
+
+struct DecompressedArray<T> {
+  nums: Vec<T>,
+  data_type: DataType,
+  nulls: Option<NullBuffer>,
+}
+
+impl<T: NumberLike> DecompressedArray<T> {
+  fn new(bytes: &[u8]) -> QCompressResult<Self> {
+    let mut decompressor = Decompressor::<T>::default();
+    decompressor.extend_bytes(bytes);
+    let nums = decompressor.simple_decompress()?;
+    let metadata = decompressor.header_metadata();
+
+    let data_type_bytes = metadata.get(DATA_TYPE_KEY).ok_or_else(|| QCompressError::invalid_argument(
+      "file has no arrow data type; was it written by compress_array?"
+    ))?;
+    let data_type = core::str::from_utf8(data_type_bytes).ok()
+      .and_then(|s| DataType::from_str(s).ok())
+      .ok_or_else(|| QCompressError::corruption("invalid arrow data type"))?;
+    let nulls = metadata.get(VALIDITY_KEY)
+      .map(|bytes| parse_validity(bytes))
+      .transpose()?;
+    if let Some(nulls) = &nulls {
+      if nulls.len() - nulls.null_count() != nums.len() {
+        return Err(QCompressError::corruption(format!(
+          "arrow validity bitmap has {} valid elements but there are {} numbers",
+          nulls.len() - nulls.null_count(),
+          nums.len(),
+        )));
+      }
+    }
+
+    Ok(DecompressedArray { nums, data_type, nulls })
+  }
+
+  fn into_array<A: ArrowPrimitiveType, F: Fn(T) -> A::Native>(self, convert: F) -> ArrayRef {
+    let values = match &self.nulls {
+      Some(nulls) => {
+        let mut nums = self.nums.into_iter();
+        nulls.iter()
+          .map(|is_valid| if is_valid {
+            convert(nums.next().unwrap())
+          } else {
+            A::Native::default()
+          })
+          .collect::<Vec<_>>()
+      },
+      None => self.nums.into_iter().map(convert).collect::<Vec<_>>(),
+    };
+    let array = PrimitiveArray::<A>::new(values.into(), self.nulls)
+      .with_data_type(self.data_type);
+    Arc::new(array)
+  }
+
+  fn mismatch_error(&self) -> QCompressError {
+    QCompressError::corruption(format!(
+      "arrow data type {} does not match data type with header byte {}",
+      self.data_type,
+      T::HEADER_BYTE,
+    ))
+  }
+}
+
+/// Decompresses a .qco file written by [`compress_array`] into an Arrow
+/// array with the original data type and nulls.
+/// Will return an error if the file lacks the Arrow data type, or if there
+/// are any compatibility, corruption, or insufficient data issues.
+pub fn decompress_to_array(bytes: &[u8]) -> QCompressResult<ArrayRef> {
+  let header_byte = bytes.get(MAGIC_HEADER.len()).copied().ok_or_else(|| QCompressError::insufficient_data(
+    "not enough bytes for the magic header and data type byte"
+  ))?;
+
+  match header_byte {
+    <i32 as NumberLike>::HEADER_BYTE => {
+      let decompressed = DecompressedArray::<i32>::new(bytes)?;
+      match decompressed.data_type {
+        DataType::Int8 => Ok(decompressed.into_array::<Int8Type, _>(|x| x as i8)),
+        DataType::Int16 => Ok(decompressed.into_array::<Int16Type, _>(|x| x as i16)),
+        DataType::Int32 => Ok(decompressed.into_array::<Int32Type, _>(|x| x)),
+        _ => Err(decompressed.mismatch_error()),
+      }
+    },
+    <i64 as NumberLike>::HEADER_BYTE => {
+      let decompressed = DecompressedArray::<i64>::new(bytes)?;
+      match decompressed.data_type {
+        DataType::Int64 => Ok(decompressed.into_array::<Int64Type, _>(|x| x)),
+        DataType::Timestamp(TimeUnit::Second, _) =>
+          Ok(decompressed.into_array::<TimestampSecondType, _>(|x| x)),
+        DataType::Timestamp(TimeUnit::Millisecond, _) =>
+          Ok(decompressed.into_array::<TimestampMillisecondType, _>(|x| x)),
+        _ => Err(decompressed.mismatch_error()),
+      }
+    },
+    <u32 as NumberLike>::HEADER_BYTE => {
+      let decompressed = DecompressedArray::<u32>::new(bytes)?;
+      match decompressed.data_type {
+        DataType::UInt8 => Ok(decompressed.into_array::<UInt8Type, _>(|x| x as u8)),
+        DataType::UInt16 => Ok(decompressed.into_array::<UInt16Type, _>(|x| x as u16)),
+        DataType::UInt32 => Ok(decompressed.into_array::<UInt32Type, _>(|x| x)),
+        _ => Err(decompressed.mismatch_error()),
+      }
+    },
+    <u64 as NumberLike>::HEADER_BYTE => {
+      let decompressed = DecompressedArray::<u64>::new(bytes)?;
+      match decompressed.data_type {
+        DataType::UInt64 => Ok(decompressed.into_array::<UInt64Type, _>(|x| x)),
+        _ => Err(decompressed.mismatch_error()),
+      }
+    },
+    <f32 as NumberLike>::HEADER_BYTE => {
+      let decompressed = DecompressedArray::<f32>::new(bytes)?;
+      match decompressed.data_type {
+        DataType::Float32 => Ok(decompressed.into_array::<Float32Type, _>(|x| x)),
+        _ => Err(decompressed.mismatch_error()),
+      }
+    },
+    <f64 as NumberLike>::HEADER_BYTE => {
+      let decompressed = DecompressedArray::<f64>::new(bytes)?;
+      match decompressed.data_type {
+        DataType::Float64 => Ok(decompressed.into_array::<Float64Type, _>(|x| x)),
+        _ => Err(decompressed.mismatch_error()),
+      }
+    },
+    <TimestampMicros as NumberLike>::HEADER_BYTE => {
+      let decompressed = DecompressedArray::<TimestampMicros>::new(bytes)?;
+      match decompressed.data_type {
+        DataType::Timestamp(TimeUnit::Microsecond, _) =>
+          Ok(decompressed.into_array::<TimestampMicrosecondType, _>(|x| x.to_total_parts())),
+        _ => Err(decompressed.mismatch_error()),
+      }
+    },
+    <TimestampNanos as NumberLike>::HEADER_BYTE => {
+      let decompressed = DecompressedArray::<TimestampNanos>::new(bytes)?;
+      match decompressed.data_type {
+        DataType::Timestamp(TimeUnit::Nanosecond, _) =>
+          Ok(decompressed.into_array::<TimestampNanosecondType, _>(|x| x.to_total_parts())),
+        _ => Err(decompressed.mismatch_error()),
+      }
+    },
+    _ => Err(QCompressError::invalid_argument(format!(
+      "data type byte {} has no arrow equivalent",
+      header_byte,
+    ))),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use alloc::vec;
+  use arrow_array::{Float64Array, TimestampNanosecondArray, UInt8Array};
+
+  fn assert_recovers(array: ArrayRef) {
+    let bytes = compress_array(array.as_ref(), CompressorConfig::default()).unwrap();
+    let recovered = decompress_to_array(&bytes).unwrap();
+    assert_eq!(recovered.as_ref(), array.as_ref());
+  }
+
+  #[test]
+  fn test_recovers() {
+    assert_recovers(Arc::new(UInt8Array::from(vec![0_u8, 255, 17, 17])));
+    assert_recovers(Arc::new(Float64Array::from(vec![Some(1.5), None, None, Some(-0.25), None])));
+    assert_recovers(Arc::new(
+      TimestampNanosecondArray::from(vec![Some(1_000_000_000_i64), None, Some(1_000_000_123)])
+        .with_timezone("+01:00")
+    ));
+    assert_recovers(Arc::new(Float64Array::from(Vec::<f64>::new())));
+    assert_recovers(Arc::new(Float64Array::from(vec![None, None])));
+  }
+
+  #[test]
+  fn test_sliced_nulls() {
+    let array = Float64Array::from((0..20).map(|i| if i % 3 == 0 { None } else { Some(i as f64) }).collect::<Vec<_>>());
+    assert_recovers(Arc::new(array.slice(5, 11)));
+  }
+
+  #[test]
+  fn test_rejects_unannotated_file() {
+    let bytes = Compressor::<i32>::default().simple_compress(&[1, 2, 3]);
+    let err = decompress_to_array(&bytes).unwrap_err();
+    assert_eq!(err.kind, crate::errors::ErrorKind::InvalidArgument);
+  }
+
+  #[test]
+  fn test_rejects_invalid_config() {
+    let array = UInt8Array::from(vec![1_u8, 2, 3]);
+    let config = CompressorConfig::default().with_delta_encoding_order(8);
+    let err = compress_array(&array, config).unwrap_err();
+    assert_eq!(err.kind, crate::errors::ErrorKind::InvalidArgument);
+  }
+}
@@ -1,12 +1,257 @@
-use std::cmp::min;
-use std::io::Write;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::min;
+use core::marker::PhantomData;
 
-use crate::{Compressor, CompressorConfig, Decompressor};
+use crate::{bits, Compressor, CompressorConfig, Decompressor};
+use crate::compressor::DEFAULT_CHUNK_SIZE;
+use crate::distinct_count::mix;
+use crate::constants::MAX_DELTA_ENCODING_ORDER;
 use crate::data_types::NumberLike;
-use crate::errors::QCompressResult;
+use crate::errors::{QCompressError, QCompressResult};
 
 const AUTO_DELTA_LIMIT: usize = 1000;
 const MAX_AUTO_DELTA_COMPRESSION_LEVEL: usize = 6;
+// stratified and random samples are split into this many contiguous blocks
+const AUTO_SAMPLE_BLOCKS: usize = 10;
+
+/// Which numbers [`auto_compressor_config_with_options`] inspects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SamplingStrategy {
+  /// The first numbers.
+  /// This is cheapest, but misleading when the data's head is
+  /// unrepresentative, e.g. a warm-up period of a sensor.
+  Head,
+  /// Evenly spaced contiguous blocks spanning the whole data.
+  Stratified,
+  /// Contiguous blocks at pseudorandom positions chosen by the seed.
+  Random {
+    seed: u64,
+  },
+}
+
+/// Which settings [`auto_compressor_config_with_options`] searches over.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AutoSearch {
+  /// Only the delta encoding order, stopping at the first order that
+  /// compresses worse than the previous one.
+  DeltaOrder,
+  /// Every delta encoding order, each with and without `use_gcds`.
+  Full,
+}
+
+/// The sampling and search options for
+/// [`auto_compressor_config_with_options`].
+#[derive(Clone, Debug)]
+pub struct AutoOptions {
+  /// About how many numbers to inspect (default 1000).
+  /// Values below 1 are treated as 1.
+  pub sample_size: usize,
+  /// How to choose the numbers to inspect (default
+  /// [`SamplingStrategy::Head`]).
+  pub sampling_strategy: SamplingStrategy,
+  /// Which settings to search over (default [`AutoSearch::DeltaOrder`]).
+  pub search: AutoSearch,
+  phantom: PhantomData<()>, // for API stability
+}
+
+impl Default for AutoOptions {
+  fn default() -> Self {
+    Self {
+      sample_size: AUTO_DELTA_LIMIT,
+      sampling_strategy: SamplingStrategy::Head,
+      search: AutoSearch::DeltaOrder,
+      phantom: PhantomData,
+    }
+  }
+}
+
+impl AutoOptions {
+  /// Sets [`sample_size`][AutoOptions::sample_size].
+  pub fn with_sample_size(mut self, sample_size: usize) -> Self {
+    self.sample_size = sample_size;
+    self
+  }
+
+  /// Sets [`sampling_strategy`][AutoOptions::sampling_strategy].
+  pub fn with_sampling_strategy(mut self, strategy: SamplingStrategy) -> Self {
+    self.sampling_strategy = strategy;
+    self
+  }
+
+  /// Sets [`search`][AutoOptions::search].
+  pub fn with_search(mut self, search: AutoSearch) -> Self {
+    self.search = search;
+    self
+  }
+}
+
+/// The search space and goals for [`train_config`].
+#[derive(Clone, Debug)]
+pub struct TrainingObjectives {
+  /// The compression levels to try (default `[0, 4, 8]`).
+  pub compression_levels: Vec<usize>,
+  /// The delta encoding orders to try (default 0 through 7).
+  pub delta_encoding_orders: Vec<usize>,
+  /// Whether to try both with and without `use_gcds` (default true).
+  /// If false, only `use_gcds = true` is tried.
+  pub try_without_gcds: bool,
+  /// If set, candidates that take longer than this many seconds per
+  /// million numbers to compress the sample are only chosen if no candidate
+  /// is fast enough (default `None`).
+  /// Speed is only measured with the `std` feature.
+  pub max_seconds_per_million: Option<f64>,
+  phantom: PhantomData<()>, // for API stability
+}
+
+impl Default for TrainingObjectives {
+  fn default() -> Self {
+    Self {
+      compression_levels: vec![0, 4, 8],
+      delta_encoding_orders: (0..MAX_DELTA_ENCODING_ORDER + 1).collect(),
+      try_without_gcds: true,
+      max_seconds_per_million: None,
+      phantom: PhantomData,
+    }
+  }
+}
+
+impl TrainingObjectives {
+  /// Sets [`compression_levels`][TrainingObjectives::compression_levels].
+  pub fn with_compression_levels(mut self, levels: Vec<usize>) -> Self {
+    self.compression_levels = levels;
+    self
+  }
+
+  /// Sets [`delta_encoding_orders`][TrainingObjectives::delta_encoding_orders].
+  pub fn with_delta_encoding_orders(mut self, orders: Vec<usize>) -> Self {
+    self.delta_encoding_orders = orders;
+    self
+  }
+
+  /// Sets [`try_without_gcds`][TrainingObjectives::try_without_gcds].
+  pub fn with_try_without_gcds(mut self, try_without_gcds: bool) -> Self {
+    self.try_without_gcds = try_without_gcds;
+    self
+  }
+
+  /// Sets [`max_seconds_per_million`][TrainingObjectives::max_seconds_per_million].
+  pub fn with_max_seconds_per_million(mut self, seconds: Option<f64>) -> Self {
+    self.max_seconds_per_million = seconds;
+    self
+  }
+}
+
+/// How one candidate configuration performed in [`train_config`].
+#[derive(Clone, Debug)]
+pub struct CandidateReport {
+  pub config: CompressorConfig,
+  /// The byte size of the compressed sample.
+  pub compressed_size: usize,
+  /// The uncompressed byte size of the sample divided by its compressed size.
+  pub compression_ratio: f64,
+  /// Seconds taken to compress the sample, if measured.
+  pub compress_seconds: Option<f64>,
+}
+
+/// The result of [`train_config`].
+#[derive(Clone, Debug)]
+pub struct TrainedConfig {
+  /// The best configuration found.
+  pub config: CompressorConfig,
+  /// A report for every candidate configuration tried.
+  pub candidates: Vec<CandidateReport>,
+}
+
+#[cfg(feature = "std")]
+fn timed<R, F: FnOnce() -> R>(f: F) -> (R, Option<f64>) {
+  let start = std::time::Instant::now();
+  let res = f();
+  (res, Some(start.elapsed().as_secs_f64()))
+}
+
+#[cfg(not(feature = "std"))]
+fn timed<R, F: FnOnce() -> R>(f: F) -> (R, Option<f64>) {
+  (f(), None)
+}
+
+/// Chooses the best compression configuration for a representative
+/// `sample` of your data by compressing it with every combination of
+/// compression level, delta encoding order, and GCD setting in
+/// `objectives`.
+///
+/// Unlike [`auto_compressor_config`], which only inspects the first numbers
+/// and never tries turning off GCDs, this compresses the whole sample for
+/// each candidate, so it costs roughly as much as compressing the sample
+/// a few dozen times.
+/// The best configuration is the one with the smallest compressed size,
+/// among those meeting `max_seconds_per_million` if any do.
+/// Will return an error if `sample` is empty or `objectives` contains no
+/// valid candidates.
+pub fn train_config<T: NumberLike>(
+  sample: &[T],
+  objectives: &TrainingObjectives,
+) -> QCompressResult<TrainedConfig> {
+  if sample.is_empty() {
+    return Err(QCompressError::invalid_argument(
+      "cannot train config on empty sample"
+    ));
+  }
+
+  let uncompressed_size = sample.len() * T::PHYSICAL_BITS / 8;
+  let gcd_options: &[bool] = if objectives.try_without_gcds {
+    &[true, false]
+  } else {
+    &[true]
+  };
+  let mut candidates = Vec::new();
+  for &compression_level in &objectives.compression_levels {
+    for &delta_encoding_order in &objectives.delta_encoding_orders {
+      for &use_gcds in gcd_options {
+        let config = CompressorConfig::default()
+          .with_compression_level(compression_level)
+          .with_delta_encoding_order(delta_encoding_order)
+          .with_use_gcds(use_gcds);
+        let mut compressor = Compressor::<T>::try_from_config(config.clone())?;
+        let (res, compress_seconds) = timed(|| {
+          compressor.header()?;
+          for chunk in sample.chunks(DEFAULT_CHUNK_SIZE) {
+            compressor.chunk(chunk)?;
+          }
+          compressor.footer()
+        });
+        res?;
+        let compressed_size = compressor.byte_size();
+        candidates.push(CandidateReport {
+          config,
+          compressed_size,
+          compression_ratio: uncompressed_size as f64 / compressed_size as f64,
+          compress_seconds,
+        });
+      }
+    }
+  }
+
+  let is_fast_enough = |candidate: &CandidateReport| {
+    match (objectives.max_seconds_per_million, candidate.compress_seconds) {
+      (Some(max_seconds), Some(seconds)) =>
+        seconds * 1.0E6 / sample.len() as f64 <= max_seconds,
+      _ => true,
+    }
+  };
+  let best = candidates.iter()
+    .filter(|&candidate| is_fast_enough(candidate))
+    .min_by_key(|candidate| candidate.compressed_size)
+    .or_else(|| candidates.iter().min_by_key(|candidate| candidate.compressed_size))
+    .ok_or_else(|| QCompressError::invalid_argument(
+      "no candidate configurations to train on"
+    ))?;
+
+  Ok(TrainedConfig {
+    config: best.config.clone(),
+    candidates,
+  })
+}
 
 /// Automatically makes an educated guess for the best compression
 /// configuration, based on `nums` and `compression_level`,
@@ -23,6 +268,17 @@ pub fn auto_compress<T: NumberLike>(nums: &[T], compression_level: usize) -> Vec
   compressor.simple_compress(nums)
 }
 
+/// Like [`auto_compress`], but chooses the configuration with
+/// [`auto_compressor_config_with_options`].
+pub fn auto_compress_with_options<T: NumberLike>(
+  nums: &[T],
+  compression_level: usize,
+  options: &AutoOptions,
+) -> Vec<u8> {
+  let config = auto_compressor_config_with_options(nums, compression_level, options);
+  Compressor::from_config(config).simple_compress(nums)
+}
+
 /// Automatically makes an educated guess for the best decompression
 /// configuration, then decompresses the bytes into numbers.
 ///
@@ -30,7 +286,7 @@ pub fn auto_compress<T: NumberLike>(nums: &[T], compression_level: usize) -> Vec
 /// so there is no compute downside to using this function.
 pub fn auto_decompress<T: NumberLike>(bytes: &[u8]) -> QCompressResult<Vec<T>> {
   let mut decompressor = Decompressor::<T>::default();
-  decompressor.write_all(bytes).unwrap();
+  decompressor.extend_bytes(bytes);
   decompressor.simple_decompress()
 }
 
@@ -39,51 +295,148 @@ pub fn auto_decompress<T: NumberLike>(bytes: &[u8]) -> QCompressResult<Vec<T>> {
 ///
 /// This has some compute cost by trying different configurations on a subset
 /// of the numbers to determine the most likely one to do well.
+/// If `nums` is empty, there is nothing to guess from, so delta encoding is
+/// left off.
 /// See [`CompressorConfig`] for information about compression levels.
 pub fn auto_compressor_config<T: NumberLike>(nums: &[T], compression_level: usize) -> CompressorConfig {
-  let delta_encoding_order = auto_delta_encoding_order(nums, compression_level);
-  CompressorConfig::default()
-    .with_compression_level(compression_level)
-    .with_delta_encoding_order(delta_encoding_order)
+  auto_compressor_config_with_options(nums, compression_level, &AutoOptions::default())
 }
 
-fn auto_delta_encoding_order<T: NumberLike>(
+/// Like [`auto_compressor_config`], but with control over which numbers
+/// are inspected and which settings are searched.
+///
+/// Stratified and random samples are made of several contiguous blocks, so
+/// that delta encoding still sees consecutive numbers, and each block is
+/// compressed as its own chunk.
+/// With the default options, this is the same as
+/// [`auto_compressor_config`].
+/// ```
+/// use q_compress::{auto_compressor_config_with_options, AutoOptions, SamplingStrategy};
+///
+/// // a constant warm-up period, then a linear trend
+/// let nums = (0..100000_i64).map(|i| i.max(5000) * 1000).collect::<Vec<_>>();
+/// let options = AutoOptions::default()
+///   .with_sampling_strategy(SamplingStrategy::Stratified);
+/// let config = auto_compressor_config_with_options(&nums, 8, &options);
+/// assert_eq!(config.delta_encoding_order, 1);
+/// ```
+pub fn auto_compressor_config_with_options<T: NumberLike>(
   nums: &[T],
   compression_level: usize,
-) -> usize {
-  let head_nums = if nums.len() < AUTO_DELTA_LIMIT {
-    nums
-  } else {
-    &nums[0..AUTO_DELTA_LIMIT]
+  options: &AutoOptions,
+) -> CompressorConfig {
+  let blocks = sample_blocks(nums, options.sample_size.max(1), options.sampling_strategy);
+  let search_config = CompressorConfig::default()
+    .with_compression_level(min(compression_level, MAX_AUTO_DELTA_COMPRESSION_LEVEL));
+  let best = match options.search {
+    AutoSearch::DeltaOrder => best_config(&blocks, search_config.with_use_gcds(false), &[false], true),
+    AutoSearch::Full => best_config(&blocks, search_config, &[true, false], false),
+  };
+  let config = CompressorConfig::default()
+    .with_compression_level(compression_level)
+    .with_delta_encoding_order(best.delta_encoding_order);
+  match options.search {
+    AutoSearch::DeltaOrder => config,
+    AutoSearch::Full => config.with_use_gcds(best.use_gcds),
+  }
+}
+
+fn sample_blocks<T: NumberLike>(
+  nums: &[T],
+  sample_size: usize,
+  strategy: SamplingStrategy,
+) -> Vec<&[T]> {
+  if nums.len() <= sample_size {
+    return vec![nums];
+  }
+  let block_size = bits::ceil_div(sample_size, AUTO_SAMPLE_BLOCKS);
+  let max_start = nums.len() - block_size;
+  let starts = match strategy {
+    SamplingStrategy::Head => return vec![&nums[..sample_size]],
+    SamplingStrategy::Stratified => (0..AUTO_SAMPLE_BLOCKS)
+      .map(|i| i * max_start / (AUTO_SAMPLE_BLOCKS - 1))
+      .collect::<Vec<_>>(),
+    SamplingStrategy::Random { seed } => (0..AUTO_SAMPLE_BLOCKS as u64)
+      .map(|i| (mix(seed ^ mix(i)) % (max_start as u64 + 1)) as usize)
+      .collect::<Vec<_>>(),
   };
-  let mut best_order = usize::MAX;
+  starts.into_iter()
+    .map(|start| &nums[start..start + block_size])
+    .collect()
+}
+
+// Compresses the blocks as separate chunks with each candidate config,
+// returning the config with the smallest size.
+// Empty data can't be compressed as a chunk, so it gets the base config.
+fn best_config<T: NumberLike>(
+  blocks: &[&[T]],
+  base_config: CompressorConfig,
+  gcd_options: &[bool],
+  stop_early: bool,
+) -> CompressorConfig {
+  if blocks.iter().all(|block| block.is_empty()) {
+    return base_config;
+  }
+
+  let mut best = base_config.clone();
   let mut best_size = usize::MAX;
-  for delta_encoding_order in 0..8 {
-    // Taking deltas of a large dataset won't change the GCD,
-    // so we don't need to waste compute here inferring GCD's just to
-    // determine the best delta order.
-    let config = CompressorConfig::default()
-      .with_delta_encoding_order(delta_encoding_order)
-      .with_compression_level(min(compression_level, MAX_AUTO_DELTA_COMPRESSION_LEVEL))
-      .with_use_gcds(false);
-    let mut compressor = Compressor::<T>::from_config(config);
-    compressor.header().unwrap();
-    compressor.chunk(head_nums).unwrap(); // only unreachable errors
-    let size = compressor.byte_size();
-    if size < best_size {
-      best_order = delta_encoding_order;
-      best_size = size;
-    } else {
-      // it's almost always monotonic
+  for delta_encoding_order in 0..MAX_DELTA_ENCODING_ORDER + 1 {
+    let mut order_improved = false;
+    for &use_gcds in gcd_options {
+      let config = base_config.clone()
+        .with_delta_encoding_order(delta_encoding_order)
+        .with_use_gcds(use_gcds);
+      let mut compressor = Compressor::<T>::from_config(config.clone());
+      compressor.header().unwrap();
+      // stratified and random blocks are never empty, and a head block is
+      // only empty if all the data is
+      for block in blocks {
+        compressor.chunk(block).unwrap();
+      }
+      let size = compressor.byte_size();
+      if size < best_size {
+        best = config;
+        best_size = size;
+        order_improved = true;
+      }
+    }
+    // it's almost always monotonic
+    if stop_early && !order_improved {
       break;
     }
   }
-  best_order
+  best
+}
+
+pub(crate) fn auto_delta_encoding_order<T: NumberLike>(
+  nums: &[T],
+  compression_level: usize,
+  delta_lag: usize,
+  deterministic: bool,
+) -> usize {
+  // with a lag, each order needs that many more numbers before its deltas
+  // start
+  let limit = AUTO_DELTA_LIMIT + MAX_DELTA_ENCODING_ORDER * (delta_lag - 1);
+  let head_nums = if nums.len() < limit {
+    nums
+  } else {
+    &nums[0..limit]
+  };
+  // Taking deltas of a large dataset won't change the GCD,
+  // so we don't need to waste compute here inferring GCD's just to
+  // determine the best delta order.
+  let search_config = CompressorConfig::default()
+    .with_delta_lag(delta_lag)
+    .with_compression_level(min(compression_level, MAX_AUTO_DELTA_COMPRESSION_LEVEL))
+    .with_deterministic(deterministic);
+  best_config(&[head_nums], search_config, &[false], true).delta_encoding_order
 }
 
 #[cfg(test)]
 mod tests {
-  use crate::auto::auto_delta_encoding_order;
+  use crate::auto::*;
+  use crate::auto_compressor_config;
+  use crate::distinct_count::mix;
 
   #[test]
   fn test_auto_delta_encoding_order() {
@@ -98,8 +451,84 @@ mod tests {
       linear_trend.push(i);
       quadratic_trend.push(i * i);
     }
-    assert_eq!(auto_delta_encoding_order(&no_trend, 3), 0);
-    assert_eq!(auto_delta_encoding_order(&linear_trend, 3), 1);
-    assert_eq!(auto_delta_encoding_order(&quadratic_trend, 3), 2);
+    assert_eq!(auto_delta_encoding_order(&no_trend, 3, 1, false), 0);
+    assert_eq!(auto_delta_encoding_order(&linear_trend, 3, 1, false), 1);
+    assert_eq!(auto_delta_encoding_order(&quadratic_trend, 3, 1, false), 2);
+  }
+
+  #[test]
+  fn test_train_config() {
+    let nums = (0..3000).map(|i| i * 100 + (i * i) % 3).collect::<Vec<i64>>();
+    let objectives = TrainingObjectives::default()
+      .with_compression_levels(vec![2, 6]);
+    let trained = train_config(&nums, &objectives).unwrap();
+    assert_eq!(trained.candidates.len(), 2 * 8 * 2);
+    assert!(trained.config.delta_encoding_order > 0);
+    let best_size = trained.candidates.iter()
+      .map(|candidate| candidate.compressed_size)
+      .min()
+      .unwrap();
+    let best_candidate = trained.candidates.iter()
+      .find(|candidate| candidate.compressed_size == best_size)
+      .unwrap();
+    assert_eq!(
+      best_candidate.config.delta_encoding_order,
+      trained.config.delta_encoding_order,
+    );
+    assert!(best_candidate.compression_ratio > 1.0);
+    assert!(best_candidate.compress_seconds.is_some());
+
+    assert!(train_config::<i64>(&[], &objectives).is_err());
+    let invalid = TrainingObjectives::default().with_delta_encoding_orders(vec![8]);
+    assert!(train_config(&nums, &invalid).is_err());
+  }
+
+  #[test]
+  fn test_auto_options() {
+    // the first 2000 numbers are noise, and the rest trend linearly
+    let nums = (0..50000_i64)
+      .map(|i| if i < 2000 { (mix(i as u64) % 1000) as i64 } else { i * 1000 + i % 7 })
+      .collect::<Vec<_>>();
+    assert_eq!(auto_compressor_config(&nums, 8).delta_encoding_order, 0);
+    let default = auto_compressor_config_with_options(&nums, 8, &AutoOptions::default());
+    assert_eq!(default.delta_encoding_order, 0);
+    for strategy in [SamplingStrategy::Stratified, SamplingStrategy::Random { seed: 5 }] {
+      let options = AutoOptions::default().with_sampling_strategy(strategy);
+      let config = auto_compressor_config_with_options(&nums, 8, &options);
+      assert_eq!(config.delta_encoding_order, 1, "{:?}", strategy);
+      assert_eq!(config.compression_level, 8);
+    }
+
+    // the full search also tries turning GCDs off
+    let options = AutoOptions::default()
+      .with_sample_size(5000)
+      .with_search(AutoSearch::Full);
+    let config = auto_compressor_config_with_options(&nums[2000..], 3, &options);
+    assert_eq!(config.delta_encoding_order, 1);
+    let bytes = auto_compress_with_options(&nums, 3, &options);
+    assert_eq!(crate::auto_decompress::<i64>(&bytes).unwrap(), nums);
+
+    // samples at least as large as the data use all of it
+    let options = AutoOptions::default().with_sample_size(0);
+    auto_compressor_config_with_options(&nums[..1], 8, &options);
+  }
+
+  #[test]
+  fn test_auto_empty() {
+    assert_eq!(auto_delta_encoding_order::<i64>(&[], 8, 3, false), 0);
+    assert_eq!(auto_compressor_config::<i64>(&[], 8).delta_encoding_order, 0);
+    for strategy in [SamplingStrategy::Head, SamplingStrategy::Stratified, SamplingStrategy::Random { seed: 5 }] {
+      for search in [AutoSearch::DeltaOrder, AutoSearch::Full] {
+        let options = AutoOptions::default()
+          .with_sampling_strategy(strategy)
+          .with_search(search);
+        let config = auto_compressor_config_with_options::<i64>(&[], 8, &options);
+        assert_eq!(config.delta_encoding_order, 0);
+        let bytes = auto_compress_with_options::<i64>(&[], 8, &options);
+        assert!(crate::auto_decompress::<i64>(&bytes).unwrap().is_empty());
+      }
+    }
+    let bytes = auto_compress::<i64>(&[], 8);
+    assert!(crate::auto_decompress::<i64>(&bytes).unwrap().is_empty());
   }
 }
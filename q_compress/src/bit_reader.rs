@@ -1,4 +1,6 @@
-use std::cmp::min;
+use alloc::format;
+use alloc::vec::Vec;
+use core::cmp::min;
 
 use crate::bit_words::BitWords;
 use crate::bits;
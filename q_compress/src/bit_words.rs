@@ -1,5 +1,6 @@
-use std::convert::TryInto;
-use std::cmp::min;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::cmp::min;
 
 use crate::bits;
 use crate::constants::{BYTES_PER_WORD, WORD_SIZE};
@@ -66,9 +67,14 @@ impl BitWords {
   }
 
   pub fn truncate_left(&mut self, words_to_free: usize) {
-    self.words = self.words[words_to_free..].to_vec();
+    self.words.drain(..words_to_free);
     self.total_bits -= words_to_free * WORD_SIZE;
   }
+
+  pub fn clear(&mut self) {
+    self.words.clear();
+    self.total_bits = 0;
+  }
 }
 
 #[cfg(test)]
@@ -1,3 +1,8 @@
+use alloc::format;
+use alloc::vec::Vec;
+use core::cmp::min;
+use core::convert::TryInto;
+
 use crate::bits;
 use crate::bits::BASE_BIT_MASK;
 use crate::errors::{QCompressError, QCompressResult};
@@ -46,7 +51,21 @@ impl BitWriter {
   /// misaligned.
   pub fn write_aligned_bytes(&mut self, bytes: &[u8]) -> QCompressResult<()> {
     if self.j % 8 == 0 {
-      for &byte in bytes {
+      // fill out the current word byte by byte, then copy whole words
+      let first_word_end = min((WORD_SIZE - self.j) / 8, bytes.len());
+      for &byte in &bytes[..first_word_end] {
+        *self.last_mut() |= (byte as usize) << (WORD_SIZE - 8 - self.j);
+        self.j += 8;
+      }
+      let word_chunks = bytes[first_word_end..].chunks_exact(BYTES_PER_WORD);
+      let last_bytes = word_chunks.remainder();
+      self.words.extend(
+        word_chunks.map(|word_bytes| usize::from_be_bytes(word_bytes.try_into().unwrap()))
+      );
+      if bytes.len() > first_word_end {
+        self.j = WORD_SIZE;
+      }
+      for &byte in last_bytes {
         self.refresh_if_needed();
         *self.last_mut() |= (byte as usize) << (WORD_SIZE - 8 - self.j);
         self.j += 8;
@@ -85,8 +104,9 @@ impl BitWriter {
 
   /// Appends the bits to the writer.
   pub fn write(&mut self, bs: &[bool]) {
-    for &b in bs {
-      self.write_one(b);
+    for chunk in bs.chunks(64) {
+      let x = chunk.iter().fold(0_u64, |acc, &b| (acc << 1) | b as u64);
+      self.write_diff(x, chunk.len());
     }
   }
 
@@ -132,16 +152,22 @@ impl BitWriter {
 
     self.write_usize(x, jumpstart);
     x >>= jumpstart;
-    for _ in jumpstart..BITS_TO_ENCODE_N_ENTRIES {
-      if x > 0 {
-        self.write_one(true);
-        self.write_one(x & 1 > 0);
-        x >>= 1;
-      } else {
-        break;
-      }
+    // the rest of the varint is a (continue, bit) pair for each remaining bit
+    // and a final 0 unless all BITS_TO_ENCODE_N_ENTRIES bits were used;
+    // this fits in a u64, so we gather it into a single write
+    let mut tail = 0_u64;
+    let mut n_tail_bits = 0;
+    let max_tail_bits = 2 * (BITS_TO_ENCODE_N_ENTRIES - jumpstart);
+    while x > 0 {
+      tail = (tail << 2) | 2 | (x & 1) as u64;
+      n_tail_bits += 2;
+      x >>= 1;
+    }
+    if n_tail_bits < max_tail_bits {
+      tail <<= 1;
+      n_tail_bits += 1;
     }
-    self.write_one(false);
+    self.write_diff(tail, n_tail_bits);
   }
 
   pub(crate) fn finish_byte(&mut self) {
@@ -169,21 +195,40 @@ impl BitWriter {
     }
   }
 
+  // returns the bytes written from byte_idx onward
+  pub(crate) fn bytes_since(&self, byte_idx: usize) -> Vec<u8> {
+    let start_word_idx = byte_idx / BYTES_PER_WORD;
+    let padded_start_idx = byte_idx % BYTES_PER_WORD;
+    let n_bytes = self.byte_size() - byte_idx;
+    let mut res = bits::words_to_bytes(&self.words[start_word_idx..]);
+    res.drain(..padded_start_idx);
+    res.truncate(n_bytes);
+    res
+  }
+
   pub fn drain_bytes(&mut self) -> Vec<u8> {
     let byte_size = self.byte_size();
     let mut res = bits::words_to_bytes(&self.words);
     res.truncate(byte_size);
+    self.clear();
+    res
+  }
 
+  // discards everything written, keeping the allocated words for reuse
+  pub(crate) fn clear(&mut self) {
     self.words.clear();
     self.j = WORD_SIZE;
-
-    res
   }
 }
 
 #[cfg(test)]
 mod tests {
+  use alloc::vec::Vec;
+
   use super::BitWriter;
+  use crate::bit_reader::BitReader;
+  use crate::bit_words::BitWords;
+  use crate::constants::MAX_ENTRIES;
 
   #[test]
   fn test_write_bigger_num() {
@@ -235,6 +280,45 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_aligned_bytes_across_words() {
+    let bytes = (0..37).collect::<Vec<u8>>();
+    for n_prefix_bytes in 0..10 {
+      let mut writer = BitWriter::default();
+      for _ in 0..n_prefix_bytes {
+        writer.write_usize(255, 8);
+      }
+      writer.write_aligned_bytes(&bytes).expect("misaligned");
+      writer.write_aligned_bytes(&[]).expect("misaligned");
+      writer.write_usize(5, 3);
+
+      let mut expected = vec![255; n_prefix_bytes];
+      expected.extend(&bytes);
+      expected.push(160);
+      assert_eq!(writer.drain_bytes(), expected);
+    }
+  }
+
+  #[test]
+  fn test_varint_and_bools_round_trip() {
+    let bools = (0..150).map(|i| i % 3 == 0).collect::<Vec<_>>();
+    let varints = [(0, 0), (1, 0), (100, 3), (MAX_ENTRIES, 0), (MAX_ENTRIES, 24), (77, 24)];
+    let mut writer = BitWriter::default();
+    writer.write_one(true);
+    writer.write(&bools);
+    for &(x, jumpstart) in &varints {
+      writer.write_varint(x, jumpstart);
+    }
+    let words = BitWords::from(writer.drain_bytes());
+
+    let mut reader = BitReader::from(&words);
+    assert!(reader.read_one().unwrap());
+    assert_eq!(reader.read(bools.len()).unwrap(), bools);
+    for &(x, jumpstart) in &varints {
+      assert_eq!(reader.read_varint(jumpstart).unwrap(), x);
+    }
+  }
+
   #[test]
   fn test_assign_usize() {
     let mut writer = BitWriter::default();
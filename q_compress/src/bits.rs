@@ -1,5 +1,9 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
 use crate::constants::WORD_SIZE;
 use crate::data_types::UnsignedLike;
+use crate::float_math;
 
 pub const BASE_BIT_MASK: usize = 1 << (WORD_SIZE - 1);
 const BUMPY_MAX_K: usize = 129;
@@ -9,7 +13,7 @@ const BUMPY_LOG_TABLE: [(f64, f64); BUMPY_MAX_K] = {
   let exp_bit = 1_u64 << 52;
   while k < BUMPY_MAX_K {
     let mem_repr_exp = exp_bit * (k + 1023 + 1) as u64;
-    res[k] = ((k + 2) as f64, unsafe { std::mem::transmute(mem_repr_exp) });
+    res[k] = ((k + 2) as f64, unsafe { core::mem::transmute(mem_repr_exp) });
     k += 1;
   }
   res
@@ -89,7 +93,8 @@ pub fn bits_to_string(bits: &[bool]) -> String {
 
 #[inline(always)]
 fn bumpy_log(x: f64) -> f64 {
-  let k = x.log2() as usize;
+  // floor(log2(x)) for x >= 1, read exactly from the float's exponent
+  let k = (x.to_bits() >> 52) as usize - 1023;
   let (base, exp) = BUMPY_LOG_TABLE[k];
   base - exp / x
 }
@@ -103,8 +108,13 @@ pub fn avg_offset_bits<U: UnsignedLike>(lower: U, upper: U, gcd: U) -> f64 {
 // model this cost and get slightly different bumpy log formulas,
 // but I haven't found
 // anything that beats a simple log. Plus it's computationally cheap.
-pub fn avg_depth_bits(weight: usize, total_weight: usize) -> f64 {
-  (total_weight as f64 / weight as f64).log2()
+pub fn avg_depth_bits(weight: usize, total_weight: usize, deterministic: bool) -> f64 {
+  let ratio = total_weight as f64 / weight as f64;
+  if deterministic {
+    float_math::portable_log2(ratio)
+  } else {
+    float_math::log2(ratio)
+  }
 }
 
 pub fn ceil_div(x: usize, divisor: usize) -> usize {
@@ -130,10 +140,12 @@ mod tests {
 
   #[test]
   fn test_depth_bits() {
-    assert_eq!(avg_depth_bits(2, 2), 0.0);
-    assert_eq!(avg_depth_bits(2, 4), 1.0);
-    assert_eq!(avg_depth_bits(2, 8), 2.0);
-    assert_eq!(avg_depth_bits(4, 8), 1.0);
+    for deterministic in [false, true] {
+      assert_eq!(avg_depth_bits(2, 2, deterministic), 0.0);
+      assert_eq!(avg_depth_bits(2, 4, deterministic), 1.0);
+      assert_eq!(avg_depth_bits(2, 8, deterministic), 2.0);
+      assert_eq!(avg_depth_bits(4, 8, deterministic), 1.0);
+    }
   }
 
   #[test]
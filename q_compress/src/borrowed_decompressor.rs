@@ -0,0 +1,178 @@
+use alloc::vec::Vec;
+use core::cmp::min;
+
+use crate::{ChunkMetadata, Decompressor, DecompressorConfig, Flags};
+use crate::bits;
+use crate::data_types::NumberLike;
+use crate::errors::{ErrorKind, QCompressResult};
+
+const INITIAL_READ_SIZE: usize = 1 << 12;
+
+/// Decompresses from a borrowed byte slice, such as a memory-mapped file,
+/// with bounded memory.
+///
+/// A [`Decompressor`] owns its compressed data, so handing it a
+/// large file would double resident memory.
+/// `BorrowedDecompressor` instead copies over only the bytes needed for the
+/// next header, chunk metadata, or chunk body, and frees each chunk's
+/// compressed data once it is decoded.
+/// At any time it holds a copy of roughly one chunk's worth of compressed
+/// data.
+/// This is not zero-copy: every byte of the input is still copied once.
+///
+/// Like [`Decompressor`], all methods leave its state unchanged if they
+/// return an error.
+/// ```
+/// use q_compress::{BorrowedDecompressor, Compressor};
+///
+/// let bytes = Compressor::<i64>::default().simple_compress(&[1, 2, 3]);
+/// let mut decompressor = BorrowedDecompressor::<i64>::new(&bytes);
+/// let nums = decompressor.simple_decompress().expect("decompression");
+/// assert_eq!(nums, vec![1, 2, 3]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct BorrowedDecompressor<'a, T: NumberLike> {
+  bytes: &'a [u8],
+  byte_idx: usize,
+  decompressor: Decompressor<T>,
+}
+
+impl<'a, T: NumberLike> BorrowedDecompressor<'a, T> {
+  /// Creates a new borrowed decompressor over `bytes` with the default
+  /// [`DecompressorConfig`].
+  pub fn new(bytes: &'a [u8]) -> Self {
+    Self::from_config(DecompressorConfig::default(), bytes)
+  }
+
+  /// Creates a new borrowed decompressor over `bytes`, given a
+  /// [`DecompressorConfig`].
+  pub fn from_config(config: DecompressorConfig, bytes: &'a [u8]) -> Self {
+    Self {
+      bytes,
+      byte_idx: 0,
+      decompressor: Decompressor::from_config(config),
+    }
+  }
+
+  fn write_next_bytes(&mut self, n: usize) {
+    let end = min(self.byte_idx + n, self.bytes.len());
+    self.decompressor.extend_bytes(&self.bytes[self.byte_idx..end]);
+    self.byte_idx = end;
+  }
+
+  // retries with more and more of the input until we stop running out of data
+  fn with_more_data_as_needed<X, F>(&mut self, mut f: F) -> QCompressResult<X>
+  where F: FnMut(&mut Decompressor<T>) -> QCompressResult<X> {
+    let mut read_size = INITIAL_READ_SIZE;
+    loop {
+      match f(&mut self.decompressor) {
+        Err(e) if matches!(e.kind, ErrorKind::InsufficientData) && self.byte_idx < self.bytes.len() => {
+          self.write_next_bytes(read_size);
+          read_size *= 2;
+        }
+        res => return res,
+      }
+    }
+  }
+
+  fn ensure_chunk_body_available(&mut self) {
+    if let Some(body_bits) = self.decompressor.chunk_body_bits_remaining() {
+      let bits_missing = body_bits.saturating_sub(self.decompressor.bits_available());
+      self.write_next_bytes(bits::ceil_div(bits_missing, 8));
+    }
+  }
+
+  /// Reads the header, returning its [`Flags`].
+  /// Wraps [`Decompressor::header`].
+  pub fn header(&mut self) -> QCompressResult<Flags> {
+    self.with_more_data_as_needed(|decompressor| decompressor.header())
+  }
+
+  /// Returns the [`UserMetadata`][crate::UserMetadata] from the header.
+  /// Wraps [`Decompressor::header_metadata`].
+  pub fn header_metadata(&self) -> &crate::UserMetadata {
+    self.decompressor.header_metadata()
+  }
+
+  /// Reads a [`ChunkMetadata`], returning it, or `None` if it instead finds
+  /// the footer.
+  /// Wraps [`Decompressor::chunk_metadata`].
+  pub fn chunk_metadata(&mut self) -> QCompressResult<Option<ChunkMetadata<T>>> {
+    self.with_more_data_as_needed(|decompressor| decompressor.chunk_metadata())
+  }
+
+  /// Reads a chunk body, returning it as a vector of numbers.
+  /// Wraps [`Decompressor::chunk_body`].
+  pub fn chunk_body(&mut self) -> QCompressResult<Vec<T>> {
+    self.ensure_chunk_body_available();
+    let nums = self.decompressor.chunk_body()?;
+    self.decompressor.free_compressed_memory();
+    Ok(nums)
+  }
+
+  /// Reads a chunk body into the start of `dst`, returning the number of
+  /// numbers written.
+  /// Wraps [`Decompressor::chunk_body_into`].
+  pub fn chunk_body_into(&mut self, dst: &mut [T]) -> QCompressResult<usize> {
+    self.ensure_chunk_body_available();
+    let n = self.decompressor.chunk_body_into(dst)?;
+    self.decompressor.free_compressed_memory();
+    Ok(n)
+  }
+
+  /// Decompresses the whole input, returning a vector of numbers.
+  /// Will return an error if there are any compatibility, corruption,
+  /// or insufficient data issues.
+  pub fn simple_decompress(&mut self) -> QCompressResult<Vec<T>> {
+    let mut res = Vec::new();
+    self.header()?;
+    while let Some(meta) = self.chunk_metadata()? {
+      let start = res.len();
+      res.resize(start + meta.n, T::default());
+      let n = self.chunk_body_into(&mut res[start..])?;
+      res.truncate(start + n);
+    }
+    Ok(res)
+  }
+
+  /// Returns the number of bytes of the input not yet handed to the
+  /// underlying decompressor.
+  pub fn bytes_remaining(&self) -> usize {
+    self.bytes.len() - self.byte_idx
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{BorrowedDecompressor, Compressor, CompressorConfig};
+  use crate::errors::ErrorKind;
+
+  #[test]
+  fn test_borrowed_recovers_multiple_chunks() {
+    let nums = (0..10000).map(|i| (i * i) % 1001).collect::<Vec<i64>>();
+    let mut compressor = Compressor::<i64>::from_config(
+      CompressorConfig::default().with_delta_encoding_order(1)
+    );
+    compressor.header().unwrap();
+    for chunk in nums.chunks(3000) {
+      compressor.chunk(chunk).unwrap();
+    }
+    compressor.footer().unwrap();
+    let bytes = compressor.drain_bytes();
+
+    let mut decompressor = BorrowedDecompressor::<i64>::new(&bytes);
+    assert_eq!(decompressor.simple_decompress().unwrap(), nums);
+    assert_eq!(decompressor.bytes_remaining(), 0);
+  }
+
+  #[test]
+  fn test_borrowed_insufficient_data() {
+    let nums = (0..1000).collect::<Vec<i64>>();
+    let bytes = Compressor::<i64>::default().simple_compress(&nums);
+    let mut decompressor = BorrowedDecompressor::<i64>::new(&bytes[..bytes.len() - 10]);
+    decompressor.header().unwrap();
+    decompressor.chunk_metadata().unwrap();
+    let err = decompressor.chunk_body().unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InsufficientData));
+  }
+}
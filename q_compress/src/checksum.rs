@@ -0,0 +1,77 @@
+// CRC32 (IEEE polynomial, as used by zlib and gzip) of chunk bodies.
+// We implement it here rather than depending on a crate so that it works
+// the same way with or without std.
+
+const POLYNOMIAL: u32 = 0xEDB88320;
+
+const fn make_table() -> [u32; 256] {
+  let mut table = [0_u32; 256];
+  let mut i = 0;
+  while i < 256 {
+    let mut crc = i as u32;
+    let mut k = 0;
+    while k < 8 {
+      crc = if crc & 1 == 1 {
+        (crc >> 1) ^ POLYNOMIAL
+      } else {
+        crc >> 1
+      };
+      k += 1;
+    }
+    table[i] = crc;
+    i += 1;
+  }
+  table
+}
+
+static TABLE: [u32; 256] = make_table();
+
+#[derive(Clone, Copy, Debug)]
+pub struct Crc32 {
+  state: u32,
+}
+
+impl Default for Crc32 {
+  fn default() -> Self {
+    Crc32 { state: u32::MAX }
+  }
+}
+
+impl Crc32 {
+  pub fn update(&mut self, bytes: &[u8]) {
+    for &byte in bytes {
+      let idx = ((self.state ^ byte as u32) & 0xFF) as usize;
+      self.state = TABLE[idx] ^ (self.state >> 8);
+    }
+  }
+
+  pub fn finish(&self) -> u32 {
+    !self.state
+  }
+}
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+  let mut hasher = Crc32::default();
+  hasher.update(bytes);
+  hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_crc32() {
+    assert_eq!(crc32(&[]), 0);
+    assert_eq!(crc32(b"123456789"), 0xCBF43926);
+  }
+
+  #[test]
+  fn test_incremental_crc32() {
+    let mut hasher = Crc32::default();
+    hasher.update(b"1234");
+    hasher.update(b"");
+    hasher.update(b"56789");
+    assert_eq!(hasher.finish(), crc32(b"123456789"));
+  }
+}
@@ -1,10 +1,13 @@
-use std::cmp::min;
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::min;
 
 use crate::bit_reader::BitReader;
-use crate::{ChunkMetadata, delta_encoding, PrefixMetadata};
+use crate::{AffineTransform, ChunkMetadata, delta_encoding, float_split, Flags, NanPositions, PrefixMetadata, Quantization, xor_encoding};
 use crate::data_types::NumberLike;
 use crate::delta_encoding::DeltaMoments;
-use crate::errors::QCompressResult;
+use crate::errors::{QCompressError, QCompressResult};
 use crate::num_decompressor::NumDecompressor;
 
 pub struct Numbers<T: NumberLike> {
@@ -12,40 +15,218 @@ pub struct Numbers<T: NumberLike> {
   pub finished_chunk_body: bool,
 }
 
+pub struct BatchInfo {
+  pub n: usize,
+  pub finished_chunk_body: bool,
+}
+
+// how to convert the signed integers a chunk's numbers were compressed as
+// back into numbers, if the compressor converted them
+#[derive(Clone, Copy, Debug)]
+pub enum IntConversion {
+  Decimal(u32),
+  Quantized(Quantization),
+}
+
+impl IntConversion {
+  fn from_metadata<T: NumberLike>(metadata: &ChunkMetadata<T>) -> Option<Self> {
+    match (metadata.decimal_exponent, metadata.quantization) {
+      (Some(exponent), _) => Some(IntConversion::Decimal(exponent)),
+      (None, Some(quantization)) => Some(IntConversion::Quantized(quantization)),
+      (None, None) => None,
+    }
+  }
+
+  fn convert<T: NumberLike>(&self, nums: &mut [T]) {
+    match *self {
+      IntConversion::Decimal(exponent) => for num in nums {
+        *num = T::from_decimal_int(num.to_signed(), exponent);
+      },
+      IntConversion::Quantized(quantization) => for num in nums {
+        *num = T::dequantize(num.to_signed(), quantization);
+      },
+    }
+  }
+}
+
+// every conversion from the numbers a chunk body decodes to back into the
+// chunk's numbers, in the reverse order the compressor applied them
+#[derive(Clone, Copy, Debug)]
+pub struct Conversion<T: NumberLike> {
+  affine_transform: Option<AffineTransform<T>>,
+  int_conversion: Option<IntConversion>,
+}
+
+impl<T: NumberLike> Conversion<T> {
+  fn from_metadata(metadata: &ChunkMetadata<T>) -> Option<Self> {
+    let affine_transform = metadata.affine_transform;
+    let int_conversion = IntConversion::from_metadata(metadata);
+    if affine_transform.is_none() && int_conversion.is_none() {
+      return None;
+    }
+    Some(Conversion {
+      affine_transform,
+      int_conversion,
+    })
+  }
+
+  fn convert(&self, nums: &mut [T]) {
+    if let Some(affine_transform) = &self.affine_transform {
+      affine_transform.apply(nums);
+    }
+    if let Some(int_conversion) = &self.int_conversion {
+      int_conversion.convert(nums);
+    }
+  }
+}
+
 // ChunkBodyDecompressor wraps NumDecompressor and handles reconstruction from
-// delta encoding.
+// delta, XOR, or float split encoding.
+// Raw chunks need no reconstruction, so they use the Simple variant.
+// Chunks with NaN positions wrap the decompressor for their other numbers
+// in the WithNans variant.
 #[derive(Clone, Debug)]
 pub enum ChunkBodyDecompressor<T: NumberLike> {
   Simple {
     num_decompressor: NumDecompressor<T::Unsigned>,
+    unsigneds: Vec<T::Unsigned>,
+    conversion: Option<Conversion<T>>,
   },
   Delta {
     n: usize,
     num_decompressor: NumDecompressor<T::Unsigned>,
+    u_deltas: Vec<T::Unsigned>,
+    signeds: Vec<T::Signed>,
     delta_moments: DeltaMoments<T>,
     nums_processed: usize,
+    conversion: Option<Conversion<T>>,
+  },
+  Xor {
+    n: usize,
+    num_decompressor: NumDecompressor<T::Unsigned>,
+    u_xors: Vec<T::Unsigned>,
+    // the unsigned representation of the last number decompressed, or of
+    // the first number if none have been
+    last: T::Unsigned,
+    nums_processed: usize,
+    conversion: Option<Conversion<T>>,
+  },
+  FloatSplit {
+    n: usize,
+    mantissa_bits: usize,
+    // the exponents come first in the body, so we decompress all of them
+    // before any mantissas
+    exponent_decompressor: NumDecompressor<T::Unsigned>,
+    mantissa_decompressor: NumDecompressor<T::Unsigned>,
+    exponents: Vec<T::Unsigned>,
+    mantissas: Vec<T::Unsigned>,
+    nums_processed: usize,
+    conversion: Option<Conversion<T>>,
+  },
+  WithNans {
+    n: usize,
+    inner: Box<ChunkBodyDecompressor<T>>,
+    nan_positions: NanPositions<T>,
+    // scratch space for the numbers the inner decompressor returns
+    valid: Vec<T>,
+    nums_processed: usize,
   },
 }
 
 impl<T: NumberLike> ChunkBodyDecompressor<T> {
-  pub(crate) fn new(metadata: &ChunkMetadata<T>) -> QCompressResult<Self> {
+  pub(crate) fn new(metadata: &ChunkMetadata<T>, flags: &Flags) -> QCompressResult<Self> {
+    match &metadata.nan_positions {
+      Some(nan_positions) => Ok(Self::WithNans {
+        n: metadata.n,
+        inner: Box::new(Self::new_for_n(
+          metadata,
+          metadata.n - nan_positions.count(),
+          flags,
+        )?),
+        nan_positions: nan_positions.clone(),
+        valid: Vec::new(),
+        nums_processed: 0,
+      }),
+      None => Self::new_for_n(metadata, metadata.n, flags),
+    }
+  }
+
+  // builds the decompressor for a body of n numbers
+  fn new_for_n(metadata: &ChunkMetadata<T>, n: usize, flags: &Flags) -> QCompressResult<Self> {
     Ok(match &metadata.prefix_metadata {
       PrefixMetadata::Simple { prefixes } => Self::Simple {
         num_decompressor: NumDecompressor::new(
-          metadata.n,
+          n,
           metadata.compressed_body_size,
-          prefixes.clone()
-        )?
+          prefixes.clone(),
+          flags.use_chunk_checksums,
+        )?,
+        unsigneds: Vec::new(),
+        conversion: Conversion::from_metadata(metadata),
       },
       PrefixMetadata::Delta { prefixes, delta_moments } => Self::Delta {
-        n: metadata.n,
+        n,
         num_decompressor: NumDecompressor::new(
-          metadata.n.saturating_sub(delta_moments.order()),
+          n.saturating_sub(delta_moments.moments.len()),
           metadata.compressed_body_size,
-          prefixes.clone()
+          prefixes.clone(),
+          flags.use_chunk_checksums,
         )?,
+        u_deltas: Vec::new(),
+        signeds: Vec::new(),
         delta_moments: delta_moments.clone(),
         nums_processed: 0,
+        conversion: Conversion::from_metadata(metadata),
+      },
+      PrefixMetadata::Raw { lower, bit_width } => Self::Simple {
+        num_decompressor: NumDecompressor::new_raw(
+          n,
+          metadata.compressed_body_size,
+          lower.to_unsigned(),
+          *bit_width,
+          flags.use_chunk_checksums,
+        ),
+        unsigneds: Vec::new(),
+        conversion: Conversion::from_metadata(metadata),
+      },
+      PrefixMetadata::Xor { prefixes, first } => Self::Xor {
+        n,
+        num_decompressor: NumDecompressor::new(
+          n.saturating_sub(1),
+          metadata.compressed_body_size,
+          prefixes.clone(),
+          flags.use_chunk_checksums,
+        )?,
+        u_xors: Vec::new(),
+        last: first.to_unsigned(),
+        nums_processed: 0,
+        conversion: Conversion::from_metadata(metadata),
+      },
+      PrefixMetadata::FloatSplit {
+        exponent_prefixes,
+        mantissa_prefixes,
+        exponent_body_size,
+      } => Self::FloatSplit {
+        n,
+        mantissa_bits: T::MANTISSA_BITS.ok_or_else(|| QCompressError::corruption(
+          "float split encoding is only valid for floats"
+        ))?,
+        exponent_decompressor: NumDecompressor::new(
+          n,
+          *exponent_body_size,
+          exponent_prefixes.clone(),
+          flags.use_chunk_checksums,
+        )?.without_trailing_checksum(),
+        mantissa_decompressor: NumDecompressor::new(
+          n,
+          metadata.compressed_body_size.saturating_sub(*exponent_body_size),
+          mantissa_prefixes.clone(),
+          flags.use_chunk_checksums,
+        )?,
+        exponents: Vec::new(),
+        mantissas: Vec::new(),
+        nums_processed: 0,
+        conversion: Conversion::from_metadata(metadata),
       },
     })
   }
@@ -56,63 +237,234 @@ impl<T: NumberLike> ChunkBodyDecompressor<T> {
     limit: usize,
     error_on_insufficient_data: bool,
   ) -> QCompressResult<Numbers<T>> {
-    match self {
-      Self::Simple { num_decompressor } => num_decompressor.decompress_unsigneds_limited(
-        reader,
-        limit,
-        error_on_insufficient_data,
-      ).map(|u| {
-        Numbers {
-          nums: u.unsigneds.into_iter().map(T::from_unsigned).collect(),
-          finished_chunk_body: u.finished_chunk_body,
+    let mut nums = vec![T::default(); min(limit, self.n_remaining())];
+    let batch = self.decompress_next_batch_into(
+      reader,
+      &mut nums,
+      error_on_insufficient_data,
+    )?;
+    nums.truncate(batch.n);
+    Ok(Numbers {
+      nums,
+      finished_chunk_body: batch.finished_chunk_body,
+    })
+  }
+
+  // decompresses up to dst.len() numbers into the start of dst, reusing
+  // this decompressor's scratch space instead of allocating
+  pub fn decompress_next_batch_into(
+    &mut self,
+    reader: &mut BitReader,
+    dst: &mut [T],
+    error_on_insufficient_data: bool,
+  ) -> QCompressResult<BatchInfo> {
+    let batch = match self {
+      Self::Simple { num_decompressor, unsigneds, conversion } => {
+        let finished_chunk_body = num_decompressor.decompress_unsigneds_limited(
+          reader,
+          dst.len(),
+          error_on_insufficient_data,
+          unsigneds,
+        )?;
+        for (num, &u) in dst.iter_mut().zip(unsigneds.iter()) {
+          *num = T::from_unsigned(u);
         }
-      }),
+        if let Some(conversion) = conversion {
+          conversion.convert(&mut dst[..unsigneds.len()]);
+        }
+        BatchInfo {
+          n: unsigneds.len(),
+          finished_chunk_body,
+        }
+      },
       Self::Delta {
         n,
         num_decompressor,
+        u_deltas,
+        signeds,
         delta_moments,
         nums_processed,
+        conversion,
       } => {
-        let u_deltas = num_decompressor.decompress_unsigneds_limited(
+        let finished_deltas = num_decompressor.decompress_unsigneds_limited(
           reader,
-          limit,
+          dst.len(),
           error_on_insufficient_data,
+          u_deltas,
         )?;
-        let batch_size = if u_deltas.finished_chunk_body {
-          min(limit, *n - *nums_processed)
+        let batch_size = if finished_deltas {
+          min(dst.len(), *n - *nums_processed)
         } else {
-          u_deltas.unsigneds.len()
+          u_deltas.len()
         };
-        let signeds = u_deltas.unsigneds.into_iter()
-          .map(T::Signed::from_unsigned)
-          .collect::<Vec<_>>();
-        let nums = delta_encoding::reconstruct_nums(
+        delta_encoding::reconstruct_nums_into(
           delta_moments,
-          &signeds,
-          batch_size,
+          u_deltas,
+          signeds,
+          &mut dst[..batch_size],
         );
+        if let Some(conversion) = conversion {
+          conversion.convert(&mut dst[..batch_size]);
+        }
         *nums_processed += batch_size;
-        Ok(Numbers {
-          nums,
+        BatchInfo {
+          n: batch_size,
           finished_chunk_body: nums_processed == n,
-        })
-      }
+        }
+      },
+      Self::Xor {
+        n,
+        num_decompressor,
+        u_xors,
+        last,
+        nums_processed,
+        conversion,
+      } => {
+        // the first number comes from the metadata rather than the body
+        let wants_first = *nums_processed == 0 && !dst.is_empty();
+        let finished_xors = num_decompressor.decompress_unsigneds_limited(
+          reader,
+          dst.len() - wants_first as usize,
+          error_on_insufficient_data,
+          u_xors,
+        )?;
+        // but if it's the only number, the body must be finished too
+        let n_first = if wants_first && (*n > 1 || finished_xors) {
+          1
+        } else {
+          0
+        };
+        if n_first == 1 {
+          dst[0] = T::from_unsigned(*last);
+        }
+        let batch_size = n_first + u_xors.len();
+        xor_encoding::reconstruct_nums_into(
+          last,
+          u_xors,
+          &mut dst[n_first..batch_size],
+        );
+        if let Some(conversion) = conversion {
+          conversion.convert(&mut dst[..batch_size]);
+        }
+        *nums_processed += batch_size;
+        BatchInfo {
+          n: batch_size,
+          finished_chunk_body: nums_processed == n,
+        }
+      },
+      Self::FloatSplit {
+        n,
+        mantissa_bits,
+        exponent_decompressor,
+        mantissa_decompressor,
+        exponents,
+        mantissas,
+        nums_processed,
+        conversion,
+      } => {
+        if exponents.len() < *n {
+          // mantissas is free to use as scratch space until then
+          let finished_exponents = exponent_decompressor.decompress_unsigneds_limited(
+            reader,
+            *n - exponents.len(),
+            error_on_insufficient_data,
+            mantissas,
+          )?;
+          exponents.extend_from_slice(mantissas);
+          mantissas.clear();
+          if !finished_exponents {
+            return Ok(BatchInfo {
+              n: 0,
+              finished_chunk_body: false,
+            });
+          }
+          mantissa_decompressor.continue_checksum_from(exponent_decompressor);
+        }
+
+        let finished_chunk_body = mantissa_decompressor.decompress_unsigneds_limited(
+          reader,
+          dst.len(),
+          error_on_insufficient_data,
+          mantissas,
+        )?;
+        let batch_size = mantissas.len();
+        float_split::reconstruct_nums_into(
+          &exponents[*nums_processed..],
+          mantissas,
+          *mantissa_bits,
+          &mut dst[..batch_size],
+        );
+        if let Some(conversion) = conversion {
+          conversion.convert(&mut dst[..batch_size]);
+        }
+        *nums_processed += batch_size;
+        BatchInfo {
+          n: batch_size,
+          finished_chunk_body,
+        }
+      },
+      Self::WithNans {
+        n,
+        inner,
+        nan_positions,
+        valid,
+        nums_processed,
+      } => {
+        let end = min(*nums_processed + dst.len(), *n);
+        let n_valid = nan_positions.count_valid_in(*nums_processed..end);
+        valid.resize(n_valid, T::default());
+        // the inner body may already be finished if only NaNs remain
+        let n_valid_decompressed = if n_valid > 0 {
+          inner.decompress_next_batch_into(reader, valid, error_on_insufficient_data)?.n
+        } else {
+          0
+        };
+        let batch_size = nan_positions.fill(
+          *nums_processed,
+          &valid[..n_valid_decompressed],
+          &mut dst[..end - *nums_processed],
+        );
+        *nums_processed += batch_size;
+        BatchInfo {
+          n: batch_size,
+          finished_chunk_body: nums_processed == n,
+        }
+      },
+    };
+    // a corrupt chunk can decode to numbers outside the type's range
+    T::validate_batch(&dst[..batch.n])?;
+    Ok(batch)
+  }
+
+  pub fn n_remaining(&self) -> usize {
+    match self {
+      Self::Simple { num_decompressor, .. } => num_decompressor.n_remaining(),
+      Self::Delta { n, nums_processed, .. } |
+      Self::Xor { n, nums_processed, .. } |
+      Self::FloatSplit { n, nums_processed, .. } |
+      Self::WithNans { n, nums_processed, .. } => *n - *nums_processed,
     }
   }
 
   pub fn bits_remaining(&self) -> usize {
     match self {
-      Self::Simple { num_decompressor } => num_decompressor.bits_remaining(),
-      Self::Delta { num_decompressor, n: _, delta_moments: _, nums_processed: _ } => num_decompressor.bits_remaining(),
+      Self::Simple { num_decompressor, .. } => num_decompressor.bits_remaining(),
+      Self::Delta { num_decompressor, .. } |
+      Self::Xor { num_decompressor, .. } => num_decompressor.bits_remaining(),
+      Self::FloatSplit { exponent_decompressor, mantissa_decompressor, .. } => {
+        exponent_decompressor.bits_remaining() + mantissa_decompressor.bits_remaining()
+      },
+      Self::WithNans { inner, .. } => inner.bits_remaining(),
     }
   }
 }
 
 #[cfg(test)]
 mod tests {
-  use std::marker::PhantomData;
+  use core::marker::PhantomData;
 
   use super::ChunkBodyDecompressor;
+  use crate::{CompressorConfig, Flags, UserMetadata};
   use crate::chunk_metadata::{ChunkMetadata, PrefixMetadata};
   use crate::errors::ErrorKind;
   use crate::prefix::Prefix;
@@ -138,6 +490,14 @@ mod tests {
         prefix_w_code(vec![false]),
         prefix_w_code(vec![true, false]),
       ]},
+      decimal_exponent: None,
+      quantization: None,
+      min_max: None,
+      distinct_count: None,
+      nan_positions: None,
+      affine_transform: None,
+      uses_shared_prefixes: false,
+      user_metadata: UserMetadata::new(),
       phantom: PhantomData,
     };
     let metadata_duplicating_prefix = ChunkMetadata::<i64> {
@@ -148,11 +508,19 @@ mod tests {
         prefix_w_code(vec![false]),
         prefix_w_code(vec![true]),
       ]},
+      decimal_exponent: None,
+      quantization: None,
+      min_max: None,
+      distinct_count: None,
+      nan_positions: None,
+      affine_transform: None,
+      uses_shared_prefixes: false,
+      user_metadata: UserMetadata::new(),
       phantom: PhantomData,
     };
 
     for bad_metadata in vec![metadata_missing_prefix, metadata_duplicating_prefix] {
-      let result = ChunkBodyDecompressor::new(&bad_metadata);
+      let result = ChunkBodyDecompressor::new(&bad_metadata, &Flags::from(&CompressorConfig::default()));
       match result {
         Ok(_) => panic!("expected an error for bad metadata: {:?}", bad_metadata),
         Err(e) if matches!(e.kind, ErrorKind::Corruption) => (),
@@ -160,4 +528,53 @@ mod tests {
       }
     }
   }
+
+  #[cfg(feature = "timestamps_96")]
+  #[test]
+  fn test_out_of_range_timestamps_error_not_panic() {
+    use crate::bit_reader::BitReader;
+    use crate::bit_words::BitWords;
+    use crate::data_types::TimestampNanos96;
+    use crate::delta_encoding::DeltaMoments;
+
+    // deltas of 1 starting from the latest valid timestamp
+    let latest = TimestampNanos96::from_secs_and_nanos(i64::MAX, 999_999_999);
+    let metadata = ChunkMetadata::<TimestampNanos96> {
+      n: 3,
+      compressed_body_size: 0,
+      prefix_metadata: PrefixMetadata::Delta {
+        prefixes: vec![Prefix {
+          count: 2,
+          code: vec![],
+          lower: 1,
+          upper: 1,
+          run_len_jumpstart: None,
+          gcd: 1,
+          phantom: PhantomData,
+        }],
+        delta_moments: DeltaMoments {
+          moments: vec![latest.to_total_parts()],
+          lag: 1,
+          phantom: PhantomData,
+        },
+      },
+      decimal_exponent: None,
+      quantization: None,
+      min_max: None,
+      distinct_count: None,
+      nan_positions: None,
+      affine_transform: None,
+      uses_shared_prefixes: false,
+      user_metadata: UserMetadata::new(),
+      phantom: PhantomData,
+    };
+    let flags = Flags::from(&CompressorConfig::default().with_use_chunk_checksums(false));
+    let mut decompressor = ChunkBodyDecompressor::new(&metadata, &flags).unwrap();
+    let words = BitWords::default();
+    let mut reader = BitReader::from(&words);
+    match decompressor.decompress_next_batch(&mut reader, 3, true) {
+      Ok(numbers) => panic!("expected an error for out of range timestamps: {:?}", numbers.nums),
+      Err(e) => assert!(matches!(e.kind, ErrorKind::Corruption), "{:?}", e),
+    }
+  }
 }
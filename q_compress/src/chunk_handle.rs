@@ -0,0 +1,205 @@
+use alloc::vec::Vec;
+
+use crate::ChunkMetadata;
+use crate::bit_reader::BitReader;
+use crate::bit_words::BitWords;
+use crate::data_types::NumberLike;
+use crate::decompressor::ChunkStart;
+use crate::errors::{ErrorPosition, QCompressResult, Section};
+
+// roughly the number of numbers decoded by each call to next_block
+pub(crate) const BLOCK_SIZE: usize = 1 << 10;
+
+/// A chunk whose metadata has been read but whose body is only decoded on
+/// demand, one block of about 1000 numbers at a time.
+///
+/// Get these from [`Decompressor::chunks`][crate::Decompressor::chunks].
+/// This lets query engines check each chunk's metadata before decoding it,
+/// interleave decoding with filtering, and stop early within a chunk.
+/// Chunks are independent of each other, so they can be decoded in any
+/// order, or not at all.
+/// ```
+/// use std::io::Write;
+/// use q_compress::{Compressor, CompressorConfig, Decompressor};
+///
+/// let nums = (0..5000_i64).collect::<Vec<_>>();
+/// let config = CompressorConfig::default().with_use_min_max(true);
+/// let bytes = Compressor::<i64>::from_config(config).simple_compress(&nums);
+/// let mut decompressor = Decompressor::<i64>::default();
+/// decompressor.write_all(&bytes).unwrap();
+/// for mut chunk in decompressor.chunks().expect("chunks") {
+///   // find the first number at least 1234 without decoding the rest
+///   while let Some(block) = chunk.next_block().expect("block") {
+///     if let Some(&x) = block.iter().find(|&&x| x >= 1234) {
+///       assert_eq!(x, 1234);
+///       break;
+///     }
+///   }
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ChunkHandle<'a, T: NumberLike> {
+  // its bit_idx is the position of the rest of the chunk body
+  start: ChunkStart<T>,
+  words: &'a BitWords,
+  freed_bits: usize,
+}
+
+// Decodes up to `limit` numbers of the chunk body from the start's position,
+// advancing it.
+pub(crate) fn decompress_chunk_body<T: NumberLike>(
+  start: &mut ChunkStart<T>,
+  words: &BitWords,
+  freed_bits: usize,
+  limit: usize,
+) -> QCompressResult<Vec<T>> {
+  let mut reader = BitReader::from(words);
+  reader.seek_to(start.bit_idx);
+  let res = start.body_decompressor.decompress_next_batch(&mut reader, limit, true);
+  match res {
+    Ok(numbers) => {
+      start.bit_idx = reader.bit_idx();
+      Ok(numbers.nums)
+    },
+    Err(e) => Err(e.at(ErrorPosition {
+      section: Section::ChunkBody,
+      chunk_idx: Some(start.chunk_idx),
+      bit_idx: freed_bits + reader.bit_idx(),
+    })),
+  }
+}
+
+impl<'a, T: NumberLike> ChunkHandle<'a, T> {
+  pub(crate) fn new(start: ChunkStart<T>, words: &'a BitWords, freed_bits: usize) -> Self {
+    Self {
+      start,
+      words,
+      freed_bits,
+    }
+  }
+
+  /// Returns the chunk's metadata, which is available without decoding any
+  /// of its body.
+  pub fn metadata(&self) -> &ChunkMetadata<T> {
+    &self.start.metadata
+  }
+
+  /// Returns the index of this chunk within its file.
+  pub fn chunk_idx(&self) -> usize {
+    self.start.chunk_idx
+  }
+
+  /// Returns the number of numbers in the chunk that have not been decoded
+  /// yet.
+  pub fn n_remaining(&self) -> usize {
+    self.start.body_decompressor.n_remaining()
+  }
+
+  /// Decodes and returns the next block of up to about 1000 numbers, or
+  /// `None` once the whole chunk body has been decoded.
+  /// Will return an error if the chunk body is corrupt.
+  pub fn next_block(&mut self) -> QCompressResult<Option<Vec<T>>> {
+    if self.n_remaining() == 0 {
+      return Ok(None);
+    }
+    self.decompress(BLOCK_SIZE).map(Some)
+  }
+
+  /// Decodes and returns all the numbers in the chunk that have not been
+  /// decoded yet.
+  /// Will return an error if the chunk body is corrupt.
+  pub fn decompress_rest(&mut self) -> QCompressResult<Vec<T>> {
+    self.decompress(usize::MAX)
+  }
+
+  fn decompress(&mut self, limit: usize) -> QCompressResult<Vec<T>> {
+    decompress_chunk_body(&mut self.start, self.words, self.freed_bits, limit)
+  }
+}
+
+impl<'a, T: NumberLike> Iterator for ChunkHandle<'a, T> {
+  type Item = QCompressResult<Vec<T>>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.next_block().transpose()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{Compressor, CompressorConfig, Decompressor};
+  use crate::errors::ErrorKind;
+
+  fn decompressor_for(
+    nums: &[i32],
+    chunk_size: usize,
+    config: CompressorConfig,
+  ) -> Decompressor<i32> {
+    let mut compressor = Compressor::<i32>::from_config(config);
+    compressor.header().unwrap();
+    for chunk in nums.chunks(chunk_size) {
+      compressor.chunk(chunk).unwrap();
+    }
+    compressor.footer().unwrap();
+    let mut decompressor = Decompressor::<i32>::default();
+    decompressor.extend_bytes(&compressor.drain_bytes());
+    decompressor
+  }
+
+  #[test]
+  fn test_chunk_handles_decode_in_blocks() {
+    let nums = (0..7000).map(|i| (i * 37) % 1001).collect::<Vec<_>>();
+    for delta_order in [0, 2] {
+      let config = CompressorConfig::default().with_delta_encoding_order(delta_order);
+      let mut decompressor = decompressor_for(&nums, 3000, config);
+      let chunks = decompressor.chunks().unwrap();
+      assert_eq!(
+        chunks.iter().map(|chunk| chunk.metadata().n).collect::<Vec<_>>(),
+        vec![3000, 3000, 1000],
+      );
+
+      // decode the chunks out of order and in blocks
+      let mut chunks = chunks;
+      let last = chunks.pop().unwrap();
+      let mut recovered = Vec::new();
+      for mut chunk in chunks {
+        let first_block = chunk.next_block().unwrap().unwrap();
+        assert!(first_block.len() < chunk.metadata().n);
+        assert_eq!(chunk.n_remaining(), chunk.metadata().n - first_block.len());
+        recovered.extend(first_block);
+        for block in chunk {
+          recovered.extend(block.unwrap());
+        }
+      }
+      recovered.extend(last.collect::<Result<Vec<_>, _>>().unwrap().concat());
+      assert_eq!(recovered, nums);
+    }
+  }
+
+  #[test]
+  fn test_chunk_handle_stops_early() {
+    let nums = (0..5000).collect::<Vec<_>>();
+    let mut decompressor = decompressor_for(&nums, 10000, CompressorConfig::default());
+    let mut chunk = decompressor.chunks().unwrap().remove(0);
+    let block = chunk.next_block().unwrap().unwrap();
+    assert_eq!(block, nums[..block.len()]);
+    assert_eq!(chunk.decompress_rest().unwrap(), nums[block.len()..]);
+    assert!(chunk.next_block().unwrap().is_none());
+  }
+
+  #[test]
+  fn test_chunk_handles_errors() {
+    let nums = (0..100).collect::<Vec<_>>();
+    let bytes = Compressor::<i32>::default().simple_compress(&nums);
+    let mut decompressor = Decompressor::<i32>::default();
+    decompressor.extend_bytes(&bytes[..bytes.len() - 1]);
+    let err = decompressor.chunks().unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InsufficientData));
+
+    let mut decompressor = decompressor_for(&nums, 10000, CompressorConfig::default());
+    decompressor.header().unwrap();
+    decompressor.chunk_metadata().unwrap();
+    let err = decompressor.chunks().unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+  }
+}
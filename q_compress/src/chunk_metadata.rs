@@ -1,6 +1,10 @@
-use std::marker::PhantomData;
+use alloc::format;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::mem::size_of;
 use crate::bit_reader::BitReader;
-use crate::{Flags, gcd_utils};
+use crate::bit_words::BitWords;
+use crate::{AffineTransform, DistinctCount, Flags, gcd_utils, NanPositions, Quantization, user_metadata, UserMetadata};
 use crate::bit_writer::BitWriter;
 use crate::constants::*;
 use crate::delta_encoding::DeltaMoments;
@@ -8,10 +12,17 @@ use crate::prefix::Prefix;
 use crate::data_types::{NumberLike, UnsignedLike};
 use crate::errors::{QCompressResult, QCompressError};
 
-/// A wrapper for prefixes in the two cases cases: delta encoded or not.
+/// A wrapper for prefixes in the four cases: delta encoded, XOR encoded,
+/// float split, or none of those, or for the bit width of a raw chunk that
+/// has no prefixes.
 /// 
 /// This is the part of chunk metadata that describes *how* the data was
 /// compressed - the Huffman codes used and what ranges they specify.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(
+  serialize = "T: serde::Serialize, T::Signed: serde::Serialize, T::Unsigned: serde::Serialize",
+  deserialize = "T: serde::Deserialize<'de>, T::Signed: serde::Deserialize<'de>, T::Unsigned: serde::Deserialize<'de>",
+)))]
 #[derive(Clone, Debug, PartialEq)]
 pub enum PrefixMetadata<T: NumberLike> {
   /// `Simple` prefix metadata corresponds to the case when delta encoding is
@@ -22,7 +33,8 @@ pub enum PrefixMetadata<T: NumberLike> {
     prefixes: Vec<Prefix<T>>,
   },
   /// `Delta` prefix metadata corresponds to the case when delta encoding is
-  /// on.
+  /// on, either for the whole file or, with the `use_chunk_delta_orders`
+  /// flag, for this chunk.
   ///
   /// It contains prefixes of the associated `SignedLike` type (what the
   /// deltas are expressed as). For instance, a chunk of delta-encoded `f64`s
@@ -35,7 +47,46 @@ pub enum PrefixMetadata<T: NumberLike> {
   Delta {
     prefixes: Vec<Prefix<T::Signed>>,
     delta_moments: DeltaMoments<T>,
-  }
+  },
+  /// `Xor` prefix metadata corresponds to chunks the compressor chose to
+  /// XOR encode, which can only happen when the `use_xor_encoding` flag is
+  /// on.
+  ///
+  /// It contains the first number of the chunk and prefixes of the
+  /// associated `SignedLike` type.
+  /// Each later number is the XOR of the previous number's unsigned
+  /// representation and the unsigned representation of the next value
+  /// decoded from these prefixes.
+  Xor {
+    prefixes: Vec<Prefix<T::Signed>>,
+    first: T,
+  },
+  /// `Raw` prefix metadata corresponds to chunks the compressor chose to
+  /// store without prefixes, which can only happen when the
+  /// `use_raw_fallback` flag is on.
+  ///
+  /// Each number's unsigned representation minus that of `lower` is
+  /// written in exactly `bit_width` bits.
+  Raw {
+    lower: T,
+    bit_width: usize,
+  },
+  /// `FloatSplit` prefix metadata corresponds to chunks of floats the
+  /// compressor chose to split, which can only happen when the
+  /// `use_float_split` flag is on.
+  ///
+  /// Each number's unsigned representation is split into its high bits,
+  /// holding the float's sign and exponent, and its low mantissa bits.
+  /// The chunk body contains all the high bits, compressed with
+  /// `exponent_prefixes` and taking `exponent_body_size` bytes, followed by
+  /// all the mantissas, compressed with `mantissa_prefixes`.
+  /// Like those of `Xor`, both sets of prefixes are of the associated
+  /// `SignedLike` type.
+  FloatSplit {
+    exponent_prefixes: Vec<Prefix<T::Signed>>,
+    mantissa_prefixes: Vec<Prefix<T::Signed>>,
+    exponent_body_size: usize,
+  },
 }
 
 /// The metadata of a .qco file chunk.
@@ -45,6 +96,11 @@ pub enum PrefixMetadata<T: NumberLike> {
 /// be done easily - see the fast_seeking.rs example.
 /// One can also create a rough histogram (or a histogram of deltas, if
 /// delta encoding was used) by aggregating chunk metadata.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(
+  serialize = "T: serde::Serialize, T::Signed: serde::Serialize, T::Unsigned: serde::Serialize",
+  deserialize = "T: serde::Deserialize<'de>, T::Signed: serde::Deserialize<'de>, T::Unsigned: serde::Deserialize<'de>",
+)))]
 #[derive(Clone, Debug, PartialEq)]
 pub struct ChunkMetadata<T> where T: NumberLike {
   /// The count of numbers in the chunk.
@@ -54,16 +110,169 @@ pub struct ChunkMetadata<T> where T: NumberLike {
   pub compressed_body_size: usize,
   /// *How* the chunk body was compressed.
   pub prefix_metadata: PrefixMetadata<T>,
+  /// The power of ten each float in the chunk was multiplied by to get an
+  /// integer, if the chunk used a decimal multiplier.
+  /// When present, the prefixes and delta moments describe floats whose
+  /// bits are those integers, rather than the original floats.
+  /// This is only ever present when the `use_decimal_multipliers` flag is
+  /// on.
+  pub decimal_exponent: Option<u32>,
+  /// How the chunk's numbers were rounded, if the chunk was compressed
+  /// lossily.
+  /// When present, the prefixes and delta moments describe numbers whose
+  /// signed representations are the rounded integers, and the chunk has no
+  /// decimal exponent.
+  /// This is only ever present when the `use_quantization` flag is on.
+  pub quantization: Option<Quantization>,
+  /// The exact minimum and maximum of the chunk's numbers, if the
+  /// compressor stored them.
+  /// Numbers are ordered by their unsigned representations, which for
+  /// floats is numerical order with negative NaNs first and positive NaNs
+  /// last.
+  /// For lossily compressed chunks, these are the minimum and maximum of
+  /// the numbers decompression returns.
+  /// This is only ever present when the `use_min_max` flag is on.
+  pub min_max: Option<(T, T)>,
+  /// The number of distinct numbers in the chunk, if the compressor stored
+  /// it.
+  /// For lossily compressed chunks, this counts the distinct numbers
+  /// decompression returns.
+  /// This is only ever present when the `use_distinct_count` flag is on.
+  pub distinct_count: Option<DistinctCount>,
+  /// Where the chunk's NaNs were, if the compressor stripped them out of
+  /// the chunk body.
+  /// In that case, `n` counts the NaNs, but the prefixes and delta moments
+  /// describe only the chunk's other numbers, in order.
+  /// The minimum, maximum, and distinct count still include the NaNs.
+  /// This is only ever present when the `use_nan_positions` flag is on.
+  pub nan_positions: Option<NanPositions<T>>,
+  /// The spacing and offset the chunk's numbers were written as multiples
+  /// of, if the compressor found one.
+  /// When present, the prefixes and delta moments describe numbers whose
+  /// signed representations are the multiples.
+  /// For chunks with a decimal exponent or quantization, it applies to
+  /// their integers.
+  /// This is only ever present when the `use_affine_transforms` flag is on.
+  pub affine_transform: Option<AffineTransform<T>>,
+  /// Whether the chunk's prefixes are the file's shared prefix table
+  /// rather than its own, in which case they were not written in the chunk
+  /// metadata and their counts describe the sample they were trained on.
+  /// This is only ever true when the `use_shared_prefixes` flag is on.
+  pub uses_shared_prefixes: bool,
+  /// Application-defined key-value pairs written with the chunk.
+  /// This is only ever nonempty when the `use_user_metadata` flag is on.
+  pub user_metadata: UserMetadata,
   // Make it API-stable to add more fields in the future
   pub(crate) phantom: PhantomData<()>,
 }
 
+// The prefix table written once after the header when the
+// `use_shared_prefixes` flag is on, for chunks to use instead of their own.
+// Like chunk prefixes, they describe deltas if the file's delta encoding
+// order is nonzero.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum SharedPrefixes<T: NumberLike> {
+  Simple(Vec<Prefix<T>>),
+  Delta(Vec<Prefix<T::Signed>>),
+}
+
+// Limits on what parsing chunk metadata may claim, so that a small untrusted
+// file can't make a decompressor allocate more than its config allows.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct MemoryLimits {
+  pub max_n_per_chunk: usize,
+  // bytes still available for the numbers and prefixes of chunks
+  pub remaining_memory: usize,
+}
+
+impl Default for MemoryLimits {
+  fn default() -> Self {
+    Self {
+      max_n_per_chunk: MAX_ENTRIES,
+      remaining_memory: usize::MAX,
+    }
+  }
+}
+
+impl MemoryLimits {
+  fn check_n(&self, n: usize) -> QCompressResult<()> {
+    if n > self.max_n_per_chunk {
+      return Err(QCompressError::invalid_argument(format!(
+        "chunk with {} numbers exceeds max_n_per_chunk of {}",
+        n,
+        self.max_n_per_chunk,
+      )));
+    }
+    Ok(())
+  }
+
+  pub(crate) fn reserve(&mut self, n_bytes: usize, description: &str) -> QCompressResult<()> {
+    if n_bytes > self.remaining_memory {
+      return Err(QCompressError::invalid_argument(format!(
+        "{} would take {} bytes, but only {} remain within max_total_memory",
+        description,
+        n_bytes,
+        self.remaining_memory,
+      )));
+    }
+    self.remaining_memory -= n_bytes;
+    Ok(())
+  }
+}
+
+fn total_count<T: NumberLike>(prefixes: &[Prefix<T>]) -> usize {
+  prefixes.iter().map(|p| p.count).sum()
+}
+
+impl<T: NumberLike> SharedPrefixes<T> {
+  pub(crate) fn parse_from(reader: &mut BitReader, flags: &Flags) -> QCompressResult<Self> {
+    let n = reader.read_usize(BITS_TO_ENCODE_N_ENTRIES)?;
+    let res = if flags.delta_encoding_order == 0 {
+      SharedPrefixes::Simple(parse_prefixes::<T>(reader, flags, n, &mut MemoryLimits::default())?)
+    } else {
+      SharedPrefixes::Delta(parse_prefixes::<T::Signed>(reader, flags, n, &mut MemoryLimits::default())?)
+    };
+    reader.drain_empty_byte(|| QCompressError::corruption(
+      "nonzero bits in end of final byte of shared prefixes"
+    ))?;
+    Ok(res)
+  }
+
+  pub(crate) fn write_to(&self, writer: &mut BitWriter, flags: &Flags) {
+    match self {
+      SharedPrefixes::Simple(prefixes) => {
+        let n = total_count(prefixes);
+        writer.write_usize(n, BITS_TO_ENCODE_N_ENTRIES);
+        write_prefixes(prefixes, writer, flags, n);
+      },
+      SharedPrefixes::Delta(prefixes) => {
+        let n = total_count(prefixes);
+        writer.write_usize(n, BITS_TO_ENCODE_N_ENTRIES);
+        write_prefixes(prefixes, writer, flags, n);
+      },
+    }
+    writer.finish_byte();
+  }
+}
+
+// the largest offset from the lower bound a raw number of this bit width
+// can have
+pub(crate) fn raw_max_offset<U: UnsignedLike>(bit_width: usize) -> U {
+  if bit_width == 0 {
+    U::ZERO
+  } else {
+    U::MAX >> (U::BITS - bit_width)
+  }
+}
+
 fn parse_prefixes<T: NumberLike>(
   reader: &mut BitReader,
   flags: &Flags,
   n: usize,
+  limits: &mut MemoryLimits,
 ) -> QCompressResult<Vec<Prefix<T>>> {
   let n_pref = reader.read_usize(BITS_TO_ENCODE_N_PREFIXES)?;
+  limits.reserve(n_pref * size_of::<Prefix<T>>(), "prefixes")?;
   let mut prefixes = Vec::with_capacity(n_pref);
   let bits_to_encode_code_len = flags.bits_to_encode_code_len();
   let bits_to_encode_count = flags.bits_to_encode_count(n);
@@ -155,27 +364,233 @@ fn write_prefixes<T: NumberLike>(
 }
 
 impl<T> ChunkMetadata<T> where T: NumberLike {
+  /// Creates chunk metadata for `n` numbers whose chunk body takes
+  /// `compressed_body_size` bytes and was compressed with
+  /// `prefix_metadata`.
+  /// It has no decimal exponent, quantization, min and max, shared
+  /// prefixes, or user metadata, but since all fields are public, those can
+  /// be set afterward.
+  ///
+  /// Together with [`Prefix::new`] and [`to_bytes`][Self::to_bytes], this
+  /// lets tools write chunk metadata without a
+  /// [`Compressor`][crate::Compressor].
+  pub fn new(n: usize, compressed_body_size: usize, prefix_metadata: PrefixMetadata<T>) -> Self {
+    ChunkMetadata {
+      n,
+      compressed_body_size,
+      prefix_metadata,
+      decimal_exponent: None,
+      quantization: None,
+      min_max: None,
+      distinct_count: None,
+      nan_positions: None,
+      affine_transform: None,
+      uses_shared_prefixes: false,
+      user_metadata: UserMetadata::new(),
+      phantom: PhantomData,
+    }
+  }
+
+  /// Returns the bytes of this chunk metadata as they would appear in a
+  /// .qco file written with the given flags, right after the magic chunk
+  /// byte and right before the chunk body.
+  ///
+  /// The flags determine which optional fields get written, so fields the
+  /// flags don't allow for (e.g. a decimal exponent without
+  /// `use_decimal_multipliers`) are silently dropped.
+  pub fn to_bytes(&self, flags: &Flags) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    self.write_to(&mut writer, flags);
+    writer.drain_bytes()
+  }
+
+  /// Parses chunk metadata from the start of `bytes`, as written by
+  /// [`to_bytes`][Self::to_bytes] with the same flags.
+  /// Any bytes after the metadata are ignored.
+  /// Will return an error if the bytes are corrupt or end too soon, or if
+  /// the chunk uses shared prefixes, which aren't available here.
+  pub fn from_bytes(bytes: &[u8], flags: &Flags) -> QCompressResult<Self> {
+    let words = BitWords::from(bytes);
+    let mut reader = BitReader::from(&words);
+    Self::parse_from(&mut reader, flags)
+  }
+
   pub fn parse_from(reader: &mut BitReader, flags: &Flags) -> QCompressResult<Self> {
+    Self::parse_with_shared_prefixes(reader, flags, None, &mut MemoryLimits::default())
+  }
+
+  pub(crate) fn parse_with_shared_prefixes(
+    reader: &mut BitReader,
+    flags: &Flags,
+    shared_prefixes: Option<&SharedPrefixes<T>>,
+    limits: &mut MemoryLimits,
+  ) -> QCompressResult<Self> {
     let n = reader.read_usize(BITS_TO_ENCODE_N_ENTRIES)?;
+    limits.check_n(n)?;
+    limits.reserve(n * size_of::<T>(), "chunk numbers")?;
     let compressed_body_size = reader.read_usize(BITS_TO_ENCODE_COMPRESSED_BODY_SIZE)?;
-    let prefix_metadata = if flags.delta_encoding_order == 0 {
-      let prefixes = parse_prefixes::<T>(reader, flags, n)?;
-      PrefixMetadata::Simple {
-        prefixes,
+    let decimal_exponent = if flags.use_decimal_multipliers && reader.read_one()? {
+      let exponent = reader.read_usize(BITS_TO_ENCODE_DECIMAL_EXPONENT)? as u32;
+      if !matches!(T::MAX_DECIMAL_EXPONENT, Some(max_exponent) if exponent <= max_exponent) {
+        return Err(QCompressError::corruption(format!(
+          "invalid decimal exponent {} for data type with header byte {}",
+          exponent,
+          T::HEADER_BYTE,
+        )));
       }
+      Some(exponent)
+    } else {
+      None
+    };
+    let quantization = if flags.use_quantization && reader.read_one()? {
+      Some(Quantization::parse_from::<T>(reader)?)
+    } else {
+      None
+    };
+    let min_max = if flags.use_min_max && reader.read_one()? {
+      let min = T::read_from(reader)?;
+      let max = T::read_from(reader)?;
+      if min.to_unsigned() > max.to_unsigned() {
+        return Err(QCompressError::corruption(format!(
+          "chunk min {} exceeds max {}",
+          min,
+          max,
+        )));
+      }
+      Some((min, max))
+    } else {
+      None
+    };
+    let distinct_count = if flags.use_distinct_count && reader.read_one()? {
+      let is_exact = reader.read_one()?;
+      let count = reader.read_usize(BITS_TO_ENCODE_N_ENTRIES)?;
+      if count > n || (count == 0 && n > 0) {
+        return Err(QCompressError::corruption(format!(
+          "chunk distinct count {} is invalid for {} numbers",
+          count,
+          n,
+        )));
+      }
+      Some(DistinctCount { count, is_exact })
+    } else {
+      None
+    };
+    let nan_positions = if flags.use_nan_positions && reader.read_one()? {
+      Some(NanPositions::parse_from(reader, n, limits)?)
+    } else {
+      None
+    };
+    let affine_transform = if flags.use_affine_transforms && reader.read_one()? {
+      Some(AffineTransform::parse_from(reader)?)
     } else {
-      let delta_moments = DeltaMoments::<T>::parse_from(reader, flags.delta_encoding_order)?;
-      let prefixes = parse_prefixes::<T::Signed>(reader, flags, n)?;
-      PrefixMetadata::Delta {
+      None
+    };
+    let mut uses_shared_prefixes = false;
+    let prefix_metadata = if flags.use_raw_fallback && reader.read_one()? {
+      let lower = T::read_from(reader)?;
+      let bit_width = reader.read_usize(BITS_TO_ENCODE_RAW_BIT_WIDTH)?;
+      if bit_width > T::Unsigned::BITS ||
+        T::Unsigned::MAX - lower.to_unsigned() < raw_max_offset::<T::Unsigned>(bit_width) {
+        return Err(QCompressError::corruption(format!(
+          "invalid raw bit width {} for lower bound {}",
+          bit_width,
+          lower,
+        )));
+      }
+      PrefixMetadata::Raw {
+        lower,
+        bit_width,
+      }
+    } else if flags.use_xor_encoding && reader.read_one()? {
+      let first = T::read_from(reader)?;
+      let prefixes = parse_prefixes::<T::Signed>(reader, flags, n, limits)?;
+      PrefixMetadata::Xor {
         prefixes,
-        delta_moments,
+        first,
+      }
+    } else if flags.use_float_split && reader.read_one()? {
+      if T::MANTISSA_BITS.is_none() {
+        return Err(QCompressError::corruption(format!(
+          "float split chunk for data type with header byte {}",
+          T::HEADER_BYTE,
+        )));
+      }
+      let exponent_body_size = reader.read_usize(BITS_TO_ENCODE_COMPRESSED_BODY_SIZE)?;
+      if exponent_body_size > compressed_body_size {
+        return Err(QCompressError::corruption(format!(
+          "exponent body size {} exceeds compressed body size {}",
+          exponent_body_size,
+          compressed_body_size,
+        )));
+      }
+      let exponent_prefixes = parse_prefixes::<T::Signed>(reader, flags, n, limits)?;
+      let mantissa_prefixes = parse_prefixes::<T::Signed>(reader, flags, n, limits)?;
+      PrefixMetadata::FloatSplit {
+        exponent_prefixes,
+        mantissa_prefixes,
+        exponent_body_size,
+      }
+    } else {
+      let order = if flags.use_chunk_delta_orders {
+        reader.read_usize(BITS_TO_ENCODE_DELTA_ENCODING_ORDER)?
+      } else {
+        flags.delta_encoding_order
+      };
+      if order == 0 {
+        uses_shared_prefixes = flags.use_shared_prefixes && reader.read_one()?;
+        let prefixes = if uses_shared_prefixes {
+          match shared_prefixes {
+            Some(SharedPrefixes::Simple(prefixes)) => prefixes.clone(),
+            _ => return Err(QCompressError::corruption(
+              "chunk uses shared prefixes, but there are none without delta encoding"
+            )),
+          }
+        } else {
+          parse_prefixes::<T>(reader, flags, n, limits)?
+        };
+        PrefixMetadata::Simple {
+          prefixes,
+        }
+      } else {
+        let delta_moments = DeltaMoments::<T>::parse_from(reader, order, flags.delta_lag)?;
+        uses_shared_prefixes = flags.use_shared_prefixes && reader.read_one()?;
+        let prefixes = if uses_shared_prefixes {
+          match shared_prefixes {
+            Some(SharedPrefixes::Delta(prefixes)) if order == flags.delta_encoding_order =>
+              prefixes.clone(),
+            _ => return Err(QCompressError::corruption(format!(
+              "chunk uses shared prefixes, but there are none for delta encoding order {}",
+              order,
+            ))),
+          }
+        } else {
+          parse_prefixes::<T::Signed>(reader, flags, n, limits)?
+        };
+        PrefixMetadata::Delta {
+          prefixes,
+          delta_moments,
+        }
       }
     };
 
+    let user_metadata = if flags.use_user_metadata {
+      user_metadata::parse_from(reader)?
+    } else {
+      UserMetadata::new()
+    };
+
     Ok(Self {
       n,
       compressed_body_size,
       prefix_metadata,
+      decimal_exponent,
+      quantization,
+      min_max,
+      distinct_count,
+      nan_positions,
+      affine_transform,
+      uses_shared_prefixes,
+      user_metadata,
       phantom: PhantomData,
     })
   }
@@ -183,18 +598,104 @@ impl<T> ChunkMetadata<T> where T: NumberLike {
   pub fn write_to(&self, writer: &mut BitWriter, flags: &Flags) {
     writer.write_usize(self.n, BITS_TO_ENCODE_N_ENTRIES);
     writer.write_usize(self.compressed_body_size, BITS_TO_ENCODE_COMPRESSED_BODY_SIZE);
+    if flags.use_decimal_multipliers {
+      writer.write_one(self.decimal_exponent.is_some());
+      if let Some(exponent) = self.decimal_exponent {
+        writer.write_usize(exponent as usize, BITS_TO_ENCODE_DECIMAL_EXPONENT);
+      }
+    }
+    if flags.use_quantization {
+      writer.write_one(self.quantization.is_some());
+      if let Some(quantization) = &self.quantization {
+        quantization.write_to(writer);
+      }
+    }
+    if flags.use_min_max {
+      writer.write_one(self.min_max.is_some());
+      if let Some((min, max)) = self.min_max {
+        min.write_to(writer);
+        max.write_to(writer);
+      }
+    }
+    if flags.use_distinct_count {
+      writer.write_one(self.distinct_count.is_some());
+      if let Some(distinct_count) = self.distinct_count {
+        writer.write_one(distinct_count.is_exact);
+        writer.write_usize(distinct_count.count, BITS_TO_ENCODE_N_ENTRIES);
+      }
+    }
+    if flags.use_nan_positions {
+      writer.write_one(self.nan_positions.is_some());
+      if let Some(nan_positions) = &self.nan_positions {
+        nan_positions.write_to(writer, self.n);
+      }
+    }
+    if flags.use_affine_transforms {
+      writer.write_one(self.affine_transform.is_some());
+      if let Some(affine_transform) = &self.affine_transform {
+        affine_transform.write_to(writer);
+      }
+    }
+    if flags.use_raw_fallback {
+      writer.write_one(matches!(self.prefix_metadata, PrefixMetadata::Raw { .. }));
+    }
+    if flags.use_xor_encoding && !matches!(self.prefix_metadata, PrefixMetadata::Raw { .. }) {
+      writer.write_one(matches!(self.prefix_metadata, PrefixMetadata::Xor { .. }));
+    }
+    if flags.use_float_split && !matches!(
+      self.prefix_metadata,
+      PrefixMetadata::Raw { .. } | PrefixMetadata::Xor { .. }
+    ) {
+      writer.write_one(matches!(self.prefix_metadata, PrefixMetadata::FloatSplit { .. }));
+    }
     match &self.prefix_metadata {
       PrefixMetadata::Simple { prefixes} => {
-        write_prefixes(prefixes, writer, flags, self.n);
+        if flags.use_chunk_delta_orders {
+          writer.write_usize(0, BITS_TO_ENCODE_DELTA_ENCODING_ORDER);
+        }
+        self.write_prefixes_unless_shared(prefixes, writer, flags);
       },
       PrefixMetadata::Delta { prefixes, delta_moments } => {
+        if flags.use_chunk_delta_orders {
+          writer.write_usize(delta_moments.order(), BITS_TO_ENCODE_DELTA_ENCODING_ORDER);
+        }
         delta_moments.write_to(writer);
+        self.write_prefixes_unless_shared(prefixes, writer, flags);
+      },
+      PrefixMetadata::Xor { prefixes, first } => {
+        first.write_to(writer);
         write_prefixes(prefixes, writer, flags, self.n);
       },
+      PrefixMetadata::Raw { lower, bit_width } => {
+        lower.write_to(writer);
+        writer.write_usize(*bit_width, BITS_TO_ENCODE_RAW_BIT_WIDTH);
+      },
+      PrefixMetadata::FloatSplit { exponent_prefixes, mantissa_prefixes, exponent_body_size } => {
+        writer.write_usize(*exponent_body_size, BITS_TO_ENCODE_COMPRESSED_BODY_SIZE);
+        write_prefixes(exponent_prefixes, writer, flags, self.n);
+        write_prefixes(mantissa_prefixes, writer, flags, self.n);
+      },
+    }
+    if flags.use_user_metadata {
+      user_metadata::write_to(&self.user_metadata, writer);
     }
     writer.finish_byte();
   }
 
+  fn write_prefixes_unless_shared<P: NumberLike>(
+    &self,
+    prefixes: &[Prefix<P>],
+    writer: &mut BitWriter,
+    flags: &Flags,
+  ) {
+    if flags.use_shared_prefixes {
+      writer.write_one(self.uses_shared_prefixes);
+    }
+    if !self.uses_shared_prefixes {
+      write_prefixes(prefixes, writer, flags, self.n);
+    }
+  }
+
   pub(crate) fn update_write_compressed_body_size(
     &self,
     writer: &mut BitWriter,
@@ -0,0 +1,266 @@
+//! Tools for writing .qco files that older versions of `q_compress` can read.
+//!
+//! New features of the format are always gated behind [`Flags`], so every
+//! version of `q_compress` can decompress files written by every older
+//! version.
+//! The reverse is not true: a file using a newer flag causes older versions
+//! to return a compatibility error.
+//! When readers may lag behind writers, use
+//! [`max_writable_version`] to check which readers a
+//! [`CompressorConfig`] requires, or [`config_for_version`] to restrict a
+//! config to what a given version can read.
+
+use alloc::format;
+use core::fmt::{Display, Formatter};
+
+use crate::{CompressorConfig, Flags};
+use crate::errors::{QCompressError, QCompressResult};
+
+/// A `q_compress` version, used to describe format compatibility.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+  pub major: u32,
+  pub minor: u32,
+  pub patch: u32,
+}
+
+impl Version {
+  /// Creates a version from its major, minor, and patch numbers.
+  pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+    Version { major, minor, patch }
+  }
+}
+
+impl Display for Version {
+  fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+    write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+  }
+}
+
+/// The version that introduced the .qco format.
+pub const INITIAL_VERSION: Version = Version::new(0, 1, 0);
+/// The version that introduced `use_5_bit_code_len`.
+pub const CODE_LEN_5_BIT_VERSION: Version = Version::new(0, 5, 0);
+/// The version that introduced delta encoding.
+pub const DELTA_ENCODING_VERSION: Version = Version::new(0, 6, 0);
+/// The version that introduced `use_min_count_encoding`.
+pub const MIN_COUNT_ENCODING_VERSION: Version = Version::new(0, 9, 1);
+/// The version that introduced `use_gcds`.
+pub const GCDS_VERSION: Version = Version::new(0, 10, 0);
+/// The version that introduced `use_chunk_checksums` and
+/// `use_file_checksum`.
+pub const CHECKSUMS_VERSION: Version = Version::new(0, 11, 2);
+/// The version that introduced `use_decimal_multipliers`.
+pub const DECIMAL_MULTIPLIERS_VERSION: Version = Version::new(0, 11, 2);
+/// The version that introduced `use_xor_encoding`.
+pub const XOR_ENCODING_VERSION: Version = Version::new(0, 11, 2);
+/// The version that introduced `use_chunk_delta_orders`.
+pub const CHUNK_DELTA_ORDERS_VERSION: Version = Version::new(0, 11, 2);
+/// The version that introduced `delta_lag`.
+pub const DELTA_LAG_VERSION: Version = Version::new(0, 11, 2);
+/// The version that introduced `use_quantization`.
+pub const QUANTIZATION_VERSION: Version = Version::new(0, 11, 2);
+/// The version that introduced `use_raw_fallback`.
+pub const RAW_FALLBACK_VERSION: Version = Version::new(0, 11, 2);
+/// The version that introduced `use_shared_prefixes`.
+pub const SHARED_PREFIXES_VERSION: Version = Version::new(0, 11, 2);
+/// The version that introduced `use_user_metadata`.
+pub const USER_METADATA_VERSION: Version = Version::new(0, 11, 2);
+/// The version that introduced `use_float_split`.
+pub const FLOAT_SPLIT_VERSION: Version = Version::new(0, 11, 2);
+/// The version that introduced `use_min_max`.
+pub const MIN_MAX_VERSION: Version = Version::new(0, 11, 2);
+/// The version that introduced `use_distinct_count`.
+pub const DISTINCT_COUNT_VERSION: Version = Version::new(0, 11, 2);
+/// The version that introduced `use_nan_positions`.
+pub const NAN_POSITIONS_VERSION: Version = Version::new(0, 11, 2);
+/// The version that introduced `canonicalize_negative_zero` and
+/// `flush_subnormals`.
+pub const FLOAT_CANONICALIZATION_VERSION: Version = Version::new(0, 11, 2);
+/// The version that introduced `use_affine_transforms`.
+pub const AFFINE_TRANSFORMS_VERSION: Version = Version::new(0, 11, 2);
+
+/// Returns the oldest version of `q_compress` able to decompress files
+/// written with these flags.
+pub fn min_reader_version(flags: &Flags) -> Version {
+  let mut res = INITIAL_VERSION;
+  let mut require = |used: bool, version: Version| {
+    if used && version > res {
+      res = version;
+    }
+  };
+  require(flags.use_5_bit_code_len, CODE_LEN_5_BIT_VERSION);
+  require(flags.delta_encoding_order > 0, DELTA_ENCODING_VERSION);
+  require(flags.use_min_count_encoding, MIN_COUNT_ENCODING_VERSION);
+  require(flags.use_gcds, GCDS_VERSION);
+  require(flags.use_chunk_checksums || flags.use_file_checksum, CHECKSUMS_VERSION);
+  require(flags.use_decimal_multipliers, DECIMAL_MULTIPLIERS_VERSION);
+  require(flags.use_xor_encoding, XOR_ENCODING_VERSION);
+  require(flags.use_chunk_delta_orders, CHUNK_DELTA_ORDERS_VERSION);
+  require(flags.delta_lag > 1, DELTA_LAG_VERSION);
+  require(flags.use_quantization, QUANTIZATION_VERSION);
+  require(flags.use_raw_fallback, RAW_FALLBACK_VERSION);
+  require(flags.use_shared_prefixes, SHARED_PREFIXES_VERSION);
+  require(flags.use_user_metadata, USER_METADATA_VERSION);
+  require(flags.use_float_split, FLOAT_SPLIT_VERSION);
+  require(flags.use_min_max, MIN_MAX_VERSION);
+  require(flags.use_distinct_count, DISTINCT_COUNT_VERSION);
+  require(flags.use_nan_positions, NAN_POSITIONS_VERSION);
+  require(
+    flags.canonicalize_negative_zero || flags.flush_subnormals,
+    FLOAT_CANONICALIZATION_VERSION,
+  );
+  require(flags.use_affine_transforms, AFFINE_TRANSFORMS_VERSION);
+  res
+}
+
+/// Returns the newest format version compressing with this config can
+/// write, which is the oldest version of `q_compress` able to decompress the
+/// result.
+pub fn max_writable_version(config: &CompressorConfig) -> Version {
+  min_reader_version(&Flags::from(config))
+}
+
+/// Returns a copy of `config` with any features newer than `version`
+/// turned off, so that `version` can read its output.
+///
+/// Features that only improve compression ratio (like `use_gcds`,
+/// `use_decimal_multipliers`, `use_xor_encoding`, `use_chunk_delta_orders`,
+/// `use_raw_fallback`, `use_float_split_encoding`, `use_nan_positions`, and
+/// `use_affine_transforms`)
+/// or add integrity checks are turned off, as are lossy options like
+/// `tolerance`, `canonicalize_negative_zero`, and `flush_subnormals`.
+/// Will return an error if `config` depends on a feature `version` lacks,
+/// like a nonzero `delta_encoding_order` before 0.6.0 or a `delta_lag`
+/// other than 1, `use_user_metadata`, `use_min_max`, or `use_distinct_count`
+/// before 0.11.2, or if `version` is older than what this version of
+/// `q_compress` can write at all.
+pub fn config_for_version(
+  config: &CompressorConfig,
+  version: Version,
+) -> QCompressResult<CompressorConfig> {
+  // we always use 5 bit code lengths and min count encoding
+  if version < MIN_COUNT_ENCODING_VERSION {
+    return Err(QCompressError::invalid_argument(format!(
+      "cannot write files readable by versions before {} (requested {})",
+      MIN_COUNT_ENCODING_VERSION,
+      version,
+    )));
+  }
+
+  if version < DELTA_LAG_VERSION && config.delta_lag > 1 {
+    return Err(QCompressError::invalid_argument(format!(
+      "cannot write files with delta lag {} readable by versions before {} (requested {})",
+      config.delta_lag,
+      DELTA_LAG_VERSION,
+      version,
+    )));
+  }
+
+  if version < USER_METADATA_VERSION && config.use_user_metadata {
+    return Err(QCompressError::invalid_argument(format!(
+      "cannot write files with user metadata readable by versions before {} (requested {})",
+      USER_METADATA_VERSION,
+      version,
+    )));
+  }
+
+  if version < MIN_MAX_VERSION && config.use_min_max {
+    return Err(QCompressError::invalid_argument(format!(
+      "cannot write files with chunk min and max readable by versions before {} (requested {})",
+      MIN_MAX_VERSION,
+      version,
+    )));
+  }
+
+  if version < DISTINCT_COUNT_VERSION && config.use_distinct_count {
+    return Err(QCompressError::invalid_argument(format!(
+      "cannot write files with chunk distinct counts readable by versions before {} (requested {})",
+      DISTINCT_COUNT_VERSION,
+      version,
+    )));
+  }
+
+  let mut res = config.clone();
+  if version < GCDS_VERSION {
+    res.use_gcds = false;
+  }
+  if version < CHECKSUMS_VERSION {
+    res.use_chunk_checksums = false;
+    res.use_file_checksum = false;
+  }
+  if version < DECIMAL_MULTIPLIERS_VERSION {
+    res.use_decimal_multipliers = false;
+  }
+  if version < XOR_ENCODING_VERSION {
+    res.use_xor_encoding = false;
+  }
+  if version < RAW_FALLBACK_VERSION {
+    res.use_raw_fallback = false;
+  }
+  if version < FLOAT_SPLIT_VERSION {
+    res.use_float_split_encoding = false;
+  }
+  if version < NAN_POSITIONS_VERSION {
+    res.use_nan_positions = false;
+  }
+  if version < AFFINE_TRANSFORMS_VERSION {
+    res.use_affine_transforms = false;
+  }
+  // compressing losslessly only costs compression ratio
+  if version < QUANTIZATION_VERSION {
+    res.tolerance = None;
+  }
+  if version < FLOAT_CANONICALIZATION_VERSION {
+    res.canonicalize_negative_zero = false;
+    res.flush_subnormals = false;
+  }
+  // older versions can still use the file-wide delta_encoding_order
+  if version < CHUNK_DELTA_ORDERS_VERSION {
+    res.use_chunk_delta_orders = false;
+  }
+  Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_max_writable_version() {
+    assert_eq!(
+      max_writable_version(&CompressorConfig::default()),
+      GCDS_VERSION,
+    );
+    assert_eq!(
+      max_writable_version(&CompressorConfig::default().with_use_gcds(false)),
+      MIN_COUNT_ENCODING_VERSION,
+    );
+    assert_eq!(
+      max_writable_version(&CompressorConfig::default().with_use_chunk_checksums(true)),
+      CHECKSUMS_VERSION,
+    );
+  }
+
+  #[test]
+  fn test_config_for_version() -> QCompressResult<()> {
+    let config = CompressorConfig::default()
+      .with_delta_encoding_order(2)
+      .with_use_file_checksum(true);
+    for version in [MIN_COUNT_ENCODING_VERSION, GCDS_VERSION, CHECKSUMS_VERSION] {
+      let restricted = config_for_version(&config, version)?;
+      assert!(max_writable_version(&restricted) <= version);
+      assert_eq!(restricted.delta_encoding_order, 2);
+    }
+    assert!(config_for_version(&config, DELTA_ENCODING_VERSION).is_err());
+    assert!(config_for_version(&config.clone().with_use_min_max(true), GCDS_VERSION).is_err());
+    assert!(config_for_version(&config.clone().with_use_distinct_count(true), GCDS_VERSION).is_err());
+    assert!(config_for_version(&config.with_delta_lag(24), GCDS_VERSION).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_version_display() {
+    assert_eq!(format!("{}", Version::new(0, 11, 2)), "0.11.2");
+  }
+}
@@ -1,3 +1,6 @@
+use alloc::format;
+use alloc::vec::Vec;
+
 use crate::data_types::{NumberLike, UnsignedLike};
 use crate::prefix::PrefixCompressionInfo;
 use crate::errors::{QCompressResult, QCompressError};
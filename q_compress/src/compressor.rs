@@ -1,10 +1,14 @@
-use std::cmp::{max, min};
-use std::fmt::Debug;
-use std::marker::PhantomData;
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::{max, min};
+use core::fmt::Debug;
+use core::marker::PhantomData;
 
-use crate::{Flags, gcd_utils, huffman_encoding};
+use crate::{affine, auto, bits, checksum, chunk_metadata, distinct_count, Flags, float_split, gcd_utils, huffman_encoding, nan_positions, Progress, ProgressUpdate, user_metadata, UserMetadata};
 use crate::bit_writer::BitWriter;
-use crate::chunk_metadata::{ChunkMetadata, PrefixMetadata};
+use crate::checksum::Crc32;
+use crate::chunk_metadata::{ChunkMetadata, PrefixMetadata, SharedPrefixes};
 use crate::compression_table::CompressionTable;
 use crate::constants::*;
 use crate::data_types::{NumberLike, UnsignedLike};
@@ -14,10 +18,17 @@ use crate::errors::{QCompressError, QCompressResult};
 use crate::gcd_utils::{GcdOperator, GeneralGcdOp, TrivialGcdOp};
 use crate::prefix::{Prefix, PrefixCompressionInfo, WeightedPrefix};
 use crate::prefix_optimization;
+use crate::quantization;
+use crate::quantization::{Quantization, Tolerance};
+use crate::float_math;
+use crate::xor_encoding;
 
 const MIN_N_TO_USE_RUN_LEN: usize = 1001;
-const MIN_FREQUENCY_TO_USE_RUN_LEN: f64 = 0.8;
-const DEFAULT_CHUNK_SIZE: usize = 1000000;
+const DEFAULT_RUN_LEN_MIN_FREQUENCY: f64 = 0.8;
+// the training sample sizes of the fast compression levels
+const FASTEST_LEVELS_SAMPLE_SIZE: usize = 1 << 13;
+const FAST_LEVELS_SAMPLE_SIZE: usize = 1 << 16;
+pub(crate) const DEFAULT_CHUNK_SIZE: usize = 1000000;
 
 struct JumpstartConfiguration {
   weight: usize,
@@ -44,7 +55,69 @@ pub struct CompressorConfig {
   /// high as level level 0's.
   /// * Level 12 can achieve a few % better compression than 8 with 4096
   /// prefixes but runs ~5x slower in many cases.
+  ///
+  /// Setting this alone only changes the number of prefixes.
+  /// [`CompressorConfig::from_level`] also adjusts the other knobs that
+  /// trade compression speed for ratio.
   pub compression_level: usize,
+  /// `max_n_prefixes` caps the number of prefixes per chunk, regardless of
+  /// `compression_level` (default `None`).
+  ///
+  /// Prefix optimization time and chunk metadata size grow with the number
+  /// of prefixes, so latency-sensitive writers can cap it while keeping a
+  /// high compression level's other behaviors.
+  /// For instance, `Some(64)` limits every chunk to the 64 prefixes of level
+  /// 6.
+  /// It must be at least 1.
+  pub max_n_prefixes: Option<usize>,
+  /// `run_len_min_frequency` is the fraction of a chunk's numbers a range
+  /// must hold before its prefix gets a
+  /// [`run_len_jumpstart`][crate::Prefix::run_len_jumpstart] (default 0.8).
+  ///
+  /// Such a prefix describes a run of consecutive numbers in its range at
+  /// once, which saves bits when the data repeats the same value in bursts.
+  /// Lowering this lets less dominant but bursty values use run lengths,
+  /// at the cost of a few extra bits for each number that isn't repeated.
+  /// It only applies to chunks of more than 1000 numbers and must be in
+  /// the range (0, 1].
+  pub run_len_min_frequency: f64,
+  /// `run_len_jumpstart` overrides the jumpstart of every run length prefix
+  /// the compressor creates (default `None`).
+  ///
+  /// The jumpstart is the number of bits always spent on each run's length;
+  /// longer runs take 2 more bits per additional bit of length.
+  /// By default it is chosen from each prefix's frequency or actual runs,
+  /// but data with runs of a known typical length can do better by setting
+  /// it to about `log2` of that length.
+  /// It must be between 1 and 24 inclusive.
+  pub run_len_jumpstart: Option<usize>,
+  /// `decode_speed_overhead` is the fraction of compressed size prefix
+  /// optimization may give up to make decompression faster (default 0.0).
+  ///
+  /// By default, each chunk gets the prefixes that minimize its size.
+  /// With e.g. `0.02`, the compressor instead chooses the fewest prefixes it
+  /// can while keeping each chunk's estimated size within 2% of that
+  /// minimum.
+  /// Fewer prefixes mean shorter Huffman codes and smaller decoding tables,
+  /// which suits read-heavy workloads that decompress the same files many
+  /// times.
+  /// Higher values take slightly longer to compress.
+  /// It must be in the range [0, 1].
+  pub decode_speed_overhead: f64,
+  /// `training_sample_size` fits each chunk's prefixes to an evenly spaced
+  /// sample of about this many of its numbers (default `None`).
+  ///
+  /// By default, the compressor sorts every number in a chunk to choose its
+  /// prefixes, which dominates compression time for large chunks.
+  /// A sample of e.g. 10000 numbers is much cheaper and usually loses only a
+  /// little compression ratio, so this suits latency-sensitive writers.
+  /// The prefixes still cover every number, but lose any
+  /// [GCDs][CompressorConfig::use_gcds], and explicit run lengths are only
+  /// found if the sample has them too.
+  /// It only affects how the compressor chooses prefixes, so the format is
+  /// unchanged.
+  /// It must be at least 1.
+  pub training_sample_size: Option<usize>,
   /// `delta_encoding_order` ranges from 0 to 7 inclusive (default 0).
   ///
   /// It is the number of times to apply delta encoding
@@ -66,6 +139,15 @@ pub struct CompressorConfig {
   /// If you're unsure, use
   /// [`auto_compressor_config()`][crate::auto_compressor_config] to choose it.
   pub delta_encoding_order: usize,
+  /// `delta_lag` ranges from 1 to 65535 inclusive (default 1).
+  ///
+  /// It is how many positions earlier each number is differenced against
+  /// during delta encoding.
+  /// For instance, hourly data with daily seasonality might use a lag of
+  /// 24, so that each number is compared to the one a day earlier rather
+  /// than an hour earlier.
+  /// It has no effect unless delta encoding is on.
+  pub delta_lag: usize,
   /// `use_gcds` improves compression ratio in cases where all
   /// numbers in a range share a nontrivial Greatest Common Divisor
   /// (default true).
@@ -79,6 +161,186 @@ pub struct CompressorConfig {
   /// When this is helpful and in rare cases when it isn't, compression speed
   /// is slightly reduced.
   pub use_gcds: bool,
+  /// `gcd_hints` are divisors the compressor checks first when finding each
+  /// prefix's GCD (default empty).
+  ///
+  /// For instance, minute-aligned millisecond timestamps might hint 60000,
+  /// and page-aligned byte offsets might hint 4096.
+  /// Each prefix uses the largest hint that divides all its numbers'
+  /// offsets from its lower bound, even if other prefixes' numbers aren't aligned,
+  /// and otherwise falls back to finding its exact GCD.
+  /// Checking a hint is cheaper than finding the exact GCD, so this speeds
+  /// up compression of such data.
+  /// Hints apply to delta encoded numbers too, but not to floats, and have
+  /// no effect unless `use_gcds` is on.
+  /// Every hint must be positive.
+  pub gcd_hints: Vec<usize>,
+  /// `use_chunk_checksums` appends a CRC32 checksum to each chunk body
+  /// (default false).
+  ///
+  /// This costs 4 bytes per chunk and lets the decompressor detect corrupted
+  /// chunk bodies that would otherwise decode into wrong numbers.
+  pub use_chunk_checksums: bool,
+  /// `use_file_checksum` appends a CRC32 checksum of the whole file after
+  /// its termination byte (default false).
+  ///
+  /// This costs 4 bytes per file and lets readers validate a complete file
+  /// cheaply with [`Decompressor::verify`][crate::Decompressor::verify].
+  pub use_file_checksum: bool,
+  /// `use_decimal_multipliers` lets each chunk of floats that are all
+  /// decimals with a few digits after the point be compressed as integers
+  /// (default false).
+  ///
+  /// For instance, prices like `[1.99, 0.25, 12.5]` are all multiples of
+  /// 0.01, so the compressor can compress `[199, 25, 1250]` instead and
+  /// store the decimal exponent 2 in the chunk metadata.
+  /// This is lossless: a chunk only uses a decimal exponent if every number
+  /// converts back to exactly itself.
+  /// It has no effect on data types other than floats.
+  pub use_decimal_multipliers: bool,
+  /// `use_xor_encoding` lets each chunk be compressed as the XOR of
+  /// consecutive numbers instead, whenever that takes fewer bits
+  /// (default false).
+  ///
+  /// This suits slowly varying floats, where consecutive numbers often
+  /// share their sign, exponent, and leading mantissa bits, so their XORs
+  /// have many leading zeros.
+  /// Since the compressor tries both encodings for each chunk, this
+  /// roughly halves compression speed.
+  pub use_xor_encoding: bool,
+  /// `use_chunk_delta_orders` makes the compressor choose a delta encoding
+  /// order for each chunk and store it in the chunk metadata (default
+  /// false).
+  ///
+  /// This suits data that alternates between smooth segments, where
+  /// higher orders win, and noisy segments, where order 0 wins.
+  /// Like [`auto_compressor_config()`][crate::auto_compressor_config], the
+  /// compressor picks each order by trying them on the chunk's first
+  /// numbers, adding a small compute cost per chunk.
+  /// When on, `delta_encoding_order` is ignored.
+  pub use_chunk_delta_orders: bool,
+  /// `tolerance` turns on lossy compression of floats, allowing each
+  /// decompressed number to differ from the original by up to the
+  /// tolerance (default `None`).
+  ///
+  /// Each chunk's numbers are rounded to the coarsest grid satisfying the
+  /// tolerance and compressed as integers on that grid, which can improve
+  /// compression ratio substantially.
+  /// For instance, sensor readings that only need to be accurate to 0.001
+  /// can use `Some(Tolerance::Absolute(0.001))`.
+  /// Relative tolerances round off low mantissa bits instead.
+  /// A chunk is compressed losslessly if any of its numbers can't be
+  /// rounded within the tolerance, e.g. NaNs with an absolute tolerance.
+  /// It has no effect on data types other than floats.
+  pub tolerance: Option<Tolerance>,
+  /// `use_raw_fallback` lets each chunk be stored raw, with every number
+  /// written at the same fixed bit width, whenever that takes no more bits
+  /// than the prefixes would (default false).
+  ///
+  /// This suits incompressible data like random IDs or hashes, where
+  /// prefixes can't beat the numbers' own bit width.
+  /// Raw chunks skip the prefix overhead and decompress much faster.
+  pub use_raw_fallback: bool,
+  /// `use_float_split_encoding` lets each chunk of floats be compressed as
+  /// two separate streams, one of signs and exponents and one of mantissas,
+  /// whenever that takes fewer bits (default false).
+  ///
+  /// This is experimental.
+  /// It suits floats whose exponents take only a few values while their
+  /// mantissas vary widely, since each stream gets its own prefixes
+  /// instead of the prefixes having to describe both at once.
+  /// Since the compressor tries both encodings for each chunk, this
+  /// roughly halves compression speed.
+  /// It has no effect on data types other than floats.
+  pub use_float_split_encoding: bool,
+  /// `use_user_metadata` reserves a section of
+  /// [`UserMetadata`][crate::UserMetadata] in the header and each chunk
+  /// metadata (default false).
+  ///
+  /// This lets applications carry information like schema names, units,
+  /// or source IDs inside the .qco file.
+  /// Set the header's with
+  /// [`Compressor::set_header_metadata`][crate::Compressor::set_header_metadata]
+  /// and each chunk's with
+  /// [`Compressor::chunk_with_metadata`][crate::Compressor::chunk_with_metadata].
+  pub use_user_metadata: bool,
+  /// `use_min_max` stores the exact minimum and maximum of each chunk's
+  /// numbers in its metadata (default false).
+  ///
+  /// Prefix bounds only loosely bound a chunk's numbers, so this allows
+  /// precise pruning and statistics without decompressing chunk bodies,
+  /// at the cost of two numbers per chunk.
+  /// See [`ChunkMetadata::min_max`].
+  pub use_min_max: bool,
+  /// `use_distinct_count` stores the number of distinct numbers in each
+  /// chunk in its metadata (default false).
+  ///
+  /// The count is exact for chunks with few distinct numbers and a
+  /// HyperLogLog estimate otherwise, so query planners can get cardinality
+  /// estimates without decompressing chunk bodies.
+  /// It costs a few bytes per chunk and some compression time.
+  /// See [`ChunkMetadata::distinct_count`].
+  pub use_distinct_count: bool,
+  /// `use_nan_positions` strips NaNs out of each chunk's body and instead
+  /// stores their positions in its metadata (default false).
+  ///
+  /// Data with missing values encoded as NaN, like pandas columns, would
+  /// otherwise need prefixes covering NaNs as well as the real numbers.
+  /// Decompression puts the NaNs back, so it is still lossless.
+  /// A chunk's NaNs are only stripped if they all have the same bits.
+  /// It has no effect on data types other than floats.
+  /// See [`ChunkMetadata::nan_positions`].
+  pub use_nan_positions: bool,
+  /// `use_affine_transforms` looks for a spacing `a` and offset `b` such
+  /// that each chunk's numbers are all `a * k + b` for integers `k`, and if
+  /// so, compresses the `k`s instead (default false).
+  ///
+  /// This generalizes [GCDs][CompressorConfig::use_gcds] from each prefix
+  /// to the whole chunk, so it also helps delta, XOR, and raw encoding.
+  /// Scaled sensor outputs and quantized or decimal floats often have this
+  /// structure.
+  /// It applies to integers and to floats converted to integers by decimal
+  /// multipliers or quantization.
+  /// See [`ChunkMetadata::affine_transform`].
+  pub use_affine_transforms: bool,
+  /// `canonicalize_negative_zero` replaces every -0.0 with +0.0 before
+  /// compressing (default false).
+  ///
+  /// Compression is normally bit-exact, so -0.0 and +0.0 are distinct
+  /// numbers, which can cost ratio for data where the sign of zero is an
+  /// accident of arithmetic.
+  /// This is recorded in the file's flags.
+  /// It has no effect on data types other than floats.
+  pub canonicalize_negative_zero: bool,
+  /// `flush_subnormals` replaces every subnormal float with a zero of the
+  /// same sign before compressing (default false).
+  ///
+  /// Subnormals are tiny numbers that often come from numerical noise and
+  /// each need their own prefix range far from the rest of the data.
+  /// Combine it with `canonicalize_negative_zero` to make all flushed
+  /// numbers +0.0.
+  /// This is recorded in the file's flags.
+  /// It has no effect on data types other than floats.
+  pub flush_subnormals: bool,
+  /// `deterministic` guarantees byte-identical output for identical numbers
+  /// and configs on every platform (default false).
+  ///
+  /// Otherwise the compressor's cost model uses the platform's `log2`,
+  /// which can differ in its last bit between operating systems and
+  /// architectures and occasionally tip the choice of prefixes.
+  /// In deterministic mode it uses a portable `log2` built only from basic
+  /// IEEE 754 operations, costing a little compression speed.
+  /// Deterministic output is also kept identical across patch versions of
+  /// this crate, so it suits content-addressed storage, where spurious byte
+  /// differences would break deduplication.
+  /// This covers every compression decision made for a given config,
+  /// including per-chunk ones like
+  /// [`use_chunk_delta_orders`][CompressorConfig::use_chunk_delta_orders],
+  /// but not the choice of config itself by functions like
+  /// [`train_config()`][crate::train_config].
+  /// It assumes IEEE 754 double precision arithmetic, which every common
+  /// target except x87-only 32-bit x86 has.
+  pub deterministic: bool,
   // Make it API-stable to add more fields in the future
   phantom: PhantomData<()>,
 }
@@ -87,45 +349,342 @@ impl Default for CompressorConfig {
   fn default() -> Self {
     Self {
       compression_level: DEFAULT_COMPRESSION_LEVEL,
+      max_n_prefixes: None,
+      run_len_min_frequency: DEFAULT_RUN_LEN_MIN_FREQUENCY,
+      run_len_jumpstart: None,
+      decode_speed_overhead: 0.0,
+      training_sample_size: None,
       delta_encoding_order: 0,
+      delta_lag: 1,
       use_gcds: true,
+      gcd_hints: Vec::new(),
+      use_chunk_checksums: false,
+      use_file_checksum: false,
+      use_decimal_multipliers: false,
+      use_xor_encoding: false,
+      use_chunk_delta_orders: false,
+      tolerance: None,
+      use_raw_fallback: false,
+      use_float_split_encoding: false,
+      use_user_metadata: false,
+      use_min_max: false,
+      use_distinct_count: false,
+      use_nan_positions: false,
+      use_affine_transforms: false,
+      canonicalize_negative_zero: false,
+      flush_subnormals: false,
+      deterministic: false,
       phantom: PhantomData,
     }
   }
 }
 
 impl CompressorConfig {
+  /// Returns the default config with the bundle of speed/ratio knobs for a
+  /// compression level from 0 to 12.
+  ///
+  /// | level | up to this many prefixes | `use_gcds` | `training_sample_size` |
+  /// |-------|--------------------------|------------|------------------------|
+  /// | 0-2   | 2^level                  | false      | 8192                   |
+  /// | 3-5   | 2^level                  | true       | 65536                  |
+  /// | 6-12  | 2^level                  | true       | `None`                 |
+  ///
+  /// At every level, prefixes are optimized exactly
+  /// (`decode_speed_overhead` is 0) and no
+  /// [`max_n_prefixes`][CompressorConfig::max_n_prefixes] cap applies.
+  /// `from_level(8)` is the same as the default config.
+  /// Each knob can still be overridden afterward:
+  /// ```
+  /// use q_compress::CompressorConfig;
+  ///
+  /// let config = CompressorConfig::from_level(2).with_use_gcds(true);
+  /// assert_eq!(config.compression_level, 2);
+  /// assert_eq!(config.training_sample_size, Some(8192));
+  /// ```
+  /// Levels above 12 are kept as-is and fail
+  /// [validation][CompressorConfig::validate].
+  pub fn from_level(level: usize) -> Self {
+    let (use_gcds, training_sample_size) = match level {
+      0..=2 => (false, Some(FASTEST_LEVELS_SAMPLE_SIZE)),
+      3..=5 => (true, Some(FAST_LEVELS_SAMPLE_SIZE)),
+      _ => (true, None),
+    };
+    Self::default()
+      .with_compression_level(level)
+      .with_use_gcds(use_gcds)
+      .with_training_sample_size(training_sample_size)
+  }
+
   /// Sets [`compression_level`][CompressorConfig::compression_level].
   pub fn with_compression_level(mut self, level: usize) -> Self {
     self.compression_level = level;
     self
   }
 
+  /// Sets [`max_n_prefixes`][CompressorConfig::max_n_prefixes].
+  pub fn with_max_n_prefixes(mut self, max_n_prefixes: Option<usize>) -> Self {
+    self.max_n_prefixes = max_n_prefixes;
+    self
+  }
+
+  /// Sets [`run_len_min_frequency`][CompressorConfig::run_len_min_frequency].
+  pub fn with_run_len_min_frequency(mut self, frequency: f64) -> Self {
+    self.run_len_min_frequency = frequency;
+    self
+  }
+
+  /// Sets [`run_len_jumpstart`][CompressorConfig::run_len_jumpstart].
+  pub fn with_run_len_jumpstart(mut self, jumpstart: Option<usize>) -> Self {
+    self.run_len_jumpstart = jumpstart;
+    self
+  }
+
+  /// Sets [`decode_speed_overhead`][CompressorConfig::decode_speed_overhead].
+  pub fn with_decode_speed_overhead(mut self, overhead: f64) -> Self {
+    self.decode_speed_overhead = overhead;
+    self
+  }
+
+  /// Sets [`training_sample_size`][CompressorConfig::training_sample_size].
+  pub fn with_training_sample_size(mut self, sample_size: Option<usize>) -> Self {
+    self.training_sample_size = sample_size;
+    self
+  }
+
   /// Sets [`delta_encoding_order`][CompressorConfig::delta_encoding_order].
   pub fn with_delta_encoding_order(mut self, order: usize) -> Self {
     self.delta_encoding_order = order;
     self
   }
 
+  /// Sets [`delta_lag`][CompressorConfig::delta_lag].
+  pub fn with_delta_lag(mut self, lag: usize) -> Self {
+    self.delta_lag = lag;
+    self
+  }
+
   /// Sets [`use_gcds`][CompressorConfig::use_gcds].
   pub fn with_use_gcds(mut self, use_gcds: bool) -> Self {
     self.use_gcds = use_gcds;
     self
   }
+
+  /// Sets [`gcd_hints`][CompressorConfig::gcd_hints].
+  pub fn with_gcd_hints(mut self, gcd_hints: Vec<usize>) -> Self {
+    self.gcd_hints = gcd_hints;
+    self
+  }
+
+  /// Sets [`use_chunk_checksums`][CompressorConfig::use_chunk_checksums].
+  pub fn with_use_chunk_checksums(mut self, use_chunk_checksums: bool) -> Self {
+    self.use_chunk_checksums = use_chunk_checksums;
+    self
+  }
+
+  /// Sets [`use_file_checksum`][CompressorConfig::use_file_checksum].
+  pub fn with_use_file_checksum(mut self, use_file_checksum: bool) -> Self {
+    self.use_file_checksum = use_file_checksum;
+    self
+  }
+
+  /// Sets [`use_decimal_multipliers`][CompressorConfig::use_decimal_multipliers].
+  pub fn with_use_decimal_multipliers(mut self, use_decimal_multipliers: bool) -> Self {
+    self.use_decimal_multipliers = use_decimal_multipliers;
+    self
+  }
+
+  /// Sets [`use_xor_encoding`][CompressorConfig::use_xor_encoding].
+  pub fn with_use_xor_encoding(mut self, use_xor_encoding: bool) -> Self {
+    self.use_xor_encoding = use_xor_encoding;
+    self
+  }
+
+  /// Sets [`use_chunk_delta_orders`][CompressorConfig::use_chunk_delta_orders].
+  pub fn with_use_chunk_delta_orders(mut self, use_chunk_delta_orders: bool) -> Self {
+    self.use_chunk_delta_orders = use_chunk_delta_orders;
+    self
+  }
+
+  /// Sets [`tolerance`][CompressorConfig::tolerance].
+  pub fn with_tolerance(mut self, tolerance: Option<Tolerance>) -> Self {
+    self.tolerance = tolerance;
+    self
+  }
+
+  /// Sets [`use_raw_fallback`][CompressorConfig::use_raw_fallback].
+  pub fn with_use_raw_fallback(mut self, use_raw_fallback: bool) -> Self {
+    self.use_raw_fallback = use_raw_fallback;
+    self
+  }
+
+  /// Sets [`use_float_split_encoding`][CompressorConfig::use_float_split_encoding].
+  pub fn with_use_float_split_encoding(mut self, use_float_split_encoding: bool) -> Self {
+    self.use_float_split_encoding = use_float_split_encoding;
+    self
+  }
+
+  /// Sets [`use_user_metadata`][CompressorConfig::use_user_metadata].
+  pub fn with_use_user_metadata(mut self, use_user_metadata: bool) -> Self {
+    self.use_user_metadata = use_user_metadata;
+    self
+  }
+
+  /// Sets [`use_min_max`][CompressorConfig::use_min_max].
+  pub fn with_use_min_max(mut self, use_min_max: bool) -> Self {
+    self.use_min_max = use_min_max;
+    self
+  }
+
+  /// Sets [`use_distinct_count`][CompressorConfig::use_distinct_count].
+  pub fn with_use_distinct_count(mut self, use_distinct_count: bool) -> Self {
+    self.use_distinct_count = use_distinct_count;
+    self
+  }
+
+  /// Sets [`use_nan_positions`][CompressorConfig::use_nan_positions].
+  pub fn with_use_nan_positions(mut self, use_nan_positions: bool) -> Self {
+    self.use_nan_positions = use_nan_positions;
+    self
+  }
+
+  /// Sets [`use_affine_transforms`][CompressorConfig::use_affine_transforms].
+  pub fn with_use_affine_transforms(mut self, use_affine_transforms: bool) -> Self {
+    self.use_affine_transforms = use_affine_transforms;
+    self
+  }
+
+  /// Sets [`canonicalize_negative_zero`][CompressorConfig::canonicalize_negative_zero].
+  pub fn with_canonicalize_negative_zero(mut self, canonicalize_negative_zero: bool) -> Self {
+    self.canonicalize_negative_zero = canonicalize_negative_zero;
+    self
+  }
+
+  /// Sets [`flush_subnormals`][CompressorConfig::flush_subnormals].
+  pub fn with_flush_subnormals(mut self, flush_subnormals: bool) -> Self {
+    self.flush_subnormals = flush_subnormals;
+    self
+  }
+
+  /// Sets [`deterministic`][CompressorConfig::deterministic].
+  pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+    self.deterministic = deterministic;
+    self
+  }
+
+  /// Returns an invalid argument error if any setting is out of range:
+  /// a `compression_level` above 12, a `max_n_prefixes` of 0, a
+  /// `run_len_min_frequency` outside (0, 1], a `run_len_jumpstart` outside
+  /// 1 to 24, a `decode_speed_overhead` outside [0, 1], a
+  /// `training_sample_size` of 0, a `gcd_hints` entry of 0,
+  /// a `delta_encoding_order` above 7, a `delta_lag` outside 1 to 65535, or a
+  /// `tolerance` that isn't positive and finite.
+  ///
+  /// [`Compressor::try_from_config`] calls this, so misconfiguration
+  /// fails before anything is compressed.
+  ///
+  /// Settings that are in range but don't apply are accepted and ignored,
+  /// since one config may be shared across data types, e.g. by a
+  /// [`MixedCompressor`][crate::MixedCompressor]:
+  /// * `tolerance`, `use_decimal_multipliers`, `use_float_split_encoding`,
+  ///   `use_nan_positions`, `canonicalize_negative_zero`, and
+  ///   `flush_subnormals` do nothing for non-float data types beyond being
+  ///   recorded in the file's flags.
+  /// * `delta_encoding_order` is overridden by `use_chunk_delta_orders`, and
+  ///   `delta_lag` does nothing without delta encoding.
+  /// * `gcd_hints` do nothing without `use_gcds`.
+  pub fn validate(&self) -> QCompressResult<()> {
+    if self.compression_level > MAX_COMPRESSION_LEVEL {
+      return Err(QCompressError::invalid_argument(format!(
+        "compression level may not exceed {} (was {})",
+        MAX_COMPRESSION_LEVEL,
+        self.compression_level,
+      )));
+    }
+    if self.max_n_prefixes == Some(0) {
+      return Err(QCompressError::invalid_argument(
+        "max number of prefixes must be positive"
+      ));
+    }
+    if !(self.run_len_min_frequency > 0.0 && self.run_len_min_frequency <= 1.0) {
+      return Err(QCompressError::invalid_argument(format!(
+        "run length min frequency must be in (0, 1] (was {})",
+        self.run_len_min_frequency,
+      )));
+    }
+    if let Some(jumpstart) = self.run_len_jumpstart {
+      if jumpstart == 0 || jumpstart > MAX_JUMPSTART {
+        return Err(QCompressError::invalid_argument(format!(
+          "run length jumpstart must be between 1 and {} (was {})",
+          MAX_JUMPSTART,
+          jumpstart,
+        )));
+      }
+    }
+    if !(self.decode_speed_overhead >= 0.0 && self.decode_speed_overhead <= 1.0) {
+      return Err(QCompressError::invalid_argument(format!(
+        "decode speed overhead must be in [0, 1] (was {})",
+        self.decode_speed_overhead,
+      )));
+    }
+    if self.gcd_hints.contains(&0) {
+      return Err(QCompressError::invalid_argument(
+        "GCD hints must be positive"
+      ));
+    }
+    if self.training_sample_size == Some(0) {
+      return Err(QCompressError::invalid_argument(
+        "training sample size must be positive"
+      ));
+    }
+    if self.delta_encoding_order > MAX_DELTA_ENCODING_ORDER {
+      return Err(QCompressError::invalid_argument(format!(
+        "delta encoding order may not exceed {} (was {})",
+        MAX_DELTA_ENCODING_ORDER,
+        self.delta_encoding_order,
+      )));
+    }
+    if self.delta_lag == 0 || self.delta_lag > MAX_DELTA_LAG {
+      return Err(QCompressError::invalid_argument(format!(
+        "delta lag must be between 1 and {} (was {})",
+        MAX_DELTA_LAG,
+        self.delta_lag,
+      )));
+    }
+    if let Some(tolerance) = &self.tolerance {
+      tolerance.validate()?;
+    }
+    Ok(())
+  }
 }
 
 // InternalCompressorConfig captures all settings that don't belong in flags
 // i.e. these don't get written to the resulting bytes and aren't needed for
 // decoding
 #[derive(Clone, Debug)]
-struct InternalCompressorConfig {
+pub(crate) struct InternalCompressorConfig {
   pub compression_level: usize,
+  pub max_n_prefixes: Option<usize>,
+  pub run_len_min_frequency: f64,
+  pub run_len_jumpstart: Option<usize>,
+  pub decode_speed_overhead: f64,
+  pub training_sample_size: Option<usize>,
+  pub gcd_hints: Vec<usize>,
+  pub tolerance: Option<Tolerance>,
+  pub deterministic: bool,
 }
 
 impl From<&CompressorConfig> for InternalCompressorConfig {
   fn from(config: &CompressorConfig) -> Self {
     InternalCompressorConfig {
       compression_level: config.compression_level,
+      max_n_prefixes: config.max_n_prefixes,
+      run_len_min_frequency: config.run_len_min_frequency,
+      run_len_jumpstart: config.run_len_jumpstart,
+      decode_speed_overhead: config.decode_speed_overhead,
+      training_sample_size: config.training_sample_size,
+      gcd_hints: config.gcd_hints.clone(),
+      tolerance: config.tolerance,
+      deterministic: config.deterministic,
     }
   }
 }
@@ -136,14 +695,27 @@ impl Default for InternalCompressorConfig {
   }
 }
 
+impl InternalCompressorConfig {
+  fn log2(&self, x: f64) -> f64 {
+    if self.deterministic {
+      float_math::portable_log2(x)
+    } else {
+      float_math::log2(x)
+    }
+  }
+}
+
 fn choose_run_len_jumpstart(
   count: usize,
   n: usize,
+  internal_config: &InternalCompressorConfig,
 ) -> JumpstartConfiguration {
   let freq = (count as f64) / (n as f64);
   let non_freq = 1.0 - freq;
-  let jumpstart = min((-non_freq.log2()).ceil() as usize, MAX_JUMPSTART);
-  let expected_n_runs = (freq * non_freq * n as f64).ceil() as usize;
+  let jumpstart = internal_config.run_len_jumpstart.unwrap_or_else(|| {
+    min(float_math::ceil(-internal_config.log2(non_freq)) as usize, MAX_JUMPSTART)
+  });
+  let expected_n_runs = float_math::ceil(freq * non_freq * n as f64) as usize;
   JumpstartConfiguration {
     weight: expected_n_runs,
     jumpstart,
@@ -157,6 +729,8 @@ struct PrefixBuffer<'a, T: NumberLike> {
   pub n_unsigneds: usize,
   pub sorted: &'a [T::Unsigned],
   pub use_gcd: bool,
+  pub gcd_hints: &'a [usize],
+  pub internal_config: &'a InternalCompressorConfig,
 }
 
 fn push_pref<T: NumberLike>(
@@ -173,13 +747,15 @@ fn push_pref<T: NumberLike>(
   let lower = T::from_unsigned(sorted[i]);
   let upper = T::from_unsigned(sorted[j - 1]);
   let gcd = if buffer.use_gcd {
-    gcd_utils::gcd(&sorted[i..j])
+    gcd_utils::gcd_with_hints(&sorted[i..j], buffer.gcd_hints)
   } else {
     T::Unsigned::ONE
   };
-  if n_unsigneds < MIN_N_TO_USE_RUN_LEN || frequency < MIN_FREQUENCY_TO_USE_RUN_LEN || count == n_unsigneds {
+  if n_unsigneds < MIN_N_TO_USE_RUN_LEN ||
+    frequency < buffer.internal_config.run_len_min_frequency ||
+    count == n_unsigneds {
     // The usual case - a prefix for a range that represents either 100% or
-    // <=80% of the data.
+    // less than the run length min frequency (80% by default) of the data.
     buffer.seq.push(WeightedPrefix::new(
       count,
       count,
@@ -191,7 +767,7 @@ fn push_pref<T: NumberLike>(
   } else {
     // The weird case - a range that represents almost all (but not all) the data.
     // We create extra prefixes that can describe `reps` copies of the range at once.
-    let config = choose_run_len_jumpstart(count, n_unsigneds);
+    let config = choose_run_len_jumpstart(count, n_unsigneds, buffer.internal_config);
     buffer.seq.push(WeightedPrefix::new(
       count,
       config.weight,
@@ -204,12 +780,89 @@ fn push_pref<T: NumberLike>(
   *buffer.prefix_idx = new_prefix_idx;
 }
 
+// the value and length of each run of 2 or more identical unsigneds,
+// sorted by value
+fn repeated_runs<U: UnsignedLike>(unsigneds: &[U]) -> Vec<(U, usize)> {
+  let mut res = Vec::new();
+  let mut i = 0;
+  while i < unsigneds.len() {
+    let mut j = i + 1;
+    while j < unsigneds.len() && unsigneds[j] == unsigneds[i] {
+      j += 1;
+    }
+    if j - i > 1 {
+      res.push((unsigneds[i], j - i));
+    }
+    i = j;
+  }
+  res.sort_unstable();
+  res
+}
+
+// Gives a run length jumpstart to each single-value prefix whose numbers
+// come in runs long enough that encoding each run's length directly takes
+// fewer bits than repeating the prefix for every number.
+// Unlike the frequency-based run lengths from push_pref, this uses the
+// actual runs in the data, so it helps even for values that are only a
+// small fraction of the data, as long as they're clumped together.
+fn choose_explicit_run_lens<T: NumberLike>(
+  prefs: &mut [WeightedPrefix<T>],
+  runs: &[(T::Unsigned, usize)],
+  internal_config: &InternalCompressorConfig,
+) {
+  let mut total_weight = prefs.iter().map(|wp| wp.weight).sum::<usize>();
+  // every Huffman code takes at least 1 bit unless there's only 1 prefix
+  let min_depth_bits = if prefs.len() > 1 { 1.0 } else { 0.0 };
+  let depth_bits = |weight: usize, total_weight: usize| {
+    f64::max(
+      bits::avg_depth_bits(weight, total_weight, internal_config.deterministic),
+      min_depth_bits,
+    )
+  };
+  for wp in prefs.iter_mut() {
+    let p = &wp.prefix;
+    let value = p.lower.to_unsigned();
+    if p.run_len_jumpstart.is_some() || p.upper.to_unsigned() != value {
+      continue;
+    }
+
+    let start = runs.partition_point(|&(other, _)| other < value);
+    let value_runs = &runs[start..];
+    let n_repeated_runs = value_runs.iter().take_while(|&&(other, _)| other == value).count();
+    if n_repeated_runs == 0 {
+      continue;
+    }
+    let value_runs = &value_runs[..n_repeated_runs];
+    let repeated_count = value_runs.iter().map(|&(_, len)| len).sum::<usize>();
+    let n_runs = p.count - repeated_count + n_repeated_runs;
+    let jumpstart = internal_config.run_len_jumpstart.unwrap_or_else(|| min(
+      float_math::ceil(internal_config.log2(p.count as f64 / n_runs as f64)) as usize,
+      MAX_JUMPSTART,
+    ));
+
+    // we store 1 less than each run's length
+    let varint_bits = (n_runs - n_repeated_runs) * varint_bit_size(0, jumpstart) +
+      value_runs.iter()
+        .map(|&(_, len)| varint_bit_size(len - 1, jumpstart))
+        .sum::<usize>();
+    let run_len_total_weight = total_weight - wp.weight + n_runs;
+    let bits_without = p.count as f64 * depth_bits(wp.weight, total_weight);
+    let bits_with = n_runs as f64 * depth_bits(n_runs, run_len_total_weight) +
+      varint_bits as f64;
+    if bits_with < bits_without {
+      wp.prefix.run_len_jumpstart = Some(jumpstart);
+      wp.weight = n_runs;
+      total_weight = run_len_total_weight;
+    }
+  }
+}
+
 // 2 ^ comp level, with 2 caveats:
 // * Enforce n_prefixes <= n_unsigneds
 // * Due to prefix optimization compute cost ~ O(4 ^ comp level), limit max comp level when
 // n_unsigneds is small
 fn choose_max_n_prefixes(comp_level: usize, n_unsigneds: usize) -> usize {
-  let log_n = (n_unsigneds as f64).log2().floor() as usize;
+  let log_n = (usize::BITS - 1 - max(n_unsigneds, 1).leading_zeros()) as usize;
   let max_comp_level_for_n = min(MAX_COMPRESSION_LEVEL, log_n / 2 + 5);
   let real_comp_level = comp_level.saturating_sub(MAX_COMPRESSION_LEVEL - max_comp_level_for_n);
   min(1_usize << real_comp_level, n_unsigneds)
@@ -221,7 +874,10 @@ fn choose_unoptimized_prefixes<T: NumberLike>(
   flags: &Flags,
 ) -> Vec<WeightedPrefix<T>> {
   let n_unsigneds = sorted.len();
-  let max_n_pref = choose_max_n_prefixes(internal_config.compression_level, n_unsigneds);
+  let mut max_n_pref = choose_max_n_prefixes(internal_config.compression_level, n_unsigneds);
+  if let Some(max_n_prefixes) = internal_config.max_n_prefixes {
+    max_n_pref = min(max_n_pref, max(max_n_prefixes, 1));
+  }
   let mut raw_prefs: Vec<WeightedPrefix<T>> = Vec::new();
   let mut pref_idx = 0_usize;
 
@@ -235,6 +891,13 @@ fn choose_unoptimized_prefixes<T: NumberLike>(
     n_unsigneds,
     sorted,
     use_gcd,
+    // hinted divisors describe numbers, not floats' bits
+    gcd_hints: if T::MANTISSA_BITS.is_none() {
+      &internal_config.gcd_hints
+    } else {
+      &[]
+    },
+    internal_config,
   };
 
   for j in 0..n_unsigneds {
@@ -257,7 +920,25 @@ fn choose_unoptimized_prefixes<T: NumberLike>(
   raw_prefs
 }
 
-fn train_prefixes<T: NumberLike>(
+// the smallest decimal exponent that losslessly turns every number into an
+// integer, if any
+pub(crate) fn choose_decimal_exponent<T: NumberLike>(nums: &[T]) -> Option<u32> {
+  let max_exponent = T::MAX_DECIMAL_EXPONENT?;
+  (0..=max_exponent).find(|&exponent| {
+    nums.iter().all(|&x| x.to_decimal_int(exponent).is_some())
+  })
+}
+
+// numbers of the same type whose signed representations are the decimal
+// integers, so the rest of compression (including delta encoding) can treat
+// them like any other chunk
+pub(crate) fn decimal_int_nums<T: NumberLike>(nums: &[T], exponent: u32) -> Vec<T> {
+  nums.iter()
+    .map(|&x| T::from_signed(x.to_decimal_int(exponent).unwrap()))
+    .collect()
+}
+
+pub(crate) fn train_prefixes<T: NumberLike>(
   unsigneds: Vec<T::Unsigned>,
   internal_config: &InternalCompressorConfig,
   flags: &Flags,
@@ -283,7 +964,14 @@ fn train_prefixes<T: NumberLike>(
     )));
   }
 
-  let unoptimized_prefs = {
+  if let Some(sample_size) = internal_config.training_sample_size {
+    if unsigneds.len() > sample_size {
+      return Ok(train_prefixes_on_sample(&unsigneds, sample_size, internal_config, flags, n));
+    }
+  }
+
+  let runs = repeated_runs(&unsigneds);
+  let mut unoptimized_prefs = {
     let mut sorted = unsigneds;
     sorted.sort_unstable();
     choose_unoptimized_prefixes(
@@ -292,11 +980,16 @@ fn train_prefixes<T: NumberLike>(
       flags
     )
   };
+  if !runs.is_empty() {
+    choose_explicit_run_lens(&mut unoptimized_prefs, &runs, internal_config);
+  }
 
   let mut optimized_prefs = prefix_optimization::optimize_prefixes(
     unoptimized_prefs,
     flags,
     n,
+    internal_config.deterministic,
+    internal_config.decode_speed_overhead,
   );
 
   huffman_encoding::make_huffman_code(&mut optimized_prefs);
@@ -307,6 +1000,95 @@ fn train_prefixes<T: NumberLike>(
   Ok(prefixes)
 }
 
+// Fits prefixes to an evenly spaced sample of the unsigneds instead of
+// sorting them all.
+// The prefixes are then widened or supplemented to cover every unsigned, so
+// they lose any GCDs, and given their exact counts.
+fn train_prefixes_on_sample<T: NumberLike>(
+  unsigneds: &[T::Unsigned],
+  sample_size: usize,
+  internal_config: &InternalCompressorConfig,
+  flags: &Flags,
+  n: usize,
+) -> Vec<Prefix<T>> {
+  let stride = bits::ceil_div(unsigneds.len(), sample_size);
+  let mut sorted = unsigneds.iter()
+    .step_by(stride)
+    .copied()
+    .collect::<Vec<_>>();
+  sorted.sort_unstable();
+  let sample_flags = Flags {
+    use_gcds: false,
+    ..flags.clone()
+  };
+  let unoptimized_prefs = choose_unoptimized_prefixes::<T>(
+    &sorted,
+    internal_config,
+    &sample_flags,
+  );
+  let mut prefs = prefix_optimization::optimize_prefixes(
+    unoptimized_prefs,
+    &sample_flags,
+    n,
+    internal_config.deterministic,
+    internal_config.decode_speed_overhead,
+  );
+
+  // close the gaps between prefixes, keeping run length prefixes narrow
+  for i in 0..prefs.len() - 1 {
+    let next_lower = prefs[i + 1].prefix.lower.to_unsigned();
+    if prefs[i].prefix.run_len_jumpstart.is_none() {
+      prefs[i].prefix.upper = T::from_unsigned(next_lower - T::Unsigned::ONE);
+    } else {
+      let upper = prefs[i].prefix.upper.to_unsigned();
+      prefs[i + 1].prefix.lower = T::from_unsigned(upper + T::Unsigned::ONE);
+    }
+  }
+  // unsampled outliers get prefixes of their own, so they don't widen the
+  // prefixes of common numbers
+  let lowest = unsigneds.iter().copied().min().unwrap();
+  let highest = unsigneds.iter().copied().max().unwrap();
+  let sampled_lowest = prefs[0].prefix.lower.to_unsigned();
+  if lowest < sampled_lowest {
+    let outlier_pref = WeightedPrefix::new(
+      1,
+      1,
+      T::from_unsigned(lowest),
+      T::from_unsigned(sampled_lowest - T::Unsigned::ONE),
+      None,
+      T::Unsigned::ONE,
+    );
+    prefs.insert(0, outlier_pref);
+  }
+  let sampled_highest = prefs[prefs.len() - 1].prefix.upper.to_unsigned();
+  if highest > sampled_highest {
+    prefs.push(WeightedPrefix::new(
+      1,
+      1,
+      T::from_unsigned(sampled_highest + T::Unsigned::ONE),
+      T::from_unsigned(highest),
+      None,
+      T::Unsigned::ONE,
+    ));
+  }
+
+  let n_prefs = prefs.len();
+  let mut counts = vec![0; n_prefs];
+  for &u in unsigneds {
+    counts[prefs.partition_point(|wp| wp.prefix.lower.to_unsigned() <= u) - 1] += 1;
+  }
+  for (wp, &count) in prefs.iter_mut().zip(&counts) {
+    // run length prefixes have weights that differ from their counts
+    wp.weight = max(1, wp.weight * count / wp.prefix.count);
+    wp.prefix.count = count;
+  }
+
+  huffman_encoding::make_huffman_code(&mut prefs);
+  prefs.into_iter()
+    .map(|wp| wp.prefix)
+    .collect()
+}
+
 #[derive(Clone)]
 struct TrainedChunkCompressor<U: UnsignedLike, GcdOp: GcdOperator<U>> {
   pub table: CompressionTable<U>,
@@ -328,7 +1110,191 @@ fn trained_compress_chunk_nums<T: NumberLike>(
   }
 }
 
+// the number of bits trained_compress_chunk_nums would write for the
+// unsigneds, without finishing the byte
+pub(crate) fn trained_chunk_body_bit_size<T: NumberLike>(
+  prefixes: &[Prefix<T>],
+  unsigneds: &[T::Unsigned],
+) -> QCompressResult<usize> {
+  let table = CompressionTable::from(prefixes);
+  if gcd_utils::use_gcd_arithmetic(prefixes) {
+    TrainedChunkCompressor::<T::Unsigned, GeneralGcdOp> { table, op: PhantomData }
+      .body_bit_size(unsigneds)
+  } else {
+    TrainedChunkCompressor::<T::Unsigned, TrivialGcdOp> { table, op: PhantomData }
+      .body_bit_size(unsigneds)
+  }
+}
+
+// the exact min and max of the numbers decompression will return for a
+// nonempty chunk
+fn chunk_min_max<T: NumberLike>(nums: &[T], quantization: Option<Quantization>) -> (T, T) {
+  let min = nums.iter().copied().min_by_key(|x| x.to_unsigned()).unwrap();
+  let max = nums.iter().copied().max_by_key(|x| x.to_unsigned()).unwrap();
+  match quantization {
+    // rounding is monotonic, so it keeps the min and max in place
+    Some(quantization) => {
+      let round = |x: T| T::dequantize(x.quantize(quantization).unwrap(), quantization);
+      (round(min), round(max))
+    },
+    None => (min, max),
+  }
+}
+
+// the raw prefix metadata covering all the unsigneds with the smallest
+// fixed bit width
+fn raw_prefix_metadata<T: NumberLike>(unsigneds: &[T::Unsigned]) -> PrefixMetadata<T> {
+  let lower = unsigneds.iter().copied().min().unwrap_or(T::Unsigned::ZERO);
+  let upper = unsigneds.iter().copied().max().unwrap_or(T::Unsigned::ZERO);
+  let mut bit_width = 0;
+  while bit_width < T::Unsigned::BITS && (upper - lower) >> bit_width > T::Unsigned::ZERO {
+    bit_width += 1;
+  }
+  // the decompressor requires every offset of this bit width to be
+  // representable, so we may need to start lower
+  let lower = min(
+    lower,
+    T::Unsigned::MAX - chunk_metadata::raw_max_offset::<T::Unsigned>(bit_width),
+  );
+  PrefixMetadata::Raw {
+    lower: T::from_unsigned(lower),
+    bit_width,
+  }
+}
+
+// the prefix metadata with the shared prefixes in place of its own, if they
+// describe the same kind of numbers
+fn with_shared_prefixes<T: NumberLike>(
+  prefix_metadata: &PrefixMetadata<T>,
+  shared_prefixes: &SharedPrefixes<T>,
+  flags: &Flags,
+) -> Option<PrefixMetadata<T>> {
+  match (prefix_metadata, shared_prefixes) {
+    (PrefixMetadata::Simple { .. }, SharedPrefixes::Simple(prefixes)) => Some(PrefixMetadata::Simple {
+      prefixes: prefixes.clone(),
+    }),
+    (PrefixMetadata::Delta { delta_moments, .. }, SharedPrefixes::Delta(prefixes))
+    if delta_moments.order() == flags.delta_encoding_order => Some(PrefixMetadata::Delta {
+      prefixes: prefixes.clone(),
+      delta_moments: delta_moments.clone(),
+    }),
+    _ => None,
+  }
+}
+
+// writes the chunk body for whichever prefixes the metadata has
+fn compress_chunk_body<T: NumberLike>(
+  prefix_metadata: &PrefixMetadata<T>,
+  unsigneds: &[T::Unsigned],
+  writer: &mut BitWriter,
+) -> QCompressResult<()> {
+  match prefix_metadata {
+    PrefixMetadata::Simple { prefixes } => trained_compress_chunk_nums(prefixes, unsigneds, writer),
+    PrefixMetadata::Delta { prefixes, .. } | PrefixMetadata::Xor { prefixes, .. } =>
+      trained_compress_chunk_nums(prefixes, unsigneds, writer),
+    PrefixMetadata::Raw { lower, bit_width } => {
+      let lower_unsigned = lower.to_unsigned();
+      for &u in unsigneds {
+        writer.write_diff(u - lower_unsigned, *bit_width);
+      }
+      writer.finish_byte();
+      Ok(())
+    },
+    PrefixMetadata::FloatSplit { exponent_prefixes, mantissa_prefixes, .. } => {
+      let (exponents, mantissas) = split_by_mantissa::<T>(unsigneds);
+      trained_compress_chunk_nums(exponent_prefixes, &exponents, writer)?;
+      trained_compress_chunk_nums(mantissa_prefixes, &mantissas, writer)
+    },
+  }
+}
+
+// splits the unsigneds of a float split chunk into its exponents and
+// mantissas
+fn split_by_mantissa<T: NumberLike>(
+  unsigneds: &[T::Unsigned],
+) -> (Vec<T::Unsigned>, Vec<T::Unsigned>) {
+  float_split::split_unsigneds(unsigneds, T::MANTISSA_BITS.unwrap_or(0))
+}
+
+// the number of bits a chunk's metadata and body would take, so we can
+// choose between encodings
+fn chunk_bit_size<T: NumberLike>(
+  prefix_metadata: &PrefixMetadata<T>,
+  uses_shared_prefixes: bool,
+  unsigneds: &[T::Unsigned],
+  n: usize,
+  flags: &Flags,
+) -> QCompressResult<usize> {
+  let mut writer = BitWriter::default();
+  ChunkMetadata {
+    n,
+    compressed_body_size: 0,
+    prefix_metadata: prefix_metadata.clone(),
+    decimal_exponent: None,
+    quantization: None,
+    min_max: None,
+    distinct_count: None,
+    nan_positions: None,
+    affine_transform: None,
+    uses_shared_prefixes,
+    user_metadata: UserMetadata::new(),
+    phantom: PhantomData,
+  }.write_to(&mut writer, flags);
+  let body_bit_size = match prefix_metadata {
+    PrefixMetadata::Simple { prefixes } => trained_chunk_body_bit_size(prefixes, unsigneds)?,
+    PrefixMetadata::Delta { prefixes, .. } | PrefixMetadata::Xor { prefixes, .. } =>
+      trained_chunk_body_bit_size(prefixes, unsigneds)?,
+    PrefixMetadata::Raw { bit_width, .. } => unsigneds.len() * bit_width,
+    PrefixMetadata::FloatSplit { mantissa_prefixes, exponent_body_size, .. } => {
+      let (_, mantissas) = split_by_mantissa::<T>(unsigneds);
+      exponent_body_size * 8 + trained_chunk_body_bit_size(mantissa_prefixes, &mantissas)?
+    },
+  };
+  Ok(writer.bit_size() + body_bit_size)
+}
+
+fn varint_bit_size(x: usize, jumpstart: usize) -> usize {
+  let remaining = x >> jumpstart;
+  let n_extra_bits = min(
+    (usize::BITS - remaining.leading_zeros()) as usize,
+    BITS_TO_ENCODE_N_ENTRIES - jumpstart,
+  );
+  // the final 0 is only written if some bits went unused
+  let terminator = (jumpstart + n_extra_bits < BITS_TO_ENCODE_N_ENTRIES) as usize;
+  jumpstart + 2 * n_extra_bits + terminator
+}
+
 impl<U, GcdOp> TrainedChunkCompressor<U, GcdOp> where U: UnsignedLike, GcdOp: GcdOperator<U> {
+  // mirrors compress_nums, but only counts bits
+  fn body_bit_size(&self, unsigneds: &[U]) -> QCompressResult<usize> {
+    let mut res = 0;
+    let mut i = 0;
+    while i < unsigneds.len() {
+      let p = self.table.search(unsigneds[i])?;
+      res += p.code_len;
+      let reps = match p.run_len_jumpstart {
+        None => 1,
+        Some(jumpstart) => {
+          let reps = 1 + unsigneds.iter()
+            .skip(i + 1)
+            .take_while(|&&other| p.contains(other))
+            .count();
+          res += varint_bit_size(reps - 1, jumpstart);
+          reps
+        }
+      };
+      for &unsigned in unsigneds.iter().skip(i).take(reps) {
+        let off = GcdOp::get_offset(unsigned - p.lower, p.gcd);
+        res += p.k;
+        if off < p.only_k_bits_lower || off > p.only_k_bits_upper {
+          res += 1;
+        }
+      }
+      i += reps;
+    }
+    Ok(res)
+  }
+
   fn compress_nums(&self, unsigneds: &[U], writer: &mut BitWriter) -> QCompressResult<()> {
     let mut i = 0;
     while i < unsigneds.len() {
@@ -383,6 +1349,8 @@ impl<U, GcdOp> TrainedChunkCompressor<U, GcdOp> where U: UnsignedLike, GcdOp: Gc
 struct State {
   has_written_header: bool,
   has_written_footer: bool,
+  // running checksum of bytes already drained from the writer
+  file_checksum: Crc32,
 }
 
 /// Converts vectors of numbers into compressed bytes.
@@ -414,6 +1382,8 @@ struct State {
 pub struct Compressor<T> where T: NumberLike {
   internal_config: InternalCompressorConfig,
   flags: Flags,
+  shared_prefixes: Option<SharedPrefixes<T>>,
+  header_metadata: UserMetadata,
   writer: BitWriter,
   state: State,
   phantom: PhantomData<T>,
@@ -430,21 +1400,70 @@ impl<T> Compressor<T> where T: NumberLike {
   /// Internally, the compressor builds [`Flags`] as well as an internal
   /// configuration that doesn't show up in the output file.
   /// You can inspect the flags it chooses with [`.flags()`][Self::flags].
+  ///
+  /// An invalid config only produces an error once the compressor writes
+  /// the header or a chunk; use
+  /// [`try_from_config`][Self::try_from_config] to catch it here instead.
   pub fn from_config(config: CompressorConfig) -> Self {
     Self {
       internal_config: InternalCompressorConfig::from(&config),
       flags: Flags::from(&config),
+      shared_prefixes: None,
+      header_metadata: UserMetadata::new(),
       writer: BitWriter::default(),
       state: State::default(),
       phantom: PhantomData,
     }
   }
 
+  // Creates a compressor that writes chunks for an existing file, as if it
+  // had already written that file's header with these flags and shared
+  // prefixes.
+  pub(crate) fn for_existing_header(
+    config: &CompressorConfig,
+    flags: Flags,
+    shared_prefixes: Option<SharedPrefixes<T>>,
+  ) -> Self {
+    let mut internal_config = InternalCompressorConfig::from(config);
+    if !flags.use_quantization {
+      internal_config.tolerance = None;
+    }
+    Self {
+      internal_config,
+      flags,
+      shared_prefixes,
+      header_metadata: UserMetadata::new(),
+      writer: BitWriter::default(),
+      state: State {
+        has_written_header: true,
+        ..Default::default()
+      },
+      phantom: PhantomData,
+    }
+  }
+
+  /// Creates a new compressor like [`from_config`][Self::from_config], but
+  /// returns an invalid argument error if the config is invalid.
+  /// See [`CompressorConfig::validate`].
+  pub fn try_from_config(config: CompressorConfig) -> QCompressResult<Self> {
+    config.validate()?;
+    Ok(Self::from_config(config))
+  }
+
   /// Returns a reference to the compressor's flags.
   pub fn flags(&self) -> &Flags {
     &self.flags
   }
 
+  /// Discards any bytes not yet drained and returns the compressor to its
+  /// initial state, ready to write a new .qco file with the same config.
+  /// Unlike creating a new compressor, this keeps its allocated memory,
+  /// which helps when compressing many small series.
+  pub fn reset(&mut self) {
+    self.writer.clear();
+    self.state = State::default();
+  }
+
   /// Writes out a header using the compressor's data type and flags.
   /// Will return an error if the compressor has already written the header or
   /// footer.
@@ -453,7 +1472,9 @@ impl<T> Compressor<T> where T: NumberLike {
   /// * a 4-byte magic header for "qco!" in ascii,
   /// * a byte for the data type (e.g. `i64` has byte 1 and `f64` has byte
   /// 5), and
-  /// * bytes for the flags used to compress.
+  /// * bytes for the flags used to compress,
+  /// * any [header metadata][Self::set_header_metadata], and
+  /// * any [shared prefixes][Self::train_shared_prefixes].
   pub fn header(&mut self) -> QCompressResult<()> {
     if self.state.has_written_header {
       return Err(QCompressError::invalid_argument(
@@ -468,10 +1489,93 @@ impl<T> Compressor<T> where T: NumberLike {
     self.writer.write_aligned_bytes(&MAGIC_HEADER)?;
     self.writer.write_aligned_byte(T::HEADER_BYTE)?;
     self.flags.write(&mut self.writer)?;
+    if self.flags.use_user_metadata {
+      user_metadata::write_to(&self.header_metadata, &mut self.writer);
+      self.writer.finish_byte();
+    }
+    if let Some(shared_prefixes) = &self.shared_prefixes {
+      shared_prefixes.write_to(&mut self.writer, &self.flags);
+    }
     self.state.has_written_header = true;
     Ok(())
   }
 
+  /// Sets the [`UserMetadata`] to write in the header.
+  /// Will return an error if the compressor has already written the header,
+  /// `use_user_metadata` is off, or the metadata is too large.
+  pub fn set_header_metadata(&mut self, metadata: UserMetadata) -> QCompressResult<()> {
+    if self.state.has_written_header {
+      return Err(QCompressError::invalid_argument(
+        "attempted to set header metadata after header"
+      ));
+    }
+    if !self.flags.use_user_metadata {
+      return Err(QCompressError::invalid_argument(
+        "cannot set header metadata without use_user_metadata"
+      ));
+    }
+    user_metadata::validate(&metadata)?;
+    self.header_metadata = metadata;
+    Ok(())
+  }
+
+  /// Trains prefixes on `sample` and writes them once after the header, so
+  /// that chunks can use them instead of storing their own.
+  /// This helps when there are many small chunks with similar
+  /// distributions, where each chunk's prefixes would take up much of its
+  /// size.
+  /// Will return an error if the compressor has already written the header
+  /// or the sample has no more numbers than the delta encoding order needs.
+  ///
+  /// Each chunk still trains its own prefixes and uses the shared ones only
+  /// if they contain every number in the chunk and take fewer bits.
+  /// So the sample should cover the full range of the data; in particular,
+  /// chunks converted to integers by decimal multipliers or a tolerance
+  /// won't fit prefixes trained on the original numbers.
+  /// When delta encoding, only chunks of the file's `delta_encoding_order`
+  /// can use the shared prefixes, and XOR encoded or raw chunks never do.
+  pub fn train_shared_prefixes(&mut self, sample: &[T]) -> QCompressResult<()> {
+    if self.state.has_written_header {
+      return Err(QCompressError::invalid_argument(
+        "attempted to train shared prefixes after header"
+      ));
+    }
+
+    // Chunks can only use shared prefixes that contain all their numbers,
+    // and checking that is simpler without GCDs.
+    let flags = Flags {
+      use_gcds: false,
+      ..self.flags.clone()
+    };
+    let order = self.flags.delta_encoding_order;
+    let unsigneds = if order == 0 {
+      sample.iter()
+        .map(|x| x.to_unsigned())
+        .collect::<Vec<_>>()
+    } else {
+      delta_encoding::nth_order_deltas(sample, order, self.flags.delta_lag)
+        .iter()
+        .map(|x| x.to_unsigned())
+        .collect::<Vec<_>>()
+    };
+    if unsigneds.is_empty() {
+      return Err(QCompressError::invalid_argument(format!(
+        "sample of {} numbers is too small to train shared prefixes with delta encoding order {}",
+        sample.len(),
+        order,
+      )));
+    }
+
+    let shared_prefixes = if order == 0 {
+      SharedPrefixes::Simple(train_prefixes(unsigneds, &self.internal_config, &flags, sample.len())?)
+    } else {
+      SharedPrefixes::Delta(train_prefixes(unsigneds, &self.internal_config, &flags, sample.len())?)
+    };
+    self.flags.use_shared_prefixes = true;
+    self.shared_prefixes = Some(shared_prefixes);
+    Ok(())
+  }
+
   /// Writes out a chunk of data representing the provided numbers.
   /// Will return an error if the compressor has not yet written the header
   /// or already written the footer.
@@ -479,6 +1583,10 @@ impl<T> Compressor<T> where T: NumberLike {
   /// Each chunk contains a [`ChunkMetadata`] section followed by the chunk body.
   /// The chunk body encodes the numbers passed in here.
   pub fn chunk(&mut self, nums: &[T]) -> QCompressResult<ChunkMetadata<T>> {
+    self.chunk_with_metadata(nums, UserMetadata::new())
+  }
+
+  fn check_can_write_chunk(&self) -> QCompressResult<()> {
     if !self.state.has_written_header {
       return Err(QCompressError::invalid_argument(
         "attempted to write chunk before header"
@@ -489,19 +1597,169 @@ impl<T> Compressor<T> where T: NumberLike {
         "attempted to write chunk to terminated compressor"
       ));
     }
+    Ok(())
+  }
+
+  /// Writes consecutive chunks of `chunk_size` numbers (the last may be
+  /// smaller), like calling [`chunk`][Self::chunk] on each, but compresses
+  /// them concurrently on the `rayon` thread pool.
+  /// Returns each chunk's metadata in order.
+  /// Will return an error in the same cases as `chunk`, or if `chunk_size`
+  /// is 0.
+  #[cfg(feature = "parallel")]
+  pub fn chunks_parallel(
+    &mut self,
+    nums: &[T],
+    chunk_size: usize,
+  ) -> QCompressResult<Vec<ChunkMetadata<T>>> {
+    self.check_can_write_chunk()?;
     if nums.is_empty() {
       return Err(QCompressError::invalid_argument(
         "cannot compress empty chunk"
       ));
     }
+    if chunk_size == 0 {
+      return Err(QCompressError::invalid_argument(
+        "chunk size must be positive"
+      ));
+    }
+
+    let mut metas = Vec::new();
+    for (meta, bytes) in self.compress_chunks_in_parallel(nums, chunk_size)? {
+      self.writer.write_aligned_bytes(&bytes)?;
+      metas.push(meta);
+    }
+    Ok(metas)
+  }
+
+  /// Like [`chunk`][Self::chunk], but also writes the given
+  /// [`UserMetadata`] into the chunk metadata.
+  /// Will return an error in the same cases as `chunk`, or if `metadata` is
+  /// nonempty and `use_user_metadata` is off, or if `metadata` is too
+  /// large.
+  pub fn chunk_with_metadata(
+    &mut self,
+    nums: &[T],
+    metadata: UserMetadata,
+  ) -> QCompressResult<ChunkMetadata<T>> {
+    self.check_can_write_chunk()?;
+    if nums.is_empty() {
+      return Err(QCompressError::invalid_argument(
+        "cannot compress empty chunk"
+      ));
+    }
+    if !metadata.is_empty() && !self.flags.use_user_metadata {
+      return Err(QCompressError::invalid_argument(
+        "cannot write chunk metadata without use_user_metadata"
+      ));
+    }
+    user_metadata::validate(&metadata)?;
+
+    let canonical_nums;
+    let canonicalize = self.flags.canonicalize_negative_zero || self.flags.flush_subnormals;
+    let nums = if canonicalize && T::MANTISSA_BITS.is_some() {
+      canonical_nums = nums.iter()
+        .map(|x| x.canonicalized(self.flags.canonicalize_negative_zero, self.flags.flush_subnormals))
+        .collect::<Vec<_>>();
+      canonical_nums.as_slice()
+    } else {
+      nums
+    };
+
+    // NaN positions are written in the metadata, so everything else only
+    // sees the other numbers
+    let stripped = if self.flags.use_nan_positions {
+      nan_positions::strip_nans(nums)
+    } else {
+      None
+    };
+    let (nan_positions, nums) = match &stripped {
+      Some((nan_positions, valid)) => (Some(nan_positions.clone()), valid.as_slice()),
+      None => (None, nums),
+    };
+
+    let quantization = match self.internal_config.tolerance {
+      Some(tolerance) => quantization::choose_quantization(nums, tolerance)?,
+      None => None,
+    };
 
     self.writer.write_aligned_byte(MAGIC_CHUNK_BYTE)?;
 
+    let decimal_exponent = if quantization.is_none() && self.flags.use_decimal_multipliers {
+      choose_decimal_exponent(nums)
+    } else {
+      None
+    };
+    let min_max = if self.flags.use_min_max {
+      let (mut min, mut max) = chunk_min_max(nums, quantization);
+      if let Some(nan_positions) = &nan_positions {
+        let nan = nan_positions.nan;
+        if nan.to_unsigned() < min.to_unsigned() {
+          min = nan;
+        }
+        if nan.to_unsigned() > max.to_unsigned() {
+          max = nan;
+        }
+      }
+      Some((min, max))
+    } else {
+      None
+    };
+
+    let int_nums;
+    let nums = match (quantization, decimal_exponent) {
+      (Some(quantization), _) => {
+        int_nums = quantization::quantized_nums(nums, quantization);
+        &int_nums
+      },
+      (None, Some(exponent)) => {
+        int_nums = decimal_int_nums(nums, exponent);
+        &int_nums
+      },
+      (None, None) => nums,
+    };
+    // quantizing and decimal multipliers map distinct decompressed numbers
+    // to distinct integers
+    let distinct_count = if self.flags.use_distinct_count {
+      let mut distinct_count = distinct_count::distinct_count(nums);
+      // the stripped NaNs are all one more distinct number
+      distinct_count.count += nan_positions.is_some() as usize;
+      Some(distinct_count)
+    } else {
+      None
+    };
+
+    // floats' bits rarely share a spacing, so we only look for one among
+    // integers
+    let is_int = T::MANTISSA_BITS.is_none() || quantization.is_some() || decimal_exponent.is_some();
+    let affine_transform = if self.flags.use_affine_transforms && is_int {
+      affine::choose_affine_transform(nums)
+    } else {
+      None
+    };
+    let multiples;
+    let nums = match &affine_transform {
+      Some(affine_transform) => {
+        multiples = affine_transform.multiples(nums);
+        &multiples
+      },
+      None => nums,
+    };
+
     let n = nums.len();
     let pre_meta_bit_idx = self.writer.bit_size();
 
-    let order = self.flags.delta_encoding_order;
-    let (mut metadata, post_meta_byte_idx) = if order == 0 {
+    let order = if self.flags.use_chunk_delta_orders {
+      auto::auto_delta_encoding_order(
+        nums,
+        self.internal_config.compression_level,
+        self.flags.delta_lag,
+        self.internal_config.deterministic,
+      )
+    } else {
+      self.flags.delta_encoding_order
+    };
+    let (mut prefix_metadata, mut unsigneds) = if order == 0 {
       let unsigneds = nums.iter()
         .map(|x| x.to_unsigned())
         .collect::<Vec<_>>();
@@ -511,26 +1769,11 @@ impl<T> Compressor<T> where T: NumberLike {
         &self.flags,
         n,
       )?;
-      let prefix_metadata = PrefixMetadata::Simple {
-        prefixes: prefixes.clone(),
-      };
-      let metadata = ChunkMetadata {
-        n,
-        compressed_body_size: 0,
-        prefix_metadata,
-        phantom: PhantomData,
-      };
-      metadata.write_to(&mut self.writer, &self.flags);
-      let post_meta_idx = self.writer.byte_size();
-      trained_compress_chunk_nums(
-        &prefixes,
-        &unsigneds,
-        &mut self.writer
-      )?;
-      (metadata, post_meta_idx)
+      (PrefixMetadata::Simple { prefixes }, unsigneds)
     } else {
-      let delta_moments = DeltaMoments::from(nums, order);
-      let deltas = delta_encoding::nth_order_deltas(nums, order);
+      let lag = self.flags.delta_lag;
+      let delta_moments = DeltaMoments::from(nums, order, lag);
+      let deltas = delta_encoding::nth_order_deltas(nums, order, lag);
       let unsigneds = deltas.iter()
         .map(|x| x.to_unsigned())
         .collect::<Vec<_>>();
@@ -540,31 +1783,124 @@ impl<T> Compressor<T> where T: NumberLike {
         &self.flags,
         n,
       )?;
-      let prefix_metadata = PrefixMetadata::Delta {
-        delta_moments,
-        prefixes: prefixes.clone(),
-      };
-      let metadata = ChunkMetadata {
+      (PrefixMetadata::Delta { delta_moments, prefixes }, unsigneds)
+    };
+
+    let mut uses_shared_prefixes = false;
+    let maybe_shared_prefix_metadata = self.shared_prefixes.as_ref()
+      .and_then(|shared_prefixes| with_shared_prefixes(&prefix_metadata, shared_prefixes, &self.flags));
+    if let Some(shared_prefix_metadata) = maybe_shared_prefix_metadata {
+      // this errors if the shared prefixes don't contain every number
+      let shared_size = chunk_bit_size(&shared_prefix_metadata, true, &unsigneds, n, &self.flags);
+      if let Ok(shared_size) = shared_size {
+        if shared_size < chunk_bit_size(&prefix_metadata, false, &unsigneds, n, &self.flags)? {
+          prefix_metadata = shared_prefix_metadata;
+          uses_shared_prefixes = true;
+        }
+      }
+    }
+
+    if self.flags.use_xor_encoding {
+      let xor_unsigneds = xor_encoding::xor_unsigneds(nums);
+      let prefixes = train_prefixes(
+        xor_unsigneds.clone(),
+        &self.internal_config,
+        &self.flags,
         n,
-        compressed_body_size: 0,
-        prefix_metadata,
-        phantom: PhantomData,
+      )?;
+      let xor_prefix_metadata = PrefixMetadata::Xor {
+        prefixes,
+        first: nums[0],
       };
-      metadata.write_to(&mut self.writer, &self.flags);
-      let post_meta_idx = self.writer.byte_size();
-      trained_compress_chunk_nums(
-        &prefixes,
-        &unsigneds,
-        &mut self.writer
+      if chunk_bit_size(&xor_prefix_metadata, false, &xor_unsigneds, n, &self.flags)? <
+        chunk_bit_size(&prefix_metadata, uses_shared_prefixes, &unsigneds, n, &self.flags)? {
+        prefix_metadata = xor_prefix_metadata;
+        unsigneds = xor_unsigneds;
+        uses_shared_prefixes = false;
+      }
+    }
+
+    if let Some(mantissa_bits) = T::MANTISSA_BITS.filter(|_| self.flags.use_float_split) {
+      let split_unsigneds = nums.iter()
+        .map(|x| x.to_unsigned())
+        .collect::<Vec<_>>();
+      let (exponents, mantissas) = float_split::split_unsigneds(&split_unsigneds, mantissa_bits);
+      let exponent_prefixes = train_prefixes::<T::Signed>(
+        exponents.clone(),
+        &self.internal_config,
+        &self.flags,
+        n,
       )?;
-      (metadata, post_meta_idx)
+      let exponent_body_size = bits::ceil_div(
+        trained_chunk_body_bit_size(&exponent_prefixes, &exponents)?,
+        8,
+      );
+      let mantissa_prefixes = train_prefixes(
+        mantissas,
+        &self.internal_config,
+        &self.flags,
+        n,
+      )?;
+      let split_prefix_metadata = PrefixMetadata::FloatSplit {
+        exponent_prefixes,
+        mantissa_prefixes,
+        exponent_body_size,
+      };
+      if chunk_bit_size(&split_prefix_metadata, false, &split_unsigneds, n, &self.flags)? <
+        chunk_bit_size(&prefix_metadata, uses_shared_prefixes, &unsigneds, n, &self.flags)? {
+        prefix_metadata = split_prefix_metadata;
+        unsigneds = split_unsigneds;
+        uses_shared_prefixes = false;
+      }
+    }
+
+    if self.flags.use_raw_fallback {
+      let raw_unsigneds = nums.iter()
+        .map(|x| x.to_unsigned())
+        .collect::<Vec<_>>();
+      let raw_prefix_metadata = raw_prefix_metadata::<T>(&raw_unsigneds);
+      // on a tie, raw is better since it decompresses faster
+      if chunk_bit_size(&raw_prefix_metadata, false, &raw_unsigneds, n, &self.flags)? <=
+        chunk_bit_size(&prefix_metadata, uses_shared_prefixes, &unsigneds, n, &self.flags)? {
+        prefix_metadata = raw_prefix_metadata;
+        unsigneds = raw_unsigneds;
+        uses_shared_prefixes = false;
+      }
+    }
+
+    let n_nans = nan_positions.as_ref().map_or(0, |nan_positions| nan_positions.count());
+    let mut metadata = ChunkMetadata {
+      n: n + n_nans,
+      compressed_body_size: 0,
+      prefix_metadata,
+      decimal_exponent,
+      quantization,
+      min_max,
+      distinct_count,
+      nan_positions,
+      affine_transform,
+      uses_shared_prefixes,
+      user_metadata: metadata,
+      phantom: PhantomData,
     };
+    metadata.write_to(&mut self.writer, &self.flags);
+    let post_meta_byte_idx = self.writer.byte_size();
+    compress_chunk_body(
+      &metadata.prefix_metadata,
+      &unsigneds,
+      &mut self.writer,
+    )?;
     metadata.compressed_body_size = self.writer.byte_size() - post_meta_byte_idx;
+    if self.flags.use_chunk_checksums {
+      let body_checksum = checksum::crc32(&self.writer.bytes_since(post_meta_byte_idx));
+      self.writer.write_aligned_bytes(&body_checksum.to_le_bytes())?;
+    }
     metadata.update_write_compressed_body_size(&mut self.writer, pre_meta_bit_idx);
     Ok(metadata)
   }
 
-  /// Writes out a single footer byte indicating that the .qco file has ended.
+  /// Writes out a single footer byte indicating that the .qco file has ended,
+  /// followed by a checksum of the file if `use_file_checksum` is on.
   /// Will return an error if the compressor has not yet written the header
   /// or already written the footer.
   pub fn footer(&mut self) -> QCompressResult<()> {
@@ -579,19 +1915,107 @@ impl<T> Compressor<T> where T: NumberLike {
       ));
     }
     self.writer.write_aligned_byte(MAGIC_TERMINATION_BYTE)?;
+    if self.flags.use_file_checksum {
+      let mut file_checksum = self.state.file_checksum;
+      file_checksum.update(&self.writer.bytes_since(0));
+      self.writer.write_aligned_bytes(&file_checksum.finish().to_le_bytes())?;
+    }
     self.state.has_written_footer = true;
     Ok(())
   }
 
   /// Takes in a slice of numbers and returns compressed bytes.
+  ///
+  /// With the `parallel` feature, chunks are compressed concurrently on
+  /// the `rayon` thread pool.
+  /// Panics if the compressor's config is invalid, so prefer
+  /// [`try_from_config`][Self::try_from_config] for configs you don't
+  /// control.
   pub fn simple_compress(&mut self, nums: &[T]) -> Vec<u8> {
-    // The following unwraps are safe because the writer will be byte-aligned
-    // after each step and ensure each chunk has appropriate size.
+    self.simple_compress_with_progress(nums, |_| ())
+  }
+
+  // Each chunk starts byte-aligned and doesn't depend on the others, so we
+  // can compress them independently and concatenate their bytes.
+  #[cfg(feature = "parallel")]
+  fn compress_chunks_in_parallel(
+    &self,
+    nums: &[T],
+    chunk_size: usize,
+  ) -> QCompressResult<Vec<(ChunkMetadata<T>, Vec<u8>)>> {
+    use rayon::prelude::*;
+
+    nums.par_chunks(chunk_size)
+      .map(|chunk| {
+        let mut chunk_compressor = Self {
+          internal_config: self.internal_config.clone(),
+          flags: self.flags.clone(),
+          writer: BitWriter::default(),
+          shared_prefixes: self.shared_prefixes.clone(),
+          header_metadata: UserMetadata::new(),
+          state: State {
+            has_written_header: true,
+            ..Default::default()
+          },
+          phantom: PhantomData,
+        };
+        let meta = chunk_compressor.chunk(chunk)?;
+        Ok((meta, chunk_compressor.writer.drain_bytes()))
+      })
+      .collect()
+  }
+
+  /// Like [`simple_compress`][Compressor::simple_compress], but takes numbers
+  /// from an iterator, such as a generator or database cursor.
+  /// Only one chunk's worth of numbers is buffered at a time, so the input
+  /// never needs to be materialized in full.
+  pub fn compress_from_iter<I: IntoIterator<Item=T>>(&mut self, nums: I) -> Vec<u8> {
+    // The following unwraps are safe for the same reasons as in
+    // simple_compress.
+    self.header().unwrap();
+    let iter = nums.into_iter();
+    let mut buffer = Vec::with_capacity(min(iter.size_hint().0, DEFAULT_CHUNK_SIZE));
+    for num in iter {
+      buffer.push(num);
+      if buffer.len() == DEFAULT_CHUNK_SIZE {
+        self.chunk(&buffer).unwrap();
+        buffer.clear();
+      }
+    }
+    if !buffer.is_empty() {
+      self.chunk(&buffer).unwrap();
+    }
+
+    self.footer().unwrap();
+    self.drain_bytes()
+  }
+
+  /// Like [`simple_compress`][Compressor::simple_compress], but calls
+  /// `progress` after each chunk.
+  pub fn simple_compress_with_progress<P: Progress>(
+    &mut self,
+    nums: &[T],
+    mut progress: P,
+  ) -> Vec<u8> {
+    // The following unwraps are safe for valid configs because the writer
+    // will be byte-aligned after each step and ensure each chunk has
+    // appropriate size.
     self.header().unwrap();
-    nums.chunks(DEFAULT_CHUNK_SIZE)
-      .for_each(|chunk| {
-        self.chunk(chunk).unwrap();
+    #[cfg(feature = "parallel")]
+    let chunk_bytess = self.compress_chunks_in_parallel(nums, DEFAULT_CHUNK_SIZE).unwrap();
+    let mut n_numbers = 0;
+    for (chunk_idx, chunk) in nums.chunks(DEFAULT_CHUNK_SIZE).enumerate() {
+      #[cfg(feature = "parallel")]
+      self.writer.write_aligned_bytes(&chunk_bytess[chunk_idx].1).unwrap();
+      #[cfg(not(feature = "parallel"))]
+      self.chunk(chunk).unwrap();
+      n_numbers += chunk.len();
+      progress.on_chunk(ProgressUpdate {
+        n_chunks: chunk_idx + 1,
+        n_numbers,
+        n_bytes: self.writer.byte_size(),
       });
+    }
 
     self.footer().unwrap();
     self.drain_bytes()
@@ -603,7 +2027,11 @@ impl<T> Compressor<T> where T: NumberLike {
   /// In the future we may implement a method to write to a `std::io::Write` or
   /// implement `Compressor` as `std::io::Read`, TBD.
   pub fn drain_bytes(&mut self) -> Vec<u8> {
-    self.writer.drain_bytes()
+    let bytes = self.writer.drain_bytes();
+    if self.flags.use_file_checksum && !self.state.has_written_footer {
+      self.state.file_checksum.update(&bytes);
+    }
+    bytes
   }
 
   /// Returns the number of bytes produced by the compressor so far that have
@@ -615,7 +2043,8 @@ impl<T> Compressor<T> where T: NumberLike {
 
 #[cfg(test)]
 mod tests {
-  use super::choose_max_n_prefixes;
+  use super::{choose_max_n_prefixes, Compressor, CompressorConfig, DEFAULT_COMPRESSION_LEVEL};
+  use crate::{AffineTransform, PrefixMetadata};
 
   #[test]
   fn test_choose_max_n_prefixes() {
@@ -630,4 +2059,285 @@ mod tests {
     assert_eq!(choose_max_n_prefixes(12, 1 << 14), 1 << 12);
     assert_eq!(choose_max_n_prefixes(12, 1 << 20), 1 << 12);
   }
+
+  #[test]
+  fn test_max_n_prefixes() {
+    // 50 far-apart clusters, which each want their own prefix
+    let nums = (0..10000_i64).map(|i| (i % 50) * 1_000_000 + (i * 7919) % 100).collect::<Vec<_>>();
+    let n_prefixes = |max_n_prefixes: Option<usize>| {
+      let mut compressor = Compressor::<i64>::from_config(
+        CompressorConfig::default()
+          .with_compression_level(12)
+          .with_max_n_prefixes(max_n_prefixes)
+      );
+      compressor.header().unwrap();
+      match compressor.chunk(&nums).unwrap().prefix_metadata {
+        PrefixMetadata::Simple { prefixes } => prefixes.len(),
+        _ => panic!("expected simple prefix metadata"),
+      }
+    };
+    assert!(n_prefixes(None) > 16);
+    assert!(n_prefixes(Some(16)) <= 16);
+    assert_eq!(n_prefixes(Some(1)), 1);
+  }
+
+  #[test]
+  fn test_run_len_config() {
+    // bursts of 6 numbers in [0, 3] between widely spread numbers, so that
+    // neither of the 2 prefixes at level 2 holds 80% of the data
+    let nums = (0..10000_i64)
+      .map(|i| if i % 10 < 6 { (i / 10) % 4 } else { 1000 + (i * 7919) % 100000 })
+      .collect::<Vec<_>>();
+    let jumpstarts = |config: CompressorConfig| {
+      let mut compressor = Compressor::<i64>::from_config(config.with_compression_level(2));
+      compressor.header().unwrap();
+      let prefixes = match compressor.chunk(&nums).unwrap().prefix_metadata {
+        PrefixMetadata::Simple { prefixes } => prefixes,
+        _ => panic!("expected simple prefix metadata"),
+      };
+      compressor.footer().unwrap();
+      assert_eq!(crate::auto_decompress::<i64>(&compressor.drain_bytes()).unwrap(), nums);
+      prefixes.iter().filter_map(|p| p.run_len_jumpstart).collect::<Vec<_>>()
+    };
+    assert!(jumpstarts(CompressorConfig::default()).is_empty());
+    let low_freq = CompressorConfig::default().with_run_len_min_frequency(0.4);
+    assert!(!jumpstarts(low_freq.clone()).is_empty());
+    let fixed = jumpstarts(low_freq.with_run_len_jumpstart(Some(3)));
+    assert!(!fixed.is_empty() && fixed.iter().all(|&jumpstart| jumpstart == 3));
+  }
+
+  #[test]
+  fn test_decode_speed_overhead() {
+    // a skewed, noisy distribution that the optimal prefixes split finely
+    let nums = (0..20000_i64)
+      .map(|i| {
+        let x = (i * 7919) % 10007;
+        x * x / 1000
+      })
+      .collect::<Vec<_>>();
+    let compress = |overhead: f64| {
+      let mut compressor = Compressor::<i64>::from_config(
+        CompressorConfig::default().with_decode_speed_overhead(overhead)
+      );
+      compressor.header().unwrap();
+      let n_prefixes = match compressor.chunk(&nums).unwrap().prefix_metadata {
+        PrefixMetadata::Simple { prefixes } => prefixes.len(),
+        _ => panic!("expected simple prefix metadata"),
+      };
+      compressor.footer().unwrap();
+      let bytes = compressor.drain_bytes();
+      assert_eq!(crate::auto_decompress::<i64>(&bytes).unwrap(), nums);
+      (n_prefixes, bytes.len())
+    };
+    let (optimal_n_prefixes, optimal_size) = compress(0.0);
+    let (n_prefixes, size) = compress(0.02);
+    assert!(n_prefixes < optimal_n_prefixes);
+    // the overhead bounds the estimated size, which is close to the real one
+    assert!(size as f64 <= optimal_size as f64 * 1.025);
+    assert!(compress(1.0).0 <= n_prefixes);
+
+    let invalid = CompressorConfig::default().with_decode_speed_overhead(-0.1);
+    assert!(Compressor::<i64>::try_from_config(invalid).is_err());
+    let invalid = CompressorConfig::default().with_decode_speed_overhead(f64::NAN);
+    assert!(Compressor::<i64>::try_from_config(invalid).is_err());
+  }
+
+  #[test]
+  fn test_training_sample_size() {
+    // the extremes and the cluster of multiples of 7 are rarely sampled
+    let mut nums = (0..100000_i64)
+      .map(|i| {
+        let x = (i * 7919) % 10007;
+        if x < 10 { i * 7 } else { x * x / 1000 }
+      })
+      .collect::<Vec<_>>();
+    nums[12345] = i64::MIN;
+    nums[54321] = i64::MAX;
+    let compress = |config: CompressorConfig| {
+      let mut compressor = Compressor::<i64>::from_config(config);
+      compressor.header().unwrap();
+      let prefixes = match compressor.chunk(&nums).unwrap().prefix_metadata {
+        PrefixMetadata::Simple { prefixes } => prefixes,
+        _ => panic!("expected simple prefix metadata"),
+      };
+      assert_eq!(prefixes.iter().map(|p| p.count).sum::<usize>(), nums.len());
+      compressor.footer().unwrap();
+      let bytes = compressor.drain_bytes();
+      assert_eq!(crate::auto_decompress::<i64>(&bytes).unwrap(), nums);
+      bytes.len()
+    };
+    let full_size = compress(CompressorConfig::default());
+    // even a tiny sample must cover every number
+    for sample_size in [1, 100, 10000] {
+      let size = compress(CompressorConfig::default().with_training_sample_size(Some(sample_size)));
+      if sample_size == 10000 {
+        assert!((size as f64) < full_size as f64 * 1.05);
+      }
+    }
+    // delta encoding and run lengths
+    let bursty = (0..10000_i64).map(|i| if i % 10 < 8 { 5 } else { i }).collect::<Vec<_>>();
+    for config in [
+      CompressorConfig::default().with_delta_encoding_order(1),
+      CompressorConfig::default(),
+    ] {
+      let mut compressor = Compressor::<i64>::from_config(config.with_training_sample_size(Some(2000)));
+      let bytes = compressor.simple_compress(&bursty);
+      assert_eq!(crate::auto_decompress::<i64>(&bytes).unwrap(), bursty);
+    }
+
+    let invalid = CompressorConfig::default().with_training_sample_size(Some(0));
+    assert!(Compressor::<i64>::try_from_config(invalid).is_err());
+  }
+
+  #[test]
+  fn test_from_level() {
+    let default = CompressorConfig::default();
+    let level_8 = CompressorConfig::from_level(DEFAULT_COMPRESSION_LEVEL);
+    assert_eq!(level_8.compression_level, default.compression_level);
+    assert_eq!(level_8.use_gcds, default.use_gcds);
+    assert_eq!(level_8.training_sample_size, default.training_sample_size);
+
+    let nums = (0..100000_i64)
+      .map(|i| {
+        let x = (i * 7919) % 10007;
+        x * x / 1000
+      })
+      .collect::<Vec<_>>();
+    let sizes = [0, 3, 6, 12].map(|level| {
+      let config = CompressorConfig::from_level(level);
+      let bytes = Compressor::<i64>::try_from_config(config).unwrap().simple_compress(&nums);
+      assert_eq!(crate::auto_decompress::<i64>(&bytes).unwrap(), nums);
+      bytes.len()
+    });
+    assert!(sizes[0] > sizes[3]);
+    assert!(Compressor::<i64>::try_from_config(CompressorConfig::from_level(13)).is_err());
+  }
+
+  #[test]
+  fn test_float_canonicalization() {
+    let nums = (0..3000)
+      .map(|i| match i % 3 {
+        0 => 0.0,
+        1 => -0.0,
+        _ => f64::from_bits(i) * if i % 2 == 0 { 1.0 } else { -1.0 },
+      })
+      .collect::<Vec<_>>();
+    let compress = |config: CompressorConfig| {
+      Compressor::<f64>::from_config(config).simple_compress(&nums)
+    };
+    let exact = compress(CompressorConfig::default());
+    let exact_nums = crate::auto_decompress::<f64>(&exact).unwrap();
+    assert!(exact_nums.iter().zip(&nums).all(|(x, y)| x.to_bits() == y.to_bits()));
+
+    let canonical = compress(
+      CompressorConfig::default()
+        .with_canonicalize_negative_zero(true)
+        .with_flush_subnormals(true)
+    );
+    assert!(canonical.len() < exact.len());
+    let canonical_nums = crate::auto_decompress::<f64>(&canonical).unwrap();
+    assert!(canonical_nums.iter().all(|x| x.to_bits() == 0));
+    let mut decompressor = crate::Decompressor::<f64>::default();
+    decompressor.extend_bytes(&canonical);
+    let flags = decompressor.header().unwrap();
+    assert!(flags.canonicalize_negative_zero && flags.flush_subnormals);
+
+    // integers are unaffected
+    let ints = [0_i64, -1, 1];
+    let bytes = Compressor::<i64>::from_config(
+      CompressorConfig::default().with_canonicalize_negative_zero(true)
+    ).simple_compress(&ints);
+    assert_eq!(crate::auto_decompress::<i64>(&bytes).unwrap(), ints);
+  }
+
+  #[test]
+  fn test_gcd_hints() {
+    // minute-aligned millisecond timestamps with a few unaligned ones
+    let nums = (0..20000_i64)
+      .map(|i| {
+        let minutes = 28_000_000 + (i * 7919) % 5000;
+        minutes * 60000 + if i % 1000 == 0 { 17 } else { 0 }
+      })
+      .collect::<Vec<_>>();
+    let compress = |config: CompressorConfig| {
+      let bytes = Compressor::<i64>::try_from_config(config).unwrap().simple_compress(&nums);
+      assert_eq!(crate::auto_decompress::<i64>(&bytes).unwrap(), nums);
+      bytes.len()
+    };
+    let size = compress(CompressorConfig::default());
+    let hinted_size = compress(CompressorConfig::default().with_gcd_hints(vec![1000, 60000]));
+    // prefixes of aligned numbers use a hint, which is no better than their
+    // exact GCD here
+    assert!(hinted_size <= size * 101 / 100);
+    compress(
+      CompressorConfig::default()
+        .with_gcd_hints(vec![60000])
+        .with_delta_encoding_order(1)
+    );
+    let invalid = CompressorConfig::default().with_gcd_hints(vec![0]);
+    assert!(Compressor::<i64>::try_from_config(invalid).is_err());
+  }
+
+  #[test]
+  fn test_affine_transforms() {
+    // a sensor scaled by 37 with a bias of 5
+    let nums = (0..5000_i64)
+      .map(|i| 37 * ((i * 7919) % 3000 + i / 10) + 5)
+      .collect::<Vec<_>>();
+    let compress = |config: CompressorConfig| {
+      let mut compressor = Compressor::<i64>::from_config(config);
+      compressor.header().unwrap();
+      let meta = compressor.chunk(&nums).unwrap();
+      compressor.footer().unwrap();
+      let bytes = compressor.drain_bytes();
+      assert_eq!(crate::auto_decompress::<i64>(&bytes).unwrap(), nums);
+      (meta.affine_transform, bytes.len())
+    };
+    for config in [
+      CompressorConfig::default(),
+      CompressorConfig::default().with_use_gcds(false),
+      CompressorConfig::default().with_delta_encoding_order(1),
+      CompressorConfig::default().with_use_raw_fallback(true),
+    ] {
+      let (transform, size) = compress(config.clone());
+      assert_eq!(transform, None);
+      let (transform, affine_size) = compress(config.with_use_affine_transforms(true));
+      assert_eq!(transform, Some(AffineTransform { scale: 37, offset: 5 }));
+      // prefix GCDs already capture most of the spacing, so the transform
+      // may only cost its metadata
+      assert!(affine_size <= size * 102 / 100, "{} > {}", affine_size, size);
+    }
+    // without GCDs, only the affine transform captures the spacing
+    let (_, size) = compress(CompressorConfig::default().with_use_gcds(false));
+    let (_, affine_size) = compress(
+      CompressorConfig::default()
+        .with_use_gcds(false)
+        .with_use_affine_transforms(true)
+    );
+    assert!(affine_size < size * 9 / 10);
+
+    // floats in steps of 0.25 apart from a decimal offset
+    let floats = (0..1000).map(|i| 0.1 + (i % 77) as f64 * 0.25).collect::<Vec<_>>();
+    let config = CompressorConfig::default()
+      .with_use_decimal_multipliers(true)
+      .with_use_affine_transforms(true)
+      .with_use_xor_encoding(true);
+    let mut compressor = Compressor::<f64>::from_config(config);
+    compressor.header().unwrap();
+    let meta = compressor.chunk(&floats).unwrap();
+    compressor.footer().unwrap();
+    let bytes = compressor.drain_bytes();
+    assert_eq!(meta.decimal_exponent, Some(2));
+    assert_eq!(meta.affine_transform.map(|transform| transform.scale), Some(25));
+    let decompressed = crate::auto_decompress::<f64>(&bytes).unwrap();
+    assert!(decompressed.iter().zip(&floats).all(|(x, y)| x.to_bits() == y.to_bits()));
+
+    // floats without a decimal exponent keep their bits
+    let mut compressor = Compressor::<f64>::from_config(
+      CompressorConfig::default().with_use_affine_transforms(true)
+    );
+    compressor.header().unwrap();
+    let meta = compressor.chunk(&[1.0, 3.0, 5.0]).unwrap();
+    assert_eq!(meta.affine_transform, None);
+  }
 }
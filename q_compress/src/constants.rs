@@ -1,15 +1,45 @@
 pub const MAGIC_HEADER: [u8; 4] = [113, 99, 111, 33]; // ascii for qco!
 pub const MAGIC_CHUNK_BYTE: u8 = 44; // ,
 pub const MAGIC_TERMINATION_BYTE: u8 = 46; // .
+pub const MAGIC_MULTI_SERIES_HEADER: [u8; 4] = [113, 99, 109, 33]; // ascii for qcm!
+pub const MAGIC_TUPLES_HEADER: [u8; 4] = [113, 99, 116, 33]; // ascii for qct!
+pub const MAGIC_MULTI_COLUMN_HEADER: [u8; 4] = [113, 99, 99, 33]; // ascii for qcc!
+pub const MAGIC_MIXED_HEADER: [u8; 4] = [113, 99, 120, 33]; // ascii for qcx!
+pub const MAGIC_SNAPSHOT_HEADER: [u8; 4] = [113, 99, 115, 33]; // ascii for qcs!
 
 pub const MAX_DELTA_ENCODING_ORDER: usize = 7;
 pub const BITS_TO_ENCODE_DELTA_ENCODING_ORDER: usize = 3;
+pub const MAX_DELTA_LAG: usize = (1 << 16) - 1;
+pub const BITS_TO_ENCODE_DELTA_LAG: usize = 16;
 pub const MAX_ENTRIES: usize = (1 << 24) - 1;
 pub const BITS_TO_ENCODE_N_ENTRIES: usize = 24;
 pub const BITS_TO_ENCODE_N_PREFIXES: usize = 15;
 pub const MAX_JUMPSTART: usize = BITS_TO_ENCODE_N_ENTRIES;
 pub const BITS_TO_ENCODE_JUMPSTART: usize = 5;
 pub const BITS_TO_ENCODE_COMPRESSED_BODY_SIZE: usize = 32;
+pub const BYTES_PER_CHUNK_CHECKSUM: usize = 4;
+pub const MAX_DECIMAL_EXPONENT: usize = 22;
+pub const BITS_TO_ENCODE_DECIMAL_EXPONENT: usize = 5;
+pub const BITS_TO_ENCODE_DROPPED_MANTISSA_BITS: usize = 6;
+pub const BITS_TO_ENCODE_RAW_BIT_WIDTH: usize = 8;
+pub const BITS_TO_ENCODE_AFFINE_SCALE_WIDTH: usize = 8;
+pub const BYTES_PER_FILE_CHECKSUM: usize = 4;
+pub const MAX_N_SERIES: usize = (1 << 16) - 1;
+pub const BITS_TO_ENCODE_N_SERIES: usize = 16;
+pub const BITS_TO_ENCODE_SERIES_BYTE_SIZE: usize = 64;
+pub const MAX_ARITY: usize = (1 << 16) - 1;
+pub const BITS_TO_ENCODE_ARITY: usize = 16;
+pub const BITS_TO_ENCODE_DIMENSION_BYTE_SIZE: usize = 64;
+pub const MAX_N_COLUMNS: usize = (1 << 16) - 1;
+pub const BITS_TO_ENCODE_N_COLUMNS: usize = 16;
+pub const MAX_COLUMN_NAME_LEN: usize = (1 << 16) - 1;
+pub const BITS_TO_ENCODE_COLUMN_NAME_LEN: usize = 16;
+pub const BITS_TO_ENCODE_COLUMN_BYTE_SIZE: usize = 64;
+pub const MAX_N_USER_METADATA_ENTRIES: usize = (1 << 16) - 1;
+pub const BITS_TO_ENCODE_N_USER_METADATA_ENTRIES: usize = 16;
+pub const MAX_USER_METADATA_LEN: usize = (1 << 24) - 1;
+pub const BITS_TO_ENCODE_USER_METADATA_LEN: usize = 24;
+pub const BITS_TO_ENCODE_SNAPSHOT_USIZE: usize = 64;
 
 // MAX_PREFIX_TABLE_SIZE_LOG is a performance tuning parameter
 // Too high, and we use excessive memory and in some cases hurt performance.
@@ -41,6 +71,11 @@ mod tests {
     assert_can_encode(BITS_TO_ENCODE_DELTA_ENCODING_ORDER, MAX_DELTA_ENCODING_ORDER);
   }
 
+  #[test]
+  fn test_bits_to_encode_delta_lag() {
+    assert_can_encode(BITS_TO_ENCODE_DELTA_LAG, MAX_DELTA_LAG);
+  }
+
   #[test]
   fn test_bits_to_encode_n_entries() {
     assert_can_encode(BITS_TO_ENCODE_N_ENTRIES, MAX_ENTRIES);
@@ -51,6 +86,46 @@ mod tests {
     assert_can_encode(BITS_TO_ENCODE_JUMPSTART, MAX_JUMPSTART);
   }
 
+  #[test]
+  fn test_bits_to_encode_decimal_exponent() {
+    assert_can_encode(BITS_TO_ENCODE_DECIMAL_EXPONENT, MAX_DECIMAL_EXPONENT);
+  }
+
+  #[test]
+  fn test_bits_to_encode_dropped_mantissa_bits() {
+    // enough for any dropped bits less than 64, the largest physical bits
+    // of a float
+    assert_can_encode(BITS_TO_ENCODE_DROPPED_MANTISSA_BITS, 63);
+  }
+
+  #[test]
+  fn test_bits_to_encode_raw_bit_width() {
+    // enough for the full width of a u128, the largest unsigned type
+    assert_can_encode(BITS_TO_ENCODE_RAW_BIT_WIDTH, 128);
+  }
+
+  #[test]
+  fn test_bits_to_encode_n_series() {
+    assert_can_encode(BITS_TO_ENCODE_N_SERIES, MAX_N_SERIES);
+  }
+
+  #[test]
+  fn test_bits_to_encode_arity() {
+    assert_can_encode(BITS_TO_ENCODE_ARITY, MAX_ARITY);
+  }
+
+  #[test]
+  fn test_bits_to_encode_columns() {
+    assert_can_encode(BITS_TO_ENCODE_N_COLUMNS, MAX_N_COLUMNS);
+    assert_can_encode(BITS_TO_ENCODE_COLUMN_NAME_LEN, MAX_COLUMN_NAME_LEN);
+  }
+
+  #[test]
+  fn test_bits_to_encode_user_metadata() {
+    assert_can_encode(BITS_TO_ENCODE_N_USER_METADATA_ENTRIES, MAX_N_USER_METADATA_ENTRIES);
+    assert_can_encode(BITS_TO_ENCODE_USER_METADATA_LEN, MAX_USER_METADATA_LEN);
+  }
+
   #[test]
   fn test_prefix_table_size_fits_in_word() {
     assert!(MAX_PREFIX_TABLE_SIZE_LOG > 0);
@@ -1,4 +1,6 @@
-use std::convert::TryInto;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryInto;
 
 use crate::data_types::{NumberLike, SignedLike};
 use crate::errors::QCompressResult;
@@ -0,0 +1,155 @@
+use core::convert::TryFrom;
+
+use alloc::format;
+
+use crate::constants::MAGIC_HEADER;
+use crate::data_types::{NumberLike, TimestampMicros, TimestampNanos};
+use crate::errors::{QCompressError, QCompressResult};
+
+/// The data type of a .qco file, as identified by the byte right after its
+/// 4-byte magic header.
+///
+/// This lets tools decide which [`NumberLike`] type to decompress a file
+/// as, or describe it, without knowing it in advance.
+/// Each data type's header byte is part of the .qco format and will never
+/// change.
+/// The 128-bit integer and 96-bit timestamp variants exist regardless of
+/// the `timestamps_96` feature, though decompressing them requires it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DataType {
+  Bool,
+  F32,
+  F64,
+  I16,
+  I32,
+  I64,
+  I128,
+  TimestampMicros,
+  TimestampMicros96,
+  TimestampNanos,
+  TimestampNanos96,
+  U16,
+  U32,
+  U64,
+  U128,
+}
+
+// these types are only implemented with the timestamps_96 feature
+const TIMESTAMP_NANOS_96_HEADER_BYTE: u8 = 8;
+const TIMESTAMP_MICROS_96_HEADER_BYTE: u8 = 9;
+const I128_HEADER_BYTE: u8 = 10;
+const U128_HEADER_BYTE: u8 = 11;
+
+const ALL: [DataType; 15] = [
+  DataType::Bool,
+  DataType::F32,
+  DataType::F64,
+  DataType::I16,
+  DataType::I32,
+  DataType::I64,
+  DataType::I128,
+  DataType::TimestampMicros,
+  DataType::TimestampMicros96,
+  DataType::TimestampNanos,
+  DataType::TimestampNanos96,
+  DataType::U16,
+  DataType::U32,
+  DataType::U64,
+  DataType::U128,
+];
+
+impl DataType {
+  /// Returns the data type's header byte.
+  pub fn header_byte(self) -> u8 {
+    match self {
+      DataType::Bool => bool::HEADER_BYTE,
+      DataType::F32 => f32::HEADER_BYTE,
+      DataType::F64 => f64::HEADER_BYTE,
+      DataType::I16 => i16::HEADER_BYTE,
+      DataType::I32 => i32::HEADER_BYTE,
+      DataType::I64 => i64::HEADER_BYTE,
+      DataType::I128 => I128_HEADER_BYTE,
+      DataType::TimestampMicros => TimestampMicros::HEADER_BYTE,
+      DataType::TimestampMicros96 => TIMESTAMP_MICROS_96_HEADER_BYTE,
+      DataType::TimestampNanos => TimestampNanos::HEADER_BYTE,
+      DataType::TimestampNanos96 => TIMESTAMP_NANOS_96_HEADER_BYTE,
+      DataType::U16 => u16::HEADER_BYTE,
+      DataType::U32 => u32::HEADER_BYTE,
+      DataType::U64 => u64::HEADER_BYTE,
+      DataType::U128 => U128_HEADER_BYTE,
+    }
+  }
+
+  /// Returns the data type of a [`NumberLike`] type.
+  /// Will return an error for custom `NumberLike` implementations whose
+  /// header byte isn't one of this crate's.
+  pub fn of<T: NumberLike>() -> QCompressResult<Self> {
+    Self::try_from(T::HEADER_BYTE)
+  }
+
+  /// Returns the data type of a .qco file from its first bytes.
+  /// Will return an error if there are too few bytes, the magic header
+  /// doesn't match, or the data type is unknown.
+  pub fn from_file_bytes(bytes: &[u8]) -> QCompressResult<Self> {
+    let magic_len = MAGIC_HEADER.len();
+    if bytes.len() <= magic_len {
+      return Err(QCompressError::insufficient_data(
+        "not enough bytes for the magic header and data type byte"
+      ));
+    }
+    if bytes[..magic_len] != MAGIC_HEADER {
+      return Err(QCompressError::corruption(format!(
+        "magic header does not match {:?}; instead found {:?}",
+        MAGIC_HEADER,
+        &bytes[..magic_len],
+      )));
+    }
+    Self::try_from(bytes[magic_len])
+  }
+}
+
+impl TryFrom<u8> for DataType {
+  type Error = QCompressError;
+
+  /// Returns the data type with the given header byte.
+  /// Will return a compatibility error if no data type has it, since it may
+  /// have been added in a newer version.
+  fn try_from(header_byte: u8) -> QCompressResult<Self> {
+    ALL.iter()
+      .copied()
+      .find(|dtype| dtype.header_byte() == header_byte)
+      .ok_or_else(|| QCompressError::compatibility(format!(
+        "unknown data type byte {}",
+        header_byte,
+      )))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Compressor;
+
+  #[test]
+  fn test_header_bytes() {
+    for dtype in ALL {
+      assert_eq!(DataType::try_from(dtype.header_byte()).unwrap(), dtype);
+    }
+    assert_eq!(DataType::of::<u32>().unwrap(), DataType::U32);
+    #[cfg(feature = "timestamps_96")]
+    {
+      use crate::data_types::{TimestampMicros96, TimestampNanos96};
+      assert_eq!(DataType::of::<TimestampMicros96>().unwrap(), DataType::TimestampMicros96);
+      assert_eq!(DataType::of::<TimestampNanos96>().unwrap(), DataType::TimestampNanos96);
+      assert_eq!(DataType::of::<i128>().unwrap(), DataType::I128);
+      assert_eq!(DataType::of::<u128>().unwrap(), DataType::U128);
+    }
+    assert!(DataType::try_from(200).is_err());
+
+    let bytes = Compressor::<f32>::default().simple_compress(&[1.0, 2.0]);
+    assert_eq!(DataType::from_file_bytes(&bytes).unwrap(), DataType::F32);
+    assert!(DataType::from_file_bytes(&bytes[..4]).is_err());
+    assert!(DataType::from_file_bytes(&bytes[1..]).is_err());
+  }
+}
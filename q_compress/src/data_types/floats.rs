@@ -1,17 +1,30 @@
-use std::convert::TryInto;
+use alloc::vec::Vec;
+use core::convert::TryInto;
 
+use crate::constants::MAX_DECIMAL_EXPONENT;
 use crate::data_types::NumberLike;
 use crate::errors::QCompressResult;
+use crate::float_math;
+use crate::quantization::{Quantization, Tolerance};
+
+// 10^k is exactly representable as an f64 for k <= 22 and as an f32 for
+// k <= 10
+const POWERS_OF_TEN: [f64; MAX_DECIMAL_EXPONENT + 1] = [
+  1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11,
+  1e12, 1e13, 1e14, 1e15, 1e16, 1e17, 1e18, 1e19, 1e20, 1e21, 1e22,
+];
 
 // Note that in all conversions between float and unsigned int, we are using
 // the unsigned int to indicate an offset.
 // For instance, since f32 has 23 fraction bits, here we want 1.0 + 3_u32 to be
 // 1.0 + (3.0 * 2.0 ^ -23).
 macro_rules! impl_float_number {
-  ($t: ty, $signed: ty, $unsigned: ty, $bits: expr, $sign_bit_mask: expr, $header_byte: expr) => {
+  ($t: ty, $signed: ty, $unsigned: ty, $bits: expr, $sign_bit_mask: expr, $exponent_mask: expr, $mantissa_bits: expr, $header_byte: expr, $max_decimal_exponent: expr, $max_exact_int: expr) => {
     impl NumberLike for $t {
       const HEADER_BYTE: u8 = $header_byte;
       const PHYSICAL_BITS: usize = $bits;
+      const MAX_DECIMAL_EXPONENT: Option<u32> = Some($max_decimal_exponent);
+      const MANTISSA_BITS: Option<usize> = Some($mantissa_bits);
 
       type Signed = $signed;
       type Unsigned = $unsigned;
@@ -46,6 +59,114 @@ macro_rules! impl_float_number {
         }
       }
 
+      fn is_nan(self) -> bool {
+        <$t>::is_nan(self)
+      }
+
+      fn canonicalized(self, negative_zero: bool, subnormals: bool) -> Self {
+        let mut res = self;
+        if subnormals && res.is_subnormal() {
+          // keep the sign, so -0.0 only becomes +0.0 if asked
+          res = Self::from_bits(res.to_bits() & $sign_bit_mask);
+        }
+        if negative_zero && res == 0.0 {
+          res = 0.0;
+        }
+        res
+      }
+
+      fn to_decimal_int(self, decimal_exponent: u32) -> Option<Self::Signed> {
+        let multiplier = POWERS_OF_TEN[decimal_exponent as usize] as $t;
+        let scaled = self * multiplier;
+        // this also rules out NaNs and infinities
+        if !(-$max_exact_int..=$max_exact_int).contains(&scaled) {
+          return None;
+        }
+        // round half away from zero, since we don't have `round` without std
+        let int = if scaled < 0.0 {
+          -((0.5 - scaled) as $signed)
+        } else {
+          (scaled + 0.5) as $signed
+        };
+        if Self::from_decimal_int(int, decimal_exponent).to_bits() == self.to_bits() {
+          Some(int)
+        } else {
+          None
+        }
+      }
+
+      fn from_decimal_int(decimal_int: Self::Signed, decimal_exponent: u32) -> Self {
+        decimal_int as $t / POWERS_OF_TEN[decimal_exponent as usize] as $t
+      }
+
+      fn quantization_for(tolerance: Tolerance) -> Option<Quantization> {
+        match tolerance {
+          Tolerance::Absolute(tolerance) => Some(Quantization::Step(2.0 * tolerance)),
+          Tolerance::Relative(tolerance) => {
+            // rounding off k of the mantissa bits of a normal float changes
+            // it by at most 2^(k - mantissa_bits - 1) of its magnitude
+            let dropped_bits = float_math::floor(float_math::log2(tolerance)) as i64 + $mantissa_bits + 1;
+            if dropped_bits > 0 {
+              Some(Quantization::DroppedMantissaBits(dropped_bits.min($mantissa_bits) as u32))
+            } else {
+              None
+            }
+          },
+        }
+      }
+
+      fn quantize(self, quantization: Quantization) -> Option<Self::Signed> {
+        match quantization {
+          Quantization::Step(step) => {
+            let step = step as $t;
+            let scaled = self / step;
+            // this also rules out NaNs and infinities
+            if !(-$max_exact_int..=$max_exact_int).contains(&scaled) {
+              return None;
+            }
+            let int = if scaled < 0.0 {
+              -((0.5 - scaled) as $signed)
+            } else {
+              (scaled + 0.5) as $signed
+            };
+            let half_step = step / 2.0;
+            let error = Self::dequantize(int, quantization) - self;
+            if (-half_step..=half_step).contains(&error) {
+              Some(int)
+            } else {
+              None
+            }
+          },
+          Quantization::DroppedMantissaBits(dropped_bits) => {
+            let bits = self.to_bits();
+            let low_mask: $unsigned = (1 << dropped_bits) - 1;
+            let exponent = bits & $exponent_mask;
+            if exponent == 0 || exponent == $exponent_mask {
+              // subnormals, zeros, infinities, and NaNs have no bound on
+              // relative error, so we only accept them if they're unchanged
+              return if bits & low_mask == 0 {
+                Some(self.to_signed() >> dropped_bits)
+              } else {
+                None
+              };
+            }
+            let rounded = (bits + (1 << (dropped_bits - 1))) & !low_mask;
+            if rounded & $exponent_mask == $exponent_mask {
+              // rounded up to infinity
+              return None;
+            }
+            Some((rounded as $signed) >> dropped_bits)
+          },
+        }
+      }
+
+      fn dequantize(int: Self::Signed, quantization: Quantization) -> Self {
+        match quantization {
+          Quantization::Step(step) => int as $t * step as $t,
+          Quantization::DroppedMantissaBits(dropped_bits) => Self::from_signed(int << dropped_bits),
+        }
+      }
+
       fn to_bytes(self) -> Vec<u8> {
         self.to_be_bytes().to_vec()
       }
@@ -57,5 +178,60 @@ macro_rules! impl_float_number {
   }
 }
 
-impl_float_number!(f32, i32, u32, 32, 1_u32 << 31, 6);
-impl_float_number!(f64, i64, u64, 64, 1_u64 << 63, 5);
+impl_float_number!(f32, i32, u32, 32, 1_u32 << 31, 0xff_u32 << 23, 23, 6, 10, 16777216.0);
+impl_float_number!(f64, i64, u64, 64, 1_u64 << 63, 0x7ff_u64 << 52, 52, 5, 22, 9007199254740992.0);
+
+#[cfg(test)]
+mod tests {
+  use crate::data_types::NumberLike;
+  use crate::quantization::{Quantization, Tolerance};
+
+  #[test]
+  fn test_decimal_ints() {
+    assert_eq!(1.25_f64.to_decimal_int(1), None);
+    assert_eq!(1.25_f64.to_decimal_int(2), Some(125));
+    assert_eq!(f64::from_decimal_int(125, 2), 1.25);
+    assert_eq!((-0.3_f64).to_decimal_int(1), Some(-3));
+    assert_eq!(f32::from_decimal_int((-0.7_f32).to_decimal_int(3).unwrap(), 3), -0.7);
+
+    assert_eq!((-0.0_f64).to_decimal_int(0), None);
+    assert_eq!(f64::NAN.to_decimal_int(0), None);
+    assert_eq!(f64::INFINITY.to_decimal_int(0), None);
+    assert_eq!(1e300_f64.to_decimal_int(0), None);
+    assert_eq!(0.1_f32.to_decimal_int(10), None);
+  }
+
+  #[test]
+  fn test_quantize() {
+    let step = f64::quantization_for(Tolerance::Absolute(0.05)).unwrap();
+    assert_eq!(step, Quantization::Step(0.1));
+    assert_eq!(1.26_f64.quantize(step), Some(13));
+    assert_eq!(f64::dequantize(-4, step), -0.4);
+    assert_eq!(f64::NAN.quantize(step), None);
+
+    let dropped = f32::quantization_for(Tolerance::Relative(1e-3)).unwrap();
+    assert_eq!(dropped, Quantization::DroppedMantissaBits(14));
+    for x in [1.0_f32, -123.456, 1234.5678, 1e30, -0.0, f32::INFINITY] {
+      let y = f32::dequantize(x.quantize(dropped).unwrap(), dropped);
+      assert!((y - x).abs() <= 1e-3 * x.abs() || y == x, "{} became {}", x, y);
+    }
+    assert_eq!(f32::MAX.quantize(dropped), None);
+    assert_eq!(f32::from_bits(1).quantize(dropped), None);
+    assert_eq!(f64::quantization_for(Tolerance::Relative(1e-20)), None);
+  }
+
+  #[test]
+  fn test_canonicalized() {
+    let bits = |x: f64| x.to_bits();
+    let tiny = f64::from_bits(1);
+    assert_eq!(bits((-0.0_f64).canonicalized(false, false)), bits(-0.0));
+    assert_eq!(bits((-0.0_f64).canonicalized(true, false)), bits(0.0));
+    assert_eq!(bits((-tiny).canonicalized(true, false)), bits(-tiny));
+    assert_eq!(bits((-tiny).canonicalized(false, true)), bits(-0.0));
+    assert_eq!(bits((-tiny).canonicalized(true, true)), bits(0.0));
+    assert_eq!(bits(f64::MIN_POSITIVE.canonicalized(true, true)), bits(f64::MIN_POSITIVE));
+    assert!(f64::NAN.canonicalized(true, true).is_nan());
+    assert_eq!((-1.5_f32).canonicalized(true, true), -1.5);
+    assert_eq!((-0.0_f32).canonicalized(true, false).to_bits(), 0);
+  }
+}
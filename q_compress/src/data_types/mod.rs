@@ -1,14 +1,18 @@
-use std::fmt::{Debug, Display};
-use std::ops::{Add, BitAnd, BitOrAssign, Div, Mul, RemAssign, Shl, Shr, Sub};
+use alloc::vec::Vec;
+use core::fmt::{Debug, Display};
+use core::ops::{Add, BitAnd, BitOrAssign, BitXor, Div, Mul, RemAssign, Shl, Shr, Sub};
 
 use crate::bit_reader::BitReader;
 use crate::bit_writer::BitWriter;
 use crate::bits;
 use crate::errors::QCompressResult;
+use crate::quantization::{Quantization, Tolerance};
 
+pub use data_type::DataType;
 pub use timestamps::{TimestampMicros, TimestampNanos};
 
 mod boolean;
+mod data_type;
 mod floats;
 mod signeds;
 mod timestamps;
@@ -34,11 +38,23 @@ pub use timestamps_96::{TimestampMicros96, TimestampNanos96};
 /// each other and easily compressible, which would not be the case with
 /// unsigned integers.
 /// Note: API stability of `SignedLike` is not guaranteed.
-pub trait SignedLike {
+pub trait SignedLike: Send + Sync {
   const ZERO: Self;
 
   fn wrapping_add(self, other: Self) -> Self;
   fn wrapping_sub(self, other: Self) -> Self;
+
+  /// Replaces each element of `xs` with `acc` plus the sum of the elements
+  /// before it, and returns `acc` plus the sum of all of them.
+  /// This undoes one order of delta encoding.
+  fn exclusive_prefix_sum(mut acc: Self, xs: &mut [Self]) -> Self where Self: Copy {
+    for x in xs.iter_mut() {
+      let next = acc.wrapping_add(*x);
+      *x = acc;
+      acc = next;
+    }
+    acc
+  }
 }
 
 /// Trait for data types that behave like unsigned integers.
@@ -51,9 +67,9 @@ pub trait SignedLike {
 ///
 /// Note: API stability of `UnsignedLike` is not guaranteed.
 pub trait UnsignedLike: Add<Output=Self> + BitAnd<Output=Self> + BitOrAssign +
-Copy + Debug + Display + Div<Output=Self> + Mul<Output = Self> + Ord +
+BitXor<Output=Self> + Copy + Debug + Display + Div<Output=Self> + Mul<Output = Self> + Ord +
 PartialOrd + RemAssign + Shl<usize, Output=Self> + Shr<usize, Output=Self> +
-Sub<Output=Self> {
+Sub<Output=Self> + Send + Sync {
   const ZERO: Self;
   const ONE: Self;
   const MAX: Self;
@@ -65,6 +81,9 @@ Sub<Output=Self> {
 
   fn to_f64(self) -> f64;
 
+  fn wrapping_add(self, other: Self) -> Self;
+  fn wrapping_mul(self, other: Self) -> Self;
+
   /// Shifts the unsigned integer right and returns its lowest bits as a
   /// `usize`.
   /// For example,
@@ -107,8 +126,11 @@ Sub<Output=Self> {
 /// uncompressed representation is used to store metadata in each chunk of the
 /// Quantile Compression format.
 ///
+/// Numbers must also be `Send` and `Sync` so that chunks can be compressed
+/// and decompressed in parallel.
+///
 /// Note: API stability of `NumberLike` is not guaranteed.
-pub trait NumberLike: Copy + Debug + Display + Default + PartialEq + 'static {
+pub trait NumberLike: Copy + Debug + Display + Default + PartialEq + Send + Sync + 'static {
   /// A number from 0-255 that corresponds to the number's data type.
   ///
   /// Each `NumberLike` implementation should have a different `HEADER_BYTE`.
@@ -158,6 +180,75 @@ pub trait NumberLike: Copy + Debug + Display + Default + PartialEq + 'static {
   /// Used during delta decoding to convert back from a signed integer.
   fn from_signed(signed: Self::Signed) -> Self;
 
+  /// The number of mantissa bits at the bottom of the unsigned
+  /// representation, or `None` if this type has no mantissa.
+  /// Only floats have mantissas, which float split encoding compresses
+  /// separately from their signs and exponents.
+  const MANTISSA_BITS: Option<usize> = None;
+
+  /// Used during compression with NaN positions to find the numbers to
+  /// strip out of the chunk body.
+  /// Only floats have NaNs.
+  fn is_nan(self) -> bool {
+    false
+  }
+
+  /// Used during compression with float canonicalization to replace -0.0
+  /// with +0.0 and/or flush subnormals to zero.
+  /// Only floats are changed.
+  fn canonicalized(self, negative_zero: bool, subnormals: bool) -> Self {
+    let _ = (negative_zero, subnormals);
+    self
+  }
+
+  /// The largest `decimal_exponent` this type supports for decimal
+  /// multipliers, or `None` if it doesn't support them.
+  /// Only floats support decimal multipliers.
+  const MAX_DECIMAL_EXPONENT: Option<u32> = None;
+
+  /// Used during compression with decimal multipliers to convert
+  /// `self * 10^decimal_exponent` to a signed integer.
+  /// Returns `None` unless that product is an integer that
+  /// `from_decimal_int` converts back to exactly `self`.
+  fn to_decimal_int(self, _decimal_exponent: u32) -> Option<Self::Signed> {
+    None
+  }
+
+  /// Used during decompression with decimal multipliers to convert back
+  /// from the result of `to_decimal_int`.
+  fn from_decimal_int(decimal_int: Self::Signed, _decimal_exponent: u32) -> Self {
+    Self::from_signed(decimal_int)
+  }
+
+  /// Used during lossy compression to choose how to round numbers within
+  /// `tolerance`, or `None` if this type doesn't support lossy compression.
+  /// Only floats support lossy compression.
+  fn quantization_for(_tolerance: Tolerance) -> Option<Quantization> {
+    None
+  }
+
+  /// Used during lossy compression to round the number to a signed
+  /// integer.
+  /// Returns `None` unless `dequantize` converts that integer back to a
+  /// number within the tolerance `quantization` was chosen for.
+  fn quantize(self, _quantization: Quantization) -> Option<Self::Signed> {
+    None
+  }
+
+  /// Used during decompression of lossy chunks to convert back from the
+  /// result of `quantize`.
+  fn dequantize(int: Self::Signed, _quantization: Quantization) -> Self {
+    Self::from_signed(int)
+  }
+
+  /// Used at the end of decompressing each batch of numbers to return a
+  /// corruption error if any of them are invalid for this type.
+  /// Only 96-bit timestamps have invalid values, since their in-memory
+  /// representation is wider than their range.
+  fn validate_batch(_nums: &[Self]) -> QCompressResult<()> {
+    Ok(())
+  }
+
   /// Returns an uncompressed representation for the number.
   fn to_bytes(self) -> Vec<u8>;
 
@@ -1,10 +1,16 @@
-use std::convert::TryInto;
+use alloc::vec::Vec;
+use core::convert::TryInto;
 
 use crate::data_types::{NumberLike, SignedLike};
 use crate::errors::QCompressResult;
+#[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+use crate::simd;
 
 macro_rules! impl_signed {
   ($t: ty, $unsigned: ty, $header_byte: expr) => {
+    impl_signed!($t, $unsigned, $header_byte, {});
+  };
+  ($t: ty, $unsigned: ty, $header_byte: expr, {$($simd_fns: item)*}) => {
     impl SignedLike for $t {
       const ZERO: Self = 0;
 
@@ -15,6 +21,8 @@ macro_rules! impl_signed {
       fn wrapping_sub(self, other: Self) -> Self {
         self.wrapping_sub(other)
       }
+
+      $($simd_fns)*
     }
 
     impl NumberLike for $t {
@@ -52,7 +60,21 @@ macro_rules! impl_signed {
 }
 
 impl_signed!(i16, u16, 13);
+#[cfg(not(all(target_arch = "x86_64", target_feature = "sse2")))]
 impl_signed!(i32, u32, 3);
+#[cfg(not(all(target_arch = "x86_64", target_feature = "sse2")))]
 impl_signed!(i64, u64, 1);
+#[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+impl_signed!(i32, u32, 3, {
+  fn exclusive_prefix_sum(acc: Self, xs: &mut [Self]) -> Self {
+    simd::exclusive_prefix_sum_i32(acc, xs)
+  }
+});
+#[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+impl_signed!(i64, u64, 1, {
+  fn exclusive_prefix_sum(acc: Self, xs: &mut [Self]) -> Self {
+    simd::exclusive_prefix_sum_i64(acc, xs)
+  }
+});
 #[cfg(feature = "timestamps_96")]
 impl_signed!(i128, u128, 10);
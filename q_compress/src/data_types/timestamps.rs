@@ -1,5 +1,9 @@
-use std::convert::{TryFrom, TryInto};
-use std::fmt::{Display, Formatter};
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::fmt::{Display, Formatter};
+#[cfg(feature = "std")]
+use core::convert::TryFrom;
+#[cfg(feature = "std")]
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::data_types::NumberLike;
@@ -23,6 +27,7 @@ macro_rules! impl_timestamp {
     /// for this type.
     ///
     /// Provides conversions to/from `SystemTime`.
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
     pub struct $t(i64);
 
@@ -37,8 +42,8 @@ macro_rules! impl_timestamp {
 
       /// Returns a timestamp with the corresponding seconds and fractional
       /// nanoseconds since the Unix Epoch.
-      /// Will panic if the time specified is outside the valid range.
-      pub(crate) fn from_secs_and_nanos(seconds: i64, subsec_nanos: i64) -> QCompressResult<Self> {
+      /// Will return an error if the time specified is outside the valid range.
+      pub fn from_secs_and_nanos(seconds: i64, subsec_nanos: i64) -> QCompressResult<Self> {
         seconds.checked_mul($parts_per_sec)
           .and_then(|seconds_parts| seconds_parts.checked_add(subsec_nanos / Self::NS_PER_PART))
           .map($t::new)
@@ -46,7 +51,7 @@ macro_rules! impl_timestamp {
       }
 
       /// Returns the `(seconds, subsec_nanos)` since the Unix Epoch.
-      fn to_secs_and_nanos(self) -> (i64, i64) {
+      pub fn to_secs_and_nanos(self) -> (i64, i64) {
         let parts = self.0;
         let seconds = parts.div_euclid($parts_per_sec);
         let subsec_nanos = parts.rem_euclid($parts_per_sec) * Self::NS_PER_PART;
@@ -60,6 +65,7 @@ macro_rules! impl_timestamp {
       }
     }
 
+    #[cfg(feature = "std")]
     impl TryFrom<SystemTime> for $t {
       type Error = QCompressError;
 
@@ -82,6 +88,7 @@ macro_rules! impl_timestamp {
       }
     }
 
+    #[cfg(feature = "std")]
     impl From<$t> for SystemTime {
       fn from(value: $t) -> SystemTime {
         let (seconds, subsec_nanos) = value.to_secs_and_nanos();
@@ -100,7 +107,7 @@ macro_rules! impl_timestamp {
     }
 
     impl Display for $t {
-      fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+      fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(
           f,
           "Timestamp({}/{})",
@@ -149,7 +156,7 @@ impl_timestamp!(TimestampMicros, 1_000_000_i64, 15, "microsecond");
 
 #[cfg(test)]
 mod tests {
-  use std::convert::TryFrom;
+  use core::convert::TryFrom;
   use std::time::{Duration, SystemTime};
 
   use crate::data_types::{TimestampMicros, TimestampNanos};
@@ -1,5 +1,10 @@
-use std::convert::{TryFrom, TryInto};
-use std::fmt::{Display, Formatter};
+use alloc::{format, vec};
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::fmt::{Display, Formatter};
+#[cfg(feature = "std")]
+use core::convert::TryFrom;
+#[cfg(feature = "std")]
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::errors::{QCompressError, QCompressResult};
@@ -23,6 +28,7 @@ macro_rules! impl_timestamp_96 {
     /// other major tools today.
     ///
     /// Provides conversions to/from `SystemTime`.
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
     pub struct $t(i128);
 
@@ -73,11 +79,8 @@ macro_rules! impl_timestamp_96 {
       /// for seconds and 32-bit unsigned integer for the fractional part
       /// of the second. However, the in-memory representation uses a 128-bit
       /// signed integer for the total number of fractional parts.
-      /// It is theoretically possible for a corrupt delta-encoded file to
-      /// cause a decompressor to return invalid timestamps.
-      /// If you are concerned about a data corruption affecting such a case
-      /// without being noticed, you may want to `.validate()` every returned
-      /// timestamp.
+      /// Decompressors validate every timestamp they return, so a corrupt
+      /// file yields a corruption error rather than invalid timestamps.
       /// This is also done during `TryFrom` converter to `SystemTime`.
       pub fn validate(&self) -> QCompressResult<()> {
         if Self::is_valid(self.0) {
@@ -96,6 +99,7 @@ macro_rules! impl_timestamp_96 {
       }
     }
 
+    #[cfg(feature = "std")]
     impl From<SystemTime> for $t {
       fn from(system_time: SystemTime) -> Self {
         let (seconds, subsec_nanos) = match system_time.duration_since(UNIX_EPOCH) {
@@ -116,6 +120,7 @@ macro_rules! impl_timestamp_96 {
       }
     }
 
+    #[cfg(feature = "std")]
     impl TryFrom<$t> for SystemTime {
       type Error = QCompressError;
 
@@ -139,7 +144,7 @@ macro_rules! impl_timestamp_96 {
     }
 
     impl Display for $t {
-      fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+      fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(
           f,
           "Timestamp96({}/{})",
@@ -182,6 +187,18 @@ macro_rules! impl_timestamp_96 {
         let parts = (u128::from_be_bytes(full_bytes.try_into().unwrap()) as i128) + Self::MIN;
         Self::new(parts)
       }
+
+      fn validate_batch(nums: &[Self]) -> QCompressResult<()> {
+        // one branch per batch; only look for the culprit if there is one
+        let (min, max) = nums.iter().fold(
+          (i128::MAX, i128::MIN),
+          |(min, max), num| (min.min(num.0), max.max(num.0)),
+        );
+        if nums.is_empty() || (Self::is_valid(min) && Self::is_valid(max)) {
+          return Ok(());
+        }
+        nums.iter().try_for_each(|num| num.validate())
+      }
     }
   }
 }
@@ -191,7 +208,7 @@ impl_timestamp_96!(TimestampMicros96, 1_000_000_u32, 9, "microsecond");
 
 #[cfg(test)]
 mod tests {
-  use std::convert::TryFrom;
+  use core::convert::TryFrom;
   use std::time::{Duration, SystemTime};
   use crate::data_types::{TimestampMicros96, TimestampNanos96};
   use crate::errors::QCompressResult;
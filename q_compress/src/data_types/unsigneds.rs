@@ -1,4 +1,5 @@
-use std::convert::TryInto;
+use alloc::vec::Vec;
+use core::convert::TryInto;
 
 use crate::data_types::{NumberLike, UnsignedLike};
 use crate::errors::QCompressResult;
@@ -19,6 +20,14 @@ macro_rules! impl_unsigned {
         self as f64
       }
 
+      fn wrapping_add(self, other: Self) -> Self {
+        <$t>::wrapping_add(self, other)
+      }
+
+      fn wrapping_mul(self, other: Self) -> Self {
+        <$t>::wrapping_mul(self, other)
+      }
+
       // These lshift an rshift implementations look slow, but all the
       // conditionals and masks are zero-costed away by the compiler.
       fn rshift_word(self, shift: usize) -> usize {
@@ -1,15 +1,19 @@
-use std::fmt::Debug;
-use std::io::Write;
-use std::marker::PhantomData;
+use alloc::format;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::marker::PhantomData;
 
-use crate::Flags;
+use crate::{Flags, Progress, ProgressUpdate, user_metadata, UserMetadata};
 use crate::bit_reader::BitReader;
 use crate::bit_words::BitWords;
 use crate::chunk_body_decompressor::ChunkBodyDecompressor;
-use crate::chunk_metadata::{ChunkMetadata};
-use crate::constants::{MAGIC_CHUNK_BYTE, MAGIC_HEADER, MAGIC_TERMINATION_BYTE, WORD_SIZE};
+use crate::chunk_handle::{BLOCK_SIZE, ChunkHandle};
+use crate::chunk_metadata::{ChunkMetadata, MemoryLimits, SharedPrefixes};
+use crate::{bits, checksum};
+use crate::constants::{BYTES_PER_FILE_CHECKSUM, MAGIC_CHUNK_BYTE, MAGIC_HEADER, MAGIC_TERMINATION_BYTE, MAX_ENTRIES, WORD_SIZE};
 use crate::data_types::NumberLike;
-use crate::errors::{ErrorKind, QCompressError, QCompressResult};
+use crate::errors::{ErrorKind, ErrorPosition, QCompressError, QCompressResult, Section};
+use crate::snapshot::DecompressorSnapshot;
 
 /// All configurations available for a [`Decompressor`].
 #[derive(Clone, Debug)]
@@ -17,6 +21,34 @@ pub struct DecompressorConfig {
   /// The maximum number of numbers to decode at a time when streaming through
   /// the decompressor as an iterator.
   pub numbers_limit_per_item: usize,
+  /// Whether [`simple_decompress`][Decompressor::simple_decompress] and
+  /// [`decompress_into`][Decompressor::decompress_into] should skip over
+  /// corrupted chunks instead of returning an error (default false).
+  ///
+  /// When a chunk fails to parse, decode, or match its checksum, the
+  /// decompressor scans forward for the next plausible chunk boundary and
+  /// continues from there.
+  /// Each skipped section is reported by
+  /// [`corrupted_sections`][Decompressor::corrupted_sections].
+  pub recover_on_corruption: bool,
+  /// The largest count of numbers a chunk may have (default
+  /// 2^24 - 1, the most the format allows).
+  ///
+  /// Chunk metadata claiming more numbers produces an invalid argument error
+  /// before anything is allocated for the chunk.
+  pub max_n_per_chunk: usize,
+  /// The most bytes the decompressor may allocate for chunks' numbers and
+  /// prefixes over the whole file (default unlimited).
+  ///
+  /// Each chunk's metadata is checked against what remains as it is parsed,
+  /// before anything is allocated for it, so a tiny file claiming huge
+  /// chunks produces an invalid argument error instead of exhausting memory.
+  /// This bounds the total size of what
+  /// [`simple_decompress`][Decompressor::simple_decompress] returns, so
+  /// services decoding untrusted files should set it.
+  /// It is counted over every chunk until the decompressor is
+  /// [`reset`][Decompressor::reset], even when streaming.
+  pub max_total_memory: usize,
   phantom: PhantomData<()>, // for API stability
 }
 
@@ -24,6 +56,9 @@ impl Default for DecompressorConfig {
   fn default() -> Self {
     Self {
       numbers_limit_per_item: 100000,
+      recover_on_corruption: false,
+      max_n_per_chunk: MAX_ENTRIES,
+      max_total_memory: usize::MAX,
       phantom: PhantomData,
     }
   }
@@ -35,6 +70,59 @@ impl DecompressorConfig {
     self.numbers_limit_per_item = limit;
     self
   }
+
+  /// Sets [`recover_on_corruption`][DecompressorConfig::recover_on_corruption].
+  pub fn with_recover_on_corruption(mut self, recover_on_corruption: bool) -> Self {
+    self.recover_on_corruption = recover_on_corruption;
+    self
+  }
+
+  /// Sets [`max_n_per_chunk`][DecompressorConfig::max_n_per_chunk].
+  pub fn with_max_n_per_chunk(mut self, max_n_per_chunk: usize) -> Self {
+    self.max_n_per_chunk = max_n_per_chunk;
+    self
+  }
+
+  /// Sets [`max_total_memory`][DecompressorConfig::max_total_memory].
+  pub fn with_max_total_memory(mut self, max_total_memory: usize) -> Self {
+    self.max_total_memory = max_total_memory;
+    self
+  }
+
+  /// Returns an invalid argument error if `numbers_limit_per_item` is 0,
+  /// since iterating would then never yield any numbers.
+  ///
+  /// [`Decompressor::try_from_config`] calls this.
+  pub fn validate(&self) -> QCompressResult<()> {
+    if self.numbers_limit_per_item == 0 {
+      return Err(QCompressError::invalid_argument(
+        "numbers limit per item must be positive"
+      ));
+    }
+    Ok(())
+  }
+
+  fn memory_limits(&self, memory_used: usize) -> MemoryLimits {
+    MemoryLimits {
+      max_n_per_chunk: self.max_n_per_chunk,
+      remaining_memory: self.max_total_memory.saturating_sub(memory_used),
+    }
+  }
+}
+
+/// A section of compressed data skipped due to corruption when
+/// [`recover_on_corruption`][DecompressorConfig::recover_on_corruption] is on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CorruptedSection {
+  /// The index into the decompressed numbers where the lost numbers would
+  /// have been.
+  pub num_idx: usize,
+  /// The count of numbers lost, if known.
+  /// This is only known when the corrupted chunk's metadata was readable and
+  /// the next chunk was found exactly where that metadata said it would be.
+  pub n_lost: Option<usize>,
+  /// The error that caused the section to be skipped.
+  pub error: QCompressError,
 }
 
 /// The different types of data encountered when iterating through the
@@ -51,11 +139,71 @@ pub enum DecompressedItem<T: NumberLike> {
 struct State<T: NumberLike> {
   bit_idx: usize,
   flags: Option<Flags>,
+  shared_prefixes: Option<SharedPrefixes<T>>,
+  header_metadata: UserMetadata,
   chunk_body_decompressor: Option<ChunkBodyDecompressor<T>>,
   terminated: bool,
+  // number of bits freed from the start of the compressed data
+  freed_bits: usize,
+  // number of chunks whose metadata has been read
+  n_chunks: usize,
+  // bytes counted against max_total_memory
+  memory_used: usize,
+  corrupted_sections: Vec<CorruptedSection>,
+  // a copy of the header, so snapshots can be restored without the start
+  // of the file
+  header_bytes: Vec<u8>,
+  // where the chunk most recently started, its count of numbers, and the
+  // memory used before it, so snapshots taken within its body can restore it
+  chunk_start_bit_idx: usize,
+  chunk_n: usize,
+  memory_used_before_chunk: usize,
+}
+
+impl<T: NumberLike> State<T> {
+  fn read_header(&mut self, reader: &mut BitReader) -> QCompressResult<Flags> {
+    let header = read_header::<T>(reader)?;
+    let mut header_reader = reader.clone();
+    header_reader.seek_to(0);
+    self.header_bytes = header_reader.read_aligned_bytes(bits::ceil_div(reader.bit_idx(), 8))?;
+    self.flags = Some(header.flags.clone());
+    self.header_metadata = header.header_metadata;
+    self.shared_prefixes = header.shared_prefixes;
+    Ok(header.flags)
+  }
+
+  fn start_chunk_body(
+    &mut self,
+    chunk_body_decompressor: ChunkBodyDecompressor<T>,
+    chunk_start_bit_idx: usize,
+    memory_used: usize,
+  ) {
+    self.chunk_n = chunk_body_decompressor.n_remaining();
+    self.chunk_body_decompressor = Some(chunk_body_decompressor);
+    self.n_chunks += 1;
+    self.chunk_start_bit_idx = self.freed_bits + chunk_start_bit_idx;
+    self.memory_used_before_chunk = self.memory_used;
+    self.memory_used = memory_used;
+  }
 }
 
-pub(crate) fn read_header<T: NumberLike>(reader: &mut BitReader) -> QCompressResult<Flags> {
+// where a chunk body starts, and how to decode it
+#[derive(Clone, Debug)]
+pub(crate) struct ChunkStart<T: NumberLike> {
+  pub metadata: ChunkMetadata<T>,
+  pub chunk_idx: usize,
+  pub bit_idx: usize,
+  pub body_decompressor: ChunkBodyDecompressor<T>,
+}
+
+// everything in the header after the data type byte
+pub(crate) struct Header<T: NumberLike> {
+  pub flags: Flags,
+  pub header_metadata: UserMetadata,
+  pub shared_prefixes: Option<SharedPrefixes<T>>,
+}
+
+pub(crate) fn read_header<T: NumberLike>(reader: &mut BitReader) -> QCompressResult<Header<T>> {
   let bytes = reader.read_aligned_bytes(MAGIC_HEADER.len())?;
   if bytes != MAGIC_HEADER {
     return Err(QCompressError::corruption(format!(
@@ -74,12 +222,44 @@ pub(crate) fn read_header<T: NumberLike>(reader: &mut BitReader) -> QCompressRes
     )));
   }
 
-  Flags::parse_from(reader)
+  let flags = Flags::parse_from(reader)?;
+  let header_metadata = if flags.use_user_metadata {
+    let res = user_metadata::parse_from(reader)?;
+    reader.drain_empty_byte(|| QCompressError::corruption(
+      "nonzero bits in end of final byte of header metadata"
+    ))?;
+    res
+  } else {
+    UserMetadata::new()
+  };
+  let shared_prefixes = if flags.use_shared_prefixes {
+    Some(SharedPrefixes::parse_from(reader, &flags)?)
+  } else {
+    None
+  };
+  Ok(Header {
+    flags,
+    header_metadata,
+    shared_prefixes,
+  })
 }
 
-pub(crate) fn read_chunk_meta<T: NumberLike>(reader: &mut BitReader, flags: &Flags) -> QCompressResult<Option<ChunkMetadata<T>>> {
+fn read_file_checksum(reader: &mut BitReader) -> QCompressResult<u32> {
+  let bytes = reader.read_aligned_bytes(BYTES_PER_FILE_CHECKSUM)?;
+  Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub(crate) fn read_chunk_meta<T: NumberLike>(
+  reader: &mut BitReader,
+  flags: &Flags,
+  shared_prefixes: Option<&SharedPrefixes<T>>,
+  limits: &mut MemoryLimits,
+) -> QCompressResult<Option<ChunkMetadata<T>>> {
   let magic_byte = reader.read_aligned_bytes(1)?[0];
   if magic_byte == MAGIC_TERMINATION_BYTE {
+    if flags.use_file_checksum {
+      read_file_checksum(reader)?;
+    }
     return Ok(None);
   } else if magic_byte != MAGIC_CHUNK_BYTE {
     return Err(QCompressError::corruption(format!(
@@ -89,7 +269,7 @@ pub(crate) fn read_chunk_meta<T: NumberLike>(reader: &mut BitReader, flags: &Fla
   }
 
   // otherwise there is indeed another chunk
-  let metadata = ChunkMetadata::parse_from(reader, flags)?;
+  let metadata = ChunkMetadata::parse_with_shared_prefixes(reader, flags, shared_prefixes, limits)?;
   reader.drain_empty_byte(|| QCompressError::corruption(
     "nonzero bits in end of final byte of chunk metadata"
   ))?;
@@ -141,9 +321,10 @@ pub struct Decompressor<T> where T: NumberLike {
   state: State<T>,
 }
 
-impl<T: NumberLike> Write for Decompressor<T> {
+#[cfg(feature = "std")]
+impl<T: NumberLike> std::io::Write for Decompressor<T> {
   fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-    self.words.extend_bytes(buf);
+    self.extend_bytes(buf);
     Ok(buf.len())
   }
 
@@ -161,6 +342,119 @@ impl<T> Decompressor<T> where T: NumberLike {
     }
   }
 
+  /// Creates a new decompressor like [`from_config`][Self::from_config],
+  /// but returns an invalid argument error if the config is invalid.
+  /// See [`DecompressorConfig::validate`].
+  pub fn try_from_config(config: DecompressorConfig) -> QCompressResult<Self> {
+    config.validate()?;
+    Ok(Self::from_config(config))
+  }
+
+  /// Creates a decompressor that resumes from a [`DecompressorSnapshot`],
+  /// given the .qco file's bytes starting at the snapshot's
+  /// [`resume_byte_idx`][DecompressorSnapshot::resume_byte_idx].
+  ///
+  /// More bytes can be written afterward as usual.
+  /// If the snapshot was taken within a chunk body, the bytes must extend at
+  /// least as far as the decompressor had read into it, since this decodes
+  /// that chunk up to the same point again.
+  /// Will return an error if the config is invalid,
+  /// the snapshot is inconsistent with the bytes,
+  /// or the bytes run out before the snapshot's position.
+  pub fn from_snapshot(
+    config: DecompressorConfig,
+    snapshot: &DecompressorSnapshot,
+    bytes: &[u8],
+  ) -> QCompressResult<Self> {
+    let mut res = Self::try_from_config(config)?;
+    res.extend_bytes(bytes);
+    if snapshot.header_bytes.is_empty() {
+      // nothing was read before the snapshot
+      return Ok(res);
+    }
+
+    let words = BitWords::from(&snapshot.header_bytes);
+    res.state.read_header(&mut BitReader::from(&words))?;
+    res.state.freed_bits = snapshot.resume_byte_idx * 8;
+    res.state.n_chunks = snapshot.n_chunks;
+    res.state.memory_used = snapshot.memory_used;
+    res.state.terminated = snapshot.terminated;
+
+    if let Some(n_decompressed) = snapshot.n_decompressed_in_chunk {
+      // read the unfinished chunk's metadata again and decode its body up
+      // to where the snapshot was taken
+      res.state.n_chunks -= 1;
+      if res.chunk_metadata()?.is_none() {
+        return Err(QCompressError::corruption(
+          "snapshot is within a chunk body, but found a footer instead"
+        ));
+      }
+      let mut n_remaining = n_decompressed;
+      while n_remaining > 0 {
+        let numbers = res.with_reader(|reader, state, _| {
+          state.chunk_body_decompressor.as_mut().unwrap().decompress_next_batch(
+            reader,
+            n_remaining.min(BLOCK_SIZE),
+            true,
+          )
+        })?;
+        if numbers.nums.is_empty() {
+          return Err(QCompressError::corruption(format!(
+            "snapshot is {} numbers past the end of its chunk",
+            n_remaining,
+          )));
+        }
+        n_remaining -= numbers.nums.len();
+        if numbers.finished_chunk_body {
+          res.state.chunk_body_decompressor = None;
+        }
+      }
+    }
+    Ok(res)
+  }
+
+  /// Returns a [`DecompressorSnapshot`] of the decompressor's progress,
+  /// from which [`from_snapshot`][Self::from_snapshot] can resume.
+  pub fn snapshot(&self) -> DecompressorSnapshot {
+    let state = &self.state;
+    match &state.chunk_body_decompressor {
+      Some(cbd) if !state.terminated => {
+        DecompressorSnapshot {
+          header_bytes: state.header_bytes.clone(),
+          resume_byte_idx: state.chunk_start_bit_idx / 8,
+          n_chunks: state.n_chunks,
+          memory_used: state.memory_used_before_chunk,
+          terminated: false,
+          n_decompressed_in_chunk: Some(state.chunk_n - cbd.n_remaining()),
+        }
+      },
+      _ => DecompressorSnapshot {
+        header_bytes: state.header_bytes.clone(),
+        resume_byte_idx: bits::ceil_div(state.freed_bits + state.bit_idx, 8),
+        n_chunks: state.n_chunks,
+        memory_used: state.memory_used,
+        terminated: state.terminated,
+        n_decompressed_in_chunk: None,
+      },
+    }
+  }
+
+  /// Appends compressed bytes to the decompressor's data.
+  /// This does the same thing as its `std::io::Write` implementation,
+  /// which is unavailable without the `std` feature.
+  pub fn extend_bytes(&mut self, bytes: &[u8]) {
+    self.words.extend_bytes(bytes);
+  }
+
+  /// Discards all compressed data and returns the decompressor to its
+  /// initial state, ready to read a new .qco file with the same config.
+  /// Unlike creating a new decompressor, this keeps the memory allocated for
+  /// compressed data, which helps when decompressing many small files.
+  pub fn reset(&mut self) {
+    self.words.clear();
+    self.state = State::default();
+  }
+
   /// Returns the current bit position into the compressed data the
   /// decompressor is pointed at.
   /// Note that when memory is freed, this will decrease.
@@ -168,15 +462,45 @@ impl<T> Decompressor<T> where T: NumberLike {
     self.state.bit_idx
   }
 
+  // number of bits written to the decompressor but not yet decoded
+  pub(crate) fn bits_available(&self) -> usize {
+    self.words.total_bits - self.state.bit_idx
+  }
+
+  pub(crate) fn chunk_body_bits_remaining(&self) -> Option<usize> {
+    self.state.chunk_body_decompressor.as_ref().map(|cbd| cbd.bits_remaining())
+  }
+
+  // the position of a failure at the bit index, given the current state
+  fn error_position(&self, bit_idx: usize) -> ErrorPosition {
+    let state = &self.state;
+    let (section, chunk_idx) = if state.flags.is_none() {
+      (Section::Header, None)
+    } else if state.chunk_body_decompressor.is_none() {
+      (Section::ChunkMetadata, Some(state.n_chunks))
+    } else {
+      (Section::ChunkBody, Some(state.n_chunks.saturating_sub(1)))
+    };
+    ErrorPosition {
+      section,
+      chunk_idx,
+      bit_idx: state.freed_bits + bit_idx,
+    }
+  }
+
   fn with_reader<X, F>(&mut self, f: F) -> QCompressResult<X>
   where F: FnOnce(&mut BitReader, &mut State<T>, &DecompressorConfig) -> QCompressResult<X> {
     let mut reader = BitReader::from(&self.words);
     reader.seek_to(self.state.bit_idx);
+    // state is unchanged on error, so it still describes what we were reading
     let res = f(&mut reader, &mut self.state, &self.config);
-    if res.is_ok() {
-      self.state.bit_idx = reader.bit_idx();
+    match res {
+      Ok(x) => {
+        self.state.bit_idx = reader.bit_idx();
+        Ok(x)
+      },
+      Err(e) => Err(e.at(self.error_position(reader.bit_idx()))),
     }
-    res
   }
 
   fn check_not_terminated(&self) -> QCompressResult<()> {
@@ -201,11 +525,13 @@ impl<T> Decompressor<T> where T: NumberLike {
         "attempted to decompress header for the 2nd time"
       ))
     }
-    self.with_reader(|reader, state, _| {
-      let flags = read_header::<T>(reader)?;
-      state.flags = Some(flags.clone());
-      Ok(flags)
-    })
+    self.with_reader(|reader, state, _| state.read_header(reader))
+  }
+
+  /// Returns the [`UserMetadata`] from the header, which is empty if the
+  /// header has not been read yet or the file has none.
+  pub fn header_metadata(&self) -> &UserMetadata {
+    &self.state.header_metadata
   }
 
   /// Reads a [`ChunkMetadata`], returning it.
@@ -228,11 +554,18 @@ impl<T> Decompressor<T> where T: NumberLike {
         "attempted to decompress chunk metadata before chunk body was finished"
       ));
     }
-    self.with_reader(|reader, state, _| {
+    self.with_reader(|reader, state, config| {
+      let start_bit_idx = reader.bit_idx();
       let flags = state.flags.as_ref().unwrap();
-      let maybe_meta = read_chunk_meta(reader, flags)?;
+      let mut limits = config.memory_limits(state.memory_used);
+      let maybe_meta = read_chunk_meta(reader, flags, state.shared_prefixes.as_ref(), &mut limits)?;
       if let Some(meta) = &maybe_meta {
-        state.chunk_body_decompressor = Some(ChunkBodyDecompressor::new(meta)?)
+        let cbd = ChunkBodyDecompressor::new(meta, flags)?;
+        state.start_chunk_body(
+          cbd,
+          start_bit_idx,
+          config.max_total_memory - limits.remaining_memory,
+        );
       }
       Ok(maybe_meta)
     })
@@ -264,7 +597,7 @@ impl<T> Decompressor<T> where T: NumberLike {
         "unable to skip chunk body to bit index {} when only {} bits available",
         skipped_bit_idx,
         self.words.total_bits,
-      )))
+      )).at(self.error_position(self.words.total_bits)))
     }
   }
 
@@ -286,16 +619,64 @@ impl<T> Decompressor<T> where T: NumberLike {
     })
   }
 
+  /// Reads a chunk body into the start of `dst`, returning the number of
+  /// numbers written.
+  /// This avoids allocating a new vector for each chunk; `dst` must have
+  /// room for at least the chunk's remaining numbers, which is `n` from its
+  /// [`ChunkMetadata`].
+  /// Will return an error if the decompressor is not in a chunk body,
+  /// `dst` is too small,
+  /// runs out of data,
+  /// or finds any corruptions.
+  pub fn chunk_body_into(&mut self, dst: &mut [T]) -> QCompressResult<usize> {
+    self.check_in_chunk_body()?;
+    let n_remaining = self.state.chunk_body_decompressor.as_ref().unwrap().n_remaining();
+    if dst.len() < n_remaining {
+      return Err(QCompressError::invalid_argument(format!(
+        "destination of length {} is too small for chunk body with {} numbers remaining",
+        dst.len(),
+        n_remaining,
+      )));
+    }
+    self.with_reader(|reader, state, _| {
+      let chunk_body_decompressor = state.chunk_body_decompressor.as_mut().unwrap();
+      let batch = chunk_body_decompressor.decompress_next_batch_into(
+        reader,
+        &mut dst[..n_remaining],
+        true,
+      )?;
+      state.chunk_body_decompressor = None;
+      Ok(batch.n)
+    })
+  }
+
   /// Takes in compressed bytes and returns a vector of numbers.
   /// Will return an error if there are any compatibility, corruption,
   /// or insufficient data issues.
   pub fn simple_decompress(&mut self) -> QCompressResult<Vec<T>> {
+    self.simple_decompress_with_progress(|_| ())
+  }
+
+  /// Like [`simple_decompress`][Decompressor::simple_decompress], but
+  /// calls `progress` after each chunk.
+  pub fn simple_decompress_with_progress<P: Progress>(
+    &mut self,
+    mut progress: P,
+  ) -> QCompressResult<Vec<T>> {
     // cloning/extending by a single chunk's numbers can slow down by 2%
     // so we just take ownership of the first chunk's numbers instead
     let mut res: Option<Vec<T>> = None;
     self.header()?;
-    while self.chunk_metadata()?.is_some() {
-      let nums = self.chunk_body()?;
+    let mut n = 0;
+    let mut n_chunks = 0;
+    while let Some(nums) = self.next_chunk_nums(n)? {
+      n += nums.len();
+      n_chunks += 1;
+      progress.on_chunk(ProgressUpdate {
+        n_chunks,
+        n_numbers: n,
+        n_bytes: self.state.bit_idx / 8,
+      });
       res = match res {
         Some(mut existing) => {
           existing.extend(nums);
@@ -309,6 +690,225 @@ impl<T> Decompressor<T> where T: NumberLike {
     Ok(res.unwrap_or_default())
   }
 
+  /// Like [`simple_decompress`][Decompressor::simple_decompress], but
+  /// decodes chunk bodies concurrently on the `rayon` thread pool.
+  ///
+  /// This first scans through all the chunk metadata to find where each
+  /// chunk body starts, so it needs the whole file to have been written to
+  /// the decompressor.
+  /// With [`recover_on_corruption`][DecompressorConfig::recover_on_corruption]
+  /// on, chunks are decoded sequentially instead, since corruption can hide
+  /// where the next chunk starts.
+  /// Will return an error if there are any compatibility, corruption,
+  /// or insufficient data issues.
+  #[cfg(feature = "parallel")]
+  pub fn simple_decompress_parallel(&mut self) -> QCompressResult<Vec<T>> {
+    use rayon::prelude::*;
+
+    if self.config.recover_on_corruption {
+      return self.simple_decompress();
+    }
+
+    self.header()?;
+    let mut chunk_starts = Vec::new();
+    while self.chunk_metadata()?.is_some() {
+      let cbd = self.state.chunk_body_decompressor.clone().unwrap();
+      chunk_starts.push((self.state.n_chunks - 1, self.state.bit_idx, cbd));
+      self.skip_chunk_body()?;
+    }
+
+    let words = &self.words;
+    let freed_bits = self.state.freed_bits;
+    let numss = chunk_starts.into_par_iter()
+      .map(|(chunk_idx, bit_idx, mut cbd)| {
+        let mut reader = BitReader::from(words);
+        reader.seek_to(bit_idx);
+        cbd.decompress_next_batch(&mut reader, usize::MAX, true)
+          .map(|numbers| numbers.nums)
+          .map_err(|e| e.at(ErrorPosition {
+            section: Section::ChunkBody,
+            chunk_idx: Some(chunk_idx),
+            bit_idx: freed_bits + reader.bit_idx(),
+          }))
+      })
+      .collect::<QCompressResult<Vec<_>>>()?;
+    Ok(numss.concat())
+  }
+
+  /// Reads the metadata of every remaining chunk and returns a
+  /// [`ChunkHandle`] for each, whose body is only decoded on demand.
+  ///
+  /// This reads the header first if it has not been read yet, and needs the
+  /// rest of the file to have been written to the decompressor.
+  /// The handles borrow the decompressor's compressed data, and the
+  /// decompressor is left past the footer.
+  /// Will return an error if the decompressor is in a chunk body,
+  /// runs out of data,
+  /// or finds any corruptions in the header or chunk metadata.
+  pub fn chunks(&mut self) -> QCompressResult<Vec<ChunkHandle<'_, T>>> {
+    let chunk_starts = self.scan_chunk_starts()?;
+    let words = &self.words;
+    let freed_bits = self.state.freed_bits;
+    Ok(chunk_starts.into_iter()
+      .map(|start| ChunkHandle::new(start, words, freed_bits))
+      .collect())
+  }
+
+  // Reads the header if needed and the metadata of every remaining chunk,
+  // skipping over their bodies and leaving the decompressor past the footer.
+  pub(crate) fn scan_chunk_starts(&mut self) -> QCompressResult<Vec<ChunkStart<T>>> {
+    let initial_state = self.state.clone();
+    let res = self.scan_chunk_starts_dirty();
+    if res.is_err() {
+      self.state = initial_state;
+    }
+    res
+  }
+
+  fn scan_chunk_starts_dirty(&mut self) -> QCompressResult<Vec<ChunkStart<T>>> {
+    if self.state.flags.is_none() {
+      self.header()?;
+    }
+    let mut res = Vec::new();
+    while let Some(metadata) = self.chunk_metadata()? {
+      res.push(ChunkStart {
+        metadata,
+        chunk_idx: self.state.n_chunks - 1,
+        bit_idx: self.state.bit_idx,
+        body_decompressor: self.state.chunk_body_decompressor.clone().unwrap(),
+      });
+      self.skip_chunk_body()?;
+    }
+    self.state.terminated = true;
+    Ok(res)
+  }
+
+  pub(crate) fn words(&self) -> &BitWords {
+    &self.words
+  }
+
+  pub(crate) fn freed_bits(&self) -> usize {
+    self.state.freed_bits
+  }
+
+  /// Takes in compressed bytes and writes all their numbers into the start of
+  /// `dst`, returning the number of numbers written.
+  /// Like [`simple_decompress`][Decompressor::simple_decompress], but avoids
+  /// allocating when the caller already has a large enough buffer, e.g. one
+  /// reused across files.
+  /// Will return an error if `dst` is too small or if there are any
+  /// compatibility, corruption, or insufficient data issues.
+  pub fn decompress_into(&mut self, dst: &mut [T]) -> QCompressResult<usize> {
+    self.header()?;
+    let mut n = 0;
+    if self.config.recover_on_corruption {
+      // we don't know how large a recovered chunk is until we decode it
+      while let Some(nums) = self.next_chunk_nums(n)? {
+        if dst.len() < n + nums.len() {
+          return Err(QCompressError::invalid_argument(format!(
+            "destination of length {} is too small for recovered chunk with {} numbers at index {}",
+            dst.len(),
+            nums.len(),
+            n,
+          )));
+        }
+        dst[n..n + nums.len()].copy_from_slice(&nums);
+        n += nums.len();
+      }
+    } else {
+      while self.chunk_metadata()?.is_some() {
+        n += self.chunk_body_into(&mut dst[n..])?;
+      }
+    }
+    Ok(n)
+  }
+
+  /// Returns the sections of compressed data skipped so far due to
+  /// corruption.
+  /// This is always empty unless
+  /// [`recover_on_corruption`][DecompressorConfig::recover_on_corruption]
+  /// is on.
+  pub fn corrupted_sections(&self) -> &[CorruptedSection] {
+    &self.state.corrupted_sections
+  }
+
+  // Reads the next chunk's metadata and body, returning None at the footer.
+  // If recovering on corruption, this skips to the next plausible chunk
+  // instead of returning corruption or insufficient data errors.
+  fn next_chunk_nums(&mut self, num_idx: usize) -> QCompressResult<Option<Vec<T>>> {
+    let chunk_start_bit_idx = self.state.bit_idx;
+    let mut expected_end = None;
+    let res = match self.chunk_metadata() {
+      Ok(Some(meta)) => {
+        let cbd = self.state.chunk_body_decompressor.as_ref().unwrap();
+        expected_end = Some((self.state.bit_idx + cbd.bits_remaining(), meta.n));
+        self.chunk_body().map(Some)
+      },
+      Ok(None) => Ok(None),
+      Err(e) => Err(e),
+    };
+
+    match res {
+      Err(e) if self.config.recover_on_corruption &&
+        matches!(e.kind, ErrorKind::Corruption | ErrorKind::InsufficientData) => {
+        self.state.chunk_body_decompressor = None;
+        let (recovered_bit_idx, nums) = self.find_next_chunk(chunk_start_bit_idx + 8);
+        let n_lost = match expected_end {
+          Some((end_bit_idx, n)) if end_bit_idx == recovered_bit_idx => Some(n),
+          _ => None,
+        };
+        self.state.corrupted_sections.push(CorruptedSection {
+          num_idx,
+          n_lost,
+          error: e,
+        });
+        Ok(nums)
+      },
+      _ => res,
+    }
+  }
+
+  // Scans byte by byte for the first position at or after start_bit_idx
+  // holding a chunk that decodes successfully or a footer that ends the data.
+  // Moves the decompressor past it and returns the position it started at,
+  // along with the chunk's numbers.
+  // If nothing is found, everything remaining is lost and we terminate.
+  fn find_next_chunk(&mut self, start_bit_idx: usize) -> (usize, Option<Vec<T>>) {
+    let flags = self.state.flags.clone().unwrap();
+    let mut bit_idx = start_bit_idx;
+    while bit_idx < self.words.total_bits {
+      let mut reader = BitReader::from(&self.words);
+      reader.seek_to(bit_idx);
+      let mut limits = self.config.memory_limits(self.state.memory_used);
+      let shared_prefixes = self.state.shared_prefixes.as_ref();
+      let decoded = read_chunk_meta(&mut reader, &flags, shared_prefixes, &mut limits).and_then(|maybe_meta| {
+        match maybe_meta {
+          Some(meta) => {
+            let mut cbd = ChunkBodyDecompressor::new(&meta, &flags)?;
+            let numbers = cbd.decompress_next_batch(&mut reader, usize::MAX, true)?;
+            Ok(Some(numbers.nums))
+          },
+          None if reader.bits_remaining() == 0 => Ok(None),
+          None => Err(QCompressError::corruption("footer is not at end of data")),
+        }
+      });
+      if let Ok(maybe_nums) = decoded {
+        self.state.terminated = maybe_nums.is_none();
+        if maybe_nums.is_some() {
+          self.state.n_chunks += 1;
+          self.state.memory_used = self.config.max_total_memory - limits.remaining_memory;
+        }
+        self.state.bit_idx = reader.bit_idx();
+        return (bit_idx, maybe_nums);
+      }
+      bit_idx += 8;
+    }
+
+    self.state.terminated = true;
+    self.state.bit_idx = self.words.total_bits;
+    (self.words.total_bits, None)
+  }
+
   /// Frees memory used for storing compressed bytes the decompressor has
   /// already decoded.
   /// Note that calling this too frequently can cause performance issues.
@@ -317,7 +917,55 @@ impl<T> Decompressor<T> where T: NumberLike {
     if words_to_free > 0 {
       self.words.truncate_left(words_to_free);
       self.state.bit_idx -= words_to_free * WORD_SIZE;
+      self.state.freed_bits += words_to_free * WORD_SIZE;
+    }
+  }
+
+  /// Checks the file-level checksum of a complete .qco file without decoding
+  /// any numbers, leaving the decompressor's state unchanged.
+  /// This requires the file to have been compressed with
+  /// [`use_file_checksum`][crate::CompressorConfig::use_file_checksum] and
+  /// all its bytes to have been written to the decompressor.
+  /// Will return an error if the file has no file checksum,
+  /// compressed memory has already been freed,
+  /// the decompressor runs out of data,
+  /// or the checksum does not match.
+  pub fn verify(&self) -> QCompressResult<()> {
+    if self.state.freed_bits > 0 {
+      return Err(QCompressError::invalid_argument(
+        "cannot verify file checksum after freeing compressed memory"
+      ));
     }
+    let mut reader = BitReader::from(&self.words);
+    let Header { flags, shared_prefixes, .. } = read_header::<T>(&mut reader)?;
+    if !flags.use_file_checksum {
+      return Err(QCompressError::invalid_argument(
+        "cannot verify file compressed without use_file_checksum"
+      ));
+    }
+    // no numbers are allocated, so memory limits don't apply
+    let mut limits = MemoryLimits::default();
+    while let Some(meta) = read_chunk_meta(&mut reader, &flags, shared_prefixes.as_ref(), &mut limits)? {
+      // we only need the metadata to know how far to skip
+      let cbd = ChunkBodyDecompressor::new(&meta, &flags)?;
+      reader.seek(cbd.bits_remaining());
+    }
+
+    // reading the footer also read the file checksum, so we go back for it
+    reader.rewind(BYTES_PER_FILE_CHECKSUM * 8);
+    let checksummed_byte_size = reader.aligned_byte_idx()?;
+    let expected = read_file_checksum(&mut reader)?;
+    let mut bytes = bits::words_to_bytes(&self.words.words);
+    bytes.truncate(checksummed_byte_size);
+    let actual = checksum::crc32(&bytes);
+    if expected != actual {
+      return Err(QCompressError::corruption(format!(
+        "file checksum {:#010x} does not match expected {:#010x}",
+        actual,
+        expected,
+      )));
+    }
+    Ok(())
   }
 }
 
@@ -331,20 +979,24 @@ impl<T: NumberLike> Iterator for &mut Decompressor<T> {
       }
 
       if state.flags.is_none() {
-        match read_header::<T>(reader) {
-          Ok(flags) => {
-            state.flags = Some(flags.clone());
-            Ok(Some(DecompressedItem::Flags(flags)))
-          },
+        match state.read_header(reader) {
+          Ok(flags) => Ok(Some(DecompressedItem::Flags(flags))),
           Err(e) if matches!(e.kind, ErrorKind::InsufficientData) => Ok(None),
           Err(e) => Err(e),
         }
       } else if state.chunk_body_decompressor.is_none() {
-        match read_chunk_meta::<T>(reader, state.flags.as_ref().unwrap()) {
+        let start_bit_idx = reader.bit_idx();
+        let flags = state.flags.as_ref().unwrap();
+        let mut limits = config.memory_limits(state.memory_used);
+        match read_chunk_meta(reader, flags, state.shared_prefixes.as_ref(), &mut limits) {
           Ok(Some(meta)) => {
-            match ChunkBodyDecompressor::new(&meta) {
+            match ChunkBodyDecompressor::new(&meta, flags) {
               Ok(cbd) => {
-                state.chunk_body_decompressor = Some(cbd);
+                state.start_chunk_body(
+                  cbd,
+                  start_bit_idx,
+                  config.max_total_memory - limits.remaining_memory,
+                );
                 Ok(Some(DecompressedItem::ChunkMetadata(meta)))
               }
               Err(e) => Err(e)
@@ -0,0 +1,151 @@
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::data_types::{NumberLike, UnsignedLike};
+use crate::float_math;
+
+// Chunks with at most this many distinct numbers get an exact count.
+const MAX_EXACT_DISTINCT_COUNT: usize = 1 << 12;
+// The HyperLogLog sketch uses 2^this registers, for a relative standard
+// error of about 1.04 / sqrt(2^this), or 1.6%.
+const SKETCH_PRECISION: usize = 12;
+
+/// The number of distinct numbers in a chunk, as stored in
+/// [`ChunkMetadata::distinct_count`][crate::ChunkMetadata::distinct_count].
+///
+/// Numbers are distinct if their unsigned representations are, so e.g.
+/// `0.0` and `-0.0` count as distinct floats.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DistinctCount {
+  /// The number of distinct numbers, or an estimate of it.
+  pub count: usize,
+  /// Whether `count` is exact.
+  /// When false, `count` is a HyperLogLog estimate with a relative
+  /// standard error of about 1.6%.
+  pub is_exact: bool,
+}
+
+// splitmix64's finalizer, which mixes every input bit into every output bit
+pub(crate) fn mix(mut x: u64) -> u64 {
+  x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+  x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+  x ^ (x >> 31)
+}
+
+fn hash<U: UnsignedLike>(u: U) -> u64 {
+  let mut res = 0;
+  // usize may only hold 32 bits
+  for shift in (0..U::BITS).step_by(32) {
+    res = mix(res ^ (u.rshift_word(shift) as u64 & 0xffffffff));
+  }
+  res
+}
+
+struct Sketch {
+  registers: Vec<u8>,
+}
+
+impl Sketch {
+  fn new() -> Self {
+    Sketch { registers: vec![0; 1 << SKETCH_PRECISION] }
+  }
+
+  fn insert(&mut self, h: u64) {
+    let idx = (h >> (64 - SKETCH_PRECISION)) as usize;
+    let rank = ((h << SKETCH_PRECISION).leading_zeros() as usize)
+      .min(64 - SKETCH_PRECISION) + 1;
+    self.registers[idx] = self.registers[idx].max(rank as u8);
+  }
+
+  fn estimate(&self) -> f64 {
+    let m = self.registers.len() as f64;
+    let alpha = 0.7213 / (1.0 + 1.079 / m);
+    let inv_sum = self.registers.iter()
+      .map(|&r| 1.0 / (1_u64 << r) as f64)
+      .sum::<f64>();
+    let raw = alpha * m * m / inv_sum;
+    let n_zeros = self.registers.iter().filter(|&&r| r == 0).count();
+    if raw <= 2.5 * m && n_zeros > 0 {
+      // linear counting is more accurate for small cardinalities
+      m * float_math::log2(m / n_zeros as f64) * core::f64::consts::LN_2
+    } else {
+      raw
+    }
+  }
+}
+
+// Counts the distinct numbers exactly while there are few of them, then
+// falls back to a HyperLogLog sketch.
+pub(crate) fn distinct_count<T: NumberLike>(nums: &[T]) -> DistinctCount {
+  let mut exact = BTreeSet::new();
+  let mut sketch = Sketch::new();
+  let mut is_exact = true;
+  for &num in nums {
+    let u = num.to_unsigned();
+    sketch.insert(hash(u));
+    if is_exact {
+      exact.insert(u);
+      if exact.len() > MAX_EXACT_DISTINCT_COUNT {
+        is_exact = false;
+        exact.clear();
+      }
+    }
+  }
+
+  if is_exact {
+    DistinctCount {
+      count: exact.len(),
+      is_exact,
+    }
+  } else {
+    // we know there are more distinct numbers than we counted exactly, and
+    // no more than there are numbers
+    let estimate = float_math::floor(sketch.estimate() + 0.5) as usize;
+    DistinctCount {
+      count: estimate.clamp(MAX_EXACT_DISTINCT_COUNT + 1, nums.len()),
+      is_exact,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_exact_distinct_count() {
+    assert_eq!(
+      distinct_count::<i32>(&[]),
+      DistinctCount { count: 0, is_exact: true },
+    );
+    let nums = (0..10000).map(|i| (i * 7) % 1000).collect::<Vec<i64>>();
+    assert_eq!(
+      distinct_count(&nums),
+      DistinctCount { count: 1000, is_exact: true },
+    );
+    assert_eq!(
+      distinct_count(&[0.0_f32, -0.0, 1.0, 0.0]),
+      DistinctCount { count: 3, is_exact: true },
+    );
+  }
+
+  #[test]
+  fn test_estimated_distinct_count() {
+    for true_count in [5000_u64, 50000, 300000] {
+      let nums = (0..300000_u64)
+        .map(|i| (i % true_count) * 1234567)
+        .collect::<Vec<_>>();
+      let res = distinct_count(&nums);
+      assert!(!res.is_exact);
+      let rel_err = (res.count as f64 / true_count as f64 - 1.0).abs();
+      assert!(rel_err < 0.06, "{} {:?}", true_count, res);
+    }
+
+    // numbers differing only in their high bits are still distinct
+    let nums = (0..20000_u64).map(|i| i << 40).collect::<Vec<_>>();
+    let rel_err = (distinct_count(&nums).count as f64 / 20000.0 - 1.0).abs();
+    assert!(rel_err < 0.06);
+  }
+}
@@ -0,0 +1,314 @@
+use alloc::format;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::{checksum, ChunkMetadata, Compressor, CompressorConfig, Flags};
+use crate::bit_reader::BitReader;
+use crate::bit_words::BitWords;
+use crate::chunk_body_decompressor::ChunkBodyDecompressor;
+use crate::chunk_metadata::{MemoryLimits, SharedPrefixes};
+use crate::constants::MAGIC_TERMINATION_BYTE;
+use crate::data_types::NumberLike;
+use crate::decompressor::{read_chunk_meta, read_header, Header};
+use crate::errors::{QCompressError, QCompressResult};
+
+// where the parts of a complete .qco file are
+struct FileLayout<T: NumberLike> {
+  flags: Flags,
+  shared_prefixes: Option<SharedPrefixes<T>>,
+  header_size: usize,
+  // each chunk's byte range (including its metadata) and metadata
+  chunks: Vec<(Range<usize>, ChunkMetadata<T>)>,
+}
+
+fn read_layout<T: NumberLike>(bytes: &[u8]) -> QCompressResult<FileLayout<T>> {
+  let words = BitWords::from(bytes);
+  let mut reader = BitReader::from(&words);
+  let Header { flags, shared_prefixes, .. } = read_header::<T>(&mut reader)?;
+  let header_size = reader.aligned_byte_idx()?;
+  // no numbers are allocated, so memory limits don't apply
+  let mut limits = MemoryLimits::default();
+  let mut chunks = Vec::new();
+  loop {
+    let start = reader.aligned_byte_idx()?;
+    match read_chunk_meta(&mut reader, &flags, shared_prefixes.as_ref(), &mut limits)? {
+      Some(meta) => {
+        // we only need the metadata to know how far to skip
+        let cbd = ChunkBodyDecompressor::new(&meta, &flags)?;
+        reader.seek(cbd.bits_remaining());
+        chunks.push((start..reader.aligned_byte_idx()?, meta));
+      },
+      None => break,
+    }
+  }
+  Ok(FileLayout {
+    flags,
+    shared_prefixes,
+    header_size,
+    chunks,
+  })
+}
+
+// Writes the original header, the given chunks, and a new footer.
+fn write_file<'a, I: Iterator<Item=&'a [u8]>>(
+  bytes: &[u8],
+  header_size: usize,
+  flags: &Flags,
+  chunks: I,
+) -> Vec<u8> {
+  let mut res = bytes[..header_size].to_vec();
+  for chunk_bytes in chunks {
+    res.extend_from_slice(chunk_bytes);
+  }
+  res.push(MAGIC_TERMINATION_BYTE);
+  if flags.use_file_checksum {
+    let file_checksum = checksum::crc32(&res);
+    res.extend_from_slice(&file_checksum.to_le_bytes());
+  }
+  res
+}
+
+/// Recompresses a single chunk of a complete .qco file with new numbers,
+/// returning the edited file.
+///
+/// This suits late-arriving corrections to historical data: only the one
+/// chunk is recompressed, and the rest of the file is copied as-is.
+/// The new chunk may hold a different count of numbers than the old one,
+/// and it keeps the old chunk's user metadata.
+/// It is compressed with the file's flags and shared prefixes; only the
+/// settings of `config` that don't show up in the flags apply, like
+/// compression level and tolerance.
+/// The file checksum, if any, is updated.
+/// ```
+/// use q_compress::{auto_decompress, rewrite_chunk, Compressor, CompressorConfig};
+///
+/// let mut compressor = Compressor::<i64>::default();
+/// compressor.header().unwrap();
+/// compressor.chunk(&[1, 2, 3]).unwrap();
+/// compressor.chunk(&[4, 5, 6]).unwrap();
+/// compressor.footer().unwrap();
+/// let bytes = compressor.drain_bytes();
+///
+/// let edited = rewrite_chunk::<i64>(&bytes, 0, &[1, 2, 2, 3], CompressorConfig::default()).expect("rewrite");
+/// assert_eq!(auto_decompress::<i64>(&edited).unwrap(), vec![1, 2, 2, 3, 4, 5, 6]);
+/// ```
+/// Will return an error if the file is corrupt or incomplete,
+/// there is no chunk with that index,
+/// `nums` is empty,
+/// or the config is invalid.
+pub fn rewrite_chunk<T: NumberLike>(
+  bytes: &[u8],
+  chunk_idx: usize,
+  nums: &[T],
+  config: CompressorConfig,
+) -> QCompressResult<Vec<u8>> {
+  config.validate()?;
+  let FileLayout { flags, shared_prefixes, header_size, chunks } = read_layout::<T>(bytes)?;
+  let (_, meta) = chunks.get(chunk_idx).ok_or_else(|| QCompressError::invalid_argument(format!(
+    "cannot rewrite chunk {} of file with {} chunks",
+    chunk_idx,
+    chunks.len(),
+  )))?;
+
+  let mut compressor = Compressor::<T>::for_existing_header(&config, flags.clone(), shared_prefixes);
+  compressor.chunk_with_metadata(nums, meta.user_metadata.clone())?;
+  let new_chunk_bytes = compressor.drain_bytes();
+
+  let chunk_bytes = chunks.iter()
+    .enumerate()
+    .map(|(idx, (other_range, _))| if idx == chunk_idx {
+      new_chunk_bytes.as_slice()
+    } else {
+      &bytes[other_range.clone()]
+    });
+  Ok(write_file(bytes, header_size, &flags, chunk_bytes))
+}
+
+fn drop_leading_chunks<T: NumberLike>(bytes: &[u8], layout: &FileLayout<T>, n_chunks: usize) -> Vec<u8> {
+  let chunk_bytes = layout.chunks[n_chunks..]
+    .iter()
+    .map(|(range, _)| &bytes[range.clone()]);
+  write_file(bytes, layout.header_size, &layout.flags, chunk_bytes)
+}
+
+/// Removes the first `n_chunks` chunks of a complete .qco file, returning
+/// the trimmed file.
+///
+/// This suits expiring old data: the header and remaining chunks are copied
+/// as-is, so nothing is decompressed or recompressed.
+/// The file checksum, if any, is updated.
+/// Will return an error if the file is corrupt or incomplete or has fewer
+/// than `n_chunks` chunks.
+pub fn trim_chunks<T: NumberLike>(bytes: &[u8], n_chunks: usize) -> QCompressResult<Vec<u8>> {
+  let layout = read_layout::<T>(bytes)?;
+  if n_chunks > layout.chunks.len() {
+    return Err(QCompressError::invalid_argument(format!(
+      "cannot trim {} chunks from file with {} chunks",
+      n_chunks,
+      layout.chunks.len(),
+    )));
+  }
+  Ok(drop_leading_chunks(bytes, &layout, n_chunks))
+}
+
+/// Removes the chunks of a complete .qco file that lie entirely before the
+/// number with this index, returning the trimmed file.
+///
+/// The chunk containing the index is kept whole, so the trimmed file may
+/// still contain some numbers before it.
+/// Like [`trim_chunks`], this doesn't decompress anything.
+/// Will return an error if the file is corrupt or incomplete.
+pub fn trim_before_index<T: NumberLike>(bytes: &[u8], index: usize) -> QCompressResult<Vec<u8>> {
+  let layout = read_layout::<T>(bytes)?;
+  let mut n_chunks = 0;
+  let mut chunk_end_idx = 0;
+  for (_, meta) in &layout.chunks {
+    chunk_end_idx += meta.n;
+    if chunk_end_idx > index {
+      break;
+    }
+    n_chunks += 1;
+  }
+  Ok(drop_leading_chunks(bytes, &layout, n_chunks))
+}
+
+/// Removes the leading chunks of a complete .qco file whose numbers are all
+/// less than `value`, returning the trimmed file.
+///
+/// This suits expiring data older than a timestamp from a file of
+/// increasing timestamps: trimming stops at the first chunk containing any
+/// number at least `value`.
+/// Chunks with [min and max][crate::CompressorConfig::use_min_max] are
+/// checked without decompressing them; other chunks are decompressed to
+/// check, but nothing is recompressed.
+/// Will return an error if the file is corrupt or incomplete.
+pub fn trim_before_value<T: NumberLike>(bytes: &[u8], value: T) -> QCompressResult<Vec<u8>> {
+  let layout = read_layout::<T>(bytes)?;
+  let threshold = value.to_unsigned();
+  let words = BitWords::from(bytes);
+  let mut n_chunks = 0;
+  for (range, meta) in &layout.chunks {
+    let max = match meta.min_max {
+      Some((_, max)) => max,
+      None => {
+        let mut reader = BitReader::from(&words);
+        reader.seek_to(range.start * 8);
+        let mut limits = MemoryLimits::default();
+        // we already read this chunk's metadata once, so it's there
+        read_chunk_meta(&mut reader, &layout.flags, layout.shared_prefixes.as_ref(), &mut limits)?;
+        ChunkBodyDecompressor::new(meta, &layout.flags)?
+          .decompress_next_batch(&mut reader, usize::MAX, true)?
+          .nums
+          .into_iter()
+          .max_by_key(|x| x.to_unsigned())
+          .unwrap_or(value)
+      },
+    };
+    if max.to_unsigned() >= threshold {
+      break;
+    }
+    n_chunks += 1;
+  }
+  Ok(drop_leading_chunks(bytes, &layout, n_chunks))
+}
+
+#[cfg(test)]
+mod tests {
+  use alloc::vec;
+
+  use crate::{auto_decompress, Decompressor, UserMetadata};
+  use crate::errors::ErrorKind;
+  use super::*;
+
+  fn compress(chunks: &[Vec<f64>], config: CompressorConfig) -> Vec<u8> {
+    let mut compressor = Compressor::<f64>::from_config(config);
+    compressor.train_shared_prefixes(&chunks.concat()).unwrap();
+    compressor.header().unwrap();
+    for (idx, chunk) in chunks.iter().enumerate() {
+      let mut metadata = UserMetadata::new();
+      metadata.insert("idx".into(), vec![idx as u8]);
+      compressor.chunk_with_metadata(chunk, metadata).unwrap();
+    }
+    compressor.footer().unwrap();
+    compressor.drain_bytes()
+  }
+
+  #[test]
+  fn test_rewrite_chunk() {
+    let chunks = (0..3)
+      .map(|c| (0..1000).map(|i| (c * 1000 + i) as f64 * 0.5).collect::<Vec<_>>())
+      .collect::<Vec<_>>();
+    let config = CompressorConfig::default()
+      .with_delta_encoding_order(1)
+      .with_use_chunk_checksums(true)
+      .with_use_file_checksum(true)
+      .with_use_user_metadata(true);
+    let bytes = compress(&chunks, config);
+
+    let mut new_chunk = chunks[1].clone();
+    new_chunk[500] = -7.25;
+    new_chunk.extend([1.0; 100]);
+    let edited = rewrite_chunk(&bytes, 1, &new_chunk, CompressorConfig::default()).unwrap();
+    let expected = [chunks[0].clone(), new_chunk, chunks[2].clone()].concat();
+    assert_eq!(auto_decompress::<f64>(&edited).unwrap(), expected);
+
+    let mut decompressor = Decompressor::<f64>::default();
+    decompressor.extend_bytes(&edited);
+    decompressor.verify().unwrap();
+    let user_metadatas = decompressor.chunks().unwrap()
+      .iter()
+      .map(|chunk| chunk.metadata().user_metadata["idx"].clone())
+      .collect::<Vec<_>>();
+    assert_eq!(user_metadatas, vec![vec![0], vec![1], vec![2]]);
+  }
+
+  #[test]
+  fn test_rewrite_chunk_errors() {
+    let bytes = compress(&[vec![1.0, 2.0]], CompressorConfig::default().with_use_user_metadata(true));
+    for err in [
+      rewrite_chunk::<f64>(&bytes, 1, &[1.0], CompressorConfig::default()).unwrap_err(),
+      rewrite_chunk::<f64>(&bytes, 0, &[], CompressorConfig::default()).unwrap_err(),
+    ] {
+      assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+    }
+    let err = rewrite_chunk::<f64>(&bytes[..bytes.len() - 1], 0, &[1.0], CompressorConfig::default()).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InsufficientData));
+    let err = rewrite_chunk::<f32>(&bytes, 0, &[1.0], CompressorConfig::default()).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Corruption));
+  }
+
+  #[test]
+  fn test_trim() {
+    let chunks = (0..4)
+      .map(|c| (0..500).map(|i| (c * 500 + i) as f64).collect::<Vec<_>>())
+      .collect::<Vec<_>>();
+    for use_min_max in [false, true] {
+      let config = CompressorConfig::default()
+        .with_use_file_checksum(true)
+        .with_use_user_metadata(true)
+        .with_use_min_max(use_min_max);
+      let bytes = compress(&chunks, config);
+      let decompress_trimmed = |trimmed: Vec<u8>| {
+        let mut decompressor = Decompressor::<f64>::default();
+        decompressor.extend_bytes(&trimmed);
+        decompressor.verify().unwrap();
+        decompressor.simple_decompress().unwrap()
+      };
+
+      assert_eq!(decompress_trimmed(trim_chunks::<f64>(&bytes, 1).unwrap()), chunks[1..].concat());
+      assert!(decompress_trimmed(trim_chunks::<f64>(&bytes, 4).unwrap()).is_empty());
+      for (index, n_trimmed_chunks) in [(0, 0), (499, 0), (500, 1), (1999, 3), (5000, 4)] {
+        let trimmed = trim_before_index::<f64>(&bytes, index).unwrap();
+        assert_eq!(decompress_trimmed(trimmed), chunks[n_trimmed_chunks..].concat());
+      }
+      for (value, n_trimmed_chunks) in [(-1.0, 0), (499.5, 1), (1000.0, 2), (1e9, 4)] {
+        let trimmed = trim_before_value::<f64>(&bytes, value).unwrap();
+        assert_eq!(decompress_trimmed(trimmed), chunks[n_trimmed_chunks..].concat());
+      }
+    }
+
+    let bytes = compress(&chunks, CompressorConfig::default().with_use_user_metadata(true));
+    let err = trim_chunks::<f64>(&bytes, 5).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+  }
+}
@@ -1,6 +1,7 @@
-use std::error::Error;
-use std::fmt::{Display, Formatter};
-use std::fmt;
+use alloc::format;
+use alloc::string::{String, ToString};
+use core::fmt::{Display, Formatter};
+use core::fmt;
 
 /// The different kinds of errors for `q_compress`.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -19,11 +20,56 @@ pub enum ErrorKind {
   InvalidArgument,
 }
 
+/// The part of a .qco file a decompressor was reading when it failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+  Header,
+  /// Chunk metadata, or the footer where chunk metadata would start.
+  ChunkMetadata,
+  ChunkBody,
+}
+
+/// Where in the compressed data a decompression error occurred.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorPosition {
+  /// The part of the file being read.
+  pub section: Section,
+  /// The index of the chunk being read, if in a chunk.
+  pub chunk_idx: Option<usize>,
+  /// The bit index into the compressed data where reading stopped.
+  /// Unlike [`Decompressor::bit_idx`][crate::Decompressor::bit_idx], this
+  /// counts from the start of the data even after compressed memory is
+  /// freed.
+  pub bit_idx: usize,
+}
+
+impl ErrorPosition {
+  /// Returns the index of the byte containing
+  /// [`bit_idx`][ErrorPosition::bit_idx].
+  pub fn byte_idx(&self) -> usize {
+    self.bit_idx / 8
+  }
+}
+
+impl Display for ErrorPosition {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match (self.section, self.chunk_idx) {
+      (Section::Header, _) => write!(f, "header")?,
+      (Section::ChunkMetadata, Some(chunk_idx)) => write!(f, "chunk {} metadata", chunk_idx)?,
+      (Section::ChunkBody, Some(chunk_idx)) => write!(f, "chunk {} body", chunk_idx)?,
+      (section, None) => write!(f, "{:?}", section)?,
+    }
+    write!(f, " at byte {} (bit idx {})", self.byte_idx(), self.bit_idx)
+  }
+}
+
 /// The error type used in results for all `q_compress` functionality.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct QCompressError {
   pub kind: ErrorKind,
   pub message: String,
+  /// Where the error occurred, for errors from reading compressed data.
+  pub position: Option<ErrorPosition>,
 }
 
 impl QCompressError {
@@ -31,9 +77,18 @@ impl QCompressError {
     QCompressError {
       kind,
       message: message.as_ref().to_string(),
+      position: None,
     }
   }
 
+  // keeps the innermost position if there already is one
+  pub(crate) fn at(mut self, position: ErrorPosition) -> Self {
+    if self.position.is_none() {
+      self.position = Some(position);
+    }
+    self
+  }
+
   pub(crate) fn compatibility<S: AsRef<str>>(message: S) -> Self {
     Self::new(ErrorKind::Compatibility, message)
   }
@@ -73,10 +128,15 @@ impl Display for QCompressError {
       "q_compress {:?} error: {}",
       self.kind,
       &self.message
-    )
+    )?;
+    if let Some(position) = &self.position {
+      write!(f, " (reading {})", position)?;
+    }
+    Ok(())
   }
 }
 
-impl Error for QCompressError {}
+#[cfg(feature = "std")]
+impl std::error::Error for QCompressError {}
 
 pub type QCompressResult<T> = Result<T, QCompressError>;
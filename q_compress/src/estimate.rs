@@ -0,0 +1,434 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use crate::auto::auto_delta_encoding_order;
+use crate::{affine, ChunkMetadata, Compressor, CompressorConfig, DistinctCount, Flags, Prefix, PrefixMetadata, UserMetadata};
+use crate::bit_writer::BitWriter;
+use crate::compressor::{choose_decimal_exponent, decimal_int_nums, DEFAULT_CHUNK_SIZE, InternalCompressorConfig, train_prefixes, trained_chunk_body_bit_size};
+use crate::constants::{BYTES_PER_CHUNK_CHECKSUM, BYTES_PER_FILE_CHECKSUM};
+use crate::data_types::NumberLike;
+use crate::delta_encoding;
+use crate::delta_encoding::DeltaMoments;
+use crate::errors::QCompressResult;
+use crate::float_math;
+use crate::xor_encoding;
+use crate::quantization;
+
+const ESTIMATE_N_BLOCKS: usize = 10;
+const ESTIMATE_BLOCK_SIZE: usize = 1000;
+// how many standard errors wide the bounds are
+const ESTIMATE_BOUND_STD_ERRS: f64 = 3.0;
+
+/// An estimate of the byte size of a .qco file, returned by
+/// [`estimate_compressed_size`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SizeEstimate {
+  /// The estimated byte size.
+  pub bytes: usize,
+  /// A rough lower bound on the byte size.
+  pub lower_bound: usize,
+  /// A rough upper bound on the byte size.
+  pub upper_bound: usize,
+}
+
+/// The empirical entropy of numbers compared to the bits compression
+/// achieves on them, returned by [`estimate_entropy`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EntropyEstimate {
+  /// The count of numbers in chunk bodies.
+  /// This excludes numbers stored in chunk metadata instead, like delta
+  /// moments.
+  pub n_body_numbers: usize,
+  /// The empirical Shannon entropy of the numbers each chunk body encodes
+  /// (e.g. deltas, for delta-encoded chunks), in bits per number,
+  /// weighted by chunk body size.
+  pub entropy_bits_per_number: f64,
+  /// The bits per number chunk bodies actually take.
+  pub achieved_bits_per_number: f64,
+}
+
+impl EntropyEstimate {
+  /// Returns how many more bits per number compression takes than the
+  /// entropy.
+  ///
+  /// When this is small, no config tuning can improve compression much
+  /// without changing how the numbers are transformed (e.g. a different
+  /// delta encoding order).
+  pub fn excess_bits_per_number(&self) -> f64 {
+    self.achieved_bits_per_number - self.entropy_bits_per_number
+  }
+}
+
+// Contiguous blocks spread evenly through the numbers, so that deltas within
+// each block are representative.
+fn sample_blocks<T: NumberLike>(nums: &[T]) -> Vec<&[T]> {
+  if nums.len() <= ESTIMATE_N_BLOCKS * ESTIMATE_BLOCK_SIZE {
+    return vec![nums];
+  }
+
+  let stride = nums.len() / ESTIMATE_N_BLOCKS;
+  (0..ESTIMATE_N_BLOCKS)
+    .map(|i| &nums[i * stride..i * stride + ESTIMATE_BLOCK_SIZE])
+    .collect()
+}
+
+fn block_unsigneds<T: NumberLike>(block: &[T], order: usize, lag: usize) -> Vec<T::Unsigned> {
+  if order == 0 {
+    block.iter().map(|x| x.to_unsigned()).collect()
+  } else {
+    delta_encoding::nth_order_deltas(block, order, lag)
+      .into_iter()
+      .map(|x| x.to_unsigned())
+      .collect()
+  }
+}
+
+// trains prefixes on all the blocks together, then counts the body bits each
+// block would take
+fn train_and_count<P: NumberLike>(
+  unsignedss: &[Vec<P::Unsigned>],
+  internal_config: &InternalCompressorConfig,
+  flags: &Flags,
+) -> QCompressResult<(Vec<Prefix<P>>, Vec<usize>)> {
+  let sample_unsigneds = unsignedss.concat();
+  let sample_n = sample_unsigneds.len();
+  let prefixes = train_prefixes::<P>(sample_unsigneds, internal_config, flags, sample_n)?;
+  let block_bits = unsignedss.iter()
+    .map(|unsigneds| trained_chunk_body_bit_size(&prefixes, unsigneds))
+    .collect::<QCompressResult<Vec<_>>>()?;
+  Ok((prefixes, block_bits))
+}
+
+/// Estimates the byte size of compressing `nums` with `config` via
+/// [`Compressor::simple_compress`], without compressing them.
+///
+/// This trains prefixes on a sample of the numbers (a few contiguous blocks
+/// spread throughout) and counts how many bits the sample would take,
+/// skipping the cost of encoding everything.
+/// The bounds come from how much the bits per number vary between sample
+/// blocks, so they are only a rough guide, especially for data whose
+/// distribution drifts over time.
+/// Will return an error if `config` is invalid.
+pub fn estimate_compressed_size<T: NumberLike>(
+  nums: &[T],
+  config: &CompressorConfig,
+) -> QCompressResult<SizeEstimate> {
+  let flags = Flags::from(config);
+  let internal_config = InternalCompressorConfig::from(config);
+
+  let mut compressor = Compressor::<T>::try_from_config(config.clone())?;
+  compressor.header()?;
+  let mut fixed_bytes = compressor.byte_size() + 1;
+  if flags.use_file_checksum {
+    fixed_bytes += BYTES_PER_FILE_CHECKSUM;
+  }
+  if nums.is_empty() {
+    return Ok(SizeEstimate {
+      bytes: fixed_bytes,
+      lower_bound: fixed_bytes,
+      upper_bound: fixed_bytes,
+    });
+  }
+
+  let blocks = sample_blocks(nums);
+  // we assume every chunk can use the sample's quantization or decimal
+  // exponent
+  let quantization = match internal_config.tolerance {
+    Some(tolerance) => quantization::choose_quantization(&blocks.concat(), tolerance)?,
+    None => None,
+  };
+  let decimal_exponent = if quantization.is_none() && flags.use_decimal_multipliers {
+    choose_decimal_exponent(&blocks.concat())
+  } else {
+    None
+  };
+  let int_blocks;
+  let blocks = match (quantization, decimal_exponent) {
+    (Some(quantization), _) => {
+      int_blocks = blocks.iter()
+        .map(|block| quantization::quantized_nums(block, quantization))
+        .collect::<Vec<_>>();
+      int_blocks.iter().map(|block| block.as_slice()).collect()
+    },
+    (None, Some(exponent)) => {
+      int_blocks = blocks.iter()
+        .map(|block| decimal_int_nums(block, exponent))
+        .collect::<Vec<_>>();
+      int_blocks.iter().map(|block| block.as_slice()).collect()
+    },
+    (None, None) => blocks,
+  };
+  // and likewise the sample's affine transform
+  let is_int = T::MANTISSA_BITS.is_none() || quantization.is_some() || decimal_exponent.is_some();
+  let affine_transform = if flags.use_affine_transforms && is_int {
+    affine::choose_affine_transform(&blocks.concat())
+  } else {
+    None
+  };
+  let multiple_blocks;
+  let blocks = match &affine_transform {
+    Some(affine_transform) => {
+      multiple_blocks = blocks.iter()
+        .map(|block| affine_transform.multiples(block))
+        .collect::<Vec<_>>();
+      multiple_blocks.iter().map(|block| block.as_slice()).collect()
+    },
+    None => blocks,
+  };
+  // we likewise assume every chunk uses the first block's delta order
+  let order = if flags.use_chunk_delta_orders {
+    auto_delta_encoding_order(
+      blocks[0],
+      internal_config.compression_level,
+      flags.delta_lag,
+      internal_config.deterministic,
+    )
+  } else {
+    flags.delta_encoding_order
+  };
+  let unsignedss = blocks.iter()
+    .map(|block| block_unsigneds(block, order, flags.delta_lag))
+    .collect::<Vec<_>>();
+  let sample_n = unsignedss.iter().map(|unsigneds| unsigneds.len()).sum::<usize>();
+
+  // the chunk metadata size barely depends on n, so we use the sample's
+  let (prefix_metadata, block_bits) = if order == 0 {
+    let (prefixes, block_bits) = train_and_count::<T>(&unsignedss, &internal_config, &flags)?;
+    (PrefixMetadata::Simple { prefixes }, block_bits)
+  } else {
+    let (prefixes, block_bits) = train_and_count::<T::Signed>(&unsignedss, &internal_config, &flags)?;
+    let delta_moments = DeltaMoments::from(blocks[0], order, flags.delta_lag);
+    (PrefixMetadata::Delta { delta_moments, prefixes }, block_bits)
+  };
+  let metadata = ChunkMetadata::<T> {
+    n: blocks.iter().map(|block| block.len()).sum(),
+    compressed_body_size: 0,
+    prefix_metadata,
+    decimal_exponent,
+    quantization,
+    // only the sizes of the min, max, and distinct count matter here
+    min_max: blocks.first()
+      .and_then(|block| block.first())
+      .filter(|_| flags.use_min_max)
+      .map(|&x| (x, x)),
+    distinct_count: Some(DistinctCount { count: 1, is_exact: true })
+      .filter(|_| flags.use_distinct_count),
+    nan_positions: None,
+    affine_transform,
+    uses_shared_prefixes: false,
+    user_metadata: UserMetadata::new(),
+    phantom: PhantomData,
+  };
+  let mut writer = BitWriter::default();
+  metadata.write_to(&mut writer, &flags);
+  let mut bytes_per_chunk = 1 + writer.byte_size();
+  if flags.use_chunk_checksums {
+    bytes_per_chunk += BYTES_PER_CHUNK_CHECKSUM;
+  }
+
+  let sample_bits = block_bits.iter().sum::<usize>();
+  let block_bits_per_nums = unsignedss.iter()
+    .zip(&block_bits)
+    .filter(|(unsigneds, _)| !unsigneds.is_empty())
+    .map(|(unsigneds, &bits)| bits as f64 / unsigneds.len() as f64)
+    .collect::<Vec<_>>();
+  let bits_per_num = if sample_n == 0 {
+    0.0
+  } else {
+    sample_bits as f64 / sample_n as f64
+  };
+
+  let mut bytes = fixed_bytes;
+  let mut body_nums = 0;
+  for chunk in nums.chunks(DEFAULT_CHUNK_SIZE) {
+    let chunk_body_nums = chunk.len().saturating_sub(order * flags.delta_lag);
+    body_nums += chunk_body_nums;
+    bytes += bytes_per_chunk + float_math::ceil(bits_per_num * chunk_body_nums as f64 / 8.0) as usize;
+  }
+
+  // the standard error of the mean bits per number, across sample blocks
+  let n_blocks = block_bits_per_nums.len();
+  let std_err = if n_blocks < 2 {
+    0.0
+  } else {
+    let variance = block_bits_per_nums.iter()
+      .map(|&x| (x - bits_per_num) * (x - bits_per_num))
+      .sum::<f64>() / (n_blocks - 1) as f64;
+    float_math::sqrt(variance / n_blocks as f64)
+  };
+  let margin = float_math::ceil(ESTIMATE_BOUND_STD_ERRS * std_err * body_nums as f64 / 8.0) as usize;
+
+  Ok(SizeEstimate {
+    bytes,
+    lower_bound: bytes.saturating_sub(margin).max(fixed_bytes),
+    upper_bound: bytes + margin,
+  })
+}
+
+// the empirical Shannon entropy of the unsigneds, in total bits
+fn entropy_bits<U: Ord + Copy>(mut unsigneds: Vec<U>) -> f64 {
+  let n = unsigneds.len() as f64;
+  unsigneds.sort_unstable();
+  let mut res = 0.0;
+  let mut start = 0;
+  for i in 1..unsigneds.len() + 1 {
+    if i == unsigneds.len() || unsigneds[i] != unsigneds[start] {
+      let count = (i - start) as f64;
+      res -= count * float_math::log2(count / n);
+      start = i;
+    }
+  }
+  res
+}
+
+// the unsigneds a chunk body encodes for the chunk's numbers
+fn body_unsigneds<T: NumberLike>(nums: &[T], meta: &ChunkMetadata<T>, delta_lag: usize) -> Vec<T::Unsigned> {
+  let int_nums;
+  let nums = match (meta.quantization, meta.decimal_exponent) {
+    (Some(quantization), _) => {
+      int_nums = quantization::quantized_nums(nums, quantization);
+      &int_nums
+    },
+    (None, Some(exponent)) => {
+      int_nums = decimal_int_nums(nums, exponent);
+      &int_nums
+    },
+    (None, None) => nums,
+  };
+  let multiples;
+  let nums = match &meta.affine_transform {
+    Some(affine_transform) => {
+      multiples = affine_transform.multiples(nums);
+      &multiples
+    },
+    None => nums,
+  };
+  match &meta.prefix_metadata {
+    PrefixMetadata::Simple { .. } |
+    PrefixMetadata::Raw { .. } |
+    PrefixMetadata::FloatSplit { .. } => block_unsigneds(nums, 0, delta_lag),
+    PrefixMetadata::Delta { delta_moments, .. } => block_unsigneds(nums, delta_moments.order(), delta_lag),
+    PrefixMetadata::Xor { .. } => xor_encoding::xor_unsigneds(nums),
+  }
+}
+
+/// Compresses `nums` with `config` as
+/// [`Compressor::simple_compress`] would and compares the bits per number
+/// of the chunk bodies to the empirical Shannon entropy of what they
+/// encode.
+///
+/// The entropy is the fewest bits per number any code could take if it
+/// already knew each chunk's exact distribution of numbers, so it is an
+/// optimistic target:
+/// for data where most numbers are distinct, like continuous floats, it
+/// is far below what any compressor can achieve.
+/// But when compression is already close to it, further config tuning
+/// can't help.
+/// Will return an error if `config` is invalid.
+pub fn estimate_entropy<T: NumberLike>(
+  nums: &[T],
+  config: &CompressorConfig,
+) -> QCompressResult<EntropyEstimate> {
+  let mut compressor = Compressor::<T>::try_from_config(config.clone())?;
+  compressor.header()?;
+  let mut n_body_numbers = 0;
+  let mut entropy = 0.0;
+  let mut body_bits = 0;
+  for chunk in nums.chunks(DEFAULT_CHUNK_SIZE) {
+    let meta = compressor.chunk(chunk)?;
+    let unsigneds = body_unsigneds(chunk, &meta, config.delta_lag);
+    n_body_numbers += unsigneds.len();
+    entropy += entropy_bits(unsigneds);
+    body_bits += meta.compressed_body_size * 8;
+    compressor.drain_bytes();
+  }
+
+  let per_number = |bits: f64| if n_body_numbers == 0 {
+    0.0
+  } else {
+    bits / n_body_numbers as f64
+  };
+  Ok(EntropyEstimate {
+    n_body_numbers,
+    entropy_bits_per_number: per_number(entropy),
+    achieved_bits_per_number: per_number(body_bits as f64),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::bits;
+  use super::*;
+
+  fn assert_estimate_close<T: NumberLike>(nums: &[T], config: CompressorConfig) {
+    let estimate = estimate_compressed_size(nums, &config).unwrap();
+    let actual = Compressor::<T>::from_config(config).simple_compress(nums).len();
+    assert!(estimate.lower_bound <= estimate.bytes && estimate.bytes <= estimate.upper_bound);
+    let error = (estimate.bytes as f64 - actual as f64).abs();
+    assert!(
+      error <= 0.1 * actual as f64 + 16.0,
+      "estimate {:?} far from actual {}",
+      estimate,
+      actual,
+    );
+  }
+
+  #[test]
+  fn test_body_bit_size_matches_compression() {
+    let mut sparse = vec![0_u32; 2000];
+    for i in (0..2000).step_by(37) {
+      sparse[i] = i as u32;
+    }
+    let spread = (0..2000).map(|i| (i * i) % 1009).collect::<Vec<u32>>();
+    for nums in [sparse, spread] {
+      let config = CompressorConfig::default();
+      let unsigneds = nums.clone();
+      let prefixes = train_prefixes::<u32>(
+        nums.clone(),
+        &InternalCompressorConfig::from(&config),
+        &Flags::from(&config),
+        nums.len(),
+      ).unwrap();
+      let bits = trained_chunk_body_bit_size(&prefixes, &unsigneds).unwrap();
+      let mut compressor = Compressor::<u32>::from_config(config);
+      compressor.header().unwrap();
+      let meta = compressor.chunk(&nums).unwrap();
+      assert_eq!(meta.compressed_body_size, bits::ceil_div(bits, 8));
+    }
+  }
+
+  #[test]
+  fn test_estimates() {
+    let spread = (0..30000).map(|i| ((i * i) % 1009) as i64).collect::<Vec<_>>();
+    assert_estimate_close(&spread, CompressorConfig::default());
+    let trend = (0..30000).map(|i| (i * 3 + (i * i) % 7) as i64).collect::<Vec<_>>();
+    assert_estimate_close(&trend, CompressorConfig::default().with_delta_encoding_order(1));
+    assert_estimate_close(&[1.5_f64, 2.5, -3.0], CompressorConfig::default());
+    assert_estimate_close::<f32>(&[], CompressorConfig::default());
+
+    let invalid = CompressorConfig::default().with_compression_level(13);
+    assert!(estimate_compressed_size(&spread, &invalid).is_err());
+  }
+
+  #[test]
+  fn test_estimate_entropy() {
+    // 16 equally likely values take 4 bits each
+    let uniform = (0..20000).map(|i| ((i * 7) % 16) as i64).collect::<Vec<_>>();
+    let estimate = estimate_entropy(&uniform, &CompressorConfig::default()).unwrap();
+    assert_eq!(estimate.n_body_numbers, 20000);
+    assert!((estimate.entropy_bits_per_number - 4.0).abs() < 1e-9);
+    assert!(estimate.excess_bits_per_number() >= 0.0);
+    assert!(estimate.excess_bits_per_number() < 0.1);
+
+    // the deltas of a line are constant
+    let line = (0..20000).map(|i| i * 3).collect::<Vec<i64>>();
+    let config = CompressorConfig::default().with_delta_encoding_order(1);
+    let estimate = estimate_entropy(&line, &config).unwrap();
+    assert_eq!(estimate.n_body_numbers, 19999);
+    assert_eq!(estimate.entropy_bits_per_number, 0.0);
+
+    let estimate = estimate_entropy::<f32>(&[], &CompressorConfig::default()).unwrap();
+    assert_eq!(estimate.achieved_bits_per_number, 0.0);
+  }
+}
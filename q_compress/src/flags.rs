@@ -2,16 +2,20 @@
 // of the .qco file.
 // New flags may be added in over time in a backward-compatible way.
 
-use std::cmp::min;
-use std::convert::{TryFrom, TryInto};
-use std::marker::PhantomData;
+use alloc::{format, vec};
+use alloc::vec::Vec;
+use core::cmp::min;
+use core::convert::{TryFrom, TryInto};
+use core::marker::PhantomData;
 
 use crate::bit_reader::BitReader;
+use crate::bit_words::BitWords;
 use crate::{CompressorConfig};
 use crate::bit_writer::BitWriter;
 use crate::bits;
-use crate::constants::{BITS_TO_ENCODE_DELTA_ENCODING_ORDER, BITS_TO_ENCODE_N_ENTRIES, MAX_DELTA_ENCODING_ORDER};
+use crate::constants::{BITS_TO_ENCODE_DELTA_ENCODING_ORDER, BITS_TO_ENCODE_DELTA_LAG, BITS_TO_ENCODE_N_ENTRIES, MAX_DELTA_ENCODING_ORDER, MAX_DELTA_LAG};
 use crate::errors::{QCompressError, QCompressResult};
+use crate::float_math;
 
 /// The configuration stored in a .qco file's header.
 ///
@@ -24,6 +28,7 @@ use crate::errors::{QCompressError, QCompressResult};
 /// internally by `Compressor::from_config`.
 /// However, in some circumstances you may want to inspect flags during
 /// decompression.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Debug, PartialEq)]
 pub struct Flags {
   /// Whether to use 5 bits to encode the length of a prefix Huffman code,
@@ -58,6 +63,121 @@ pub struct Flags {
   ///
   /// Introduced in 0.10.0.
   pub use_gcds: bool,
+  /// Whether each chunk body is followed by a 4-byte CRC32 checksum of the
+  /// body's bytes.
+  /// When true, the decompressor verifies each chunk body against its
+  /// checksum and returns a corruption error on mismatch.
+  ///
+  /// Introduced in 0.11.2.
+  pub use_chunk_checksums: bool,
+  /// Whether the termination byte is followed by a 4-byte CRC32 checksum of
+  /// all the file's bytes up to and including the termination byte.
+  /// This can be checked with
+  /// [`Decompressor::verify`][crate::Decompressor::verify].
+  ///
+  /// Introduced in 0.11.2.
+  pub use_file_checksum: bool,
+  /// Whether each chunk metadata may contain a decimal exponent `d`, in
+  /// which case every float `x` in the chunk was compressed as the integer
+  /// `x * 10^d`.
+  /// See [`ChunkMetadata::decimal_exponent`][crate::ChunkMetadata::decimal_exponent].
+  ///
+  /// Introduced in 0.11.2.
+  pub use_decimal_multipliers: bool,
+  /// Whether each chunk metadata contains a bit for whether the chunk was
+  /// XOR encoded, in which case it stores the chunk's first number and
+  /// compresses the XOR of each consecutive pair of numbers instead.
+  /// See [`PrefixMetadata::Xor`][crate::PrefixMetadata::Xor].
+  ///
+  /// Introduced in 0.11.2.
+  pub use_xor_encoding: bool,
+  /// Whether each chunk metadata (unless XOR encoded) contains its own
+  /// delta encoding order, stored as 3 bits.
+  /// When true, the file-wide `delta_encoding_order` is 0 and unused.
+  ///
+  /// Introduced in 0.11.2.
+  pub use_chunk_delta_orders: bool,
+  /// How many positions earlier each number's delta was taken against
+  /// during delta encoding, 1 for ordinary deltas.
+  /// When greater than 1, this is stored as a bit followed by 16 bits for
+  /// the lag; otherwise it is stored as a single 0 bit.
+  /// See `CompressorConfig` for more details.
+  ///
+  /// Introduced in 0.11.2.
+  pub delta_lag: usize,
+  /// Whether each chunk metadata may contain a
+  /// [`Quantization`][crate::Quantization], in which
+  /// case the chunk was compressed lossily.
+  /// See [`ChunkMetadata::quantization`][crate::ChunkMetadata::quantization].
+  ///
+  /// Introduced in 0.11.2.
+  pub use_quantization: bool,
+  /// Whether each chunk metadata contains a bit for whether the chunk was
+  /// stored raw, in which case every number is written with the same
+  /// fixed bit width instead of using prefixes.
+  /// See [`PrefixMetadata::Raw`][crate::PrefixMetadata::Raw].
+  ///
+  /// Introduced in 0.11.2.
+  pub use_raw_fallback: bool,
+  /// Whether the flags are followed by a prefix table shared across chunks,
+  /// and each chunk metadata (unless XOR encoded or raw) contains a bit for
+  /// whether it uses the shared table instead of its own prefixes.
+  /// The shared table describes deltas if `delta_encoding_order` is
+  /// nonzero, so only chunks of that order may use it.
+  /// See [`Compressor::train_shared_prefixes`][crate::Compressor::train_shared_prefixes].
+  ///
+  /// Introduced in 0.11.2.
+  pub use_shared_prefixes: bool,
+  /// Whether the header and each chunk metadata contain a section of
+  /// [`UserMetadata`][crate::UserMetadata] key-value pairs.
+  ///
+  /// Introduced in 0.11.2.
+  pub use_user_metadata: bool,
+  /// Whether each chunk metadata (unless raw) contains a bit for whether
+  /// the chunk was float split encoded, in which case the signs and
+  /// exponents of its floats were compressed separately from their
+  /// mantissas.
+  /// See [`PrefixMetadata::FloatSplit`][crate::PrefixMetadata::FloatSplit].
+  ///
+  /// Introduced in 0.11.2.
+  pub use_float_split: bool,
+  /// Whether each chunk metadata contains a bit for whether it holds the
+  /// exact minimum and maximum of the chunk's numbers, followed by them.
+  /// See [`ChunkMetadata::min_max`][crate::ChunkMetadata::min_max].
+  ///
+  /// Introduced in 0.11.2.
+  pub use_min_max: bool,
+  /// Whether each chunk metadata contains a bit for whether it holds the
+  /// number of distinct numbers in the chunk, followed by it.
+  /// See [`ChunkMetadata::distinct_count`][crate::ChunkMetadata::distinct_count].
+  ///
+  /// Introduced in 0.11.2.
+  pub use_distinct_count: bool,
+  /// Whether each chunk metadata contains a bit for whether the chunk's
+  /// NaNs were stripped out of its body, followed by their positions.
+  /// See [`ChunkMetadata::nan_positions`][crate::ChunkMetadata::nan_positions].
+  ///
+  /// Introduced in 0.11.2.
+  pub use_nan_positions: bool,
+  /// Whether each chunk metadata contains a bit for whether the chunk's
+  /// numbers were compressed as multiples of a common spacing, followed by
+  /// the spacing and offset.
+  /// See [`ChunkMetadata::affine_transform`][crate::ChunkMetadata::affine_transform].
+  ///
+  /// Introduced in 0.11.2.
+  pub use_affine_transforms: bool,
+  /// Whether the compressor replaced every -0.0 with +0.0, so the file
+  /// doesn't preserve the sign of zero.
+  /// This doesn't affect decompression.
+  ///
+  /// Introduced in 0.11.2.
+  pub canonicalize_negative_zero: bool,
+  /// Whether the compressor flushed every subnormal float to zero, so the
+  /// file doesn't preserve subnormals.
+  /// This doesn't affect decompression.
+  ///
+  /// Introduced in 0.11.2.
+  pub flush_subnormals: bool,
   // Make it API-stable to add more fields in the future
   pub(crate) phantom: PhantomData<()>,
 }
@@ -71,6 +191,23 @@ impl TryFrom<Vec<bool>> for Flags {
       delta_encoding_order: 0,
       use_min_count_encoding: false,
       use_gcds: false,
+      use_chunk_checksums: false,
+      use_file_checksum: false,
+      use_decimal_multipliers: false,
+      use_xor_encoding: false,
+      use_chunk_delta_orders: false,
+      delta_lag: 1,
+      use_quantization: false,
+      use_raw_fallback: false,
+      use_shared_prefixes: false,
+      use_user_metadata: false,
+      use_float_split: false,
+      use_min_max: false,
+      use_distinct_count: false,
+      use_nan_positions: false,
+      canonicalize_negative_zero: false,
+      flush_subnormals: false,
+      use_affine_transforms: false,
       phantom: PhantomData,
     };
 
@@ -87,6 +224,47 @@ impl TryFrom<Vec<bool>> for Flags {
 
     flags.use_gcds = bit_iter.next() == Some(&true);
 
+    flags.use_chunk_checksums = bit_iter.next() == Some(&true);
+
+    flags.use_file_checksum = bit_iter.next() == Some(&true);
+
+    flags.use_decimal_multipliers = bit_iter.next() == Some(&true);
+
+    flags.use_xor_encoding = bit_iter.next() == Some(&true);
+
+    flags.use_chunk_delta_orders = bit_iter.next() == Some(&true);
+
+    if bit_iter.next() == Some(&true) {
+      let mut delta_lag_bits = Vec::new();
+      while delta_lag_bits.len() < BITS_TO_ENCODE_DELTA_LAG {
+        delta_lag_bits.push(bit_iter.next().cloned().unwrap_or(false));
+      }
+      flags.delta_lag = bits::bits_to_usize(&delta_lag_bits);
+      if flags.delta_lag == 0 {
+        return Err(QCompressError::corruption("delta lag may not be 0"));
+      }
+    }
+
+    flags.use_quantization = bit_iter.next() == Some(&true);
+
+    flags.use_raw_fallback = bit_iter.next() == Some(&true);
+
+    flags.use_shared_prefixes = bit_iter.next() == Some(&true);
+
+    flags.use_user_metadata = bit_iter.next() == Some(&true);
+
+    flags.use_float_split = bit_iter.next() == Some(&true);
+
+    flags.use_min_max = bit_iter.next() == Some(&true);
+    flags.use_distinct_count = bit_iter.next() == Some(&true);
+
+    flags.use_nan_positions = bit_iter.next() == Some(&true);
+
+    flags.canonicalize_negative_zero = bit_iter.next() == Some(&true);
+    flags.flush_subnormals = bit_iter.next() == Some(&true);
+
+    flags.use_affine_transforms = bit_iter.next() == Some(&true);
+
     for &bit in bit_iter {
       if bit {
         return Err(QCompressError::compatibility(
@@ -119,6 +297,48 @@ impl TryInto<Vec<bool>> for &Flags {
 
     res.push(self.use_gcds);
 
+    res.push(self.use_chunk_checksums);
+
+    res.push(self.use_file_checksum);
+
+    res.push(self.use_decimal_multipliers);
+
+    res.push(self.use_xor_encoding);
+
+    res.push(self.use_chunk_delta_orders);
+
+    if self.delta_lag == 0 || self.delta_lag > MAX_DELTA_LAG {
+      return Err(QCompressError::invalid_argument(format!(
+        "delta lag must be between 1 and {} (was {})",
+        MAX_DELTA_LAG,
+        self.delta_lag,
+      )));
+    }
+    res.push(self.delta_lag > 1);
+    if self.delta_lag > 1 {
+      res.extend(bits::usize_truncated_to_bits(self.delta_lag, BITS_TO_ENCODE_DELTA_LAG));
+    }
+
+    res.push(self.use_quantization);
+
+    res.push(self.use_raw_fallback);
+
+    res.push(self.use_shared_prefixes);
+
+    res.push(self.use_user_metadata);
+
+    res.push(self.use_float_split);
+
+    res.push(self.use_min_max);
+    res.push(self.use_distinct_count);
+
+    res.push(self.use_nan_positions);
+
+    res.push(self.canonicalize_negative_zero);
+    res.push(self.flush_subnormals);
+
+    res.push(self.use_affine_transforms);
+
     let necessary_len = res.iter()
       .rposition(|&bit| bit)
       .map(|idx| idx + 1)
@@ -130,6 +350,32 @@ impl TryInto<Vec<bool>> for &Flags {
 }
 
 impl Flags {
+  /// Parses flags from the start of `bytes`, returning them along with the
+  /// number of bytes they took.
+  ///
+  /// In a .qco file, the flags start right after the 4-byte magic header
+  /// and the data type byte (see [`DataType`][crate::data_types::DataType]).
+  /// They take one byte per 7 bits of flags, with the last bit of each byte
+  /// indicating whether another byte follows.
+  /// This layout is part of the .qco format and won't change.
+  /// Will return an error if the bytes end too soon, or a compatibility
+  /// error if they contain flags from a newer version of `q_compress`.
+  pub fn from_bytes(bytes: &[u8]) -> QCompressResult<(Self, usize)> {
+    let words = BitWords::from(bytes);
+    let mut reader = BitReader::from(&words);
+    let flags = Self::parse_from(&mut reader)?;
+    Ok((flags, reader.aligned_byte_idx()?))
+  }
+
+  /// Returns the bytes of these flags as written in a .qco file header.
+  /// Will return an error if the flags are invalid, e.g. with too large a
+  /// `delta_encoding_order`.
+  pub fn to_bytes(&self) -> QCompressResult<Vec<u8>> {
+    let mut writer = BitWriter::default();
+    self.write(&mut writer)?;
+    Ok(writer.drain_bytes())
+  }
+
   pub(crate) fn parse_from(reader: &mut BitReader) -> QCompressResult<Self> {
     reader.aligned_byte_idx()?; // assert it's byte-aligned
     let mut bools = Vec::new();
@@ -170,7 +416,7 @@ impl Flags {
 
   pub(crate) fn bits_to_encode_count(&self, n: usize) -> usize {
     if self.use_min_count_encoding {
-      ((n + 1) as f64).log2().ceil() as usize
+      float_math::ceil(float_math::log2((n + 1) as f64)) as usize
     } else {
       BITS_TO_ENCODE_N_ENTRIES
     }
@@ -181,9 +427,31 @@ impl From<&CompressorConfig> for Flags {
   fn from(config: &CompressorConfig) -> Self {
     Flags {
       use_5_bit_code_len: true,
-      delta_encoding_order: config.delta_encoding_order,
+      delta_encoding_order: if config.use_chunk_delta_orders {
+        0
+      } else {
+        config.delta_encoding_order
+      },
       use_min_count_encoding: true,
       use_gcds: config.use_gcds,
+      use_chunk_checksums: config.use_chunk_checksums,
+      use_file_checksum: config.use_file_checksum,
+      use_decimal_multipliers: config.use_decimal_multipliers,
+      use_xor_encoding: config.use_xor_encoding,
+      use_chunk_delta_orders: config.use_chunk_delta_orders,
+      delta_lag: config.delta_lag,
+      use_quantization: config.tolerance.is_some(),
+      use_raw_fallback: config.use_raw_fallback,
+      // only Compressor::train_shared_prefixes turns this on
+      use_shared_prefixes: false,
+      use_user_metadata: config.use_user_metadata,
+      use_float_split: config.use_float_split_encoding,
+      use_min_max: config.use_min_max,
+      use_distinct_count: config.use_distinct_count,
+      use_nan_positions: config.use_nan_positions,
+      canonicalize_negative_zero: config.canonicalize_negative_zero,
+      flush_subnormals: config.flush_subnormals,
+      use_affine_transforms: config.use_affine_transforms,
       phantom: PhantomData,
     }
   }
@@ -0,0 +1,114 @@
+// Float functions like log2 live in std rather than core, so without std
+// we fall back to libm.
+
+#[cfg(feature = "std")]
+pub fn log2(x: f64) -> f64 {
+  x.log2()
+}
+
+#[cfg(not(feature = "std"))]
+pub fn log2(x: f64) -> f64 {
+  libm::log2(x)
+}
+
+#[cfg(feature = "std")]
+pub fn floor(x: f64) -> f64 {
+  x.floor()
+}
+
+#[cfg(not(feature = "std"))]
+pub fn floor(x: f64) -> f64 {
+  libm::floor(x)
+}
+
+#[cfg(feature = "std")]
+pub fn ceil(x: f64) -> f64 {
+  x.ceil()
+}
+
+#[cfg(not(feature = "std"))]
+pub fn ceil(x: f64) -> f64 {
+  libm::ceil(x)
+}
+
+#[cfg(feature = "std")]
+pub fn sqrt(x: f64) -> f64 {
+  x.sqrt()
+}
+
+#[cfg(not(feature = "std"))]
+pub fn sqrt(x: f64) -> f64 {
+  libm::sqrt(x)
+}
+
+const MANTISSA_BITS: u32 = 52;
+const MANTISSA_MASK: u64 = (1 << MANTISSA_BITS) - 1;
+const EXPONENT_BIAS: i64 = 1023;
+const N_LOG_SERIES_TERMS: usize = 12;
+
+// The platform's log2 may differ in the last bit between operating systems
+// and architectures, so deterministic compression uses this one instead.
+// It only uses basic IEEE 754 operations in a fixed order, which are
+// correctly rounded and therefore give identical results everywhere.
+// It is exact for powers of 2 and otherwise accurate to a few ulps.
+pub fn portable_log2(x: f64) -> f64 {
+  if x.is_nan() || x < 0.0 {
+    return f64::NAN;
+  } else if x == 0.0 {
+    return f64::NEG_INFINITY;
+  } else if x == f64::INFINITY {
+    return x;
+  }
+
+  let mut bits = x.to_bits();
+  let mut exponent = 0_i64;
+  if bits >> MANTISSA_BITS == 0 {
+    // subnormal; scale it up to a normal number first
+    bits = (x * (1_u64 << MANTISSA_BITS) as f64).to_bits();
+    exponent -= MANTISSA_BITS as i64;
+  }
+  exponent += (bits >> MANTISSA_BITS) as i64 - EXPONENT_BIAS;
+  let mut mantissa = f64::from_bits((bits & MANTISSA_MASK) | ((EXPONENT_BIAS as u64) << MANTISSA_BITS));
+  if mantissa > core::f64::consts::SQRT_2 {
+    mantissa /= 2.0;
+    exponent += 1;
+  }
+
+  // now mantissa is in [sqrt(1/2), sqrt(2)] and
+  // ln(mantissa) = 2 * atanh(s) = 2 * (s + s^3 / 3 + s^5 / 5 + ...)
+  // with |s| < 0.18, so a few terms of the series suffice
+  let s = (mantissa - 1.0) / (mantissa + 1.0);
+  let s2 = s * s;
+  let mut series = 0.0;
+  for i in (0..N_LOG_SERIES_TERMS).rev() {
+    series = 1.0 / (2 * i + 1) as f64 + s2 * series;
+  }
+  exponent as f64 + 2.0 * s * series * core::f64::consts::LOG2_E
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_portable_log2() {
+    for k in -1022..1024 {
+      let x = f64::from_bits(((k + EXPONENT_BIAS) as u64) << MANTISSA_BITS);
+      assert_eq!(portable_log2(x), k as f64);
+    }
+    for k in 0..MANTISSA_BITS {
+      assert_eq!(portable_log2(f64::from_bits(1 << k)), k as f64 - 1074.0);
+    }
+    let mut x = 1E-300_f64;
+    while x < 1E300 {
+      let expected = x.log2();
+      assert!((portable_log2(x) - expected).abs() <= 1E-14 * expected.abs().max(1.0), "{}", x);
+      x *= 1.37;
+    }
+    assert!((portable_log2(3.0) - 1.584962500721156).abs() < 1E-15);
+    assert_eq!(portable_log2(0.0), f64::NEG_INFINITY);
+    assert_eq!(portable_log2(f64::INFINITY), f64::INFINITY);
+    assert!(portable_log2(-1.0).is_nan());
+    assert!(portable_log2(f64::NAN).is_nan());
+  }
+}
@@ -0,0 +1,50 @@
+use alloc::vec::Vec;
+
+use crate::data_types::{NumberLike, UnsignedLike};
+
+// Splits each unsigned representation into its high bits (the float's sign
+// and exponent) and its low `mantissa_bits` bits (the mantissa).
+pub fn split_unsigneds<U: UnsignedLike>(
+  unsigneds: &[U],
+  mantissa_bits: usize,
+) -> (Vec<U>, Vec<U>) {
+  let mantissa_mask = U::MAX >> (U::BITS - mantissa_bits);
+  unsigneds.iter()
+    .map(|&u| (u >> mantissa_bits, u & mantissa_mask))
+    .unzip()
+}
+
+// Writes one number to each element of `dst`, joining each of `exponents`
+// with the corresponding element of `mantissas`.
+pub fn reconstruct_nums_into<T: NumberLike>(
+  exponents: &[T::Unsigned],
+  mantissas: &[T::Unsigned],
+  mantissa_bits: usize,
+  dst: &mut [T],
+) {
+  for ((num, &exponent), &mantissa) in dst.iter_mut().zip(exponents).zip(mantissas) {
+    let mut u = exponent << mantissa_bits;
+    u |= mantissa;
+    *num = T::from_unsigned(u);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_split_and_reconstruct() {
+    let nums = vec![-3.5_f64, -0.0, 0.0, 1.0, 1.5, f64::INFINITY, f64::MIN_POSITIVE / 2.0];
+    let unsigneds = nums.iter().map(|x| x.to_unsigned()).collect::<Vec<_>>();
+    let (exponents, mantissas) = split_unsigneds(&unsigneds, 52);
+    assert!(exponents.iter().all(|&e| e < 1 << 12));
+    assert!(mantissas.iter().all(|&m| m < 1 << 52));
+    // 1.0 and 1.5 share their sign and exponent
+    assert_eq!(exponents[3], exponents[4]);
+    let mut reconstructed = vec![0.0; nums.len()];
+    reconstruct_nums_into(&exponents, &mantissas, 52, &mut reconstructed);
+    let bits = |xs: &[f64]| xs.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+    assert_eq!(bits(&reconstructed), bits(&nums));
+  }
+}
@@ -1,8 +1,11 @@
+use alloc::format;
+
 use crate::data_types::{NumberLike, UnsignedLike};
 use crate::bit_reader::BitReader;
 use crate::bit_writer::BitWriter;
 use crate::{Flags, Prefix};
 use crate::errors::{QCompressError, QCompressResult};
+use crate::float_math;
 
 // fast if b is small, requires b > 0
 pub fn pair_gcd<U: UnsignedLike>(mut a: U, mut b: U) -> U {
@@ -34,6 +37,36 @@ pub fn gcd<U: UnsignedLike>(sorted: &[U]) -> U {
   res
 }
 
+// Like gcd, but first checks whether any hinted divisors divide every
+// number's offset from the lowest, returning the largest that does.
+// That only takes one remainder per number per hint, which is cheaper than
+// finding the exact GCD.
+pub fn gcd_with_hints<U: UnsignedLike>(sorted: &[U], hints: &[usize]) -> U {
+  let lower = sorted[0];
+  let range = sorted[sorted.len() - 1] - lower;
+  let mut res = None;
+  for &hint in hints {
+    let divisor = U::from_word(hint);
+    // skip hints that don't fit in the type or can't be a valid GCD
+    if hint <= 1 || divisor.rshift_word(0) != hint || divisor > range {
+      continue;
+    }
+    let divides = |&x: &U| {
+      let mut remainder = x - lower;
+      remainder %= divisor;
+      remainder == U::ZERO
+    };
+    let is_larger = match res {
+      Some(best) => divisor > best,
+      None => true,
+    };
+    if is_larger && sorted.iter().all(divides) {
+      res = Some(divisor);
+    }
+  }
+  res.unwrap_or_else(|| gcd(sorted))
+}
+
 // Returns Some(gcd) if it is more concise to use the same GCD for all prefixes
 // Returns None if it is more concise to describe each prefix's GCD separately
 // 4 cases:
@@ -92,7 +125,7 @@ pub fn use_gcd_arithmetic<T: NumberLike>(prefixes: &[Prefix<T>]) -> bool {
 }
 
 pub fn gcd_bits_required<U: UnsignedLike>(range: U) -> usize {
-  range.to_f64().log2().ceil() as usize
+  float_math::ceil(float_math::log2(range.to_f64())) as usize
 }
 
 // to store gcd, we write and read gcd - 1 in the minimum number of bits
@@ -191,6 +224,20 @@ mod tests {
     assert_eq!(pair_gcd(7, (1_u64 << 63) - 1), 7);
   }
 
+  #[test]
+  fn test_gcd_with_hints() {
+    let sorted = [60000_u64, 120000, 300000];
+    assert_eq!(gcd(&sorted), 60000);
+    assert_eq!(gcd_with_hints(&sorted, &[7, 60000, 1000]), 60000);
+    assert_eq!(gcd_with_hints(&sorted, &[7, 1000]), 1000);
+    assert_eq!(gcd_with_hints(&sorted, &[7, 0, 1]), 60000);
+    // too large to be a GCD of this range
+    assert_eq!(gcd_with_hints(&[0_u64, 60000], &[120000]), 60000);
+    // too large for the type
+    assert_eq!(gcd_with_hints(&[0_u16, 65535], &[65535 * 65536]), 65535);
+    assert_eq!(gcd_with_hints(&[5_u32, 5], &[5]), 1);
+  }
+
   #[test]
   fn test_gcd() {
     assert_eq!(gcd(&[0_u32, 4, 6, 8, 10]), 2);
@@ -1,4 +1,5 @@
-use std::cmp::min;
+use alloc::vec::Vec;
+use core::cmp::min;
 
 use crate::bit_reader::BitReader;
 use crate::constants::MAX_PREFIX_TABLE_SIZE_LOG;
@@ -126,3 +127,47 @@ where T: NumberLike {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use alloc::vec::Vec;
+
+  use crate::bit_reader::BitReader;
+  use crate::bit_words::BitWords;
+  use crate::bit_writer::BitWriter;
+  use crate::constants::MAX_PREFIX_TABLE_SIZE_LOG;
+  use crate::huffman_decoding::HuffmanTable;
+  use crate::huffman_encoding::make_huffman_code;
+  use crate::prefix::{Prefix, WeightedPrefix};
+
+  #[test]
+  fn test_nested_tables() {
+    // exponential weights give a maximally deep code, so decoding has to
+    // descend through several nested lookup tables
+    let mut weighted = (0..16_u32)
+      .map(|i| WeightedPrefix::new(1 << i, 1 << i, i, i, None, 1))
+      .collect::<Vec<_>>();
+    make_huffman_code(&mut weighted);
+    let prefixes = weighted.into_iter()
+      .map(|wp| wp.prefix)
+      .collect::<Vec<Prefix<u32>>>();
+    assert!(prefixes.iter().any(|p| p.code.len() > 2 * MAX_PREFIX_TABLE_SIZE_LOG));
+
+    let mut writer = BitWriter::default();
+    for p in prefixes.iter().rev() {
+      writer.write(&p.code);
+    }
+    let words = BitWords::from(writer.drain_bytes());
+    let table = HuffmanTable::from(&prefixes);
+    let mut checked_reader = BitReader::from(&words);
+    let mut unchecked_reader = BitReader::from(&words);
+    for p in prefixes.iter().rev() {
+      let info = table.search_with_reader(&mut checked_reader).unwrap();
+      assert_eq!(info.lower_unsigned, p.lower);
+      assert_eq!(info.depth, p.code.len());
+      let info = table.unchecked_search_with_reader(&mut unchecked_reader);
+      assert_eq!(info.lower_unsigned, p.lower);
+    }
+    assert_eq!(checked_reader.bit_idx(), unchecked_reader.bit_idx());
+  }
+}
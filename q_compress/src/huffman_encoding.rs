@@ -1,5 +1,6 @@
-use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use alloc::collections::BinaryHeap;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
 
 use crate::prefix::WeightedPrefix;
 use crate::data_types::NumberLike;
@@ -67,7 +68,10 @@ impl HuffmanItem {
 
 impl Ord for HuffmanItem {
   fn cmp(&self, other: &Self) -> Ordering {
-    other.weight.cmp(&self.weight) // flipped order to make it a min heap
+    // flipped order to make it a min heap;
+    // breaking ties by id keeps the codes independent of the heap's
+    // implementation
+    (other.weight, other.id).cmp(&(self.weight, self.id))
   }
 }
 
@@ -103,7 +107,7 @@ pub fn make_huffman_code<T: NumberLike>(prefix_sequence: &mut [WeightedPrefix<T>
 
 #[cfg(test)]
 mod tests {
-  use std::marker::PhantomData;
+  use core::marker::PhantomData;
   use crate::huffman_encoding::make_huffman_code;
   use crate::prefix::{WeightedPrefix, Prefix};
 
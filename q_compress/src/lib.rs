@@ -2,38 +2,104 @@
 //! <https://crates.io/crates/q_compress> or
 //! <https://github.com/mwlon/quantile-compression/tree/main/q_compress>.
 #![allow(clippy::needless_range_loop)]
+#![cfg_attr(not(feature = "std"), no_std)]
 #[doc = include_str!("../README.md")]
 
+pub use affine::AffineTransform;
 pub use auto::{auto_compress, auto_compressor_config, auto_decompress};
+pub use auto::{auto_compress_with_options, auto_compressor_config_with_options, AutoOptions, AutoSearch, SamplingStrategy};
+pub use auto::{train_config, CandidateReport, TrainedConfig, TrainingObjectives};
+pub use borrowed_decompressor::BorrowedDecompressor;
+pub use chunk_handle::ChunkHandle;
 pub use chunk_metadata::{ChunkMetadata, PrefixMetadata};
 pub use compressor::{Compressor, CompressorConfig};
 pub use constants::DEFAULT_COMPRESSION_LEVEL;
-pub use decompressor::{DecompressedItem, Decompressor, DecompressorConfig};
+pub use decompressor::{CorruptedSection, DecompressedItem, Decompressor, DecompressorConfig};
+pub use distinct_count::DistinctCount;
+pub use edit::{rewrite_chunk, trim_before_index, trim_before_value, trim_chunks};
+pub use estimate::{estimate_compressed_size, estimate_entropy, EntropyEstimate, SizeEstimate};
 pub use flags::Flags;
+pub use mixed::{mixed_decompress, MixedBatch, MixedCompressor};
+pub use multi_column::{ColumnInfo, MultiColumnCompressor, MultiColumnDecompressor};
+pub use multi_series::{multi_series_compress, multi_series_decompress};
+pub use nan_positions::NanPositions;
 pub use prefix::Prefix;
+pub use progress::{Progress, ProgressUpdate};
+pub use quantile::{approx_quantile, QuantileEstimate};
+pub use quantization::{Quantization, Tolerance};
+pub use report::{ChunkReport, PrefixReport};
+pub use seekable::SeekableDecompressor;
+pub use snapshot::DecompressorSnapshot;
+#[cfg(feature = "std")]
+pub use streaming::{copy_compress, WriteCompressor};
+pub use time_series::{TimeSeriesCompressor, TimeSeriesDecompressor};
+pub use tuples::{tuples_compress, tuples_decompress};
+pub use user_metadata::UserMetadata;
 
+#[cfg(not(any(feature = "std", feature = "libm")))]
+compile_error!("q_compress requires either the `std` or `libm` feature for float math");
+
+extern crate alloc;
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod compat;
 pub mod data_types;
 pub mod errors;
+#[cfg(feature = "ndarray")]
+pub mod ndarray;
+#[cfg(feature = "object_store")]
+pub mod object_store;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod test_vectors;
+pub mod verify;
 
+mod affine;
 mod auto;
 mod bit_reader;
 mod bit_words;
 mod bit_writer;
 mod bits;
+mod borrowed_decompressor;
+mod checksum;
 mod chunk_body_decompressor;
+mod chunk_handle;
 mod chunk_metadata;
 mod constants;
 mod compression_table;
 mod compressor;
 mod decompressor;
 mod delta_encoding;
+mod distinct_count;
+mod edit;
+mod estimate;
 mod flags;
+mod float_math;
+mod float_split;
 mod gcd_utils;
 mod huffman_decoding;
 mod huffman_encoding;
+mod mixed;
+mod multi_column;
+mod multi_series;
+mod nan_positions;
 mod num_decompressor;
 mod prefix;
 mod prefix_optimization;
+mod progress;
+mod quantile;
+mod quantization;
+mod report;
+mod seekable;
+mod simd;
+mod snapshot;
+#[cfg(feature = "std")]
+mod streaming;
+mod time_series;
+mod tuples;
+mod user_metadata;
+mod xor_encoding;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,332 @@
+use core::convert::TryFrom;
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use crate::{ChunkMetadata, Compressor, CompressorConfig, Flags};
+use crate::bit_reader::BitReader;
+use crate::bit_words::BitWords;
+use crate::chunk_body_decompressor::ChunkBodyDecompressor;
+use crate::chunk_metadata::MemoryLimits;
+use crate::constants::{MAGIC_CHUNK_BYTE, MAGIC_MIXED_HEADER, MAGIC_TERMINATION_BYTE};
+use crate::data_types::{DataType, NumberLike, TimestampMicros, TimestampNanos};
+#[cfg(feature = "timestamps_96")]
+use crate::data_types::{TimestampMicros96, TimestampNanos96};
+use crate::errors::{QCompressError, QCompressResult};
+
+/// Compresses chunks of different data types into a single file.
+///
+/// This suits streams like logs of counters with mixed widths, which would
+/// otherwise need one .qco file per data type.
+/// Every chunk is compressed with the same config, but may have its own
+/// data type.
+/// Read the file with [`mixed_decompress`].
+///
+/// The file consists of a 4-byte magic header for "qcx!" in ascii, the
+/// flags, each chunk, and a termination byte.
+/// Each chunk is the magic chunk byte, its data type byte, and then its
+/// metadata and body exactly as in a .qco file.
+/// Since the chunks don't share a data type, the file can't use shared
+/// prefixes, header user metadata, or a file checksum; chunk checksums
+/// work as usual.
+/// ```
+/// use q_compress::{mixed_decompress, CompressorConfig, MixedBatch, MixedCompressor};
+///
+/// let mut compressor = MixedCompressor::from_config(CompressorConfig::default())
+///   .expect("config");
+/// compressor.header().expect("header");
+/// compressor.chunk(&[1_u16, 2, 3]).expect("u16 chunk");
+/// compressor.chunk(&[-1_i64, 1 << 40]).expect("i64 chunk");
+/// compressor.footer().expect("footer");
+/// let bytes = compressor.drain_bytes();
+///
+/// let batches = mixed_decompress(&bytes).expect("decompress");
+/// assert_eq!(batches, vec![
+///   MixedBatch::U16(vec![1, 2, 3]),
+///   MixedBatch::I64(vec![-1, 1 << 40]),
+/// ]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MixedCompressor {
+  config: CompressorConfig,
+  flags: Flags,
+  bytes: Vec<u8>,
+  has_written_header: bool,
+  has_written_footer: bool,
+}
+
+impl MixedCompressor {
+  /// Creates a new mixed compressor, given a [`CompressorConfig`].
+  /// Will return an error if the config is invalid or uses a file checksum.
+  pub fn from_config(config: CompressorConfig) -> QCompressResult<Self> {
+    config.validate()?;
+    if config.use_file_checksum {
+      return Err(QCompressError::invalid_argument(
+        "mixed data type files do not support file checksums"
+      ));
+    }
+    Ok(Self {
+      flags: Flags::from(&config),
+      config,
+      bytes: Vec::new(),
+      has_written_header: false,
+      has_written_footer: false,
+    })
+  }
+
+  /// Returns a reference to the compressor's flags.
+  pub fn flags(&self) -> &Flags {
+    &self.flags
+  }
+
+  /// Writes the header.
+  /// Will return an error if the header has already been written.
+  pub fn header(&mut self) -> QCompressResult<()> {
+    if self.has_written_header {
+      return Err(QCompressError::invalid_argument(
+        "cannot write header twice"
+      ));
+    }
+    self.bytes.extend_from_slice(&MAGIC_MIXED_HEADER);
+    self.bytes.extend(self.flags.to_bytes()?);
+    self.has_written_header = true;
+    Ok(())
+  }
+
+  /// Compresses the numbers as a chunk of their own data type, returning
+  /// its metadata.
+  /// Will return an error if the header has not been written, the footer
+  /// has been written, or `T` is not one of this crate's data types.
+  pub fn chunk<T: NumberLike>(&mut self, nums: &[T]) -> QCompressResult<ChunkMetadata<T>> {
+    if !self.has_written_header {
+      return Err(QCompressError::invalid_argument(
+        "must write header before chunks"
+      ));
+    }
+    if self.has_written_footer {
+      return Err(QCompressError::invalid_argument(
+        "cannot write chunk after footer"
+      ));
+    }
+    let data_type = DataType::of::<T>()?;
+
+    // the chunk bytes are the same as in a .qco file with these flags, after
+    // its header
+    let mut compressor = Compressor::<T>::from_config(self.config.clone());
+    compressor.header()?;
+    compressor.drain_bytes();
+    let meta = compressor.chunk(nums)?;
+    let chunk_bytes = compressor.drain_bytes();
+    self.bytes.push(MAGIC_CHUNK_BYTE);
+    self.bytes.push(data_type.header_byte());
+    self.bytes.extend_from_slice(&chunk_bytes[1..]);
+    Ok(meta)
+  }
+
+  /// Writes the footer.
+  /// Will return an error if the header has not been written or the footer
+  /// has already been written.
+  pub fn footer(&mut self) -> QCompressResult<()> {
+    if !self.has_written_header {
+      return Err(QCompressError::invalid_argument(
+        "must write header before footer"
+      ));
+    }
+    if self.has_written_footer {
+      return Err(QCompressError::invalid_argument(
+        "cannot write footer twice"
+      ));
+    }
+    self.bytes.push(MAGIC_TERMINATION_BYTE);
+    self.has_written_footer = true;
+    Ok(())
+  }
+
+  /// Returns all bytes produced since the last call, freeing them.
+  pub fn drain_bytes(&mut self) -> Vec<u8> {
+    core::mem::take(&mut self.bytes)
+  }
+}
+
+macro_rules! mixed_batch {
+  {$($(#[$attr:meta])* $variant:ident => $t:ty,)+} => {
+    /// The decompressed numbers of one chunk of a file written by
+    /// [`MixedCompressor`], tagged by data type.
+    #[derive(Clone, Debug, PartialEq)]
+    #[non_exhaustive]
+    pub enum MixedBatch {
+      $($(#[$attr])* $variant(Vec<$t>),)+
+    }
+
+    impl MixedBatch {
+      /// Returns the batch's data type.
+      pub fn data_type(&self) -> DataType {
+        match self {
+          $($(#[$attr])* Self::$variant(_) => DataType::$variant,)+
+        }
+      }
+
+      /// Returns the number of numbers in the batch.
+      pub fn len(&self) -> usize {
+        match self {
+          $($(#[$attr])* Self::$variant(nums) => nums.len(),)+
+        }
+      }
+
+      /// Returns whether the batch has no numbers.
+      pub fn is_empty(&self) -> bool {
+        self.len() == 0
+      }
+    }
+
+    fn read_batch(
+      data_type: DataType,
+      reader: &mut BitReader,
+      flags: &Flags,
+    ) -> QCompressResult<MixedBatch> {
+      match data_type {
+        $($(#[$attr])* DataType::$variant => Ok(MixedBatch::$variant(read_chunk(reader, flags)?)),)+
+        #[allow(unreachable_patterns)]
+        _ => Err(QCompressError::compatibility(format!(
+          "data type {:?} is not enabled in this build of q_compress",
+          data_type,
+        ))),
+      }
+    }
+  }
+}
+
+mixed_batch! {
+  Bool => bool,
+  F32 => f32,
+  F64 => f64,
+  I16 => i16,
+  I32 => i32,
+  I64 => i64,
+  #[cfg(feature = "timestamps_96")]
+  I128 => i128,
+  TimestampMicros => TimestampMicros,
+  #[cfg(feature = "timestamps_96")]
+  TimestampMicros96 => TimestampMicros96,
+  TimestampNanos => TimestampNanos,
+  #[cfg(feature = "timestamps_96")]
+  TimestampNanos96 => TimestampNanos96,
+  U16 => u16,
+  U32 => u32,
+  U64 => u64,
+  #[cfg(feature = "timestamps_96")]
+  U128 => u128,
+}
+
+fn read_chunk<T: NumberLike>(reader: &mut BitReader, flags: &Flags) -> QCompressResult<Vec<T>> {
+  let meta = ChunkMetadata::<T>::parse_with_shared_prefixes(
+    reader,
+    flags,
+    None,
+    &mut MemoryLimits::default(),
+  )?;
+  reader.drain_empty_byte(|| QCompressError::corruption(
+    "nonzero bits in end of final byte of chunk metadata"
+  ))?;
+  let mut body_decompressor = ChunkBodyDecompressor::new(&meta, flags)?;
+  Ok(body_decompressor.decompress_next_batch(reader, usize::MAX, true)?.nums)
+}
+
+/// Decompresses a file written by [`MixedCompressor`] into one batch per
+/// chunk.
+///
+/// Will return an error if the file is corrupt or incomplete, or if it has
+/// a chunk of a data type this build of `q_compress` doesn't support.
+pub fn mixed_decompress(bytes: &[u8]) -> QCompressResult<Vec<MixedBatch>> {
+  let words = BitWords::from(bytes);
+  let mut reader = BitReader::from(&words);
+  let magic = reader.read_aligned_bytes(MAGIC_MIXED_HEADER.len())?;
+  if magic != MAGIC_MIXED_HEADER {
+    return Err(QCompressError::corruption(format!(
+      "magic mixed header does not match {:?}; instead found {:?}",
+      MAGIC_MIXED_HEADER,
+      magic,
+    )));
+  }
+  let flags = Flags::parse_from(&mut reader)?;
+  if flags.use_shared_prefixes || flags.use_file_checksum {
+    return Err(QCompressError::corruption(
+      "mixed data type files cannot use shared prefixes or a file checksum"
+    ));
+  }
+
+  let mut res = Vec::new();
+  loop {
+    let magic_byte = reader.read_aligned_bytes(1)?[0];
+    if magic_byte == MAGIC_TERMINATION_BYTE {
+      return Ok(res);
+    } else if magic_byte != MAGIC_CHUNK_BYTE {
+      return Err(QCompressError::corruption(format!(
+        "invalid magic chunk byte: {}",
+        magic_byte
+      )));
+    }
+    let data_type = DataType::try_from(reader.read_aligned_bytes(1)?[0])?;
+    res.push(read_batch(data_type, &mut reader, &flags)?);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::errors::ErrorKind;
+  use super::*;
+
+  #[test]
+  fn test_mixed_recovers() {
+    let config = CompressorConfig::default()
+      .with_delta_encoding_order(1)
+      .with_use_chunk_checksums(true)
+      .with_use_user_metadata(true);
+    let mut compressor = MixedCompressor::from_config(config).unwrap();
+    compressor.header().unwrap();
+    let counters = (0..1000_u32).map(|i| i * 3).collect::<Vec<_>>();
+    let big_counters = (0..500_u64).map(|i| (1 << 40) + i * i).collect::<Vec<_>>();
+    let timestamps = (0..300).map(|i| TimestampMicros::new(i * 1000)).collect::<Vec<_>>();
+    compressor.chunk(&counters).unwrap();
+    compressor.chunk(&big_counters).unwrap();
+    compressor.chunk(&counters[..10]).unwrap();
+    compressor.chunk(&timestamps).unwrap();
+    compressor.footer().unwrap();
+    let bytes = compressor.drain_bytes();
+
+    let batches = mixed_decompress(&bytes).unwrap();
+    assert_eq!(batches, vec![
+      MixedBatch::U32(counters.clone()),
+      MixedBatch::U64(big_counters),
+      MixedBatch::U32(counters[..10].to_vec()),
+      MixedBatch::TimestampMicros(timestamps),
+    ]);
+    assert_eq!(batches[1].data_type(), DataType::U64);
+    assert_eq!(batches[2].len(), 10);
+  }
+
+  #[test]
+  fn test_mixed_errors() {
+    let config = CompressorConfig::default().with_use_file_checksum(true);
+    assert!(MixedCompressor::from_config(config).is_err());
+
+    let mut compressor = MixedCompressor::from_config(CompressorConfig::default()).unwrap();
+    assert!(compressor.chunk(&[1_i32]).is_err());
+    compressor.header().unwrap();
+    compressor.chunk(&[1.0_f32, 2.0]).unwrap();
+    compressor.footer().unwrap();
+    assert!(compressor.chunk(&[1_i32]).is_err());
+    let bytes = compressor.drain_bytes();
+
+    let err = mixed_decompress(&bytes[..bytes.len() - 1]).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InsufficientData));
+    let mut bad_type_bytes = bytes.clone();
+    // the data type byte follows the 4-byte magic header, flags, and magic
+    // chunk byte
+    let type_byte_idx = 5 + compressor.flags().to_bytes().unwrap().len();
+    assert_eq!(bad_type_bytes[type_byte_idx], f32::HEADER_BYTE);
+    bad_type_bytes[type_byte_idx] = 200;
+    let err = mixed_decompress(&bad_type_bytes).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Compatibility));
+  }
+}
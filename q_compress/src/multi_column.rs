@@ -0,0 +1,296 @@
+use core::convert::TryFrom;
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::{auto_decompress, Compressor, CompressorConfig};
+use crate::bit_reader::BitReader;
+use crate::bit_words::BitWords;
+use crate::bit_writer::BitWriter;
+use crate::constants::{BITS_TO_ENCODE_COLUMN_BYTE_SIZE, BITS_TO_ENCODE_COLUMN_NAME_LEN, BITS_TO_ENCODE_N_COLUMNS, MAGIC_MULTI_COLUMN_HEADER, MAX_COLUMN_NAME_LEN, MAX_N_COLUMNS};
+use crate::data_types::{DataType, NumberLike};
+use crate::errors::{QCompressError, QCompressResult};
+
+/// A column's entry in the table of contents of a multi-column container.
+/// See [`MultiColumnDecompressor::columns`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnInfo {
+  /// The column's name, which is unique within its container.
+  pub name: String,
+  /// The column's data type.
+  pub data_type: DataType,
+  /// The byte size of the column's .qco file.
+  pub compressed_size: usize,
+  // byte index of the column's .qco file within the container
+  offset: usize,
+}
+
+/// Builds a container of multiple named, typed columns, each of which is a
+/// complete .qco file.
+///
+/// Columns may have different data types and configs, but are typically
+/// the same length, like the columns of a table.
+/// The container consists of a 4-byte magic header for "qcc!" in ascii,
+/// a table of contents with the number of columns and each column's name,
+/// data type byte, and byte size,
+/// and then the .qco files themselves in the order they were added.
+/// Read it with [`MultiColumnDecompressor`].
+/// ```
+/// use q_compress::{CompressorConfig, MultiColumnCompressor, MultiColumnDecompressor};
+///
+/// let mut compressor = MultiColumnCompressor::default();
+/// compressor.column("id", &[1_u32, 2, 3], CompressorConfig::default()).expect("id");
+/// compressor.column("price", &[1.5_f64, 2.5, 2.0], CompressorConfig::default()).expect("price");
+/// let bytes = compressor.finish().expect("finish");
+///
+/// let decompressor = MultiColumnDecompressor::new(&bytes).expect("table of contents");
+/// assert_eq!(decompressor.columns()[1].name, "price");
+/// assert_eq!(decompressor.column::<f64>("price").expect("price"), vec![1.5, 2.5, 2.0]);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MultiColumnCompressor {
+  columns: Vec<(String, DataType, Vec<u8>)>,
+}
+
+impl MultiColumnCompressor {
+  /// Compresses the numbers with the given config and adds them as a
+  /// column.
+  /// Will return an error if the name is already taken or too long, if
+  /// there are already too many columns, or if the config is invalid.
+  pub fn column<T: NumberLike>(
+    &mut self,
+    name: &str,
+    nums: &[T],
+    config: CompressorConfig,
+  ) -> QCompressResult<()> {
+    let data_type = DataType::of::<T>()?;
+    self.validate_new_column(name)?;
+    let bytes = Compressor::<T>::try_from_config(config)?.simple_compress(nums);
+    self.columns.push((name.to_string(), data_type, bytes));
+    Ok(())
+  }
+
+  /// Adds an already compressed .qco file as a column.
+  /// Will return an error if the name is already taken or too long, if
+  /// there are already too many columns, or if the bytes don't start with
+  /// a .qco header.
+  pub fn compressed_column(&mut self, name: &str, bytes: Vec<u8>) -> QCompressResult<()> {
+    let data_type = DataType::from_file_bytes(&bytes).map_err(|_| {
+      QCompressError::invalid_argument(format!(
+        "column {:?} is not a .qco file",
+        name,
+      ))
+    })?;
+    self.validate_new_column(name)?;
+    self.columns.push((name.to_string(), data_type, bytes));
+    Ok(())
+  }
+
+  fn validate_new_column(&self, name: &str) -> QCompressResult<()> {
+    if self.columns.len() >= MAX_N_COLUMNS {
+      return Err(QCompressError::invalid_argument(format!(
+        "number of columns may not exceed {}",
+        MAX_N_COLUMNS,
+      )));
+    }
+    if name.len() > MAX_COLUMN_NAME_LEN {
+      return Err(QCompressError::invalid_argument(format!(
+        "column names may not exceed {} bytes (was {})",
+        MAX_COLUMN_NAME_LEN,
+        name.len(),
+      )));
+    }
+    if self.columns.iter().any(|(existing, _, _)| existing == name) {
+      return Err(QCompressError::invalid_argument(format!(
+        "duplicate column name {:?}",
+        name,
+      )));
+    }
+    Ok(())
+  }
+
+  /// Returns the bytes of the container with all columns added so far.
+  pub fn finish(self) -> QCompressResult<Vec<u8>> {
+    let mut writer = BitWriter::default();
+    writer.write_aligned_bytes(&MAGIC_MULTI_COLUMN_HEADER)?;
+    writer.write_usize(self.columns.len(), BITS_TO_ENCODE_N_COLUMNS);
+    for (name, data_type, bytes) in &self.columns {
+      writer.write_usize(name.len(), BITS_TO_ENCODE_COLUMN_NAME_LEN);
+      writer.write_aligned_bytes(name.as_bytes())?;
+      writer.write_usize(data_type.header_byte() as usize, 8);
+      writer.write_usize(bytes.len(), BITS_TO_ENCODE_COLUMN_BYTE_SIZE);
+    }
+    for (_, _, bytes) in &self.columns {
+      writer.write_aligned_bytes(bytes)?;
+    }
+    Ok(writer.drain_bytes())
+  }
+}
+
+/// Reads a container written by [`MultiColumnCompressor`], decompressing
+/// only the columns asked for.
+#[derive(Clone, Debug)]
+pub struct MultiColumnDecompressor<'a> {
+  bytes: &'a [u8],
+  columns: Vec<ColumnInfo>,
+}
+
+impl<'a> MultiColumnDecompressor<'a> {
+  /// Parses the container's table of contents.
+  /// Will return an error if it is corrupt, if the container ends before
+  /// its last column does, or if a column has a data type unknown to this
+  /// version of `q_compress`.
+  pub fn new(bytes: &'a [u8]) -> QCompressResult<Self> {
+    let words = BitWords::from(bytes);
+    let mut reader = BitReader::from(&words);
+    let magic = reader.read_aligned_bytes(MAGIC_MULTI_COLUMN_HEADER.len())?;
+    if magic != MAGIC_MULTI_COLUMN_HEADER {
+      return Err(QCompressError::corruption(format!(
+        "magic multi-column header does not match {:?}; instead found {:?}",
+        MAGIC_MULTI_COLUMN_HEADER,
+        magic,
+      )));
+    }
+
+    let n_columns = reader.read_usize(BITS_TO_ENCODE_N_COLUMNS)?;
+    let mut columns = Vec::with_capacity(n_columns);
+    for _ in 0..n_columns {
+      let name_len = reader.read_usize(BITS_TO_ENCODE_COLUMN_NAME_LEN)?;
+      let name = String::from_utf8(reader.read_aligned_bytes(name_len)?).map_err(|_| {
+        QCompressError::corruption("column name is not valid UTF-8")
+      })?;
+      let data_type = DataType::try_from(reader.read_usize(8)? as u8)?;
+      let compressed_size = reader.read_usize(BITS_TO_ENCODE_COLUMN_BYTE_SIZE)?;
+      columns.push(ColumnInfo {
+        name,
+        data_type,
+        compressed_size,
+        offset: 0,
+      });
+    }
+
+    let mut offset = reader.aligned_byte_idx()?;
+    for column in &mut columns {
+      column.offset = offset;
+      offset = offset.checked_add(column.compressed_size).ok_or_else(|| {
+        QCompressError::corruption(format!(
+          "column {:?} has impossible byte size {}",
+          column.name,
+          column.compressed_size,
+        ))
+      })?;
+    }
+    if offset > bytes.len() {
+      return Err(QCompressError::insufficient_data(format!(
+        "columns end at byte {} but there are only {} bytes",
+        offset,
+        bytes.len(),
+      )));
+    }
+
+    Ok(Self { bytes, columns })
+  }
+
+  /// Returns the table of contents: each column's name, data type, and
+  /// size, in order.
+  pub fn columns(&self) -> &[ColumnInfo] {
+    &self.columns
+  }
+
+  fn column_info(&self, name: &str) -> QCompressResult<&ColumnInfo> {
+    self.columns.iter()
+      .find(|column| column.name == name)
+      .ok_or_else(|| QCompressError::invalid_argument(format!(
+        "no column named {:?}",
+        name,
+      )))
+  }
+
+  /// Returns the compressed .qco file of the named column.
+  /// Will return an error if there is no such column.
+  pub fn column_bytes(&self, name: &str) -> QCompressResult<&'a [u8]> {
+    let column = self.column_info(name)?;
+    Ok(&self.bytes[column.offset..column.offset + column.compressed_size])
+  }
+
+  /// Decompresses the named column.
+  /// Will return an error if there is no such column, if its data type
+  /// isn't `T`, or if its .qco file is corrupt.
+  pub fn column<T: NumberLike>(&self, name: &str) -> QCompressResult<Vec<T>> {
+    let column = self.column_info(name)?;
+    if column.data_type.header_byte() != T::HEADER_BYTE {
+      return Err(QCompressError::invalid_argument(format!(
+        "column {:?} has data type {:?}, not the requested one",
+        name,
+        column.data_type,
+      )));
+    }
+    auto_decompress::<T>(self.column_bytes(name)?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::auto_compress;
+  use crate::data_types::TimestampMicros;
+  use crate::errors::ErrorKind;
+  use super::*;
+
+  #[test]
+  fn test_multi_column_recovers() {
+    let timestamps = (0..1000)
+      .map(|i| TimestampMicros::new(1_600_000_000_000_000 + i * 1_000_000))
+      .collect::<Vec<_>>();
+    let temperatures = (0..1000).map(|i| 20.0 + (i % 50) as f32 * 0.1).collect::<Vec<_>>();
+    let mut compressor = MultiColumnCompressor::default();
+    compressor.column(
+      "time",
+      &timestamps,
+      CompressorConfig::default().with_delta_encoding_order(2),
+    ).unwrap();
+    compressor.compressed_column("temperature °C", auto_compress(&temperatures, 6)).unwrap();
+    compressor.column::<i64>("empty", &[], CompressorConfig::default()).unwrap();
+    let bytes = compressor.finish().unwrap();
+
+    let decompressor = MultiColumnDecompressor::new(&bytes).unwrap();
+    let names_and_types = decompressor.columns().iter()
+      .map(|column| (column.name.as_str(), column.data_type))
+      .collect::<Vec<_>>();
+    assert_eq!(names_and_types, vec![
+      ("time", DataType::TimestampMicros),
+      ("temperature °C", DataType::F32),
+      ("empty", DataType::I64),
+    ]);
+    assert_eq!(decompressor.column::<f32>("temperature °C").unwrap(), temperatures);
+    assert_eq!(decompressor.column::<TimestampMicros>("time").unwrap(), timestamps);
+    assert!(decompressor.column::<i64>("empty").unwrap().is_empty());
+    assert_eq!(
+      decompressor.column_bytes("temperature °C").unwrap().len(),
+      decompressor.columns()[1].compressed_size,
+    );
+  }
+
+  #[test]
+  fn test_multi_column_errors() {
+    let mut compressor = MultiColumnCompressor::default();
+    compressor.column("a", &[1_i32, 2], CompressorConfig::default()).unwrap();
+    let err = compressor.column("a", &[1_i32], CompressorConfig::default()).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+    let err = compressor.compressed_column("b", vec![1, 2, 3]).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+    let bytes = compressor.finish().unwrap();
+
+    let decompressor = MultiColumnDecompressor::new(&bytes).unwrap();
+    for err in [
+      decompressor.column::<i32>("b").unwrap_err(),
+      decompressor.column::<u32>("a").unwrap_err(),
+    ] {
+      assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+    }
+    let err = MultiColumnDecompressor::new(&bytes[..bytes.len() - 1]).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InsufficientData));
+    let err = MultiColumnDecompressor::new(&bytes[1..]).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Corruption));
+  }
+}
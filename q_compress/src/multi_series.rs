@@ -0,0 +1,183 @@
+use alloc::{format, vec};
+use alloc::vec::Vec;
+
+use crate::{auto_compressor_config, auto_decompress, Compressor, CompressorConfig};
+use crate::bit_reader::BitReader;
+use crate::bit_words::BitWords;
+use crate::bit_writer::BitWriter;
+use crate::constants::{BITS_TO_ENCODE_N_SERIES, BITS_TO_ENCODE_SERIES_BYTE_SIZE, MAGIC_MULTI_SERIES_HEADER, MAX_N_SERIES};
+use crate::data_types::NumberLike;
+use crate::errors::{QCompressError, QCompressResult};
+
+const TIMESTAMP_DELTA_ENCODING_ORDER: usize = 2;
+
+/// Compresses a timestamp column once along with any number of value
+/// columns that share it, returning a single self-describing container.
+///
+/// The timestamps are compressed with 2nd order delta encoding, which suits
+/// approximately periodic sampling, and each value series gets its own
+/// configuration as in [`auto_compress`][crate::auto_compress].
+/// Every series must have the same length as `timestamps`.
+/// Will return an error if there are too many series, any series has a
+/// different length, or `compression_level` is invalid.
+///
+/// The container consists of a 4-byte magic header for "qcm!" in ascii,
+/// the number of value series,
+/// the byte size of each embedded .qco file,
+/// and then the .qco files themselves (timestamps first).
+/// ```
+/// use q_compress::{multi_series_compress, multi_series_decompress};
+///
+/// let timestamps = vec![100_i64, 200, 300];
+/// let series = vec![vec![1.0_f32, 1.5, 2.0], vec![7.0, 7.0, 7.0]];
+/// let bytes = multi_series_compress(&timestamps, &series, 8).expect("compress");
+/// let (rec_timestamps, rec_series) = multi_series_decompress::<i64, f32>(&bytes)
+///   .expect("decompress");
+/// assert_eq!(rec_timestamps, timestamps);
+/// assert_eq!(rec_series, series);
+/// ```
+pub fn multi_series_compress<Ts: NumberLike, V: NumberLike>(
+  timestamps: &[Ts],
+  series: &[Vec<V>],
+  compression_level: usize,
+) -> QCompressResult<Vec<u8>> {
+  if series.len() > MAX_N_SERIES {
+    return Err(QCompressError::invalid_argument(format!(
+      "number of series may not exceed {} (was {})",
+      MAX_N_SERIES,
+      series.len(),
+    )));
+  }
+  for (i, values) in series.iter().enumerate() {
+    if values.len() != timestamps.len() {
+      return Err(QCompressError::invalid_argument(format!(
+        "series {} has {} values but there are {} timestamps",
+        i,
+        values.len(),
+        timestamps.len(),
+      )));
+    }
+  }
+
+  let mut compressed_columns = vec![
+    Compressor::<Ts>::try_from_config(timestamp_config(compression_level))?.simple_compress(timestamps)
+  ];
+  for values in series {
+    let config = auto_compressor_config(values, compression_level);
+    compressed_columns.push(Compressor::<V>::try_from_config(config)?.simple_compress(values));
+  }
+  write_container(&compressed_columns)
+}
+
+pub(crate) fn timestamp_config(compression_level: usize) -> CompressorConfig {
+  CompressorConfig::default()
+    .with_compression_level(compression_level)
+    .with_delta_encoding_order(TIMESTAMP_DELTA_ENCODING_ORDER)
+}
+
+// writes the compressed timestamps followed by the compressed value series
+pub(crate) fn write_container(compressed_columns: &[Vec<u8>]) -> QCompressResult<Vec<u8>> {
+  let series = &compressed_columns[1..];
+  let mut writer = BitWriter::default();
+  writer.write_aligned_bytes(&MAGIC_MULTI_SERIES_HEADER)?;
+  writer.write_usize(series.len(), BITS_TO_ENCODE_N_SERIES);
+  for column in compressed_columns {
+    writer.write_usize(column.len(), BITS_TO_ENCODE_SERIES_BYTE_SIZE);
+  }
+  for column in compressed_columns {
+    writer.write_aligned_bytes(column)?;
+  }
+  Ok(writer.drain_bytes())
+}
+
+/// Decompresses a container written by [`multi_series_compress`] into its
+/// timestamps and value series.
+///
+/// Will return an error if the container or any of its .qco files are
+/// corrupt, incomplete, or of the wrong data types.
+pub fn multi_series_decompress<Ts: NumberLike, V: NumberLike>(
+  bytes: &[u8],
+) -> QCompressResult<(Vec<Ts>, Vec<Vec<V>>)> {
+  let words = BitWords::from(bytes);
+  let mut reader = BitReader::from(&words);
+  let magic = reader.read_aligned_bytes(MAGIC_MULTI_SERIES_HEADER.len())?;
+  if magic != MAGIC_MULTI_SERIES_HEADER {
+    return Err(QCompressError::corruption(format!(
+      "magic multi-series header does not match {:?}; instead found {:?}",
+      MAGIC_MULTI_SERIES_HEADER,
+      magic,
+    )));
+  }
+  let n_series = reader.read_usize(BITS_TO_ENCODE_N_SERIES)?;
+  let mut column_sizes = Vec::with_capacity(n_series + 1);
+  for _ in 0..n_series + 1 {
+    column_sizes.push(reader.read_usize(BITS_TO_ENCODE_SERIES_BYTE_SIZE)?);
+  }
+
+  let timestamps = auto_decompress::<Ts>(&reader.read_aligned_bytes(column_sizes[0])?)?;
+  let mut series = Vec::with_capacity(n_series);
+  for (i, &size) in column_sizes.iter().enumerate().skip(1) {
+    let values = auto_decompress::<V>(&reader.read_aligned_bytes(size)?)?;
+    if values.len() != timestamps.len() {
+      return Err(QCompressError::corruption(format!(
+        "series {} has {} values but there are {} timestamps",
+        i - 1,
+        values.len(),
+        timestamps.len(),
+      )));
+    }
+    series.push(values);
+  }
+  Ok((timestamps, series))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::data_types::TimestampMicros;
+  use crate::errors::ErrorKind;
+  use super::{multi_series_compress, multi_series_decompress};
+
+  #[test]
+  fn test_multi_series_recovers() {
+    let timestamps = (0..1000)
+      .map(|i| TimestampMicros::new(1_600_000_000_000_000 + i * 1_000_000 + i % 3))
+      .collect::<Vec<_>>();
+    let series = vec![
+      (0..1000).map(|i| (i % 17) as i64).collect::<Vec<_>>(),
+      (0..1000).map(|i| i * i).collect::<Vec<_>>(),
+      vec![-5; 1000],
+    ];
+    let bytes = multi_series_compress(&timestamps, &series, 6).unwrap();
+    let (rec_timestamps, rec_series) = multi_series_decompress::<TimestampMicros, i64>(&bytes).unwrap();
+    assert_eq!(rec_timestamps, timestamps);
+    assert_eq!(rec_series, series);
+  }
+
+  #[test]
+  fn test_multi_series_no_series() {
+    let timestamps = vec![1_i64, 2, 3];
+    let bytes = multi_series_compress::<i64, f64>(&timestamps, &[], 6).unwrap();
+    let (rec_timestamps, rec_series) = multi_series_decompress::<i64, f64>(&bytes).unwrap();
+    assert_eq!(rec_timestamps, timestamps);
+    assert!(rec_series.is_empty());
+
+    let bytes = multi_series_compress::<i64, f32>(&[], &[vec![]], 6).unwrap();
+    let (rec_timestamps, rec_series) = multi_series_decompress::<i64, f32>(&bytes).unwrap();
+    assert!(rec_timestamps.is_empty());
+    assert_eq!(rec_series, vec![Vec::<f32>::new()]);
+  }
+
+  #[test]
+  fn test_multi_series_errors() {
+    let err = multi_series_compress(&[1_i64, 2], &[vec![1_i32]], 6).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+    let err = multi_series_compress(&[1_i64, 2], &[vec![1_i32, 2]], 13).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+
+    let bytes = multi_series_compress(&[1_i64, 2], &[vec![1_i32, 2]], 6).unwrap();
+    let err = multi_series_decompress::<i64, i32>(&bytes[..bytes.len() - 1]).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InsufficientData));
+    let err = multi_series_decompress::<i64, u32>(&bytes).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Corruption));
+  }
+}
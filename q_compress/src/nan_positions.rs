@@ -0,0 +1,360 @@
+use alloc::format;
+use alloc::vec::Vec;
+use core::cmp::min;
+use core::mem::size_of;
+use core::ops::Range;
+
+use crate::bit_reader::BitReader;
+use crate::bit_writer::BitWriter;
+use crate::chunk_metadata::MemoryLimits;
+use crate::data_types::NumberLike;
+use crate::errors::{QCompressError, QCompressResult};
+
+/// Where a chunk's NaNs were, when the compressor stripped them out of the
+/// chunk body, as stored in
+/// [`ChunkMetadata::nan_positions`][crate::ChunkMetadata::nan_positions].
+///
+/// The chunk body only holds the chunk's other numbers, and decompression
+/// puts `nan` back at each of these positions.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct NanPositions<T> {
+  /// The NaN found at every position, bit for bit.
+  pub nan: T,
+  /// The sorted, disjoint, nonempty ranges of indices within the chunk that
+  /// hold NaNs.
+  pub runs: Vec<Range<usize>>,
+}
+
+// the number of bits to encode any index or count up to n
+fn bits_to_encode_index(n: usize) -> usize {
+  (usize::BITS - n.leading_zeros()) as usize
+}
+
+impl<T: NumberLike> NanPositions<T> {
+  /// Returns the number of NaNs.
+  pub fn count(&self) -> usize {
+    self.runs.iter().map(|run| run.len()).sum()
+  }
+
+  // the number of positions in the range that aren't NaN
+  pub(crate) fn count_valid_in(&self, range: Range<usize>) -> usize {
+    let first_run_idx = self.runs.partition_point(|run| run.end <= range.start);
+    let n_nans = self.runs[first_run_idx..].iter()
+      .take_while(|run| run.start < range.end)
+      .map(|run| min(run.end, range.end) - run.start.max(range.start))
+      .sum::<usize>();
+    range.len() - n_nans
+  }
+
+  // Fills dst with the numbers starting at index `start` of the chunk,
+  // taking non-NaN numbers from `valid` in order.
+  // Returns how many numbers were filled, which is less than dst.len() only
+  // if `valid` runs out.
+  pub(crate) fn fill(&self, start: usize, valid: &[T], dst: &mut [T]) -> usize {
+    let mut run_idx = self.runs.partition_point(|run| run.end <= start);
+    let mut valid_iter = valid.iter();
+    for (i, x) in dst.iter_mut().enumerate() {
+      let idx = start + i;
+      if run_idx < self.runs.len() && idx >= self.runs[run_idx].end {
+        run_idx += 1;
+      }
+      if run_idx < self.runs.len() && self.runs[run_idx].contains(&idx) {
+        *x = self.nan;
+      } else {
+        match valid_iter.next() {
+          Some(&num) => *x = num,
+          None => return i,
+        }
+      }
+    }
+    dst.len()
+  }
+
+  // Writes the runs either as a list or as a bitmap of the chunk's n
+  // numbers, whichever is smaller.
+  pub(crate) fn write_to(&self, writer: &mut BitWriter, n: usize) {
+    self.nan.write_to(writer);
+    let index_bits = bits_to_encode_index(n);
+    let list_bits = index_bits * (1 + 2 * self.runs.len());
+    let use_bitmap = n < list_bits;
+    writer.write_one(use_bitmap);
+    if use_bitmap {
+      let mut idx = 0;
+      for run in &self.runs {
+        for _ in idx..run.start {
+          writer.write_one(false);
+        }
+        for _ in run.clone() {
+          writer.write_one(true);
+        }
+        idx = run.end;
+      }
+      for _ in idx..n {
+        writer.write_one(false);
+      }
+    } else {
+      writer.write_usize(self.runs.len(), index_bits);
+      let mut idx = 0;
+      for run in &self.runs {
+        writer.write_usize(run.start - idx, index_bits);
+        writer.write_usize(run.len(), index_bits);
+        idx = run.end;
+      }
+    }
+  }
+
+  pub(crate) fn parse_from(
+    reader: &mut BitReader,
+    n: usize,
+    limits: &mut MemoryLimits,
+  ) -> QCompressResult<Self> {
+    let nan = T::read_from(reader)?;
+    if !nan.is_nan() {
+      return Err(QCompressError::corruption(format!(
+        "NaN positions have non-NaN value {}",
+        nan,
+      )));
+    }
+
+    let index_bits = bits_to_encode_index(n);
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    if reader.read_one()? {
+      for idx in 0..n {
+        if reader.read_one()? {
+          match runs.last_mut() {
+            Some(run) if run.end == idx => run.end += 1,
+            _ => {
+              limits.reserve(size_of::<Range<usize>>(), "NaN positions")?;
+              runs.push(idx..idx + 1);
+            },
+          }
+        }
+      }
+    } else {
+      let n_runs = reader.read_usize(index_bits)?;
+      limits.reserve(n_runs * size_of::<Range<usize>>(), "NaN positions")?;
+      runs.reserve(n_runs);
+      let mut idx = 0;
+      for _ in 0..n_runs {
+        let start = idx + reader.read_usize(index_bits)?;
+        let end = start + reader.read_usize(index_bits)?;
+        // runs must be separated by numbers and end within the chunk
+        if end == start || (!runs.is_empty() && start == idx) || end > n {
+          return Err(QCompressError::corruption(format!(
+            "invalid NaN run {:?} for chunk of {} numbers",
+            start..end,
+            n,
+          )));
+        }
+        runs.push(start..end);
+        idx = end;
+      }
+    }
+
+    let res = Self { nan, runs };
+    let n_nans = res.count();
+    if n_nans == 0 || n_nans == n {
+      return Err(QCompressError::corruption(format!(
+        "NaN positions must cover some but not all of the chunk, but covered {} of {} numbers",
+        n_nans,
+        n,
+      )));
+    }
+    Ok(res)
+  }
+}
+
+// Splits the numbers into their NaNs' positions and the rest of the
+// numbers, if there are some of each and the NaNs all have the same bits.
+pub(crate) fn strip_nans<T: NumberLike>(nums: &[T]) -> Option<(NanPositions<T>, Vec<T>)> {
+  let first_nan = *nums.iter().find(|x| x.is_nan())?;
+  let mut runs: Vec<Range<usize>> = Vec::new();
+  let mut valid = Vec::with_capacity(nums.len());
+  for (idx, &x) in nums.iter().enumerate() {
+    if !x.is_nan() {
+      valid.push(x);
+      continue;
+    }
+    if !x.num_eq(&first_nan) {
+      return None;
+    }
+    match runs.last_mut() {
+      Some(run) if run.end == idx => run.end += 1,
+      _ => runs.push(idx..idx + 1),
+    }
+  }
+  if valid.is_empty() {
+    return None;
+  }
+  Some((NanPositions { nan: first_nan, runs }, valid))
+}
+
+#[cfg(test)]
+mod tests {
+  use alloc::vec;
+
+  use crate::{Compressor, CompressorConfig, DecompressedItem, Decompressor, DecompressorConfig};
+  use crate::bit_words::BitWords;
+  use super::*;
+
+  fn compress(nums: &[f64], config: CompressorConfig) -> Vec<u8> {
+    let mut compressor = Compressor::<f64>::from_config(config);
+    compressor.header().unwrap();
+    for chunk in nums.chunks(1000) {
+      compressor.chunk(chunk).unwrap();
+    }
+    compressor.footer().unwrap();
+    compressor.drain_bytes()
+  }
+
+  fn decompress(bytes: &[u8]) -> Vec<f64> {
+    // small batches split NaN runs and the chunk bodies between items
+    let mut decompressor = Decompressor::<f64>::from_config(
+      DecompressorConfig::default().with_numbers_limit_per_item(77)
+    );
+    decompressor.extend_bytes(bytes);
+    let mut res = Vec::new();
+    for item in &mut decompressor {
+      if let DecompressedItem::Numbers(nums) = item.unwrap() {
+        res.extend(nums);
+      }
+    }
+    res
+  }
+
+  fn to_bits(nums: &[f64]) -> Vec<u64> {
+    nums.iter().map(|x| x.to_bits()).collect()
+  }
+
+  // a smooth series with a sensor dropout, a chunk that is all NaN,
+  // and isolated NaNs
+  fn gappy_nums() -> Vec<f64> {
+    (0..3000)
+      .map(|i| {
+        if (300..450).contains(&i) || (1000..2000).contains(&i) || i % 97 == 13 {
+          f64::NAN
+        } else {
+          (i as f64 * 0.01).sin().round() * 100.0 + i as f64
+        }
+      })
+      .collect()
+  }
+
+  fn check_roundtrip(nums: &[f64]) {
+    let (positions, valid) = strip_nans(nums).unwrap();
+    assert!(valid.iter().all(|x| !x.is_nan()));
+    assert_eq!(positions.count(), nums.len() - valid.len());
+    assert_eq!(positions.count_valid_in(0..nums.len()), valid.len());
+
+    let mut writer = BitWriter::default();
+    positions.write_to(&mut writer, nums.len());
+    let words = BitWords::from(writer.drain_bytes());
+    let mut reader = BitReader::from(&words);
+    let parsed = NanPositions::<f64>::parse_from(&mut reader, nums.len(), &mut MemoryLimits::default()).unwrap();
+    assert_eq!(parsed.runs, positions.runs);
+
+    // fill in pieces
+    let mut dst = vec![0.0; nums.len()];
+    let mut n_valid_used = 0;
+    for start in (0..nums.len()).step_by(4) {
+      let end = min(start + 4, nums.len());
+      let n_valid = positions.count_valid_in(start..end);
+      let n_filled = positions.fill(start, &valid[n_valid_used..n_valid_used + n_valid], &mut dst[start..end]);
+      assert_eq!(n_filled, end - start);
+      n_valid_used += n_valid;
+    }
+    assert_eq!(
+      dst.iter().map(|x| x.to_bits()).collect::<Vec<_>>(),
+      nums.iter().map(|x| x.to_bits()).collect::<Vec<_>>(),
+    );
+  }
+
+  #[test]
+  fn test_strip_and_restore() {
+    let nan = f64::NAN;
+    // sparse runs, written as a list
+    let mut nums = (0..100).map(|i| i as f64).collect::<Vec<_>>();
+    nums[0] = nan;
+    nums[50] = nan;
+    nums[51] = nan;
+    nums[99] = nan;
+    check_roundtrip(&nums);
+    // dense runs, written as a bitmap
+    let nums = (0..100)
+      .map(|i| if i % 3 == 1 { nan } else { i as f64 })
+      .collect::<Vec<_>>();
+    check_roundtrip(&nums);
+  }
+
+  #[test]
+  fn test_strip_nans_requires_identical_nans() {
+    assert!(strip_nans(&[1.0, 2.0]).is_none());
+    assert!(strip_nans(&[f64::NAN, f64::NAN]).is_none());
+    assert!(strip_nans(&[f64::NAN, 1.0, -f64::NAN]).is_none());
+    let (positions, valid) = strip_nans(&[-f64::NAN, 1.0, -f64::NAN]).unwrap();
+    assert_eq!(positions.runs, vec![0..1, 2..3]);
+    assert_eq!(valid, vec![1.0]);
+  }
+
+  #[test]
+  fn test_fill_stops_when_valid_runs_out() {
+    let (positions, _) = strip_nans(&[1.0, f64::NAN, 2.0, 3.0]).unwrap();
+    let mut dst = [0.0; 4];
+    assert_eq!(positions.fill(0, &[1.0], &mut dst), 2);
+    assert_eq!(dst[0], 1.0);
+    assert!(dst[1].is_nan());
+  }
+
+  #[test]
+  fn test_compress_with_nan_positions() {
+    let nums = gappy_nums();
+    for config in [
+      CompressorConfig::default(),
+      CompressorConfig::default().with_delta_encoding_order(2),
+      CompressorConfig::default()
+        .with_use_min_max(true)
+        .with_use_distinct_count(true)
+        .with_use_chunk_checksums(true),
+      CompressorConfig::default()
+        .with_use_xor_encoding(true)
+        .with_use_raw_fallback(true),
+    ] {
+      let without = compress(&nums, config.clone());
+      let with = compress(&nums, config.clone().with_use_nan_positions(true));
+      assert_eq!(to_bits(&decompress(&with)), to_bits(&nums), "{:?}", config);
+      assert!(with.len() < without.len(), "{:?}", config);
+    }
+  }
+
+  #[test]
+  fn test_nan_positions_metadata() {
+    let mut nums = gappy_nums();
+    // the last chunk's NaNs have differing bits, so they stay in its body
+    nums[2500] = -f64::NAN;
+    let config = CompressorConfig::default()
+      .with_use_nan_positions(true)
+      .with_use_min_max(true)
+      .with_use_distinct_count(true);
+    let mut compressor = Compressor::<f64>::from_config(config);
+    compressor.header().unwrap();
+    let metas = nums.chunks(1000)
+      .map(|chunk| compressor.chunk(chunk).unwrap())
+      .collect::<Vec<_>>();
+    compressor.footer().unwrap();
+    assert_eq!(to_bits(&decompress(&compressor.drain_bytes())), to_bits(&nums));
+
+    let first_positions = metas[0].nan_positions.as_ref().unwrap();
+    assert_eq!(first_positions.count(), nums[..1000].iter().filter(|x| x.is_nan()).count());
+    assert_eq!(metas[0].n, 1000);
+    let (_, max) = metas[0].min_max.unwrap();
+    assert!(max.is_nan());
+    let distinct_count = metas[0].distinct_count.unwrap();
+    let (_, valid) = strip_nans(&nums[..1000]).unwrap();
+    assert_eq!(distinct_count.count, crate::distinct_count::distinct_count(&valid).count + 1);
+    // entirely NaN
+    assert!(metas[1].nan_positions.is_none());
+    // mixed NaN bits
+    assert!(metas[2].nan_positions.is_none());
+  }
+}
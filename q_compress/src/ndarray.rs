@@ -0,0 +1,218 @@
+//! Compression of multi-dimensional `ndarray` arrays.
+//!
+//! Gridded data, like a temperature field over latitude and longitude, is
+//! often smooth along one axis and not another.
+//! [`compress_ndarray`] lays out the numbers so that consecutive ones are
+//! neighbors along a chosen delta axis, so delta encoding takes differences
+//! along that axis.
+//! The shape and delta axis are recorded in the header [`UserMetadata`], so
+//! [`decompress_ndarray`] returns the original array.
+//! ```
+//! use ndarray::{Array2, Axis};
+//! use q_compress::CompressorConfig;
+//! use q_compress::ndarray::{compress_ndarray, decompress_ndarray};
+//!
+//! // smooth along axis 0, noisy along axis 1
+//! let array = Array2::from_shape_fn((100, 3), |(i, j)| (i * 10 + j * 777 % 13) as i64);
+//! let config = CompressorConfig::default().with_delta_encoding_order(1);
+//! let bytes = compress_ndarray(&array, Axis(0), config).expect("compress");
+//! let recovered = decompress_ndarray::<i64, _>(&bytes).expect("decompress");
+//! assert_eq!(recovered, array);
+//! ```
+
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use ndarray::{Array, ArrayBase, ArrayD, Axis, Data, Dimension, IxDyn};
+
+use crate::{Compressor, CompressorConfig, Decompressor, UserMetadata};
+use crate::data_types::NumberLike;
+use crate::errors::{QCompressError, QCompressResult};
+
+/// The header metadata key for the array's shape, stored as each axis's
+/// length in 8 little-endian bytes.
+pub const SHAPE_KEY: &str = "ndarray.shape";
+/// The header metadata key for the delta axis, stored in 8 little-endian
+/// bytes.
+pub const DELTA_AXIS_KEY: &str = "ndarray.delta_axis";
+
+const U64_BYTES: usize = 8;
+
+fn u64s_to_bytes(values: &[usize]) -> Vec<u8> {
+  values.iter()
+    .flat_map(|&value| (value as u64).to_le_bytes())
+    .collect()
+}
+
+fn bytes_to_u64s(bytes: &[u8], name: &str) -> QCompressResult<Vec<usize>> {
+  let values = bytes.chunks_exact(U64_BYTES);
+  if !values.remainder().is_empty() {
+    return Err(QCompressError::corruption(format!(
+      "invalid ndarray {} of {} bytes",
+      name,
+      bytes.len(),
+    )));
+  }
+  Ok(values
+    .map(|value_bytes| {
+      let mut le_bytes = [0; U64_BYTES];
+      le_bytes.copy_from_slice(value_bytes);
+      u64::from_le_bytes(le_bytes) as usize
+    })
+    .collect())
+}
+
+// the order of the original axes after moving the delta axis to the end
+fn delta_axis_last(ndim: usize, delta_axis: usize) -> Vec<usize> {
+  let mut res = (0..ndim)
+    .filter(|&axis| axis != delta_axis)
+    .collect::<Vec<_>>();
+  res.push(delta_axis);
+  res
+}
+
+/// Compresses the array into a .qco file, enabling
+/// [`use_user_metadata`][CompressorConfig::use_user_metadata] to record its
+/// shape and delta axis.
+///
+/// The numbers are compressed in row-major order, except with `delta_axis`
+/// moved to be the last (fastest-varying) axis.
+/// So with a nonzero
+/// [`delta_encoding_order`][CompressorConfig::delta_encoding_order], delta
+/// encoding differences each number against its neighbor along
+/// `delta_axis`.
+/// Will return an error if the config is invalid or `delta_axis` is out of
+/// bounds for the array.
+pub fn compress_ndarray<T, S, D>(
+  array: &ArrayBase<S, D>,
+  delta_axis: Axis,
+  config: CompressorConfig,
+) -> QCompressResult<Vec<u8>>
+where T: NumberLike, S: Data<Elem=T>, D: Dimension {
+  let ndim = array.ndim();
+  if delta_axis.index() >= ndim {
+    return Err(QCompressError::invalid_argument(format!(
+      "delta axis {} is out of bounds for array with {} dimensions",
+      delta_axis.index(),
+      ndim,
+    )));
+  }
+
+  let mut metadata = UserMetadata::new();
+  metadata.insert(SHAPE_KEY.to_string(), u64s_to_bytes(array.shape()));
+  metadata.insert(DELTA_AXIS_KEY.to_string(), u64s_to_bytes(&[delta_axis.index()]));
+  let nums = array.view()
+    .into_dyn()
+    .permuted_axes(delta_axis_last(ndim, delta_axis.index()))
+    .iter()
+    .copied()
+    .collect::<Vec<_>>();
+
+  let mut compressor = Compressor::<T>::try_from_config(config.with_use_user_metadata(true))?;
+  compressor.set_header_metadata(metadata)?;
+  Ok(compressor.simple_compress(&nums))
+}
+
+/// Decompresses a .qco file written by [`compress_ndarray`] into an array
+/// with the original shape.
+///
+/// `D` may be [`IxDyn`] to accept any number of dimensions.
+/// Will return an error if the file lacks the shape, the number of
+/// dimensions doesn't match `D`, or if there are any compatibility,
+/// corruption, or insufficient data issues.
+pub fn decompress_ndarray<T, D>(bytes: &[u8]) -> QCompressResult<Array<T, D>>
+where T: NumberLike, D: Dimension {
+  let mut decompressor = Decompressor::<T>::default();
+  decompressor.extend_bytes(bytes);
+  let nums = decompressor.simple_decompress()?;
+  let metadata = decompressor.header_metadata();
+
+  let shape_bytes = metadata.get(SHAPE_KEY).ok_or_else(|| QCompressError::invalid_argument(
+    "file has no ndarray shape; was it written by compress_ndarray?"
+  ))?;
+  let shape = bytes_to_u64s(shape_bytes, "shape")?;
+  let delta_axis = match metadata.get(DELTA_AXIS_KEY) {
+    Some(delta_axis_bytes) => match bytes_to_u64s(delta_axis_bytes, "delta axis")?.as_slice() {
+      &[delta_axis] if delta_axis < shape.len() => delta_axis,
+      _ => return Err(QCompressError::corruption("invalid ndarray delta axis")),
+    },
+    None => shape.len().saturating_sub(1),
+  };
+
+  let axis_order = delta_axis_last(shape.len(), delta_axis);
+  let permuted_shape = axis_order.iter()
+    .map(|&axis| shape[axis])
+    .collect::<Vec<_>>();
+  let n_nums = nums.len();
+  let permuted = ArrayD::from_shape_vec(IxDyn(&permuted_shape), nums)
+    .map_err(|_| QCompressError::corruption(format!(
+      "ndarray shape {:?} does not match the {} numbers",
+      shape,
+      n_nums,
+    )))?;
+  let mut inverse_order = alloc::vec![0; axis_order.len()];
+  for (permuted_axis, &axis) in axis_order.iter().enumerate() {
+    inverse_order[axis] = permuted_axis;
+  }
+  permuted.permuted_axes(inverse_order)
+    .as_standard_layout()
+    .into_owned()
+    .into_dimensionality::<D>()
+    .map_err(|_| QCompressError::invalid_argument(format!(
+      "array has {} dimensions, which does not match the requested dimension type",
+      shape.len(),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+  use ndarray::{Array1, Array3, Ix2};
+
+  use super::*;
+
+  #[test]
+  fn test_recovers_each_delta_axis() {
+    let array = Array3::from_shape_fn((4, 5, 6), |(i, j, k)| (i * 100 + j * 10 + k) as f32);
+    for axis in 0..3 {
+      let bytes = compress_ndarray(&array, Axis(axis), CompressorConfig::default()).unwrap();
+      let recovered = decompress_ndarray::<f32, _>(&bytes).unwrap();
+      assert_eq!(recovered, array);
+      let recovered_dyn = decompress_ndarray::<f32, IxDyn>(&bytes).unwrap();
+      assert_eq!(recovered_dyn, array.clone().into_dyn());
+    }
+
+    // non-standard layouts compress in logical order
+    let transposed = array.view().reversed_axes();
+    let bytes = compress_ndarray(&transposed, Axis(1), CompressorConfig::default()).unwrap();
+    assert_eq!(decompress_ndarray::<f32, _>(&bytes).unwrap(), transposed);
+  }
+
+  #[test]
+  fn test_delta_axis_helps() {
+    // smooth along axis 0, random-ish along axis 1
+    let array = ndarray::Array2::from_shape_fn((500, 20), |(i, j)| {
+      (i as i64) * 3 + ((j as i64 * 7919) % 1009) * 1000
+    });
+    let config = CompressorConfig::default().with_delta_encoding_order(1);
+    let along_0 = compress_ndarray(&array, Axis(0), config.clone()).unwrap();
+    let along_1 = compress_ndarray(&array, Axis(1), config).unwrap();
+    assert!(along_0.len() < along_1.len() / 2);
+  }
+
+  #[test]
+  fn test_errors() {
+    let array = Array1::from(vec![1_u32, 2, 3]);
+    assert!(compress_ndarray(&array, Axis(1), CompressorConfig::default()).is_err());
+    let invalid = CompressorConfig::default().with_delta_encoding_order(8);
+    let err = compress_ndarray(&array, Axis(0), invalid).unwrap_err();
+    assert_eq!(err.kind, crate::errors::ErrorKind::InvalidArgument);
+
+    let bytes = compress_ndarray(&array, Axis(0), CompressorConfig::default()).unwrap();
+    let err = decompress_ndarray::<u32, Ix2>(&bytes).unwrap_err();
+    assert_eq!(err.kind, crate::errors::ErrorKind::InvalidArgument);
+
+    let bytes = Compressor::<u32>::default().simple_compress(&[1, 2, 3]);
+    assert!(decompress_ndarray::<u32, IxDyn>(&bytes).is_err());
+  }
+}
@@ -1,8 +1,11 @@
-use std::cmp::{max, min};
+use alloc::format;
+use alloc::vec::Vec;
+use core::cmp::{max, min};
 
 use crate::bit_reader::BitReader;
 use crate::{bits, gcd_utils, Prefix};
-use crate::constants::{BITS_TO_ENCODE_N_ENTRIES, MAX_ENTRIES, MAX_PREFIX_TABLE_SIZE_LOG};
+use crate::checksum::Crc32;
+use crate::constants::{BITS_TO_ENCODE_N_ENTRIES, BYTES_PER_CHUNK_CHECKSUM, MAX_ENTRIES, MAX_PREFIX_TABLE_SIZE_LOG};
 use crate::data_types::{NumberLike, UnsignedLike};
 use crate::errors::{ErrorKind, QCompressError, QCompressResult};
 use crate::gcd_utils::{GcdOperator, GeneralGcdOp, TrivialGcdOp};
@@ -21,29 +24,32 @@ fn validate_prefix_tree<T: NumberLike>(prefixes: &[Prefix<T>]) -> QCompressResul
     max_depth = max(max_depth, p.code.len());
   }
 
-  let max_n_leafs = 1_usize << max_depth;
-  let mut is_specifieds = vec![false; max_n_leafs];
-  for p in prefixes {
-    let base_idx = bits::bits_to_usize_truncated(&p.code, max_depth);
-    let n_leafs = 1_usize << (max_depth - p.code.len());
-    for is_specified in is_specifieds.iter_mut().skip(base_idx).take(n_leafs) {
-      if *is_specified {
-        return Err(QCompressError::corruption(format!(
-          "multiple prefixes for {} found in chunk metadata",
-          bits::bits_to_string(&p.code),
-        )));
-      }
-      *is_specified = true;
-    }
-  }
-  for (idx, is_specified) in is_specifieds.iter().enumerate() {
-    if !is_specified {
-      let code = bits::usize_truncated_to_bits(idx, max_depth);
+  // Each prefix covers a range of leaves at max_depth. Instead of marking
+  // all 2^max_depth leaves, which untrusted metadata could make huge, we
+  // check that the ranges tile the leaves when sorted.
+  let mut leaf_ranges = prefixes.iter()
+    .map(|p| (bits::bits_to_usize_truncated(&p.code, max_depth), p.code.len(), &p.code))
+    .collect::<Vec<_>>();
+  leaf_ranges.sort_unstable_by_key(|&(base_idx, code_len, _)| (base_idx, code_len));
+  let mut next_idx = 0;
+  for (base_idx, code_len, code) in leaf_ranges {
+    if base_idx < next_idx {
       return Err(QCompressError::corruption(format!(
-        "no prefixes for {} found in chunk metadata",
-        bits::bits_to_string(&code),
+        "multiple prefixes for {} found in chunk metadata",
+        bits::bits_to_string(code),
       )));
     }
+    if base_idx > next_idx {
+      break;
+    }
+    next_idx = base_idx + (1_usize << (max_depth - code_len));
+  }
+  if next_idx < 1_usize << max_depth {
+    let code = bits::usize_truncated_to_bits(next_idx, max_depth);
+    return Err(QCompressError::corruption(format!(
+      "no prefixes for {} found in chunk metadata",
+      bits::bits_to_string(&code),
+    )));
   }
   Ok(())
 }
@@ -79,22 +85,27 @@ fn max_bits_overshot<T: NumberLike>(p: &Prefix<T>) -> usize {
   }
 }
 
-pub struct Unsigneds<U: UnsignedLike> {
-  pub unsigneds: Vec<U>,
-  pub finished_chunk_body: bool,
-}
-
 #[derive(Clone, Copy, Debug)]
 struct IncompletePrefix<U: UnsignedLike> {
   prefix: PrefixDecompressionInfo<U>,
   remaining_reps: usize,
 }
 
+// for raw chunks, where every number is an offset of the same bit width
+#[derive(Clone, Copy, Debug)]
+struct RawInfo<U: UnsignedLike> {
+  lower_unsigned: U,
+  bit_width: usize,
+}
+
 #[derive(Clone, Debug)]
 struct State<U: UnsignedLike> {
   n_processed: usize,
   bits_processed: usize,
   incomplete_prefix: Option<IncompletePrefix<U>>,
+  // running checksum of the body bytes fully processed so far, if the chunk
+  // has a checksum that hasn't been verified yet
+  checksum: Option<Crc32>,
 }
 
 // NumDecompressor does the main work of decoding bytes into NumberLikes
@@ -107,6 +118,10 @@ pub struct NumDecompressor<U> where U: UnsignedLike {
   max_bits_per_num_block: usize,
   max_overshoot_per_num_block: usize,
   use_gcd: bool,
+  raw: Option<RawInfo<U>>,
+  // false when the body is only the first part of a float split chunk body,
+  // so its checksum continues into the next part's
+  checksum_follows_body: bool,
 
   // mutable state
   state: State<U>,
@@ -122,9 +137,7 @@ fn unchecked_decompress_offsets<U: UnsignedLike, GcdOp: GcdOperator<U>>(
   if reps > 1 && p.k == 0 {
     // this branch is purely for performance reasons
     // the reps > 1 check also improves performance
-    for _ in 0..reps {
-      unsigneds.push(p.lower_unsigned);
-    }
+    unsigneds.resize(unsigneds.len() + reps, p.lower_unsigned);
   } else {
     for _ in 0..reps {
       let mut offset = reader.unchecked_read_diff(p.k);
@@ -162,6 +175,7 @@ impl<U> NumDecompressor<U> where U: UnsignedLike {
     n: usize,
     compressed_body_size: usize,
     prefixes: Vec<Prefix<T>>,
+    use_checksum: bool,
   ) -> QCompressResult<Self> {
     if prefixes.is_empty() && n > 0 {
       return Err(QCompressError::corruption(format!(
@@ -188,16 +202,73 @@ impl<U> NumDecompressor<U> where U: UnsignedLike {
       max_bits_per_num_block,
       max_overshoot_per_num_block,
       use_gcd,
+      raw: None,
+      checksum_follows_body: true,
       state: State {
         n_processed: 0,
         bits_processed: 0,
         incomplete_prefix: None,
+        checksum: use_checksum.then(Crc32::default),
       },
     })
   }
 
+  pub(crate) fn new_raw(
+    n: usize,
+    compressed_body_size: usize,
+    lower_unsigned: U,
+    bit_width: usize,
+    use_checksum: bool,
+  ) -> Self {
+    NumDecompressor {
+      huffman_table: HuffmanTable::default(),
+      n,
+      compressed_body_size,
+      max_bits_per_num_block: bit_width,
+      max_overshoot_per_num_block: 0,
+      use_gcd: false,
+      raw: Some(RawInfo {
+        lower_unsigned,
+        bit_width,
+      }),
+      checksum_follows_body: true,
+      state: State {
+        n_processed: 0,
+        bits_processed: 0,
+        incomplete_prefix: None,
+        checksum: use_checksum.then(Crc32::default),
+      },
+    }
+  }
+
+  // Makes this decompress the first part of a body split into multiple
+  // parts, so that instead of verifying a checksum after this part, the
+  // next part continues it with `continue_checksum_from`.
+  pub(crate) fn without_trailing_checksum(mut self) -> Self {
+    self.checksum_follows_body = false;
+    self
+  }
+
+  // Continues the running checksum of the previous part of the body, once
+  // it's finished.
+  pub(crate) fn continue_checksum_from(&mut self, previous: &Self) {
+    if self.state.checksum.is_some() {
+      self.state.checksum = previous.state.checksum;
+    }
+  }
+
+  // includes the checksum following the body, if it hasn't been read yet
   pub fn bits_remaining(&self) -> usize {
-    self.compressed_body_size * 8 - self.state.bits_processed
+    let checksum_bits = if self.state.checksum.is_some() && self.checksum_follows_body {
+      BYTES_PER_CHUNK_CHECKSUM * 8
+    } else {
+      0
+    };
+    self.compressed_body_size * 8 + checksum_bits - self.state.bits_processed
+  }
+
+  pub fn n_remaining(&self) -> usize {
+    self.n - self.state.n_processed
   }
 
   fn limit_reps(
@@ -265,6 +336,13 @@ impl<U> NumDecompressor<U> where U: UnsignedLike {
     p: PrefixDecompressionInfo<U>,
     reps: usize,
   ) -> QCompressResult<()> {
+    if p.k == 0 {
+      // a single-value prefix reads no offset bits, so long runs of it
+      // can't run out of data
+      unsigneds.resize(unsigneds.len() + reps, p.lower_unsigned);
+      return Ok(());
+    }
+
     for _ in 0..reps {
       let start_bit_idx = reader.bit_idx();
       let maybe_err = decompress_offset_dirty(reader, unsigneds, p);
@@ -277,6 +355,8 @@ impl<U> NumDecompressor<U> where U: UnsignedLike {
     Ok(())
   }
 
+  // Replaces the contents of `unsigneds` with the next batch of up to `limit`
+  // numbers and returns whether the chunk body is finished.
   // If hits a corruption, it returns an error and leaves reader and self unchanged.
   // State managed here: n_processed, bits_processed
   pub fn decompress_unsigneds_limited(
@@ -284,41 +364,91 @@ impl<U> NumDecompressor<U> where U: UnsignedLike {
     reader: &mut BitReader,
     limit: usize,
     error_on_insufficient_data: bool,
-  ) -> QCompressResult<Unsigneds<U>> {
+    unsigneds: &mut Vec<U>,
+  ) -> QCompressResult<bool> {
     let initial_reader = reader.clone();
     let initial_state = self.state.clone();
+    unsigneds.clear();
     let res = if self.use_gcd {
-      self.decompress_unsigneds_limited_dirty::<GeneralGcdOp>(reader, limit, error_on_insufficient_data)
+      self.decompress_unsigneds_limited_dirty::<GeneralGcdOp>(reader, limit, error_on_insufficient_data, unsigneds)
     } else {
-      self.decompress_unsigneds_limited_dirty::<TrivialGcdOp>(reader, limit, error_on_insufficient_data)
+      self.decompress_unsigneds_limited_dirty::<TrivialGcdOp>(reader, limit, error_on_insufficient_data, unsigneds)
     };
-    match &res {
-      Ok(numbers) => {
-        self.state.n_processed += numbers.unsigneds.len();
-
-        if numbers.finished_chunk_body {
-          reader.drain_empty_byte(|| QCompressError::corruption(
-            "nonzero bits in end of final byte of chunk numbers"
-          ))?;
-        }
-        self.state.bits_processed += reader.bit_idx() - initial_reader.bit_idx();
-        if numbers.finished_chunk_body {
-          let compressed_body_bit_size = self.compressed_body_size * 8;
-          if compressed_body_bit_size != self.state.bits_processed {
-            return Err(QCompressError::corruption(format!(
-              "expected the compressed body to contain {} bits but instead processed {}",
-              compressed_body_bit_size,
-              self.state.bits_processed,
-            )));
-          }
-        }
-      },
-      Err(_) => {
+    let res = res.and_then(|finished_chunk_body| self.finish_batch(
+      reader,
+      &initial_reader,
+      finished_chunk_body,
+      unsigneds.len(),
+    ));
+    match res {
+      Ok(_) => res,
+      Err(e) => {
         *reader = initial_reader;
         self.state = initial_state;
+        // we may have run out of data while reading the checksum
+        if matches!(e.kind, ErrorKind::InsufficientData) && !error_on_insufficient_data {
+          unsigneds.clear();
+          Ok(false)
+        } else {
+          Err(e)
+        }
       },
     }
-    res
+  }
+
+  fn finish_batch(
+    &mut self,
+    reader: &mut BitReader,
+    initial_reader: &BitReader,
+    finished_chunk_body: bool,
+    n_decompressed: usize,
+  ) -> QCompressResult<bool> {
+    self.state.n_processed += n_decompressed;
+
+    if finished_chunk_body {
+      reader.drain_empty_byte(|| QCompressError::corruption(
+        "nonzero bits in end of final byte of chunk numbers"
+      ))?;
+    }
+    let initial_bits_processed = self.state.bits_processed;
+    self.state.bits_processed += reader.bit_idx() - initial_reader.bit_idx();
+    if finished_chunk_body {
+      let compressed_body_bit_size = self.compressed_body_size * 8;
+      if compressed_body_bit_size != self.state.bits_processed {
+        return Err(QCompressError::corruption(format!(
+          "expected the compressed body to contain {} bits but instead processed {}",
+          compressed_body_bit_size,
+          self.state.bits_processed,
+        )));
+      }
+    }
+
+    let checksum_follows_body = self.checksum_follows_body;
+    if let Some(checksum) = &mut self.state.checksum {
+      // hash all body bytes we've fully processed but not yet hashed
+      let hashed_byte_idx = initial_bits_processed / 8;
+      let mut checksum_reader = initial_reader.clone();
+      checksum_reader.rewind(initial_bits_processed % 8);
+      let new_bytes = checksum_reader.read_aligned_bytes(
+        self.state.bits_processed / 8 - hashed_byte_idx
+      )?;
+      checksum.update(&new_bytes);
+
+      if finished_chunk_body && checksum_follows_body {
+        let bytes = reader.read_aligned_bytes(BYTES_PER_CHUNK_CHECKSUM)?;
+        let expected = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let actual = checksum.finish();
+        if expected != actual {
+          return Err(QCompressError::corruption(format!(
+            "chunk body checksum {:#010x} does not match expected {:#010x}",
+            actual,
+            expected,
+          )));
+        }
+        self.state.checksum = None;
+      }
+    }
+    Ok(finished_chunk_body)
   }
 
   // After much debugging a performance degradation from error handling changes,
@@ -334,32 +464,45 @@ impl<U> NumDecompressor<U> where U: UnsignedLike {
     reader: &mut BitReader,
     limit: usize,
     error_on_insufficient_data: bool,
-  ) -> QCompressResult<Unsigneds<U>> {
+    unsigneds: &mut Vec<U>,
+  ) -> QCompressResult<bool> {
     let batch_size = min(
       self.n - self.state.n_processed,
       limit,
     );
-    // we'll modify this result as we decode numbers and if we encounter an insufficient data error
+    // we'll return this unless we encounter an insufficient data error
     let completed_body = limit >= self.n - self.state.n_processed;
-    let mut numbers = Unsigneds {
-      unsigneds: Vec::with_capacity(batch_size),
-      finished_chunk_body: completed_body,
-    };
-    let unsigneds = &mut numbers.unsigneds;
+    unsigneds.reserve(batch_size);
 
     if batch_size == 0 {
-      return Ok(numbers);
+      return Ok(completed_body);
     }
 
-    let mark_insufficient = |mut numbers: Unsigneds<U>, e: QCompressError| {
+    let mark_insufficient = |e: QCompressError| {
       if error_on_insufficient_data {
         Err(e)
       } else {
-        numbers.finished_chunk_body = false;
-        Ok(numbers)
+        Ok(false)
       }
     };
 
+    if let Some(RawInfo { lower_unsigned, bit_width }) = self.raw {
+      // every number has the same width, so we can check for sufficient
+      // data once for the whole batch
+      let n_safe = reader.bits_remaining()
+        .checked_div(bit_width)
+        .map_or(batch_size, |n_available| min(batch_size, n_available));
+      for _ in 0..n_safe {
+        unsigneds.push(lower_unsigned + reader.unchecked_read_diff::<U>(bit_width));
+      }
+      if n_safe < batch_size {
+        return mark_insufficient(QCompressError::insufficient_data(
+          "decompress_unsigneds_limited_dirty(): ran out of data reading raw numbers"
+        ));
+      }
+      return Ok(completed_body);
+    }
+
     if let Some(IncompletePrefix {
       prefix,
       remaining_reps
@@ -380,18 +523,17 @@ impl<U> NumDecompressor<U> where U: UnsignedLike {
       match incomplete_res {
         Ok(_) => (),
         Err(e) if matches!(e.kind, ErrorKind::InsufficientData) =>
-          return mark_insufficient(numbers, e),
+          return mark_insufficient(e),
         Err(e) => return Err(e),
       };
     }
 
     if self.max_bits_per_num_block == 0 {
+      // the chunk is constant, so its body is empty and we can fill the
+      // batch in one go
       let mut temp = Vec::with_capacity(1);
       self.unchecked_decompress_num_block::<GcdOp>(reader, &mut temp, 1);
-      let constant_num = temp[0];
-      while unsigneds.len() < batch_size {
-        unsigneds.push(constant_num);
-      }
+      unsigneds.resize(batch_size, temp[0]);
     } else {
       loop {
         let remaining_unsigneds = batch_size - unsigneds.len();
@@ -403,7 +545,7 @@ impl<U> NumDecompressor<U> where U: UnsignedLike {
 
         if guaranteed_safe_num_blocks >= UNCHECKED_NUM_THRESHOLD {
           let mut block_idx = 0;
-          while block_idx < guaranteed_safe_num_blocks && unsigneds.len() < self.n {
+          while block_idx < guaranteed_safe_num_blocks && unsigneds.len() < batch_size {
             self.unchecked_decompress_num_block::<GcdOp>(reader, unsigneds, batch_size);
             block_idx += 1;
           }
@@ -416,12 +558,12 @@ impl<U> NumDecompressor<U> where U: UnsignedLike {
         match self.decompress_num_block(reader, unsigneds, batch_size) {
           Ok(_) => (),
           Err(e) if matches!(e.kind, ErrorKind::InsufficientData) =>
-            return mark_insufficient(numbers, e),
+            return mark_insufficient(e),
           Err(e) => return Err(e),
         }
       }
     }
 
-    Ok(numbers)
+    Ok(completed_body)
   }
 }
@@ -0,0 +1,427 @@
+//! Reading parts of .qco files from object storage with ranged GETs.
+//!
+//! [`RangeReader::open`] fetches only the header and each chunk's metadata,
+//! skipping over chunk bodies.
+//! Reading an index range or a subset of chunks then fetches only the
+//! bodies of those chunks, so a large chunked file on S3, GCS, Azure, or
+//! HTTP can be queried without downloading it.
+//! ```
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! use std::sync::Arc;
+//! use object_store::{ObjectStore, memory::InMemory, path::Path};
+//! use q_compress::Compressor;
+//! use q_compress::object_store::{RangeReader, RangeReaderConfig};
+//!
+//! let mut compressor = Compressor::<i64>::default();
+//! compressor.header().unwrap();
+//! for chunk in (0..10_000).collect::<Vec<i64>>().chunks(1000) {
+//!   compressor.chunk(chunk).unwrap();
+//! }
+//! compressor.footer().unwrap();
+//!
+//! let store = Arc::new(InMemory::new());
+//! let location = Path::from("nums.qco");
+//! store.put(&location, compressor.drain_bytes().into()).await.unwrap();
+//!
+//! let reader = RangeReader::<i64>::open(store, location, RangeReaderConfig::default())
+//!   .await
+//!   .expect("open");
+//! // only fetches the body of the 6th chunk
+//! let nums = reader.read_range(5500..5503).await.expect("read");
+//! assert_eq!(nums, vec![5500, 5501, 5502]);
+//! # });
+//! ```
+
+use std::io;
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::sync::Arc;
+
+use ::object_store::{ObjectStore, path::Path};
+
+use crate::{ChunkMetadata, Decompressor, Flags, PrefixMetadata, UserMetadata};
+use crate::data_types::NumberLike;
+use crate::errors::{ErrorKind, QCompressError};
+
+fn to_io_error(e: QCompressError) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// All configurations available for a [`RangeReader`].
+#[derive(Clone, Debug)]
+pub struct RangeReaderConfig {
+  /// The number of bytes to fetch at a time when looking for the header or
+  /// a chunk's metadata (default 64KiB).
+  ///
+  /// When the metadata turns out to be longer, the fetch is retried with
+  /// twice as many bytes.
+  /// If the fetched bytes happen to contain the next chunk's metadata too,
+  /// no further fetch is needed for it, so larger values mean fewer GETs
+  /// for files with small chunks.
+  pub metadata_fetch_size: usize,
+  phantom: PhantomData<()>, // for API stability
+}
+
+impl Default for RangeReaderConfig {
+  fn default() -> Self {
+    Self {
+      metadata_fetch_size: 1 << 16,
+      phantom: PhantomData,
+    }
+  }
+}
+
+impl RangeReaderConfig {
+  /// Sets [`metadata_fetch_size`][RangeReaderConfig::metadata_fetch_size].
+  pub fn with_metadata_fetch_size(mut self, metadata_fetch_size: usize) -> Self {
+    self.metadata_fetch_size = metadata_fetch_size;
+    self
+  }
+}
+
+/// The metadata and location of a chunk in a .qco file in object storage.
+#[derive(Clone, Debug)]
+pub struct RemoteChunk<T: NumberLike> {
+  /// The chunk's metadata.
+  pub metadata: ChunkMetadata<T>,
+  /// The index of the chunk's first number within the file.
+  pub start_idx: usize,
+  /// The byte range of the chunk body (including its checksum, if any)
+  /// within the file.
+  pub body_byte_range: Range<usize>,
+  // the chunk metadata, including the magic chunk byte
+  meta_bytes: Vec<u8>,
+}
+
+impl<T: NumberLike> RemoteChunk<T> {
+  /// Returns the range of indices of the chunk's numbers within the file.
+  pub fn idx_range(&self) -> Range<usize> {
+    self.start_idx..self.start_idx + self.metadata.n
+  }
+
+  /// Returns the smallest and largest values the chunk could contain
+  /// according to its prefixes, if they can be known without decompressing
+  /// it.
+  ///
+  /// This is only possible for chunks without delta, XOR, or raw encoding,
+  /// a decimal exponent, quantization, or an affine transform.
+  /// Bounds are compared by the numbers' unsigned representations, so
+  /// for floats, NaNs may fall outside them.
+  pub fn value_bounds(&self) -> Option<(T, T)> {
+    if self.metadata.decimal_exponent.is_some() ||
+      self.metadata.quantization.is_some() ||
+      self.metadata.affine_transform.is_some() {
+      return None;
+    }
+    match &self.metadata.prefix_metadata {
+      PrefixMetadata::Simple { prefixes } => {
+        let lower = prefixes.iter().map(|p| p.lower).min_by_key(|x| x.to_unsigned())?;
+        let upper = prefixes.iter().map(|p| p.upper).max_by_key(|x| x.to_unsigned())?;
+        Some((lower, upper))
+      }
+      _ => None,
+    }
+  }
+}
+
+// a window of the file's bytes, refetched as needed
+struct Fetcher<'a> {
+  store: &'a dyn ObjectStore,
+  location: &'a Path,
+  size: usize,
+  start: usize,
+  bytes: Vec<u8>,
+}
+
+impl<'a> Fetcher<'a> {
+  // returns the bytes from `start` onward, at least `min_len` of them
+  // unless the file ends sooner
+  async fn bytes_from(&mut self, start: usize, min_len: usize) -> io::Result<&[u8]> {
+    let end = (start + min_len).min(self.size);
+    let window_end = self.start + self.bytes.len();
+    if start < self.start || end > window_end {
+      let range = start..end.max(start);
+      self.bytes = self.store.get_range(self.location, range).await?.to_vec();
+      self.start = start;
+    }
+    Ok(&self.bytes[start - self.start..])
+  }
+}
+
+/// Reads .qco files in object storage, fetching only the chunk bodies that
+/// are needed.
+///
+/// Opening the reader fetches the header and all chunk metadata; reading
+/// numbers fetches the relevant chunk bodies with ranged GETs.
+#[derive(Clone, Debug)]
+pub struct RangeReader<T: NumberLike> {
+  store: Arc<dyn ObjectStore>,
+  location: Path,
+  // a decompressor that has read exactly the header
+  header_decompressor: Decompressor<T>,
+  flags: Flags,
+  chunks: Vec<RemoteChunk<T>>,
+}
+
+impl<T: NumberLike> RangeReader<T> {
+  /// Fetches the header and chunk metadata of the .qco file at `location`.
+  /// Will return an error if fetching fails, or if there are any
+  /// compatibility, corruption, or insufficient data issues in the
+  /// header or chunk metadata.
+  pub async fn open(
+    store: Arc<dyn ObjectStore>,
+    location: Path,
+    config: RangeReaderConfig,
+  ) -> io::Result<Self> {
+    let size = store.head(&location).await?.size;
+    let fetch_size = config.metadata_fetch_size.max(1);
+    let mut fetcher = Fetcher {
+      store: store.as_ref(),
+      location: &location,
+      size,
+      start: 0,
+      bytes: Vec::new(),
+    };
+
+    let mut min_len = fetch_size;
+    let (header_decompressor, flags) = loop {
+      let bytes = fetcher.bytes_from(0, min_len).await?;
+      let mut decompressor = Decompressor::<T>::default();
+      decompressor.extend_bytes(bytes);
+      match decompressor.header() {
+        Ok(flags) => {
+          let header_size = decompressor.bit_idx() / 8;
+          let mut header_decompressor = Decompressor::<T>::default();
+          header_decompressor.extend_bytes(&bytes[..header_size]);
+          header_decompressor.header().map_err(to_io_error)?;
+          break (header_decompressor, flags);
+        }
+        Err(e) if e.kind == ErrorKind::InsufficientData && bytes.len() < size => {
+          min_len = bytes.len() * 2;
+        }
+        Err(e) => return Err(to_io_error(e)),
+      }
+    };
+
+    let mut chunks = Vec::new();
+    let header_size = header_decompressor.bit_idx() / 8;
+    let mut offset = header_size;
+    let mut start_idx = 0;
+    loop {
+      let mut min_len = fetch_size;
+      let (metadata, meta_size, body_size) = loop {
+        let bytes = fetcher.bytes_from(offset, min_len).await?;
+        let mut decompressor = header_decompressor.clone();
+        decompressor.extend_bytes(bytes);
+        match decompressor.chunk_metadata() {
+          Ok(maybe_meta) => {
+            let meta_size = decompressor.bit_idx() / 8 - header_size;
+            let body_size = decompressor.chunk_body_bits_remaining().unwrap_or(0) / 8;
+            break (maybe_meta, meta_size, body_size);
+          }
+          Err(e) if e.kind == ErrorKind::InsufficientData && offset + bytes.len() < size => {
+            min_len = bytes.len() * 2;
+          }
+          Err(e) => return Err(to_io_error(e)),
+        }
+      };
+
+      let metadata = match metadata {
+        Some(metadata) => metadata,
+        None => break,
+      };
+      let meta_bytes = fetcher.bytes_from(offset, meta_size).await?[..meta_size].to_vec();
+      let body_start = offset + meta_size;
+      let n = metadata.n;
+      chunks.push(RemoteChunk {
+        metadata,
+        start_idx,
+        body_byte_range: body_start..body_start + body_size,
+        meta_bytes,
+      });
+      start_idx += n;
+      offset = body_start + body_size;
+    }
+
+    Ok(Self {
+      store,
+      location,
+      header_decompressor,
+      flags,
+      chunks,
+    })
+  }
+
+  /// Returns the file's [`Flags`].
+  pub fn flags(&self) -> &Flags {
+    &self.flags
+  }
+
+  /// Returns the [`UserMetadata`] from the file's header.
+  pub fn header_metadata(&self) -> &UserMetadata {
+    self.header_decompressor.header_metadata()
+  }
+
+  /// Returns the file's chunks, in order.
+  pub fn chunks(&self) -> &[RemoteChunk<T>] {
+    &self.chunks
+  }
+
+  /// Returns the total count of numbers in the file.
+  pub fn n(&self) -> usize {
+    self.chunks.iter().map(|chunk| chunk.metadata.n).sum()
+  }
+
+  // fetches the given chunk bodies, coalescing nearby ranges, and
+  // decompresses them in order
+  async fn read_chunk_idxs(&self, chunk_idxs: &[usize]) -> io::Result<Vec<Vec<T>>> {
+    let ranges = chunk_idxs.iter()
+      .map(|&idx| self.chunks[idx].body_byte_range.clone())
+      .collect::<Vec<_>>();
+    let bodies = self.store.get_ranges(&self.location, &ranges).await?;
+    chunk_idxs.iter()
+      .zip(bodies)
+      .map(|(&idx, body)| {
+        let chunk = &self.chunks[idx];
+        let mut decompressor = self.header_decompressor.clone();
+        decompressor.extend_bytes(&chunk.meta_bytes);
+        decompressor.extend_bytes(&body);
+        decompressor.chunk_metadata().map_err(to_io_error)?;
+        decompressor.chunk_body().map_err(to_io_error)
+      })
+      .collect()
+  }
+
+  /// Fetches and decompresses the chunk at `chunk_idx`.
+  /// Will return an error if the index is out of bounds, fetching fails, or
+  /// the chunk body is corrupt.
+  pub async fn read_chunk(&self, chunk_idx: usize) -> io::Result<Vec<T>> {
+    if chunk_idx >= self.chunks.len() {
+      return Err(to_io_error(QCompressError::invalid_argument(format!(
+        "chunk index {} is out of bounds for file with {} chunks",
+        chunk_idx,
+        self.chunks.len(),
+      ))));
+    }
+    Ok(self.read_chunk_idxs(&[chunk_idx]).await?.pop().unwrap())
+  }
+
+  /// Returns the numbers in `idx_range` of the file, fetching only the
+  /// chunks that overlap it.
+  /// Will return an error if the range extends past the end of the file,
+  /// fetching fails, or a chunk body is corrupt.
+  pub async fn read_range(&self, idx_range: Range<usize>) -> io::Result<Vec<T>> {
+    let n = self.n();
+    if idx_range.start > idx_range.end || idx_range.end > n {
+      return Err(to_io_error(QCompressError::invalid_argument(format!(
+        "index range {:?} is invalid for file with {} numbers",
+        idx_range,
+        n,
+      ))));
+    }
+
+    let chunk_idxs = (0..self.chunks.len())
+      .filter(|&idx| {
+        let chunk_range = self.chunks[idx].idx_range();
+        chunk_range.start < idx_range.end && idx_range.start < chunk_range.end
+      })
+      .collect::<Vec<_>>();
+    let bodies = self.read_chunk_idxs(&chunk_idxs).await?;
+    let mut res = Vec::with_capacity(idx_range.len());
+    for (&idx, nums) in chunk_idxs.iter().zip(bodies) {
+      let start_idx = self.chunks[idx].start_idx;
+      let lower = idx_range.start.saturating_sub(start_idx);
+      let upper = (idx_range.end - start_idx).min(nums.len());
+      res.extend_from_slice(&nums[lower..upper]);
+    }
+    Ok(res)
+  }
+
+  /// Returns the numbers of every chunk for which `predicate` is true,
+  /// fetching only those chunks.
+  ///
+  /// This is how to read a time or value range: for instance, the
+  /// predicate can check [`RemoteChunk::value_bounds`], or bounds the
+  /// writer stored in each chunk's
+  /// [`user_metadata`][ChunkMetadata::user_metadata].
+  /// Will return an error if fetching fails or a chunk body is corrupt.
+  pub async fn read_chunks_where<F>(&self, mut predicate: F) -> io::Result<Vec<T>>
+  where F: FnMut(&RemoteChunk<T>) -> bool {
+    let chunk_idxs = (0..self.chunks.len())
+      .filter(|&idx| predicate(&self.chunks[idx]))
+      .collect::<Vec<_>>();
+    Ok(self.read_chunk_idxs(&chunk_idxs).await?.concat())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ::object_store::memory::InMemory;
+
+  use crate::{Compressor, CompressorConfig};
+
+  use super::*;
+
+  async fn write_chunks(
+    store: &InMemory,
+    config: CompressorConfig,
+    chunks: &[Vec<i64>],
+  ) -> Path {
+    let mut compressor = Compressor::<i64>::from_config(config);
+    compressor.header().unwrap();
+    for chunk in chunks {
+      compressor.chunk(chunk).unwrap();
+    }
+    compressor.footer().unwrap();
+    let location = Path::from("test.qco");
+    store.put(&location, compressor.drain_bytes().into()).await.unwrap();
+    location
+  }
+
+  #[tokio::test]
+  async fn test_read_range() {
+    let chunks = (0..5)
+      .map(|i| (0..1000).map(|j| i * 1000 + (j * 7919) % 1000).collect::<Vec<i64>>())
+      .collect::<Vec<_>>();
+    let all = chunks.concat();
+    let store = Arc::new(InMemory::new());
+    let config = CompressorConfig::default().with_use_chunk_checksums(true);
+    let location = write_chunks(&store, config, &chunks).await;
+
+    // a tiny fetch size forces metadata fetches to be retried
+    for fetch_size in [1, 100, 1 << 20] {
+      let reader_config = RangeReaderConfig::default().with_metadata_fetch_size(fetch_size);
+      let reader = RangeReader::<i64>::open(store.clone(), location.clone(), reader_config)
+        .await
+        .unwrap();
+      assert_eq!(reader.chunks().len(), 5);
+      assert_eq!(reader.n(), 5000);
+      assert_eq!(reader.read_range(0..5000).await.unwrap(), all);
+      assert_eq!(reader.read_range(1500..3001).await.unwrap(), all[1500..3001]);
+      assert_eq!(reader.read_range(7..7).await.unwrap(), Vec::<i64>::new());
+      assert_eq!(reader.read_chunk(4).await.unwrap(), chunks[4]);
+      assert!(reader.read_range(4000..5001).await.is_err());
+      assert!(reader.read_chunk(5).await.is_err());
+    }
+  }
+
+  #[tokio::test]
+  async fn test_read_chunks_where() {
+    let chunks = (0..4)
+      .map(|i| (0..100).map(|j| i * 100 + j).collect::<Vec<i64>>())
+      .collect::<Vec<_>>();
+    let store = Arc::new(InMemory::new());
+    let location = write_chunks(&store, CompressorConfig::default(), &chunks).await;
+    let reader = RangeReader::<i64>::open(store, location, RangeReaderConfig::default())
+      .await
+      .unwrap();
+
+    let bounds = reader.chunks()[2].value_bounds().unwrap();
+    assert!(bounds.0 <= 200 && bounds.1 >= 299);
+    let nums = reader.read_chunks_where(|chunk| {
+      chunk.value_bounds().map_or(true, |(lower, upper)| lower <= 250 && upper >= 250)
+    }).await.unwrap();
+    assert!(nums.contains(&250));
+    assert!(nums.len() < 400);
+  }
+}
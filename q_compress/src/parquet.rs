@@ -0,0 +1,168 @@
+//! Helpers for using q_compress on Parquet page payloads.
+//!
+//! Parquet readers only support a fixed set of compression codecs, so
+//! q_compress can't be registered as one.
+//! Instead, these functions convert between the PLAIN-encoded values of a
+//! Parquet data page (as written by parquet-rs with PLAIN encoding and no
+//! compression) and a .qco file, much like byte stream split is an
+//! alternative encoding of the same values.
+//! A pipeline can swap each page's value bytes for their .qco equivalent
+//! on write and swap them back before handing pages to a Parquet reader.
+//!
+//! `INT32`, `INT64`, `FLOAT`, and `DOUBLE` physical types are supported.
+//! ```
+//! use parquet::basic::Type;
+//! use q_compress::CompressorConfig;
+//! use q_compress::parquet::{compress_plain_page, decompress_plain_page};
+//!
+//! let plain = [1_i64, 2, 3].iter().flat_map(|x| x.to_le_bytes()).collect::<Vec<_>>();
+//! let bytes = compress_plain_page(Type::INT64, &plain, CompressorConfig::default())
+//!   .expect("compress");
+//! let recovered = decompress_plain_page(Type::INT64, &bytes).expect("decompress");
+//! assert_eq!(recovered, plain);
+//! ```
+
+use alloc::format;
+use alloc::vec::Vec;
+use core::mem;
+
+use ::parquet::basic::Type as PhysicalType;
+
+use crate::{Compressor, CompressorConfig, Decompressor};
+use crate::data_types::NumberLike;
+use crate::errors::{QCompressError, QCompressResult};
+
+// a number type with a Parquet PLAIN encoding of fixed-width little-endian
+// bytes
+trait PlainNumber: NumberLike {
+  fn from_plain(bytes: &[u8]) -> Self;
+
+  fn extend_plain(self, dst: &mut Vec<u8>);
+}
+
+macro_rules! impl_plain_number {
+  ($t: ty) => {
+    impl PlainNumber for $t {
+      fn from_plain(bytes: &[u8]) -> Self {
+        let mut le_bytes = [0; mem::size_of::<$t>()];
+        le_bytes.copy_from_slice(bytes);
+        <$t>::from_le_bytes(le_bytes)
+      }
+
+      fn extend_plain(self, dst: &mut Vec<u8>) {
+        dst.extend_from_slice(&self.to_le_bytes());
+      }
+    }
+  }
+}
+
+impl_plain_number!(i32);
+impl_plain_number!(i64);
+impl_plain_number!(f32);
+impl_plain_number!(f64);
+
+fn compress_plain<T: PlainNumber>(
+  plain: &[u8],
+  config: CompressorConfig,
+) -> QCompressResult<Vec<u8>> {
+  let size = mem::size_of::<T>();
+  let values = plain.chunks_exact(size);
+  if !values.remainder().is_empty() {
+    return Err(QCompressError::invalid_argument(format!(
+      "PLAIN page of {} bytes is not a whole number of {}-byte values",
+      plain.len(),
+      size,
+    )));
+  }
+
+  let mut compressor = Compressor::<T>::try_from_config(config)?;
+  let nums = values
+    .map(T::from_plain)
+    .collect::<Vec<_>>();
+  Ok(compressor.simple_compress(&nums))
+}
+
+fn decompress_plain<T: PlainNumber>(bytes: &[u8]) -> QCompressResult<Vec<u8>> {
+  let mut decompressor = Decompressor::<T>::default();
+  decompressor.extend_bytes(bytes);
+  let nums = decompressor.simple_decompress()?;
+  let mut res = Vec::with_capacity(nums.len() * mem::size_of::<T>());
+  for num in nums {
+    num.extend_plain(&mut res);
+  }
+  Ok(res)
+}
+
+fn unsupported_type_error(physical_type: PhysicalType) -> QCompressError {
+  QCompressError::invalid_argument(format!(
+    "unsupported parquet physical type {}",
+    physical_type,
+  ))
+}
+
+/// Compresses the PLAIN-encoded values of a Parquet page with the given
+/// physical type into a .qco file.
+/// Will return an error if the config is invalid, the physical type is
+/// unsupported, or `plain` is not a whole number of values.
+pub fn compress_plain_page(
+  physical_type: PhysicalType,
+  plain: &[u8],
+  config: CompressorConfig,
+) -> QCompressResult<Vec<u8>> {
+  match physical_type {
+    PhysicalType::INT32 => compress_plain::<i32>(plain, config),
+    PhysicalType::INT64 => compress_plain::<i64>(plain, config),
+    PhysicalType::FLOAT => compress_plain::<f32>(plain, config),
+    PhysicalType::DOUBLE => compress_plain::<f64>(plain, config),
+    other => Err(unsupported_type_error(other)),
+  }
+}
+
+/// Decompresses a .qco file written by [`compress_plain_page`] back into
+/// PLAIN-encoded values of the given physical type.
+/// Will return an error if the physical type is unsupported or does not
+/// match the file's data type, or if there are any compatibility,
+/// corruption, or insufficient data issues.
+pub fn decompress_plain_page(
+  physical_type: PhysicalType,
+  bytes: &[u8],
+) -> QCompressResult<Vec<u8>> {
+  match physical_type {
+    PhysicalType::INT32 => decompress_plain::<i32>(bytes),
+    PhysicalType::INT64 => decompress_plain::<i64>(bytes),
+    PhysicalType::FLOAT => decompress_plain::<f32>(bytes),
+    PhysicalType::DOUBLE => decompress_plain::<f64>(bytes),
+    other => Err(unsupported_type_error(other)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::errors::ErrorKind;
+
+  #[test]
+  fn test_recovers() {
+    let plain = (0..1000)
+      .flat_map(|i| (i as f32 / 7.0).to_le_bytes())
+      .collect::<Vec<_>>();
+    let bytes = compress_plain_page(PhysicalType::FLOAT, &plain, CompressorConfig::default()).unwrap();
+    assert!(bytes.len() < plain.len());
+    assert_eq!(decompress_plain_page(PhysicalType::FLOAT, &bytes).unwrap(), plain);
+  }
+
+  #[test]
+  fn test_errors() {
+    let config = CompressorConfig::default();
+    let err = compress_plain_page(PhysicalType::INT32, &[1, 2, 3], config.clone()).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::InvalidArgument);
+    let err = compress_plain_page(PhysicalType::BYTE_ARRAY, &[], config.clone()).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::InvalidArgument);
+    let invalid = config.clone().with_delta_encoding_order(8);
+    let err = compress_plain_page(PhysicalType::INT32, &[1, 0, 0, 0], invalid).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::InvalidArgument);
+
+    let bytes = compress_plain_page(PhysicalType::INT32, &[1, 0, 0, 0], config).unwrap();
+    assert!(decompress_plain_page(PhysicalType::INT64, &bytes).is_err());
+  }
+}
@@ -1,7 +1,11 @@
-use std::fmt::{Display, Formatter};
-use std::marker::PhantomData;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::marker::PhantomData;
 use crate::bits;
 use crate::data_types::{NumberLike, UnsignedLike};
+use crate::float_math;
 
 /// A pairing of a Huffman code with a numerical range.
 ///
@@ -14,6 +18,11 @@ use crate::data_types::{NumberLike, UnsignedLike};
 /// it, then writes out its Huffman code, optionally the number of
 /// consecutive repetitions of that number if `run_length_jumpstart` is
 /// available, and then the exact offset within the range for the number.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(
+  serialize = "T: serde::Serialize, T::Unsigned: serde::Serialize",
+  deserialize = "T: serde::Deserialize<'de>, T::Unsigned: serde::Deserialize<'de>",
+)))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Prefix<T> where T: NumberLike {
   /// The count of numbers in the chunk that fall into this Prefix's range.
@@ -41,7 +50,7 @@ pub struct Prefix<T> where T: NumberLike {
 }
 
 impl<T: NumberLike> Display for Prefix<T> {
-  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+  fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
     let jumpstart_str = if let Some(jumpstart) = self.run_len_jumpstart {
       format!(" (jumpstart: {})", jumpstart)
     } else {
@@ -78,14 +87,41 @@ pub(crate) struct KInfo<T: NumberLike> {
 }
 
 impl<T: NumberLike> Prefix<T> {
+  /// Creates a prefix for the range from `lower` to `upper` inclusive with
+  /// the given count and Huffman code.
+  /// It has no `run_len_jumpstart` and a `gcd` of 1, but since all fields
+  /// are public, those can be set afterward.
+  ///
+  /// This is for tools that build their own prefixes instead of having a
+  /// [`Compressor`][crate::Compressor] train them.
+  /// Nothing here checks that the prefixes of a chunk are consistent, e.g.
+  /// that their codes are prefix-free; writing inconsistent ones yields
+  /// metadata decompression will reject.
+  pub fn new(count: usize, code: Vec<bool>, lower: T, upper: T) -> Self {
+    Prefix {
+      count,
+      code,
+      lower,
+      upper,
+      run_len_jumpstart: None,
+      gcd: T::Unsigned::ONE,
+      phantom: PhantomData,
+    }
+  }
+
   pub(crate) fn k_info(&self) -> KInfo<T> {
     let diff = (self.upper.to_unsigned() - self.lower.to_unsigned()) / self.gcd;
-    let k = (diff.to_f64() + 1.0).log2().floor() as usize;
-    let only_k_bits_upper = if k == T::Unsigned::BITS {
+    let mut k = float_math::floor(float_math::log2(diff.to_f64() + 1.0)) as usize;
+    let max_with_k_bits = |k: usize| if k == T::Unsigned::BITS {
       T::Unsigned::MAX
     } else {
       (T::Unsigned::ONE << k) - T::Unsigned::ONE
     };
+    // converting diff to a float can round it up to the next power of 2
+    if max_with_k_bits(k) > diff {
+      k -= 1;
+    }
+    let only_k_bits_upper = max_with_k_bits(k);
     let only_k_bits_lower = diff - only_k_bits_upper;
 
     KInfo {
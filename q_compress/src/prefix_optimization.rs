@@ -1,4 +1,5 @@
-use std::marker::PhantomData;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
 
 use crate::{Flags, gcd_utils, Prefix};
 use crate::bits::{avg_depth_bits, avg_offset_bits};
@@ -12,9 +13,10 @@ fn prefix_bit_cost<U: UnsignedLike>(
   weight: usize,
   total_weight: usize,
   gcd: U,
+  deterministic: bool,
 ) -> f64 {
   let offset_cost = avg_offset_bits(lower, upper, gcd);
-  let huffman_cost = avg_depth_bits(weight, total_weight);
+  let huffman_cost = avg_depth_bits(weight, total_weight, deterministic);
   let gcd_cost = if gcd > U::ONE {
     gcd_utils::gcd_bits_required(upper - lower) as f64
   } else {
@@ -26,59 +28,46 @@ fn prefix_bit_cost<U: UnsignedLike>(
     (offset_cost + huffman_cost) * weight as f64 // body cost
 }
 
-// this is an exact optimal strategy
-pub fn optimize_prefixes<T: NumberLike>(
-  wprefixes: Vec<WeightedPrefix<T>>,
-  flags: &Flags,
-  n: usize,
-) -> Vec<WeightedPrefix<T>> {
-  let mut c = 0;
-  let mut cum_weight = Vec::with_capacity(wprefixes.len() + 1);
-  cum_weight.push(0);
-  for wp in &wprefixes {
-    c += wp.weight;
-    cum_weight.push(c);
-  }
-  let prefixes = wprefixes.iter()
-    .map(|wp| wp.prefix.clone())
-    .collect::<Vec<_>>();
-  let gcds = prefixes.iter()
-    .map(|p| p.gcd)
-    .collect::<Vec<_>>();
-  let total_weight = c;
-  let lower_unsigneds = prefixes.iter()
-    .map(|p| p.lower.to_unsigned())
-    .collect::<Vec<_>>();
-  let upper_unsigneds = prefixes.iter()
-    .map(|p| p.upper.to_unsigned())
-    .collect::<Vec<_>>();
-
-  let maybe_rep_idx = prefixes.iter()
-    .position(|p| p.run_len_jumpstart.is_some());
+// the number of times to halve the interval when searching for the largest
+// per-prefix penalty that fits within the size overhead
+const PENALTY_SEARCH_ITERS: usize = 16;
 
-  let mut best_costs = Vec::with_capacity(wprefixes.len() + 1);
-  let mut best_paths = Vec::with_capacity(wprefixes.len() + 1);
+// Finds the partition of the prefixes into merged ranges that minimizes the
+// estimated size plus `prefix_penalty` bits per resulting prefix.
+// Returns the (start, end) indices of each merged range and its estimated
+// size in bits, excluding penalties.
+#[allow(clippy::too_many_arguments)]
+fn best_path<T: NumberLike>(
+  prefixes: &[Prefix<T>],
+  cum_weight: &[usize],
+  lower_unsigneds: &[T::Unsigned],
+  upper_unsigneds: &[T::Unsigned],
+  base_meta_cost: f64,
+  fold_gcd: bool,
+  deterministic: bool,
+  prefix_penalty: f64,
+) -> (Vec<(usize, usize)>, f64) {
+  let total_weight = *cum_weight.last().unwrap();
+  let mut best_costs = Vec::with_capacity(prefixes.len() + 1);
+  let mut best_sizes = Vec::with_capacity(prefixes.len() + 1);
+  let mut best_paths = Vec::with_capacity(prefixes.len() + 1);
   best_costs.push(0.0);
+  best_sizes.push(0.0);
   best_paths.push(Vec::new());
 
-  let bits_to_encode_count = flags.bits_to_encode_count(n);
-  let base_meta_cost = bits_to_encode_count as f64 +
-    2.0 * T::PHYSICAL_BITS as f64 + // lower and upper bounds
-    flags.bits_to_encode_code_len() as f64 +
-    if flags.use_gcds { 1.0 } else { 0.0 } + // bit to say whether there is GCD or not
-    1.0; // bit to say there is no run len jumpstart
-  // determine whether we can skip GCD folding to improve performance in some cases
-  let fold_gcd = gcd_utils::use_gcd_prefix_optimize(&prefixes, flags);
-
-  for i in 0..wprefixes.len() {
+  // prefixes with run lengths can't be merged with any others
+  let mut maybe_last_rep_idx = None;
+  for i in 0..prefixes.len() {
     let mut best_cost = f64::MAX;
+    let mut best_size = f64::MAX;
     let mut best_j = usize::MAX;
     let upper = upper_unsigneds[i];
     let cum_weight_i = cum_weight[i + 1];
-    let start_j = match maybe_rep_idx {
-      Some(ind) if ind < i => ind + 1,
-      Some(ind) if ind == i => ind,
-      _ => 0,
+    let start_j = if prefixes[i].run_len_jumpstart.is_some() {
+      maybe_last_rep_idx = Some(i);
+      i
+    } else {
+      maybe_last_rep_idx.map_or(0, |ind| ind + 1)
     };
     let mut gcd_acc = None;
     for j in (start_j..i + 1).rev() {
@@ -87,35 +76,108 @@ pub fn optimize_prefixes<T: NumberLike>(
         gcd_utils::fold_prefix_gcds_left(
           lower,
           upper_unsigneds[j],
-          gcds[j],
+          prefixes[j].gcd,
           upper,
           &mut gcd_acc
         );
       }
-      let cost = best_costs[j] + prefix_bit_cost::<T::Unsigned>(
+      let size = prefix_bit_cost::<T::Unsigned>(
         base_meta_cost,
         lower,
         upper,
         cum_weight_i - cum_weight[j],
         total_weight,
         gcd_acc.unwrap_or(T::Unsigned::ONE),
+        deterministic,
       );
+      let cost = best_costs[j] + size + prefix_penalty;
       if cost < best_cost {
         best_cost = cost;
+        best_size = best_sizes[j] + size;
         best_j = j;
       }
     }
 
     best_costs.push(best_cost);
+    best_sizes.push(best_size);
     let mut best_path = Vec::with_capacity(best_paths[best_j].len() + 1);
     best_path.extend(&best_paths[best_j]);
     best_path.push((best_j, i));
     best_paths.push(best_path);
   }
 
-  let path = best_paths.last().unwrap();
+  (best_paths.pop().unwrap(), best_sizes.pop().unwrap())
+}
+
+// Without a size overhead, this is an exact optimal strategy.
+// With one, it finds the largest per-prefix penalty whose optimal path
+// stays within the overhead, which trades size for fewer prefixes.
+pub fn optimize_prefixes<T: NumberLike>(
+  wprefixes: Vec<WeightedPrefix<T>>,
+  flags: &Flags,
+  n: usize,
+  deterministic: bool,
+  size_overhead: f64,
+) -> Vec<WeightedPrefix<T>> {
+  let mut c = 0;
+  let mut cum_weight = Vec::with_capacity(wprefixes.len() + 1);
+  cum_weight.push(0);
+  for wp in &wprefixes {
+    c += wp.weight;
+    cum_weight.push(c);
+  }
+  let prefixes = wprefixes.iter()
+    .map(|wp| wp.prefix.clone())
+    .collect::<Vec<_>>();
+  let lower_unsigneds = prefixes.iter()
+    .map(|p| p.lower.to_unsigned())
+    .collect::<Vec<_>>();
+  let upper_unsigneds = prefixes.iter()
+    .map(|p| p.upper.to_unsigned())
+    .collect::<Vec<_>>();
+
+  let bits_to_encode_count = flags.bits_to_encode_count(n);
+  let base_meta_cost = bits_to_encode_count as f64 +
+    2.0 * T::PHYSICAL_BITS as f64 + // lower and upper bounds
+    flags.bits_to_encode_code_len() as f64 +
+    if flags.use_gcds { 1.0 } else { 0.0 } + // bit to say whether there is GCD or not
+    1.0; // bit to say there is no run len jumpstart
+  // determine whether we can skip GCD folding to improve performance in some cases
+  let fold_gcd = gcd_utils::use_gcd_prefix_optimize(&prefixes, flags);
+
+  let path_with_penalty = |prefix_penalty: f64| best_path(
+    &prefixes,
+    &cum_weight,
+    &lower_unsigneds,
+    &upper_unsigneds,
+    base_meta_cost,
+    fold_gcd,
+    deterministic,
+    prefix_penalty,
+  );
+  let (mut path, optimal_size) = path_with_penalty(0.0);
+  if size_overhead > 0.0 && path.len() > 1 {
+    let max_size = optimal_size * (1.0 + size_overhead);
+    // a penalty of the whole size always leaves as few prefixes as possible
+    let mut lo = 0.0;
+    let mut hi = optimal_size;
+    for _ in 0..PENALTY_SEARCH_ITERS {
+      let mid = (lo + hi) / 2.0;
+      let (candidate, size) = path_with_penalty(mid);
+      if size <= max_size {
+        lo = mid;
+        path = candidate;
+        if path.len() == 1 {
+          break;
+        }
+      } else {
+        hi = mid;
+      }
+    }
+  }
+
   let mut res = Vec::with_capacity(path.len());
-  for &(j, i) in path {
+  for (j, i) in path {
     let mut count = 0;
     let mut gcd_acc = None;
     for (k, p) in prefixes.iter().enumerate().take(i + 1).skip(j).rev() {
@@ -149,7 +211,7 @@ pub fn optimize_prefixes<T: NumberLike>(
 
 #[cfg(test)]
 mod tests {
-  use std::marker::PhantomData;
+  use core::marker::PhantomData;
 
   use crate::Flags;
   use crate::prefix::WeightedPrefix;
@@ -161,6 +223,23 @@ mod tests {
       use_min_count_encoding: true,
       use_5_bit_code_len: true,
       delta_encoding_order: 0,
+      use_chunk_checksums: false,
+      use_file_checksum: false,
+      use_decimal_multipliers: false,
+      use_xor_encoding: false,
+      use_chunk_delta_orders: false,
+      delta_lag: 1,
+      use_quantization: false,
+      use_raw_fallback: false,
+      use_shared_prefixes: false,
+      use_user_metadata: false,
+      use_float_split: false,
+      use_min_max: false,
+      use_distinct_count: false,
+      use_nan_positions: false,
+      use_affine_transforms: false,
+      canonicalize_negative_zero: false,
+      flush_subnormals: false,
       phantom: PhantomData,
     }
   }
@@ -175,6 +254,8 @@ mod tests {
       wps,
       &basic_flags(),
       100,
+      false,
+      0.0,
     );
     let expected = vec![
       WeightedPrefix::new(2, 2, 1000_i32, 2000, None, 1000_u32),
@@ -192,6 +273,8 @@ mod tests {
       wps,
       &basic_flags(),
       100,
+      false,
+      0.0,
     );
     let expected = vec![
       WeightedPrefix::new(101, 101, 1000_i32, 2100, None, 10_u32),
@@ -209,6 +292,8 @@ mod tests {
       wps,
       &basic_flags(),
       100,
+      false,
+      0.0,
     );
     let expected = vec![
       WeightedPrefix::new(10, 10, 1000_i32, 1135, None, 5_u32),
@@ -226,6 +311,8 @@ mod tests {
       wps,
       &basic_flags(),
       100,
+      false,
+      0.0,
     );
     let expected = vec![
       WeightedPrefix::new(100, 100, 1000_i32, 1100, None, 10_u32),
@@ -233,4 +320,55 @@ mod tests {
     ];
     assert_eq!(res, expected);
   }
+
+  #[test]
+  fn test_optimize_multiple_run_len_prefixes() {
+    let wps = vec![
+      WeightedPrefix::new(50, 1, 1000_i32, 1000, Some(5), 1_u32),
+      WeightedPrefix::new(1, 1, 1001_i32, 1001, None, 1_u32),
+      WeightedPrefix::new(1, 1, 1002_i32, 1002, None, 1_u32),
+      WeightedPrefix::new(50, 1, 1003_i32, 1003, Some(5), 1_u32),
+    ];
+    let res = optimize_prefixes(
+      wps,
+      &basic_flags(),
+      100,
+      false,
+      0.0,
+    );
+    let expected = vec![
+      WeightedPrefix::new(50, 1, 1000_i32, 1000, Some(5), 1_u32),
+      WeightedPrefix::new(2, 2, 1001_i32, 1002, None, 1_u32),
+      WeightedPrefix::new(50, 1, 1003_i32, 1003, Some(5), 1_u32),
+    ];
+    assert_eq!(res, expected);
+  }
+
+  #[test]
+  fn test_optimize_with_size_overhead() {
+    let wps = vec![
+      WeightedPrefix::new(100, 100, 1000_i32, 1100, None, 10_u32),
+      WeightedPrefix::new(100, 100, 1101, 1201, None, 10_u32),
+    ];
+    // merging loses the GCD, which costs too much for a small overhead
+    let res = optimize_prefixes(
+      wps.clone(),
+      &basic_flags(),
+      100,
+      false,
+      0.1,
+    );
+    assert_eq!(res, wps);
+    let res = optimize_prefixes(
+      wps,
+      &basic_flags(),
+      100,
+      false,
+      1.0,
+    );
+    let expected = vec![
+      WeightedPrefix::new(200, 200, 1000_i32, 1201, None, 1_u32),
+    ];
+    assert_eq!(res, expected);
+  }
 }
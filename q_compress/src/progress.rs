@@ -0,0 +1,29 @@
+/// A snapshot of how far a long-running compression or decompression has
+/// gotten, reported after each chunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProgressUpdate {
+  /// The number of chunks finished so far.
+  pub n_chunks: usize,
+  /// The number of numbers compressed or decompressed so far.
+  pub n_numbers: usize,
+  /// The number of compressed bytes written or read so far.
+  pub n_bytes: usize,
+}
+
+/// Receives a [`ProgressUpdate`] after each chunk of
+/// [`Compressor::simple_compress_with_progress`][crate::Compressor::simple_compress_with_progress]
+/// or
+/// [`Decompressor::simple_decompress_with_progress`][crate::Decompressor::simple_decompress_with_progress],
+/// e.g. to drive a progress bar or emit heartbeats.
+///
+/// This is implemented for any `FnMut(ProgressUpdate)`, so a closure will
+/// do.
+pub trait Progress {
+  fn on_chunk(&mut self, update: ProgressUpdate);
+}
+
+impl<F: FnMut(ProgressUpdate)> Progress for F {
+  fn on_chunk(&mut self, update: ProgressUpdate) {
+    self(update)
+  }
+}
@@ -0,0 +1,207 @@
+use alloc::format;
+use alloc::vec::Vec;
+
+use crate::{ChunkMetadata, float_math, Prefix, PrefixMetadata};
+use crate::data_types::{NumberLike, UnsignedLike};
+use crate::errors::{QCompressError, QCompressResult};
+
+/// An approximate quantile computed from chunk metadata alone, returned by
+/// [`approx_quantile`] and [`ChunkMetadata::approx_quantile`].
+///
+/// The q-quantile of `n` numbers here means the number at index
+/// `round(q * (n - 1))` when they are sorted.
+/// The true quantile is guaranteed to lie in `[lower, upper]`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QuantileEstimate<T: NumberLike> {
+  /// The most likely value of the quantile, assuming the numbers in each
+  /// prefix are spread uniformly over its range.
+  pub estimate: T,
+  /// A lower bound on the quantile.
+  pub lower: T,
+  /// An upper bound on the quantile.
+  pub upper: T,
+}
+
+// the prefixes of a chunk, if they describe its numbers directly with
+// their true counts
+fn number_prefixes<T: NumberLike>(meta: &ChunkMetadata<T>) -> QCompressResult<&[Prefix<T>]> {
+  match &meta.prefix_metadata {
+    PrefixMetadata::Simple { prefixes } if meta.decimal_exponent.is_none() &&
+      meta.quantization.is_none() &&
+      meta.affine_transform.is_none() &&
+      !meta.uses_shared_prefixes => Ok(prefixes),
+    _ => Err(QCompressError::invalid_argument(
+      "approximate quantiles require chunks whose prefixes describe the numbers themselves"
+    )),
+  }
+}
+
+// the expected count of the prefix's numbers at most `u`, assuming they're
+// spread uniformly over the prefix's multiples of its gcd
+fn expected_count_at_most<T: NumberLike>(p: &Prefix<T>, u: T::Unsigned) -> f64 {
+  let lower = p.lower.to_unsigned();
+  let upper = p.upper.to_unsigned();
+  if u < lower {
+    0.0
+  } else if u >= upper {
+    p.count as f64
+  } else {
+    let gcd = p.gcd.max(T::Unsigned::ONE);
+    let n_values = ((upper - lower) / gcd).to_f64() + 1.0;
+    let n_values_at_most = ((u - lower) / gcd).to_f64() + 1.0;
+    p.count as f64 * n_values_at_most / n_values
+  }
+}
+
+// the smallest bound such that prefixes totaling more than `rank` numbers
+// have their bound at most it
+fn bound_for_rank<T: NumberLike>(
+  prefixes: &[&Prefix<T>],
+  rank: usize,
+  bound: impl Fn(&Prefix<T>) -> T::Unsigned,
+) -> T::Unsigned {
+  let mut sorted = prefixes.to_vec();
+  sorted.sort_unstable_by_key(|p| bound(p));
+  let mut cumulative = 0;
+  for p in &sorted {
+    cumulative += p.count;
+    if cumulative > rank {
+      return bound(p);
+    }
+  }
+  unreachable!("rank was less than the total count")
+}
+
+/// Returns an approximate `q`-quantile of all the numbers in the given
+/// chunks, computed from their prefixes alone, without decompressing any
+/// chunk bodies.
+///
+/// Each chunk's prefixes act as a histogram of its numbers.
+/// The returned bounds are exact: the true quantile always lies between
+/// them.
+/// They are tightest for a single chunk, where they are the bounds of the
+/// prefix containing the quantile, and widen when prefixes of different
+/// chunks overlap.
+/// Higher compression levels use more, narrower prefixes, so they give
+/// tighter bounds.
+///
+/// Will return an error if `q` is not in `[0, 1]`, if the chunks contain
+/// no numbers, or if any chunk's prefixes don't describe its numbers
+/// directly, as for delta, XOR, float split, or raw encoding, decimal
+/// multipliers, quantization, or shared prefixes.
+pub fn approx_quantile<T: NumberLike>(
+  metadatas: &[ChunkMetadata<T>],
+  q: f64,
+) -> QCompressResult<QuantileEstimate<T>> {
+  if !(0.0..=1.0).contains(&q) {
+    return Err(QCompressError::invalid_argument(format!(
+      "quantile must be between 0 and 1 (was {})",
+      q,
+    )));
+  }
+  let mut prefixes = Vec::new();
+  for meta in metadatas {
+    prefixes.extend(number_prefixes(meta)?);
+  }
+  let n = prefixes.iter().map(|p| p.count).sum::<usize>();
+  if n == 0 {
+    return Err(QCompressError::invalid_argument(
+      "cannot compute quantile of no numbers"
+    ));
+  }
+
+  let rank = float_math::floor(q * (n - 1) as f64 + 0.5) as usize;
+  let lower = bound_for_rank(&prefixes, rank, |p| p.lower.to_unsigned());
+  let upper = bound_for_rank(&prefixes, rank, |p| p.upper.to_unsigned());
+
+  // binary search for the first number whose expected count of numbers at
+  // most it reaches the middle of the rank
+  let target = rank as f64 + 0.5;
+  let mut lo = lower;
+  let mut hi = upper;
+  while lo < hi {
+    let mid = lo + (hi - lo) / (T::Unsigned::ONE + T::Unsigned::ONE);
+    let expected = prefixes.iter()
+      .map(|p| expected_count_at_most(p, mid))
+      .sum::<f64>();
+    if expected >= target {
+      hi = mid;
+    } else {
+      lo = mid + T::Unsigned::ONE;
+    }
+  }
+
+  Ok(QuantileEstimate {
+    estimate: T::from_unsigned(lo),
+    lower: T::from_unsigned(lower),
+    upper: T::from_unsigned(upper),
+  })
+}
+
+impl<T: NumberLike> ChunkMetadata<T> {
+  /// Returns an approximate `q`-quantile of the chunk's numbers, computed
+  /// from its prefixes alone.
+  /// See [`approx_quantile`] for the error bounds and when this returns an
+  /// error.
+  pub fn approx_quantile(&self, q: f64) -> QCompressResult<QuantileEstimate<T>> {
+    approx_quantile(core::slice::from_ref(self), q)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{Compressor, CompressorConfig};
+  use super::*;
+
+  fn chunk_metadata<T: NumberLike>(nums: &[T], config: CompressorConfig) -> ChunkMetadata<T> {
+    let mut compressor = Compressor::<T>::from_config(config);
+    compressor.header().unwrap();
+    compressor.chunk(nums).unwrap()
+  }
+
+  fn exact_quantile<T: NumberLike>(nums: &[T], q: f64) -> T {
+    let mut sorted = nums.to_vec();
+    sorted.sort_unstable_by_key(|x| x.to_unsigned());
+    sorted[(q * (nums.len() - 1) as f64).round() as usize]
+  }
+
+  #[test]
+  fn test_approx_quantile() {
+    let nums = (0..10000_i64).map(|i| (i * 7919) % 10007 - 5000).collect::<Vec<_>>();
+    let config = CompressorConfig::default().with_compression_level(6);
+    let meta = chunk_metadata(&nums, config.clone());
+    for q in [0.0, 0.01, 0.25, 0.5, 0.9, 0.999, 1.0] {
+      let exact = exact_quantile(&nums, q);
+      let res = meta.approx_quantile(q).unwrap();
+      assert!(res.lower <= exact && exact <= res.upper, "{} {:?}", q, res);
+      assert!(res.lower <= res.estimate && res.estimate <= res.upper, "{} {:?}", q, res);
+      // roughly uniform data should give a close estimate
+      assert!((res.estimate - exact).abs() <= 100, "{} {:?} {}", q, res, exact);
+    }
+
+    // overlapping chunks still give valid bounds
+    let skewed = (0..3000).map(|i| (i * i) % 4000 - 5000).collect::<Vec<i64>>();
+    let metas = vec![meta, chunk_metadata(&skewed, config)];
+    let all_nums = [nums, skewed].concat();
+    for q in [0.0, 0.1, 0.5, 0.77, 1.0] {
+      let exact = exact_quantile(&all_nums, q);
+      let res = approx_quantile(&metas, q).unwrap();
+      assert!(res.lower <= exact && exact <= res.upper, "{} {:?}", q, res);
+      assert!(res.lower <= res.estimate && res.estimate <= res.upper, "{} {:?}", q, res);
+    }
+
+    // floats order by their unsigned representations
+    let floats = (0..1000).map(|i| i as f32 - 300.5).collect::<Vec<_>>();
+    let res = chunk_metadata(&floats, CompressorConfig::default()).approx_quantile(0.5).unwrap();
+    assert!(res.lower <= 199.5 && 199.5 <= res.upper, "{:?}", res);
+
+    assert!(approx_quantile(&metas, 1.5).is_err());
+    assert!(approx_quantile(&metas, f64::NAN).is_err());
+    assert!(approx_quantile::<i64>(&[], 0.5).is_err());
+    let delta_meta = chunk_metadata(
+      &[1_i64, 2, 3],
+      CompressorConfig::default().with_delta_encoding_order(1),
+    );
+    assert!(delta_meta.approx_quantile(0.5).is_err());
+  }
+}
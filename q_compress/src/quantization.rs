@@ -0,0 +1,112 @@
+use alloc::format;
+use alloc::vec::Vec;
+
+use crate::bit_reader::BitReader;
+use crate::bit_writer::BitWriter;
+use crate::constants::BITS_TO_ENCODE_DROPPED_MANTISSA_BITS;
+use crate::data_types::NumberLike;
+use crate::errors::{QCompressError, QCompressResult};
+
+/// How much error lossy compression may introduce into each number.
+///
+/// See [`CompressorConfig::tolerance`][crate::CompressorConfig::tolerance].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tolerance {
+  /// Each decompressed number may differ from the original by at most
+  /// this much.
+  Absolute(f64),
+  /// Each decompressed number may differ from the original by at most this
+  /// fraction of the original's magnitude.
+  Relative(f64),
+}
+
+impl Tolerance {
+  pub(crate) fn validate(&self) -> QCompressResult<()> {
+    let (name, tolerance) = match self {
+      Tolerance::Absolute(tolerance) => ("absolute", *tolerance),
+      Tolerance::Relative(tolerance) => ("relative", *tolerance),
+    };
+    if tolerance > 0.0 && tolerance < f64::INFINITY {
+      Ok(())
+    } else {
+      Err(QCompressError::invalid_argument(format!(
+        "{} tolerance must be positive and finite (was {})",
+        name,
+        tolerance,
+      )))
+    }
+  }
+}
+
+/// How a chunk's numbers were rounded during lossy compression.
+///
+/// Either way, the numbers were compressed as integers, which
+/// [`NumberLike::dequantize`] converts back.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Quantization {
+  /// Each number was rounded to the nearest integer multiple of this step,
+  /// used for [`Tolerance::Absolute`].
+  Step(f64),
+  /// Each float had this many of its lowest mantissa bits rounded off,
+  /// used for [`Tolerance::Relative`].
+  DroppedMantissaBits(u32),
+}
+
+impl Quantization {
+  pub(crate) fn parse_from<T: NumberLike>(reader: &mut BitReader) -> QCompressResult<Self> {
+    if reader.read_one()? {
+      let dropped_bits = reader.read_usize(BITS_TO_ENCODE_DROPPED_MANTISSA_BITS)?;
+      if dropped_bits == 0 || dropped_bits >= T::PHYSICAL_BITS {
+        return Err(QCompressError::corruption(format!(
+          "invalid count of dropped mantissa bits {} for data type with header byte {}",
+          dropped_bits,
+          T::HEADER_BYTE,
+        )));
+      }
+      Ok(Quantization::DroppedMantissaBits(dropped_bits as u32))
+    } else {
+      let step = f64::read_from(reader)?;
+      if !(step > 0.0 && step < f64::INFINITY) {
+        return Err(QCompressError::corruption(format!(
+          "invalid quantization step {}",
+          step,
+        )));
+      }
+      Ok(Quantization::Step(step))
+    }
+  }
+
+  pub(crate) fn write_to(&self, writer: &mut BitWriter) {
+    match self {
+      Quantization::Step(step) => {
+        writer.write_one(false);
+        step.write_to(writer);
+      },
+      Quantization::DroppedMantissaBits(dropped_bits) => {
+        writer.write_one(true);
+        writer.write_usize(*dropped_bits as usize, BITS_TO_ENCODE_DROPPED_MANTISSA_BITS);
+      },
+    }
+  }
+}
+
+// the quantization meeting the tolerance for every number, if the data type
+// supports one and every number can be quantized
+pub(crate) fn choose_quantization<T: NumberLike>(
+  nums: &[T],
+  tolerance: Tolerance,
+) -> QCompressResult<Option<Quantization>> {
+  tolerance.validate()?;
+  Ok(T::quantization_for(tolerance).filter(|&quantization| {
+    nums.iter().all(|&x| x.quantize(quantization).is_some())
+  }))
+}
+
+// numbers of the same type whose signed representations are the quantized
+// integers, so the rest of compression can treat them like any other chunk
+pub(crate) fn quantized_nums<T: NumberLike>(nums: &[T], quantization: Quantization) -> Vec<T> {
+  nums.iter()
+    .map(|&x| T::from_signed(x.quantize(quantization).unwrap()))
+    .collect()
+}
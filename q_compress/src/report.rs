@@ -0,0 +1,174 @@
+use alloc::vec::Vec;
+
+use crate::{ChunkMetadata, Prefix, PrefixMetadata};
+use crate::data_types::{NumberLike, UnsignedLike};
+use crate::float_math;
+
+/// Compression statistics for one prefix of a chunk, returned as part of
+/// [`ChunkMetadata::report`].
+///
+/// Bit counts are implied by the prefix alone, assuming its numbers are
+/// spread uniformly over its range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrefixReport<U: UnsignedLike> {
+  /// The count of numbers in the prefix's range.
+  pub count: usize,
+  /// `upper - lower` for the prefix, in the unsigned representation.
+  pub range_width: U,
+  /// The length of the prefix's Huffman code.
+  pub code_len: usize,
+  /// The average number of bits each number's offset within the range
+  /// takes.
+  pub offset_bits: f64,
+  /// The average number of bits each number takes: its code plus its
+  /// offset.
+  /// For a prefix with a
+  /// [`run_len_jumpstart`][crate::Prefix::run_len_jumpstart], this assumes
+  /// no repetitions, so it is an upper bound.
+  pub bits_per_number: f64,
+  /// `count * bits_per_number`.
+  pub total_bits: f64,
+}
+
+/// Compression statistics for a chunk, returned by
+/// [`ChunkMetadata::report`].
+///
+/// This explains where the bytes of a chunk body go: which prefixes hold
+/// the most numbers and how many bits each costs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChunkReport<T: NumberLike> {
+  /// The count of numbers in the chunk.
+  pub n: usize,
+  /// A report for each prefix, in the same order as the chunk's prefixes.
+  /// This is empty for raw chunks, and for float split chunks it holds the
+  /// exponent prefixes followed by the mantissa prefixes.
+  pub prefixes: Vec<PrefixReport<T::Unsigned>>,
+  /// The number of bits the chunk body takes according to its prefixes,
+  /// or its bit width for raw chunks.
+  /// For chunks using shared prefixes, whose counts describe the sample the
+  /// prefixes were trained on, this describes that sample instead.
+  pub implied_body_bits: f64,
+  /// Like `implied_body_bits`, but as if each prefix's code had the ideal,
+  /// fractional length `-log2(count / n)` given its count.
+  /// The gap between the two is what the Huffman codes lose to entropy.
+  pub entropy_body_bits: f64,
+  /// The number of bits the chunk body actually takes, excluding any
+  /// checksum.
+  pub compressed_body_bits: usize,
+}
+
+impl<T: NumberLike> ChunkReport<T> {
+  /// Returns the average number of bits each number in the chunk body
+  /// actually takes.
+  pub fn bits_per_number(&self) -> f64 {
+    self.compressed_body_bits as f64 / self.n as f64
+  }
+}
+
+fn prefix_report<P: NumberLike>(p: &Prefix<P>) -> PrefixReport<P::Unsigned> {
+  let range_width = p.upper.to_unsigned() - p.lower.to_unsigned();
+  let k_info = p.k_info();
+  // offsets from only_k_bits_lower to only_k_bits_upper take k bits and
+  // the rest take k + 1
+  let n_offsets = (range_width / p.gcd).to_f64() + 1.0;
+  let n_k_bit_offsets = (k_info.only_k_bits_upper - k_info.only_k_bits_lower).to_f64() + 1.0;
+  let offset_bits = k_info.k as f64 + 1.0 - n_k_bit_offsets / n_offsets;
+  let bits_per_number = p.code.len() as f64 + offset_bits;
+  PrefixReport {
+    count: p.count,
+    range_width,
+    code_len: p.code.len(),
+    offset_bits,
+    bits_per_number,
+    total_bits: p.count as f64 * bits_per_number,
+  }
+}
+
+// the bits a set of prefixes would take if each code had its ideal length
+fn entropy_bits<U: UnsignedLike>(reports: &[PrefixReport<U>]) -> f64 {
+  let total_count = reports.iter().map(|p| p.count).sum::<usize>() as f64;
+  reports.iter()
+    .filter(|p| p.count > 0)
+    .map(|p| {
+      let count = p.count as f64;
+      count * (p.offset_bits - float_math::log2(count / total_count))
+    })
+    .sum()
+}
+
+impl<T: NumberLike> ChunkMetadata<T> {
+  /// Returns per-prefix and total compression statistics for the chunk,
+  /// computed from its metadata alone.
+  pub fn report(&self) -> ChunkReport<T> {
+    let (prefixes, implied_body_bits, entropy_body_bits) = match &self.prefix_metadata {
+      PrefixMetadata::Simple { prefixes } => {
+        let reports = prefixes.iter().map(prefix_report).collect::<Vec<_>>();
+        let bits = reports.iter().map(|r| r.total_bits).sum();
+        let entropy = entropy_bits(&reports);
+        (reports, bits, entropy)
+      },
+      PrefixMetadata::Delta { prefixes, .. } |
+      PrefixMetadata::Xor { prefixes, .. } => {
+        let reports = prefixes.iter().map(prefix_report).collect::<Vec<_>>();
+        let bits = reports.iter().map(|r| r.total_bits).sum();
+        let entropy = entropy_bits(&reports);
+        (reports, bits, entropy)
+      },
+      PrefixMetadata::Raw { bit_width, .. } => {
+        let bits = (self.n * bit_width) as f64;
+        (Vec::new(), bits, bits)
+      },
+      PrefixMetadata::FloatSplit { exponent_prefixes, mantissa_prefixes, .. } => {
+        // each number is in one exponent prefix and one mantissa prefix
+        let mut reports = exponent_prefixes.iter().map(prefix_report).collect::<Vec<_>>();
+        let mantissa_reports = mantissa_prefixes.iter().map(prefix_report).collect::<Vec<_>>();
+        let entropy = entropy_bits(&reports) + entropy_bits(&mantissa_reports);
+        reports.extend(mantissa_reports);
+        let bits = reports.iter().map(|r| r.total_bits).sum();
+        (reports, bits, entropy)
+      },
+    };
+    ChunkReport {
+      n: self.n,
+      prefixes,
+      implied_body_bits,
+      entropy_body_bits,
+      compressed_body_bits: self.compressed_body_size * 8,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{Compressor, CompressorConfig};
+
+  #[test]
+  fn test_report() {
+    // uniformish numbers, for which the implied bits should be accurate
+    let nums = (0..10000_i64).map(|i| (i * 7919) % 1000).collect::<Vec<_>>();
+    let mut compressor = Compressor::<i64>::from_config(
+      CompressorConfig::default().with_compression_level(4)
+    );
+    compressor.header().unwrap();
+    let report = compressor.chunk(&nums).unwrap().report();
+    assert_eq!(report.n, 10000);
+    assert!(!report.prefixes.is_empty() && report.prefixes.len() <= 16);
+    assert_eq!(report.prefixes.iter().map(|p| p.count).sum::<usize>(), 10000);
+    for p in &report.prefixes {
+      assert!(p.offset_bits >= 0.0 && p.offset_bits <= 64.0);
+      assert!(p.range_width < 1000);
+    }
+    let actual = report.compressed_body_bits as f64;
+    assert!((report.implied_body_bits - actual).abs() < 0.02 * actual);
+    assert!(report.entropy_body_bits <= report.implied_body_bits + 1e-6);
+    assert!(report.bits_per_number() > 9.0 && report.bits_per_number() < 11.0);
+
+    let mut compressor = Compressor::<i64>::from_config(
+      CompressorConfig::default().with_use_raw_fallback(true)
+    );
+    compressor.header().unwrap();
+    let report = compressor.chunk(&[0, 1, 2, 3]).unwrap().report();
+    assert!(report.prefixes.is_empty());
+    assert_eq!(report.implied_body_bits, 8.0);
+  }
+}
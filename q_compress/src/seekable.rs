@@ -0,0 +1,224 @@
+use alloc::format;
+use alloc::vec::Vec;
+use core::cmp::min;
+
+use crate::{Decompressor, DecompressorConfig};
+use crate::chunk_handle::{self, BLOCK_SIZE};
+use crate::data_types::NumberLike;
+use crate::decompressor::ChunkStart;
+use crate::errors::{QCompressError, QCompressResult};
+
+/// Decompresses a complete .qco file as a random-access array of numbers.
+///
+/// On creation, this reads the metadata of every chunk (but none of their
+/// bodies) to learn which numbers each chunk holds.
+/// Afterward, [`seek_to`][Self::seek_to] positions it at any number by
+/// jumping to the start of the chunk containing it and decoding its way
+/// forward, so seeking costs at most about one chunk's worth of decoding.
+/// Seeking forward within the current chunk continues from where it left
+/// off.
+/// ```
+/// use q_compress::{Compressor, SeekableDecompressor};
+///
+/// let nums = (0..10000_i64).map(|i| i * i).collect::<Vec<_>>();
+/// let bytes = Compressor::<i64>::default().simple_compress(&nums);
+/// let mut decompressor = SeekableDecompressor::<i64>::new(&bytes).expect("chunk metadata");
+/// assert_eq!(decompressor.len(), 10000);
+/// decompressor.seek_to(5000).expect("seek");
+/// assert_eq!(decompressor.read(3).expect("read"), vec![25000000, 25010001, 25020004]);
+/// assert_eq!(decompressor.position(), 5003);
+/// ```
+#[derive(Clone, Debug)]
+pub struct SeekableDecompressor<T: NumberLike> {
+  decompressor: Decompressor<T>,
+  chunk_starts: Vec<ChunkStart<T>>,
+  // index of the first number of each chunk
+  chunk_num_idxs: Vec<usize>,
+  len: usize,
+  position: usize,
+  // the chunk containing the position, partially decoded up to it
+  current: Option<(usize, ChunkStart<T>)>,
+}
+
+impl<T: NumberLike> SeekableDecompressor<T> {
+  /// Creates a new seekable decompressor over a complete .qco file with
+  /// the default [`DecompressorConfig`].
+  /// Will return an error if the header or any chunk metadata is corrupt
+  /// or incomplete.
+  pub fn new(bytes: &[u8]) -> QCompressResult<Self> {
+    Self::from_config(DecompressorConfig::default(), bytes)
+  }
+
+  /// Creates a new seekable decompressor over a complete .qco file, given
+  /// a [`DecompressorConfig`].
+  /// Will return an error if the config is invalid or if the header or any
+  /// chunk metadata is corrupt or incomplete.
+  pub fn from_config(config: DecompressorConfig, bytes: &[u8]) -> QCompressResult<Self> {
+    let mut decompressor = Decompressor::try_from_config(config)?;
+    decompressor.extend_bytes(bytes);
+    let chunk_starts = decompressor.scan_chunk_starts()?;
+    let mut chunk_num_idxs = Vec::with_capacity(chunk_starts.len());
+    let mut len = 0;
+    for start in &chunk_starts {
+      chunk_num_idxs.push(len);
+      len += start.metadata.n;
+    }
+    Ok(Self {
+      decompressor,
+      chunk_starts,
+      chunk_num_idxs,
+      len,
+      position: 0,
+      current: None,
+    })
+  }
+
+  /// Returns the total count of numbers in the file.
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  /// Returns whether the file contains no numbers.
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// Returns the number of chunks in the file.
+  pub fn n_chunks(&self) -> usize {
+    self.chunk_starts.len()
+  }
+
+  /// Returns the index of the next number [`read`][Self::read] will
+  /// return.
+  pub fn position(&self) -> usize {
+    self.position
+  }
+
+  /// Positions the decompressor at the number with this index.
+  /// Seeking to [`len`][Self::len] is allowed and leaves nothing to read.
+  /// Will return an error if the index is greater than the length or if
+  /// the chunk body leading up to it is corrupt.
+  pub fn seek_to(&mut self, index: usize) -> QCompressResult<()> {
+    if index > self.len {
+      return Err(QCompressError::invalid_argument(format!(
+        "cannot seek to index {} of file with {} numbers",
+        index,
+        self.len,
+      )));
+    }
+
+    let chunk_idx = self.chunk_num_idxs.partition_point(|&num_idx| num_idx <= index)
+      .saturating_sub(1);
+    let continues_current = matches!(
+      &self.current,
+      Some((current_idx, _)) if *current_idx == chunk_idx && self.position <= index,
+    );
+    if !continues_current {
+      self.current = self.chunk_starts.get(chunk_idx)
+        .map(|start| (chunk_idx, start.clone()));
+      self.position = self.chunk_num_idxs.get(chunk_idx).copied().unwrap_or(0);
+    }
+
+    // skip-decode our way to the index within its chunk
+    while self.position < index {
+      let n_skipped = self.decompress_current(min(index - self.position, BLOCK_SIZE))?.len();
+      self.position += n_skipped;
+    }
+    Ok(())
+  }
+
+  /// Decodes and returns up to `n` numbers starting at the current
+  /// position, advancing past them.
+  /// This returns fewer than `n` numbers only at the end of the file.
+  /// Will return an error if a chunk body is corrupt.
+  pub fn read(&mut self, n: usize) -> QCompressResult<Vec<T>> {
+    let n = min(n, self.len - self.position);
+    let mut res = Vec::with_capacity(n);
+    while res.len() < n {
+      let current_finished = match &self.current {
+        Some((_, start)) => start.body_decompressor.n_remaining() == 0,
+        None => true,
+      };
+      if current_finished {
+        // move on to the next chunk with any numbers
+        self.seek_to(self.position)?;
+        continue;
+      }
+      let nums = self.decompress_current(n - res.len())?;
+      self.position += nums.len();
+      res.extend(nums);
+    }
+    Ok(res)
+  }
+
+  fn decompress_current(&mut self, limit: usize) -> QCompressResult<Vec<T>> {
+    let (_, start) = self.current.as_mut().unwrap();
+    chunk_handle::decompress_chunk_body(
+      start,
+      self.decompressor.words(),
+      self.decompressor.freed_bits(),
+      limit,
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{Compressor, CompressorConfig};
+  use crate::errors::ErrorKind;
+  use super::*;
+
+  fn compress_in_chunks(nums: &[i64], chunk_size: usize, config: CompressorConfig) -> Vec<u8> {
+    let mut compressor = Compressor::<i64>::from_config(config);
+    compressor.header().unwrap();
+    for chunk in nums.chunks(chunk_size) {
+      compressor.chunk(chunk).unwrap();
+    }
+    compressor.footer().unwrap();
+    compressor.drain_bytes()
+  }
+
+  #[test]
+  fn test_seek_and_read() {
+    let nums = (0..9000_i64).map(|i| (i * 7919) % 10007 - 5000).collect::<Vec<_>>();
+    for config in [
+      CompressorConfig::default(),
+      CompressorConfig::default().with_delta_encoding_order(2),
+    ] {
+      let bytes = compress_in_chunks(&nums, 2500, config);
+      let mut decompressor = SeekableDecompressor::<i64>::new(&bytes).unwrap();
+      assert_eq!(decompressor.len(), nums.len());
+      assert_eq!(decompressor.n_chunks(), 4);
+
+      // backward, forward within a chunk, across chunks, and to the end
+      for (index, n) in [
+        (7000, 10),
+        (100, 3),
+        (2000, 1),
+        (2400, 200),
+        (2499, 7000),
+        (2500, 1),
+        (0, 9000),
+        (9000, 5),
+      ] {
+        decompressor.seek_to(index).unwrap();
+        let expected = &nums[index..min(index + n, nums.len())];
+        assert_eq!(decompressor.read(n).unwrap(), expected, "{} {}", index, n);
+        assert_eq!(decompressor.position(), index + expected.len());
+      }
+    }
+  }
+
+  #[test]
+  fn test_seekable_errors() {
+    let bytes = compress_in_chunks(&[1, 2, 3], 2, CompressorConfig::default());
+    let mut decompressor = SeekableDecompressor::<i64>::new(&bytes).unwrap();
+    let err = decompressor.seek_to(4).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+    let err = SeekableDecompressor::<i64>::new(&bytes[..bytes.len() - 1]).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InsufficientData));
+
+    let empty = SeekableDecompressor::<i64>::new(&compress_in_chunks(&[], 1, CompressorConfig::default())).unwrap();
+    assert!(empty.is_empty());
+  }
+}
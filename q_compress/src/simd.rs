@@ -0,0 +1,111 @@
+// SIMD kernels for delta decoding of 32- and 64-bit integers.
+// Delta encoding needs none, since the compiler already vectorizes its
+// loop; decoding is a prefix sum, whose serial dependency it can't.
+//
+// We only use instruction sets the target is compiled with (SSE2 is part of
+// every x86_64 target), so this needs neither std nor runtime feature
+// detection. Other targets, including wasm, use the scalar loops in
+// `SignedLike`.
+
+#[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+mod sse2 {
+  use core::arch::x86_64::*;
+
+  // Each of these writes `acc` plus the sum of the preceding elements to each
+  // element and returns `acc` plus the sum of all elements.
+  // Within a vector, we shift and add to get the sums of preceding lanes,
+  // so the only serial dependency is one add per vector for `acc`.
+
+  pub fn exclusive_prefix_sum_i32(acc: i32, xs: &mut [i32]) -> i32 {
+    let mut chunks = xs.chunks_exact_mut(4);
+    // SAFETY: SSE2 is enabled at compile time, and each unaligned load and
+    // store covers exactly one chunk.
+    let mut acc = unsafe {
+      let mut acc_v = _mm_set1_epi32(acc);
+      for chunk in &mut chunks {
+        let ptr = chunk.as_mut_ptr() as *mut __m128i;
+        let x = _mm_loadu_si128(ptr);
+        // [0, a, b, c] -> [0, a, a+b, b+c] -> [0, a, a+b, a+b+c]
+        let mut preceding = _mm_slli_si128(x, 4);
+        preceding = _mm_add_epi32(preceding, _mm_slli_si128(preceding, 4));
+        preceding = _mm_add_epi32(preceding, _mm_slli_si128(preceding, 8));
+        _mm_storeu_si128(ptr, _mm_add_epi32(acc_v, preceding));
+        let total = _mm_shuffle_epi32(_mm_add_epi32(preceding, x), 0xff);
+        acc_v = _mm_add_epi32(acc_v, total);
+      }
+      _mm_cvtsi128_si32(acc_v)
+    };
+    for x in chunks.into_remainder() {
+      let next = acc.wrapping_add(*x);
+      *x = acc;
+      acc = next;
+    }
+    acc
+  }
+
+  pub fn exclusive_prefix_sum_i64(acc: i64, xs: &mut [i64]) -> i64 {
+    let mut chunks = xs.chunks_exact_mut(2);
+    // SAFETY: SSE2 is enabled at compile time, and each unaligned load and
+    // store covers exactly one chunk.
+    let mut acc = unsafe {
+      let mut acc_v = _mm_set1_epi64x(acc);
+      for chunk in &mut chunks {
+        let ptr = chunk.as_mut_ptr() as *mut __m128i;
+        let x = _mm_loadu_si128(ptr);
+        // [0, a]
+        let preceding = _mm_slli_si128(x, 8);
+        _mm_storeu_si128(ptr, _mm_add_epi64(acc_v, preceding));
+        let total = _mm_add_epi64(preceding, x);
+        acc_v = _mm_add_epi64(acc_v, _mm_unpackhi_epi64(total, total));
+      }
+      _mm_cvtsi128_si64(acc_v)
+    };
+    for x in chunks.into_remainder() {
+      let next = acc.wrapping_add(*x);
+      *x = acc;
+      acc = next;
+    }
+    acc
+  }
+}
+
+#[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+pub use sse2::*;
+
+#[cfg(all(test, target_arch = "x86_64", target_feature = "sse2"))]
+mod tests {
+  use super::*;
+
+  fn scalar_prefix_sum(mut acc: i64, xs: &mut [i64]) -> i64 {
+    for x in xs.iter_mut() {
+      let next = acc.wrapping_add(*x);
+      *x = acc;
+      acc = next;
+    }
+    acc
+  }
+
+  fn test_nums(n: usize) -> Vec<i64> {
+    (0..n as i64)
+      .map(|i| i.wrapping_mul(0x5851_f42d_4c95_7f2d) ^ (i << 3))
+      .collect()
+  }
+
+  #[test]
+  fn test_prefix_sums_match_scalar() {
+    for n in 0..12 {
+      let nums = test_nums(n);
+      let mut expected = nums.clone();
+      let expected_acc = scalar_prefix_sum(-7, &mut expected);
+
+      let mut xs = nums.clone();
+      assert_eq!(exclusive_prefix_sum_i64(-7, &mut xs), expected_acc);
+      assert_eq!(xs, expected);
+
+      let mut xs = nums.iter().map(|&x| x as i32).collect::<Vec<_>>();
+      let acc = exclusive_prefix_sum_i32(-7, &mut xs);
+      assert_eq!(acc, expected_acc as i32);
+      assert_eq!(xs, expected.iter().map(|&x| x as i32).collect::<Vec<_>>());
+    }
+  }
+}
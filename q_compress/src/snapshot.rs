@@ -0,0 +1,214 @@
+use alloc::format;
+use alloc::vec::Vec;
+
+use crate::bit_reader::BitReader;
+use crate::bit_words::BitWords;
+use crate::bit_writer::BitWriter;
+use crate::constants::{BITS_TO_ENCODE_N_ENTRIES, BITS_TO_ENCODE_SNAPSHOT_USIZE, MAGIC_SNAPSHOT_HEADER};
+use crate::errors::{QCompressError, QCompressResult};
+
+/// A checkpoint of a [`Decompressor`][crate::Decompressor]'s progress
+/// through a .qco file, from which decompression can resume in another
+/// process without replaying the file from its start.
+///
+/// Take one with
+/// [`Decompressor::snapshot`][crate::Decompressor::snapshot], persist it
+/// with [`to_bytes`][Self::to_bytes], and resume with
+/// [`Decompressor::from_snapshot`][crate::Decompressor::from_snapshot]
+/// by passing the file's bytes from
+/// [`resume_byte_idx`][Self::resume_byte_idx] onward.
+///
+/// The snapshot holds a copy of the file's header and its position.
+/// If it was taken partway through a chunk body, it holds the position of
+/// that chunk and how many of its numbers were already decompressed, so
+/// resuming decodes that one chunk up to the same point again.
+/// Corrupted sections found before the snapshot are not kept.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecompressorSnapshot {
+  // empty if the header hadn't been read yet
+  pub(crate) header_bytes: Vec<u8>,
+  pub(crate) resume_byte_idx: usize,
+  pub(crate) n_chunks: usize,
+  pub(crate) memory_used: usize,
+  pub(crate) terminated: bool,
+  // the number of numbers already decompressed from the chunk at the
+  // resume position, if the snapshot was taken within its body
+  pub(crate) n_decompressed_in_chunk: Option<usize>,
+}
+
+impl DecompressorSnapshot {
+  /// Returns the index of the byte in the .qco file where decompression
+  /// resumes.
+  /// [`Decompressor::from_snapshot`][crate::Decompressor::from_snapshot]
+  /// expects the file's bytes starting here.
+  pub fn resume_byte_idx(&self) -> usize {
+    self.resume_byte_idx
+  }
+
+  /// Returns the snapshot as bytes, which
+  /// [`from_bytes`][Self::from_bytes] can read back.
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    // these writes can't fail because the writer starts out aligned
+    writer.write_aligned_bytes(&MAGIC_SNAPSHOT_HEADER).unwrap();
+    writer.write_usize(self.header_bytes.len(), BITS_TO_ENCODE_SNAPSHOT_USIZE);
+    writer.write_aligned_bytes(&self.header_bytes).unwrap();
+    writer.write_usize(self.resume_byte_idx, BITS_TO_ENCODE_SNAPSHOT_USIZE);
+    writer.write_usize(self.n_chunks, BITS_TO_ENCODE_SNAPSHOT_USIZE);
+    writer.write_usize(self.memory_used, BITS_TO_ENCODE_SNAPSHOT_USIZE);
+    writer.write_one(self.terminated);
+    match self.n_decompressed_in_chunk {
+      Some(n) => {
+        writer.write_one(true);
+        writer.write_usize(n, BITS_TO_ENCODE_N_ENTRIES);
+      },
+      None => writer.write_one(false),
+    }
+    writer.drain_bytes()
+  }
+
+  /// Reads a snapshot written by [`to_bytes`][Self::to_bytes].
+  /// Will return an error if the bytes are corrupt or incomplete.
+  pub fn from_bytes(bytes: &[u8]) -> QCompressResult<Self> {
+    let words = BitWords::from(bytes);
+    let mut reader = BitReader::from(&words);
+    let magic = reader.read_aligned_bytes(MAGIC_SNAPSHOT_HEADER.len())?;
+    if magic != MAGIC_SNAPSHOT_HEADER {
+      return Err(QCompressError::corruption(format!(
+        "magic snapshot header does not match {:?}; instead found {:?}",
+        MAGIC_SNAPSHOT_HEADER,
+        magic,
+      )));
+    }
+    let header_size = reader.read_usize(BITS_TO_ENCODE_SNAPSHOT_USIZE)?;
+    if header_size > bytes.len() {
+      return Err(QCompressError::insufficient_data(format!(
+        "snapshot header of {} bytes is longer than the snapshot",
+        header_size,
+      )));
+    }
+    let header_bytes = reader.read_aligned_bytes(header_size)?;
+    let resume_byte_idx = reader.read_usize(BITS_TO_ENCODE_SNAPSHOT_USIZE)?;
+    let n_chunks = reader.read_usize(BITS_TO_ENCODE_SNAPSHOT_USIZE)?;
+    let memory_used = reader.read_usize(BITS_TO_ENCODE_SNAPSHOT_USIZE)?;
+    let terminated = reader.read_one()?;
+    let n_decompressed_in_chunk = if reader.read_one()? {
+      Some(reader.read_usize(BITS_TO_ENCODE_N_ENTRIES)?)
+    } else {
+      None
+    };
+    reader.drain_empty_byte(|| QCompressError::corruption(
+      "nonzero bits in end of final byte of snapshot"
+    ))?;
+    if header_bytes.is_empty() && (resume_byte_idx > 0 || n_chunks > 0) {
+      return Err(QCompressError::corruption(
+        "snapshot has progress past the header but no header"
+      ));
+    }
+    if n_decompressed_in_chunk.is_some() && (n_chunks == 0 || terminated) {
+      return Err(QCompressError::corruption(
+        "snapshot is within a chunk body but has no unfinished chunk"
+      ));
+    }
+    Ok(Self {
+      header_bytes,
+      resume_byte_idx,
+      n_chunks,
+      memory_used,
+      terminated,
+      n_decompressed_in_chunk,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{Compressor, CompressorConfig, DecompressedItem, Decompressor, DecompressorConfig};
+  use crate::errors::ErrorKind;
+  use super::*;
+
+  fn compress(nums: &[f64]) -> Vec<u8> {
+    let config = CompressorConfig::default()
+      .with_delta_encoding_order(2)
+      .with_use_chunk_checksums(true);
+    let mut compressor = Compressor::<f64>::from_config(config);
+    compressor.header().unwrap();
+    for chunk in nums.chunks(700) {
+      compressor.chunk(chunk).unwrap();
+    }
+    compressor.footer().unwrap();
+    compressor.drain_bytes()
+  }
+
+  fn numbers(decompressor: &mut Decompressor<f64>) -> Vec<f64> {
+    let mut res = Vec::new();
+    for item in decompressor {
+      if let DecompressedItem::Numbers(nums) = item.unwrap() {
+        res.extend(nums);
+      }
+    }
+    res
+  }
+
+  #[test]
+  fn test_resume_from_every_item() {
+    let nums = (0..2000).map(|i| (i as f64 * 0.1).sin()).collect::<Vec<_>>();
+    let bytes = compress(&nums);
+    let config = DecompressorConfig::default().with_numbers_limit_per_item(300);
+    let mut decompressor = Decompressor::<f64>::from_config(config.clone());
+    decompressor.extend_bytes(&bytes);
+
+    let mut n_decompressed = 0;
+    loop {
+      let snapshot = DecompressorSnapshot::from_bytes(&decompressor.snapshot().to_bytes()).unwrap();
+      assert_eq!(snapshot, decompressor.snapshot());
+      let mut resumed = Decompressor::<f64>::from_snapshot(
+        config.clone(),
+        &snapshot,
+        &bytes[snapshot.resume_byte_idx()..],
+      ).unwrap();
+      assert_eq!(numbers(&mut resumed), nums[n_decompressed..]);
+
+      match (&mut decompressor).next() {
+        Some(item) => if let DecompressedItem::Numbers(batch) = item.unwrap() {
+          n_decompressed += batch.len();
+        },
+        None => break,
+      }
+    }
+    assert_eq!(n_decompressed, nums.len());
+  }
+
+  #[test]
+  fn test_snapshot_errors() {
+    let nums = (0..1000).map(|i| i as f64).collect::<Vec<_>>();
+    let bytes = compress(&nums);
+    let mut decompressor = Decompressor::<f64>::from_config(
+      DecompressorConfig::default().with_numbers_limit_per_item(500)
+    );
+    decompressor.extend_bytes(&bytes);
+    for _ in 0..3 {
+      (&mut decompressor).next().unwrap().unwrap();
+    }
+    let snapshot = decompressor.snapshot();
+    assert_eq!(snapshot.n_decompressed_in_chunk, Some(500));
+
+    // the bytes must reach where the snapshot was taken
+    let err = Decompressor::<f64>::from_snapshot(
+      DecompressorConfig::default(),
+      &snapshot,
+      &bytes[snapshot.resume_byte_idx()..snapshot.resume_byte_idx() + 30],
+    ).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InsufficientData));
+
+    let snapshot_bytes = snapshot.to_bytes();
+    let err = DecompressorSnapshot::from_bytes(&snapshot_bytes[1..]).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Corruption));
+    let err = DecompressorSnapshot::from_bytes(&snapshot_bytes[..snapshot_bytes.len() - 1]).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InsufficientData));
+    let mut truncated_header = snapshot_bytes.clone();
+    truncated_header[4..12].copy_from_slice(&[0; 8]);
+    assert!(DecompressorSnapshot::from_bytes(&truncated_header).is_err());
+    assert!(DecompressorSnapshot::from_bytes(&[0; 3]).is_err());
+  }
+}
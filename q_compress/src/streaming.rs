@@ -0,0 +1,276 @@
+use std::io;
+use std::io::{Read, Write};
+
+use crate::{ChunkMetadata, Compressor, CompressorConfig, UserMetadata};
+use crate::compressor::DEFAULT_CHUNK_SIZE;
+use crate::data_types::NumberLike;
+use crate::errors::QCompressError;
+
+fn to_io_error(e: QCompressError) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+// fills as much of buf as possible, returning fewer bytes only at EOF
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+  let mut n_read = 0;
+  while n_read < buf.len() {
+    match reader.read(&mut buf[n_read..]) {
+      Ok(0) => break,
+      Ok(n) => n_read += n,
+      Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+      Err(e) => return Err(e),
+    }
+  }
+  Ok(n_read)
+}
+
+/// Compresses numbers from `reader` into a .qco file written to `writer`,
+/// returning the count of numbers compressed.
+///
+/// `reader` should contain the numbers back to back in their uncompressed
+/// representation (see [`NumberLike::to_bytes`]), e.g. big-endian bytes for
+/// integers and floats.
+/// Only one chunk of numbers and one chunk of compressed bytes are held in
+/// memory at a time, so this can compress data far larger than RAM.
+/// The output is the same as [`Compressor::simple_compress`] with the same
+/// config.
+/// Will return an error if reading or writing fails, `reader` ends partway
+/// through a number, or the numbers are invalid.
+pub fn copy_compress<T: NumberLike, R: Read, W: Write>(
+  mut reader: R,
+  writer: W,
+  config: &CompressorConfig,
+) -> io::Result<usize> {
+  let mut compressor = WriteCompressor::<T, W>::new(writer, config.clone())?;
+  compressor.header()?;
+
+  let bytes_per_num = T::PHYSICAL_BITS / 8;
+  let mut raw = vec![0_u8; DEFAULT_CHUNK_SIZE * bytes_per_num];
+  let mut nums = Vec::with_capacity(DEFAULT_CHUNK_SIZE);
+  let mut n = 0;
+  loop {
+    let n_raw = read_up_to(&mut reader, &mut raw)?;
+    if n_raw % bytes_per_num != 0 {
+      return Err(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        format!(
+          "input ended partway through a number; {} bytes is not a multiple of {}",
+          n + n_raw,
+          bytes_per_num,
+        ),
+      ));
+    }
+
+    nums.clear();
+    for num_bytes in raw[..n_raw].chunks_exact(bytes_per_num) {
+      nums.push(T::from_bytes(num_bytes.to_vec()).map_err(to_io_error)?);
+    }
+    if !nums.is_empty() {
+      compressor.chunk(&nums)?;
+      n += nums.len();
+    }
+
+    if n_raw < raw.len() {
+      break;
+    }
+  }
+
+  compressor.footer()?;
+  Ok(n)
+}
+
+/// A [`Compressor`] that writes its output to an [`io::Write`] sink as it
+/// goes, instead of accumulating it in memory until
+/// [`drain_bytes`][Compressor::drain_bytes].
+///
+/// Each call writes the bytes it produced to the sink before returning, so
+/// the compressor never holds more than one chunk's bytes, no matter how
+/// large the file grows.
+/// Errors from the compressor are returned as [`io::ErrorKind::InvalidData`]
+/// errors wrapping the [`QCompressError`].
+///
+/// ```
+/// use q_compress::{CompressorConfig, WriteCompressor};
+///
+/// let mut compressed = Vec::new();
+/// let mut compressor = WriteCompressor::<i64, _>::new(
+///   &mut compressed,
+///   CompressorConfig::default(),
+/// ).unwrap();
+/// compressor.header().unwrap();
+/// for chunk_idx in 0..3 {
+///   let nums = (0..1000).map(|i| chunk_idx * 1000 + i).collect::<Vec<_>>();
+///   compressor.chunk(&nums).unwrap();
+/// }
+/// compressor.footer().unwrap();
+/// let n_bytes = compressor.bytes_written();
+/// assert_eq!(compressed.len(), n_bytes);
+/// ```
+#[derive(Debug)]
+pub struct WriteCompressor<T: NumberLike, W: Write> {
+  compressor: Compressor<T>,
+  sink: W,
+  bytes_written: usize,
+}
+
+impl<T: NumberLike, W: Write> WriteCompressor<T, W> {
+  /// Creates a compressor writing to `sink`, given a [`CompressorConfig`].
+  /// Will return an error if the config is invalid.
+  pub fn new(sink: W, config: CompressorConfig) -> io::Result<Self> {
+    Ok(Self {
+      compressor: Compressor::try_from_config(config).map_err(to_io_error)?,
+      sink,
+      bytes_written: 0,
+    })
+  }
+
+  /// Returns a reference to the underlying compressor, e.g. to inspect its
+  /// [`flags`][Compressor::flags].
+  pub fn compressor(&self) -> &Compressor<T> {
+    &self.compressor
+  }
+
+  /// Returns a mutable reference to the underlying compressor, e.g. to
+  /// [set header metadata][Compressor::set_header_metadata] before writing
+  /// the header.
+  /// Any bytes written through it are only sent to the sink by the next call
+  /// on this `WriteCompressor`.
+  pub fn compressor_mut(&mut self) -> &mut Compressor<T> {
+    &mut self.compressor
+  }
+
+  /// Returns a reference to the sink.
+  pub fn get_ref(&self) -> &W {
+    &self.sink
+  }
+
+  /// Returns a mutable reference to the sink.
+  pub fn get_mut(&mut self) -> &mut W {
+    &mut self.sink
+  }
+
+  /// Returns the sink, dropping the compressor.
+  pub fn into_inner(self) -> W {
+    self.sink
+  }
+
+  /// Returns the count of bytes written to the sink so far.
+  pub fn bytes_written(&self) -> usize {
+    self.bytes_written
+  }
+
+  fn write_drained(&mut self) -> io::Result<()> {
+    let bytes = self.compressor.drain_bytes();
+    self.sink.write_all(&bytes)?;
+    self.bytes_written += bytes.len();
+    Ok(())
+  }
+
+  /// Writes the header to the sink, like [`Compressor::header`].
+  pub fn header(&mut self) -> io::Result<()> {
+    self.compressor.header().map_err(to_io_error)?;
+    self.write_drained()
+  }
+
+  /// Writes a chunk to the sink, like [`Compressor::chunk`].
+  pub fn chunk(&mut self, nums: &[T]) -> io::Result<ChunkMetadata<T>> {
+    self.chunk_with_metadata(nums, UserMetadata::new())
+  }
+
+  /// Writes a chunk to the sink, like [`Compressor::chunk_with_metadata`].
+  pub fn chunk_with_metadata(
+    &mut self,
+    nums: &[T],
+    metadata: UserMetadata,
+  ) -> io::Result<ChunkMetadata<T>> {
+    let meta = self.compressor.chunk_with_metadata(nums, metadata).map_err(to_io_error)?;
+    self.write_drained()?;
+    Ok(meta)
+  }
+
+  /// Writes the footer to the sink, like [`Compressor::footer`], and then
+  /// flushes the sink.
+  pub fn footer(&mut self) -> io::Result<()> {
+    self.compressor.footer().map_err(to_io_error)?;
+    self.write_drained()?;
+    self.sink.flush()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_copy_compress() {
+    let nums = (0..2_500_000_i32).map(|i| i % 1000 - 500).collect::<Vec<_>>();
+    let raw = nums.iter()
+      .flat_map(|x| x.to_be_bytes())
+      .collect::<Vec<_>>();
+    let config = CompressorConfig::default().with_use_file_checksum(true);
+    let mut compressed = Vec::new();
+    let n = copy_compress::<i32, _, _>(raw.as_slice(), &mut compressed, &config).unwrap();
+    assert_eq!(n, nums.len());
+    assert_eq!(compressed, Compressor::<i32>::from_config(config.clone()).simple_compress(&nums));
+
+    let mut compressed = Vec::new();
+    copy_compress::<i32, _, _>(io::empty(), &mut compressed, &config).unwrap();
+    assert_eq!(compressed, Compressor::<i32>::from_config(config.clone()).simple_compress(&[]));
+
+    let err = copy_compress::<i32, _, _>(&raw[..5], io::sink(), &config).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+  }
+
+  // counts the bytes written to it by each call, without storing them
+  #[derive(Default)]
+  struct CountingSink {
+    write_sizes: Vec<usize>,
+  }
+
+  impl Write for CountingSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      self.write_sizes.push(buf.len());
+      Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_write_compressor() {
+    let nums = (0..30000).map(|i| (i * 7) % 1000).collect::<Vec<i64>>();
+    let config = CompressorConfig::default().with_use_chunk_checksums(true);
+    let mut expected = Compressor::<i64>::from_config(config.clone());
+    expected.header().unwrap();
+
+    let mut compressor = WriteCompressor::<i64, _>::new(CountingSink::default(), config).unwrap();
+    compressor.header().unwrap();
+    assert_eq!(compressor.bytes_written(), expected.drain_bytes().len());
+    for chunk in nums.chunks(10000) {
+      let meta = compressor.chunk(chunk).unwrap();
+      assert_eq!(meta, expected.chunk(chunk).unwrap());
+      // each chunk reaches the sink as soon as it's compressed
+      let chunk_size = expected.drain_bytes().len();
+      assert_eq!(compressor.get_ref().write_sizes.last(), Some(&chunk_size));
+      assert_eq!(compressor.compressor_mut().byte_size(), 0);
+    }
+    compressor.footer().unwrap();
+    expected.footer().unwrap();
+    let sink = compressor.into_inner();
+    assert_eq!(sink.write_sizes.len(), 5);
+
+    let mut compressed = Vec::new();
+    let mut compressor = WriteCompressor::<i64, _>::new(&mut compressed, CompressorConfig::default()).unwrap();
+    let err = compressor.chunk(&nums).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    compressor.header().unwrap();
+    compressor.chunk(&nums).unwrap();
+    compressor.footer().unwrap();
+    assert_eq!(compressed, Compressor::<i64>::default().simple_compress(&nums));
+
+    let invalid = CompressorConfig::default().with_compression_level(100);
+    assert!(WriteCompressor::<i64, _>::new(io::sink(), invalid).is_err());
+  }
+}
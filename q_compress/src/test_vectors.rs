@@ -0,0 +1,498 @@
+//! Canonical .qco files for checking other implementations of the format.
+//!
+//! [`test_vectors`] returns a suite of small files covering each flag on its
+//! own, flags together, and edge cases like empty files and special float
+//! values, for every data type.
+//! Each comes with a JSON description of the file's flags, chunks, user
+//! metadata, and the exact numbers it decodes to, so a decoder written in
+//! another language can check itself against all of them mechanically.
+//! The `qcompress test-vectors` CLI command writes the suite to a
+//! directory.
+//!
+//! The JSON has these fields:
+//! * `name`: the vector's name, which is also its file stem.
+//! * `data_type` and `header_byte`: the data type, e.g. `"timestamp_micros"`
+//!   and its byte in the file's header.
+//! * `flags`: every field of the file's [`Flags`][crate::Flags].
+//! * `header_metadata`: the header's user metadata as a map from each key
+//!   to its value's bytes in hex.
+//! * `chunks`: each chunk's count of numbers `n` and user `metadata`.
+//! * `numbers`: the decoded numbers, in order.
+//!
+//! Numbers are JSON strings so that 64- and 128-bit values survive any JSON
+//! parser.
+//! Integers are in decimal, booleans are `"true"` or `"false"`, timestamps
+//! are the signed count of micro- or nanoseconds since the Unix epoch, and
+//! floats are in the shortest decimal form that parses back to the same
+//! value, or one of `"NaN"`, `"inf"`, and `"-inf"`.
+//!
+//! The files are compressed in deterministic mode, so the suite is the same
+//! on every platform.
+//! ```
+//! use q_compress::auto_decompress;
+//! use q_compress::test_vectors::test_vectors;
+//!
+//! for vector in test_vectors().expect("test vectors") {
+//!   if vector.name == "i64_delta_order_2" {
+//!     let nums = auto_decompress::<i64>(&vector.compressed).expect("decompress");
+//!     assert!(vector.description_json.contains(&format!("\"{}\"", nums[0])));
+//!   }
+//! }
+//! ```
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::{ChunkMetadata, Compressor, CompressorConfig, DecompressedItem, Decompressor, Flags, Tolerance, UserMetadata};
+use crate::data_types::{DataType, NumberLike, TimestampMicros, TimestampNanos};
+use crate::errors::QCompressResult;
+
+// the numbers of each nonempty vector are split into chunks of these sizes
+const CHUNK_SIZES: [usize; 2] = [200, 100];
+
+/// A canonical .qco file and the JSON description of what it decodes to.
+#[derive(Clone, Debug)]
+pub struct TestVector {
+  /// The vector's name, like `"f64_xor_encoding"`.
+  pub name: String,
+  /// The data type of the file.
+  pub data_type: DataType,
+  /// The .qco file's bytes.
+  pub compressed: Vec<u8>,
+  /// A JSON object describing the file, as documented in the
+  /// [module docs][self].
+  pub description_json: String,
+}
+
+struct Case {
+  name: &'static str,
+  config: CompressorConfig,
+  floats_only: bool,
+  shared_prefixes: bool,
+  user_metadata: bool,
+}
+
+impl Case {
+  fn new(name: &'static str, config: CompressorConfig) -> Self {
+    Case {
+      name,
+      config,
+      floats_only: false,
+      shared_prefixes: false,
+      user_metadata: false,
+    }
+  }
+
+  fn floats_only(mut self) -> Self {
+    self.floats_only = true;
+    self
+  }
+
+  fn with_shared_prefixes(mut self) -> Self {
+    self.shared_prefixes = true;
+    self
+  }
+
+  fn with_user_metadata(mut self) -> Self {
+    self.config.use_user_metadata = true;
+    self.user_metadata = true;
+    self
+  }
+}
+
+fn cases() -> Vec<Case> {
+  let base = CompressorConfig::default().with_deterministic(true);
+  let all_flags = base.clone()
+    .with_delta_encoding_order(1)
+    .with_delta_lag(2)
+    .with_use_chunk_checksums(true)
+    .with_use_file_checksum(true)
+    .with_use_chunk_delta_orders(true)
+    .with_use_raw_fallback(true)
+    .with_use_min_max(true)
+    .with_use_distinct_count(true);
+  vec![
+    Case::new("default", base.clone()),
+    Case::new("compression_level_0", base.clone().with_compression_level(0)),
+    Case::new("delta_order_1", base.clone().with_delta_encoding_order(1)),
+    Case::new("delta_order_2", base.clone().with_delta_encoding_order(2)),
+    Case::new("delta_order_7", base.clone().with_delta_encoding_order(7)),
+    Case::new("delta_lag_3", base.clone().with_delta_encoding_order(1).with_delta_lag(3)),
+    Case::new("no_gcds", base.clone().with_use_gcds(false)),
+    Case::new("chunk_checksums", base.clone().with_use_chunk_checksums(true)),
+    Case::new("file_checksum", base.clone().with_use_file_checksum(true)),
+    Case::new("chunk_delta_orders", base.clone().with_use_chunk_delta_orders(true)),
+    Case::new("raw_fallback", base.clone().with_use_raw_fallback(true)),
+    Case::new("min_max", base.clone().with_use_min_max(true)),
+    Case::new("distinct_count", base.clone().with_use_distinct_count(true)),
+    Case::new("affine_transforms", base.clone().with_use_affine_transforms(true)),
+    Case::new("shared_prefixes", base.clone()).with_shared_prefixes(),
+    Case::new("user_metadata", base.clone()).with_user_metadata(),
+    Case::new("decimal_multipliers", base.clone().with_use_decimal_multipliers(true)).floats_only(),
+    Case::new("xor_encoding", base.clone().with_use_xor_encoding(true)).floats_only(),
+    Case::new("float_split", base.clone().with_use_float_split_encoding(true)).floats_only(),
+    Case::new(
+      "tolerance",
+      base.clone().with_tolerance(Some(Tolerance::Absolute(0.05))),
+    ).floats_only(),
+    Case::new("all_flags", all_flags.clone())
+      .with_shared_prefixes()
+      .with_user_metadata(),
+    Case::new(
+      "all_float_flags",
+      all_flags
+        .with_use_decimal_multipliers(true)
+        .with_use_xor_encoding(true)
+        .with_use_float_split_encoding(true),
+    ).floats_only().with_user_metadata(),
+  ]
+}
+
+fn data_type_name(data_type: DataType) -> &'static str {
+  match data_type {
+    DataType::Bool => "bool",
+    DataType::F32 => "f32",
+    DataType::F64 => "f64",
+    DataType::I16 => "i16",
+    DataType::I32 => "i32",
+    DataType::I64 => "i64",
+    DataType::I128 => "i128",
+    DataType::TimestampMicros => "timestamp_micros",
+    DataType::TimestampMicros96 => "timestamp_micros_96",
+    DataType::TimestampNanos => "timestamp_nanos",
+    DataType::TimestampNanos96 => "timestamp_nanos_96",
+    DataType::U16 => "u16",
+    DataType::U32 => "u32",
+    DataType::U64 => "u64",
+    DataType::U128 => "u128",
+  }
+}
+
+fn number_json<T: NumberLike>(x: T, data_type: DataType) -> String {
+  match data_type {
+    // Debug prints the shortest representation that round trips
+    DataType::F32 | DataType::F64 => format!("\"{:?}\"", x),
+    DataType::TimestampMicros | DataType::TimestampMicros96 |
+    DataType::TimestampNanos | DataType::TimestampNanos96 => format!("\"{}\"", x.to_signed()),
+    _ => format!("\"{}\"", x),
+  }
+}
+
+fn user_metadata_json(metadata: &UserMetadata) -> String {
+  let entries = metadata.iter()
+    .map(|(key, value)| {
+      let mut hex = String::with_capacity(2 * value.len());
+      for byte in value {
+        write!(hex, "{:02x}", byte).unwrap();
+      }
+      format!("\"{}\": \"{}\"", key, hex)
+    })
+    .collect::<Vec<_>>();
+  format!("{{{}}}", entries.join(", "))
+}
+
+fn flags_json(flags: &Flags) -> String {
+  let fields = [
+    ("use_5_bit_code_len", format!("{}", flags.use_5_bit_code_len)),
+    ("delta_encoding_order", format!("{}", flags.delta_encoding_order)),
+    ("use_min_count_encoding", format!("{}", flags.use_min_count_encoding)),
+    ("use_gcds", format!("{}", flags.use_gcds)),
+    ("use_chunk_checksums", format!("{}", flags.use_chunk_checksums)),
+    ("use_file_checksum", format!("{}", flags.use_file_checksum)),
+    ("use_decimal_multipliers", format!("{}", flags.use_decimal_multipliers)),
+    ("use_xor_encoding", format!("{}", flags.use_xor_encoding)),
+    ("use_chunk_delta_orders", format!("{}", flags.use_chunk_delta_orders)),
+    ("delta_lag", format!("{}", flags.delta_lag)),
+    ("use_quantization", format!("{}", flags.use_quantization)),
+    ("use_raw_fallback", format!("{}", flags.use_raw_fallback)),
+    ("use_shared_prefixes", format!("{}", flags.use_shared_prefixes)),
+    ("use_user_metadata", format!("{}", flags.use_user_metadata)),
+    ("use_float_split", format!("{}", flags.use_float_split)),
+    ("use_min_max", format!("{}", flags.use_min_max)),
+    ("use_distinct_count", format!("{}", flags.use_distinct_count)),
+    ("use_nan_positions", format!("{}", flags.use_nan_positions)),
+    ("use_affine_transforms", format!("{}", flags.use_affine_transforms)),
+    ("canonicalize_negative_zero", format!("{}", flags.canonicalize_negative_zero)),
+    ("flush_subnormals", format!("{}", flags.flush_subnormals)),
+  ];
+  let entries = fields.iter()
+    .map(|(name, value)| format!("\"{}\": {}", name, value))
+    .collect::<Vec<_>>();
+  format!("{{{}}}", entries.join(", "))
+}
+
+fn description_json<T: NumberLike>(
+  name: &str,
+  data_type: DataType,
+  flags: &Flags,
+  header_metadata: &UserMetadata,
+  chunk_metas: &[ChunkMetadata<T>],
+  nums: &[T],
+) -> String {
+  let chunks = chunk_metas.iter()
+    .map(|meta| format!(
+      "{{\"n\": {}, \"metadata\": {}}}",
+      meta.n,
+      user_metadata_json(&meta.user_metadata),
+    ))
+    .collect::<Vec<_>>();
+  let nums = nums.iter()
+    .map(|&x| number_json(x, data_type))
+    .collect::<Vec<_>>();
+  format!(
+    "{{\n  \"name\": \"{}\",\n  \"data_type\": \"{}\",\n  \"header_byte\": {},\n  \"flags\": {},\n  \"header_metadata\": {},\n  \"chunks\": [{}],\n  \"numbers\": [{}]\n}}\n",
+    name,
+    data_type_name(data_type),
+    data_type.header_byte(),
+    flags_json(flags),
+    user_metadata_json(header_metadata),
+    chunks.join(", "),
+    nums.join(", "),
+  )
+}
+
+fn vector<T: NumberLike>(
+  name: String,
+  case: &Case,
+  chunks: &[&[T]],
+) -> QCompressResult<TestVector> {
+  let data_type = DataType::of::<T>()?;
+  let mut compressor = Compressor::<T>::try_from_config(case.config.clone())?;
+  if case.shared_prefixes {
+    compressor.train_shared_prefixes(&chunks.concat())?;
+  }
+  let mut header_metadata = UserMetadata::new();
+  if case.user_metadata {
+    header_metadata.insert(String::from("name"), name.clone().into_bytes());
+    compressor.set_header_metadata(header_metadata.clone())?;
+  }
+  compressor.header()?;
+  let mut chunk_metas = Vec::with_capacity(chunks.len());
+  for (chunk_idx, &chunk) in chunks.iter().enumerate() {
+    let mut chunk_metadata = UserMetadata::new();
+    if case.user_metadata {
+      chunk_metadata.insert(String::from("chunk"), vec![chunk_idx as u8]);
+    }
+    chunk_metas.push(compressor.chunk_with_metadata(chunk, chunk_metadata)?);
+  }
+  compressor.footer()?;
+  let compressed = compressor.drain_bytes();
+
+  // the expected numbers differ from the originals when lossy
+  let mut decompressor = Decompressor::<T>::default();
+  decompressor.extend_bytes(&compressed);
+  let mut nums = Vec::new();
+  for item in &mut decompressor {
+    if let DecompressedItem::Numbers(batch) = item? {
+      nums.extend(batch);
+    }
+  }
+
+  let description_json = description_json(
+    &name,
+    data_type,
+    compressor.flags(),
+    &header_metadata,
+    &chunk_metas,
+    &nums,
+  );
+  Ok(TestVector {
+    name,
+    data_type,
+    compressed,
+    description_json,
+  })
+}
+
+fn push_vectors<T: NumberLike>(
+  nums: &[T],
+  is_float: bool,
+  res: &mut Vec<TestVector>,
+) -> QCompressResult<()> {
+  let type_name = data_type_name(DataType::of::<T>()?);
+  let (first, second) = nums.split_at(CHUNK_SIZES[0]);
+  for case in cases() {
+    if case.floats_only && !is_float {
+      continue;
+    }
+    let name = format!("{}_{}", type_name, case.name);
+    res.push(vector(name, &case, &[first, second])?);
+  }
+
+  let default_case = Case::new("", CompressorConfig::default().with_deterministic(true));
+  res.push(vector::<T>(format!("{}_empty", type_name), &default_case, &[])?);
+  res.push(vector(format!("{}_single_number", type_name), &default_case, &[&nums[..1]])?);
+  Ok(())
+}
+
+// a trend plus deterministic noise, in [-500, 1400)
+fn base_sequence() -> Vec<i64> {
+  let n = CHUNK_SIZES.iter().sum::<usize>() as i64;
+  (0..n).map(|i| (i * 7919) % 1009 - 500 + 3 * i).collect()
+}
+
+fn float_specials() -> Vec<f64> {
+  vec![
+    0.0,
+    -0.0,
+    1.0,
+    -1.5,
+    f64::INFINITY,
+    f64::NEG_INFINITY,
+    f64::NAN,
+    f64::MIN_POSITIVE,
+    f64::from_bits(1),
+    f64::MAX,
+    f64::MIN,
+    1.0e-300,
+  ]
+}
+
+fn push_float_vectors<T: NumberLike>(
+  nums: &[T],
+  specials: &[T],
+  res: &mut Vec<TestVector>,
+) -> QCompressResult<()> {
+  push_vectors(nums, true, res)?;
+  let type_name = data_type_name(DataType::of::<T>()?);
+  for (case_name, config) in [
+    ("specials", CompressorConfig::default()),
+    ("specials_xor_encoding", CompressorConfig::default().with_use_xor_encoding(true)),
+    ("specials_nan_positions", CompressorConfig::default().with_use_nan_positions(true)),
+    (
+      "specials_canonicalized",
+      CompressorConfig::default()
+        .with_canonicalize_negative_zero(true)
+        .with_flush_subnormals(true),
+    ),
+  ] {
+    let case = Case::new(case_name, config.with_deterministic(true));
+    let name = format!("{}_{}", type_name, case_name);
+    res.push(vector(name, &case, &[specials])?);
+  }
+  Ok(())
+}
+
+/// Returns the suite of canonical test vectors.
+///
+/// The suite only grows over time: a vector, once added, keeps its name and
+/// numbers.
+/// Vectors for 128-bit integers and 96-bit timestamps are only included
+/// with the `timestamps_96` feature.
+/// Will return an error only if this crate fails to compress or decompress
+/// its own data, which would be a bug.
+pub fn test_vectors() -> QCompressResult<Vec<TestVector>> {
+  let base = base_sequence();
+  let mut res = Vec::new();
+
+  let bools = base.iter().map(|&x| x % 3 == 0 || x > 800).collect::<Vec<_>>();
+  push_vectors(&bools, false, &mut res)?;
+
+  let decimals = base.iter().map(|&x| x as f64 / 100.0).collect::<Vec<_>>();
+  let specials = float_specials();
+  push_float_vectors(
+    &decimals.iter().map(|&x| x as f32).collect::<Vec<_>>(),
+    &specials.iter().map(|&x| x as f32).collect::<Vec<_>>(),
+    &mut res,
+  )?;
+  push_float_vectors(&decimals, &specials, &mut res)?;
+
+  push_vectors(&base.iter().map(|&x| x as i16).collect::<Vec<_>>(), false, &mut res)?;
+  push_vectors(&base.iter().map(|&x| x as i32).collect::<Vec<_>>(), false, &mut res)?;
+  push_vectors(&base, false, &mut res)?;
+
+  // microseconds into November 2023
+  let micros = base.iter()
+    .enumerate()
+    .map(|(i, &x)| 1_700_000_000_000_000 + 1_000_000 * i as i64 + x)
+    .collect::<Vec<_>>();
+  push_vectors(
+    &micros.iter().map(|&t| TimestampMicros::new(t)).collect::<Vec<_>>(),
+    false,
+    &mut res,
+  )?;
+  push_vectors(
+    &micros.iter().map(|&t| TimestampNanos::new(1000 * t)).collect::<Vec<_>>(),
+    false,
+    &mut res,
+  )?;
+
+  let unsigneds = base.iter().map(|&x| (x + 500) as u64).collect::<Vec<_>>();
+  push_vectors(&unsigneds.iter().map(|&x| x as u16).collect::<Vec<_>>(), false, &mut res)?;
+  push_vectors(&unsigneds.iter().map(|&x| x as u32).collect::<Vec<_>>(), false, &mut res)?;
+  push_vectors(&unsigneds, false, &mut res)?;
+
+  #[cfg(feature = "timestamps_96")]
+  {
+    use crate::data_types::{TimestampMicros96, TimestampNanos96};
+
+    push_vectors(&base.iter().map(|&x| x as i128).collect::<Vec<_>>(), false, &mut res)?;
+    push_vectors(&unsigneds.iter().map(|&x| x as u128).collect::<Vec<_>>(), false, &mut res)?;
+    push_vectors(
+      &micros.iter().map(|&t| TimestampMicros96::new(t as i128)).collect::<QCompressResult<Vec<_>>>()?,
+      false,
+      &mut res,
+    )?;
+    push_vectors(
+      &micros.iter().map(|&t| TimestampNanos96::new(1000 * t as i128)).collect::<QCompressResult<Vec<_>>>()?,
+      false,
+      &mut res,
+    )?;
+  }
+
+  Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+  use alloc::collections::BTreeSet;
+
+  use super::*;
+
+  #[test]
+  fn test_vectors_are_distinct_and_decode() {
+    let vectors = test_vectors().unwrap();
+    let names = vectors.iter().map(|v| v.name.as_str()).collect::<BTreeSet<_>>();
+    assert_eq!(names.len(), vectors.len());
+    assert!(names.contains("u32_all_flags"));
+    assert!(names.contains("f64_specials"));
+    assert!(names.contains("timestamp_nanos_empty"));
+    for vector in &vectors {
+      assert_eq!(DataType::from_file_bytes(&vector.compressed).unwrap(), vector.data_type);
+      assert!(vector.description_json.contains(&format!("\"name\": \"{}\"", vector.name)));
+    }
+  }
+
+  #[test]
+  fn test_vectors_match_originals() {
+    let vectors = test_vectors().unwrap();
+    let find = |name: &str| vectors.iter().find(|v| v.name == name).unwrap();
+
+    let nums = crate::auto_decompress::<i64>(&find("i64_all_flags").compressed).unwrap();
+    assert_eq!(nums, base_sequence());
+    let description = &find("i64_all_flags").description_json;
+    assert!(description.contains("\"delta_lag\": 2"));
+    assert!(description.contains("\"header_metadata\": {\"name\": \"6936345f616c6c5f666c616773\"}"));
+    assert!(description.contains("{\"n\": 100, \"metadata\": {\"chunk\": \"01\"}}"));
+    assert!(description.contains("\"numbers\": [\"-500\", \"359\","));
+
+    let description = &find("f64_specials").description_json;
+    assert!(description.contains(
+      "\"numbers\": [\"0.0\", \"-0.0\", \"1.0\", \"-1.5\", \"inf\", \"-inf\", \"NaN\", \"2.2250738585072014e-308\", \"5e-324\","
+    ));
+    let description = &find("f64_specials_canonicalized").description_json;
+    assert!(description.contains("\"canonicalize_negative_zero\": true"));
+    assert!(description.contains(
+      "\"numbers\": [\"0.0\", \"0.0\", \"1.0\", \"-1.5\", \"inf\", \"-inf\", \"NaN\", \"2.2250738585072014e-308\", \"0.0\","
+    ));
+    let description = &find("timestamp_micros_single_number").description_json;
+    assert!(description.contains("\"chunks\": [{\"n\": 1, \"metadata\": {}}]"));
+    assert!(description.contains("\"numbers\": [\"1699999999999500\"]"));
+    let description = &find("bool_empty").description_json;
+    assert!(description.contains("\"chunks\": [],"));
+    assert!(description.contains("\"numbers\": []"));
+  }
+}
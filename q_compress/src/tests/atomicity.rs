@@ -1,11 +1,22 @@
 use std::io::Write;
-use crate::{Compressor, Decompressor};
+use crate::{Compressor, CompressorConfig, Decompressor};
 use crate::errors::ErrorKind;
 
 #[test]
 fn test_errors_do_not_mutate_decompressor() {
+  assert_errors_do_not_mutate_decompressor(CompressorConfig::default());
+}
+
+#[test]
+fn test_errors_do_not_mutate_decompressor_with_checksums() {
+  assert_errors_do_not_mutate_decompressor(
+    CompressorConfig::default().with_use_chunk_checksums(true)
+  );
+}
+
+fn assert_errors_do_not_mutate_decompressor(config: CompressorConfig) {
   let nums = vec![1, 2, 3, 4, 5];
-  let mut compressor = Compressor::default();
+  let mut compressor = Compressor::from_config(config);
   let compressed = compressor.simple_compress(&nums);
   let mut decompressor = Decompressor::<i32>::default();
 
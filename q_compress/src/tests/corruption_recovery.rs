@@ -0,0 +1,87 @@
+use std::io::Write;
+use crate::{Compressor, CompressorConfig, Decompressor, DecompressorConfig};
+use crate::errors::ErrorKind;
+
+// returns the compressed bytes and the byte index where each chunk starts
+fn compress_chunks(numss: &[Vec<i64>]) -> (Vec<u8>, Vec<usize>) {
+  let mut compressor = Compressor::<i64>::from_config(
+    CompressorConfig::default().with_use_chunk_checksums(true)
+  );
+  compressor.header().unwrap();
+  let mut bytes = compressor.drain_bytes();
+  let mut chunk_starts = Vec::new();
+  for nums in numss {
+    chunk_starts.push(bytes.len());
+    compressor.chunk(nums).unwrap();
+    bytes.extend(compressor.drain_bytes());
+  }
+  compressor.footer().unwrap();
+  bytes.extend(compressor.drain_bytes());
+  (bytes, chunk_starts)
+}
+
+fn example_chunks() -> Vec<Vec<i64>> {
+  (0..3)
+    .map(|chunk_idx| (0..100).map(|i| chunk_idx * 1000 + (i * i) % 37).collect())
+    .collect()
+}
+
+fn recovering_decompressor(bytes: &[u8]) -> Decompressor<i64> {
+  let mut decompressor = Decompressor::<i64>::from_config(
+    DecompressorConfig::default().with_recover_on_corruption(true)
+  );
+  decompressor.write_all(bytes).unwrap();
+  decompressor
+}
+
+#[test]
+fn test_recovers_from_corrupted_body() {
+  let numss = example_chunks();
+  let (mut bytes, chunk_starts) = compress_chunks(&numss);
+  // flip a bit in the last byte of the middle chunk's body
+  bytes[chunk_starts[2] - 5] ^= 1;
+
+  let mut decompressor = Decompressor::<i64>::default();
+  decompressor.write_all(&bytes).unwrap();
+  let err = decompressor.simple_decompress().unwrap_err();
+  assert!(matches!(err.kind, ErrorKind::Corruption));
+
+  let mut decompressor = recovering_decompressor(&bytes);
+  let nums = decompressor.simple_decompress().unwrap();
+  assert_eq!(nums, [numss[0].clone(), numss[2].clone()].concat());
+  let sections = decompressor.corrupted_sections();
+  assert_eq!(sections.len(), 1);
+  assert_eq!(sections[0].num_idx, 100);
+  assert_eq!(sections[0].n_lost, Some(100));
+  assert!(matches!(sections[0].error.kind, ErrorKind::Corruption));
+}
+
+#[test]
+fn test_recovers_from_corrupted_metadata() {
+  let numss = example_chunks();
+  let (mut bytes, chunk_starts) = compress_chunks(&numss);
+  bytes[chunk_starts[1]] = 0;
+
+  let mut decompressor = recovering_decompressor(&bytes);
+  let mut dst = vec![0; 300];
+  let n = decompressor.decompress_into(&mut dst).unwrap();
+  assert_eq!(&dst[..n], &[numss[0].clone(), numss[2].clone()].concat());
+  let sections = decompressor.corrupted_sections();
+  assert_eq!(sections.len(), 1);
+  assert_eq!(sections[0].num_idx, 100);
+  assert_eq!(sections[0].n_lost, None);
+}
+
+#[test]
+fn test_recovers_from_truncation() {
+  let numss = example_chunks();
+  let (bytes, chunk_starts) = compress_chunks(&numss);
+
+  let mut decompressor = recovering_decompressor(&bytes[..chunk_starts[2] + 10]);
+  let nums = decompressor.simple_decompress().unwrap();
+  assert_eq!(nums, [numss[0].clone(), numss[1].clone()].concat());
+  let sections = decompressor.corrupted_sections();
+  assert_eq!(sections.len(), 1);
+  assert_eq!(sections[0].num_idx, 200);
+  assert!(matches!(sections[0].error.kind, ErrorKind::InsufficientData));
+}
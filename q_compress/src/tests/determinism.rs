@@ -0,0 +1,72 @@
+use crate::{Compressor, CompressorConfig};
+use crate::checksum::crc32;
+use crate::data_types::NumberLike;
+
+// a fixed pseudorandom sequence, so these tests don't depend on any RNG
+// crate's implementation
+fn lcg_sequence(n: usize) -> Vec<u64> {
+  let mut state = 12345_u64;
+  (0..n)
+    .map(|_| {
+      state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+      state >> 11
+    })
+    .collect()
+}
+
+fn deterministic_bytes<T: NumberLike>(nums: &[T], config: CompressorConfig) -> Vec<u8> {
+  let mut compressor = Compressor::<T>::from_config(config.with_deterministic(true));
+  let bytes = compressor.simple_compress(nums);
+  assert_eq!(crate::auto_decompress::<T>(&bytes).unwrap(), nums);
+  bytes
+}
+
+// These checksums pin down the exact bytes of deterministic compression.
+// They must never change, on any platform or in any patch version; if one
+// of these fails, deterministic output has been broken.
+#[test]
+fn test_deterministic_output() {
+  let seq = lcg_sequence(30000);
+
+  let skewed = seq.iter()
+    .map(|&x| {
+      let r = x % 1000;
+      if r < 500 { 7 } else { (r * r) as i64 - 1000 * (x % 7) as i64 }
+    })
+    .collect::<Vec<_>>();
+  let bytes = deterministic_bytes(&skewed, CompressorConfig::default());
+  assert_eq!(crc32(&bytes), 1424995110);
+
+  let walk = seq.iter()
+    .scan(0.0_f64, |acc, &x| {
+      *acc += (x % 2001) as f64 / 100.0 - 10.0;
+      Some(*acc)
+    })
+    .collect::<Vec<_>>();
+  let config = CompressorConfig::default()
+    .with_compression_level(10)
+    .with_use_chunk_delta_orders(true)
+    .with_use_xor_encoding(true);
+  let bytes = deterministic_bytes(&walk, config);
+  assert_eq!(crc32(&bytes), 1857264944);
+
+  let multiples = seq.iter()
+    .map(|&x| ((x % 50000) * 30) as u32)
+    .collect::<Vec<_>>();
+  let config = CompressorConfig::default().with_compression_level(12);
+  let bytes = deterministic_bytes(&multiples, config);
+  assert_eq!(crc32(&bytes), 1287422545);
+}
+
+#[test]
+fn test_deterministic_compression_ratio() {
+  let nums = lcg_sequence(10000).iter()
+    .map(|&x| (x % 100) as i32 * (x % 3) as i32)
+    .collect::<Vec<_>>();
+  for level in [0, 4, 8, 12] {
+    let config = CompressorConfig::default().with_compression_level(level);
+    let deterministic_size = deterministic_bytes(&nums, config.clone()).len();
+    let size = Compressor::<i32>::from_config(config).simple_compress(&nums).len();
+    assert!(deterministic_size as f64 <= 1.01 * size as f64, "{}", level);
+  }
+}
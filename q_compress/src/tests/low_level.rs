@@ -1,8 +1,8 @@
 use std::io::Write;
-use crate::{Compressor, CompressorConfig, DecompressedItem, Decompressor};
+use crate::{Compressor, CompressorConfig, DecompressedItem, Decompressor, DistinctCount, PrefixMetadata, Tolerance, UserMetadata};
 use crate::data_types::NumberLike;
 use crate::decompressor::DecompressorConfig;
-use crate::errors::ErrorKind;
+use crate::errors::{ErrorKind, Section};
 
 #[test]
 fn test_low_level_short() {
@@ -28,11 +28,594 @@ fn test_low_level_sparse() {
   assert_lowest_level_behavior(vec![nums]);
 }
 
+#[test]
+fn test_chunk_body_into_small_dst() {
+  let mut compressor = Compressor::<i32>::default();
+  let compressed = compressor.simple_compress(&[1, 2, 3]);
+  let mut decompressor = Decompressor::<i32>::default();
+  decompressor.write_all(&compressed).unwrap();
+  decompressor.header().unwrap();
+  decompressor.chunk_metadata().unwrap();
+  let mut dst = [0; 2];
+  let err = decompressor.chunk_body_into(&mut dst).unwrap_err();
+  assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+  let mut dst = [0; 3];
+  assert_eq!(decompressor.chunk_body_into(&mut dst).unwrap(), 3);
+  assert_eq!(dst, [1, 2, 3]);
+}
+
+#[test]
+fn test_chunk_checksum_detects_corruption() {
+  let nums = (0..1000).map(|i| (i * i) % 777).collect::<Vec<i64>>();
+  let mut compressor = Compressor::<i64>::from_config(
+    CompressorConfig::default().with_use_chunk_checksums(true)
+  );
+  let mut bytes = compressor.simple_compress(&nums);
+  let mut decompressor = Decompressor::<i64>::default();
+  decompressor.write_all(&bytes).unwrap();
+  assert_eq!(decompressor.simple_decompress().unwrap(), nums);
+
+  // flip a bit in the last byte of the chunk body, right before the checksum
+  let body_end_idx = bytes.len() - 1 - 4;
+  bytes[body_end_idx - 1] ^= 1;
+  let mut decompressor = Decompressor::<i64>::default();
+  decompressor.write_all(&bytes).unwrap();
+  let err = decompressor.simple_decompress().unwrap_err();
+  assert!(matches!(err.kind, ErrorKind::Corruption));
+}
+
+#[test]
+fn test_error_position() {
+  let mut compressor = Compressor::<i64>::from_config(
+    CompressorConfig::default().with_use_chunk_checksums(true)
+  );
+  compressor.header().unwrap();
+  compressor.chunk(&(0..100).collect::<Vec<_>>()).unwrap();
+  let mut bytes = compressor.drain_bytes();
+  let chunk_1_start = bytes.len();
+  compressor.chunk(&(0..100).map(|i| i * i).collect::<Vec<_>>()).unwrap();
+  compressor.footer().unwrap();
+  bytes.extend(compressor.drain_bytes());
+
+  // flip a bit in the last chunk's body, right before its checksum
+  let body_end_idx = bytes.len() - 1 - 4;
+  bytes[body_end_idx - 1] ^= 1;
+  let mut decompressor = Decompressor::<i64>::default();
+  decompressor.write_all(&bytes).unwrap();
+  decompressor.header().unwrap();
+  decompressor.chunk_metadata().unwrap();
+  decompressor.chunk_body().unwrap();
+  // positions still count from the start of the file after freeing memory
+  decompressor.free_compressed_memory();
+  decompressor.chunk_metadata().unwrap();
+  let err = decompressor.chunk_body().unwrap_err();
+  assert!(matches!(err.kind, ErrorKind::Corruption));
+  let position = err.position.clone().unwrap();
+  assert_eq!(position.section, Section::ChunkBody);
+  assert_eq!(position.chunk_idx, Some(1));
+  assert!(position.byte_idx() > chunk_1_start && position.byte_idx() <= bytes.len());
+  assert!(err.to_string().contains("reading chunk 1 body at byte"));
+
+  let mut decompressor = Decompressor::<i64>::default();
+  decompressor.write_all(&bytes[..2]).unwrap();
+  let err = decompressor.header().unwrap_err();
+  assert_eq!(err.position.unwrap().section, Section::Header);
+}
+
+#[test]
+fn test_config_validation() {
+  let invalid_configs = [
+    CompressorConfig::default().with_compression_level(13),
+    CompressorConfig::default().with_run_len_min_frequency(0.0),
+    CompressorConfig::default().with_run_len_min_frequency(f64::NAN),
+    CompressorConfig::default().with_run_len_jumpstart(Some(25)),
+    CompressorConfig::default().with_delta_encoding_order(8),
+    CompressorConfig::default().with_delta_lag(0),
+    CompressorConfig::default().with_tolerance(Some(Tolerance::Absolute(-1.0))),
+  ];
+  for config in invalid_configs {
+    let err = Compressor::<f64>::try_from_config(config.clone()).err().unwrap();
+    assert!(matches!(err.kind, ErrorKind::InvalidArgument), "{:?}", config);
+  }
+  let config = CompressorConfig::default()
+    .with_compression_level(12)
+    .with_delta_encoding_order(7)
+    .with_delta_lag(65535);
+  assert!(Compressor::<f64>::try_from_config(config).is_ok());
+
+  let config = DecompressorConfig::default().with_numbers_limit_per_item(0);
+  let err = Decompressor::<f64>::try_from_config(config).err().unwrap();
+  assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+  assert!(Decompressor::<f64>::try_from_config(DecompressorConfig::default()).is_ok());
+}
+
+#[test]
+fn test_memory_limits() {
+  let mut compressor = Compressor::<i64>::default();
+  compressor.header().unwrap();
+  for _ in 0..3 {
+    compressor.chunk(&(0..1000).collect::<Vec<_>>()).unwrap();
+  }
+  compressor.footer().unwrap();
+  let bytes = compressor.drain_bytes();
+
+  let decompress = |config: DecompressorConfig| {
+    let mut decompressor = Decompressor::<i64>::from_config(config);
+    decompressor.write_all(&bytes).unwrap();
+    decompressor.simple_decompress()
+  };
+  assert_eq!(decompress(DecompressorConfig::default()).unwrap().len(), 3000);
+  let err = decompress(DecompressorConfig::default().with_max_n_per_chunk(999)).unwrap_err();
+  assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+  assert_eq!(err.position.unwrap().chunk_idx, Some(0));
+
+  // the third chunk's numbers don't fit
+  let config = DecompressorConfig::default().with_max_total_memory(2500 * 8);
+  let err = decompress(config).unwrap_err();
+  assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+  assert_eq!(err.position.unwrap().chunk_idx, Some(2));
+  let config = DecompressorConfig::default().with_max_total_memory(3500 * 8);
+  assert_eq!(decompress(config).unwrap().len(), 3000);
+
+  // the limit applies to streaming too
+  let mut decompressor = Decompressor::<i64>::from_config(
+    DecompressorConfig::default().with_max_total_memory(1500 * 8)
+  );
+  decompressor.write_all(&bytes).unwrap();
+  // errors leave the state unchanged, so we stop at the first one
+  let err = (&mut decompressor).find_map(|item| item.err()).unwrap();
+  assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+}
+
+#[test]
+fn test_verify_file_checksum() {
+  let mut compressor = Compressor::<i64>::from_config(
+    CompressorConfig::default().with_use_file_checksum(true)
+  );
+  // drain between chunks to check that drained bytes are still checksummed
+  compressor.header().unwrap();
+  compressor.chunk(&[1, 2, 3]).unwrap();
+  let mut bytes = compressor.drain_bytes();
+  compressor.chunk(&[4, 5, 6]).unwrap();
+  compressor.footer().unwrap();
+  bytes.extend(compressor.drain_bytes());
+
+  let mut decompressor = Decompressor::<i64>::default();
+  decompressor.write_all(&bytes).unwrap();
+  decompressor.verify().unwrap();
+  assert_eq!(decompressor.simple_decompress().unwrap(), vec![1, 2, 3, 4, 5, 6]);
+
+  let mut corrupted = bytes.clone();
+  corrupted[bytes.len() / 2] ^= 1;
+  let mut decompressor = Decompressor::<i64>::default();
+  decompressor.write_all(&corrupted).unwrap();
+  let err = decompressor.verify().unwrap_err();
+  assert!(matches!(err.kind, ErrorKind::Corruption));
+
+  let mut decompressor = Decompressor::<i64>::default();
+  decompressor.write_all(&bytes[..bytes.len() - 1]).unwrap();
+  let err = decompressor.verify().unwrap_err();
+  assert!(matches!(err.kind, ErrorKind::InsufficientData));
+
+  let bytes = Compressor::<i64>::default().simple_compress(&[1, 2, 3]);
+  let mut decompressor = Decompressor::<i64>::default();
+  decompressor.write_all(&bytes).unwrap();
+  let err = decompressor.verify().unwrap_err();
+  assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+}
+
+#[test]
+fn test_progress() {
+  let nums = vec![true; 2_500_000];
+  let mut compressor = Compressor::<bool>::default();
+  let mut compress_updates = Vec::new();
+  let bytes = compressor.simple_compress_with_progress(
+    &nums,
+    |update| compress_updates.push(update),
+  );
+  assert_eq!(
+    compress_updates.iter().map(|update| update.n_numbers).collect::<Vec<_>>(),
+    vec![1_000_000, 2_000_000, 2_500_000],
+  );
+  assert_eq!(compress_updates[2].n_chunks, 3);
+
+  let mut decompressor = Decompressor::<bool>::default();
+  decompressor.write_all(&bytes).unwrap();
+  let mut decompress_updates = Vec::new();
+  let decompressed = decompressor.simple_decompress_with_progress(
+    |update| decompress_updates.push(update),
+  ).unwrap();
+  assert_eq!(decompressed, nums);
+  assert_eq!(decompress_updates, compress_updates);
+}
+
+#[test]
+fn test_compress_from_iter() {
+  let nums = (0..2_500_000).map(|i| i % 3 == 0).collect::<Vec<_>>();
+  let expected = Compressor::<bool>::default().simple_compress(&nums);
+  let bytes = Compressor::<bool>::default().compress_from_iter(nums.iter().cloned());
+  assert_eq!(bytes, expected);
+
+  let expected = Compressor::<i32>::default().simple_compress(&[]);
+  let bytes = Compressor::<i32>::default().compress_from_iter(core::iter::empty());
+  assert_eq!(bytes, expected);
+}
+
+#[test]
+fn test_constant_chunk() {
+  for delta_encoding_order in [0, 2] {
+    let config = CompressorConfig::default()
+      .with_delta_encoding_order(delta_encoding_order);
+    let mut compressor = Compressor::<f64>::from_config(config);
+    compressor.header().unwrap();
+    let meta = compressor.chunk(&[2.5; 10000]).unwrap();
+    assert_eq!(meta.compressed_body_size, 0);
+    compressor.footer().unwrap();
+    let bytes = compressor.drain_bytes();
+
+    let mut decompressor = Decompressor::<f64>::from_config(
+      DecompressorConfig::default().with_numbers_limit_per_item(3000)
+    );
+    decompressor.write_all(&bytes).unwrap();
+    let mut n = 0;
+    for item in &mut decompressor {
+      if let DecompressedItem::Numbers(nums) = item.unwrap() {
+        assert!(nums.len() <= 3000);
+        assert!(nums.iter().all(|&x| x == 2.5));
+        n += nums.len();
+      }
+    }
+    assert_eq!(n, 10000);
+  }
+}
+
+#[test]
+fn test_reset() {
+  let config = CompressorConfig::default().with_use_file_checksum(true);
+  let mut compressor = Compressor::<i32>::from_config(config.clone());
+  let mut decompressor = Decompressor::<i32>::default();
+  for series_idx in 0..3 {
+    let nums = (0..100).map(|i| i * series_idx).collect::<Vec<_>>();
+    let expected = Compressor::<i32>::from_config(config.clone())
+      .simple_compress(&nums);
+
+    // leave some undrained bytes behind before resetting
+    compressor.header().unwrap();
+    compressor.reset();
+    let bytes = compressor.simple_compress(&nums);
+    assert_eq!(bytes, expected);
+    compressor.reset();
+
+    decompressor.write_all(&bytes[..10]).unwrap();
+    decompressor.header().unwrap();
+    decompressor.reset();
+    decompressor.write_all(&bytes).unwrap();
+    assert_eq!(decompressor.simple_decompress().unwrap(), nums);
+    decompressor.verify().unwrap();
+    decompressor.reset();
+  }
+}
+
+#[test]
+fn test_chunk_delta_orders() {
+  let smooth = (0..3000_i64).map(|i| i * i).collect::<Vec<_>>();
+  let noisy = (0..3000_i64).map(|i| (i * i * i) % 1009).collect::<Vec<_>>();
+  let mut compressor = Compressor::<i64>::from_config(
+    CompressorConfig::default()
+      .with_delta_encoding_order(5)
+      .with_use_chunk_delta_orders(true)
+  );
+  assert_eq!(compressor.flags().delta_encoding_order, 0);
+  compressor.header().unwrap();
+  let mut orders = Vec::new();
+  for nums in [&smooth, &noisy, &smooth] {
+    let meta = compressor.chunk(nums).unwrap();
+    orders.push(match meta.prefix_metadata {
+      PrefixMetadata::Delta { delta_moments, .. } => delta_moments.order(),
+      _ => 0,
+    });
+  }
+  assert_eq!(orders, vec![2, 0, 2]);
+  compressor.footer().unwrap();
+  let bytes = compressor.drain_bytes();
+
+  let mut decompressor = Decompressor::<i64>::default();
+  decompressor.write_all(&bytes).unwrap();
+  assert_eq!(
+    decompressor.simple_decompress().unwrap(),
+    [smooth.clone(), noisy, smooth].concat(),
+  );
+}
+
+#[test]
+fn test_shared_prefixes() {
+  let sample = (0..3000_i64).map(|i| 5000 + (i * i * 7) % 1000).collect::<Vec<_>>();
+  let chunks = sample.chunks(50)
+    .map(|chunk| chunk.to_vec())
+    .chain([vec![1_000_000, 5000]])
+    .collect::<Vec<_>>();
+  for delta_encoding_order in [0, 1] {
+    let config = CompressorConfig::default()
+      .with_delta_encoding_order(delta_encoding_order)
+      .with_use_file_checksum(true);
+    let mut sizes = Vec::new();
+    for use_shared_prefixes in [false, true] {
+      let mut compressor = Compressor::<i64>::from_config(config.clone());
+      if use_shared_prefixes {
+        compressor.train_shared_prefixes(&sample).unwrap();
+      }
+      assert_eq!(compressor.flags().use_shared_prefixes, use_shared_prefixes);
+      compressor.header().unwrap();
+      for (i, chunk) in chunks.iter().enumerate() {
+        let meta = compressor.chunk(chunk).unwrap();
+        // the last chunk doesn't fit in the shared prefixes
+        assert_eq!(meta.uses_shared_prefixes, use_shared_prefixes && i < chunks.len() - 1);
+      }
+      compressor.footer().unwrap();
+      let bytes = compressor.drain_bytes();
+      sizes.push(bytes.len());
+
+      let mut decompressor = Decompressor::<i64>::default();
+      decompressor.write_all(&bytes).unwrap();
+      assert_eq!(decompressor.simple_decompress().unwrap(), chunks.concat());
+      decompressor.verify().unwrap();
+    }
+    assert!(sizes[1] < sizes[0] * 9 / 10, "{:?}", sizes);
+  }
+
+  let mut compressor = Compressor::<i64>::default();
+  assert!(compressor.train_shared_prefixes(&[]).is_err());
+  compressor.header().unwrap();
+  assert!(compressor.train_shared_prefixes(&sample).is_err());
+}
+
+#[test]
+fn test_user_metadata() {
+  let header_metadata = UserMetadata::from([
+    ("schema".to_string(), b"temperature".to_vec()),
+    ("units".to_string(), "°C".as_bytes().to_vec()),
+  ]);
+  let chunk_metadata = UserMetadata::from([
+    ("source".to_string(), vec![0, 1, 255]),
+  ]);
+  let config = CompressorConfig::default().with_use_user_metadata(true);
+  let mut compressor = Compressor::<f32>::from_config(config);
+  compressor.set_header_metadata(header_metadata.clone()).unwrap();
+  compressor.header().unwrap();
+  compressor.chunk_with_metadata(&[1.5, 2.5], chunk_metadata.clone()).unwrap();
+  compressor.chunk(&[3.5]).unwrap();
+  assert!(compressor.set_header_metadata(UserMetadata::new()).is_err());
+  compressor.footer().unwrap();
+  let bytes = compressor.drain_bytes();
+
+  let mut decompressor = Decompressor::<f32>::default();
+  decompressor.write_all(&bytes).unwrap();
+  assert!(decompressor.header_metadata().is_empty());
+  decompressor.header().unwrap();
+  assert_eq!(decompressor.header_metadata(), &header_metadata);
+  let meta = decompressor.chunk_metadata().unwrap().unwrap();
+  assert_eq!(meta.user_metadata, chunk_metadata);
+  assert_eq!(decompressor.chunk_body().unwrap(), vec![1.5, 2.5]);
+  let meta = decompressor.chunk_metadata().unwrap().unwrap();
+  assert!(meta.user_metadata.is_empty());
+  let mut decompressor = Decompressor::<f32>::default();
+  decompressor.write_all(&bytes).unwrap();
+  assert_eq!(decompressor.simple_decompress().unwrap(), vec![1.5, 2.5, 3.5]);
+
+  let mut compressor = Compressor::<f32>::default();
+  assert!(compressor.set_header_metadata(header_metadata).is_err());
+  compressor.header().unwrap();
+  assert!(compressor.chunk_with_metadata(&[1.0], chunk_metadata).is_err());
+}
+
+#[test]
+fn test_min_max() {
+  let nums = vec![3.5_f64, -7.25, 0.0, 1E10, -0.0, 2.0];
+  let config = CompressorConfig::default().with_use_min_max(true);
+  let mut compressor = Compressor::<f64>::from_config(config.clone());
+  compressor.header().unwrap();
+  let meta = compressor.chunk(&nums).unwrap();
+  assert_eq!(meta.min_max, Some((-7.25, 1E10)));
+  compressor.chunk(&[f64::NAN, 1.0]).unwrap();
+  compressor.footer().unwrap();
+  let bytes = compressor.drain_bytes();
+
+  let mut decompressor = Decompressor::<f64>::default();
+  decompressor.write_all(&bytes).unwrap();
+  decompressor.header().unwrap();
+  assert_eq!(decompressor.chunk_metadata().unwrap().unwrap(), meta);
+  decompressor.skip_chunk_body().unwrap();
+  let (min, max) = decompressor.chunk_metadata().unwrap().unwrap().min_max.unwrap();
+  assert_eq!(min, 1.0);
+  assert!(max.is_nan());
+
+  // lossy chunks store the min and max of what decompresses
+  let config = config.with_tolerance(Some(Tolerance::Absolute(0.5)));
+  let mut compressor = Compressor::<f64>::from_config(config.clone());
+  compressor.header().unwrap();
+  let meta = compressor.chunk(&nums).unwrap();
+  let bytes = Compressor::<f64>::from_config(config).simple_compress(&nums);
+  let decompressed = crate::auto_decompress::<f64>(&bytes).unwrap();
+  let expected_min = decompressed.iter().copied().fold(f64::INFINITY, f64::min);
+  let expected_max = decompressed.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+  assert_eq!(meta.min_max, Some((expected_min, expected_max)));
+
+  let mut compressor = Compressor::<i32>::default();
+  compressor.header().unwrap();
+  assert_eq!(compressor.chunk(&[1, 2]).unwrap().min_max, None);
+}
+
+#[test]
+fn test_distinct_count() {
+  let nums = (0..20000_i64).map(|i| (i * i) % 101).collect::<Vec<_>>();
+  let config = CompressorConfig::default()
+    .with_use_distinct_count(true)
+    .with_delta_encoding_order(1);
+  let mut compressor = Compressor::<i64>::from_config(config.clone());
+  compressor.header().unwrap();
+  let meta = compressor.chunk(&nums).unwrap();
+  assert_eq!(meta.distinct_count, Some(DistinctCount { count: 51, is_exact: true }));
+  let many_nums = (0..20000_i64).collect::<Vec<_>>();
+  let many_meta = compressor.chunk(&many_nums).unwrap();
+  let many_count = many_meta.distinct_count.unwrap();
+  assert!(!many_count.is_exact);
+  assert!(many_count.count > 18000 && many_count.count <= 20000, "{:?}", many_count);
+  compressor.footer().unwrap();
+  let bytes = compressor.drain_bytes();
+
+  let mut decompressor = Decompressor::<i64>::default();
+  decompressor.write_all(&bytes).unwrap();
+  decompressor.header().unwrap();
+  assert_eq!(decompressor.chunk_metadata().unwrap().unwrap(), meta);
+  decompressor.skip_chunk_body().unwrap();
+  assert_eq!(decompressor.chunk_metadata().unwrap().unwrap(), many_meta);
+
+  // lossy chunks count the distinct numbers that decompress
+  let floats = (0..1000).map(|i| i as f64 * 0.01).collect::<Vec<_>>();
+  let config = CompressorConfig::default()
+    .with_use_distinct_count(true)
+    .with_tolerance(Some(Tolerance::Absolute(0.5)));
+  let mut compressor = Compressor::<f64>::from_config(config.clone());
+  compressor.header().unwrap();
+  let meta = compressor.chunk(&floats).unwrap();
+  let bytes = Compressor::<f64>::from_config(config).simple_compress(&floats);
+  let mut decompressed_bits = crate::auto_decompress::<f64>(&bytes).unwrap()
+    .iter()
+    .map(|x| x.to_bits())
+    .collect::<Vec<_>>();
+  decompressed_bits.sort_unstable();
+  decompressed_bits.dedup();
+  assert!(decompressed_bits.len() < floats.len());
+  assert_eq!(
+    meta.distinct_count,
+    Some(DistinctCount { count: decompressed_bits.len(), is_exact: true }),
+  );
+
+  let mut compressor = Compressor::<i32>::default();
+  compressor.header().unwrap();
+  assert_eq!(compressor.chunk(&[1, 2]).unwrap().distinct_count, None);
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_simple_decompress_parallel() {
+  let nums = (0..2_500_000_i64).map(|i| (i * i) % 1009).collect::<Vec<_>>();
+  let config = CompressorConfig::default()
+    .with_delta_encoding_order(1)
+    .with_use_chunk_checksums(true);
+  let bytes = Compressor::<i64>::from_config(config).simple_compress(&nums);
+
+  let mut decompressor = Decompressor::<i64>::default();
+  decompressor.write_all(&bytes).unwrap();
+  assert_eq!(decompressor.simple_decompress_parallel().unwrap(), nums);
+
+  let mut decompressor = Decompressor::<i64>::default();
+  decompressor.write_all(&bytes[..bytes.len() - 1]).unwrap();
+  let err = decompressor.simple_decompress_parallel().unwrap_err();
+  assert!(matches!(err.kind, ErrorKind::InsufficientData));
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_chunks_parallel() {
+  let nums = (0..25_000_i64).map(|i| (i * i) % 1009).collect::<Vec<_>>();
+  let config = CompressorConfig::default()
+    .with_delta_encoding_order(1)
+    .with_use_file_checksum(true);
+  let mut compressor = Compressor::<i64>::from_config(config.clone());
+  assert!(compressor.chunks_parallel(&nums, 10_000).is_err());
+  compressor.header().unwrap();
+  assert!(compressor.chunks_parallel(&nums, 0).is_err());
+  let metas = compressor.chunks_parallel(&nums, 10_000).unwrap();
+  assert_eq!(metas.iter().map(|m| m.n).collect::<Vec<_>>(), vec![10_000, 10_000, 5_000]);
+  compressor.footer().unwrap();
+  let bytes = compressor.drain_bytes();
+
+  // same bytes as compressing the chunks sequentially
+  let mut sequential = Compressor::<i64>::from_config(config);
+  sequential.header().unwrap();
+  for chunk in nums.chunks(10_000) {
+    sequential.chunk(chunk).unwrap();
+  }
+  sequential.footer().unwrap();
+  assert_eq!(bytes, sequential.drain_bytes());
+}
+
+#[test]
+fn test_construct_metadata() {
+  use crate::{ChunkMetadata, Flags, Prefix};
+  use crate::constants::{MAGIC_CHUNK_BYTE, MAGIC_TERMINATION_BYTE};
+
+  let config = CompressorConfig::default().with_use_chunk_checksums(false);
+  let flags = Flags::from(&config);
+  let mut compressor = Compressor::<i64>::from_config(config);
+  compressor.header().unwrap();
+  let header_bytes = compressor.drain_bytes();
+  let meta = compressor.chunk(&[1, 4, 9, 16, 25, -100]).unwrap();
+  let chunk_bytes = compressor.drain_bytes();
+  let meta_bytes = meta.to_bytes(&flags);
+  assert_eq!(meta_bytes, chunk_bytes[1..1 + meta_bytes.len()]);
+  assert_eq!(ChunkMetadata::<i64>::from_bytes(&chunk_bytes[1..], &flags).unwrap(), meta);
+
+  // a chunk of 3 fives needs no body bits at all
+  let prefix = Prefix::new(3, vec![], 5_i64, 5);
+  let meta = ChunkMetadata::new(3, 0, PrefixMetadata::Simple { prefixes: vec![prefix] });
+  let mut bytes = header_bytes;
+  bytes.push(MAGIC_CHUNK_BYTE);
+  bytes.extend(meta.to_bytes(&flags));
+  bytes.push(MAGIC_TERMINATION_BYTE);
+  let mut decompressor = Decompressor::<i64>::default();
+  decompressor.write_all(&bytes).unwrap();
+  assert_eq!(decompressor.simple_decompress().unwrap(), vec![5, 5, 5]);
+}
+
+#[test]
+fn test_flags_bytes() {
+  use crate::Flags;
+  use crate::data_types::DataType;
+
+  let config = CompressorConfig::default()
+    .with_delta_encoding_order(3)
+    .with_use_user_metadata(true);
+  let flags = Flags::from(&config);
+  let bytes = Compressor::<u16>::from_config(config).simple_compress(&[1, 2, 3]);
+  assert_eq!(DataType::from_file_bytes(&bytes).unwrap(), DataType::U16);
+  let (parsed, flags_len) = Flags::from_bytes(&bytes[5..]).unwrap();
+  assert_eq!(parsed, flags);
+  assert_eq!(flags.to_bytes().unwrap(), bytes[5..5 + flags_len]);
+
+  // a continuation bit with nothing after it
+  let err = Flags::from_bytes(&[0x81]).unwrap_err();
+  assert!(matches!(err.kind, ErrorKind::InsufficientData), "{:?}", err);
+  // a flag from the future
+  let err = Flags::from_bytes(&[0x81, 0x01, 0x01, 0x01, 0x01, 0xfe]).unwrap_err();
+  assert!(matches!(err.kind, ErrorKind::Compatibility), "{:?}", err);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_round_trip() {
+  use crate::{ChunkMetadata, Flags};
+
+  let config = CompressorConfig::default().with_delta_encoding_order(2);
+  let flags = Flags::from(&config);
+  let mut compressor = Compressor::<i64>::from_config(config);
+  compressor.header().unwrap();
+  let meta = compressor.chunk(&[1, 4, 9, 16, 25, -100]).unwrap();
+
+  let json = serde_json::to_string(&flags).unwrap();
+  assert_eq!(serde_json::from_str::<Flags>(&json).unwrap(), flags);
+  let json = serde_json::to_string(&meta).unwrap();
+  assert_eq!(serde_json::from_str::<ChunkMetadata<i64>>(&json).unwrap(), meta);
+}
+
 fn assert_lowest_level_behavior<T: NumberLike>(numss: Vec<Vec<T>>) {
-  for delta_encoding_order in [0, 7] {
-    println!("deo={}", delta_encoding_order);
+  for (delta_encoding_order, use_chunk_checksums) in [(0, false), (7, false), (0, true), (7, true)] {
+    println!("deo={} checksums={}", delta_encoding_order, use_chunk_checksums);
     let mut compressor = Compressor::<T>::from_config(
-      CompressorConfig::default().with_delta_encoding_order(delta_encoding_order)
+      CompressorConfig::default()
+        .with_delta_encoding_order(delta_encoding_order)
+        .with_use_chunk_checksums(use_chunk_checksums)
     );
     compressor.header().unwrap();
     let mut metadatas = Vec::new();
@@ -67,6 +650,7 @@ fn assert_lowest_level_behavior<T: NumberLike>(numss: Vec<Vec<T>>) {
         },
         DecompressedItem::Numbers(nums) => {
           assert!(!terminated);
+          assert!(!nums.is_empty() && nums.len() <= 2);
           chunk_nums.extend(&nums);
         }
         DecompressedItem::Footer => {
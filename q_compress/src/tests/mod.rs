@@ -1,5 +1,7 @@
 mod atomicity;
 mod backward_compatibility;
+mod corruption_recovery;
+mod determinism;
 mod low_level;
 mod recovery;
 mod stability;
\ No newline at end of file
@@ -1,7 +1,7 @@
 use std::io::Write;
-use crate::{Compressor, CompressorConfig, Decompressor};
+use crate::{Compressor, CompressorConfig, Decompressor, PrefixMetadata, Tolerance};
 use crate::data_types::{NumberLike, TimestampMicros, TimestampNanos};
-use crate::errors::QCompressResult;
+use crate::errors::{ErrorKind, QCompressResult};
 
 #[test]
 fn test_edge_cases() {
@@ -149,38 +149,310 @@ fn test_with_gcds() {
   assert_recovers(sparse_with_gcd, 4, "sparse with gcd");
 }
 
-fn assert_recovers<T: NumberLike>(nums: Vec<T>, compression_level: usize, name: &str) {
-  for delta_encoding_order in [0, 1, 7] {
-    for use_gcds in [false, true] {
-      let debug_info = format!(
-        "name={} delta_encoding_order={}, use_gcds={}",
-        name,
+#[test]
+fn test_with_decimal_multipliers() {
+  let prices = (0..1000).map(|i| ((i * 7919) % 100000) as f64 / 100.0).collect::<Vec<_>>();
+  assert_recovers(prices.clone(), 6, "prices");
+  assert_recovers(vec![-0.3_f64, 0.0, 0.1, -2.7], 3, "signed decimals");
+  assert_recovers(vec![0.1_f64, -0.0], 1, "negative zero");
+  assert_recovers(vec![0.5_f64, f64::NAN, 1.5], 1, "nan");
+  assert_recovers(vec![1e-7_f64, 1e22], 1, "extreme exponents");
+  assert_recovers(vec![21.5_f32, 19.25, -4.125], 2, "f32 decimals");
+  assert_recovers(vec![100_i64, 200, 300], 1, "ints");
+
+  let config = CompressorConfig::default();
+  let plain_size = Compressor::<f64>::from_config(config.clone())
+    .simple_compress(&prices)
+    .len();
+  let decimal_size = Compressor::<f64>::from_config(config.with_use_decimal_multipliers(true))
+    .simple_compress(&prices)
+    .len();
+  assert!(decimal_size < plain_size);
+}
+
+#[test]
+fn test_with_xor_encoding() {
+  let slow_floats = (0..1000)
+    .map(|i| 20.0 + (i as f32 / 100.0).sin())
+    .collect::<Vec<_>>();
+  assert_recovers(slow_floats.clone(), 6, "slow floats");
+  assert_recovers(vec![1.5_f64], 1, "single float");
+  assert_recovers(vec![-0.0_f64, 0.0, f64::NAN, f64::INFINITY], 2, "special floats");
+  assert_recovers(vec![3_u32, 3, 3, 7], 1, "ints");
+
+  let config = CompressorConfig::default();
+  let mut compressor = Compressor::<f32>::from_config(config.clone());
+  compressor.header().unwrap();
+  let plain_meta = compressor.chunk(&slow_floats).unwrap();
+  let mut compressor = Compressor::<f32>::from_config(config.with_use_xor_encoding(true));
+  compressor.header().unwrap();
+  let xor_meta = compressor.chunk(&slow_floats).unwrap();
+  assert!(matches!(xor_meta.prefix_metadata, PrefixMetadata::Xor { first, .. } if first == 20.0));
+  assert!(xor_meta.compressed_body_size < plain_meta.compressed_body_size);
+}
+
+#[test]
+fn test_with_float_split_encoding() {
+  // a few distinct mantissas scaled by many powers of 2
+  let mantissas = [0x3f_c24a_1b37_d9e5_u64, 0x1_0f3c_88a2_6b17, 0xa_5e71_c0d4_923f];
+  let mut state = 7_u64;
+  let scaled_floats = (0..2000)
+    .map(|_| {
+      state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+      let exponent = (state >> 33) % 900 + 100;
+      f64::from_bits((exponent << 52) | mantissas[(state >> 60) as usize % 3])
+    })
+    .collect::<Vec<_>>();
+  assert_recovers(scaled_floats.clone(), 6, "scaled floats");
+  assert_recovers(vec![1.5_f32], 1, "single float");
+  assert_recovers(vec![-0.0_f64, 0.0, f64::NAN, f64::INFINITY, -f64::MIN_POSITIVE], 2, "special floats");
+
+  let config = CompressorConfig::default();
+  let mut compressor = Compressor::<f64>::from_config(config.clone());
+  compressor.header().unwrap();
+  let plain_meta = compressor.chunk(&scaled_floats).unwrap();
+  let mut compressor = Compressor::<f64>::from_config(config.clone().with_use_float_split_encoding(true));
+  compressor.header().unwrap();
+  let split_meta = compressor.chunk(&scaled_floats).unwrap();
+  match &split_meta.prefix_metadata {
+    PrefixMetadata::FloatSplit { mantissa_prefixes, exponent_body_size, .. } => {
+      assert!(mantissa_prefixes.len() <= 3);
+      assert!(*exponent_body_size < split_meta.compressed_body_size);
+    },
+    other => panic!("expected float split prefixes but got {:?}", other),
+  }
+  assert!(split_meta.compressed_body_size < plain_meta.compressed_body_size / 2);
+
+  // the chunk checksum covers the exponents as well as the mantissas
+  let mut compressed = Compressor::<f64>::from_config(
+    config.clone().with_use_float_split_encoding(true).with_use_chunk_checksums(true)
+  ).simple_compress(&scaled_floats);
+  // the body is followed by the 4-byte checksum and 1-byte footer
+  let body_start = compressed.len() - 5 - split_meta.compressed_body_size;
+  compressed[body_start + 1] ^= 4;
+  let mut decompressor = Decompressor::<f64>::default();
+  decompressor.write_all(&compressed).unwrap();
+  let err = decompressor.simple_decompress().unwrap_err();
+  assert!(matches!(err.kind, ErrorKind::Corruption), "{:?}", err);
+
+  // integers never split
+  let mut compressor = Compressor::<i64>::from_config(config.with_use_float_split_encoding(true));
+  compressor.header().unwrap();
+  let int_meta = compressor.chunk(&[1 << 40, 3 << 50, 5]).unwrap();
+  assert!(!matches!(int_meta.prefix_metadata, PrefixMetadata::FloatSplit { .. }));
+}
+
+#[test]
+fn test_with_delta_lag() {
+  // hourly readings with daily seasonality and a slow trend
+  let daily = [3, 1, 0, 0, 2, 9, 30, 55, 61, 48, 40, 42, 47, 45, 41, 44, 58, 70, 66, 41, 25, 14, 8, 5];
+  let nums = (0..5000_i64)
+    .map(|i| daily[i as usize % 24] * 100 + i / 10 + (i * i) % 3)
+    .collect::<Vec<_>>();
+  let mut sizes = Vec::new();
+  for (delta_encoding_order, delta_lag) in [(1, 1), (1, 24), (2, 24), (1, 7000)] {
+    let config = CompressorConfig::default()
+      .with_delta_encoding_order(delta_encoding_order)
+      .with_delta_lag(delta_lag);
+    for use_chunk_delta_orders in [false, true] {
+      let compressed = Compressor::<i64>::from_config(
+        config.clone().with_use_chunk_delta_orders(use_chunk_delta_orders)
+      ).simple_compress(&nums);
+      let mut decompressor = Decompressor::<i64>::default();
+      decompressor.write_all(&compressed).unwrap();
+      assert_eq!(decompressor.header().unwrap().delta_lag, delta_lag);
+      let mut decompressor = Decompressor::<i64>::default();
+      decompressor.write_all(&compressed).unwrap();
+      assert_eq!(
+        decompressor.simple_decompress().unwrap(),
+        nums,
+        "order={} lag={} use_chunk_delta_orders={}",
         delta_encoding_order,
-        use_gcds,
+        delta_lag,
+        use_chunk_delta_orders,
       );
-      let mut compressor = Compressor::<T>::from_config(
-        CompressorConfig::default()
-          .with_compression_level(compression_level)
-          .with_delta_encoding_order(delta_encoding_order)
-          .with_use_gcds(use_gcds)
-      );
-      let compressed = compressor.simple_compress(&nums);
-      let mut decompressor = Decompressor::<T>::default();
+    }
+    sizes.push(Compressor::<i64>::from_config(config).simple_compress(&nums).len());
+  }
+  assert!(sizes[1] < sizes[0] / 2);
+
+  let mut compressor = Compressor::<i64>::from_config(CompressorConfig::default().with_delta_lag(0));
+  assert!(compressor.header().is_err());
+}
+
+#[test]
+fn test_with_tolerance() {
+  let readings = (0..3000)
+    .map(|i| 20.0 + (i as f64 / 50.0).sin() + ((i * 7919) % 1000) as f64 * 1.0e-6)
+    .collect::<Vec<_>>();
+  let lossless_size = Compressor::<f64>::default().simple_compress(&readings).len();
+  for (tolerance, max_error) in [
+    (Tolerance::Absolute(1.0e-3), (|_| 1.0e-3) as fn(f64) -> f64),
+    (Tolerance::Relative(1.0e-3), |x: f64| 1.0e-3 * x.abs()),
+  ] {
+    for delta_encoding_order in [0, 1] {
+      let config = CompressorConfig::default()
+        .with_delta_encoding_order(delta_encoding_order)
+        .with_tolerance(Some(tolerance));
+      let mut compressor = Compressor::<f64>::from_config(config);
+      let compressed = compressor.simple_compress(&readings);
+      assert!(compressed.len() < lossless_size / 2, "{:?}", tolerance);
+      let mut decompressor = Decompressor::<f64>::default();
       decompressor.write_all(&compressed).unwrap();
-      let decompressed = decompressor.simple_decompress()
-        .expect("decompression error");
-      // We can't do assert_eq on the whole vector because even bitwise identical
-      // floats sometimes aren't equal by ==.
-      assert_eq!(decompressed.len(), nums.len(), "{}", debug_info);
-      for i in 0..decompressed.len() {
-        assert!(
-          decompressed[i].num_eq(&nums[i]),
-          "{} != {}; {}",
-          decompressed[i],
-          nums[i],
-          debug_info,
+      let decompressed = decompressor.simple_decompress().unwrap();
+      assert_eq!(decompressed.len(), readings.len());
+      for (&x, &y) in readings.iter().zip(&decompressed) {
+        assert!((x - y).abs() <= max_error(x), "{} became {} with {:?}", x, y, tolerance);
+      }
+    }
+  }
+
+  // chunks that can't be rounded within the tolerance are lossless
+  let config = CompressorConfig::default().with_tolerance(Some(Tolerance::Absolute(0.5)));
+  let mut compressor = Compressor::<f64>::from_config(config.clone());
+  compressor.header().unwrap();
+  let meta = compressor.chunk(&[1.1, f64::NAN]).unwrap();
+  assert_eq!(meta.quantization, None);
+  compressor.footer().unwrap();
+  let mut decompressor = Decompressor::<f64>::default();
+  decompressor.write_all(&compressor.drain_bytes()).unwrap();
+  let decompressed = decompressor.simple_decompress().unwrap();
+  assert_eq!(decompressed[0], 1.1);
+  assert!(decompressed[1].is_nan());
+  let bytes = Compressor::<i32>::from_config(config).simple_compress(&[1, 2, 3]);
+  let mut decompressor = Decompressor::<i32>::default();
+  decompressor.write_all(&bytes).unwrap();
+  assert_eq!(decompressor.simple_decompress().unwrap(), vec![1, 2, 3]);
+
+  let mut compressor = Compressor::<f64>::from_config(
+    CompressorConfig::default().with_tolerance(Some(Tolerance::Relative(-1.0)))
+  );
+  compressor.header().unwrap();
+  assert!(compressor.chunk(&[1.0]).is_err());
+}
+
+#[test]
+fn test_with_raw_fallback() {
+  // well-mixed hashes that prefixes can't compress
+  let hashes = (0..2000_u64)
+    .map(|i| {
+      let mut x = i.wrapping_mul(0x9e3779b97f4a7c15);
+      x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+      x ^ (x >> 31)
+    })
+    .collect::<Vec<_>>();
+  let ids = hashes.iter()
+    .map(|&x| 1000 + (x >> 44) as u32)
+    .collect::<Vec<_>>();
+  assert_recovers(hashes.clone(), 8, "hashes");
+  assert_recovers(ids.clone(), 8, "ids");
+
+  let config = CompressorConfig::default().with_use_raw_fallback(true);
+  let mut compressor = Compressor::<u32>::from_config(config.clone());
+  compressor.header().unwrap();
+  let meta = compressor.chunk(&ids).unwrap();
+  assert_eq!(
+    meta.prefix_metadata,
+    PrefixMetadata::Raw { lower: *ids.iter().min().unwrap(), bit_width: 20 },
+  );
+  assert_eq!(meta.compressed_body_size, ids.len() * 20 / 8);
+
+  // compressible chunks still use prefixes
+  let mut compressor = Compressor::<u32>::from_config(config);
+  compressor.header().unwrap();
+  let skewed = (0..1000).map(|i| if i % 100 == 0 { 1000000 } else { i % 3 }).collect::<Vec<_>>();
+  let meta = compressor.chunk(&skewed).unwrap();
+  assert!(matches!(meta.prefix_metadata, PrefixMetadata::Simple { .. }));
+}
+
+#[test]
+fn test_with_long_runs() {
+  // no value is frequent enough to get run lengths on its own, but every
+  // value comes in long runs
+  let nums = (0..30000_u32)
+    .map(|i| [5, 7, 9][(i / 1000) as usize % 3])
+    .collect::<Vec<_>>();
+  assert_recovers(nums.clone(), 6, "long runs");
+
+  let mut compressor = Compressor::<u32>::default();
+  compressor.header().unwrap();
+  let meta = compressor.chunk(&nums).unwrap();
+  match meta.prefix_metadata {
+    PrefixMetadata::Simple { prefixes } => {
+      assert!(prefixes.iter().all(|p| p.run_len_jumpstart.is_some()), "{:?}", prefixes);
+    },
+    other => panic!("expected simple prefixes but got {:?}", other),
+  }
+  assert!(meta.compressed_body_size < 100);
+
+  // runs interrupted by singletons still recover
+  let mut interrupted = nums;
+  for i in (0..interrupted.len()).step_by(777) {
+    interrupted[i] = 11;
+  }
+  assert_recovers(interrupted, 6, "interrupted long runs");
+}
+
+fn assert_recovers<T: NumberLike>(nums: Vec<T>, compression_level: usize, name: &str) {
+  for delta_encoding_order in [0, 1, 7] {
+    for use_gcds in [false, true] {
+      for (use_decimal_multipliers, use_xor_encoding, use_raw_fallback, use_float_split_encoding) in [
+        (false, false, false, false),
+        (true, false, false, false),
+        (false, true, false, false),
+        (true, true, false, false),
+        (false, false, true, false),
+        (false, false, false, true),
+        (true, false, true, true),
+      ] {
+        let debug_info = format!(
+          "name={} delta_encoding_order={}, use_gcds={}, use_decimal_multipliers={}, use_xor_encoding={}, use_raw_fallback={}, use_float_split_encoding={}",
+          name,
+          delta_encoding_order,
+          use_gcds,
+          use_decimal_multipliers,
+          use_xor_encoding,
+          use_raw_fallback,
+          use_float_split_encoding,
+        );
+        let mut compressor = Compressor::<T>::from_config(
+          CompressorConfig::default()
+            .with_compression_level(compression_level)
+            .with_delta_encoding_order(delta_encoding_order)
+            .with_use_gcds(use_gcds)
+            .with_use_decimal_multipliers(use_decimal_multipliers)
+            .with_use_xor_encoding(use_xor_encoding)
+            .with_use_raw_fallback(use_raw_fallback)
+            .with_use_float_split_encoding(use_float_split_encoding)
         );
+        let compressed = compressor.simple_compress(&nums);
+        let mut decompressor = Decompressor::<T>::default();
+        decompressor.write_all(&compressed).unwrap();
+        let decompressed = decompressor.simple_decompress()
+          .expect("decompression error");
+        // We can't do assert_eq on the whole vector because even bitwise identical
+        // floats sometimes aren't equal by ==.
+        assert_eq!(decompressed.len(), nums.len(), "{}", debug_info);
+        for i in 0..decompressed.len() {
+          assert!(
+            decompressed[i].num_eq(&nums[i]),
+            "{} != {}; {}",
+            decompressed[i],
+            nums[i],
+            debug_info,
+          );
+        }
+
+        let mut decompressor = Decompressor::<T>::default();
+        decompressor.write_all(&compressed).unwrap();
+        let mut dst = vec![T::default(); nums.len() + 1];
+        let n = decompressor.decompress_into(&mut dst)
+          .expect("decompression into error");
+        assert_eq!(n, nums.len(), "{}", debug_info);
+        for i in 0..n {
+          assert!(dst[i].num_eq(&nums[i]), "{}", debug_info);
+        }
       }
     }
   }
-}
\ No newline at end of file
+}
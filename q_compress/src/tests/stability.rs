@@ -84,3 +84,24 @@ fn test_insufficient_data_long_offsets() {
     _ => panic!("expected simple prefix info"),
   }
 }
+
+#[test]
+fn test_full_width_run_length_varint() {
+  // the first run is long enough for its run length varint to use all
+  // BITS_TO_ENCODE_N_ENTRIES bits, and more numbers follow it
+  let mut nums = vec![false; (1 << 23) + 2];
+  nums.push(true);
+  nums.push(false);
+  let mut compressor = Compressor::<bool>::default();
+  compressor.header().expect("header");
+  let metadata = compressor.chunk(&nums).expect("chunk");
+  compressor.footer().expect("footer");
+  let compressed = compressor.drain_bytes();
+  match metadata.prefix_metadata {
+    PrefixMetadata::Simple { prefixes } => {
+      assert!(prefixes.iter().any(|p| p.run_len_jumpstart.is_some()));
+    },
+    _ => panic!("expected simple prefix info"),
+  }
+  assert_eq!(crate::auto_decompress::<bool>(&compressed).expect("decompress"), nums);
+}
@@ -0,0 +1,190 @@
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use crate::{auto_compressor_config, multi_series_decompress, Compressor, CompressorConfig, DEFAULT_COMPRESSION_LEVEL};
+use crate::data_types::NumberLike;
+use crate::errors::{QCompressError, QCompressResult};
+use crate::multi_series;
+
+/// Compresses a series of (timestamp, value) pairs into a single container
+/// holding both columns.
+///
+/// By default, timestamps are compressed with 2nd order delta encoding,
+/// which suits approximately periodic sampling, and values with a config
+/// chosen as in [`auto_compress`][crate::auto_compress].
+/// Either can be overridden with
+/// [`with_timestamp_config`][Self::with_timestamp_config] or
+/// [`with_value_config`][Self::with_value_config].
+///
+/// The container is the same as [`multi_series_compress`][crate::multi_series_compress]
+/// writes with a single value series.
+/// ```
+/// use q_compress::{TimeSeriesCompressor, TimeSeriesDecompressor};
+///
+/// let pairs = vec![(100_i64, 1.0_f32), (200, 1.5), (300, 2.0)];
+/// let bytes = TimeSeriesCompressor::default().compress_pairs(&pairs).expect("compress");
+/// let recovered = TimeSeriesDecompressor::<i64, f32>::default()
+///   .decompress_pairs(&bytes)
+///   .expect("decompress");
+/// assert_eq!(recovered, pairs);
+/// ```
+#[derive(Clone, Debug)]
+pub struct TimeSeriesCompressor<Ts: NumberLike, V: NumberLike> {
+  compression_level: usize,
+  timestamp_config: Option<CompressorConfig>,
+  value_config: Option<CompressorConfig>,
+  phantom: PhantomData<(Ts, V)>,
+}
+
+impl<Ts: NumberLike, V: NumberLike> Default for TimeSeriesCompressor<Ts, V> {
+  fn default() -> Self {
+    Self {
+      compression_level: DEFAULT_COMPRESSION_LEVEL,
+      timestamp_config: None,
+      value_config: None,
+      phantom: PhantomData,
+    }
+  }
+}
+
+impl<Ts: NumberLike, V: NumberLike> TimeSeriesCompressor<Ts, V> {
+  /// Sets the compression level used by the default timestamp and value
+  /// configs.
+  pub fn with_compression_level(mut self, level: usize) -> Self {
+    self.compression_level = level;
+    self
+  }
+
+  /// Compresses timestamps with this config instead of the default.
+  pub fn with_timestamp_config(mut self, config: CompressorConfig) -> Self {
+    self.timestamp_config = Some(config);
+    self
+  }
+
+  /// Compresses values with this config instead of choosing one
+  /// automatically.
+  pub fn with_value_config(mut self, config: CompressorConfig) -> Self {
+    self.value_config = Some(config);
+    self
+  }
+
+  /// Compresses the timestamps and the values that go with them.
+  /// Will return an error if they have different lengths or if either
+  /// config is invalid.
+  pub fn compress(&self, timestamps: &[Ts], values: &[V]) -> QCompressResult<Vec<u8>> {
+    if values.len() != timestamps.len() {
+      return Err(QCompressError::invalid_argument(format!(
+        "there are {} values but {} timestamps",
+        values.len(),
+        timestamps.len(),
+      )));
+    }
+
+    let timestamp_config = self.timestamp_config.clone()
+      .unwrap_or_else(|| multi_series::timestamp_config(self.compression_level));
+    let value_config = self.value_config.clone()
+      .unwrap_or_else(|| auto_compressor_config(values, self.compression_level));
+    let compressed_columns = vec![
+      Compressor::<Ts>::try_from_config(timestamp_config)?.simple_compress(timestamps),
+      Compressor::<V>::try_from_config(value_config)?.simple_compress(values),
+    ];
+    multi_series::write_container(&compressed_columns)
+  }
+
+  /// Compresses (timestamp, value) pairs.
+  /// Will return an error if either config is invalid.
+  pub fn compress_pairs(&self, pairs: &[(Ts, V)]) -> QCompressResult<Vec<u8>> {
+    let (timestamps, values): (Vec<Ts>, Vec<V>) = pairs.iter().copied().unzip();
+    self.compress(&timestamps, &values)
+  }
+}
+
+/// Decompresses a container written by [`TimeSeriesCompressor`].
+#[derive(Clone, Debug)]
+pub struct TimeSeriesDecompressor<Ts: NumberLike, V: NumberLike> {
+  phantom: PhantomData<(Ts, V)>,
+}
+
+impl<Ts: NumberLike, V: NumberLike> Default for TimeSeriesDecompressor<Ts, V> {
+  fn default() -> Self {
+    Self { phantom: PhantomData }
+  }
+}
+
+impl<Ts: NumberLike, V: NumberLike> TimeSeriesDecompressor<Ts, V> {
+  /// Returns the timestamps and values as separate vectors.
+  /// Will return an error if the container is corrupt, incomplete, of the
+  /// wrong data types, or holds other than exactly one value series.
+  pub fn decompress(&self, bytes: &[u8]) -> QCompressResult<(Vec<Ts>, Vec<V>)> {
+    let (timestamps, mut series) = multi_series_decompress::<Ts, V>(bytes)?;
+    if series.len() != 1 {
+      return Err(QCompressError::corruption(format!(
+        "expected 1 value series but found {}",
+        series.len(),
+      )));
+    }
+    Ok((timestamps, series.remove(0)))
+  }
+
+  /// Returns (timestamp, value) pairs.
+  /// Will return an error in the same cases as
+  /// [`decompress`][Self::decompress].
+  pub fn decompress_pairs(&self, bytes: &[u8]) -> QCompressResult<Vec<(Ts, V)>> {
+    let (timestamps, values) = self.decompress(bytes)?;
+    Ok(timestamps.into_iter().zip(values).collect())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::multi_series_compress;
+  use crate::data_types::TimestampNanos;
+  use crate::errors::ErrorKind;
+  use super::*;
+
+  #[test]
+  fn test_time_series_recovers() {
+    let pairs = (0..5000)
+      .map(|i| (
+        TimestampNanos::new(1_650_000_000_000_000_000 + i * 10_000_000 + i % 5),
+        (i % 360) as f64 * 0.25,
+      ))
+      .collect::<Vec<_>>();
+    let compressor = TimeSeriesCompressor::default().with_compression_level(6);
+    let bytes = compressor.compress_pairs(&pairs).unwrap();
+    let decompressor = TimeSeriesDecompressor::<TimestampNanos, f64>::default();
+    assert_eq!(decompressor.decompress_pairs(&bytes).unwrap(), pairs);
+
+    let (timestamps, values): (Vec<_>, Vec<_>) = pairs.iter().copied().unzip();
+    let bytes = compressor
+      .with_timestamp_config(CompressorConfig::default().with_delta_encoding_order(1))
+      .with_value_config(CompressorConfig::default().with_use_gcds(false))
+      .compress(&timestamps, &values)
+      .unwrap();
+    assert_eq!(decompressor.decompress(&bytes).unwrap(), (timestamps, values));
+  }
+
+  #[test]
+  fn test_time_series_errors() {
+    let compressor = TimeSeriesCompressor::<i64, i32>::default();
+    let err = compressor.compress(&[1, 2], &[3]).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+    let err = compressor
+      .clone()
+      .with_value_config(CompressorConfig::default().with_delta_encoding_order(100))
+      .compress(&[1], &[3])
+      .unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+
+    // empty series are fine, even with automatic value configs
+    let bytes = TimeSeriesCompressor::<i64, f32>::default().compress(&[], &[]).unwrap();
+    let decompressor = TimeSeriesDecompressor::<i64, f32>::default();
+    assert_eq!(decompressor.decompress(&bytes).unwrap(), (vec![], vec![]));
+
+    let bytes = multi_series_compress::<i64, i32>(&[1, 2], &[vec![3, 4], vec![5, 6]], 6).unwrap();
+    let err = TimeSeriesDecompressor::<i64, i32>::default().decompress(&bytes).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Corruption));
+  }
+}
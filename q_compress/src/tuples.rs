@@ -0,0 +1,183 @@
+use alloc::format;
+use alloc::vec::Vec;
+
+use crate::{auto_decompress, Compressor, CompressorConfig};
+use crate::bit_reader::BitReader;
+use crate::bit_words::BitWords;
+use crate::bit_writer::BitWriter;
+use crate::constants::{BITS_TO_ENCODE_ARITY, BITS_TO_ENCODE_DIMENSION_BYTE_SIZE, MAGIC_TUPLES_HEADER, MAX_ARITY};
+use crate::data_types::NumberLike;
+use crate::errors::{QCompressError, QCompressResult};
+
+/// Compresses interleaved tuples of a fixed arity, such as (latitude,
+/// longitude) pairs or (x, y, z) accelerometer readings, returning a single
+/// self-describing container.
+///
+/// `nums` holds the tuples one after another, so its length must be a
+/// multiple of `arity`.
+/// Each dimension is de-interleaved into its own stream and compressed with
+/// its own config from `configs`, which must have one config per
+/// dimension.
+/// This compresses far better than compressing the interleaved numbers
+/// directly, since neighboring numbers of the same dimension tend to be
+/// similar while alternating dimensions are not.
+///
+/// The container consists of a 4-byte magic header for "qct!" in ascii,
+/// the arity,
+/// the byte size of each embedded .qco file,
+/// and then the .qco files themselves (one per dimension, in order).
+/// ```
+/// use q_compress::{auto_compressor_config, tuples_compress, tuples_decompress};
+///
+/// let lat_lons = vec![37.77_f64, -122.42, 37.78, -122.41, 37.79, -122.40];
+/// let configs = vec![auto_compressor_config(&lat_lons, 8); 2];
+/// let bytes = tuples_compress(&lat_lons, 2, &configs).expect("compress");
+/// let (arity, recovered) = tuples_decompress::<f64>(&bytes).expect("decompress");
+/// assert_eq!(arity, 2);
+/// assert_eq!(recovered, lat_lons);
+/// ```
+pub fn tuples_compress<T: NumberLike>(
+  nums: &[T],
+  arity: usize,
+  configs: &[CompressorConfig],
+) -> QCompressResult<Vec<u8>> {
+  if arity == 0 || arity > MAX_ARITY {
+    return Err(QCompressError::invalid_argument(format!(
+      "arity must be between 1 and {} (was {})",
+      MAX_ARITY,
+      arity,
+    )));
+  }
+  if !nums.len().is_multiple_of(arity) {
+    return Err(QCompressError::invalid_argument(format!(
+      "number of numbers {} is not a multiple of the arity {}",
+      nums.len(),
+      arity,
+    )));
+  }
+  if configs.len() != arity {
+    return Err(QCompressError::invalid_argument(format!(
+      "there are {} configs but the arity is {}",
+      configs.len(),
+      arity,
+    )));
+  }
+
+  let mut compressed_dims = Vec::with_capacity(arity);
+  for (dim, config) in configs.iter().enumerate() {
+    let dim_nums = nums.iter()
+      .skip(dim)
+      .step_by(arity)
+      .copied()
+      .collect::<Vec<_>>();
+    let mut compressor = Compressor::<T>::try_from_config(config.clone())?;
+    compressed_dims.push(compressor.simple_compress(&dim_nums));
+  }
+
+  let mut writer = BitWriter::default();
+  writer.write_aligned_bytes(&MAGIC_TUPLES_HEADER)?;
+  writer.write_usize(arity, BITS_TO_ENCODE_ARITY);
+  for dim_bytes in &compressed_dims {
+    writer.write_usize(dim_bytes.len(), BITS_TO_ENCODE_DIMENSION_BYTE_SIZE);
+  }
+  for dim_bytes in &compressed_dims {
+    writer.write_aligned_bytes(dim_bytes)?;
+  }
+  Ok(writer.drain_bytes())
+}
+
+/// Decompresses a container written by [`tuples_compress`], returning the
+/// arity and the re-interleaved tuples.
+///
+/// Will return an error if the container or any of its .qco files are
+/// corrupt, incomplete, or of the wrong data type.
+pub fn tuples_decompress<T: NumberLike>(bytes: &[u8]) -> QCompressResult<(usize, Vec<T>)> {
+  let words = BitWords::from(bytes);
+  let mut reader = BitReader::from(&words);
+  let magic = reader.read_aligned_bytes(MAGIC_TUPLES_HEADER.len())?;
+  if magic != MAGIC_TUPLES_HEADER {
+    return Err(QCompressError::corruption(format!(
+      "magic tuples header does not match {:?}; instead found {:?}",
+      MAGIC_TUPLES_HEADER,
+      magic,
+    )));
+  }
+  let arity = reader.read_usize(BITS_TO_ENCODE_ARITY)?;
+  if arity == 0 {
+    return Err(QCompressError::corruption("tuple arity is 0"));
+  }
+  let mut dim_sizes = Vec::with_capacity(arity);
+  for _ in 0..arity {
+    dim_sizes.push(reader.read_usize(BITS_TO_ENCODE_DIMENSION_BYTE_SIZE)?);
+  }
+
+  let mut dims: Vec<Vec<T>> = Vec::with_capacity(arity);
+  for (dim, &size) in dim_sizes.iter().enumerate() {
+    let dim_nums = auto_decompress::<T>(&reader.read_aligned_bytes(size)?)?;
+    if dim > 0 && dim_nums.len() != dims[0].len() {
+      return Err(QCompressError::corruption(format!(
+        "dimension {} has {} numbers but dimension 0 has {}",
+        dim,
+        dim_nums.len(),
+        dims[0].len(),
+      )));
+    }
+    dims.push(dim_nums);
+  }
+
+  let n_tuples = dims[0].len();
+  let mut nums = Vec::with_capacity(n_tuples * arity);
+  for i in 0..n_tuples {
+    nums.extend(dims.iter().map(|dim_nums| dim_nums[i]));
+  }
+  Ok((arity, nums))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{auto_compress, CompressorConfig};
+  use crate::errors::ErrorKind;
+  use super::{tuples_compress, tuples_decompress};
+
+  #[test]
+  fn test_tuples_recover() {
+    // a GPS-like trace whose dimensions differ greatly in scale
+    let nums = (0..3000_i64)
+      .flat_map(|i| [4_000_000 + i * 3, -12_000_000 - i * 5 + i % 4, i % 7])
+      .collect::<Vec<_>>();
+    let configs = vec![
+      CompressorConfig::default().with_delta_encoding_order(1),
+      CompressorConfig::default().with_delta_encoding_order(1),
+      CompressorConfig::default(),
+    ];
+    let bytes = tuples_compress(&nums, 3, &configs).unwrap();
+    assert!(bytes.len() * 2 < auto_compress(&nums, 6).len());
+    let (arity, recovered) = tuples_decompress::<i64>(&bytes).unwrap();
+    assert_eq!(arity, 3);
+    assert_eq!(recovered, nums);
+
+    let bytes = tuples_compress::<f32>(&[], 2, &configs[..2]).unwrap();
+    assert_eq!(tuples_decompress::<f32>(&bytes).unwrap(), (2, vec![]));
+  }
+
+  #[test]
+  fn test_tuples_errors() {
+    let configs = vec![CompressorConfig::default(); 2];
+    for (nums, arity, n_configs) in [
+      (vec![1_i32, 2, 3], 2, 2),
+      (vec![1, 2], 0, 0),
+      (vec![1, 2], 2, 1),
+    ] {
+      let err = tuples_compress(&nums, arity, &configs[..n_configs]).unwrap_err();
+      assert!(matches!(err.kind, ErrorKind::InvalidArgument));
+    }
+    let invalid_configs = vec![CompressorConfig::default().with_delta_encoding_order(100)];
+    assert!(tuples_compress(&[1_i32], 1, &invalid_configs).is_err());
+
+    let bytes = tuples_compress(&[1_i32, 2], 2, &configs).unwrap();
+    let err = tuples_decompress::<i32>(&bytes[..bytes.len() - 1]).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InsufficientData));
+    let err = tuples_decompress::<u32>(&bytes).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Corruption));
+  }
+}
@@ -0,0 +1,76 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::bit_reader::BitReader;
+use crate::bit_writer::BitWriter;
+use crate::constants::{BITS_TO_ENCODE_N_USER_METADATA_ENTRIES, BITS_TO_ENCODE_USER_METADATA_LEN, MAX_N_USER_METADATA_ENTRIES, MAX_USER_METADATA_LEN};
+use crate::errors::{QCompressError, QCompressResult};
+
+/// Application-defined key-value pairs stored alongside the numbers, like a
+/// schema name, units, or source IDs.
+///
+/// Values are arbitrary bytes; to store a string, use its UTF-8 bytes.
+/// See [`CompressorConfig::use_user_metadata`][crate::CompressorConfig::use_user_metadata].
+pub type UserMetadata = BTreeMap<String, Vec<u8>>;
+
+pub(crate) fn validate(metadata: &UserMetadata) -> QCompressResult<()> {
+  if metadata.len() > MAX_N_USER_METADATA_ENTRIES {
+    return Err(QCompressError::invalid_argument(format!(
+      "user metadata may not have more than {} entries (had {})",
+      MAX_N_USER_METADATA_ENTRIES,
+      metadata.len(),
+    )));
+  }
+  for (key, value) in metadata {
+    if key.len() > MAX_USER_METADATA_LEN || value.len() > MAX_USER_METADATA_LEN {
+      return Err(QCompressError::invalid_argument(format!(
+        "user metadata keys and values may not exceed {} bytes (key {:?} had {} bytes and its value had {})",
+        MAX_USER_METADATA_LEN,
+        key,
+        key.len(),
+        value.len(),
+      )));
+    }
+  }
+  Ok(())
+}
+
+fn read_bytes(reader: &mut BitReader) -> QCompressResult<Vec<u8>> {
+  let len = reader.read_usize(BITS_TO_ENCODE_USER_METADATA_LEN)?;
+  let mut res = Vec::with_capacity(len);
+  for _ in 0..len {
+    res.push(reader.read_usize(8)? as u8);
+  }
+  Ok(res)
+}
+
+fn write_bytes(bytes: &[u8], writer: &mut BitWriter) {
+  writer.write_usize(bytes.len(), BITS_TO_ENCODE_USER_METADATA_LEN);
+  for &byte in bytes {
+    writer.write_usize(byte as usize, 8);
+  }
+}
+
+pub(crate) fn parse_from(reader: &mut BitReader) -> QCompressResult<UserMetadata> {
+  let n_entries = reader.read_usize(BITS_TO_ENCODE_N_USER_METADATA_ENTRIES)?;
+  let mut res = UserMetadata::new();
+  for _ in 0..n_entries {
+    let key = String::from_utf8(read_bytes(reader)?).map_err(|_| QCompressError::corruption(
+      "user metadata key is not valid UTF-8"
+    ))?;
+    let value = read_bytes(reader)?;
+    res.insert(key, value);
+  }
+  Ok(res)
+}
+
+// assumes the metadata has been validated
+pub(crate) fn write_to(metadata: &UserMetadata, writer: &mut BitWriter) {
+  writer.write_usize(metadata.len(), BITS_TO_ENCODE_N_USER_METADATA_ENTRIES);
+  for (key, value) in metadata {
+    write_bytes(key.as_bytes(), writer);
+    write_bytes(value, writer);
+  }
+}
@@ -0,0 +1,237 @@
+//! Cheap checks that a .qco file is well-formed.
+//!
+//! [`check_structure`] reads every part of a file except the numbers in its
+//! chunk bodies, so it costs a small fraction of decompressing it.
+//! This suits ingest pipelines that want to reject truncated or corrupted
+//! uploads before accepting them.
+//! Since chunk bodies aren't decoded, a file that passes can still fail to
+//! decompress if a body is corrupt and the file has no checksums to catch
+//! it.
+
+use alloc::format;
+
+use crate::{checksum, Flags};
+use crate::bit_reader::BitReader;
+use crate::bit_words::BitWords;
+use crate::chunk_body_decompressor::ChunkBodyDecompressor;
+use crate::chunk_metadata::MemoryLimits;
+use crate::constants::{BYTES_PER_CHUNK_CHECKSUM, BYTES_PER_FILE_CHECKSUM};
+use crate::data_types::{DataType, NumberLike, TimestampMicros, TimestampNanos};
+use crate::decompressor::{read_chunk_meta, read_header, Header};
+use crate::errors::{ErrorPosition, QCompressError, QCompressResult, Section};
+
+fn read_checksum(bytes: &[u8]) -> u32 {
+  u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn check_chunk_body(
+  bytes: &[u8],
+  flags: &Flags,
+  body_start: usize,
+  compressed_body_size: usize,
+) -> QCompressResult<()> {
+  let checksum_size = if flags.use_chunk_checksums {
+    BYTES_PER_CHUNK_CHECKSUM
+  } else {
+    0
+  };
+  let body_end = body_start.saturating_add(compressed_body_size);
+  let end = body_end.saturating_add(checksum_size);
+  if end > bytes.len() {
+    return Err(QCompressError::insufficient_data(format!(
+      "chunk body of {} bytes extends past the end of the {}-byte file",
+      end - body_start,
+      bytes.len(),
+    )));
+  }
+
+  if flags.use_chunk_checksums {
+    let expected = read_checksum(&bytes[body_end..end]);
+    let actual = checksum::crc32(&bytes[body_start..body_end]);
+    if expected != actual {
+      return Err(QCompressError::corruption(format!(
+        "chunk body checksum {:#010x} does not match expected {:#010x}",
+        actual,
+        expected,
+      )));
+    }
+  }
+  Ok(())
+}
+
+fn check_structure_as<T: NumberLike>(bytes: &[u8]) -> QCompressResult<()> {
+  let words = BitWords::from(bytes);
+  let mut reader = BitReader::from(&words);
+  let Header { flags, shared_prefixes, .. } = read_header::<T>(&mut reader)
+    .map_err(|e| e.at(ErrorPosition {
+      section: Section::Header,
+      chunk_idx: None,
+      bit_idx: reader.bit_idx(),
+    }))?;
+
+  // no numbers are allocated, so memory limits don't apply
+  let mut limits = MemoryLimits::default();
+  let mut chunk_idx = 0;
+  loop {
+    let meta_start = reader.bit_idx();
+    let maybe_meta = read_chunk_meta(&mut reader, &flags, shared_prefixes.as_ref(), &mut limits)
+      .map_err(|e| e.at(ErrorPosition {
+        section: Section::ChunkMetadata,
+        chunk_idx: Some(chunk_idx),
+        bit_idx: meta_start,
+      }))?;
+    let meta = match maybe_meta {
+      Some(meta) => meta,
+      None => break,
+    };
+
+    let body_start = reader.aligned_byte_idx()?;
+    let body_position = ErrorPosition {
+      section: Section::ChunkBody,
+      chunk_idx: Some(chunk_idx),
+      bit_idx: body_start * 8,
+    };
+    // constructing the body decompressor validates the prefixes' codes
+    let cbd = ChunkBodyDecompressor::new(&meta, &flags)
+      .map_err(|e| e.at(body_position.clone()))?;
+    check_chunk_body(bytes, &flags, body_start, meta.compressed_body_size)
+      .map_err(|e| e.at(body_position))?;
+    reader.seek(cbd.bits_remaining());
+    chunk_idx += 1;
+  }
+
+  let end = reader.aligned_byte_idx()?;
+  if end < bytes.len() {
+    return Err(QCompressError::corruption(format!(
+      "found {} extra bytes after the end of the file",
+      bytes.len() - end,
+    )));
+  }
+  if flags.use_file_checksum {
+    let checksummed_size = end - BYTES_PER_FILE_CHECKSUM;
+    let expected = read_checksum(&bytes[checksummed_size..end]);
+    let actual = checksum::crc32(&bytes[..checksummed_size]);
+    if expected != actual {
+      return Err(QCompressError::corruption(format!(
+        "file checksum {:#010x} does not match expected {:#010x}",
+        actual,
+        expected,
+      )));
+    }
+  }
+  Ok(())
+}
+
+/// Checks that a complete .qco file is internally consistent without
+/// decoding any of its numbers.
+///
+/// This reads the header, flags, and every chunk's metadata, checking that
+/// each chunk's prefixes have valid bounds and Huffman codes,
+/// that each chunk body fits in the file,
+/// that the file ends exactly after its footer,
+/// and that any chunk or file checksums match.
+/// The data type is read from the file's header byte.
+/// ```
+/// use q_compress::Compressor;
+/// use q_compress::verify::check_structure;
+///
+/// let bytes = Compressor::<i64>::default().simple_compress(&[1, 2, 3]);
+/// assert!(check_structure(&bytes).is_ok());
+/// assert!(check_structure(&bytes[..bytes.len() - 1]).is_err());
+/// ```
+/// Will return an error if the file is corrupt or incomplete, if it has
+/// bytes after its end, or if its data type is unknown or requires the
+/// `timestamps_96` feature.
+pub fn check_structure(bytes: &[u8]) -> QCompressResult<()> {
+  match DataType::from_file_bytes(bytes)? {
+    DataType::Bool => check_structure_as::<bool>(bytes),
+    DataType::F32 => check_structure_as::<f32>(bytes),
+    DataType::F64 => check_structure_as::<f64>(bytes),
+    DataType::I16 => check_structure_as::<i16>(bytes),
+    DataType::I32 => check_structure_as::<i32>(bytes),
+    DataType::I64 => check_structure_as::<i64>(bytes),
+    DataType::TimestampMicros => check_structure_as::<TimestampMicros>(bytes),
+    DataType::TimestampNanos => check_structure_as::<TimestampNanos>(bytes),
+    DataType::U16 => check_structure_as::<u16>(bytes),
+    DataType::U32 => check_structure_as::<u32>(bytes),
+    DataType::U64 => check_structure_as::<u64>(bytes),
+    #[cfg(feature = "timestamps_96")]
+    DataType::I128 => check_structure_as::<i128>(bytes),
+    #[cfg(feature = "timestamps_96")]
+    DataType::TimestampMicros96 => check_structure_as::<crate::data_types::TimestampMicros96>(bytes),
+    #[cfg(feature = "timestamps_96")]
+    DataType::TimestampNanos96 => check_structure_as::<crate::data_types::TimestampNanos96>(bytes),
+    #[cfg(feature = "timestamps_96")]
+    DataType::U128 => check_structure_as::<u128>(bytes),
+    #[allow(unreachable_patterns)]
+    data_type => Err(QCompressError::compatibility(format!(
+      "checking {:?} files requires the timestamps_96 feature",
+      data_type,
+    ))),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{Compressor, CompressorConfig};
+  use crate::errors::ErrorKind;
+  use crate::test_vectors::test_vectors;
+  use super::*;
+
+  fn compress(config: CompressorConfig) -> Vec<u8> {
+    let nums = (0..3000).map(|i| ((i * 37) % 1001) as f64 / 10.0).collect::<Vec<_>>();
+    let mut compressor = Compressor::<f64>::from_config(config);
+    compressor.header().unwrap();
+    for chunk in nums.chunks(1000) {
+      compressor.chunk(chunk).unwrap();
+    }
+    compressor.footer().unwrap();
+    compressor.drain_bytes()
+  }
+
+  #[test]
+  fn test_check_structure_accepts_test_vectors() {
+    for vector in test_vectors().unwrap() {
+      check_structure(&vector.compressed).unwrap();
+    }
+  }
+
+  #[test]
+  fn test_check_structure_rejects_truncation_and_extra_bytes() {
+    let bytes = compress(CompressorConfig::default());
+    check_structure(&bytes).unwrap();
+    for len in [3, 10, bytes.len() / 2, bytes.len() - 1] {
+      assert!(check_structure(&bytes[..len]).is_err(), "{}", len);
+    }
+    let mut extended = bytes.clone();
+    extended.push(0);
+    let err = check_structure(&extended).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Corruption));
+  }
+
+  #[test]
+  fn test_check_structure_checksums() {
+    let bytes = compress(
+      CompressorConfig::default()
+        .with_use_chunk_checksums(true)
+        .with_use_file_checksum(true)
+    );
+    check_structure(&bytes).unwrap();
+
+    // corrupt the end of the last chunk body, which only a checksum catches
+    let mut corrupted = bytes.clone();
+    let idx = bytes.len() - 1 - BYTES_PER_FILE_CHECKSUM - BYTES_PER_CHUNK_CHECKSUM - 1;
+    corrupted[idx] ^= 1;
+    let err = check_structure(&corrupted).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Corruption));
+    let position = err.position.unwrap();
+    assert_eq!(position.section, Section::ChunkBody);
+    assert_eq!(position.chunk_idx, Some(2));
+
+    let mut corrupted = bytes;
+    let last = corrupted.len() - 1;
+    corrupted[last] ^= 1;
+    let err = check_structure(&corrupted).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Corruption));
+  }
+}
@@ -0,0 +1,43 @@
+use alloc::vec::Vec;
+
+use crate::data_types::NumberLike;
+
+// the XOR of each consecutive pair of numbers' unsigned representations
+pub fn xor_unsigneds<T: NumberLike>(nums: &[T]) -> Vec<T::Unsigned> {
+  nums.windows(2)
+    .map(|pair| pair[0].to_unsigned() ^ pair[1].to_unsigned())
+    .collect()
+}
+
+// Writes one number to each element of `dst`, XORing each of `u_xors` onto
+// `last`, the unsigned representation of the number before `dst` starts.
+pub fn reconstruct_nums_into<T: NumberLike>(
+  last: &mut T::Unsigned,
+  u_xors: &[T::Unsigned],
+  dst: &mut [T],
+) {
+  for (num, &u_xor) in dst.iter_mut().zip(u_xors) {
+    *last = *last ^ u_xor;
+    *num = T::from_unsigned(*last);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_reconstruct_in_batches() {
+    let nums = (0..100).map(|i| 20.0 + (i as f64 / 10.0).sin()).collect::<Vec<_>>();
+    let u_xors = xor_unsigneds(&nums);
+    assert_eq!(u_xors.len(), nums.len() - 1);
+    let mut last = nums[0].to_unsigned();
+    let mut reconstructed = vec![nums[0]];
+    for batch in u_xors.chunks(30) {
+      let start = reconstructed.len();
+      reconstructed.resize(start + batch.len(), 0.0);
+      reconstruct_nums_into(&mut last, batch, &mut reconstructed[start..]);
+    }
+    assert_eq!(reconstructed, nums);
+  }
+}
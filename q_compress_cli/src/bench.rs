@@ -0,0 +1,11 @@
+use anyhow::Result;
+
+use crate::compress;
+use crate::handlers;
+use crate::opt::BenchOpt;
+
+pub fn bench(opt: BenchOpt) -> Result<()> {
+  let (schema, dtype) = compress::infer_schema(&opt.input)?;
+  let handler = handlers::from_dtype(dtype);
+  handler.bench(&opt, &schema)
+}
@@ -0,0 +1,126 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use arrow::datatypes::Schema;
+
+use q_compress::{Compressor, CompressorConfig, Decompressor};
+use q_compress::data_types::NumberLike;
+
+use crate::arrow_number_like::ArrowNumberLike;
+use crate::compress_handler;
+use crate::handlers::HandlerImpl;
+use crate::opt::BenchOpt;
+use crate::utils;
+
+pub trait BenchHandler {
+  fn bench(&self, opt: &BenchOpt, schema: &Schema) -> Result<()>;
+}
+
+struct BenchResult {
+  size: usize,
+  compress_dt: Duration,
+  decompress_dt: Duration,
+}
+
+fn compress_chunked<T: NumberLike>(
+  nums: &[T],
+  config: CompressorConfig,
+  chunk_size: usize,
+) -> Result<Vec<u8>> {
+  let mut compressor = Compressor::<T>::from_config(config);
+  compressor.header()?;
+  for chunk in nums.chunks(chunk_size) {
+    compressor.chunk(chunk)?;
+  }
+  compressor.footer()?;
+  Ok(compressor.drain_bytes())
+}
+
+fn bench_config<T: NumberLike>(
+  nums: &[T],
+  config: CompressorConfig,
+  opt: &BenchOpt,
+) -> Result<BenchResult> {
+  let mut compress_dt = Duration::MAX;
+  let mut decompress_dt = Duration::MAX;
+  let mut size = 0;
+  for _ in 0..opt.iters.max(1) {
+    let t = Instant::now();
+    let bytes = compress_chunked(nums, config.clone(), opt.chunk_size)?;
+    compress_dt = compress_dt.min(t.elapsed());
+    size = bytes.len();
+
+    let t = Instant::now();
+    let mut decompressor = Decompressor::<T>::default();
+    decompressor.extend_bytes(&bytes);
+    let recovered = decompressor.simple_decompress()?;
+    decompress_dt = decompress_dt.min(t.elapsed());
+    if recovered.len() != nums.len() {
+      return Err(anyhow!(
+        "decompressed {} numbers but compressed {}",
+        recovered.len(),
+        nums.len(),
+      ));
+    }
+  }
+  Ok(BenchResult {
+    size,
+    compress_dt,
+    decompress_dt,
+  })
+}
+
+fn mb_per_sec(n_bytes: usize, dt: Duration) -> f64 {
+  n_bytes as f64 / dt.as_secs_f64().max(f64::MIN_POSITIVE) / 1.0E6
+}
+
+impl<T: ArrowNumberLike> BenchHandler for HandlerImpl<T> {
+  fn bench(&self, opt: &BenchOpt, schema: &Schema) -> Result<()> {
+    if !T::IS_ARROW {
+      return Err(anyhow!(
+        "data type {} not supported by arrow converters",
+        utils::dtype_name::<T>()
+      ));
+    }
+
+    let mut reader = compress_handler::new_column_reader::<T>(schema, &opt.input, opt.chunk_size)?;
+    let mut nums = Vec::new();
+    while let Some(batch_result) = reader.next_batch() {
+      nums.extend(batch_result?);
+    }
+    let uncompressed_size = T::PHYSICAL_BITS / 8 * nums.len();
+    println!(
+      "benchmarking {} {} numbers ({} bytes uncompressed)\n",
+      nums.len(),
+      utils::dtype_name::<T>(),
+      uncompressed_size,
+    );
+
+    println!(
+      "{:>11} {:>5} {:>5} {:>12} {:>8} {:>15} {:>17}",
+      "delta order", "level", "gcds", "size", "ratio", "compress MB/s", "decompress MB/s",
+    );
+    for &delta_encoding_order in &opt.delta_orders {
+      for &level in &opt.levels {
+        for use_gcds in [true, false] {
+          let config = CompressorConfig::default()
+            .with_delta_encoding_order(delta_encoding_order)
+            .with_compression_level(level)
+            .with_use_gcds(use_gcds);
+          let res = bench_config(&nums, config, opt)?;
+          println!(
+            "{:>11} {:>5} {:>5} {:>12} {:>8.3} {:>15.1} {:>17.1}",
+            delta_encoding_order,
+            level,
+            use_gcds,
+            res.size,
+            uncompressed_size as f64 / res.size as f64,
+            mb_per_sec(uncompressed_size, res.compress_dt),
+            mb_per_sec(uncompressed_size, res.decompress_dt),
+          );
+        }
+      }
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,24 @@
+use anyhow::{anyhow, Result};
+
+use crate::opt::CatOpt;
+use crate::{handlers, utils};
+
+pub fn cat(opt: CatOpt) -> Result<()> {
+  let first_path = opt.in_paths.first()
+    .ok_or_else(|| anyhow!("no input files given"))?;
+  let header_byte = utils::read_header_byte(first_path)?;
+  for path in &opt.in_paths[1..] {
+    let other_byte = utils::read_header_byte(path)?;
+    if other_byte != header_byte {
+      return Err(anyhow!(
+        "{:?} has data type byte {} but {:?} has {}",
+        path,
+        other_byte,
+        first_path,
+        header_byte,
+      ));
+    }
+  }
+  let handler = handlers::from_header_byte(header_byte)?;
+  handler.cat(&opt)
+}
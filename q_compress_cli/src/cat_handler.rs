@@ -0,0 +1,191 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use q_compress::{Decompressor, Flags};
+use q_compress::data_types::NumberLike;
+use q_compress::errors::{ErrorKind, QCompressResult};
+
+use crate::handlers::HandlerImpl;
+use crate::opt::CatOpt;
+use crate::utils;
+
+const READ_SIZE: usize = 1 << 16;
+const BYTES_PER_CHECKSUM: usize = 4;
+
+pub trait CatHandler {
+  fn cat(&self, opt: &CatOpt) -> Result<()>;
+}
+
+// writes bytes while keeping a running CRC32 for the file checksum
+struct ChecksumWriter<W: Write> {
+  inner: W,
+  hasher: crc32fast::Hasher,
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let n = self.inner.write(buf)?;
+    self.hasher.update(&buf[..n]);
+    Ok(n)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+// Reads a .qco file's sections as raw bytes, parsing only the header and
+// chunk metadata.
+struct RawQcoReader<T: NumberLike> {
+  reader: BufReader<File>,
+  // bytes read from the file but not yet consumed
+  buf: Vec<u8>,
+  header_decompressor: Decompressor<T>,
+  header_bytes: Vec<u8>,
+  flags: Flags,
+}
+
+// runs `f` on a decompressor fed `buf`, reading more into `buf` until it has
+// enough data
+fn with_more_data<T, X, F>(
+  reader: &mut impl Read,
+  buf: &mut Vec<u8>,
+  base: &Decompressor<T>,
+  mut f: F,
+) -> Result<(X, Decompressor<T>)>
+where T: NumberLike, F: FnMut(&mut Decompressor<T>) -> QCompressResult<X> {
+  let mut read_size = READ_SIZE;
+  loop {
+    let mut decompressor = base.clone();
+    decompressor.extend_bytes(buf);
+    match f(&mut decompressor) {
+      Ok(x) => return Ok((x, decompressor)),
+      Err(e) if e.kind == ErrorKind::InsufficientData => {
+        let n_read = reader.take(read_size as u64).read_to_end(buf)?;
+        if n_read == 0 {
+          return Err(anyhow!("file ended early: {}", e));
+        }
+        read_size *= 2;
+      },
+      Err(e) => return Err(e.into()),
+    }
+  }
+}
+
+impl<T: NumberLike> RawQcoReader<T> {
+  fn open(path: &Path) -> Result<Self> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut buf = Vec::new();
+    let (flags, decompressor) = with_more_data(
+      &mut reader,
+      &mut buf,
+      &Decompressor::<T>::default(),
+      |d| d.header(),
+    )?;
+    let header_size = decompressor.bit_idx() / 8;
+    let header_bytes = buf.drain(..header_size).collect::<Vec<_>>();
+    let mut header_decompressor = Decompressor::<T>::default();
+    header_decompressor.extend_bytes(&header_bytes);
+    header_decompressor.header()?;
+    Ok(Self {
+      reader,
+      buf,
+      header_decompressor,
+      header_bytes,
+      flags,
+    })
+  }
+
+  // Copies the next chunk's bytes to `dst`, returning false at the footer.
+  fn copy_chunk<W: Write>(&mut self, dst: &mut W) -> Result<bool> {
+    let (maybe_meta, decompressor) = with_more_data(
+      &mut self.reader,
+      &mut self.buf,
+      &self.header_decompressor,
+      |d| d.chunk_metadata(),
+    )?;
+    let meta = match maybe_meta {
+      Some(meta) => meta,
+      None => return Ok(false),
+    };
+
+    let meta_size = decompressor.bit_idx() / 8 - self.header_bytes.len();
+    let mut remaining = meta_size + meta.compressed_body_size;
+    if self.flags.use_chunk_checksums {
+      remaining += BYTES_PER_CHECKSUM;
+    }
+    let from_buf = remaining.min(self.buf.len());
+    dst.write_all(&self.buf[..from_buf])?;
+    self.buf.drain(..from_buf);
+    remaining -= from_buf;
+    let n_copied = io::copy(&mut (&mut self.reader).take(remaining as u64), dst)?;
+    if n_copied < remaining as u64 {
+      return Err(anyhow!("file ended early in a chunk body"));
+    }
+    Ok(true)
+  }
+}
+
+impl<T: NumberLike> CatHandler for HandlerImpl<T> {
+  fn cat(&self, opt: &CatOpt) -> Result<()> {
+    let mut readers = Vec::new();
+    for path in &opt.in_paths {
+      let reader = RawQcoReader::<T>::open(path)?;
+      if let Some(first) = readers.first() {
+        let first: &RawQcoReader<T> = first;
+        if reader.flags != first.flags {
+          return Err(anyhow!(
+            "{:?} has flags {:?} but {:?} has flags {:?}",
+            path,
+            reader.flags,
+            opt.in_paths[0],
+            first.flags,
+          ));
+        }
+        if reader.header_bytes != first.header_bytes {
+          return Err(anyhow!(
+            "{:?} and {:?} have the same flags but different header metadata or shared prefixes",
+            path,
+            opt.in_paths[0],
+          ));
+        }
+      }
+      readers.push(reader);
+    }
+
+    // the files' chunks are interchangeable, so we copy them as-is
+    let file = utils::create_file(&opt.out_path, opt.overwrite)?;
+    let mut writer = ChecksumWriter {
+      inner: io::BufWriter::new(file),
+      hasher: crc32fast::Hasher::new(),
+    };
+    let first = &readers[0];
+    writer.write_all(&first.header_bytes)?;
+    let use_file_checksum = first.flags.use_file_checksum;
+    let mut n_chunks = 0;
+    for reader in &mut readers {
+      while reader.copy_chunk(&mut writer)? {
+        n_chunks += 1;
+      }
+    }
+
+    // the footer is the termination byte the parser stopped at
+    let termination_byte = readers.last().unwrap().buf[0];
+    writer.write_all(&[termination_byte])?;
+    if use_file_checksum {
+      let checksum = writer.hasher.clone().finalize();
+      writer.inner.write_all(&checksum.to_le_bytes())?;
+    }
+    writer.flush()?;
+    println!(
+      "concatenated {} chunks from {} files into {:?}",
+      n_chunks,
+      readers.len(),
+      opt.out_path,
+    );
+    Ok(())
+  }
+}
@@ -1,28 +1,34 @@
-use std::fs::File;
+use std::fs::{self, File};
+use std::io::{self, BufRead, Cursor, Read};
 use std::path::Path;
+use std::str::FromStr;
 
 use anyhow::anyhow;
 use anyhow::Result;
 use arrow::csv;
-use arrow::datatypes::{Field, Schema};
+use arrow::datatypes::{DataType as ArrowDataType, Field, Schema};
+use arrow::ipc::reader::FileReader as ArrowIpcReader;
 use parquet::file::reader::{FileReader, SerializedFileReader};
 
-use crate::utils;
+use crate::{compress_handler, handlers, npy, utils};
 use crate::dtype::DType;
-use crate::handlers;
-use crate::opt::CompressOpt;
+use crate::opt::{CompressOpt, InputOpt};
 
 const MAX_INFER_SCHEMA_RECORDS: usize = 1000;
 
-fn infer_csv_schema(path: &Path, opt: &CompressOpt) -> Result<Schema> {
+fn infer_raw_csv_schema(path: &Path, opt: &InputOpt, has_header: bool) -> Result<Schema> {
   // arrow API is kinda bad right now, so we have to convert the paths
   // back to strings
-  let inferred_schema = csv::infer_schema_from_files(
+  Ok(csv::infer_schema_from_files(
     &[path.to_str().unwrap().to_string()],
     opt.delimiter as u8,
     Some(MAX_INFER_SCHEMA_RECORDS),
-    opt.csv_has_header()?,
-  )?;
+    has_header,
+  )?)
+}
+
+fn infer_csv_schema(path: &Path, opt: &InputOpt) -> Result<Schema> {
+  let inferred_schema = infer_raw_csv_schema(path, opt, opt.csv_has_header()?)?;
 
   if let Some(dtype) = &opt.dtype {
     let mut fields = Vec::new();
@@ -45,7 +51,7 @@ fn infer_csv_schema(path: &Path, opt: &CompressOpt) -> Result<Schema> {
     let col_idx = utils::find_col_idx(&inferred_schema, opt);
     let arrow_dtype = inferred_schema.fields()[col_idx].data_type();
     let dtype = DType::from_arrow(arrow_dtype)?;
-    println!(
+    eprintln!(
       "using inferred CSV column data type: {:?}",
       dtype,
     );
@@ -53,7 +59,25 @@ fn infer_csv_schema(path: &Path, opt: &CompressOpt) -> Result<Schema> {
   }
 }
 
-fn infer_parquet_schema(path: &Path, opt: &CompressOpt) -> Result<Schema> {
+// checks that the optionally specified dtype matches the file's schema
+fn check_schema_dtype(schema: &Schema, opt: &InputOpt, format_name: &str) -> Result<()> {
+  let col_idx = utils::find_col_idx(schema, opt);
+  let field = &schema.fields()[col_idx];
+  if let Some(dtype) = opt.dtype {
+    let arrow_dtype = dtype.to_arrow()?;
+    if field.data_type() != &arrow_dtype {
+      return Err(anyhow!(
+        "optionally specified dtype {:?} did not match {} schema {:?}",
+        arrow_dtype,
+        format_name,
+        field.data_type(),
+      ));
+    }
+  }
+  Ok(())
+}
+
+fn infer_parquet_schema(path: &Path, opt: &InputOpt) -> Result<Schema> {
   let file = File::open(path)?;
   let reader = SerializedFileReader::new(file)?;
   let file_meta = reader.metadata().file_metadata();
@@ -62,30 +86,52 @@ fn infer_parquet_schema(path: &Path, opt: &CompressOpt) -> Result<Schema> {
     parquet_schema,
     file_meta.key_value_metadata(),
   )?;
-  let col_idx = utils::find_col_idx(&res, opt);
-  let field = &res.fields()[col_idx];
-  if let Some(dtype) = opt.dtype {
-    let arrow_dtype = dtype.to_arrow()?;
-    if field.data_type() != &arrow_dtype {
+  check_schema_dtype(&res, opt, "parquet")?;
+  Ok(res)
+}
+
+fn infer_arrow_schema(path: &Path, opt: &InputOpt) -> Result<Schema> {
+  let reader = ArrowIpcReader::try_new(File::open(path)?)?;
+  let res = reader.schema().as_ref().clone();
+  check_schema_dtype(&res, opt, "arrow")?;
+  Ok(res)
+}
+
+// The schema is a single column named after the array, with the data type
+// mapped from the NumPy dtype.
+fn infer_npy_schema(path: &Path, opt: &InputOpt) -> Result<(Schema, DType)> {
+  if opt.col_idx.is_some() {
+    return Err(anyhow!("--col-idx is not supported for NumPy input; use --col-name"));
+  }
+  let array = npy::open(path, opt.col_name.as_deref())?;
+  let dtype = array.header.dtype;
+  if let Some(specified_dtype) = opt.dtype {
+    if specified_dtype != dtype {
       return Err(anyhow!(
-        "optionally specified dtype {:?} did not match parquet schema {:?}",
-        arrow_dtype,
-        field.data_type(),
+        "optionally specified dtype {:?} did not match NumPy dtype {:?}",
+        specified_dtype,
+        dtype,
       ));
     }
   }
-  Ok(res)
+  let schema = Schema::new(vec![Field::new(&array.name, dtype.to_arrow()?, false)]);
+  Ok((schema, dtype))
 }
 
-pub fn compress(opt: CompressOpt) -> Result<()> {
-  let schema = match (&opt.csv_path, &opt.parquet_path) {
-    (Some(csv_path), None) => infer_csv_schema(csv_path, &opt),
-    (None, Some(parquet_path)) => infer_parquet_schema(parquet_path, &opt),
+// infers the schema of the input file and the dtype of the selected column
+pub fn infer_schema(opt: &InputOpt) -> Result<(Schema, DType)> {
+  let schema = match (&opt.csv_path, &opt.parquet_path, &opt.arrow_path, &opt.npy_path) {
+    (Some(csv_path), None, None, None) => infer_csv_schema(csv_path, opt),
+    (None, Some(parquet_path), None, None) => infer_parquet_schema(parquet_path, opt),
+    (None, None, Some(arrow_path), None) => infer_arrow_schema(arrow_path, opt),
+    (None, None, None, Some(npy_path)) => return infer_npy_schema(npy_path, opt),
     _ => Err(anyhow!(
-      "conflicting or incomplete dtype information: dtype={:?}, csv-path={:?}, parquet-path={:?}",
+      "conflicting or incomplete dtype information: dtype={:?}, csv-path={:?}, parquet-path={:?}, arrow-path={:?}, npy-path={:?}",
       opt.dtype,
       opt.csv_path,
       opt.parquet_path,
+      opt.arrow_path,
+      opt.npy_path,
     ))
   }?;
   let arrow_dtype = match (&opt.col_idx, &opt.col_name) {
@@ -94,6 +140,147 @@ pub fn compress(opt: CompressOpt) -> Result<()> {
     _ => Err(anyhow!("incomplete or incompatible col name and col idx")),
   }?;
   let dtype = DType::from_arrow(arrow_dtype)?;
+  Ok((schema, dtype))
+}
+
+// Builds a CSV schema from the first line of stdin, returning a reader that
+// replays that line followed by the rest of stdin.
+// Every column but the selected one is read as strings.
+fn infer_stdin_schema(opt: &InputOpt) -> Result<(Schema, DType, Box<dyn Read>)> {
+  let dtype = opt.dtype.ok_or_else(|| anyhow!("--dtype is required when reading from stdin"))?;
+  let mut first_line = Vec::new();
+  io::stdin().lock().read_until(b'\n', &mut first_line)?;
+  let line = String::from_utf8_lossy(&first_line);
+  let cells = line.trim_end_matches(&['\r', '\n'][..])
+    .split(opt.delimiter)
+    .collect::<Vec<_>>();
+  let has_header = opt.csv_has_header()?;
+  let names = cells.iter()
+    .enumerate()
+    .map(|(i, &cell)| if has_header {
+      cell.to_string()
+    } else {
+      format!("column_{}", i + 1)
+    })
+    .collect::<Vec<_>>();
+  let col_idx = match (&opt.col_idx, &opt.col_name) {
+    (Some(col_idx), _) => *col_idx,
+    (_, Some(col_name)) => names.iter()
+      .position(|name| name == col_name)
+      .ok_or_else(|| anyhow!("column {} not found in stdin header", col_name))?,
+    _ => unreachable!("stdin column defaults to the first"),
+  };
+  if col_idx >= names.len() {
+    return Err(anyhow!(
+      "column index {} is out of bounds for stdin with {} columns",
+      col_idx,
+      names.len(),
+    ));
+  }
+
+  let arrow_dtype = dtype.to_arrow()?;
+  let fields = names.iter()
+    .enumerate()
+    .map(|(i, name)| if i == col_idx {
+      Field::new(name, arrow_dtype.clone(), false)
+    } else {
+      Field::new(name, ArrowDataType::Utf8, true)
+    })
+    .collect::<Vec<_>>();
+  let reader = Cursor::new(first_line).chain(io::stdin());
+  Ok((Schema::new(fields), dtype, Box::new(reader)))
+}
+
+// Compresses each of the --col-names CSV columns into its own .qco file
+// in a single pass over the CSV.
+// Each column is named like `name` or `name:dtype` to override inference.
+fn compress_columns(opt: &CompressOpt) -> Result<()> {
+  let input = &opt.input;
+  if input.col_name.is_some() || input.col_idx.is_some() {
+    return Err(anyhow!("--col-names cannot be combined with --col-name or --col-idx"));
+  }
+  let csv_path = match (&input.csv_path, &input.parquet_path, &input.arrow_path, &input.npy_path, &input.stdin_format) {
+    (Some(csv_path), None, None, None, None) => csv_path,
+    _ => return Err(anyhow!("--col-names requires a --csv input")),
+  };
+  let qco_dir = opt.qco_path.as_ref()
+    .ok_or_else(|| anyhow!("--col-names requires an output directory"))?;
+  fs::create_dir_all(qco_dir)?;
+
+  let inferred_schema = infer_raw_csv_schema(csv_path, input, true)?;
+  // we only parse the selected columns
+  let mut fields = inferred_schema.fields().iter()
+    .map(|field| Field::new(field.name(), ArrowDataType::Utf8, true))
+    .collect::<Vec<_>>();
+  // resolve every column's data type before creating any files
+  let mut columns = Vec::new();
+  for col_spec in &opt.col_names {
+    let (col_name, specified_dtype) = match col_spec.split_once(':') {
+      Some((col_name, dtype_str)) => (col_name, Some(DType::from_str(dtype_str)?)),
+      None => (col_spec.as_str(), None),
+    };
+    let col_idx = inferred_schema.index_of(col_name)?;
+    let dtype = match specified_dtype {
+      Some(dtype) => dtype,
+      None => DType::from_arrow(inferred_schema.field(col_idx).data_type())
+        .map_err(|e| anyhow!("column {}: {}", col_name, e))?,
+    };
+    fields[col_idx] = Field::new(col_name, dtype.to_arrow()?, false);
+    columns.push((col_idx, col_name, dtype));
+  }
+
+  let mut compressors = Vec::new();
+  for (col_idx, col_name, dtype) in columns {
+    let qco_path = qco_dir.join(format!("{}.qco", col_name));
+    eprintln!("compressing column {} as {:?} into {:?}", col_name, dtype, qco_path);
+    let dst = Box::new(utils::create_file(&qco_path, opt.overwrite)?);
+    let compressor = handlers::from_dtype(dtype).column_compressor(opt, dst)?;
+    compressors.push((col_idx, compressor));
+  }
+
+  let schema = Schema::new(fields);
+  let reader = compress_handler::new_csv_reader(
+    &schema,
+    Box::new(File::open(csv_path)?),
+    input,
+    true,
+    opt.chunk_size,
+  )?;
+  for batch_result in reader {
+    let batch = batch_result?;
+    for (col_idx, compressor) in &mut compressors {
+      compressor.push_arrow(batch.column(*col_idx))?;
+    }
+  }
+  for (_, compressor) in &mut compressors {
+    compressor.finish()?;
+  }
+  Ok(())
+}
+
+pub fn compress(mut opt: CompressOpt) -> Result<()> {
+  utils::init_threads(opt.threads)?;
+  if !opt.col_names.is_empty() {
+    return compress_columns(&opt);
+  }
+
+  if opt.input.stdin_format.is_some() {
+    let input = &opt.input;
+    if input.csv_path.is_some() ||
+      input.parquet_path.is_some() ||
+      input.arrow_path.is_some() ||
+      input.npy_path.is_some() {
+      return Err(anyhow!("cannot read from both stdin and an input file"));
+    }
+    if opt.input.col_idx.is_none() && opt.input.col_name.is_none() {
+      opt.input.col_idx = Some(0);
+    }
+    let (schema, dtype, stdin) = infer_stdin_schema(&opt.input)?;
+    let handler = handlers::from_dtype(dtype);
+    return handler.compress(&opt, &schema, Some(stdin));
+  }
+
+  let (schema, dtype) = infer_schema(&opt.input)?;
   let handler = handlers::from_dtype(dtype);
-  handler.compress(&opt, &schema)
+  handler.compress(&opt, &schema, None)
 }
@@ -1,12 +1,15 @@
-use std::fs::{File, OpenOptions};
-use std::io::Write;
+use std::fs::File;
+use std::io::{self, Read, Write};
 use std::marker::PhantomData;
 use std::path::Path;
 use std::sync::Arc;
 
 use anyhow::{anyhow, Result};
 use arrow::csv::Reader as CsvReader;
+use arrow::array::ArrayRef;
 use arrow::datatypes::{Schema, SchemaRef};
+use arrow::error::ArrowError;
+use arrow::ipc::reader::FileReader as ArrowIpcReader;
 use arrow::record_batch::RecordBatch;
 use parquet::arrow::{ArrowReader, ParquetFileArrowReader};
 use parquet::arrow::arrow_reader::ParquetRecordBatchReader;
@@ -16,97 +19,237 @@ use q_compress::{Compressor, CompressorConfig};
 use q_compress::data_types::NumberLike;
 
 use crate::arrow_number_like::ArrowNumberLike;
+use crate::decompress_handler;
 use crate::handlers::HandlerImpl;
-use crate::opt::CompressOpt;
+use crate::npy;
+use crate::opt::{CompressOpt, InputOpt};
 use crate::utils;
 
 const AUTO_DELTA_LIMIT: usize = 1000;
 
 pub trait CompressHandler {
-  fn compress(&self, opt: &CompressOpt, schema: &Schema) -> Result<()>;
+  // `stdin` replaces the input file when streaming from stdin.
+  fn compress(
+    &self,
+    opt: &CompressOpt,
+    schema: &Schema,
+    stdin: Option<Box<dyn Read>>,
+  ) -> Result<()>;
+
+  fn column_compressor(
+    &self,
+    opt: &CompressOpt,
+    dst: Box<dyn Write>,
+  ) -> Result<Box<dyn ColumnCompressor>>;
+}
+
+pub fn check_arrow<T: ArrowNumberLike>() -> Result<()> {
+  if T::IS_ARROW {
+    Ok(())
+  } else {
+    Err(anyhow!(
+      "data type {} not supported by arrow converters",
+      utils::dtype_name::<T>()
+    ))
+  }
 }
 
 impl<T: ArrowNumberLike> CompressHandler for HandlerImpl<T> {
-  fn compress(&self, opt: &CompressOpt, schema: &Schema) -> Result<()> {
-    if !T::IS_ARROW {
-      return Err(anyhow!(
-        "data type {} not supported by arrow converters",
-        utils::dtype_name::<T>()
-      ));
+  fn compress(
+    &self,
+    opt: &CompressOpt,
+    schema: &Schema,
+    stdin: Option<Box<dyn Read>>,
+  ) -> Result<()> {
+    check_arrow::<T>()?;
+
+    let dst: Box<dyn Write> = match &opt.qco_path {
+      Some(qco_path) => Box::new(utils::create_file(qco_path, opt.overwrite)?),
+      None => Box::new(io::stdout()),
+    };
+
+    let mut reader: Box<dyn ColumnReader<T>> = match stdin {
+      Some(stdin) => Box::new(CsvColumnReader::from_reader(
+        schema,
+        stdin,
+        &opt.input,
+        opt.chunk_size,
+      )?),
+      None => new_column_reader(schema, &opt.input, opt.chunk_size)?,
+    };
+    let mut compressor = ChunkedCompressor::<T>::new(opt, dst);
+    while let Some(batch_result) = reader.next_batch() {
+      compressor.push(&batch_result?)?;
     }
+    compressor.finish()
+  }
 
-    let mut open_options = OpenOptions::new();
-    open_options.write(true);
-    if opt.overwrite {
-      open_options.create(true);
-      open_options.truncate(true);
-    } else {
-      open_options.create_new(true);
+  fn column_compressor(
+    &self,
+    opt: &CompressOpt,
+    dst: Box<dyn Write>,
+  ) -> Result<Box<dyn ColumnCompressor>> {
+    check_arrow::<T>()?;
+    Ok(Box::new(ChunkedCompressor::<T>::new(opt, dst)))
+  }
+}
+
+// Compresses a column of some data type as arrow arrays of it arrive.
+pub trait ColumnCompressor {
+  fn push_arrow(&mut self, array: &ArrayRef) -> Result<()>;
+  fn finish(&mut self) -> Result<()>;
+}
+
+// Writes chunks of numbers as they arrive, so memory use is bounded by the
+// chunk size.
+// If the delta encoding order isn't specified, it waits for the first
+// numbers to choose one, since stdin can only be read once.
+struct ChunkedCompressor<T: NumberLike> {
+  level: usize,
+  delta_encoding_order: Option<usize>,
+  use_gcds: bool,
+  chunk_size: usize,
+  threads: usize,
+  compressor: Option<Compressor<T>>,
+  num_buffer: Vec<T>,
+  dst: Box<dyn Write>,
+}
+
+impl<T: NumberLike> ChunkedCompressor<T> {
+  fn new(opt: &CompressOpt, dst: Box<dyn Write>) -> Self {
+    Self {
+      level: opt.level,
+      delta_encoding_order: opt.delta_encoding_order,
+      use_gcds: !opt.disable_gcds,
+      chunk_size: opt.chunk_size,
+      threads: opt.threads,
+      compressor: None,
+      num_buffer: Vec::new(),
+      dst,
     }
-    let mut file = open_options.open(&opt.qco_path)?;
+  }
 
-    let delta_encoding_order = if let Some(order) = opt.delta_encoding_order {
+  fn start(&mut self) -> Result<()> {
+    // messages go to stderr in case the .qco file goes to stdout
+    let delta_encoding_order = if let Some(order) = self.delta_encoding_order {
       order
     } else {
-      println!(
+      eprintln!(
         "automatically choosing delta encoding order based on first nums (specify --delta-order to skip)",
       );
-      let head_nums = head_nums::<T>(schema, opt)?;
-      let best_order = q_compress::auto_compressor_config(&head_nums, opt.level).delta_encoding_order;
-      println!("determined best delta encoding order: {}", best_order);
+      let head_nums = &self.num_buffer[..self.num_buffer.len().min(AUTO_DELTA_LIMIT)];
+      let best_order = q_compress::auto_compressor_config(head_nums, self.level).delta_encoding_order;
+      eprintln!("determined best delta encoding order: {}", best_order);
       best_order
     };
 
-    let config = CompressorConfig::default()
-      .with_compression_level(opt.level)
+    // from_level turns GCDs off at the fastest levels, but the CLI has always
+    // used them unless --disable-gcds is given
+    let config = CompressorConfig::from_level(self.level)
       .with_delta_encoding_order(delta_encoding_order)
-      .with_use_gcds(!opt.disable_gcds);
-    let mut compressor = Compressor::<T>::from_config(config);
-
+      .with_use_gcds(self.use_gcds);
+    let mut compressor = Compressor::<T>::try_from_config(config)?;
     compressor.header()?;
+    self.compressor = Some(compressor);
+    Ok(())
+  }
 
-    let mut reader = new_column_reader(schema, opt)?;
-    let mut num_buffer = Vec::new();
-    while let Some(batch_result) = reader.next_batch() {
-      let batch = batch_result?;
-      num_buffer.extend(&batch);
-      if num_buffer.len() >= opt.chunk_size {
-        write_chunk(&mut compressor, &num_buffer[..opt.chunk_size], &mut file)?;
-        num_buffer = num_buffer[opt.chunk_size..].to_vec();
+  // compresses a chunk for each thread at a time
+  fn write_full_chunks(&mut self) -> Result<()> {
+    let batch_size = self.chunk_size * self.threads;
+    if let Some(compressor) = &mut self.compressor {
+      while self.num_buffer.len() >= batch_size {
+        write_chunks(
+          compressor,
+          &self.num_buffer[..batch_size],
+          self.chunk_size,
+          &mut self.dst,
+        )?;
+        self.num_buffer.drain(..batch_size);
       }
     }
-    if !num_buffer.is_empty() {
-      write_chunk(&mut compressor, &num_buffer, &mut file)?;
+    Ok(())
+  }
+
+  fn push(&mut self, nums: &[T]) -> Result<()> {
+    self.num_buffer.extend(nums);
+    if self.compressor.is_none() &&
+      (self.delta_encoding_order.is_some() || self.num_buffer.len() >= AUTO_DELTA_LIMIT) {
+      self.start()?;
+    }
+    self.write_full_chunks()
+  }
+
+  fn finish(&mut self) -> Result<()> {
+    if self.compressor.is_none() {
+      self.start()?;
+    }
+    self.write_full_chunks()?;
+    let compressor = self.compressor.as_mut().unwrap();
+    if !self.num_buffer.is_empty() {
+      write_chunks(compressor, &self.num_buffer, self.chunk_size, &mut self.dst)?;
+      self.num_buffer.clear();
     }
 
     compressor.footer()?;
-    file.write_all(&compressor.drain_bytes())?;
+    self.dst.write_all(&compressor.drain_bytes())?;
+    self.dst.flush()?;
     Ok(())
   }
 }
 
-fn new_column_reader<T: ArrowNumberLike>(
+impl<T: ArrowNumberLike> ColumnCompressor for ChunkedCompressor<T> {
+  fn push_arrow(&mut self, array: &ArrayRef) -> Result<()> {
+    self.push(&utils::arrow_to_vec::<T>(array))
+  }
+
+  fn finish(&mut self) -> Result<()> {
+    ChunkedCompressor::finish(self)
+  }
+}
+
+pub fn new_column_reader<T: ArrowNumberLike>(
   schema: &Schema,
-  opt: &CompressOpt,
+  opt: &InputOpt,
+  batch_size: usize,
 ) -> Result<Box<dyn ColumnReader<T>>> {
-  let res: Box<dyn ColumnReader<T>> = match (&opt.csv_path, &opt.parquet_path) {
-    (Some(csv_path), None) => Box::new(CsvColumnReader::new(
+  let res: Box<dyn ColumnReader<T>> = match (&opt.csv_path, &opt.parquet_path, &opt.arrow_path, &opt.npy_path) {
+    (Some(csv_path), None, None, None) => Box::new(CsvColumnReader::new(
       schema,
       csv_path,
       opt,
+      batch_size,
     )?),
-    (None, Some(parquet_path)) => Box::new(ParquetColumnReader::new(
+    (None, Some(parquet_path), None, None) => Box::new(ParquetColumnReader::new(
       schema,
       parquet_path,
       opt,
+      batch_size,
+    )?),
+    (None, None, Some(arrow_path), None) => Box::new(ArrowIpcColumnReader::new(
+      schema,
+      arrow_path,
+      opt,
+      batch_size,
+    )?),
+    (None, None, None, Some(npy_path)) => Box::new(NpyColumnReader::new(
+      schema,
+      npy_path,
+      opt,
+      batch_size,
     )?),
     _ => unreachable!("should have already checked that file is uniquely specified")
   };
   Ok(res)
 }
 
-trait ColumnReader<T: ArrowNumberLike> {
-  fn new(schema: &Schema, path: &Path, opt: &CompressOpt) -> Result<Self> where Self: Sized;
+pub trait ColumnReader<T: ArrowNumberLike> {
+  fn new(
+    schema: &Schema,
+    path: &Path,
+    opt: &InputOpt,
+    batch_size: usize,
+  ) -> Result<Self> where Self: Sized;
   fn next_arrow_batch(&mut self) -> Option<arrow::error::Result<RecordBatch>>;
   fn col_idx(&self) -> usize;
 
@@ -125,13 +268,13 @@ struct ParquetColumnReader<T> {
 }
 
 impl<T: ArrowNumberLike> ColumnReader<T> for ParquetColumnReader<T> {
-  fn new(schema: &Schema, path: &Path, opt: &CompressOpt) -> Result<Self> {
+  fn new(schema: &Schema, path: &Path, opt: &InputOpt, batch_size: usize) -> Result<Self> {
     let reader = SerializedFileReader::new(File::open(path)?)?;
     let mut arrow_reader = ParquetFileArrowReader::new(Arc::new(reader));
     let col_idx = utils::find_col_idx(schema, opt);
     let batch_reader = arrow_reader.get_record_reader_by_columns(
       vec![col_idx],
-      opt.chunk_size,
+      batch_size,
     )?;
     Ok(Self {
       batch_reader,
@@ -149,24 +292,71 @@ impl<T: ArrowNumberLike> ColumnReader<T> for ParquetColumnReader<T> {
   }
 }
 
+// Arrow IPC files are already split into record batches, so we read them
+// as they are instead of in batches of our own size.
+struct ArrowIpcColumnReader<T> {
+  ipc_reader: ArrowIpcReader<File>,
+  col_idx: usize,
+  phantom: PhantomData<T>,
+}
+
+impl<T: ArrowNumberLike> ColumnReader<T> for ArrowIpcColumnReader<T> {
+  fn new(schema: &Schema, path: &Path, opt: &InputOpt, _batch_size: usize) -> Result<Self> {
+    Ok(Self {
+      ipc_reader: ArrowIpcReader::try_new(File::open(path)?)?,
+      col_idx: utils::find_col_idx(schema, opt),
+      phantom: PhantomData,
+    })
+  }
+
+  fn next_arrow_batch(&mut self) -> Option<arrow::error::Result<RecordBatch>> {
+    self.ipc_reader.next()
+  }
+
+  fn col_idx(&self) -> usize {
+    self.col_idx
+  }
+}
+
 struct CsvColumnReader<T: ArrowNumberLike> {
-  csv_reader: CsvReader<File>,
+  csv_reader: CsvReader<Box<dyn Read>>,
   col_idx: usize,
   phantom: PhantomData<T>,
 }
 
-impl<T: ArrowNumberLike> ColumnReader<T> for CsvColumnReader<T> {
-  fn new(schema: &Schema, path: &Path, opt: &CompressOpt) -> Result<Self> where Self: Sized {
-    let csv_reader = CsvReader::from_reader(
-      File::open(path)?,
-      SchemaRef::new(schema.clone()),
+pub fn new_csv_reader(
+  schema: &Schema,
+  reader: Box<dyn Read>,
+  opt: &InputOpt,
+  has_header: bool,
+  batch_size: usize,
+) -> Result<CsvReader<Box<dyn Read>>> {
+  Ok(CsvReader::from_reader(
+    reader,
+    SchemaRef::new(schema.clone()),
+    has_header,
+    Some(opt.delimiter as u8),
+    batch_size,
+    None,
+    None,
+    Some(opt.timestamp_format.clone()),
+  ))
+}
+
+impl<T: ArrowNumberLike> CsvColumnReader<T> {
+  fn from_reader(
+    schema: &Schema,
+    reader: Box<dyn Read>,
+    opt: &InputOpt,
+    batch_size: usize,
+  ) -> Result<Self> {
+    let csv_reader = new_csv_reader(
+      schema,
+      reader,
+      opt,
       opt.csv_has_header()?,
-      Some(opt.delimiter as u8),
-      opt.chunk_size,
-      None,
-      None,
-      Some(opt.timestamp_format.clone()),
-    );
+      batch_size,
+    )?;
     let col_idx = utils::find_col_idx(schema, opt);
 
     Ok(Self {
@@ -175,6 +365,12 @@ impl<T: ArrowNumberLike> ColumnReader<T> for CsvColumnReader<T> {
       phantom: PhantomData,
     })
   }
+}
+
+impl<T: ArrowNumberLike> ColumnReader<T> for CsvColumnReader<T> {
+  fn new(schema: &Schema, path: &Path, opt: &InputOpt, batch_size: usize) -> Result<Self> {
+    Self::from_reader(schema, Box::new(File::open(path)?), opt, batch_size)
+  }
 
   fn next_arrow_batch(&mut self) -> Option<arrow::error::Result<RecordBatch>> {
     self.csv_reader.next()
@@ -185,30 +381,78 @@ impl<T: ArrowNumberLike> ColumnReader<T> for CsvColumnReader<T> {
   }
 }
 
-fn write_chunk<T: NumberLike>(
-  compressor: &mut Compressor<T>,
-  nums: &[T],
-  file: &mut File,
-) -> Result<()> {
-  compressor.chunk(nums)?;
-  file.write_all(&compressor.drain_bytes())?;
-  Ok(())
+struct NpyColumnReader<T: ArrowNumberLike> {
+  array: npy::NpyArray,
+  n_remaining: usize,
+  batch_size: usize,
+  phantom: PhantomData<T>,
 }
 
-fn head_nums<T: ArrowNumberLike>(
-  schema: &Schema,
-  opt: &CompressOpt,
-) -> Result<Vec<T>> {
-  let mut reader = new_column_reader::<T>(schema, opt)?;
-  let mut head_nums = Vec::with_capacity(AUTO_DELTA_LIMIT);
-  while let Some(batch_result) = reader.next_batch() {
-    head_nums.extend(batch_result?);
-    if head_nums.len() >= AUTO_DELTA_LIMIT {
-      break;
+impl<T: ArrowNumberLike> NpyColumnReader<T> {
+  fn read_nums(&mut self) -> Option<Result<Vec<T>>> {
+    if self.n_remaining == 0 {
+      return None;
+    }
+    let n = self.n_remaining.min(self.batch_size);
+    self.n_remaining -= n;
+    let item_size = T::PHYSICAL_BITS / 8;
+    let mut bytes = vec![0; n * item_size];
+    if let Err(e) = self.array.data.read_exact(&mut bytes) {
+      return Some(Err(e.into()));
     }
+    let big_endian = self.array.header.big_endian;
+    Some(bytes.chunks(item_size)
+      .map(|item_bytes| {
+        let mut be_bytes = item_bytes.to_vec();
+        if !big_endian {
+          be_bytes.reverse();
+        }
+        Ok(T::from_bytes(be_bytes)?)
+      })
+      .collect())
   }
-  if head_nums.len() > AUTO_DELTA_LIMIT {
-    head_nums = head_nums[0..AUTO_DELTA_LIMIT].to_vec();
+}
+
+impl<T: ArrowNumberLike> ColumnReader<T> for NpyColumnReader<T> {
+  fn new(_schema: &Schema, path: &Path, opt: &InputOpt, batch_size: usize) -> Result<Self> {
+    let array = npy::open(path, opt.col_name.as_deref())?;
+    let n_remaining = array.header.n;
+    Ok(Self {
+      array,
+      n_remaining,
+      batch_size,
+      phantom: PhantomData,
+    })
   }
-  Ok(head_nums)
+
+  fn next_arrow_batch(&mut self) -> Option<arrow::error::Result<RecordBatch>> {
+    self.read_nums().map(|nums_result| {
+      nums_result
+        .and_then(|nums| decompress_handler::to_record_batch(&nums, &self.array.name))
+        .map_err(|e| ArrowError::ExternalError(e.into()))
+    })
+  }
+
+  fn col_idx(&self) -> usize {
+    0
+  }
+
+  fn next_batch(&mut self) -> Option<Result<Vec<T>>> {
+    self.read_nums()
+  }
+}
+
+fn write_chunks<T: NumberLike>(
+  compressor: &mut Compressor<T>,
+  nums: &[T],
+  chunk_size: usize,
+  dst: &mut dyn Write,
+) -> Result<()> {
+  if nums.len() > chunk_size {
+    compressor.chunks_parallel(nums, chunk_size)?;
+  } else {
+    compressor.chunk(nums)?;
+  }
+  dst.write_all(&compressor.drain_bytes())?;
+  Ok(())
 }
@@ -1,13 +1,57 @@
-use std::fs;
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read};
+use std::thread;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
-use crate::opt::DecompressOpt;
+use crate::opt::{DecompressOpt, HeadTailOpt};
+use crate::qco_reader::FOLLOW_POLL_INTERVAL;
 use crate::{handlers, utils};
 
 pub fn decompress(opt: DecompressOpt) -> Result<()> {
-  let bytes = fs::read(&opt.qco_path)?;
-  let header_byte = utils::get_header_byte(&bytes)?;
+  utils::init_threads(opt.threads)?;
+  let mut src: Box<dyn Read> = match &opt.qco_path {
+    Some(qco_path) => Box::new(BufReader::new(File::open(qco_path)?)),
+    None if opt.follow => return Err(anyhow!("--follow requires a .qco file path")),
+    None => Box::new(io::stdin()),
+  };
+  // peek at the data type byte, then hand the handler all the bytes again
+  let mut head = Vec::with_capacity(5);
+  loop {
+    (&mut src).take(5 - head.len() as u64).read_to_end(&mut head)?;
+    if head.len() == 5 || !opt.follow {
+      break;
+    }
+    thread::sleep(FOLLOW_POLL_INTERVAL);
+  }
+  let header_byte = utils::get_header_byte(&head)?;
   let handler = handlers::from_header_byte(header_byte)?;
-  handler.decompress(&opt, &bytes)
+  handler.decompress(&opt, Box::new(Cursor::new(head).chain(src)))
+}
+
+fn range_opt(opt: &HeadTailOpt, start: usize) -> DecompressOpt {
+  DecompressOpt {
+    limit: Some(opt.n),
+    start,
+    end: None,
+    timestamp_format: opt.timestamp_format.clone(),
+    threads: 1,
+    follow: false,
+    qco_path: Some(opt.qco_path.clone()),
+  }
+}
+
+// decodes only the leading chunks that hold the first n numbers
+pub fn head(opt: HeadTailOpt) -> Result<()> {
+  decompress(range_opt(&opt, 0))
+}
+
+// counts numbers from chunk metadata first, so only the trailing chunks that
+// hold the last n numbers get decoded
+pub fn tail(opt: HeadTailOpt) -> Result<()> {
+  let header_byte = utils::read_header_byte(&opt.qco_path)?;
+  let handler = handlers::from_header_byte(header_byte)?;
+  let src = BufReader::new(File::open(&opt.qco_path)?);
+  let count = handler.count(Box::new(src))?;
+  decompress(range_opt(&opt, count.saturating_sub(opt.n)))
 }
@@ -1,113 +1,170 @@
-use std::io::Write;
+use std::io::Read;
 use std::marker::PhantomData;
 use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use arrow::array::PrimitiveArray;
 use arrow::datatypes::{Field, Schema};
 use arrow::datatypes::ArrowPrimitiveType;
 use arrow::record_batch::RecordBatch;
 use arrow::csv::WriterBuilder as CsvWriterBuilder;
+use rayon::prelude::*;
 
 use q_compress::Decompressor;
+use q_compress::errors::QCompressResult;
 
 use crate::arrow_number_like::ArrowNumberLike;
 use crate::handlers::HandlerImpl;
 use crate::opt::DecompressOpt;
+use crate::qco_reader::QcoReader;
 
 pub trait DecompressHandler {
-  fn decompress(&self, opt: &DecompressOpt, bytes: &[u8]) -> Result<()>;
+  fn decompress(&self, opt: &DecompressOpt, src: Box<dyn Read>) -> Result<()>;
+  // counts the numbers in the file without decoding any chunk bodies
+  fn count(&self, src: Box<dyn Read>) -> Result<usize>;
 }
 
 impl<T: ArrowNumberLike> DecompressHandler for HandlerImpl<T> {
-  fn decompress(&self, opt: &DecompressOpt, bytes: &[u8]) -> Result<()> {
-    let mut decompressor = Decompressor::<T>::default();
-    decompressor.write_all(bytes).unwrap();
-    decompressor.header()?;
+  fn decompress(&self, opt: &DecompressOpt, src: Box<dyn Read>) -> Result<()> {
+    let mut reader = QcoReader::<T, _>::new(src).with_follow(opt.follow);
+    reader.header()?;
 
     let mut writer = new_column_writer(opt)?;
-    let mut remaining_limit = opt.limit.unwrap_or(usize::MAX);
+    let end = match (opt.end, opt.limit) {
+      (Some(end), Some(limit)) => end.min(opt.start.saturating_add(limit)),
+      (Some(end), None) => end,
+      (None, Some(limit)) => opt.start.saturating_add(limit),
+      (None, None) => usize::MAX,
+    };
+    if end < opt.start {
+      return Err(anyhow!("end {} is before start {}", end, opt.start));
+    }
 
-    loop {
-      if remaining_limit == 0 {
-        break;
-      }
+    // index of the first number in the current chunk
+    let mut chunk_start = 0;
+    // chunk bodies to decode concurrently, with the range of each to write
+    let mut pending = Vec::new();
+    while chunk_start < end {
+      let n = match reader.chunk_metadata()? {
+        Some(meta) => meta.n,
+        None => break,
+      };
 
-      if decompressor.chunk_metadata()?.is_some() {
-        let nums = decompressor.chunk_body()?;
-        let num_slice = if nums.len() <= remaining_limit {
-          remaining_limit -= nums.len();
-          nums.as_slice()
-        } else {
-          let res = &nums[0..remaining_limit];
-          remaining_limit = 0;
-          res
-        };
-        writer.write(num_slice)?;
+      // skip bodies of chunks entirely before the start without decoding
+      if chunk_start + n <= opt.start {
+        reader.skip_chunk_body()?;
       } else {
-        break;
+        let lower = opt.start.saturating_sub(chunk_start);
+        let upper = (end - chunk_start).min(n);
+        if opt.threads > 1 {
+          pending.push((reader.split_chunk_body()?, lower, upper));
+          if pending.len() == opt.threads {
+            write_parallel(&mut pending, writer.as_mut())?;
+          }
+        } else {
+          let nums = reader.chunk_body()?;
+          writer.write(&nums[lower..upper])?;
+        }
       }
+      chunk_start += n;
     }
+    write_parallel(&mut pending, writer.as_mut())?;
 
     writer.close()?;
     Ok(())
   }
+
+  fn count(&self, src: Box<dyn Read>) -> Result<usize> {
+    let mut reader = QcoReader::<T, _>::new(src);
+    reader.header()?;
+    let mut count = 0;
+    while let Some(meta) = reader.chunk_metadata()? {
+      reader.skip_chunk_body()?;
+      count += meta.n;
+    }
+    Ok(count)
+  }
+}
+
+fn write_parallel<T: ArrowNumberLike>(
+  pending: &mut Vec<(Decompressor<T>, usize, usize)>,
+  writer: &mut dyn ColumnWriter<T>,
+) -> Result<()> {
+  let numss = pending.par_iter_mut()
+    .map(|(decompressor, _, _)| decompressor.chunk_body())
+    .collect::<QCompressResult<Vec<_>>>()?;
+  for (nums, (_, lower, upper)) in numss.iter().zip(pending.iter()) {
+    writer.write(&nums[*lower..*upper])?;
+  }
+  pending.clear();
+  Ok(())
 }
 
 fn new_column_writer<T: ArrowNumberLike>(opt: &DecompressOpt) -> Result<Box<dyn ColumnWriter<T>>> {
-  // eventually we'll likely have a txt writer and a parquet writer, etc.
   Ok(Box::new(StdoutWriter::from_opt(opt)))
 }
 
-trait ColumnWriter<T: ArrowNumberLike> {
-  fn from_opt(opt: &DecompressOpt) -> Self where Self: Sized;
+pub trait ColumnWriter<T: ArrowNumberLike> {
   fn write(&mut self, nums: &[T]) -> Result<()>;
   fn close(&mut self) -> Result<()>;
 }
 
+pub fn to_record_batch<T: ArrowNumberLike>(nums: &[T], col_name: &str) -> Result<RecordBatch> {
+  let schema = Schema::new(vec![
+    Field::new(col_name, T::ArrowPrimitive::DATA_TYPE, false)
+  ]);
+  let arrow_natives = nums.iter()
+    .map(|x| x.to_arrow());
+  let c0 = PrimitiveArray::<T::ArrowPrimitive>::from_iter_values(
+    arrow_natives
+  );
+  Ok(RecordBatch::try_new(
+    Arc::new(schema),
+    vec![Arc::new(c0)],
+  )?)
+}
+
+// formats each number on its own line
+pub fn to_text<T: ArrowNumberLike>(nums: &[T], timestamp_format: &str) -> Result<String> {
+  if T::IS_ARROW {
+    let batch = to_record_batch(nums, "c0")?;
+    let mut bytes = Vec::<u8>::new();
+    {
+      let mut writer = CsvWriterBuilder::new()
+        .has_headers(false)
+        .with_timestamp_format(timestamp_format.to_string())
+        .build(&mut bytes);
+      writer.write(&batch)?;
+    }
+    Ok(String::from_utf8(bytes)?)
+  } else {
+    let mut res = String::new();
+    for num in nums {
+      res.push_str(&num.to_string());
+      res.push('\n');
+    }
+    Ok(res)
+  }
+}
+
 #[derive(Default)]
 struct StdoutWriter<T: ArrowNumberLike> {
   timestamp_format: String,
   phantom: PhantomData<T>,
 }
 
-impl<T: ArrowNumberLike> ColumnWriter<T> for StdoutWriter<T> {
+impl<T: ArrowNumberLike> StdoutWriter<T> {
   fn from_opt(opt: &DecompressOpt) -> Self {
     Self {
       timestamp_format: opt.timestamp_format.clone(),
       ..Default::default()
     }
   }
+}
 
+impl<T: ArrowNumberLike> ColumnWriter<T> for StdoutWriter<T> {
   fn write(&mut self, nums: &[T]) -> Result<()> {
-    if T::IS_ARROW {
-      let schema = Schema::new(vec![
-        Field::new("c0", T::ArrowPrimitive::DATA_TYPE, false)
-      ]);
-      let arrow_natives = nums.iter()
-        .map(|x| x.to_arrow());
-      let c0 = PrimitiveArray::<T::ArrowPrimitive>::from_iter_values(
-        arrow_natives
-      );
-      let batch = RecordBatch::try_new(
-        Arc::new(schema),
-        vec![Arc::new(c0)],
-      )?;
-      let mut stdout_bytes = Vec::<u8>::new();
-      {
-        let mut writer = CsvWriterBuilder::new()
-          .has_headers(false)
-          .with_timestamp_format(self.timestamp_format.clone())
-          .build(&mut stdout_bytes);
-          // &mut stdout_bytes);
-        writer.write(&batch)?;
-      }
-      print!("{}", String::from_utf8(stdout_bytes)?);
-    } else {
-      for num in nums {
-        println!("{}", num);
-      }
-    }
+    print!("{}", to_text(nums, &self.timestamp_format)?);
     Ok(())
   }
 
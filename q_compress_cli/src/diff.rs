@@ -0,0 +1,21 @@
+use std::convert::TryFrom;
+
+use anyhow::{anyhow, Result};
+
+use crate::dtype::DType;
+use crate::opt::DiffOpt;
+use crate::{handlers, utils};
+
+pub fn diff(opt: DiffOpt) -> Result<()> {
+  let header_byte = utils::read_header_byte(&opt.a_path)?;
+  let other_header_byte = utils::read_header_byte(&opt.b_path)?;
+  if header_byte != other_header_byte {
+    return Err(anyhow!(
+      "cannot compare data type {:?} with {:?}",
+      DType::try_from(header_byte)?,
+      DType::try_from(other_header_byte)?,
+    ));
+  }
+  let handler = handlers::from_header_byte(header_byte)?;
+  handler.diff(&opt)
+}
@@ -0,0 +1,190 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+
+use anyhow::{anyhow, Result};
+
+use q_compress::data_types::NumberLike;
+
+use crate::handlers::HandlerImpl;
+use crate::histogram;
+use crate::opt::DiffOpt;
+use crate::qco_reader::QcoReader;
+
+pub trait DiffHandler {
+  fn diff(&self, opt: &DiffOpt) -> Result<()>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct DiffSummary<T> {
+  a_n: usize,
+  b_n: usize,
+  n_diffs: usize,
+  first_diff: Option<(usize, T, T)>,
+  max_abs_diff: f64,
+}
+
+// yields the numbers of a .qco file one decoded chunk at a time
+struct NumStream<T: NumberLike, R: Read> {
+  reader: QcoReader<T, R>,
+  nums: Vec<T>,
+  pos: usize,
+  n_read: usize,
+  finished: bool,
+}
+
+impl<T: NumberLike, R: Read> NumStream<T, R> {
+  fn new(src: R) -> Result<Self> {
+    let mut reader = QcoReader::new(src);
+    reader.header()?;
+    Ok(Self {
+      reader,
+      nums: Vec::new(),
+      pos: 0,
+      n_read: 0,
+      finished: false,
+    })
+  }
+
+  // returns the unconsumed numbers, which are empty only at the end of file
+  fn remaining(&mut self) -> Result<&[T]> {
+    while self.pos == self.nums.len() && !self.finished {
+      if self.reader.chunk_metadata()?.is_none() {
+        self.finished = true;
+        break;
+      }
+      self.nums = self.reader.chunk_body()?;
+      self.pos = 0;
+    }
+    Ok(&self.nums[self.pos..])
+  }
+
+  fn consume(&mut self, n: usize) {
+    self.pos += n;
+    self.n_read += n;
+  }
+
+  // counts the remaining numbers from chunk metadata without decoding them
+  fn count_rest(mut self) -> Result<usize> {
+    let mut n = self.n_read + self.nums.len() - self.pos;
+    while !self.finished {
+      match self.reader.chunk_metadata()? {
+        Some(meta) => {
+          self.reader.skip_chunk_body()?;
+          n += meta.n;
+        },
+        None => self.finished = true,
+      }
+    }
+    Ok(n)
+  }
+}
+
+// Numbers count as equal only if they have identical bits, so NaNs with the
+// same bits match but -0.0 and 0.0 differ.
+fn diff_streams<T: NumberLike, R: Read>(a: R, b: R) -> Result<DiffSummary<T>> {
+  let mut a_stream = NumStream::<T, R>::new(a)?;
+  let mut b_stream = NumStream::<T, R>::new(b)?;
+  let mut n_diffs = 0;
+  let mut first_diff = None;
+  let mut max_abs_diff = 0.0_f64;
+  loop {
+    let idx = a_stream.n_read;
+    let a_nums = a_stream.remaining()?;
+    let b_nums = b_stream.remaining()?;
+    let n = a_nums.len().min(b_nums.len());
+    if n == 0 {
+      break;
+    }
+    for (i, (&x, &y)) in a_nums[..n].iter().zip(&b_nums[..n]).enumerate() {
+      if x.to_unsigned() != y.to_unsigned() {
+        n_diffs += 1;
+        if first_diff.is_none() {
+          first_diff = Some((idx + i, x, y));
+        }
+        let abs_diff = (histogram::approx_f64(x) - histogram::approx_f64(y)).abs();
+        max_abs_diff = max_abs_diff.max(abs_diff);
+      }
+    }
+    a_stream.consume(n);
+    b_stream.consume(n);
+  }
+
+  Ok(DiffSummary {
+    a_n: a_stream.count_rest()?,
+    b_n: b_stream.count_rest()?,
+    n_diffs,
+    first_diff,
+    max_abs_diff,
+  })
+}
+
+impl<T: NumberLike> DiffHandler for HandlerImpl<T> {
+  fn diff(&self, opt: &DiffOpt) -> Result<()> {
+    let open = |path| -> Result<Box<dyn Read>> {
+      Ok(Box::new(BufReader::new(File::open(path)?)))
+    };
+    let summary = diff_streams::<T, _>(open(&opt.a_path)?, open(&opt.b_path)?)?;
+
+    let n_compared = summary.a_n.min(summary.b_n);
+    println!("compared {} numbers", n_compared);
+    if summary.a_n != summary.b_n {
+      println!(
+        "lengths differ: {} vs {} numbers",
+        summary.a_n,
+        summary.b_n,
+      );
+    }
+    println!("differences: {}", summary.n_diffs);
+    if let Some((idx, x, y)) = &summary.first_diff {
+      println!("first difference at index {}: {} vs {}", idx, x, y);
+      println!("max absolute difference: {}", summary.max_abs_diff);
+    }
+
+    if summary.n_diffs > 0 || summary.a_n != summary.b_n {
+      return Err(anyhow!("{:?} and {:?} differ", opt.a_path, opt.b_path));
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use q_compress::Compressor;
+
+  use super::*;
+
+  fn compress(chunks: &[&[f64]]) -> Result<Vec<u8>> {
+    let mut compressor = Compressor::<f64>::default();
+    compressor.header()?;
+    for chunk in chunks {
+      compressor.chunk(chunk)?;
+    }
+    compressor.footer()?;
+    Ok(compressor.drain_bytes())
+  }
+
+  #[test]
+  fn test_diff_across_chunk_boundaries() -> Result<()> {
+    let nums = (0..100).map(|i| i as f64).collect::<Vec<_>>();
+    let mut other = nums.clone();
+    other[40] = 41.5;
+    other[70] = 60.0;
+    other.push(100.0);
+    let a = compress(&[&nums[..50], &nums[50..]])?;
+    let b = compress(&[&other[..30], &other[30..]])?;
+
+    let summary = diff_streams::<f64, _>(a.as_slice(), b.as_slice())?;
+    assert_eq!(summary, DiffSummary {
+      a_n: 100,
+      b_n: 101,
+      n_diffs: 2,
+      first_diff: Some((40, 40.0, 41.5)),
+      max_abs_diff: 10.0,
+    });
+
+    let summary = diff_streams::<f64, _>(a.as_slice(), a.as_slice())?;
+    assert_eq!(summary.n_diffs, 0);
+    assert_eq!(summary.first_diff, None);
+    Ok(())
+  }
+}
@@ -0,0 +1,10 @@
+use anyhow::Result;
+
+use crate::opt::ExportOpt;
+use crate::{handlers, utils};
+
+pub fn export(opt: ExportOpt) -> Result<()> {
+  let header_byte = utils::read_header_byte(&opt.qco_path)?;
+  let handler = handlers::from_header_byte(header_byte)?;
+  handler.export(&opt)
+}
@@ -0,0 +1,193 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::marker::PhantomData;
+
+use anyhow::Result;
+use arrow::ipc::writer::FileWriter as ArrowIpcWriter;
+use parquet::arrow::ArrowWriter;
+
+use crate::arrow_number_like::ArrowNumberLike;
+use crate::compress_handler::check_arrow;
+use crate::decompress_handler::{self, ColumnWriter};
+use crate::handlers::HandlerImpl;
+use crate::opt::{ExportFormat, ExportOpt};
+use crate::qco_reader::QcoReader;
+use crate::utils;
+
+pub trait ExportHandler {
+  fn export(&self, opt: &ExportOpt) -> Result<()>;
+}
+
+impl<T: ArrowNumberLike> ExportHandler for HandlerImpl<T> {
+  fn export(&self, opt: &ExportOpt) -> Result<()> {
+    let src = BufReader::new(File::open(&opt.qco_path)?);
+    let mut reader = QcoReader::<T, _>::new(src);
+    reader.header()?;
+
+    let file = utils::create_file(&opt.out_path, opt.overwrite)?;
+    let mut writer: Box<dyn ColumnWriter<T>> = match opt.format {
+      ExportFormat::Csv => Box::new(CsvFileWriter::new(opt, file)?),
+      ExportFormat::Jsonl => Box::new(JsonlWriter::new(opt, file)),
+      ExportFormat::Parquet => Box::new(ParquetFileWriter::new(opt, file)?),
+      ExportFormat::Arrow => Box::new(ArrowIpcFileWriter::new(opt, file)?),
+    };
+    while reader.chunk_metadata()?.is_some() {
+      writer.write(&reader.chunk_body()?)?;
+    }
+    writer.close()
+  }
+}
+
+struct CsvFileWriter<T: ArrowNumberLike> {
+  dst: BufWriter<File>,
+  timestamp_format: String,
+  phantom: PhantomData<T>,
+}
+
+impl<T: ArrowNumberLike> CsvFileWriter<T> {
+  fn new(opt: &ExportOpt, file: File) -> Result<Self> {
+    let mut dst = BufWriter::new(file);
+    writeln!(dst, "{}", opt.col_name)?;
+    Ok(Self {
+      dst,
+      timestamp_format: opt.timestamp_format.clone(),
+      phantom: PhantomData,
+    })
+  }
+}
+
+impl<T: ArrowNumberLike> ColumnWriter<T> for CsvFileWriter<T> {
+  fn write(&mut self, nums: &[T]) -> Result<()> {
+    let text = decompress_handler::to_text(nums, &self.timestamp_format)?;
+    self.dst.write_all(text.as_bytes())?;
+    Ok(())
+  }
+
+  fn close(&mut self) -> Result<()> {
+    self.dst.flush()?;
+    Ok(())
+  }
+}
+
+struct JsonlWriter<T: ArrowNumberLike> {
+  dst: BufWriter<File>,
+  // the column name as a JSON string
+  key: String,
+  timestamp_format: String,
+  phantom: PhantomData<T>,
+}
+
+impl<T: ArrowNumberLike> JsonlWriter<T> {
+  fn new(opt: &ExportOpt, file: File) -> Self {
+    Self {
+      dst: BufWriter::new(file),
+      key: serde_json::Value::from(opt.col_name.as_str()).to_string(),
+      timestamp_format: opt.timestamp_format.clone(),
+      phantom: PhantomData,
+    }
+  }
+}
+
+// Numbers are written as JSON numbers, except that NaN and infinities become
+// null and formatted timestamps become strings.
+fn json_value(text: &str) -> String {
+  match text.parse::<f64>() {
+    Ok(x) if x.is_finite() => text.to_string(),
+    Ok(_) => "null".to_string(),
+    Err(_) => serde_json::Value::from(text).to_string(),
+  }
+}
+
+impl<T: ArrowNumberLike> ColumnWriter<T> for JsonlWriter<T> {
+  fn write(&mut self, nums: &[T]) -> Result<()> {
+    let text = decompress_handler::to_text(nums, &self.timestamp_format)?;
+    for line in text.lines() {
+      writeln!(self.dst, "{{{}:{}}}", self.key, json_value(line))?;
+    }
+    Ok(())
+  }
+
+  fn close(&mut self) -> Result<()> {
+    self.dst.flush()?;
+    Ok(())
+  }
+}
+
+struct ParquetFileWriter<T: ArrowNumberLike> {
+  // taken when closing
+  writer: Option<ArrowWriter<File>>,
+  col_name: String,
+  phantom: PhantomData<T>,
+}
+
+impl<T: ArrowNumberLike> ParquetFileWriter<T> {
+  fn new(opt: &ExportOpt, file: File) -> Result<Self> {
+    check_arrow::<T>()?;
+    let schema = decompress_handler::to_record_batch::<T>(&[], &opt.col_name)?.schema();
+    Ok(Self {
+      writer: Some(ArrowWriter::try_new(file, schema, None)?),
+      col_name: opt.col_name.clone(),
+      phantom: PhantomData,
+    })
+  }
+}
+
+impl<T: ArrowNumberLike> ColumnWriter<T> for ParquetFileWriter<T> {
+  fn write(&mut self, nums: &[T]) -> Result<()> {
+    let batch = decompress_handler::to_record_batch(nums, &self.col_name)?;
+    self.writer.as_mut().unwrap().write(&batch)?;
+    Ok(())
+  }
+
+  fn close(&mut self) -> Result<()> {
+    if let Some(writer) = self.writer.take() {
+      writer.close()?;
+    }
+    Ok(())
+  }
+}
+
+struct ArrowIpcFileWriter<T: ArrowNumberLike> {
+  writer: ArrowIpcWriter<BufWriter<File>>,
+  col_name: String,
+  phantom: PhantomData<T>,
+}
+
+impl<T: ArrowNumberLike> ArrowIpcFileWriter<T> {
+  fn new(opt: &ExportOpt, file: File) -> Result<Self> {
+    check_arrow::<T>()?;
+    let schema = decompress_handler::to_record_batch::<T>(&[], &opt.col_name)?.schema();
+    Ok(Self {
+      writer: ArrowIpcWriter::try_new(BufWriter::new(file), &schema)?,
+      col_name: opt.col_name.clone(),
+      phantom: PhantomData,
+    })
+  }
+}
+
+impl<T: ArrowNumberLike> ColumnWriter<T> for ArrowIpcFileWriter<T> {
+  fn write(&mut self, nums: &[T]) -> Result<()> {
+    let batch = decompress_handler::to_record_batch(nums, &self.col_name)?;
+    self.writer.write(&batch)?;
+    Ok(())
+  }
+
+  fn close(&mut self) -> Result<()> {
+    self.writer.finish()?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_json_value() {
+    assert_eq!(json_value("-1.5"), "-1.5");
+    assert_eq!(json_value("7"), "7");
+    assert_eq!(json_value("NaN"), "null");
+    assert_eq!(json_value("inf"), "null");
+    assert_eq!(json_value("2022-01-01T00:00:00"), "\"2022-01-01T00:00:00\"");
+  }
+}
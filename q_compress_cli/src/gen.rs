@@ -0,0 +1,147 @@
+use std::f64::consts::PI;
+use std::io::{BufWriter, Write};
+
+use anyhow::{anyhow, Result};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand_distr::{Distribution as _, Geometric, Normal, Pareto};
+
+use crate::dtype::DType;
+use crate::npy;
+use crate::opt::{Distribution, GenFormat, GenOpt};
+use crate::utils;
+
+// prime, so cosine periods don't line up with chunks
+const COSINE_PERIODS: f64 = 103.0;
+const SPARSE_PROBABILITY: f64 = 0.01;
+
+type Sampler = Box<dyn FnMut(&mut StdRng, usize) -> f64>;
+
+fn byte_size(dtype: DType) -> Result<usize> {
+  let res = match dtype {
+    DType::Bool => 1,
+    DType::I16 | DType::U16 => 2,
+    DType::F32 | DType::I32 | DType::U32 => 4,
+    DType::F64 | DType::I64 | DType::U64 => 8,
+    DType::TimestampMicros | DType::TimestampNanos => 8,
+    DType::I128 | DType::U128 => 16,
+    DType::TimestampMicros96 | DType::TimestampNanos96 => {
+      return Err(anyhow!("cannot generate data type {:?}", dtype));
+    }
+  };
+  Ok(res)
+}
+
+fn is_float(dtype: DType) -> bool {
+  matches!(dtype, DType::F32 | DType::F64)
+}
+
+// Integer types take the floor of the sample, saturating at their bounds.
+fn to_le_bytes(dtype: DType, x: f64) -> Vec<u8> {
+  match dtype {
+    DType::Bool => vec![(x.floor() != 0.0) as u8],
+    DType::F32 => (x.clamp(f32::MIN as f64, f32::MAX as f64) as f32).to_le_bytes().to_vec(),
+    DType::F64 => x.to_le_bytes().to_vec(),
+    DType::I16 => (x.floor() as i16).to_le_bytes().to_vec(),
+    DType::I32 => (x.floor() as i32).to_le_bytes().to_vec(),
+    DType::I64 | DType::TimestampMicros | DType::TimestampNanos =>
+      (x.floor() as i64).to_le_bytes().to_vec(),
+    DType::I128 => (x.floor() as i128).to_le_bytes().to_vec(),
+    DType::U16 => (x.floor() as u16).to_le_bytes().to_vec(),
+    DType::U32 => (x.floor() as u32).to_le_bytes().to_vec(),
+    DType::U64 => (x.floor() as u64).to_le_bytes().to_vec(),
+    DType::U128 => (x.floor() as u128).to_le_bytes().to_vec(),
+    DType::TimestampMicros96 | DType::TimestampNanos96 => unreachable!("rejected by byte_size"),
+  }
+}
+
+// Mirrors the distributions the benchmarks generate, returning a sample for
+// each index.
+fn sampler(opt: &GenOpt) -> Result<Sampler> {
+  let loc = opt.loc;
+  let scale = opt.scale.unwrap_or(match opt.distribution {
+    Distribution::Geometric => 2.0,
+    Distribution::Lomax => 1000.0,
+    _ => 1.0,
+  });
+  let n = opt.n as f64;
+  let res: Sampler = match opt.distribution {
+    Distribution::Normal => {
+      let normal = Normal::new(loc, scale)?;
+      Box::new(move |rng, _| normal.sample(rng))
+    },
+    Distribution::Geometric => {
+      // counts trials up to and including the first success
+      let geometric = Geometric::new(1.0 / scale)?;
+      Box::new(move |rng, _| loc + (geometric.sample(rng) + 1) as f64)
+    },
+    Distribution::Lomax => {
+      // a Pareto sample minus 1 is Lomax, which we rescale to the median
+      let pareto = Pareto::new(1.0, opt.shape)?;
+      let unscaled_median = 2.0_f64.powf(1.0 / opt.shape) - 1.0;
+      Box::new(move |rng, _| loc + (pareto.sample(rng) - 1.0) / unscaled_median * scale)
+    },
+    Distribution::Uniform => Box::new(move |rng, _| loc + scale * rng.gen::<f64>()),
+    Distribution::Constant => Box::new(move |_, _| loc),
+    Distribution::Extremes => Box::new(|rng, _| if rng.gen() { f64::MIN } else { f64::MAX }),
+    Distribution::Sparse => Box::new(move |rng, _| {
+      if rng.gen::<f64>() < SPARSE_PROBABILITY {
+        loc + scale
+      } else {
+        loc
+      }
+    }),
+    Distribution::Cosine => {
+      let period = n / COSINE_PERIODS;
+      Box::new(move |_, i| loc + scale * (i as f64 * 2.0 * PI / period).cos())
+    },
+    Distribution::NearLinear => {
+      let normal = Normal::new(0.0, 1.0)?;
+      Box::new(move |rng, i| loc + scale * (i as f64 + normal.sample(rng)))
+    },
+  };
+  Ok(res)
+}
+
+pub fn gen(opt: GenOpt) -> Result<()> {
+  let dtype = opt.dtype;
+  let size = byte_size(dtype)?;
+  let format = opt.format.unwrap_or_else(|| {
+    match opt.out_path.extension().and_then(|ext| ext.to_str()) {
+      Some("npy") => GenFormat::Npy,
+      _ => GenFormat::Bin,
+    }
+  });
+  let mut sample = sampler(&opt)?;
+  // uniform integers cover the type's whole range, which f64 can't
+  let uniform_bits = opt.distribution == Distribution::Uniform && !is_float(dtype);
+
+  // build the header first so unsupported data types don't leave a file
+  let mut header = Vec::new();
+  if format == GenFormat::Npy {
+    npy::write_header(&mut header, dtype, opt.n)?;
+  }
+  let mut writer = BufWriter::new(utils::create_file(&opt.out_path, opt.overwrite)?);
+  writer.write_all(&header)?;
+  let mut rng = StdRng::seed_from_u64(opt.seed);
+  let mut bits = vec![0; size];
+  for i in 0..opt.n {
+    if uniform_bits && dtype == DType::Bool {
+      writer.write_all(&[rng.gen::<bool>() as u8])?;
+    } else if uniform_bits {
+      rng.fill(bits.as_mut_slice());
+      writer.write_all(&bits)?;
+    } else {
+      writer.write_all(&to_le_bytes(dtype, sample(&mut rng, i)))?;
+    }
+  }
+  writer.flush()?;
+  println!(
+    "generated {} {:?} numbers from the {:?} distribution into {:?}",
+    opt.n,
+    dtype,
+    opt.distribution,
+    opt.out_path,
+  );
+  Ok(())
+}
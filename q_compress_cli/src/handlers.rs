@@ -6,10 +6,18 @@ use anyhow::Result;
 use q_compress::data_types::{TimestampMicros, TimestampNanos, TimestampMicros96, TimestampNanos96};
 
 use crate::arrow_number_like::ArrowNumberLike;
+use crate::bench_handler::BenchHandler;
+use crate::cat_handler::CatHandler;
 use crate::compress_handler::CompressHandler;
 use crate::decompress_handler::DecompressHandler;
+use crate::diff_handler::DiffHandler;
 use crate::dtype::DType;
+use crate::export_handler::ExportHandler;
 use crate::inspect_handler::InspectHandler;
+use crate::recompress_handler::RecompressHandler;
+use crate::stats_handler::StatsHandler;
+use crate::suggest_handler::SuggestHandler;
+use crate::verify_handler::VerifyHandler;
 
 fn new_boxed_handler<T: ArrowNumberLike>() -> Box<dyn Handler> {
   Box::new(HandlerImpl::<T>::default())
@@ -40,7 +48,9 @@ pub fn from_dtype(dtype: DType) -> Box<dyn Handler> {
   }
 }
 
-pub trait Handler: CompressHandler + DecompressHandler + InspectHandler {}
+pub trait Handler: BenchHandler + CatHandler + CompressHandler + DecompressHandler +
+  DiffHandler + ExportHandler + InspectHandler + RecompressHandler + StatsHandler +
+  SuggestHandler + VerifyHandler {}
 
 #[derive(Clone, Debug, Default)]
 pub struct HandlerImpl<T> {
@@ -0,0 +1,159 @@
+use q_compress::Prefix;
+use q_compress::data_types::NumberLike;
+
+const BAR_WIDTH: f64 = 50.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bin {
+  pub lower: f64,
+  pub upper: f64,
+  pub count: f64,
+}
+
+// Floats are approximated by their value and other numbers (e.g. timestamps)
+// by their signed integer representation.
+pub fn approx_f64<T: NumberLike>(x: T) -> f64 {
+  x.to_string()
+    .parse::<f64>()
+    .unwrap_or_else(|_| x.to_signed().to_string().parse::<f64>().unwrap_or(f64::NAN))
+}
+
+pub fn prefix_ranges<T: NumberLike>(prefixes: &[Prefix<T>]) -> Vec<(f64, f64, usize)> {
+  prefixes.iter()
+    .map(|p| (approx_f64(p.lower), approx_f64(p.upper), p.count))
+    .collect()
+}
+
+// Builds an equal-width histogram from (lower, upper, count) ranges,
+// assuming each range's count is spread uniformly over it.
+pub fn histogram(ranges: &[(f64, f64, usize)], n_bins: usize) -> Vec<Bin> {
+  let ranges = ranges.iter()
+    .filter(|(lower, upper, _)| lower.is_finite() && upper.is_finite())
+    .collect::<Vec<_>>();
+  if ranges.is_empty() || n_bins == 0 {
+    return Vec::new();
+  }
+
+  let min = ranges.iter().map(|r| r.0).fold(f64::INFINITY, f64::min);
+  let max = ranges.iter().map(|r| r.1).fold(f64::NEG_INFINITY, f64::max);
+  if min >= max {
+    let count = ranges.iter().map(|r| r.2).sum::<usize>();
+    return vec![Bin { lower: min, upper: max, count: count as f64 }];
+  }
+
+  let width = (max - min) / n_bins as f64;
+  let mut bins = (0..n_bins)
+    .map(|i| Bin {
+      lower: min + width * i as f64,
+      upper: min + width * (i + 1) as f64,
+      count: 0.0,
+    })
+    .collect::<Vec<_>>();
+  let bin_idx = |x: f64| (((x - min) / width) as usize).min(n_bins - 1);
+  for &&(lower, upper, count) in &ranges {
+    let count = count as f64;
+    if upper <= lower {
+      bins[bin_idx(lower)].count += count;
+      continue;
+    }
+    for bin in &mut bins[bin_idx(lower)..=bin_idx(upper)] {
+      let overlap = upper.min(bin.upper) - lower.max(bin.lower);
+      bin.count += count * overlap.max(0.0) / (upper - lower);
+    }
+  }
+  bins
+}
+
+// Approximates the `q`th quantile of the numbers described by
+// (lower, upper, count) ranges, with the same uniformity assumption.
+pub fn approx_quantile(ranges: &[(f64, f64, usize)], q: f64) -> Option<f64> {
+  let ranges = ranges.iter()
+    .filter(|(lower, upper, _)| lower.is_finite() && upper.is_finite())
+    .collect::<Vec<_>>();
+  let total = ranges.iter().map(|r| r.2).sum::<usize>() as f64;
+  if total == 0.0 {
+    return None;
+  }
+
+  let count_leq = |x: f64| ranges.iter()
+    .map(|&&(lower, upper, count)| {
+      let frac = if x >= upper {
+        1.0
+      } else if x < lower {
+        0.0
+      } else {
+        (x - lower) / (upper - lower)
+      };
+      frac * count as f64
+    })
+    .sum::<f64>();
+  let target = q.clamp(0.0, 1.0) * total;
+  let mut lo = ranges.iter().map(|r| r.0).fold(f64::INFINITY, f64::min);
+  let mut hi = ranges.iter().map(|r| r.1).fold(f64::NEG_INFINITY, f64::max);
+  // bisect for the smallest x with at least `target` numbers <= x
+  while lo < hi {
+    let mid = lo + (hi - lo) / 2.0;
+    if mid <= lo || mid >= hi {
+      break;
+    }
+    if count_leq(mid) >= target {
+      hi = mid;
+    } else {
+      lo = mid;
+    }
+  }
+  Some(if count_leq(lo) >= target { lo } else { hi })
+}
+
+pub fn print_histogram(bins: &[Bin], indent: &str) {
+  let max_count = bins.iter().map(|b| b.count).fold(0.0, f64::max);
+  for bin in bins {
+    let bar_len = if max_count > 0.0 {
+      (bin.count / max_count * BAR_WIDTH).round() as usize
+    } else {
+      0
+    };
+    println!(
+      "{}[{:.6e}, {:.6e}] {:>12.0} {}",
+      indent,
+      bin.lower,
+      bin.upper,
+      bin.count,
+      "#".repeat(bar_len),
+    );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_histogram_spreads_counts() {
+    let bins = histogram(&[(0.0, 10.0, 100), (5.0, 5.0, 7)], 2);
+    assert_eq!(bins.len(), 2);
+    assert_eq!(bins[0], Bin { lower: 0.0, upper: 5.0, count: 50.0 });
+    assert_eq!(bins[1], Bin { lower: 5.0, upper: 10.0, count: 57.0 });
+
+    let bins = histogram(&[(3.0, 3.0, 4)], 10);
+    assert_eq!(bins, vec![Bin { lower: 3.0, upper: 3.0, count: 4.0 }]);
+    assert!(histogram(&[], 10).is_empty());
+  }
+
+  #[test]
+  fn test_approx_quantile() {
+    let ranges = [(0.0, 10.0, 100), (20.0, 20.0, 100)];
+    assert_eq!(approx_quantile(&ranges, 0.0), Some(0.0));
+    assert!((approx_quantile(&ranges, 0.25).unwrap() - 5.0).abs() < 1e-9);
+    assert_eq!(approx_quantile(&ranges, 0.75), Some(20.0));
+    assert_eq!(approx_quantile(&ranges, 1.0), Some(20.0));
+    assert_eq!(approx_quantile(&[], 0.5), None);
+  }
+
+  #[test]
+  fn test_approx_f64() {
+    assert_eq!(approx_f64(-1.5_f32), -1.5);
+    assert_eq!(approx_f64(77_u64), 77.0);
+    assert_eq!(approx_f64(q_compress::data_types::TimestampMicros::new(5)), 5.0);
+  }
+}
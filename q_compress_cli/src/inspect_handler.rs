@@ -1,10 +1,12 @@
 use std::io::Write;
 use anyhow::Result;
+use serde_json::json;
 
-use q_compress::{Decompressor, Prefix, PrefixMetadata};
+use q_compress::{ChunkMetadata, Decompressor, Prefix, PrefixMetadata};
 use q_compress::data_types::NumberLike;
 
 use crate::handlers::HandlerImpl;
+use crate::histogram::{self, Bin};
 use crate::opt::InspectOpt;
 use crate::utils;
 
@@ -22,38 +24,147 @@ fn print_prefixes<T: NumberLike>(prefixes: &[Prefix<T>]) {
   }
 }
 
+struct ChunkInfo<T: NumberLike> {
+  meta: ChunkMetadata<T>,
+  metadata_size: usize,
+}
+
+fn n_prefixes<T: NumberLike>(meta: &ChunkMetadata<T>) -> usize {
+  match &meta.prefix_metadata {
+    PrefixMetadata::Simple { prefixes } => prefixes.len(),
+    PrefixMetadata::Delta { prefixes, .. } => prefixes.len(),
+    PrefixMetadata::Xor { prefixes, .. } => prefixes.len(),
+    PrefixMetadata::Raw { .. } => 0,
+    PrefixMetadata::FloatSplit { exponent_prefixes, mantissa_prefixes, .. } => {
+      exponent_prefixes.len() + mantissa_prefixes.len()
+    },
+  }
+}
+
+fn delta_moments<T: NumberLike>(meta: &ChunkMetadata<T>) -> Option<Vec<String>> {
+  match &meta.prefix_metadata {
+    PrefixMetadata::Delta { delta_moments, .. } => Some(
+      delta_moments.moments.iter().map(|m| m.to_string()).collect()
+    ),
+    _ => None,
+  }
+}
+
+// Returns what the histogram describes and its bins, if every chunk's
+// prefixes describe the numbers (or every chunk's describe deltas) directly.
+fn prefix_histogram<T: NumberLike>(
+  chunks: &[ChunkInfo<T>],
+  n_bins: usize,
+) -> Option<(&'static str, Vec<Bin>)> {
+  if chunks.is_empty() || chunks.iter().any(|c| {
+    c.meta.decimal_exponent.is_some() ||
+      c.meta.quantization.is_some() ||
+      c.meta.affine_transform.is_some()
+  }) {
+    return None;
+  }
+
+  let mut ranges = Vec::new();
+  let mut all_simple = true;
+  let mut all_delta = true;
+  for chunk in chunks {
+    match &chunk.meta.prefix_metadata {
+      PrefixMetadata::Simple { prefixes } => {
+        all_delta = false;
+        ranges.extend(histogram::prefix_ranges(prefixes));
+      },
+      PrefixMetadata::Delta { prefixes, .. } => {
+        all_simple = false;
+        ranges.extend(histogram::prefix_ranges(prefixes));
+      },
+      _ => return None,
+    }
+  }
+
+  let description = match (all_simple, all_delta) {
+    (true, _) => "numbers",
+    (_, true) => "deltas",
+    _ => return None,
+  };
+  Some((description, histogram::histogram(&ranges, n_bins)))
+}
+
+impl<T: NumberLike> HandlerImpl<T> {
+  fn inspect_json(
+    &self,
+    opt: &InspectOpt,
+    flags: &q_compress::Flags,
+    chunks: &[ChunkInfo<T>],
+    header_size: usize,
+    compressed_size: usize,
+  ) -> Result<()> {
+    let total_n = chunks.iter().map(|c| c.meta.n).sum::<usize>();
+    let chunk_values = chunks.iter()
+      .map(|c| json!({
+        "n": c.meta.n,
+        "metadata_size": c.metadata_size,
+        "body_size": c.meta.compressed_body_size,
+        "n_prefixes": n_prefixes(&c.meta),
+        "delta_moments": delta_moments(&c.meta),
+      }))
+      .collect::<Vec<_>>();
+    let histogram_value = prefix_histogram(chunks, opt.bins).map(|(description, bins)| json!({
+      "of": description,
+      "bins": bins.iter()
+        .map(|b| json!({"lower": b.lower, "upper": b.upper, "count": b.count}))
+        .collect::<Vec<_>>(),
+    }));
+    let value = json!({
+      "path": opt.path,
+      "data_type": utils::dtype_name::<T>(),
+      "flags": serde_json::to_value(flags)?,
+      "n": total_n,
+      "uncompressed_size": T::PHYSICAL_BITS / 8 * total_n,
+      "compressed_size": compressed_size,
+      "header_size": header_size,
+      "chunks": chunk_values,
+      "histogram": histogram_value,
+    });
+    println!("{}", serde_json::to_string_pretty(&value)?);
+    Ok(())
+  }
+}
+
 impl<T: NumberLike> InspectHandler for HandlerImpl<T> {
   fn header_byte(&self) -> u8 {
     T::HEADER_BYTE
   }
 
   fn inspect(&self, opt: &InspectOpt, bytes: &[u8]) -> Result<()> {
-    println!("inspecting {:?}", opt.path);
     let mut decompressor = Decompressor::<T>::default();
     decompressor.write_all(bytes).unwrap();
 
     let flags = decompressor.header()?;
-    println!("=================\n");
-    println!("data type: {}", utils::dtype_name::<T>());
-    println!("flags: {:?}", flags);
     let header_size = decompressor.bit_idx() / 8;
-    let mut metadata_size = 0;
 
-    let mut metadatas = Vec::new();
+    let mut chunks = Vec::new();
     let mut start_bit_idx = decompressor.bit_idx();
     while let Some(meta) = decompressor.chunk_metadata()? {
       let bit_idx = decompressor.bit_idx();
-      metadata_size += (bit_idx - start_bit_idx) / 8;
+      let metadata_size = (bit_idx - start_bit_idx) / 8;
 
       decompressor.skip_chunk_body()?;
-      metadatas.push(meta);
+      chunks.push(ChunkInfo { meta, metadata_size });
       start_bit_idx = decompressor.bit_idx();
     }
     let compressed_size = decompressor.bit_idx() / 8;
 
-    println!("number of chunks: {}", metadatas.len());
-    let total_n: usize = metadatas.iter()
-      .map(|m| m.n)
+    if opt.json {
+      return self.inspect_json(opt, &flags, &chunks, header_size, compressed_size);
+    }
+
+    println!("inspecting {:?}", opt.path);
+    println!("=================\n");
+    println!("data type: {}", utils::dtype_name::<T>());
+    println!("flags: {:?}", flags);
+    println!("number of chunks: {}", chunks.len());
+    let total_n: usize = chunks.iter()
+      .map(|c| c.meta.n)
       .sum();
     println!("total n: {}", total_n);
     let uncompressed_size = T::PHYSICAL_BITS / 8 * total_n;
@@ -64,21 +175,28 @@ impl<T: NumberLike> InspectHandler for HandlerImpl<T> {
       uncompressed_size as f64 / compressed_size as f64,
     );
     println!("{}header size: {}", INDENT, header_size);
-    println!("{}chunk metadata size: {}", INDENT, metadata_size);
+    println!(
+      "{}chunk metadata size: {}",
+      INDENT,
+      chunks.iter().map(|c| c.metadata_size).sum::<usize>(),
+    );
     println!(
       "{}chunk body size: {}",
       INDENT,
-      metadatas.iter().map(|m| m.compressed_body_size).sum::<usize>()
+      chunks.iter().map(|c| c.meta.compressed_body_size).sum::<usize>()
     );
     println!("{}footer size: 1", INDENT);
     println!("{}unknown trailing bytes: {}", INDENT, bytes.len() - compressed_size);
 
-    if total_n > 0 && flags.delta_encoding_order == 0 {
+    // XOR encoded chunks' prefixes don't bound the numbers themselves
+    let all_simple = chunks.iter()
+      .all(|c| matches!(c.meta.prefix_metadata, PrefixMetadata::Simple { .. }));
+    if total_n > 0 && all_simple {
       let mut bounds = Vec::new();
-      for meta in &metadatas {
-        let prefs = match &meta.prefix_metadata {
+      for chunk in &chunks {
+        let prefs = match &chunk.meta.prefix_metadata {
           PrefixMetadata::Simple { prefixes } => prefixes,
-          _ => panic!("expected simple metadata for delta encoding order 0")
+          _ => unreachable!("checked that all metadata is simple")
         };
         for pref in prefs {
           bounds.push(pref.lower);
@@ -91,12 +209,38 @@ impl<T: NumberLike> InspectHandler for HandlerImpl<T> {
       println!("[min, max] numbers: [{}, {}]", min_num, max_num);
     }
 
-    for (i, m) in metadatas.iter().enumerate() {
+    if let Some((description, bins)) = prefix_histogram(&chunks, opt.bins) {
+      println!("\napproximate histogram of {} from prefixes:", description);
+      histogram::print_histogram(&bins, INDENT);
+    }
+
+    for (i, chunk) in chunks.iter().enumerate() {
+      let m = &chunk.meta;
       println!("\nchunk {}", i);
       println!("{}n: {}", INDENT, m.n);
+      println!("{}metadata size: {}", INDENT, chunk.metadata_size);
+      println!("{}body size: {}", INDENT, m.compressed_body_size);
       match &m.prefix_metadata {
         PrefixMetadata::Simple { prefixes } => print_prefixes(prefixes),
-        PrefixMetadata::Delta {delta_moments: _, prefixes} => print_prefixes(prefixes),
+        PrefixMetadata::Delta { delta_moments, prefixes } => {
+          println!("{}delta moments: {:?}", INDENT, delta_moments.moments);
+          print_prefixes(prefixes);
+        },
+        PrefixMetadata::Xor { prefixes, first } => {
+          println!("{}first: {}", INDENT, first);
+          print_prefixes(prefixes);
+        },
+        PrefixMetadata::Raw { lower, bit_width } => {
+          println!("{}raw lower: {}", INDENT, lower);
+          println!("{}raw bit width: {}", INDENT, bit_width);
+        },
+        PrefixMetadata::FloatSplit { exponent_prefixes, mantissa_prefixes, exponent_body_size } => {
+          println!("{}exponent body size: {}", INDENT, exponent_body_size);
+          println!("{}exponents:", INDENT);
+          print_prefixes(exponent_prefixes);
+          println!("{}mantissas:", INDENT);
+          print_prefixes(mantissa_prefixes);
+        },
       }
     }
 
@@ -4,16 +4,37 @@ use structopt::StructOpt;
 use crate::opt::Opt;
 
 mod arrow_number_like;
+mod bench;
+mod bench_handler;
+mod cat;
+mod cat_handler;
 mod compress;
 mod compress_handler;
 mod decompress;
 mod decompress_handler;
+mod diff;
+mod diff_handler;
 mod dtype;
+mod export;
+mod export_handler;
+mod gen;
 mod handlers;
+mod histogram;
 mod inspect;
 mod inspect_handler;
+mod npy;
 mod opt;
+mod qco_reader;
+mod recompress;
+mod recompress_handler;
+mod stats;
+mod stats_handler;
+mod suggest;
+mod suggest_handler;
+mod test_vectors;
 mod utils;
+mod verify;
+mod verify_handler;
 
 fn main() -> Result<()> {
   let opt = Opt::from_args();
@@ -21,6 +42,18 @@ fn main() -> Result<()> {
     Opt::Compress(compress_opt) => compress::compress(compress_opt)?,
     Opt::Decompress(decompress_opt) => decompress::decompress(decompress_opt)?,
     Opt::Inspect(inspect_opt) => inspect::inspect(inspect_opt)?,
+    Opt::Bench(bench_opt) => bench::bench(bench_opt)?,
+    Opt::Recompress(recompress_opt) => recompress::recompress(recompress_opt)?,
+    Opt::Cat(cat_opt) => cat::cat(cat_opt)?,
+    Opt::Stats(stats_opt) => stats::stats(stats_opt)?,
+    Opt::Verify(verify_opt) => verify::verify(verify_opt)?,
+    Opt::Head(head_opt) => decompress::head(head_opt)?,
+    Opt::Tail(tail_opt) => decompress::tail(tail_opt)?,
+    Opt::Export(export_opt) => export::export(export_opt)?,
+    Opt::Suggest(suggest_opt) => suggest::suggest(suggest_opt)?,
+    Opt::Diff(diff_opt) => diff::diff(diff_opt)?,
+    Opt::Gen(gen_opt) => gen::gen(gen_opt)?,
+    Opt::TestVectors(test_vectors_opt) => test_vectors::test_vectors(test_vectors_opt)?,
   }
   Ok(())
 }
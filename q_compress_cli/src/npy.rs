@@ -0,0 +1,249 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use flate2::read::DeflateDecoder;
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::dtype::DType;
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+const NPY_EXTENSION: &str = ".npy";
+const HEADER_ALIGNMENT: usize = 64;
+
+// The parts of a .npy header we need to read its data: a flat array of `n`
+// numbers with a fixed byte size.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NpyHeader {
+  pub dtype: DType,
+  pub big_endian: bool,
+  pub n: usize,
+}
+
+// an array read from a .npy file or .npz member, positioned at its data
+pub struct NpyArray {
+  pub name: String,
+  pub header: NpyHeader,
+  pub data: Box<dyn Read>,
+}
+
+// Maps a NumPy dtype string like `<f8` or `<M8[us]` to our data type and
+// whether it is big endian.
+fn parse_descr(descr: &str) -> Result<(DType, bool)> {
+  let unsupported = || anyhow!("unsupported NumPy dtype {}", descr);
+  let mut chars = descr.chars();
+  let big_endian = match chars.next() {
+    Some('<') | Some('|') => false,
+    Some('>') => true,
+    Some('=') => cfg!(target_endian = "big"),
+    _ => return Err(unsupported()),
+  };
+  let kind = chars.next().ok_or_else(unsupported)?;
+  let rest = chars.as_str();
+  let (size, unit) = match rest.split_once('[') {
+    Some((size, unit)) => (size, Some(unit.trim_end_matches(']'))),
+    None => (rest, None),
+  };
+  let dtype = match (kind, size, unit) {
+    ('b', "1", None) => DType::Bool,
+    ('f', "4", None) => DType::F32,
+    ('f', "8", None) => DType::F64,
+    ('i', "2", None) => DType::I16,
+    ('i', "4", None) => DType::I32,
+    ('i', "8", None) => DType::I64,
+    ('u', "2", None) => DType::U16,
+    ('u', "4", None) => DType::U32,
+    ('u', "8", None) => DType::U64,
+    ('M', "8", Some("us")) => DType::TimestampMicros,
+    ('M', "8", Some("ns")) => DType::TimestampNanos,
+    _ => return Err(unsupported()),
+  };
+  Ok((dtype, big_endian))
+}
+
+// the little-endian NumPy dtype string for our data type
+fn descr(dtype: DType) -> Result<&'static str> {
+  let res = match dtype {
+    DType::Bool => "|b1",
+    DType::F32 => "<f4",
+    DType::F64 => "<f8",
+    DType::I16 => "<i2",
+    DType::I32 => "<i4",
+    DType::I64 => "<i8",
+    DType::U16 => "<u2",
+    DType::U32 => "<u4",
+    DType::U64 => "<u8",
+    DType::TimestampMicros => "<M8[us]",
+    DType::TimestampNanos => "<M8[ns]",
+    _ => return Err(anyhow!("data type {:?} has no NumPy equivalent", dtype)),
+  };
+  Ok(res)
+}
+
+// returns the text following `'key':` in the header's Python dict literal
+fn dict_value<'a>(dict: &'a str, key: &str) -> Result<&'a str> {
+  let pattern = format!("'{}':", key);
+  let start = dict.find(&pattern)
+    .ok_or_else(|| anyhow!("NumPy header is missing {}", key))?;
+  Ok(dict[start + pattern.len()..].trim_start())
+}
+
+fn parse_header_dict(dict: &str) -> Result<NpyHeader> {
+  let descr = dict_value(dict, "descr")?;
+  let descr = descr.strip_prefix('\'')
+    .and_then(|s| s.split('\'').next())
+    .ok_or_else(|| anyhow!("unsupported NumPy structured dtype"))?;
+  let (dtype, big_endian) = parse_descr(descr)?;
+
+  let fortran_order = dict_value(dict, "fortran_order")?.starts_with("True");
+  let shape = dict_value(dict, "shape")?;
+  let shape = shape.strip_prefix('(')
+    .and_then(|s| s.split(')').next())
+    .ok_or_else(|| anyhow!("invalid NumPy shape {}", shape))?;
+  let dims = shape.split(',')
+    .map(|dim| dim.trim())
+    .filter(|dim| !dim.is_empty())
+    .map(|dim| dim.parse::<usize>())
+    .collect::<Result<Vec<_>, _>>()?;
+  if fortran_order && dims.len() > 1 {
+    return Err(anyhow!("Fortran-ordered multidimensional arrays are not supported"));
+  }
+
+  Ok(NpyHeader {
+    dtype,
+    big_endian,
+    n: dims.iter().product(),
+  })
+}
+
+// reads the header, leaving the reader at the start of the array data
+pub fn read_header(reader: &mut dyn Read) -> Result<NpyHeader> {
+  let mut prefix = [0; 8];
+  reader.read_exact(&mut prefix)?;
+  if &prefix[..MAGIC.len()] != MAGIC {
+    return Err(anyhow!("not a NumPy .npy file"));
+  }
+  let major_version = prefix[MAGIC.len()];
+  let header_len = if major_version == 1 {
+    let mut len_bytes = [0; 2];
+    reader.read_exact(&mut len_bytes)?;
+    u16::from_le_bytes(len_bytes) as usize
+  } else {
+    let mut len_bytes = [0; 4];
+    reader.read_exact(&mut len_bytes)?;
+    u32::from_le_bytes(len_bytes) as usize
+  };
+  let mut dict = vec![0; header_len];
+  reader.read_exact(&mut dict)?;
+  parse_header_dict(&String::from_utf8_lossy(&dict))
+}
+
+// Writes a version 1 header for a flat array of `n` little-endian numbers.
+pub fn write_header(dst: &mut dyn Write, dtype: DType, n: usize) -> Result<()> {
+  let mut dict = format!(
+    "{{'descr': '{}', 'fortran_order': False, 'shape': ({},), }}",
+    descr(dtype)?,
+    n,
+  );
+  // the data must start at an aligned offset, after a newline
+  let unpadded_len = MAGIC.len() + 4 + dict.len() + 1;
+  let padding = (HEADER_ALIGNMENT - unpadded_len % HEADER_ALIGNMENT) % HEADER_ALIGNMENT;
+  dict.push_str(&" ".repeat(padding));
+  dict.push('\n');
+
+  dst.write_all(MAGIC)?;
+  dst.write_all(&[1, 0])?;
+  dst.write_all(&(dict.len() as u16).to_le_bytes())?;
+  dst.write_all(dict.as_bytes())?;
+  Ok(())
+}
+
+// Opens the member's data directly in the archive file so we can stream it.
+fn open_npz_member(path: &Path, member: Option<&str>) -> Result<(String, Box<dyn Read>)> {
+  let mut archive = ZipArchive::new(File::open(path)?)?;
+  let names = archive.file_names()
+    .filter_map(|name| name.strip_suffix(NPY_EXTENSION))
+    .map(|name| name.to_string())
+    .collect::<Vec<_>>();
+  let name = match member {
+    Some(member) => member.strip_suffix(NPY_EXTENSION).unwrap_or(member).to_string(),
+    None if names.len() == 1 => names[0].clone(),
+    None => return Err(anyhow!(
+      "--col-name must pick one of the .npz arrays {:?}",
+      names,
+    )),
+  };
+  if !names.contains(&name) {
+    return Err(anyhow!("array {} not found among .npz arrays {:?}", name, names));
+  }
+
+  let (data_start, size, compression) = {
+    let member_file = archive.by_name(&format!("{}{}", name, NPY_EXTENSION))?;
+    (member_file.data_start(), member_file.compressed_size(), member_file.compression())
+  };
+  let mut file = File::open(path)?;
+  file.seek(SeekFrom::Start(data_start))?;
+  let raw = BufReader::new(file).take(size);
+  let res: Box<dyn Read> = match compression {
+    CompressionMethod::Stored => Box::new(raw),
+    CompressionMethod::Deflated => Box::new(DeflateDecoder::new(raw)),
+    other => return Err(anyhow!("unsupported .npz compression method {:?}", other)),
+  };
+  Ok((name, res))
+}
+
+// Opens a .npy file, or a .npz archive's array chosen by `member`.
+pub fn open(path: &Path, member: Option<&str>) -> Result<NpyArray> {
+  let is_npz = matches!(path.extension().and_then(|ext| ext.to_str()), Some("npz"));
+  let (name, mut data) = if is_npz {
+    open_npz_member(path, member)?
+  } else {
+    let name = path.file_stem()
+      .map(|stem| stem.to_string_lossy().to_string())
+      .unwrap_or_default();
+    let data: Box<dyn Read> = Box::new(BufReader::new(File::open(path)?));
+    (name, data)
+  };
+  let header = read_header(&mut data)?;
+  Ok(NpyArray {
+    name,
+    header,
+    data,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_read_header() -> Result<()> {
+    let dict = "{'descr': '<M8[us]', 'fortran_order': False, 'shape': (3, 4), }";
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend([1, 0]);
+    bytes.extend((dict.len() as u16).to_le_bytes());
+    bytes.extend(dict.as_bytes());
+    let header = read_header(&mut bytes.as_slice())?;
+    assert_eq!(header, NpyHeader {
+      dtype: DType::TimestampMicros,
+      big_endian: false,
+      n: 12,
+    });
+
+    let header = parse_header_dict("{'descr': '>f4', 'fortran_order': True, 'shape': (5,), }")?;
+    assert_eq!(header, NpyHeader { dtype: DType::F32, big_endian: true, n: 5 });
+    assert!(parse_header_dict("{'descr': '<c16', 'fortran_order': False, 'shape': (5,), }").is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_write_header() -> Result<()> {
+    let mut bytes = Vec::new();
+    write_header(&mut bytes, DType::U16, 7)?;
+    assert_eq!(bytes.len() % HEADER_ALIGNMENT, 0);
+    let header = read_header(&mut bytes.as_slice())?;
+    assert_eq!(header, NpyHeader { dtype: DType::U16, big_endian: false, n: 7 });
+    Ok(())
+  }
+}
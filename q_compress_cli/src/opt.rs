@@ -1,4 +1,5 @@
 use std::path::PathBuf;
+use std::str::FromStr;
 
 use anyhow::anyhow;
 use anyhow::Result;
@@ -9,7 +10,7 @@ use crate::dtype::DType;
 #[derive(Clone, Debug, StructOpt)]
 #[structopt {
   name = "q_compress CLI",
-  about = "A command line tool to compress, decompress, and inspect .qco files",
+  about = "A command line tool to compress, decompress, inspect, and benchmark .qco files",
 }]
 pub enum Opt {
   #[structopt(name = "compress")]
@@ -18,42 +19,65 @@ pub enum Opt {
   Decompress(DecompressOpt),
   #[structopt(name = "inspect")]
   Inspect(InspectOpt),
+  #[structopt(name = "bench")]
+  Bench(BenchOpt),
+  #[structopt(name = "recompress")]
+  Recompress(RecompressOpt),
+  #[structopt(name = "cat")]
+  Cat(CatOpt),
+  #[structopt(name = "stats")]
+  Stats(StatsOpt),
+  #[structopt(name = "verify")]
+  Verify(VerifyOpt),
+  #[structopt(name = "head")]
+  Head(HeadTailOpt),
+  #[structopt(name = "tail")]
+  Tail(HeadTailOpt),
+  #[structopt(name = "export")]
+  Export(ExportOpt),
+  #[structopt(name = "suggest")]
+  Suggest(SuggestOpt),
+  #[structopt(name = "diff")]
+  Diff(DiffOpt),
+  #[structopt(name = "gen")]
+  Gen(GenOpt),
+  #[structopt(name = "test-vectors")]
+  TestVectors(TestVectorsOpt),
 }
 
 #[derive(Clone, Debug, StructOpt)]
-pub struct CompressOpt {
+pub struct InputOpt {
   #[structopt(long = "csv")]
   pub csv_path: Option<PathBuf>,
   #[structopt(long = "parquet")]
   pub parquet_path: Option<PathBuf>,
-
-  #[structopt(long, default_value = "8")]
-  pub level: usize,
-  #[structopt(long = "delta-order")]
-  pub delta_encoding_order: Option<usize>,
+  /// An Arrow IPC file (also known as Feather V2).
+  #[structopt(long = "arrow")]
+  pub arrow_path: Option<PathBuf>,
+  /// A NumPy .npy file, or a .npz archive whose array is chosen by
+  /// --col-name.
+  #[structopt(long = "npy")]
+  pub npy_path: Option<PathBuf>,
+  /// Reads the column from stdin in this format instead of from a file.
+  /// Requires --dtype and defaults to the first column.
   #[structopt(long)]
-  pub disable_gcds: bool,
+  pub stdin_format: Option<StdinFormat>,
+
   #[structopt(long)]
   pub dtype: Option<DType>,
   #[structopt(long)]
   pub col_name: Option<String>,
   #[structopt(long)]
   pub col_idx: Option<usize>,
-  #[structopt(long, default_value = "1000000")]
-  pub chunk_size: usize,
-  #[structopt(long)]
-  pub overwrite: bool,
   #[structopt(long = "csv-has-header")]
   pub has_csv_header: bool,
   #[structopt(long = "csv-timestamp-format", default_value = "%Y-%m-%dT%H:%M:%S%.f%z")]
   pub timestamp_format: String,
   #[structopt(long = "csv-delimiter", default_value = ",")]
   pub delimiter: char,
-
-  pub qco_path: PathBuf,
 }
 
-impl CompressOpt {
+impl InputOpt {
   pub fn csv_has_header(&self) -> Result<bool> {
     let res = match (&self.col_name, &self.col_idx) {
       (Some(_), None) => Ok(true),
@@ -65,10 +89,85 @@ impl CompressOpt {
   }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StdinFormat {
+  Csv,
+}
+
+impl FromStr for StdinFormat {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "csv" => Ok(StdinFormat::Csv),
+      _ => Err(anyhow!("unsupported stdin format {}; only csv can be streamed", s)),
+    }
+  }
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub struct CompressOpt {
+  #[structopt(flatten)]
+  pub input: InputOpt,
+
+  #[structopt(long, default_value = "8")]
+  pub level: usize,
+  #[structopt(long = "delta-order")]
+  pub delta_encoding_order: Option<usize>,
+  /// Skips detecting greatest common divisors, which are otherwise used at
+  /// every level.
+  #[structopt(long)]
+  pub disable_gcds: bool,
+  #[structopt(long, default_value = "1000000")]
+  pub chunk_size: usize,
+  /// Number of threads to compress chunks on concurrently.
+  /// Up to this many chunks are held in memory at once.
+  #[structopt(long, default_value = "1")]
+  pub threads: usize,
+  #[structopt(long)]
+  pub overwrite: bool,
+  /// Comma-separated CSV columns to compress in a single pass, each into
+  /// <qco-path>/<column>.qco.
+  /// Each is a column name, optionally followed by `:dtype` to override the
+  /// inferred data type.
+  #[structopt(long, require_delimiter = true)]
+  pub col_names: Vec<String>,
+
+  /// Writes to stdout if omitted.
+  /// With --col-names, the directory to write .qco files to.
+  pub qco_path: Option<PathBuf>,
+}
+
 #[derive(Clone, Debug, StructOpt)]
 pub struct DecompressOpt {
   #[structopt(long)]
   pub limit: Option<usize>,
+  /// Index of the first number to print.
+  #[structopt(long, default_value = "0")]
+  pub start: usize,
+  /// Index after the last number to print.
+  #[structopt(long)]
+  pub end: Option<usize>,
+  #[structopt(long, default_value = "%Y-%m-%dT%H:%M:%S%.f")]
+  pub timestamp_format: String,
+  /// Number of threads to decode chunks on concurrently.
+  /// Up to this many chunks are held in memory at once.
+  #[structopt(long, default_value = "1")]
+  pub threads: usize,
+  /// Keeps waiting for chunks to be written to the file, printing each as it
+  /// lands, until the file's footer is written.
+  #[structopt(long)]
+  pub follow: bool,
+
+  /// Reads from stdin if omitted.
+  pub qco_path: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub struct HeadTailOpt {
+  /// Number of numbers to print.
+  #[structopt(short = "n", default_value = "10")]
+  pub n: usize,
   #[structopt(long, default_value = "%Y-%m-%dT%H:%M:%S%.f")]
   pub timestamp_format: String,
 
@@ -77,5 +176,243 @@ pub struct DecompressOpt {
 
 #[derive(Clone, Debug, StructOpt)]
 pub struct InspectOpt {
+  /// Number of bins in the histogram approximated from prefixes.
+  #[structopt(long, default_value = "20")]
+  pub bins: usize,
+  /// Print the summary as JSON instead of text.
+  #[structopt(long)]
+  pub json: bool,
+
+  pub path: PathBuf,
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub struct BenchOpt {
+  #[structopt(flatten)]
+  pub input: InputOpt,
+
+  #[structopt(long, default_value = "0,1,2,3", use_delimiter = true)]
+  pub delta_orders: Vec<usize>,
+  #[structopt(long, default_value = "4,8,12", use_delimiter = true)]
+  pub levels: Vec<usize>,
+  #[structopt(long, default_value = "1000000")]
+  pub chunk_size: usize,
+  /// Number of times to run each config, keeping the fastest time.
+  #[structopt(long, default_value = "1")]
+  pub iters: usize,
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub struct SuggestOpt {
+  #[structopt(flatten)]
+  pub input: InputOpt,
+
+  /// Number of leading numbers to train on.
+  #[structopt(long, default_value = "100000")]
+  pub sample_size: usize,
+  #[structopt(long, default_value = "0,4,8,12", use_delimiter = true)]
+  pub levels: Vec<usize>,
+  /// Skips configs that compress slower than this many seconds per million
+  /// numbers, unless none are fast enough.
+  #[structopt(long)]
+  pub max_seconds_per_million: Option<f64>,
+  /// Number of the best candidate configs to print.
+  #[structopt(long, default_value = "5")]
+  pub top: usize,
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub struct RecompressOpt {
+  #[structopt(long, default_value = "8")]
+  pub level: usize,
+  /// Defaults to the input file's delta encoding order.
+  #[structopt(long = "delta-order")]
+  pub delta_encoding_order: Option<usize>,
+  #[structopt(long)]
+  pub disable_gcds: bool,
+  #[structopt(long)]
+  pub overwrite: bool,
+
+  pub in_path: PathBuf,
+  pub out_path: PathBuf,
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub struct CatOpt {
+  #[structopt(short = "o", long = "output")]
+  pub out_path: PathBuf,
+  #[structopt(long)]
+  pub overwrite: bool,
+
+  pub in_paths: Vec<PathBuf>,
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub struct StatsOpt {
+  /// Comma-separated quantiles to approximate from prefixes.
+  #[structopt(long, default_value = "0.01,0.1,0.25,0.5,0.75,0.9,0.99", require_delimiter = true)]
+  pub quantiles: Vec<f64>,
+
+  pub path: PathBuf,
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub struct VerifyOpt {
+  /// Also decode every chunk body, rather than just parsing metadata and
+  /// checking checksums.
+  #[structopt(long)]
+  pub decode: bool,
+
   pub path: PathBuf,
 }
+
+#[derive(Clone, Debug, StructOpt)]
+pub struct DiffOpt {
+  pub a_path: PathBuf,
+  pub b_path: PathBuf,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Distribution {
+  Normal,
+  Geometric,
+  Lomax,
+  Uniform,
+  Constant,
+  Extremes,
+  Sparse,
+  Cosine,
+  NearLinear,
+}
+
+impl FromStr for Distribution {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "normal" => Ok(Distribution::Normal),
+      "geometric" => Ok(Distribution::Geometric),
+      "lomax" => Ok(Distribution::Lomax),
+      "uniform" => Ok(Distribution::Uniform),
+      "constant" => Ok(Distribution::Constant),
+      "extremes" => Ok(Distribution::Extremes),
+      "sparse" => Ok(Distribution::Sparse),
+      "cosine" => Ok(Distribution::Cosine),
+      "near-linear" => Ok(Distribution::NearLinear),
+      _ => Err(anyhow!("unknown distribution {}", s)),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GenFormat {
+  Bin,
+  Npy,
+}
+
+impl FromStr for GenFormat {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "bin" => Ok(GenFormat::Bin),
+      "npy" => Ok(GenFormat::Npy),
+      _ => Err(anyhow!("unknown gen format {}", s)),
+    }
+  }
+}
+
+// accepts counts in scientific notation like 1e8
+fn parse_count(s: &str) -> Result<usize> {
+  let count = s.parse::<f64>()?;
+  if count < 0.0 || count.fract() != 0.0 || count > usize::MAX as f64 {
+    return Err(anyhow!("{} is not a valid count", s));
+  }
+  Ok(count as usize)
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub struct GenOpt {
+  /// One of normal, geometric, lomax, uniform, constant, extremes, sparse,
+  /// cosine, or near-linear.
+  #[structopt(long)]
+  pub distribution: Distribution,
+  /// Number of numbers to generate, e.g. 1000000 or 1e6.
+  #[structopt(long, parse(try_from_str = parse_count))]
+  pub n: usize,
+  #[structopt(long)]
+  pub dtype: DType,
+  /// Offset added to the numbers: the mean of normal, the value of constant,
+  /// and the lower bound of uniform floats.
+  #[structopt(long, default_value = "0")]
+  pub loc: f64,
+  /// The standard deviation of normal, mean of geometric, median of lomax,
+  /// width of uniform floats, amplitude of cosine, or step of near-linear.
+  /// Defaults to 1, except 2 for geometric and 1000 for lomax.
+  #[structopt(long)]
+  pub scale: Option<f64>,
+  /// The tail index of lomax.
+  #[structopt(long, default_value = "1.5")]
+  pub shape: f64,
+  #[structopt(long, default_value = "0")]
+  pub seed: u64,
+  /// Either bin, for raw little-endian numbers, or npy.
+  /// Defaults to npy if the output path ends in .npy, otherwise bin.
+  #[structopt(long)]
+  pub format: Option<GenFormat>,
+  #[structopt(short = "o", long = "output")]
+  pub out_path: PathBuf,
+  #[structopt(long)]
+  pub overwrite: bool,
+}
+
+/// Writes the suite of canonical .qco files, each with a .json file
+/// describing the numbers it decodes to, for testing other decoders.
+#[derive(Clone, Debug, StructOpt)]
+pub struct TestVectorsOpt {
+  /// Directory to write into, created if it doesn't exist.
+  #[structopt(short = "o", long = "output")]
+  pub out_dir: PathBuf,
+  #[structopt(long)]
+  pub overwrite: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExportFormat {
+  Csv,
+  Jsonl,
+  Parquet,
+  Arrow,
+}
+
+impl FromStr for ExportFormat {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "csv" => Ok(ExportFormat::Csv),
+      "jsonl" => Ok(ExportFormat::Jsonl),
+      "parquet" => Ok(ExportFormat::Parquet),
+      "arrow" | "feather" => Ok(ExportFormat::Arrow),
+      _ => Err(anyhow!("unknown export format {}", s)),
+    }
+  }
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub struct ExportOpt {
+  /// One of csv, jsonl, parquet, or arrow (an Arrow IPC file).
+  #[structopt(long)]
+  pub format: ExportFormat,
+  #[structopt(short = "o", long = "output")]
+  pub out_path: PathBuf,
+  #[structopt(long)]
+  pub overwrite: bool,
+  /// Name of the exported column.
+  #[structopt(long, default_value = "c0")]
+  pub col_name: String,
+  #[structopt(long, default_value = "%Y-%m-%dT%H:%M:%S%.f")]
+  pub timestamp_format: String,
+
+  pub qco_path: PathBuf,
+}
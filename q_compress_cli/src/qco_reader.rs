@@ -0,0 +1,186 @@
+use std::io::Read;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use q_compress::{ChunkMetadata, Decompressor, Flags, UserMetadata};
+use q_compress::data_types::NumberLike;
+use q_compress::errors::{ErrorKind, QCompressResult};
+
+const INITIAL_READ_SIZE: usize = 1 << 20;
+pub const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+// Decompresses a .qco file from a reader, holding only about one chunk of
+// compressed bytes in memory at a time.
+pub struct QcoReader<T: NumberLike, R: Read> {
+  reader: R,
+  decompressor: Decompressor<T>,
+  // reused across reads; its length is the current read size
+  buf: Vec<u8>,
+  eof: bool,
+  follow: bool,
+}
+
+impl<T: NumberLike, R: Read> QcoReader<T, R> {
+  pub fn new(reader: R) -> Self {
+    Self {
+      reader,
+      decompressor: Decompressor::default(),
+      buf: vec![0; INITIAL_READ_SIZE],
+      eof: false,
+      follow: false,
+    }
+  }
+
+  // Instead of ending at the end of the reader, waits for more data to be
+  // written, like `tail -f`.
+  // Decompression still stops at the .qco footer.
+  pub fn with_follow(mut self, follow: bool) -> Self {
+    self.follow = follow;
+    self
+  }
+
+  // returns false if the reader has no more data
+  fn read_more(&mut self) -> Result<bool> {
+    if self.eof {
+      return Ok(false);
+    }
+    let mut n_read = 0;
+    while n_read < self.buf.len() {
+      match self.reader.read(&mut self.buf[n_read..])? {
+        // return what we have so far, since the rest may never be written
+        0 if self.follow && n_read > 0 => break,
+        0 if self.follow => thread::sleep(FOLLOW_POLL_INTERVAL),
+        0 => {
+          self.eof = true;
+          break;
+        },
+        n => n_read += n,
+      }
+    }
+    self.decompressor.extend_bytes(&self.buf[..n_read]);
+    // grow reads geometrically so retrying a large chunk stays linear time,
+    // but only when a read fills the buffer; short reads mean we're waiting
+    // on the writer, not on our read size
+    if n_read == self.buf.len() {
+      self.buf.resize(2 * n_read, 0);
+    }
+    Ok(n_read > 0)
+  }
+
+  // Decompressor methods leave its state unchanged on error, so we can
+  // retry them with more data.
+  fn with_more_data<X, F>(&mut self, mut f: F) -> Result<X>
+  where F: FnMut(&mut Decompressor<T>) -> QCompressResult<X> {
+    loop {
+      match f(&mut self.decompressor) {
+        Ok(x) => {
+          self.buf.truncate(INITIAL_READ_SIZE);
+          return Ok(x);
+        },
+        Err(e) if e.kind == ErrorKind::InsufficientData => {
+          if !self.read_more()? {
+            return Err(anyhow!("file ended early: {}", e));
+          }
+        },
+        Err(e) => return Err(e.into()),
+      }
+    }
+  }
+
+  pub fn header(&mut self) -> Result<Flags> {
+    self.with_more_data(|d| d.header())
+  }
+
+  pub fn header_metadata(&self) -> &UserMetadata {
+    self.decompressor.header_metadata()
+  }
+
+  pub fn chunk_metadata(&mut self) -> Result<Option<ChunkMetadata<T>>> {
+    self.with_more_data(|d| d.chunk_metadata())
+  }
+
+  pub fn chunk_body(&mut self) -> Result<Vec<T>> {
+    let res = self.with_more_data(|d| d.chunk_body())?;
+    self.decompressor.free_compressed_memory();
+    Ok(res)
+  }
+
+  // Returns a decompressor positioned at the chunk body, e.g. to decode it on
+  // another thread, and skips past the body.
+  pub fn split_chunk_body(&mut self) -> Result<Decompressor<T>> {
+    let res = self.with_more_data(|d| {
+      let body_decompressor = d.clone();
+      d.skip_chunk_body()?;
+      Ok(body_decompressor)
+    })?;
+    self.decompressor.free_compressed_memory();
+    Ok(res)
+  }
+
+  pub fn skip_chunk_body(&mut self) -> Result<()> {
+    self.with_more_data(|d| d.skip_chunk_body())?;
+    self.decompressor.free_compressed_memory();
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use q_compress::Compressor;
+
+  use super::*;
+
+  #[test]
+  fn test_reads_chunks_larger_than_read_size() -> Result<()> {
+    // pseudorandom numbers compress poorly, so the chunks exceed a read
+    let nums = (0..400_000_u64)
+      .map(|i| i.wrapping_mul(0x9E3779B97F4A7C15))
+      .collect::<Vec<_>>();
+    let mut compressor = Compressor::<u64>::default();
+    compressor.header()?;
+    compressor.chunk(&nums[..1000])?;
+    compressor.chunk(&nums[1000..])?;
+    compressor.footer()?;
+    let bytes = compressor.drain_bytes();
+    assert!(bytes.len() > 2 * INITIAL_READ_SIZE);
+
+    let mut reader = QcoReader::<u64, _>::new(bytes.as_slice());
+    reader.header()?;
+    let mut recovered = Vec::new();
+    while reader.chunk_metadata()?.is_some() {
+      recovered.extend(reader.chunk_body()?);
+    }
+    assert_eq!(recovered, nums);
+    Ok(())
+  }
+
+  // yields a few bytes, then nothing, like a file still being written
+  struct TrickleReader {
+    calls: usize,
+  }
+
+  impl Read for TrickleReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+      self.calls += 1;
+      if self.calls % 2 == 1 {
+        buf[..3].fill(0);
+        Ok(3)
+      } else {
+        Ok(0)
+      }
+    }
+  }
+
+  #[test]
+  fn test_short_reads_keep_read_size() -> Result<()> {
+    let mut reader = QcoReader::<u64, _>::new(TrickleReader { calls: 0 })
+      .with_follow(true);
+    for _ in 0..10 {
+      assert!(reader.read_more()?);
+    }
+    assert_eq!(reader.buf.len(), INITIAL_READ_SIZE);
+    Ok(())
+  }
+}
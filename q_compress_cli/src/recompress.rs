@@ -0,0 +1,10 @@
+use anyhow::Result;
+
+use crate::opt::RecompressOpt;
+use crate::{handlers, utils};
+
+pub fn recompress(opt: RecompressOpt) -> Result<()> {
+  let header_byte = utils::read_header_byte(&opt.in_path)?;
+  let handler = handlers::from_header_byte(header_byte)?;
+  handler.recompress(&opt)
+}
@@ -0,0 +1,64 @@
+use std::fs::File;
+use std::io::{BufReader, Write};
+
+use anyhow::Result;
+
+use q_compress::{Compressor, CompressorConfig};
+use q_compress::data_types::NumberLike;
+
+use crate::handlers::HandlerImpl;
+use crate::opt::RecompressOpt;
+use crate::qco_reader::QcoReader;
+use crate::utils;
+
+pub trait RecompressHandler {
+  fn recompress(&self, opt: &RecompressOpt) -> Result<()>;
+}
+
+impl<T: NumberLike> RecompressHandler for HandlerImpl<T> {
+  fn recompress(&self, opt: &RecompressOpt) -> Result<()> {
+    let mut reader = QcoReader::<T, _>::new(BufReader::new(File::open(&opt.in_path)?));
+    let flags = reader.header()?;
+
+    // keep the input's checksums, user metadata, and chunk statistics
+    let config = CompressorConfig::default()
+      .with_compression_level(opt.level)
+      .with_delta_encoding_order(opt.delta_encoding_order.unwrap_or(flags.delta_encoding_order))
+      .with_use_gcds(!opt.disable_gcds)
+      .with_use_chunk_checksums(flags.use_chunk_checksums)
+      .with_use_file_checksum(flags.use_file_checksum)
+      .with_use_user_metadata(flags.use_user_metadata)
+      .with_use_min_max(flags.use_min_max)
+      .with_use_distinct_count(flags.use_distinct_count)
+      .with_use_nan_positions(flags.use_nan_positions)
+      .with_use_affine_transforms(flags.use_affine_transforms)
+      .with_canonicalize_negative_zero(flags.canonicalize_negative_zero)
+      .with_flush_subnormals(flags.flush_subnormals);
+    let mut compressor = Compressor::<T>::try_from_config(config)?;
+    if flags.use_user_metadata {
+      compressor.set_header_metadata(reader.header_metadata().clone())?;
+    }
+
+    let mut file = utils::create_file(&opt.out_path, opt.overwrite)?;
+    compressor.header()?;
+    file.write_all(&compressor.drain_bytes())?;
+
+    // each input chunk becomes one output chunk, so only one is in memory
+    let mut n = 0;
+    while let Some(meta) = reader.chunk_metadata()? {
+      let nums = reader.chunk_body()?;
+      if flags.use_user_metadata {
+        compressor.chunk_with_metadata(&nums, meta.user_metadata)?;
+      } else {
+        compressor.chunk(&nums)?;
+      }
+      file.write_all(&compressor.drain_bytes())?;
+      n += nums.len();
+    }
+
+    compressor.footer()?;
+    file.write_all(&compressor.drain_bytes())?;
+    println!("recompressed {} numbers to {:?}", n, opt.out_path);
+    Ok(())
+  }
+}
@@ -0,0 +1,14 @@
+use std::fs;
+
+use anyhow::Result;
+
+use crate::opt::StatsOpt;
+use crate::utils;
+use crate::handlers;
+
+pub fn stats(opt: StatsOpt) -> Result<()> {
+  let bytes = fs::read(&opt.path)?;
+  let header_byte = utils::get_header_byte(&bytes)?;
+  let handler = handlers::from_header_byte(header_byte)?;
+  handler.stats(&opt, &bytes)
+}
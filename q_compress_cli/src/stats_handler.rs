@@ -0,0 +1,131 @@
+use std::io::Write;
+
+use anyhow::Result;
+
+use q_compress::{ChunkMetadata, Decompressor, Prefix, PrefixMetadata};
+use q_compress::data_types::NumberLike;
+
+use crate::handlers::HandlerImpl;
+use crate::histogram;
+use crate::opt::StatsOpt;
+use crate::utils;
+
+const INDENT: &str = "  ";
+
+pub trait StatsHandler {
+  fn stats(&self, opt: &StatsOpt, bytes: &[u8]) -> Result<()>;
+}
+
+// Only simple prefixes without a decimal exponent, quantization, or affine
+// transform describe the numbers themselves.
+// Their lower and upper bounds are the exact min and max of their numbers.
+fn number_prefixes<T: NumberLike>(meta: &ChunkMetadata<T>) -> Option<&[Prefix<T>]> {
+  if meta.decimal_exponent.is_some() ||
+    meta.quantization.is_some() ||
+    meta.affine_transform.is_some() {
+    return None;
+  }
+  match &meta.prefix_metadata {
+    PrefixMetadata::Simple { prefixes } => Some(prefixes),
+    _ => None,
+  }
+}
+
+fn min_max<T: NumberLike>(prefixes: &[Prefix<T>]) -> Option<(T, T)> {
+  let min = prefixes.iter().map(|p| p.lower).min_by_key(|x| x.to_unsigned())?;
+  let max = prefixes.iter().map(|p| p.upper).max_by_key(|x| x.to_unsigned())?;
+  Some((min, max))
+}
+
+// the exact min and max if the chunk stored them, or else its prefixes'
+fn chunk_min_max<T: NumberLike>(meta: &ChunkMetadata<T>) -> Option<(T, T)> {
+  meta.min_max.or_else(|| number_prefixes(meta).and_then(min_max))
+}
+
+fn encoding_name<T: NumberLike>(meta: &ChunkMetadata<T>) -> &'static str {
+  match &meta.prefix_metadata {
+    PrefixMetadata::Simple { .. } => "simple",
+    PrefixMetadata::Delta { .. } => "delta",
+    PrefixMetadata::Xor { .. } => "XOR",
+    PrefixMetadata::Raw { .. } => "raw",
+    PrefixMetadata::FloatSplit { .. } => "float split",
+  }
+}
+
+impl<T: NumberLike> StatsHandler for HandlerImpl<T> {
+  fn stats(&self, opt: &StatsOpt, bytes: &[u8]) -> Result<()> {
+    let mut decompressor = Decompressor::<T>::default();
+    decompressor.write_all(bytes).unwrap();
+    decompressor.header()?;
+
+    let mut chunks = Vec::new();
+    while let Some(meta) = decompressor.chunk_metadata()? {
+      decompressor.skip_chunk_body()?;
+      chunks.push(meta);
+    }
+
+    let total_n = chunks.iter().map(|m| m.n).sum::<usize>();
+    println!("stats for {:?}", opt.path);
+    println!("=================\n");
+    println!("data type: {}", utils::dtype_name::<T>());
+    println!("number of chunks: {}", chunks.len());
+    println!("count: {}", total_n);
+
+    let all_min_maxes = chunks.iter()
+      .map(chunk_min_max)
+      .collect::<Option<Vec<_>>>();
+    if let Some(all_min_maxes) = all_min_maxes {
+      let min = all_min_maxes.iter().map(|&(min, _)| min).min_by_key(|x| x.to_unsigned());
+      let max = all_min_maxes.iter().map(|&(_, max)| max).max_by_key(|x| x.to_unsigned());
+      if let (Some(min), Some(max)) = (min, max) {
+        println!("min: {}", min);
+        println!("max: {}", max);
+      }
+    }
+
+    let all_prefixes = chunks.iter()
+      .map(number_prefixes)
+      .collect::<Option<Vec<_>>>();
+    match &all_prefixes {
+      Some(all_prefixes) => {
+        let flat = all_prefixes.concat();
+        let ranges = histogram::prefix_ranges(&flat);
+        if !opt.quantiles.is_empty() && total_n > 0 {
+          println!("\napproximate quantiles from prefixes:");
+        }
+        for &q in &opt.quantiles {
+          if let Some(value) = histogram::approx_quantile(&ranges, q) {
+            println!("{}{}: {}", INDENT, q, value);
+          }
+        }
+      },
+      None => println!(
+        "quantiles are not derivable from metadata for delta, XOR, raw, \
+         float split, decimal, or quantized chunks",
+      ),
+    }
+
+    for (i, meta) in chunks.iter().enumerate() {
+      println!("\nchunk {}", i);
+      println!("{}n: {}", INDENT, meta.n);
+      println!("{}encoding: {}", INDENT, encoding_name(meta));
+      println!("{}body size: {}", INDENT, meta.compressed_body_size);
+      if let Some((min, max)) = chunk_min_max(meta) {
+        println!("{}[min, max]: [{}, {}]", INDENT, min, max);
+      }
+      if let Some(distinct_count) = meta.distinct_count {
+        println!(
+          "{}distinct count: {}{}",
+          INDENT,
+          if distinct_count.is_exact { "" } else { "~" },
+          distinct_count.count,
+        );
+      }
+      if let Some(nan_positions) = &meta.nan_positions {
+        println!("{}NaN count: {}", INDENT, nan_positions.count());
+      }
+    }
+
+    Ok(())
+  }
+}
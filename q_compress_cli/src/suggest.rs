@@ -0,0 +1,11 @@
+use anyhow::Result;
+
+use crate::compress;
+use crate::handlers;
+use crate::opt::SuggestOpt;
+
+pub fn suggest(opt: SuggestOpt) -> Result<()> {
+  let (schema, dtype) = compress::infer_schema(&opt.input)?;
+  let handler = handlers::from_dtype(dtype);
+  handler.suggest(&opt, &schema)
+}
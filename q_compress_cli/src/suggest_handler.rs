@@ -0,0 +1,96 @@
+use anyhow::{anyhow, Result};
+use arrow::datatypes::Schema;
+
+use q_compress::{CandidateReport, TrainingObjectives};
+
+use crate::arrow_number_like::ArrowNumberLike;
+use crate::compress_handler;
+use crate::handlers::HandlerImpl;
+use crate::opt::SuggestOpt;
+use crate::utils;
+
+pub trait SuggestHandler {
+  fn suggest(&self, opt: &SuggestOpt, schema: &Schema) -> Result<()>;
+}
+
+// the compress command's flags for the candidate's config
+fn compress_flags(candidate: &CandidateReport) -> String {
+  let config = &candidate.config;
+  let mut res = format!(
+    "--delta-order {} --level {}",
+    config.delta_encoding_order,
+    config.compression_level,
+  );
+  if !config.use_gcds {
+    res.push_str(" --disable-gcds");
+  }
+  res
+}
+
+impl<T: ArrowNumberLike> SuggestHandler for HandlerImpl<T> {
+  fn suggest(&self, opt: &SuggestOpt, schema: &Schema) -> Result<()> {
+    if !T::IS_ARROW {
+      return Err(anyhow!(
+        "data type {} not supported by arrow converters",
+        utils::dtype_name::<T>()
+      ));
+    }
+
+    // a contiguous sample keeps the sequential structure delta encoding
+    // relies on
+    let mut reader = compress_handler::new_column_reader::<T>(schema, &opt.input, opt.sample_size)?;
+    let mut sample = Vec::new();
+    while sample.len() < opt.sample_size {
+      match reader.next_batch() {
+        Some(batch_result) => sample.extend(batch_result?),
+        None => break,
+      }
+    }
+    sample.truncate(opt.sample_size);
+    println!(
+      "trying configs on a sample of the first {} {} numbers\n",
+      sample.len(),
+      utils::dtype_name::<T>(),
+    );
+
+    let objectives = TrainingObjectives::default()
+      .with_compression_levels(opt.levels.clone())
+      .with_max_seconds_per_million(opt.max_seconds_per_million);
+    let trained = q_compress::train_config(&sample, &objectives)?;
+    let best = &trained.config;
+    let best_report = trained.candidates.iter()
+      .find(|candidate| {
+        let config = &candidate.config;
+        config.delta_encoding_order == best.delta_encoding_order &&
+          config.compression_level == best.compression_level &&
+          config.use_gcds == best.use_gcds
+      })
+      .unwrap();
+
+    println!("recommended flags: {}", compress_flags(best_report));
+    println!("expected compression ratio: {:.3}\n", best_report.compression_ratio);
+
+    let mut candidates = trained.candidates.iter().collect::<Vec<_>>();
+    candidates.sort_by_key(|candidate| candidate.compressed_size);
+    println!(
+      "{:>11} {:>5} {:>5} {:>12} {:>8} {:>15}",
+      "delta order", "level", "gcds", "size", "ratio", "compress secs",
+    );
+    for candidate in candidates.iter().take(opt.top) {
+      let config = &candidate.config;
+      let seconds = candidate.compress_seconds
+        .map(|s| format!("{:.4}", s))
+        .unwrap_or_default();
+      println!(
+        "{:>11} {:>5} {:>5} {:>12} {:>8.3} {:>15}",
+        config.delta_encoding_order,
+        config.compression_level,
+        config.use_gcds,
+        candidate.compressed_size,
+        candidate.compression_ratio,
+        seconds,
+      );
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,21 @@
+use std::fs;
+use std::io::Write;
+
+use anyhow::Result;
+use q_compress::test_vectors::test_vectors as generate_test_vectors;
+
+use crate::opt::TestVectorsOpt;
+use crate::utils;
+
+pub fn test_vectors(opt: TestVectorsOpt) -> Result<()> {
+  let vectors = generate_test_vectors()?;
+  fs::create_dir_all(&opt.out_dir)?;
+  for vector in &vectors {
+    let qco_path = opt.out_dir.join(format!("{}.qco", vector.name));
+    utils::create_file(&qco_path, opt.overwrite)?.write_all(&vector.compressed)?;
+    let json_path = opt.out_dir.join(format!("{}.json", vector.name));
+    utils::create_file(&json_path, opt.overwrite)?.write_all(vector.description_json.as_bytes())?;
+  }
+  println!("wrote {} test vectors into {:?}", vectors.len(), opt.out_dir);
+  Ok(())
+}
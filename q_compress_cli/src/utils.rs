@@ -1,4 +1,7 @@
 use std::any;
+use std::fs::{File, OpenOptions};
+use std::io::Read;
+use std::path::Path;
 
 use anyhow::Result;
 use arrow::array::ArrayRef;
@@ -6,7 +9,7 @@ use arrow::datatypes::Schema;
 
 use q_compress::data_types::NumberLike;
 
-use crate::opt::CompressOpt;
+use crate::opt::InputOpt;
 use crate::arrow_number_like::ArrowNumberLike;
 
 pub fn get_header_byte(bytes: &[u8]) -> Result<u8> {
@@ -17,6 +20,35 @@ pub fn get_header_byte(bytes: &[u8]) -> Result<u8> {
   }
 }
 
+// reads the data type byte without reading the rest of the file
+pub fn read_header_byte(path: &Path) -> Result<u8> {
+  let mut bytes = Vec::with_capacity(5);
+  File::open(path)?.take(5).read_to_end(&mut bytes)?;
+  get_header_byte(&bytes)
+}
+
+pub fn create_file(path: &Path, overwrite: bool) -> Result<File> {
+  let mut open_options = OpenOptions::new();
+  open_options.write(true);
+  if overwrite {
+    open_options.create(true);
+    open_options.truncate(true);
+  } else {
+    open_options.create_new(true);
+  }
+  Ok(open_options.open(path)?)
+}
+
+pub fn init_threads(threads: usize) -> Result<()> {
+  if threads == 0 {
+    return Err(anyhow::anyhow!("number of threads must be positive"));
+  }
+  rayon::ThreadPoolBuilder::new()
+    .num_threads(threads)
+    .build_global()?;
+  Ok(())
+}
+
 pub fn arrow_to_vec<T: ArrowNumberLike>(arr: &ArrayRef) -> Vec<T> {
   let primitive = arrow::array::as_primitive_array::<T::ArrowPrimitive>(arr);
   primitive.values().iter()
@@ -24,7 +56,7 @@ pub fn arrow_to_vec<T: ArrowNumberLike>(arr: &ArrayRef) -> Vec<T> {
     .collect()
 }
 
-pub fn find_col_idx(schema: &Schema, opt: &CompressOpt) -> usize {
+pub fn find_col_idx(schema: &Schema, opt: &InputOpt) -> usize {
   match (&opt.col_idx, &opt.col_name) {
     (Some(col_idx), _) => *col_idx,
     (_, Some(col_name)) => schema.fields().iter()
@@ -0,0 +1,14 @@
+use std::fs;
+
+use anyhow::Result;
+
+use crate::opt::VerifyOpt;
+use crate::utils;
+use crate::handlers;
+
+pub fn verify(opt: VerifyOpt) -> Result<()> {
+  let bytes = fs::read(&opt.path)?;
+  let header_byte = utils::get_header_byte(&bytes)?;
+  let handler = handlers::from_header_byte(header_byte)?;
+  handler.verify(&opt, &bytes)
+}
@@ -0,0 +1,145 @@
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+
+use q_compress::Decompressor;
+use q_compress::data_types::NumberLike;
+
+use crate::handlers::HandlerImpl;
+use crate::opt::VerifyOpt;
+
+const BYTES_PER_CHECKSUM: usize = 4;
+
+pub trait VerifyHandler {
+  fn verify(&self, opt: &VerifyOpt, bytes: &[u8]) -> Result<()>;
+}
+
+// Chunk checksums are CRC32s of the compressed body bytes, so we can check
+// them without decoding.
+fn check_chunk_checksum(bytes: &[u8], body_start: usize, body_size: usize) -> Result<()> {
+  let checksum_start = body_start + body_size;
+  let checksum_bytes = bytes.get(checksum_start..checksum_start + BYTES_PER_CHECKSUM)
+    .ok_or_else(|| anyhow!("file ended before chunk checksum"))?;
+  let mut le_bytes = [0; BYTES_PER_CHECKSUM];
+  le_bytes.copy_from_slice(checksum_bytes);
+  let expected = u32::from_le_bytes(le_bytes);
+  let actual = crc32fast::hash(&bytes[body_start..checksum_start]);
+  if actual != expected {
+    return Err(anyhow!(
+      "chunk checksum {:#010x} does not match expected {:#010x}",
+      actual,
+      expected,
+    ));
+  }
+  Ok(())
+}
+
+impl<T: NumberLike> VerifyHandler for HandlerImpl<T> {
+  fn verify(&self, opt: &VerifyOpt, bytes: &[u8]) -> Result<()> {
+    let mut decompressor = Decompressor::<T>::default();
+    decompressor.write_all(bytes).unwrap();
+    let flags = decompressor.header()
+      .map_err(|e| anyhow!("{:?}: header: {}", opt.path, e))?;
+
+    let mut n_chunks = 0;
+    let mut total_n = 0;
+    loop {
+      // we never free memory, so bit indices are relative to the file start
+      let chunk_start = decompressor.bit_idx() / 8;
+      let at_chunk = |e: &dyn std::fmt::Display| anyhow!(
+        "{:?}: chunk {} at byte {}: {}",
+        opt.path,
+        n_chunks,
+        chunk_start,
+        e,
+      );
+      let meta = match decompressor.chunk_metadata().map_err(|e| at_chunk(&e))? {
+        Some(meta) => meta,
+        None => break,
+      };
+
+      let body_start = decompressor.bit_idx() / 8;
+      if flags.use_chunk_checksums {
+        check_chunk_checksum(bytes, body_start, meta.compressed_body_size)
+          .map_err(|e| at_chunk(&e))?;
+      }
+      if opt.decode {
+        let nums = decompressor.chunk_body().map_err(|e| at_chunk(&e))?;
+        if nums.len() != meta.n {
+          return Err(at_chunk(&format!(
+            "decoded {} numbers but metadata says {}",
+            nums.len(),
+            meta.n,
+          )));
+        }
+      } else {
+        decompressor.skip_chunk_body().map_err(|e| at_chunk(&e))?;
+      }
+      n_chunks += 1;
+      total_n += meta.n;
+    }
+
+    let compressed_size = decompressor.bit_idx() / 8;
+    if flags.use_file_checksum {
+      decompressor.verify()
+        .map_err(|e| anyhow!("{:?}: footer at byte {}: {}", opt.path, compressed_size, e))?;
+    }
+
+    let mut checks = vec!["header", "chunk metadata"];
+    if flags.use_chunk_checksums {
+      checks.push("chunk checksums");
+    }
+    if opt.decode {
+      checks.push("decoded chunk bodies");
+    }
+    if flags.use_file_checksum {
+      checks.push("file checksum");
+    }
+    println!(
+      "{:?}: OK ({} chunks, {} numbers; checked {})",
+      opt.path,
+      n_chunks,
+      total_n,
+      checks.join(", "),
+    );
+    let n_trailing = bytes.len() - compressed_size;
+    if n_trailing > 0 {
+      println!("warning: {} unknown trailing bytes after the footer", n_trailing);
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  use q_compress::{Compressor, CompressorConfig};
+
+  use super::*;
+
+  #[test]
+  fn test_finds_corrupt_chunk() -> Result<()> {
+    let config = CompressorConfig::default()
+      .with_use_chunk_checksums(true)
+      .with_use_file_checksum(true);
+    let mut compressor = Compressor::<i64>::from_config(config);
+    compressor.header()?;
+    compressor.chunk(&(0..100).collect::<Vec<_>>())?;
+    compressor.chunk(&(0..100).map(|i| i * i).collect::<Vec<_>>())?;
+    compressor.footer()?;
+    let mut bytes = compressor.drain_bytes();
+
+    let handler = HandlerImpl::<i64>::default();
+    let opt = VerifyOpt { decode: true, path: PathBuf::from("x.qco") };
+    handler.verify(&opt, &bytes)?;
+
+    // flip a bit in the last chunk's body
+    let idx = bytes.len() - 10;
+    bytes[idx] ^= 1;
+    let err = handler.verify(&opt, &bytes).unwrap_err().to_string();
+    assert!(err.contains("chunk 1 at byte"), "{}", err);
+    assert!(err.contains("checksum"), "{}", err);
+    Ok(())
+  }
+}
@@ -0,0 +1,284 @@
+#ifndef Q_COMPRESS_H
+#define Q_COMPRESS_H
+
+/* Generated by cbindgen from q_compress_ffi; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The HDF5 filter ID for q_compress.
+ *
+ * It is provisional: it lies in 256-511, the range HDF5 reserves for
+ * testing and unregistered filters, and will change if the HDF Group
+ * assigns q_compress a registered ID.
+ */
+#define QC_HDF5_FILTER_ID 307
+
+/**
+ * The index of the `QcDtype` among the filter's client data values.
+ * Required for writing; reading detects the dtype from the .qco header.
+ */
+#define QC_HDF5_CD_DTYPE 0
+
+/**
+ * The index of the compression level among the filter's client data
+ * values, if present.
+ */
+#define QC_HDF5_CD_COMPRESSION_LEVEL 1
+
+/**
+ * The index of the delta encoding order among the filter's client data
+ * values, if present.
+ */
+#define QC_HDF5_CD_DELTA_ENCODING_ORDER 2
+
+/**
+ * The result of every fallible function.
+ */
+typedef enum QcStatus {
+  QC_STATUS_OK = 0,
+  /**
+   * The data comes from a newer, incompatible version of q_compress.
+   */
+  QC_STATUS_COMPATIBILITY = 1,
+  /**
+   * The data is inconsistent or violates the .qco format.
+   */
+  QC_STATUS_CORRUPTION = 2,
+  /**
+   * The data ended before decompression finished.
+   */
+  QC_STATUS_INSUFFICIENT_DATA = 3,
+  /**
+   * A parameter was invalid, e.g. a null pointer or an out-of-range
+   * compressor config.
+   */
+  QC_STATUS_INVALID_ARGUMENT = 4,
+} QcStatus;
+
+/**
+ * The number types supported by this API.
+ */
+typedef enum QcDtype {
+  QC_DTYPE_I32 = 0,
+  QC_DTYPE_I64 = 1,
+  QC_DTYPE_U32 = 2,
+  QC_DTYPE_U64 = 3,
+  QC_DTYPE_F32 = 4,
+  QC_DTYPE_F64 = 5,
+} QcDtype;
+
+/**
+ * Configuration for compression.
+ * Obtain defaults from `qc_default_compressor_config`.
+ */
+typedef struct QcCompressorConfig {
+  /**
+   * From 0 to 12; higher compresses better but more slowly.
+   */
+  size_t compression_level;
+  /**
+   * From 0 to 7; 0 disables delta encoding.
+   */
+  size_t delta_encoding_order;
+  /**
+   * Whether to detect and use greatest common divisors of ranges.
+   */
+  bool use_gcds;
+} QcCompressorConfig;
+
+/**
+ * Compressed bytes owned by the caller; release with `qc_free_bytes`.
+ */
+typedef struct QcBytes {
+  uint8_t *data;
+  size_t len;
+} QcBytes;
+
+/**
+ * Decompressed numbers owned by the caller; release with `qc_free_nums`.
+ * `data` points to `len` numbers of type `dtype`.
+ */
+typedef struct QcNums {
+  void *data;
+  size_t len;
+  enum QcDtype dtype;
+} QcNums;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Returns the default compressor configuration.
+ */
+struct QcCompressorConfig qc_default_compressor_config(void);
+
+/**
+ * Returns a static, null-terminated description of the status.
+ */
+const char *qc_status_message(enum QcStatus status);
+
+/**
+ * Releases bytes returned by a `qc_compress_*` function.
+ *
+ * # Safety
+ * `bytes` must have been written by a `qc_compress_*` function and not
+ * already freed.
+ */
+void qc_free_bytes(struct QcBytes bytes);
+
+/**
+ * Releases numbers returned by a `qc_decompress_*` function.
+ *
+ * # Safety
+ * `nums` must have been written by a `qc_decompress_*` function and not
+ * already freed.
+ */
+void qc_free_nums(struct QcNums nums);
+
+/**
+ * Compresses `len` `i32`s into a .qco file, writing the caller-owned result
+ * to `out`.
+ * `config` may be null to use the default configuration.
+ *
+ * # Safety
+ * `nums` must point to `len` readable numbers and `out` must be writable.
+ */
+enum QcStatus qc_compress_i32(const int32_t *nums,
+                              size_t len,
+                              const struct QcCompressorConfig *config,
+                              struct QcBytes *out);
+
+/**
+ * Decompresses a .qco file of `i32`s, writing the caller-owned result to
+ * `out`.
+ *
+ * # Safety
+ * `bytes` must point to `len` readable bytes and `out` must be writable.
+ */
+enum QcStatus qc_decompress_i32(const uint8_t *bytes, size_t len, struct QcNums *out);
+
+/**
+ * Compresses `len` `i64`s into a .qco file, writing the caller-owned result
+ * to `out`.
+ * `config` may be null to use the default configuration.
+ *
+ * # Safety
+ * `nums` must point to `len` readable numbers and `out` must be writable.
+ */
+enum QcStatus qc_compress_i64(const int64_t *nums,
+                              size_t len,
+                              const struct QcCompressorConfig *config,
+                              struct QcBytes *out);
+
+/**
+ * Decompresses a .qco file of `i64`s, writing the caller-owned result to
+ * `out`.
+ *
+ * # Safety
+ * `bytes` must point to `len` readable bytes and `out` must be writable.
+ */
+enum QcStatus qc_decompress_i64(const uint8_t *bytes, size_t len, struct QcNums *out);
+
+/**
+ * Compresses `len` `u32`s into a .qco file, writing the caller-owned result
+ * to `out`.
+ * `config` may be null to use the default configuration.
+ *
+ * # Safety
+ * `nums` must point to `len` readable numbers and `out` must be writable.
+ */
+enum QcStatus qc_compress_u32(const uint32_t *nums,
+                              size_t len,
+                              const struct QcCompressorConfig *config,
+                              struct QcBytes *out);
+
+/**
+ * Decompresses a .qco file of `u32`s, writing the caller-owned result to
+ * `out`.
+ *
+ * # Safety
+ * `bytes` must point to `len` readable bytes and `out` must be writable.
+ */
+enum QcStatus qc_decompress_u32(const uint8_t *bytes, size_t len, struct QcNums *out);
+
+/**
+ * Compresses `len` `u64`s into a .qco file, writing the caller-owned result
+ * to `out`.
+ * `config` may be null to use the default configuration.
+ *
+ * # Safety
+ * `nums` must point to `len` readable numbers and `out` must be writable.
+ */
+enum QcStatus qc_compress_u64(const uint64_t *nums,
+                              size_t len,
+                              const struct QcCompressorConfig *config,
+                              struct QcBytes *out);
+
+/**
+ * Decompresses a .qco file of `u64`s, writing the caller-owned result to
+ * `out`.
+ *
+ * # Safety
+ * `bytes` must point to `len` readable bytes and `out` must be writable.
+ */
+enum QcStatus qc_decompress_u64(const uint8_t *bytes, size_t len, struct QcNums *out);
+
+/**
+ * Compresses `len` `f32`s into a .qco file, writing the caller-owned result
+ * to `out`.
+ * `config` may be null to use the default configuration.
+ *
+ * # Safety
+ * `nums` must point to `len` readable numbers and `out` must be writable.
+ */
+enum QcStatus qc_compress_f32(const float *nums,
+                              size_t len,
+                              const struct QcCompressorConfig *config,
+                              struct QcBytes *out);
+
+/**
+ * Decompresses a .qco file of `f32`s, writing the caller-owned result to
+ * `out`.
+ *
+ * # Safety
+ * `bytes` must point to `len` readable bytes and `out` must be writable.
+ */
+enum QcStatus qc_decompress_f32(const uint8_t *bytes, size_t len, struct QcNums *out);
+
+/**
+ * Compresses `len` `f64`s into a .qco file, writing the caller-owned result
+ * to `out`.
+ * `config` may be null to use the default configuration.
+ *
+ * # Safety
+ * `nums` must point to `len` readable numbers and `out` must be writable.
+ */
+enum QcStatus qc_compress_f64(const double *nums,
+                              size_t len,
+                              const struct QcCompressorConfig *config,
+                              struct QcBytes *out);
+
+/**
+ * Decompresses a .qco file of `f64`s, writing the caller-owned result to
+ * `out`.
+ *
+ * # Safety
+ * `bytes` must point to `len` readable bytes and `out` must be writable.
+ */
+enum QcStatus qc_decompress_f64(const uint8_t *bytes, size_t len, struct QcNums *out);
+
+extern void *malloc(size_t size);
+
+extern void free(void *ptr);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* Q_COMPRESS_H */
@@ -0,0 +1,277 @@
+// An HDF5 dynamically loaded filter plugin.
+//
+// HDF5 finds plugins by loading each shared library in HDF5_PLUGIN_PATH and
+// calling H5PLget_plugin_type and H5PLget_plugin_info, so building this
+// crate's cdylib is enough to provide the filter. We only need to mirror
+// HDF5's filter class struct here, not link against HDF5 itself.
+
+use std::ffi::c_void;
+use std::os::raw::{c_char, c_int, c_uint};
+use std::{cmp, ptr, slice};
+
+use q_compress::{Compressor, CompressorConfig};
+use q_compress::data_types::NumberLike;
+
+use crate::{decompress_vec, QcDtype};
+
+/// The HDF5 filter ID for q_compress.
+///
+/// It is provisional: it lies in 256-511, the range HDF5 reserves for
+/// testing and unregistered filters, and will change if the HDF Group
+/// assigns q_compress a registered ID.
+pub const QC_HDF5_FILTER_ID: c_int = 307;
+
+/// The index of the `QcDtype` among the filter's client data values.
+/// Required for writing; reading detects the dtype from the .qco header.
+pub const QC_HDF5_CD_DTYPE: usize = 0;
+/// The index of the compression level among the filter's client data
+/// values, if present.
+pub const QC_HDF5_CD_COMPRESSION_LEVEL: usize = 1;
+/// The index of the delta encoding order among the filter's client data
+/// values, if present.
+pub const QC_HDF5_CD_DELTA_ENCODING_ORDER: usize = 2;
+
+// from H5Zpublic.h and H5PLextern.h
+const H5Z_CLASS_T_VERS: c_int = 1;
+const H5Z_FLAG_REVERSE: c_uint = 0x0100;
+const H5PL_TYPE_FILTER: c_int = 0;
+
+type H5ZFilterFunc = unsafe extern "C" fn(
+  flags: c_uint,
+  cd_nelmts: usize,
+  cd_values: *const c_uint,
+  nbytes: usize,
+  buf_size: *mut usize,
+  buf: *mut *mut c_void,
+) -> usize;
+
+// mirrors H5Z_class2_t; we have no need for the can_apply and set_local
+// callbacks, which HDF5 allows to be null
+#[repr(C)]
+struct H5ZClass2 {
+  version: c_int,
+  id: c_int,
+  encoder_present: c_uint,
+  decoder_present: c_uint,
+  name: *const c_char,
+  can_apply: *const c_void,
+  set_local: *const c_void,
+  filter: Option<H5ZFilterFunc>,
+}
+
+// the struct is immutable and its pointers are to static data
+unsafe impl Sync for H5ZClass2 {}
+
+static FILTER_CLASS: H5ZClass2 = H5ZClass2 {
+  version: H5Z_CLASS_T_VERS,
+  id: QC_HDF5_FILTER_ID,
+  encoder_present: 1,
+  decoder_present: 1,
+  name: b"q_compress\0".as_ptr() as *const c_char,
+  can_apply: ptr::null(),
+  set_local: ptr::null(),
+  filter: Some(filter),
+};
+
+// HDF5 frees filter buffers with the C allocator
+extern "C" {
+  fn malloc(size: usize) -> *mut c_void;
+  fn free(ptr: *mut c_void);
+}
+
+// a number type HDF5 stores as fixed-width little-endian bytes
+trait LeNumber: NumberLike {
+  fn from_le(bytes: &[u8]) -> Self;
+
+  fn extend_le(self, dst: &mut Vec<u8>);
+}
+
+macro_rules! impl_le_number {
+  ($t: ty) => {
+    impl LeNumber for $t {
+      fn from_le(bytes: &[u8]) -> Self {
+        let mut le_bytes = [0; std::mem::size_of::<$t>()];
+        le_bytes.copy_from_slice(bytes);
+        <$t>::from_le_bytes(le_bytes)
+      }
+
+      fn extend_le(self, dst: &mut Vec<u8>) {
+        dst.extend_from_slice(&self.to_le_bytes());
+      }
+    }
+  }
+}
+
+impl_le_number!(i32);
+impl_le_number!(i64);
+impl_le_number!(u32);
+impl_le_number!(u64);
+impl_le_number!(f32);
+impl_le_number!(f64);
+
+fn compress_le<T: LeNumber>(bytes: &[u8], config: CompressorConfig) -> Option<Vec<u8>> {
+  let values = bytes.chunks_exact(std::mem::size_of::<T>());
+  if !values.remainder().is_empty() {
+    return None;
+  }
+  let mut compressor = Compressor::<T>::try_from_config(config).ok()?;
+  let nums = values.map(T::from_le).collect::<Vec<_>>();
+  Some(compressor.simple_compress(&nums))
+}
+
+fn decompress_le<T: LeNumber>(bytes: &[u8]) -> Option<Vec<u8>> {
+  let nums = decompress_vec::<T>(bytes).ok()?;
+  let mut res = Vec::with_capacity(nums.len() * std::mem::size_of::<T>());
+  for num in nums {
+    num.extend_le(&mut res);
+  }
+  Some(res)
+}
+
+fn compress_chunk(bytes: &[u8], cd_values: &[c_uint]) -> Option<Vec<u8>> {
+  let mut config = CompressorConfig::default();
+  if let Some(&level) = cd_values.get(QC_HDF5_CD_COMPRESSION_LEVEL) {
+    config = config.with_compression_level(level as usize);
+  }
+  if let Some(&order) = cd_values.get(QC_HDF5_CD_DELTA_ENCODING_ORDER) {
+    config = config.with_delta_encoding_order(order as usize);
+  }
+  match *cd_values.get(QC_HDF5_CD_DTYPE)? {
+    x if x == QcDtype::I32 as c_uint => compress_le::<i32>(bytes, config),
+    x if x == QcDtype::I64 as c_uint => compress_le::<i64>(bytes, config),
+    x if x == QcDtype::U32 as c_uint => compress_le::<u32>(bytes, config),
+    x if x == QcDtype::U64 as c_uint => compress_le::<u64>(bytes, config),
+    x if x == QcDtype::F32 as c_uint => compress_le::<f32>(bytes, config),
+    x if x == QcDtype::F64 as c_uint => compress_le::<f64>(bytes, config),
+    _ => None,
+  }
+}
+
+fn decompress_chunk(bytes: &[u8]) -> Option<Vec<u8>> {
+  // the data type byte comes right after the 4-byte magic header
+  match *bytes.get(4)? {
+    i32::HEADER_BYTE => decompress_le::<i32>(bytes),
+    i64::HEADER_BYTE => decompress_le::<i64>(bytes),
+    u32::HEADER_BYTE => decompress_le::<u32>(bytes),
+    u64::HEADER_BYTE => decompress_le::<u64>(bytes),
+    f32::HEADER_BYTE => decompress_le::<f32>(bytes),
+    f64::HEADER_BYTE => decompress_le::<f64>(bytes),
+    _ => None,
+  }
+}
+
+// Replaces the chunk in `*buf` with its compressed or decompressed
+// equivalent, returning the new number of bytes, or 0 on failure as HDF5
+// expects.
+unsafe extern "C" fn filter(
+  flags: c_uint,
+  cd_nelmts: usize,
+  cd_values: *const c_uint,
+  nbytes: usize,
+  buf_size: *mut usize,
+  buf: *mut *mut c_void,
+) -> usize {
+  if buf.is_null() || buf_size.is_null() || (nbytes > 0 && (*buf).is_null()) {
+    return 0;
+  }
+  let input = if nbytes == 0 {
+    &[]
+  } else {
+    slice::from_raw_parts(*buf as *const u8, nbytes)
+  };
+  let maybe_output = if flags & H5Z_FLAG_REVERSE == 0 {
+    let cd_values = if cd_nelmts == 0 || cd_values.is_null() {
+      &[]
+    } else {
+      slice::from_raw_parts(cd_values, cd_nelmts)
+    };
+    compress_chunk(input, cd_values)
+  } else {
+    decompress_chunk(input)
+  };
+
+  let output = match maybe_output {
+    Some(output) => output,
+    None => return 0,
+  };
+  let new_buf = malloc(cmp::max(output.len(), 1));
+  if new_buf.is_null() {
+    return 0;
+  }
+  ptr::copy_nonoverlapping(output.as_ptr(), new_buf as *mut u8, output.len());
+  free(*buf);
+  *buf = new_buf;
+  *buf_size = output.len();
+  output.len()
+}
+
+/// Tells HDF5 this library is a filter plugin.
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "C" fn H5PLget_plugin_type() -> c_int {
+  H5PL_TYPE_FILTER
+}
+
+/// Returns a pointer to the filter's `H5Z_class2_t` for HDF5 to register.
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "C" fn H5PLget_plugin_info() -> *const c_void {
+  &FILTER_CLASS as *const H5ZClass2 as *const c_void
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // runs the filter like HDF5 would, on a malloc'd copy of `bytes`
+  unsafe fn run_filter(flags: c_uint, cd_values: &[c_uint], bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut buf = malloc(bytes.len());
+    ptr::copy_nonoverlapping(bytes.as_ptr(), buf as *mut u8, bytes.len());
+    let mut buf_size = bytes.len();
+    let n = filter(flags, cd_values.len(), cd_values.as_ptr(), bytes.len(), &mut buf_size, &mut buf);
+    let res = if n == 0 {
+      None
+    } else {
+      Some(slice::from_raw_parts(buf as *const u8, n).to_vec())
+    };
+    free(buf);
+    res
+  }
+
+  #[test]
+  fn test_round_trip() {
+    let nums = (0..1000).map(|i| (i as f64).sqrt()).collect::<Vec<_>>();
+    let bytes = nums.iter().flat_map(|x| x.to_le_bytes()).collect::<Vec<_>>();
+    let cd_values = [QcDtype::F64 as c_uint, 8, 1];
+    unsafe {
+      let compressed = run_filter(0, &cd_values, &bytes).unwrap();
+      assert!(compressed.len() < bytes.len());
+      let decompressed = run_filter(H5Z_FLAG_REVERSE, &cd_values, &compressed).unwrap();
+      assert_eq!(decompressed, bytes);
+    }
+  }
+
+  #[test]
+  fn test_failures() {
+    unsafe {
+      // no dtype
+      assert!(run_filter(0, &[], &[0; 8]).is_none());
+      // not a whole number of values
+      assert!(run_filter(0, &[QcDtype::I32 as c_uint], &[0; 7]).is_none());
+      // compression level out of range
+      assert!(run_filter(0, &[QcDtype::I32 as c_uint, 20, 0], &[0; 8]).is_none());
+      // delta encoding order out of range
+      assert!(run_filter(0, &[QcDtype::I32 as c_uint, 8, 8], &[0; 8]).is_none());
+      // not a .qco file
+      assert!(run_filter(H5Z_FLAG_REVERSE, &[], &[0; 8]).is_none());
+    }
+  }
+
+  #[test]
+  fn test_plugin_info() {
+    assert_eq!(H5PLget_plugin_type(), H5PL_TYPE_FILTER);
+    let class = unsafe { &*(H5PLget_plugin_info() as *const H5ZClass2) };
+    assert_eq!(class.id, QC_HDF5_FILTER_ID);
+    assert!(class.filter.is_some());
+  }
+}
@@ -0,0 +1,481 @@
+//! A stable C API for `q_compress`.
+//!
+//! The header `include/q_compress.h` is generated from this crate by
+//! cbindgen and checked in; see the README to regenerate it.
+//!
+//! Buffer ownership rules:
+//! * Input pointers are only borrowed for the duration of each call.
+//! * Every `QcBytes` or `QcNums` written to an `out` pointer is owned by the
+//!   caller, who must release it exactly once with `qc_free_bytes` or
+//!   `qc_free_nums` respectively.
+//! * On a non-OK status, nothing is written to `out` and nothing needs to be
+//!   freed.
+
+use std::ffi::c_void;
+use std::os::raw::c_char;
+use std::{ptr, slice};
+
+use q_compress::{Compressor, CompressorConfig, Decompressor, DEFAULT_COMPRESSION_LEVEL};
+use q_compress::data_types::NumberLike;
+use q_compress::errors::{ErrorKind, QCompressResult};
+
+mod hdf5;
+
+/// The result of every fallible function.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QcStatus {
+  Ok = 0,
+  /// The data comes from a newer, incompatible version of q_compress.
+  Compatibility = 1,
+  /// The data is inconsistent or violates the .qco format.
+  Corruption = 2,
+  /// The data ended before decompression finished.
+  InsufficientData = 3,
+  /// A parameter was invalid, e.g. a null pointer or an out-of-range
+  /// compressor config.
+  InvalidArgument = 4,
+}
+
+/// The number types supported by this API.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QcDtype {
+  I32 = 0,
+  I64 = 1,
+  U32 = 2,
+  U64 = 3,
+  F32 = 4,
+  F64 = 5,
+}
+
+/// Configuration for compression.
+/// Obtain defaults from `qc_default_compressor_config`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct QcCompressorConfig {
+  /// From 0 to 12; higher compresses better but more slowly.
+  pub compression_level: usize,
+  /// From 0 to 7; 0 disables delta encoding.
+  pub delta_encoding_order: usize,
+  /// Whether to detect and use greatest common divisors of ranges.
+  pub use_gcds: bool,
+}
+
+/// Compressed bytes owned by the caller; release with `qc_free_bytes`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct QcBytes {
+  pub data: *mut u8,
+  pub len: usize,
+}
+
+/// Decompressed numbers owned by the caller; release with `qc_free_nums`.
+/// `data` points to `len` numbers of type `dtype`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct QcNums {
+  pub data: *mut c_void,
+  pub len: usize,
+  pub dtype: QcDtype,
+}
+
+impl From<ErrorKind> for QcStatus {
+  fn from(kind: ErrorKind) -> Self {
+    match kind {
+      ErrorKind::Compatibility => QcStatus::Compatibility,
+      ErrorKind::Corruption => QcStatus::Corruption,
+      ErrorKind::InsufficientData => QcStatus::InsufficientData,
+      ErrorKind::InvalidArgument => QcStatus::InvalidArgument,
+    }
+  }
+}
+
+impl From<&QcCompressorConfig> for CompressorConfig {
+  fn from(config: &QcCompressorConfig) -> Self {
+    CompressorConfig::default()
+      .with_compression_level(config.compression_level)
+      .with_delta_encoding_order(config.delta_encoding_order)
+      .with_use_gcds(config.use_gcds)
+  }
+}
+
+/// Returns the default compressor configuration.
+#[no_mangle]
+pub extern "C" fn qc_default_compressor_config() -> QcCompressorConfig {
+  let config = CompressorConfig::default();
+  QcCompressorConfig {
+    compression_level: DEFAULT_COMPRESSION_LEVEL,
+    delta_encoding_order: config.delta_encoding_order,
+    use_gcds: config.use_gcds,
+  }
+}
+
+/// Returns a static, null-terminated description of the status.
+#[no_mangle]
+pub extern "C" fn qc_status_message(status: QcStatus) -> *const c_char {
+  let message: &'static [u8] = match status {
+    QcStatus::Ok => b"ok\0",
+    QcStatus::Compatibility => b"data is from an incompatible version of q_compress\0",
+    QcStatus::Corruption => b"data is corrupt\0",
+    QcStatus::InsufficientData => b"data ended before decompression finished\0",
+    QcStatus::InvalidArgument => b"invalid argument\0",
+  };
+  message.as_ptr() as *const c_char
+}
+
+// an empty slice may come with a null or dangling pointer, which
+// slice::from_raw_parts does not allow
+unsafe fn borrow_slice<'a, T>(ptr: *const T, len: usize) -> Option<&'a [T]> {
+  if len == 0 {
+    Some(&[])
+  } else if ptr.is_null() {
+    None
+  } else {
+    Some(slice::from_raw_parts(ptr, len))
+  }
+}
+
+fn config_or_default(config: *const QcCompressorConfig) -> CompressorConfig {
+  match unsafe { config.as_ref() } {
+    Some(config) => CompressorConfig::from(config),
+    None => CompressorConfig::default(),
+  }
+}
+
+unsafe fn compress<T: NumberLike>(
+  nums: *const T,
+  len: usize,
+  config: *const QcCompressorConfig,
+  out: *mut QcBytes,
+) -> QcStatus {
+  let nums = match borrow_slice(nums, len) {
+    Some(nums) if !out.is_null() => nums,
+    _ => return QcStatus::InvalidArgument,
+  };
+  let mut compressor = match Compressor::<T>::try_from_config(config_or_default(config)) {
+    Ok(compressor) => compressor,
+    Err(e) => return QcStatus::from(e.kind),
+  };
+  let bytes = compressor.simple_compress(nums).into_boxed_slice();
+  let len = bytes.len();
+  out.write(QcBytes {
+    data: Box::into_raw(bytes) as *mut u8,
+    len,
+  });
+  QcStatus::Ok
+}
+
+fn decompress_vec<T: NumberLike>(bytes: &[u8]) -> QCompressResult<Vec<T>> {
+  let mut decompressor = Decompressor::<T>::default();
+  decompressor.extend_bytes(bytes);
+  decompressor.simple_decompress()
+}
+
+unsafe fn decompress<T: NumberLike>(
+  bytes: *const u8,
+  len: usize,
+  dtype: QcDtype,
+  out: *mut QcNums,
+) -> QcStatus {
+  let bytes = match borrow_slice(bytes, len) {
+    Some(bytes) if !out.is_null() => bytes,
+    _ => return QcStatus::InvalidArgument,
+  };
+  match decompress_vec::<T>(bytes) {
+    Ok(nums) => {
+      let nums = nums.into_boxed_slice();
+      let len = nums.len();
+      out.write(QcNums {
+        data: Box::into_raw(nums) as *mut c_void,
+        len,
+        dtype,
+      });
+      QcStatus::Ok
+    }
+    Err(e) => QcStatus::from(e.kind),
+  }
+}
+
+unsafe fn free_boxed_slice<T>(data: *mut c_void, len: usize) {
+  if !data.is_null() {
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data as *mut T, len)));
+  }
+}
+
+/// Releases bytes returned by a `qc_compress_*` function.
+///
+/// # Safety
+/// `bytes` must have been written by a `qc_compress_*` function and not
+/// already freed.
+#[no_mangle]
+pub unsafe extern "C" fn qc_free_bytes(bytes: QcBytes) {
+  free_boxed_slice::<u8>(bytes.data as *mut c_void, bytes.len);
+}
+
+/// Releases numbers returned by a `qc_decompress_*` function.
+///
+/// # Safety
+/// `nums` must have been written by a `qc_decompress_*` function and not
+/// already freed.
+#[no_mangle]
+pub unsafe extern "C" fn qc_free_nums(nums: QcNums) {
+  match nums.dtype {
+    QcDtype::I32 => free_boxed_slice::<i32>(nums.data, nums.len),
+    QcDtype::I64 => free_boxed_slice::<i64>(nums.data, nums.len),
+    QcDtype::U32 => free_boxed_slice::<u32>(nums.data, nums.len),
+    QcDtype::U64 => free_boxed_slice::<u64>(nums.data, nums.len),
+    QcDtype::F32 => free_boxed_slice::<f32>(nums.data, nums.len),
+    QcDtype::F64 => free_boxed_slice::<f64>(nums.data, nums.len),
+  }
+}
+
+// These are written out rather than generated by a macro so that cbindgen
+// can see them.
+/// Compresses `len` `i32`s into a .qco file, writing the caller-owned result
+/// to `out`.
+/// `config` may be null to use the default configuration.
+///
+/// # Safety
+/// `nums` must point to `len` readable numbers and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn qc_compress_i32(
+  nums: *const i32,
+  len: usize,
+  config: *const QcCompressorConfig,
+  out: *mut QcBytes,
+) -> QcStatus {
+  compress(nums, len, config, out)
+}
+
+/// Decompresses a .qco file of `i32`s, writing the caller-owned result to
+/// `out`.
+///
+/// # Safety
+/// `bytes` must point to `len` readable bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn qc_decompress_i32(
+  bytes: *const u8,
+  len: usize,
+  out: *mut QcNums,
+) -> QcStatus {
+  decompress::<i32>(bytes, len, QcDtype::I32, out)
+}
+
+/// Compresses `len` `i64`s into a .qco file, writing the caller-owned result
+/// to `out`.
+/// `config` may be null to use the default configuration.
+///
+/// # Safety
+/// `nums` must point to `len` readable numbers and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn qc_compress_i64(
+  nums: *const i64,
+  len: usize,
+  config: *const QcCompressorConfig,
+  out: *mut QcBytes,
+) -> QcStatus {
+  compress(nums, len, config, out)
+}
+
+/// Decompresses a .qco file of `i64`s, writing the caller-owned result to
+/// `out`.
+///
+/// # Safety
+/// `bytes` must point to `len` readable bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn qc_decompress_i64(
+  bytes: *const u8,
+  len: usize,
+  out: *mut QcNums,
+) -> QcStatus {
+  decompress::<i64>(bytes, len, QcDtype::I64, out)
+}
+
+/// Compresses `len` `u32`s into a .qco file, writing the caller-owned result
+/// to `out`.
+/// `config` may be null to use the default configuration.
+///
+/// # Safety
+/// `nums` must point to `len` readable numbers and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn qc_compress_u32(
+  nums: *const u32,
+  len: usize,
+  config: *const QcCompressorConfig,
+  out: *mut QcBytes,
+) -> QcStatus {
+  compress(nums, len, config, out)
+}
+
+/// Decompresses a .qco file of `u32`s, writing the caller-owned result to
+/// `out`.
+///
+/// # Safety
+/// `bytes` must point to `len` readable bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn qc_decompress_u32(
+  bytes: *const u8,
+  len: usize,
+  out: *mut QcNums,
+) -> QcStatus {
+  decompress::<u32>(bytes, len, QcDtype::U32, out)
+}
+
+/// Compresses `len` `u64`s into a .qco file, writing the caller-owned result
+/// to `out`.
+/// `config` may be null to use the default configuration.
+///
+/// # Safety
+/// `nums` must point to `len` readable numbers and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn qc_compress_u64(
+  nums: *const u64,
+  len: usize,
+  config: *const QcCompressorConfig,
+  out: *mut QcBytes,
+) -> QcStatus {
+  compress(nums, len, config, out)
+}
+
+/// Decompresses a .qco file of `u64`s, writing the caller-owned result to
+/// `out`.
+///
+/// # Safety
+/// `bytes` must point to `len` readable bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn qc_decompress_u64(
+  bytes: *const u8,
+  len: usize,
+  out: *mut QcNums,
+) -> QcStatus {
+  decompress::<u64>(bytes, len, QcDtype::U64, out)
+}
+
+/// Compresses `len` `f32`s into a .qco file, writing the caller-owned result
+/// to `out`.
+/// `config` may be null to use the default configuration.
+///
+/// # Safety
+/// `nums` must point to `len` readable numbers and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn qc_compress_f32(
+  nums: *const f32,
+  len: usize,
+  config: *const QcCompressorConfig,
+  out: *mut QcBytes,
+) -> QcStatus {
+  compress(nums, len, config, out)
+}
+
+/// Decompresses a .qco file of `f32`s, writing the caller-owned result to
+/// `out`.
+///
+/// # Safety
+/// `bytes` must point to `len` readable bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn qc_decompress_f32(
+  bytes: *const u8,
+  len: usize,
+  out: *mut QcNums,
+) -> QcStatus {
+  decompress::<f32>(bytes, len, QcDtype::F32, out)
+}
+
+/// Compresses `len` `f64`s into a .qco file, writing the caller-owned result
+/// to `out`.
+/// `config` may be null to use the default configuration.
+///
+/// # Safety
+/// `nums` must point to `len` readable numbers and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn qc_compress_f64(
+  nums: *const f64,
+  len: usize,
+  config: *const QcCompressorConfig,
+  out: *mut QcBytes,
+) -> QcStatus {
+  compress(nums, len, config, out)
+}
+
+/// Decompresses a .qco file of `f64`s, writing the caller-owned result to
+/// `out`.
+///
+/// # Safety
+/// `bytes` must point to `len` readable bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn qc_decompress_f64(
+  bytes: *const u8,
+  len: usize,
+  out: *mut QcNums,
+) -> QcStatus {
+  decompress::<f64>(bytes, len, QcDtype::F64, out)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::mem::MaybeUninit;
+  use std::ptr;
+  use std::slice;
+
+  use super::*;
+
+  #[test]
+  fn test_round_trip() {
+    let nums = [-3_i64, 7, 7, 100, i64::MAX];
+    let config = QcCompressorConfig {
+      delta_encoding_order: 1,
+      ..qc_default_compressor_config()
+    };
+    unsafe {
+      let mut bytes = MaybeUninit::<QcBytes>::uninit();
+      let status = qc_compress_i64(nums.as_ptr(), nums.len(), &config, bytes.as_mut_ptr());
+      assert_eq!(status, QcStatus::Ok);
+      let bytes = bytes.assume_init();
+
+      let mut rec = MaybeUninit::<QcNums>::uninit();
+      let status = qc_decompress_i64(bytes.data, bytes.len, rec.as_mut_ptr());
+      assert_eq!(status, QcStatus::Ok);
+      let rec = rec.assume_init();
+      assert_eq!(rec.dtype, QcDtype::I64);
+      assert_eq!(slice::from_raw_parts(rec.data as *const i64, rec.len), &nums[..]);
+
+      qc_free_bytes(bytes);
+      qc_free_nums(rec);
+    }
+  }
+
+  #[test]
+  fn test_errors() {
+    unsafe {
+      let mut bytes = MaybeUninit::<QcBytes>::uninit();
+      let status = qc_compress_f32(ptr::null(), 3, ptr::null(), bytes.as_mut_ptr());
+      assert_eq!(status, QcStatus::InvalidArgument);
+
+      let mut nums = MaybeUninit::<QcNums>::uninit();
+      let truncated = [113_u8, 99, 111];
+      let status = qc_decompress_f32(truncated.as_ptr(), truncated.len(), nums.as_mut_ptr());
+      assert_eq!(status, QcStatus::InsufficientData);
+    }
+  }
+
+  #[test]
+  fn test_invalid_config() {
+    let nums = [1_i32, 2, 3];
+    let bad_order = QcCompressorConfig {
+      delta_encoding_order: 8,
+      ..qc_default_compressor_config()
+    };
+    let bad_level = QcCompressorConfig {
+      compression_level: 100,
+      ..qc_default_compressor_config()
+    };
+    for config in [bad_order, bad_level] {
+      unsafe {
+        let mut bytes = MaybeUninit::<QcBytes>::uninit();
+        let status = qc_compress_i32(nums.as_ptr(), nums.len(), &config, bytes.as_mut_ptr());
+        assert_eq!(status, QcStatus::InvalidArgument);
+      }
+    }
+  }
+}
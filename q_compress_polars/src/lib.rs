@@ -0,0 +1,417 @@
+//! Polars support for `q_compress`.
+//!
+//! [`QcoSeriesExt`] adds `qco_compress` and `qco_decompress` to
+//! [`Series`], and [`scan_qco_dir`] reads a directory of .qco files as a
+//! [`LazyFrame`] with one column per file.
+//!
+//! Each series becomes a standard .qco file whose header user metadata
+//! records the Polars data type and, if the series has nulls, its validity.
+//! Only the non-null values are compressed.
+//! Supported data types are `Int8` through `Int64`, `UInt8` through `UInt64`,
+//! `Float32`, `Float64`, and `Datetime` with any time unit and time zone.
+//! ```
+//! use polars::prelude::*;
+//! use q_compress::CompressorConfig;
+//! use q_compress_polars::QcoSeriesExt;
+//!
+//! let series = Series::new("temperature".into(), &[Some(20.5_f64), None, Some(21.0)]);
+//! let bytes = series.qco_compress(CompressorConfig::default()).expect("compress");
+//! let recovered = Series::qco_decompress("temperature", &bytes).expect("decompress");
+//! assert!(recovered.equals_missing(&series));
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use polars::prelude::*;
+use q_compress::{Compressor, CompressorConfig, Decompressor, UserMetadata};
+use q_compress::data_types::{NumberLike, TimestampMicros, TimestampNanos};
+use q_compress::errors::QCompressError;
+
+/// The header metadata key for the series' Polars data type.
+pub const DTYPE_KEY: &str = "polars.dtype";
+/// The header metadata key for a `Datetime` series' time zone, present only
+/// if it has one.
+pub const TIME_ZONE_KEY: &str = "polars.time_zone";
+/// The header metadata key for the series' validity, present only if the
+/// series has nulls.
+/// It is stored as the series length in 8 little-endian bytes, followed by
+/// 1 bit per element (1 for valid), least significant bit first.
+pub const VALIDITY_KEY: &str = "polars.validity";
+
+const VALIDITY_LEN_BYTES: usize = 8;
+// the data type byte comes right after the 4-byte magic header
+const HEADER_BYTE_IDX: usize = 4;
+
+fn qco_err(err: QCompressError) -> PolarsError {
+  polars_err!(ComputeError: "{}", err)
+}
+
+fn time_unit_name(time_unit: TimeUnit) -> &'static str {
+  match time_unit {
+    TimeUnit::Milliseconds => "datetime[ms]",
+    TimeUnit::Microseconds => "datetime[us]",
+    TimeUnit::Nanoseconds => "datetime[ns]",
+  }
+}
+
+fn dtype_name(dtype: &DataType) -> PolarsResult<&'static str> {
+  let res = match dtype {
+    DataType::Int8 => "i8",
+    DataType::Int16 => "i16",
+    DataType::Int32 => "i32",
+    DataType::Int64 => "i64",
+    DataType::UInt8 => "u8",
+    DataType::UInt16 => "u16",
+    DataType::UInt32 => "u32",
+    DataType::UInt64 => "u64",
+    DataType::Float32 => "f32",
+    DataType::Float64 => "f64",
+    DataType::Datetime(time_unit, _) => time_unit_name(*time_unit),
+    other => polars_bail!(InvalidOperation: "unsupported data type for qco: {}", other),
+  };
+  Ok(res)
+}
+
+fn validity_bytes(series: &Series) -> Vec<u8> {
+  let mut res = (series.len() as u64).to_le_bytes().to_vec();
+  res.resize(VALIDITY_LEN_BYTES + series.len().div_ceil(8), 0);
+  for (i, is_valid) in series.is_not_null().into_no_null_iter().enumerate() {
+    if is_valid {
+      res[VALIDITY_LEN_BYTES + i / 8] |= 1 << (i % 8);
+    }
+  }
+  res
+}
+
+fn parse_validity(bytes: &[u8]) -> PolarsResult<Vec<bool>> {
+  polars_ensure!(bytes.len() >= VALIDITY_LEN_BYTES, ComputeError: "invalid qco validity");
+  let mut len_bytes = [0; VALIDITY_LEN_BYTES];
+  len_bytes.copy_from_slice(&bytes[..VALIDITY_LEN_BYTES]);
+  let len = u64::from_le_bytes(len_bytes) as usize;
+  let bitmap = &bytes[VALIDITY_LEN_BYTES..];
+  polars_ensure!(bitmap.len() == len.div_ceil(8), ComputeError: "invalid qco validity");
+  Ok((0..len).map(|i| bitmap[i / 8] & (1 << (i % 8)) > 0).collect())
+}
+
+fn compress_chunked<P, T, F>(
+  ca: &ChunkedArray<P>,
+  config: CompressorConfig,
+  metadata: UserMetadata,
+  convert: F,
+) -> PolarsResult<Vec<u8>>
+where P: PolarsNumericType, T: NumberLike, F: Fn(P::Native) -> T {
+  let nums = ca.into_iter()
+    .flatten()
+    .map(convert)
+    .collect::<Vec<_>>();
+  let mut compressor = Compressor::<T>::try_from_config(config).map_err(qco_err)?;
+  compressor.set_header_metadata(metadata).map_err(qco_err)?;
+  Ok(compressor.simple_compress(&nums))
+}
+
+struct DecompressedSeries<T> {
+  nums: Vec<T>,
+  dtype_name: String,
+  time_zone: Option<TimeZone>,
+  validity: Option<Vec<bool>>,
+}
+
+impl<T: NumberLike> DecompressedSeries<T> {
+  fn new(bytes: &[u8]) -> PolarsResult<Self> {
+    let mut decompressor = Decompressor::<T>::default();
+    decompressor.extend_bytes(bytes);
+    let nums = decompressor.simple_decompress().map_err(qco_err)?;
+    let metadata = decompressor.header_metadata();
+
+    let dtype_name = metadata.get(DTYPE_KEY)
+      .and_then(|bytes| String::from_utf8(bytes.clone()).ok())
+      .ok_or_else(|| polars_err!(
+        ComputeError: "qco file has no polars data type; was it written by qco_compress?"
+      ))?;
+    let time_zone = metadata.get(TIME_ZONE_KEY)
+      .map(|bytes| String::from_utf8(bytes.clone()))
+      .transpose()
+      .map_err(|_| polars_err!(ComputeError: "invalid qco time zone"))?
+      .map(TimeZone::from);
+    let validity = metadata.get(VALIDITY_KEY)
+      .map(|bytes| parse_validity(bytes))
+      .transpose()?;
+    if let Some(validity) = &validity {
+      let n_valid = validity.iter().filter(|&&is_valid| is_valid).count();
+      polars_ensure!(
+        n_valid == nums.len(),
+        ComputeError: "qco validity has {} valid elements but there are {} numbers", n_valid, nums.len()
+      );
+    }
+
+    Ok(DecompressedSeries { nums, dtype_name, time_zone, validity })
+  }
+
+  fn into_chunked<P, F>(self, name: &str, convert: F) -> ChunkedArray<P>
+  where P: PolarsNumericType, F: Fn(T) -> P::Native {
+    match self.validity {
+      Some(validity) => {
+        let mut nums = self.nums.into_iter();
+        let values = validity.into_iter()
+          .map(|is_valid| if is_valid {
+            nums.next().map(&convert)
+          } else {
+            None
+          });
+        ChunkedArray::from_iter_options(name.into(), values)
+      },
+      None => ChunkedArray::from_vec(
+        name.into(),
+        self.nums.into_iter().map(convert).collect(),
+      ),
+    }
+  }
+
+  fn mismatch_error(&self) -> PolarsError {
+    polars_err!(
+      ComputeError: "polars data type {} does not match qco data type with header byte {}",
+      self.dtype_name,
+      T::HEADER_BYTE,
+    )
+  }
+}
+
+fn into_datetime(ca: Int64Chunked, time_unit: TimeUnit, time_zone: Option<TimeZone>) -> Series {
+  ca.into_datetime(time_unit, time_zone).into_series()
+}
+
+/// Compression and decompression of [`Series`] to and from .qco files.
+pub trait QcoSeriesExt: Sized {
+  /// Compresses the series into a .qco file, enabling
+  /// [`use_user_metadata`][CompressorConfig::use_user_metadata] to record
+  /// its data type and nulls.
+  /// Will return an error if the data type is unsupported or the config is
+  /// invalid.
+  fn qco_compress(&self, config: CompressorConfig) -> PolarsResult<Vec<u8>>;
+
+  /// Decompresses a .qco file written by
+  /// [`qco_compress`][QcoSeriesExt::qco_compress] into a series with the
+  /// given name and the original data type and nulls.
+  fn qco_decompress(name: &str, bytes: &[u8]) -> PolarsResult<Self>;
+}
+
+impl QcoSeriesExt for Series {
+  fn qco_compress(&self, config: CompressorConfig) -> PolarsResult<Vec<u8>> {
+    let dtype = self.dtype();
+    let mut metadata = UserMetadata::new();
+    metadata.insert(DTYPE_KEY.to_string(), dtype_name(dtype)?.as_bytes().to_vec());
+    if let DataType::Datetime(_, Some(time_zone)) = dtype {
+      metadata.insert(TIME_ZONE_KEY.to_string(), time_zone.as_bytes().to_vec());
+    }
+    if self.null_count() > 0 {
+      metadata.insert(VALIDITY_KEY.to_string(), validity_bytes(self));
+    }
+    let config = config.with_use_user_metadata(true);
+
+    match dtype {
+      DataType::Int8 => compress_chunked(self.i8()?, config, metadata, i16::from),
+      DataType::Int16 => compress_chunked(self.i16()?, config, metadata, |x| x),
+      DataType::Int32 => compress_chunked(self.i32()?, config, metadata, |x| x),
+      DataType::Int64 => compress_chunked(self.i64()?, config, metadata, |x| x),
+      DataType::UInt8 => compress_chunked(self.u8()?, config, metadata, u16::from),
+      DataType::UInt16 => compress_chunked(self.u16()?, config, metadata, |x| x),
+      DataType::UInt32 => compress_chunked(self.u32()?, config, metadata, |x| x),
+      DataType::UInt64 => compress_chunked(self.u64()?, config, metadata, |x| x),
+      DataType::Float32 => compress_chunked(self.f32()?, config, metadata, |x| x),
+      DataType::Float64 => compress_chunked(self.f64()?, config, metadata, |x| x),
+      DataType::Datetime(time_unit, _) => {
+        let physical = self.to_physical_repr();
+        let ca = physical.i64()?;
+        match time_unit {
+          TimeUnit::Milliseconds => compress_chunked(ca, config, metadata, |x| x),
+          TimeUnit::Microseconds => compress_chunked(ca, config, metadata, TimestampMicros::new),
+          TimeUnit::Nanoseconds => compress_chunked(ca, config, metadata, TimestampNanos::new),
+        }
+      },
+      // dtype_name already rejected other data types
+      _ => unreachable!(),
+    }
+  }
+
+  fn qco_decompress(name: &str, bytes: &[u8]) -> PolarsResult<Self> {
+    polars_ensure!(
+      bytes.len() > HEADER_BYTE_IDX,
+      ComputeError: "only {} bytes found in qco file", bytes.len()
+    );
+
+    match bytes[HEADER_BYTE_IDX] {
+      i16::HEADER_BYTE => {
+        let decompressed = DecompressedSeries::<i16>::new(bytes)?;
+        match decompressed.dtype_name.as_str() {
+          "i8" => Ok(decompressed.into_chunked::<Int8Type, _>(name, |x| x as i8).into_series()),
+          "i16" => Ok(decompressed.into_chunked::<Int16Type, _>(name, |x| x).into_series()),
+          _ => Err(decompressed.mismatch_error()),
+        }
+      },
+      i32::HEADER_BYTE => {
+        let decompressed = DecompressedSeries::<i32>::new(bytes)?;
+        match decompressed.dtype_name.as_str() {
+          "i32" => Ok(decompressed.into_chunked::<Int32Type, _>(name, |x| x).into_series()),
+          _ => Err(decompressed.mismatch_error()),
+        }
+      },
+      i64::HEADER_BYTE => {
+        let decompressed = DecompressedSeries::<i64>::new(bytes)?;
+        let time_zone = decompressed.time_zone.clone();
+        match decompressed.dtype_name.as_str() {
+          "i64" => Ok(decompressed.into_chunked::<Int64Type, _>(name, |x| x).into_series()),
+          "datetime[ms]" => {
+            let ca = decompressed.into_chunked::<Int64Type, _>(name, |x| x);
+            Ok(into_datetime(ca, TimeUnit::Milliseconds, time_zone))
+          },
+          _ => Err(decompressed.mismatch_error()),
+        }
+      },
+      u16::HEADER_BYTE => {
+        let decompressed = DecompressedSeries::<u16>::new(bytes)?;
+        match decompressed.dtype_name.as_str() {
+          "u8" => Ok(decompressed.into_chunked::<UInt8Type, _>(name, |x| x as u8).into_series()),
+          "u16" => Ok(decompressed.into_chunked::<UInt16Type, _>(name, |x| x).into_series()),
+          _ => Err(decompressed.mismatch_error()),
+        }
+      },
+      u32::HEADER_BYTE => {
+        let decompressed = DecompressedSeries::<u32>::new(bytes)?;
+        match decompressed.dtype_name.as_str() {
+          "u32" => Ok(decompressed.into_chunked::<UInt32Type, _>(name, |x| x).into_series()),
+          _ => Err(decompressed.mismatch_error()),
+        }
+      },
+      u64::HEADER_BYTE => {
+        let decompressed = DecompressedSeries::<u64>::new(bytes)?;
+        match decompressed.dtype_name.as_str() {
+          "u64" => Ok(decompressed.into_chunked::<UInt64Type, _>(name, |x| x).into_series()),
+          _ => Err(decompressed.mismatch_error()),
+        }
+      },
+      f32::HEADER_BYTE => {
+        let decompressed = DecompressedSeries::<f32>::new(bytes)?;
+        match decompressed.dtype_name.as_str() {
+          "f32" => Ok(decompressed.into_chunked::<Float32Type, _>(name, |x| x).into_series()),
+          _ => Err(decompressed.mismatch_error()),
+        }
+      },
+      f64::HEADER_BYTE => {
+        let decompressed = DecompressedSeries::<f64>::new(bytes)?;
+        match decompressed.dtype_name.as_str() {
+          "f64" => Ok(decompressed.into_chunked::<Float64Type, _>(name, |x| x).into_series()),
+          _ => Err(decompressed.mismatch_error()),
+        }
+      },
+      TimestampMicros::HEADER_BYTE => {
+        let decompressed = DecompressedSeries::<TimestampMicros>::new(bytes)?;
+        let time_zone = decompressed.time_zone.clone();
+        match decompressed.dtype_name.as_str() {
+          "datetime[us]" => {
+            let ca = decompressed.into_chunked::<Int64Type, _>(name, |x| x.to_total_parts());
+            Ok(into_datetime(ca, TimeUnit::Microseconds, time_zone))
+          },
+          _ => Err(decompressed.mismatch_error()),
+        }
+      },
+      TimestampNanos::HEADER_BYTE => {
+        let decompressed = DecompressedSeries::<TimestampNanos>::new(bytes)?;
+        let time_zone = decompressed.time_zone.clone();
+        match decompressed.dtype_name.as_str() {
+          "datetime[ns]" => {
+            let ca = decompressed.into_chunked::<Int64Type, _>(name, |x| x.to_total_parts());
+            Ok(into_datetime(ca, TimeUnit::Nanoseconds, time_zone))
+          },
+          _ => Err(decompressed.mismatch_error()),
+        }
+      },
+      other => polars_bail!(ComputeError: "qco data type byte {} has no polars equivalent", other),
+    }
+  }
+}
+
+/// Reads every .qco file written by
+/// [`qco_compress`][QcoSeriesExt::qco_compress] in the directory into a
+/// [`LazyFrame`], with one column per file named by its file stem.
+///
+/// Columns are ordered by file name, and every file must have the same
+/// number of elements.
+/// The files are decompressed eagerly, so the frame's plan starts from
+/// in-memory data.
+pub fn scan_qco_dir<P: AsRef<Path>>(dir: P) -> PolarsResult<LazyFrame> {
+  let mut paths = Vec::new();
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+    if path.extension().is_some_and(|ext| ext == "qco") {
+      paths.push(path);
+    }
+  }
+  paths.sort();
+
+  let mut columns = Vec::with_capacity(paths.len());
+  for path in &paths {
+    let name = path.file_stem().unwrap().to_string_lossy();
+    let bytes = fs::read(path)?;
+    columns.push(Series::qco_decompress(&name, &bytes)?.into_column());
+  }
+  Ok(DataFrame::new(columns)?.lazy())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn assert_recovers(series: Series) {
+    let bytes = series.qco_compress(CompressorConfig::default()).unwrap();
+    let recovered = Series::qco_decompress(series.name(), &bytes).unwrap();
+    assert_eq!(recovered.dtype(), series.dtype());
+    assert!(recovered.equals_missing(&series));
+  }
+
+  #[test]
+  fn test_recovers() {
+    assert_recovers(Series::new("a".into(), &[1_u8, 255, 3]));
+    assert_recovers(Series::new("b".into(), &[Some(-5_i8), None, Some(7)]));
+    assert_recovers(Series::new("c".into(), &[Some(1.5_f32), None, None]));
+    assert_recovers(Series::new("d".into(), Vec::<f64>::new()));
+    let datetimes = Int64Chunked::from_slice_options("e".into(), &[Some(1_000_000_i64), None, Some(2_000_000)])
+      .into_datetime(TimeUnit::Microseconds, Some("UTC".into()))
+      .into_series();
+    assert_recovers(datetimes);
+  }
+
+  #[test]
+  fn test_rejects_unsupported() {
+    let series = Series::new("s".into(), &["x", "y"]);
+    assert!(series.qco_compress(CompressorConfig::default()).is_err());
+  }
+
+  #[test]
+  fn test_rejects_invalid_config() {
+    let series = Series::new("s".into(), &[1_i64, 2, 3]);
+    let config = CompressorConfig::default().with_compression_level(13);
+    assert!(series.qco_compress(config).is_err());
+  }
+
+  #[test]
+  fn test_scan_qco_dir() {
+    let dir = std::env::temp_dir().join(format!("q_compress_polars_test_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let price = Series::new("price".into(), &[10.0_f64, 10.5, 11.0]);
+    let volume = Series::new("volume".into(), &[Some(100_i64), None, Some(300)]);
+    for series in [&price, &volume] {
+      let bytes = series.qco_compress(CompressorConfig::default()).unwrap();
+      fs::write(dir.join(format!("{}.qco", series.name())), bytes).unwrap();
+    }
+    fs::write(dir.join("notes.txt"), "not a qco file").unwrap();
+
+    let df = scan_qco_dir(&dir).unwrap()
+      .filter(col("price").gt(lit(10.0)))
+      .collect()
+      .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(df.get_column_names(), &["price", "volume"]);
+    assert_eq!(df.height(), 2);
+  }
+}
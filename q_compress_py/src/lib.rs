@@ -0,0 +1,371 @@
+//! Python bindings for `q_compress`, built with `pyo3` and `numpy`.
+//!
+//! The module exposes `compress(nums, config=None)`, taking a 1D numpy array,
+//! and `decompress(bytes)`, returning a 1D numpy array whose dtype is
+//! determined by the .qco header.
+//! It also exposes `QcoCodec`, a numcodecs-compatible codec for using
+//! q_compress as a Zarr chunk compressor.
+
+use numpy::{Element, PyArray1, PyArrayMethods};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyType};
+use q_compress::{auto_compressor_config, Compressor, Decompressor, DEFAULT_COMPRESSION_LEVEL};
+use q_compress::data_types::{NumberLike, TimestampMicros, TimestampNanos};
+use q_compress::errors::{QCompressError, QCompressResult};
+
+const HEADER_BYTE_IDX: usize = 4;
+const CODEC_ID: &str = "qco";
+
+/// Configuration for `compress`.
+///
+/// If `delta_encoding_order` is `None`, it is chosen automatically from
+/// the data.
+#[pyclass(module = "q_compress")]
+#[derive(Clone, Debug)]
+pub struct CompressorConfig {
+  #[pyo3(get, set)]
+  pub compression_level: usize,
+  #[pyo3(get, set)]
+  pub delta_encoding_order: Option<usize>,
+  #[pyo3(get, set)]
+  pub use_gcds: bool,
+}
+
+#[pymethods]
+impl CompressorConfig {
+  #[new]
+  #[pyo3(signature = (compression_level=DEFAULT_COMPRESSION_LEVEL, delta_encoding_order=None, use_gcds=true))]
+  fn new(compression_level: usize, delta_encoding_order: Option<usize>, use_gcds: bool) -> Self {
+    Self {
+      compression_level,
+      delta_encoding_order,
+      use_gcds,
+    }
+  }
+
+  fn __repr__(&self) -> String {
+    format!("{:?}", self)
+  }
+}
+
+impl Default for CompressorConfig {
+  fn default() -> Self {
+    Self::new(DEFAULT_COMPRESSION_LEVEL, None, true)
+  }
+}
+
+fn to_py_err(e: QCompressError) -> PyErr {
+  PyValueError::new_err(e.to_string())
+}
+
+// Returns an error for invalid configs rather than panicking, since
+// panics would reach Python as PanicExceptions instead of ValueErrors.
+// Empty arrays are fine: the automatic config leaves delta encoding off.
+fn compress_nums<T: NumberLike>(nums: &[T], config: &CompressorConfig) -> QCompressResult<Vec<u8>> {
+  let rust_config = match config.delta_encoding_order {
+    Some(order) => q_compress::CompressorConfig::default()
+      .with_compression_level(config.compression_level)
+      .with_delta_encoding_order(order),
+    None => auto_compressor_config(nums, config.compression_level),
+  }.with_use_gcds(config.use_gcds);
+  Ok(Compressor::<T>::try_from_config(rust_config)?.simple_compress(nums))
+}
+
+// returns None if `nums` is not an array of T
+fn try_compress_array<T: NumberLike + Element>(
+  nums: &Bound<'_, PyAny>,
+  config: &CompressorConfig,
+) -> Option<PyResult<Vec<u8>>> {
+  let arr = nums.cast::<PyArray1<T>>().ok()?;
+  let py = nums.py();
+  let readonly = arr.readonly();
+  let res = match readonly.as_slice() {
+    Ok(slice) => py.detach(|| compress_nums(slice, config)),
+    // non-contiguous arrays need to be copied first
+    Err(_) => {
+      let owned = readonly.as_array().to_vec();
+      py.detach(|| compress_nums(&owned, config))
+    }
+  };
+  Some(res.map_err(to_py_err))
+}
+
+/// Compresses a 1D numpy array into .qco bytes.
+///
+/// Supported dtypes are bool, int16, int32, int64, uint16, uint32, uint64,
+/// float32, and float64.
+#[pyfunction]
+#[pyo3(signature = (nums, config=None))]
+fn compress<'py>(
+  nums: &Bound<'py, PyAny>,
+  config: Option<CompressorConfig>,
+) -> PyResult<Bound<'py, PyBytes>> {
+  let config = config.unwrap_or_default();
+  let maybe_bytes = try_compress_array::<bool>(nums, &config)
+    .or_else(|| try_compress_array::<i16>(nums, &config))
+    .or_else(|| try_compress_array::<i32>(nums, &config))
+    .or_else(|| try_compress_array::<i64>(nums, &config))
+    .or_else(|| try_compress_array::<u16>(nums, &config))
+    .or_else(|| try_compress_array::<u32>(nums, &config))
+    .or_else(|| try_compress_array::<u64>(nums, &config))
+    .or_else(|| try_compress_array::<f32>(nums, &config))
+    .or_else(|| try_compress_array::<f64>(nums, &config));
+  match maybe_bytes {
+    Some(bytes) => Ok(PyBytes::new(nums.py(), &bytes?)),
+    None => Err(PyValueError::new_err(format!(
+      "expected a 1D numpy array of a supported dtype; found {}",
+      nums.get_type().name()?,
+    ))),
+  }
+}
+
+fn decompress_nums<T: NumberLike + Send>(py: Python<'_>, bytes: &[u8]) -> PyResult<Vec<T>> {
+  py.detach(|| {
+    let mut decompressor = Decompressor::<T>::default();
+    decompressor.extend_bytes(bytes);
+    decompressor.simple_decompress()
+  }).map_err(to_py_err)
+}
+
+// The output array takes ownership of the decompressed vector, so no copy is made.
+fn decompress_array<'py, T: NumberLike + Element>(
+  py: Python<'py>,
+  bytes: &[u8],
+) -> PyResult<Bound<'py, PyAny>> {
+  let nums = decompress_nums::<T>(py, bytes)?;
+  Ok(PyArray1::from_vec(py, nums).into_any())
+}
+
+// numpy has no equivalent timestamp type, so we return the total number of
+// microseconds or nanoseconds since the Unix Epoch as int64s
+fn decompress_timestamp_array<'py, T: NumberLike + Send, F: Fn(T) -> i64 + Send + Sync>(
+  py: Python<'py>,
+  bytes: &[u8],
+  to_parts: F,
+) -> PyResult<Bound<'py, PyAny>> {
+  let timestamps = decompress_nums::<T>(py, bytes)?;
+  let parts = timestamps.into_iter().map(to_parts).collect::<Vec<_>>();
+  Ok(PyArray1::from_vec(py, parts).into_any())
+}
+
+/// Decompresses .qco bytes into a 1D numpy array.
+///
+/// The dtype is detected from the .qco header.
+/// Timestamps are returned as int64 microseconds or nanoseconds since the
+/// Unix Epoch.
+#[pyfunction]
+fn decompress<'py>(py: Python<'py>, bytes: &[u8]) -> PyResult<Bound<'py, PyAny>> {
+  let header_byte = match bytes.get(HEADER_BYTE_IDX) {
+    Some(&byte) => byte,
+    None => return Err(PyValueError::new_err(
+      "bytes are too short to contain a .qco header"
+    )),
+  };
+  match header_byte {
+    b if b == bool::HEADER_BYTE => decompress_array::<bool>(py, bytes),
+    b if b == i16::HEADER_BYTE => decompress_array::<i16>(py, bytes),
+    b if b == i32::HEADER_BYTE => decompress_array::<i32>(py, bytes),
+    b if b == i64::HEADER_BYTE => decompress_array::<i64>(py, bytes),
+    b if b == u16::HEADER_BYTE => decompress_array::<u16>(py, bytes),
+    b if b == u32::HEADER_BYTE => decompress_array::<u32>(py, bytes),
+    b if b == u64::HEADER_BYTE => decompress_array::<u64>(py, bytes),
+    b if b == f32::HEADER_BYTE => decompress_array::<f32>(py, bytes),
+    b if b == f64::HEADER_BYTE => decompress_array::<f64>(py, bytes),
+    b if b == TimestampMicros::HEADER_BYTE => decompress_timestamp_array(
+      py,
+      bytes,
+      TimestampMicros::to_total_parts,
+    ),
+    b if b == TimestampNanos::HEADER_BYTE => decompress_timestamp_array(
+      py,
+      bytes,
+      TimestampNanos::to_total_parts,
+    ),
+    b => Err(PyValueError::new_err(format!(
+      "unsupported or unknown .qco data type byte: {}",
+      b,
+    ))),
+  }
+}
+
+// Flattens `buf` into a contiguous 1D numpy array. Non-array buffers are read
+// as bytes, and if `dtype` is given, the array's bytes are reinterpreted as
+// that dtype.
+fn flat_array<'py>(buf: &Bound<'py, PyAny>, dtype: Option<&str>) -> PyResult<Bound<'py, PyAny>> {
+  let np = buf.py().import("numpy")?;
+  let arr = if buf.hasattr("dtype")? {
+    np.call_method1("ascontiguousarray", (buf,))?
+  } else {
+    np.call_method1("frombuffer", (buf, "u1"))?
+  };
+  let flat = arr.call_method1("reshape", (-1,))?;
+  match dtype {
+    Some(dtype) => flat.call_method1("view", (dtype,)),
+    None => Ok(flat),
+  }
+}
+
+// Returns a flat view of `out` with the given dtype, for filling in place.
+// Reshaping a non-contiguous array makes a copy, and filling that copy
+// would silently leave `out` untouched, so such arrays are rejected.
+fn flat_out_view<'py>(out: &Bound<'py, PyAny>, dtype: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+  let flat = if out.hasattr("dtype")? {
+    let c_contiguous = out.getattr("flags")?
+      .getattr("c_contiguous")?
+      .extract::<bool>()?;
+    if !c_contiguous {
+      return Err(PyValueError::new_err(
+        "QcoCodec can only decode into C-contiguous output arrays"
+      ));
+    }
+    out.call_method1("reshape", (-1,))?
+  } else {
+    out.py().import("numpy")?.call_method1("frombuffer", (out, "u1"))?
+  };
+  flat.call_method1("view", (dtype,))
+}
+
+/// A codec following the numcodecs `Codec` interface, so q_compress can
+/// compress Zarr chunks.
+///
+/// `encode` flattens each chunk in C order and compresses it.
+/// Chunks that arrive as raw bytes (e.g. after another filter) are
+/// interpreted as `dtype`, which must then be set; typed arrays are
+/// reinterpreted as `dtype` if it is set.
+/// `decode` returns a flat array of the compressed dtype, or fills `out`
+/// (reinterpreting its bytes as that dtype) if it is given; `out` must be
+/// a C-contiguous array or a writable buffer.
+///
+/// Register it with `numcodecs.register_codec(q_compress.QcoCodec)` to
+/// open arrays written with it by codec id.
+#[pyclass(module = "q_compress")]
+#[derive(Clone, Debug)]
+pub struct QcoCodec {
+  #[pyo3(get)]
+  pub dtype: Option<String>,
+  #[pyo3(get)]
+  pub config: CompressorConfig,
+}
+
+#[pymethods]
+impl QcoCodec {
+  #[classattr]
+  fn codec_id() -> &'static str {
+    CODEC_ID
+  }
+
+  #[new]
+  #[pyo3(signature = (dtype=None, compression_level=DEFAULT_COMPRESSION_LEVEL, delta_encoding_order=None, use_gcds=true))]
+  fn new(
+    dtype: Option<String>,
+    compression_level: usize,
+    delta_encoding_order: Option<usize>,
+    use_gcds: bool,
+  ) -> Self {
+    Self {
+      dtype,
+      config: CompressorConfig::new(compression_level, delta_encoding_order, use_gcds),
+    }
+  }
+
+  fn encode<'py>(&self, buf: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyBytes>> {
+    if self.dtype.is_none() && !buf.hasattr("dtype")? {
+      return Err(PyValueError::new_err(
+        "QcoCodec needs a dtype to encode untyped buffers"
+      ));
+    }
+    let arr = flat_array(buf, self.dtype.as_deref())?;
+    compress(&arr, Some(self.config.clone()))
+  }
+
+  #[pyo3(signature = (buf, out=None))]
+  fn decode<'py>(
+    &self,
+    py: Python<'py>,
+    buf: &Bound<'py, PyAny>,
+    out: Option<Bound<'py, PyAny>>,
+  ) -> PyResult<Bound<'py, PyAny>> {
+    let bytes = flat_array(buf, None)?
+      .call_method0("tobytes")?
+      .extract::<Vec<u8>>()?;
+    let decompressed = decompress(py, &bytes)?;
+    match out {
+      Some(out) => {
+        let flat_out = flat_out_view(&out, &decompressed.getattr("dtype")?)?;
+        flat_out.set_item(py.Ellipsis(), decompressed)?;
+        Ok(out)
+      },
+      None => Ok(decompressed),
+    }
+  }
+
+  fn get_config<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+    let res = PyDict::new(py);
+    res.set_item("id", CODEC_ID)?;
+    res.set_item("dtype", &self.dtype)?;
+    res.set_item("compression_level", self.config.compression_level)?;
+    res.set_item("delta_encoding_order", self.config.delta_encoding_order)?;
+    res.set_item("use_gcds", self.config.use_gcds)?;
+    Ok(res)
+  }
+
+  #[classmethod]
+  fn from_config(_cls: &Bound<'_, PyType>, config: &Bound<'_, PyDict>) -> PyResult<Self> {
+    let mut res = Self::new(None, DEFAULT_COMPRESSION_LEVEL, None, true);
+    if let Some(dtype) = config.get_item("dtype")? {
+      res.dtype = dtype.extract()?;
+    }
+    if let Some(compression_level) = config.get_item("compression_level")? {
+      res.config.compression_level = compression_level.extract()?;
+    }
+    if let Some(delta_encoding_order) = config.get_item("delta_encoding_order")? {
+      res.config.delta_encoding_order = delta_encoding_order.extract()?;
+    }
+    if let Some(use_gcds) = config.get_item("use_gcds")? {
+      res.config.use_gcds = use_gcds.extract()?;
+    }
+    Ok(res)
+  }
+
+  fn __repr__(&self) -> String {
+    format!("{:?}", self)
+  }
+}
+
+#[pymodule(name = "q_compress")]
+fn q_compress_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+  m.add_class::<CompressorConfig>()?;
+  m.add_class::<QcoCodec>()?;
+  m.add_function(wrap_pyfunction!(compress, m)?)?;
+  m.add_function(wrap_pyfunction!(decompress, m)?)?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_compress_nums_recovers() {
+    let nums = (0..1000).map(|i| i * 7 - 300).collect::<Vec<i64>>();
+    for config in [
+      CompressorConfig::default(),
+      CompressorConfig::new(3, Some(1), false),
+    ] {
+      let bytes = compress_nums(&nums, &config).unwrap();
+      assert_eq!(q_compress::auto_decompress::<i64>(&bytes).unwrap(), nums);
+    }
+
+    let bytes = compress_nums::<f32>(&[], &CompressorConfig::default()).unwrap();
+    assert!(q_compress::auto_decompress::<f32>(&bytes).unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_compress_nums_errors() {
+    let nums = [1_i32, 2, 3];
+    assert!(compress_nums(&nums, &CompressorConfig::new(8, Some(8), true)).is_err());
+    assert!(compress_nums(&nums, &CompressorConfig::new(13, Some(1), true)).is_err());
+    assert!(compress_nums(&nums, &CompressorConfig::new(13, None, true)).is_err());
+  }
+}
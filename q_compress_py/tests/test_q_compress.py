@@ -0,0 +1,101 @@
+import numpy as np
+import pytest
+
+import q_compress
+
+
+@pytest.mark.parametrize('dtype', [
+  'bool', 'int16', 'int32', 'int64', 'uint16', 'uint32', 'uint64', 'float32', 'float64',
+])
+def test_round_trip(dtype):
+  nums = (np.arange(1000) % 7).astype(dtype)
+  recovered = q_compress.decompress(q_compress.compress(nums))
+  assert recovered.dtype == nums.dtype
+  np.testing.assert_array_equal(recovered, nums)
+
+
+def test_round_trip_with_config():
+  nums = np.arange(1000, dtype=np.int64) * 7
+  config = q_compress.CompressorConfig(compression_level=3, delta_encoding_order=1, use_gcds=False)
+  np.testing.assert_array_equal(q_compress.decompress(q_compress.compress(nums, config)), nums)
+
+
+def test_non_contiguous_input():
+  nums = np.arange(2000, dtype=np.int32)[::2]
+  np.testing.assert_array_equal(q_compress.decompress(q_compress.compress(nums)), nums)
+
+
+def test_empty():
+  nums = np.array([], dtype=np.int64)
+  recovered = q_compress.decompress(q_compress.compress(nums))
+  assert recovered.dtype == np.int64
+  assert len(recovered) == 0
+
+
+@pytest.mark.parametrize('config', [
+  q_compress.CompressorConfig(delta_encoding_order=8),
+  q_compress.CompressorConfig(compression_level=13),
+])
+def test_invalid_config(config):
+  with pytest.raises(ValueError):
+    q_compress.compress(np.arange(10, dtype=np.int64), config)
+
+
+def test_invalid_input():
+  with pytest.raises(ValueError):
+    q_compress.compress(np.array(['a', 'b']))
+  with pytest.raises(ValueError):
+    q_compress.decompress(b'qco')
+  compressed = q_compress.compress(np.arange(10, dtype=np.int64))
+  with pytest.raises(ValueError):
+    q_compress.decompress(compressed[:-1])
+
+
+def test_codec_typed_chunks():
+  codec = q_compress.QcoCodec(delta_encoding_order=1)
+  chunk = np.arange(200, dtype=np.float64).reshape(10, 20) * 0.5
+  decoded = codec.decode(codec.encode(chunk))
+  assert decoded.dtype == np.float64
+  np.testing.assert_array_equal(decoded, chunk.reshape(-1))
+
+
+def test_codec_byte_chunks():
+  nums = np.arange(100, dtype=np.int32)
+  # untyped buffers are read as the codec's dtype
+  codec = q_compress.QcoCodec(dtype='<i4')
+  np.testing.assert_array_equal(codec.decode(codec.encode(nums.tobytes())), nums)
+  # typed arrays are reinterpreted as the codec's dtype
+  np.testing.assert_array_equal(
+    codec.decode(codec.encode(nums.view(np.uint8))),
+    nums,
+  )
+  with pytest.raises(ValueError):
+    q_compress.QcoCodec().encode(nums.tobytes())
+
+
+def test_codec_decode_into_out():
+  codec = q_compress.QcoCodec()
+  chunk = np.arange(60, dtype=np.int64).reshape(3, 20)
+  encoded = codec.encode(chunk)
+
+  out = np.zeros((3, 20), dtype=np.int64)
+  assert codec.decode(encoded, out=out) is out
+  np.testing.assert_array_equal(out, chunk)
+
+  # byte buffers are filled with the decompressed numbers' bytes
+  out = bytearray(chunk.nbytes)
+  codec.decode(encoded, out=out)
+  np.testing.assert_array_equal(np.frombuffer(out, dtype=np.int64), chunk.reshape(-1))
+
+  # filling a copy of a non-contiguous array would leave it untouched
+  out = np.zeros((3, 40), dtype=np.int64)[:, ::2]
+  with pytest.raises(ValueError):
+    codec.decode(encoded, out=out)
+
+
+def test_codec_config():
+  codec = q_compress.QcoCodec(dtype='<f4', compression_level=4, delta_encoding_order=2, use_gcds=False)
+  config = codec.get_config()
+  assert config['id'] == q_compress.QcoCodec.codec_id
+  restored = q_compress.QcoCodec.from_config(config)
+  assert restored.get_config() == config
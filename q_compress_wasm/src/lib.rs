@@ -0,0 +1,142 @@
+//! WebAssembly bindings for `q_compress`, built with `wasm-bindgen`.
+//!
+//! For each of `i32`, `i64`, `f32`, and `f64`, this exposes
+//! * `compress_<dtype>(nums, compressionLevel)`, taking a typed array
+//!   (e.g. `Float64Array`) and returning the .qco bytes as a `Uint8Array`,
+//! * `decompress_<dtype>(bytes)`, returning a typed array, and
+//! * `inspect_<dtype>(bytes)`, returning a `ChunkInfo` for each chunk
+//!   without decompressing any chunk bodies.
+//!
+//! Errors are thrown as JS `Error`s.
+
+use q_compress::{auto_compressor_config, auto_decompress, Compressor, Decompressor, PrefixMetadata};
+use q_compress::data_types::NumberLike;
+use q_compress::errors::QCompressResult;
+use wasm_bindgen::prelude::*;
+
+/// Summary of one chunk's metadata.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkInfo {
+  /// The count of numbers in the chunk.
+  pub n: usize,
+  /// The byte length of the chunk's compressed body.
+  #[wasm_bindgen(js_name = compressedBodySize)]
+  pub compressed_body_size: usize,
+  /// The number of prefixes (ranges with Huffman codes) in the chunk.
+  #[wasm_bindgen(js_name = nPrefixes)]
+  pub n_prefixes: usize,
+  /// The delta encoding order of the chunk, or 0 if it is not delta encoded.
+  #[wasm_bindgen(js_name = deltaEncodingOrder)]
+  pub delta_encoding_order: usize,
+}
+
+fn chunk_infos<T: NumberLike>(bytes: &[u8]) -> QCompressResult<Vec<ChunkInfo>> {
+  let mut decompressor = Decompressor::<T>::default();
+  decompressor.extend_bytes(bytes);
+  decompressor.header()?;
+  let mut res = Vec::new();
+  while let Some(meta) = decompressor.chunk_metadata()? {
+    let (n_prefixes, delta_encoding_order) = match &meta.prefix_metadata {
+      PrefixMetadata::Simple { prefixes } => (prefixes.len(), 0),
+      PrefixMetadata::Delta { prefixes, delta_moments } => (prefixes.len(), delta_moments.order()),
+      PrefixMetadata::Xor { prefixes, .. } => (prefixes.len(), 0),
+      PrefixMetadata::Raw { .. } => (0, 0),
+      PrefixMetadata::FloatSplit { exponent_prefixes, mantissa_prefixes, .. } => {
+        (exponent_prefixes.len() + mantissa_prefixes.len(), 0)
+      },
+    };
+    res.push(ChunkInfo {
+      n: meta.n,
+      compressed_body_size: meta.compressed_body_size,
+      n_prefixes,
+      delta_encoding_order,
+    });
+    decompressor.skip_chunk_body()?;
+  }
+  Ok(res)
+}
+
+// Invalid compression levels must be errors rather than panics, since a
+// panic traps the whole wasm instance.
+// Empty arrays are fine: the automatic config leaves delta encoding off.
+fn compress_nums<T: NumberLike>(nums: &[T], compression_level: usize) -> QCompressResult<Vec<u8>> {
+  let config = auto_compressor_config(nums, compression_level);
+  Ok(Compressor::<T>::try_from_config(config)?.simple_compress(nums))
+}
+
+fn to_js_error<T>(result: QCompressResult<T>) -> Result<T, JsError> {
+  result.map_err(|e| JsError::new(&e.to_string()))
+}
+
+macro_rules! impl_dtype {
+  ($t: ty, $compress: ident, $decompress: ident, $inspect: ident) => {
+    #[doc = concat!("Compresses `", stringify!($t), "`s into .qco bytes.")]
+    #[wasm_bindgen]
+    pub fn $compress(nums: &[$t], compression_level: usize) -> Result<Vec<u8>, JsError> {
+      to_js_error(compress_nums(nums, compression_level))
+    }
+
+    #[doc = concat!("Decompresses .qco bytes of `", stringify!($t), "`s.")]
+    #[wasm_bindgen]
+    pub fn $decompress(bytes: &[u8]) -> Result<Vec<$t>, JsError> {
+      to_js_error(auto_decompress::<$t>(bytes))
+    }
+
+    #[doc = concat!(
+      "Reads the metadata of each chunk in .qco bytes of `",
+      stringify!($t),
+      "`s, skipping over their bodies.",
+    )]
+    #[wasm_bindgen]
+    pub fn $inspect(bytes: &[u8]) -> Result<Vec<ChunkInfo>, JsError> {
+      to_js_error(chunk_infos::<$t>(bytes))
+    }
+  }
+}
+
+impl_dtype!(i32, compress_i32, decompress_i32, inspect_i32);
+impl_dtype!(i64, compress_i64, decompress_i64, inspect_i64);
+impl_dtype!(f32, compress_f32, decompress_f32, inspect_f32);
+impl_dtype!(f64, compress_f64, decompress_f64, inspect_f64);
+
+#[cfg(test)]
+mod tests {
+  use q_compress::{Compressor, CompressorConfig};
+  use super::*;
+
+  #[test]
+  fn test_recovers() {
+    let nums = vec![1.5, -2.0, 1.5, 1E100];
+    let bytes = compress_f64(&nums, 6).unwrap();
+    assert_eq!(decompress_f64(&bytes).unwrap(), nums);
+
+    let bytes = compress_nums::<i32>(&[], 6).unwrap();
+    assert!(decompress_i32(&bytes).unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_compress_errors() {
+    assert!(compress_nums(&[1_i64, 2, 3], 13).is_err());
+    assert!(compress_nums::<f32>(&[], 13).is_err());
+  }
+
+  #[test]
+  fn test_inspect() {
+    let nums = (0..300).collect::<Vec<i64>>();
+    let mut compressor = Compressor::<i64>::from_config(
+      CompressorConfig::default().with_delta_encoding_order(1)
+    );
+    compressor.header().unwrap();
+    compressor.chunk(&nums[..100]).unwrap();
+    compressor.chunk(&nums[100..]).unwrap();
+    compressor.footer().unwrap();
+    let bytes = compressor.drain_bytes();
+
+    let infos = chunk_infos::<i64>(&bytes).unwrap();
+    assert_eq!(infos.len(), 2);
+    assert_eq!(infos[0].n, 100);
+    assert_eq!(infos[1].n, 200);
+    assert_eq!(infos[1].delta_encoding_order, 1);
+  }
+}